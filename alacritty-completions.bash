@@ -6,12 +6,56 @@ complete -F _alacritty alacritty
 # Completion function
 _alacritty()
 {
-    local cur prev prevprev opts
+    local cur prev prevprev opts msg_opts
     COMPREPLY=()
     cur="${COMP_WORDS[COMP_CWORD]}"
     prev="${COMP_WORDS[COMP_CWORD-1]}"
     prevprev="${COMP_WORDS[COMP_CWORD-2]}"
-    opts="-h --help -V --version --live-config-reload --no-live-config-reload --persistent-logging --print-events -q -qq -v -vv -vvv --ref-test -e --command --config-file -d --dimensions -t --title --working-directory"
+    opts="-h --help -V --version --live-config-reload --no-live-config-reload --persistent-logging --hold --print-config --print-events --trace-escapes -q -qq -v -vv -vvv --ref-test --ref-test-replay --ref-test-update -e --command --config-file -o --option -d --dimensions -t --title --class --embed --working-directory --record --play --windows --renderer msg migrate"
+    msg_opts="-s --socket create-window config annotate clear-annotations export-svg get-state"
+
+    # Complete the subcommands of `msg`
+    if [[ "${COMP_WORDS[1]}" == "msg" ]]; then
+        case "${prev}" in
+            --socket | -s | --working-directory)
+                local IFS=$'\n'
+                compopt -o filenames
+                COMPREPLY=( $(compgen -f -- "${cur}") )
+                return 0;;
+            msg)
+                COMPREPLY=( $(compgen -W "${msg_opts}" -- "${cur}") )
+                return 0;;
+            create-window)
+                COMPREPLY=( $(compgen -W "--working-directory -e --command" -- "${cur}") )
+                return 0;;
+            config)
+                COMPREPLY=( $(compgen -W "-r --reset" -- "${cur}") )
+                return 0;;
+            annotate)
+                COMPREPLY=( $(compgen -W "-l --label -w --window" -- "${cur}") )
+                return 0;;
+            clear-annotations)
+                COMPREPLY=( $(compgen -W "-w --window" -- "${cur}") )
+                return 0;;
+            export-svg)
+                COMPREPLY=( $(compgen -W "-w --window" -- "${cur}") )
+                return 0;;
+        esac
+        return 0
+    fi
+
+    # Complete the options of `migrate`
+    if [[ "${COMP_WORDS[1]}" == "migrate" ]]; then
+        case "${prev}" in
+            --config-file | -c)
+                local IFS=$'\n'
+                compopt -o filenames
+                COMPREPLY=( $(compgen -f -- "${cur}") )
+                return 0;;
+        esac
+        COMPREPLY=( $(compgen -W "-c --config-file --dry-run" -- "${cur}") )
+        return 0
+    fi
 
     # If `--command` or `-e` is used, stop completing
     for i in "${!COMP_WORDS[@]}"; do
@@ -34,16 +78,22 @@ _alacritty()
             # Complete all commands in $PATH
             COMPREPLY=( $(compgen -c -- "${cur}") )
             return 0;;
-        --config-file)
+        --config-file | --record | --play)
             # Path based completion
             local IFS=$'\n'
             compopt -o filenames
             COMPREPLY=( $(compgen -f -- "${cur}") )
             return 0;;
-        --dimensions | -d | --title | -t)
+        --dimensions | -d | --title | -t | --class | --embed | --windows | --option | -o)
             # Don't complete here
             return 0;;
-        --working-directory)
+        --renderer)
+            COMPREPLY=( $(compgen -W "auto gl3 gles2 software" -- "${cur}") )
+            return 0;;
+        --print-config)
+            COMPREPLY=( $(compgen -W "yaml toml" -- "${cur}") )
+            return 0;;
+        --working-directory | --ref-test-replay)
             # Directory completion
             local IFS=$'\n'
             compopt -o filenames
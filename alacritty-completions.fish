@@ -18,24 +18,141 @@ complete -c alacritty \
 complete -c alacritty \
   -l "persistent-logging" \
   -d "Keep the log file after quitting Alacritty"
+complete -c alacritty \
+  -f \
+  -a "yaml toml" \
+  -l "print-config" \
+  -d "Print the config with all imports, options and defaults applied"
+complete -c alacritty \
+  -l "hold" \
+  -d "Keep the window open after the shell or command exited"
 complete -c alacritty \
   -f \
   -l "config-file" \
   -d "Specify an alternative config file"
+complete -c alacritty \
+  -x \
+  -s "o" \
+  -l "option" \
+  -d "Override config options"
+complete -c alacritty \
+  -f \
+  -l "record" \
+  -d "Record the session to an asciicast file"
+complete -c alacritty \
+  -f \
+  -l "play" \
+  -d "Play an asciicast recording instead of starting a shell"
 complete -c alacritty \
   -l "title" \
   -d "Defines the window title"
+complete -c alacritty \
+  -x \
+  -l "class" \
+  -d "Defines the window class on X11 and the app id on Wayland"
+complete -c alacritty \
+  -x \
+  -l "embed" \
+  -d "Embed the window into an X11 window"
+complete -c alacritty \
+  -x \
+  -l "windows" \
+  -d "Number of windows to open on startup"
+complete -c alacritty \
+  -x \
+  -a "auto gl3 gles2 software" \
+  -l "renderer" \
+  -d "OpenGL version used for drawing"
 complete -c alacritty \
   -x \
   -a '(__fish_complete_directories (commandline -ct))' \
   -l "working-directory" \
   -d "Start shell in specified directory"
 
+# Messages
+complete -c alacritty \
+  -f \
+  -n "__fish_use_subcommand" \
+  -a "msg" \
+  -d "Send a message to a running Alacritty instance"
+complete -c alacritty \
+  -f \
+  -n "__fish_seen_subcommand_from msg" \
+  -a "create-window" \
+  -d "Open a new window"
+complete -c alacritty \
+  -f \
+  -n "__fish_seen_subcommand_from msg" \
+  -a "config" \
+  -d "Change config options of the running instance"
+complete -c alacritty \
+  -f \
+  -n "__fish_seen_subcommand_from msg" \
+  -a "annotate" \
+  -d "Mark a cell of the terminal"
+complete -c alacritty \
+  -f \
+  -n "__fish_seen_subcommand_from msg" \
+  -a "clear-annotations" \
+  -d "Remove all marks and bookmarks of a window"
+complete -c alacritty \
+  -f \
+  -n "__fish_seen_subcommand_from msg" \
+  -a "export-svg" \
+  -d "Write the visible screen to an SVG image in the screenshot directory"
+complete -c alacritty \
+  -f \
+  -n "__fish_seen_subcommand_from msg" \
+  -a "get-state" \
+  -d "Print the state of the instance as JSON"
+complete -c alacritty \
+  -n "__fish_seen_subcommand_from msg" \
+  -s "s" \
+  -l "socket" \
+  -d "Socket of the instance"
+complete -c alacritty \
+  -n "__fish_seen_subcommand_from config" \
+  -s "r" \
+  -l "reset" \
+  -d "Drop the options set by earlier messages"
+complete -c alacritty \
+  -x \
+  -n "__fish_seen_subcommand_from annotate" \
+  -s "l" \
+  -l "label" \
+  -d "Text describing the mark"
+complete -c alacritty \
+  -x \
+  -n "__fish_seen_subcommand_from annotate clear-annotations export-svg" \
+  -s "w" \
+  -l "window" \
+  -d "Id of the window"
+
+# Migration
+complete -c alacritty \
+  -f \
+  -n "__fish_use_subcommand" \
+  -a "migrate" \
+  -d "Convert a YAML config to TOML"
+complete -c alacritty \
+  -n "__fish_seen_subcommand_from migrate" \
+  -s "c" \
+  -l "config-file" \
+  -d "YAML config to convert"
+complete -c alacritty \
+  -n "__fish_seen_subcommand_from migrate" \
+  -l "dry-run" \
+  -d "Print the converted config instead of writing it"
+
 # Output
 complete \
   -c alacritty \
   -l "print-events" \
   -d "Print all events to stdout"
+complete \
+  -c alacritty \
+  -l "trace-escapes" \
+  -d "Log every escape sequence, and the raw bytes of unhandled ones"
 complete \
   -c alacritty \
   -s "q" \
@@ -61,6 +178,15 @@ complete \
   -c alacritty \
   -l "ref-test" \
   -d "Generates ref test"
+complete \
+  -c alacritty \
+  -f \
+  -l "ref-test-replay" \
+  -d "Replays a ref test and compares a screenshot of the result"
+complete \
+  -c alacritty \
+  -l "ref-test-update" \
+  -d "Stores the screenshot of a replayed ref test as its reference"
 
 complete \
   -c alacritty \
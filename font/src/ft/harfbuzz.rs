@@ -0,0 +1,209 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Minimal HarfBuzz bindings for shaping text
+//!
+//! The library is loaded when text is first shaped, so it is only needed at runtime and only
+//! when ligatures are drawn. Without it, shaping fails and text is drawn cell by cell.
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Once;
+use std::{io, mem, ptr, slice};
+
+enum HbBlob {}
+enum HbFace {}
+enum HbFont {}
+enum HbBuffer {}
+
+/// Glyph of a shaped buffer, `hb_glyph_info_t`
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct GlyphInfo {
+    /// Index of the glyph in the font
+    pub codepoint: u32,
+    mask: u32,
+    /// Byte offset of the first character the glyph was shaped from
+    pub cluster: u32,
+    var1: u32,
+    var2: u32,
+}
+
+/// Functions of the HarfBuzz library
+struct Library {
+    blob_create_from_file: unsafe extern "C" fn(*const c_char) -> *mut HbBlob,
+    blob_get_length: unsafe extern "C" fn(*mut HbBlob) -> c_uint,
+    blob_destroy: unsafe extern "C" fn(*mut HbBlob),
+    face_create: unsafe extern "C" fn(*mut HbBlob, c_uint) -> *mut HbFace,
+    face_get_glyph_count: unsafe extern "C" fn(*mut HbFace) -> c_uint,
+    face_destroy: unsafe extern "C" fn(*mut HbFace),
+    font_create: unsafe extern "C" fn(*mut HbFace) -> *mut HbFont,
+    font_destroy: unsafe extern "C" fn(*mut HbFont),
+    buffer_create: unsafe extern "C" fn() -> *mut HbBuffer,
+    buffer_allocation_successful: unsafe extern "C" fn(*mut HbBuffer) -> c_int,
+    buffer_add_utf8: unsafe extern "C" fn(*mut HbBuffer, *const c_char, c_int, c_uint, c_int),
+    buffer_guess_segment_properties: unsafe extern "C" fn(*mut HbBuffer),
+    buffer_get_glyph_infos: unsafe extern "C" fn(*mut HbBuffer, *mut c_uint) -> *mut GlyphInfo,
+    buffer_destroy: unsafe extern "C" fn(*mut HbBuffer),
+    shape: unsafe extern "C" fn(*mut HbFont, *mut HbBuffer, *const c_void, c_uint),
+}
+
+// The function pointers stay valid for the lifetime of the process, the library is never closed
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+impl Library {
+    /// Load the system's HarfBuzz library
+    ///
+    /// Fails if the library or one of the functions is missing, like with versions before
+    /// `hb_blob_create_from_file` was added.
+    fn open() -> Option<Library> {
+        unsafe {
+            let name = b"libharfbuzz.so.0\0";
+            let handle = libc::dlopen(name.as_ptr() as *const c_char, libc::RTLD_NOW);
+            if handle.is_null() {
+                return None;
+            }
+
+            macro_rules! symbol {
+                ($name:expr) => {{
+                    let name = concat!($name, "\0");
+                    let symbol = libc::dlsym(handle, name.as_ptr() as *const c_char);
+                    if symbol.is_null() {
+                        return None;
+                    }
+                    mem::transmute(symbol)
+                }};
+            }
+
+            Some(Library {
+                blob_create_from_file: symbol!("hb_blob_create_from_file"),
+                blob_get_length: symbol!("hb_blob_get_length"),
+                blob_destroy: symbol!("hb_blob_destroy"),
+                face_create: symbol!("hb_face_create"),
+                face_get_glyph_count: symbol!("hb_face_get_glyph_count"),
+                face_destroy: symbol!("hb_face_destroy"),
+                font_create: symbol!("hb_font_create"),
+                font_destroy: symbol!("hb_font_destroy"),
+                buffer_create: symbol!("hb_buffer_create"),
+                buffer_allocation_successful: symbol!("hb_buffer_allocation_successful"),
+                buffer_add_utf8: symbol!("hb_buffer_add_utf8"),
+                buffer_guess_segment_properties: symbol!("hb_buffer_guess_segment_properties"),
+                buffer_get_glyph_infos: symbol!("hb_buffer_get_glyph_infos"),
+                buffer_destroy: symbol!("hb_buffer_destroy"),
+                shape: symbol!("hb_shape"),
+            })
+        }
+    }
+
+    /// The library, loaded once per process
+    fn get() -> io::Result<&'static Library> {
+        static LIBRARY: AtomicPtr<Library> = AtomicPtr::new(ptr::null_mut());
+        static INIT: Once = Once::new();
+
+        INIT.call_once(|| {
+            if let Some(library) = Library::open() {
+                LIBRARY.store(Box::into_raw(Box::new(library)), Ordering::Release);
+            }
+        });
+
+        match unsafe { LIBRARY.load(Ordering::Acquire).as_ref() } {
+            Some(library) => Ok(library),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "libharfbuzz.so.0 not found or missing functions",
+            )),
+        }
+    }
+}
+
+/// A font loaded for shaping
+pub struct Font {
+    library: &'static Library,
+    font: *mut HbFont,
+}
+
+// HarfBuzz fonts are immutable once created and safe to use from any thread
+unsafe impl Send for Font {}
+
+impl Font {
+    /// Load the face at `index` of the font file at `path`
+    pub fn from_file(path: &Path, index: u32) -> io::Result<Font> {
+        let library = Library::get()?;
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        // HarfBuzz returns empty objects instead of null pointers when something fails
+        unsafe {
+            let blob = (library.blob_create_from_file)(path.as_ptr());
+            if blob.is_null() || (library.blob_get_length)(blob) == 0 {
+                (library.blob_destroy)(blob);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "unreadable font file"));
+            }
+
+            let face = (library.face_create)(blob, index);
+            (library.blob_destroy)(blob);
+            if face.is_null() || (library.face_get_glyph_count)(face) == 0 {
+                (library.face_destroy)(face);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "no font face at index"));
+            }
+
+            let font = (library.font_create)(face);
+            (library.face_destroy)(face);
+            if font.is_null() {
+                return Err(io::Error::new(io::ErrorKind::Other, "unable to create font"));
+            }
+
+            Ok(Font { library, font })
+        }
+    }
+
+    /// Shape `text` with the default features of the font
+    ///
+    /// Returns no glyphs if shaping failed, the text is drawn unshaped then.
+    pub fn shape(&self, text: &str) -> Vec<GlyphInfo> {
+        let library = self.library;
+        unsafe {
+            let buffer = (library.buffer_create)();
+            if buffer.is_null() || (library.buffer_allocation_successful)(buffer) == 0 {
+                (library.buffer_destroy)(buffer);
+                return Vec::new();
+            }
+
+            let len = text.len() as c_int;
+            (library.buffer_add_utf8)(buffer, text.as_ptr() as *const c_char, len, 0, len);
+            (library.buffer_guess_segment_properties)(buffer);
+            (library.shape)(self.font, buffer, ptr::null(), 0);
+
+            let mut len = 0;
+            let infos = (library.buffer_get_glyph_infos)(buffer, &mut len);
+            let glyphs = if infos.is_null() {
+                Vec::new()
+            } else {
+                slice::from_raw_parts(infos, len as usize).to_vec()
+            };
+            (library.buffer_destroy)(buffer);
+
+            glyphs
+        }
+    }
+}
+
+impl Drop for Font {
+    fn drop(&mut self) {
+        unsafe { (self.library.font_destroy)(self.font) }
+    }
+}
@@ -16,7 +16,7 @@
 use std::collections::HashMap;
 use std::cmp::min;
 use std::path::PathBuf;
-use std::fmt;
+use std::{fmt, io};
 
 use freetype::tt_os2::TrueTypeOS2Table;
 use freetype::{self, Library};
@@ -24,8 +24,11 @@ use libc::c_uint;
 
 
 pub mod fc;
+mod harfbuzz;
 
-use super::{FontDesc, RasterizedGlyph, Metrics, Size, FontKey, GlyphKey, Weight, Slant, Style};
+use super::{
+    FontDesc, RasterizedGlyph, Metrics, ShapedGlyph, Size, FontKey, GlyphKey, Weight, Slant, Style,
+};
 
 struct FixedSize {
     pixelsize: f64,
@@ -37,7 +40,12 @@ struct Face {
     load_flags: freetype::face::LoadFlag,
     render_mode: freetype::RenderMode,
     lcd_filter: c_uint,
-    non_scalable: Option<FixedSize>
+    non_scalable: Option<FixedSize>,
+    path: PathBuf,
+    index: isize,
+
+    /// HarfBuzz font for shaping, loaded when text is first shaped with this face
+    hb_font: Option<harfbuzz::Font>,
 }
 
 impl fmt::Debug for Face {
@@ -289,6 +297,9 @@ impl FreeTypeRasterizer {
                 render_mode: Self::ft_render_mode(pattern),
                 lcd_filter: Self::ft_lcd_filter(pattern),
                 non_scalable,
+                path: path.clone(),
+                index,
+                hb_font: None,
             };
 
             debug!("Loaded Face {:?}", face);
@@ -324,55 +335,76 @@ impl FreeTypeRasterizer {
 
     fn get_rendered_glyph(&mut self, glyph_key: GlyphKey)
                           -> Result<RasterizedGlyph, Error> {
-        // Render a custom symbol for the underline and beam cursor
-        match glyph_key.c {
-            super::UNDERLINE_CURSOR_CHAR => {
-                // Get the primary face metrics
-                // This always loads the default face
-                let full = self.full_metrics(glyph_key.font_key)?;
-
-                // Get the bottom of the bounding box
-                let descent = (full.size_metrics.descender / 64) as i32;
+        let font_key = self.face_for_glyph(glyph_key, false)?;
+        let index = self.faces[&font_key].ft_face.get_char_index(glyph_key.c as usize);
 
-                // Get the width of the cell
-                let width = full.cell_width as i32;
+        self.render_glyph_index(font_key, glyph_key.size, index as u32, glyph_key.c)
+    }
 
-                // Return the new custom glyph
-                return super::get_underline_cursor_glyph(descent, width);
-            },
-            super::BEAM_CURSOR_CHAR | super::BOX_CURSOR_CHAR => {
-                // Get the primary face metrics
-                // This always loads the default face
-                let full = self.full_metrics(glyph_key.font_key)?;
-
-                // Get the height of the cell
-                let height = (full.size_metrics.height / 64) as i32;
-
-                // Get the top of the bounding box
-                let descent = (full.size_metrics.descender / 64) as i32;
-                let ascent = height + descent;
-
-                // Get the width of the cell
-                let width = full.cell_width as i32;
-
-                // Return the new custom glyph
-                return if glyph_key.c == super::BEAM_CURSOR_CHAR {
-                    super::get_beam_cursor_glyph(ascent, height, width)
-                } else {
-                    super::get_box_cursor_glyph(ascent, height, width)
-                };
-            },
-            _ => (),
+    /// Shape `text` with the ligatures and other substitutions of the font
+    ///
+    /// Glyphs are returned in the order HarfBuzz produced them. Characters missing from the font
+    /// are shaped to glyph index `0`, since fallback fonts aren't used for shaping.
+    pub fn shape(&mut self, key: FontKey, text: &str) -> Result<Vec<ShapedGlyph>, Error> {
+        let face = self.faces.get_mut(&key).ok_or(Error::FontNotLoaded)?;
+        if face.hb_font.is_none() {
+            let hb_font = harfbuzz::Font::from_file(&face.path, face.index as u32)
+                .map_err(Error::Shaping)?;
+            face.hb_font = Some(hb_font);
         }
 
-        // Render a normal character if it's not a cursor
-        let font_key = self.face_for_glyph(glyph_key, false)?;
-        let face = &self.faces[&font_key];
-        let index = face.ft_face.get_char_index(glyph_key.c as usize);
+        let hb_font = face.hb_font.as_ref().ok_or(Error::FontNotLoaded)?;
+        let output = hb_font.shape(text);
+
+        // HarfBuzz clusters are byte offsets into the text
+        let offsets: Vec<usize> = text.char_indices().map(|(offset, _)| offset).collect();
+        let glyphs = output
+            .iter()
+            .map(|info| {
+                let cluster = offsets
+                    .binary_search(&(info.cluster as usize))
+                    .unwrap_or_else(|index| index);
+                let original = text[offsets.get(cluster).cloned().unwrap_or(text.len())..]
+                    .chars()
+                    .next()
+                    .map(|c| face.ft_face.get_char_index(c as usize) as u32);
+
+                ShapedGlyph {
+                    cluster,
+                    index: info.codepoint,
+                    substituted: original != Some(info.codepoint),
+                }
+            })
+            .collect();
+
+        Ok(glyphs)
+    }
+
+    /// Rasterize the glyph at `index` in the font, like the ones returned by `shape`
+    ///
+    /// The glyph is reported as `c`, which should be the character it was shaped from.
+    pub fn get_glyph_by_index(
+        &mut self,
+        font_key: FontKey,
+        size: Size,
+        index: u32,
+        c: char,
+    ) -> Result<RasterizedGlyph, Error> {
+        self.render_glyph_index(font_key, size, index, c)
+    }
+
+    fn render_glyph_index(
+        &mut self,
+        font_key: FontKey,
+        size: Size,
+        index: u32,
+        c: char,
+    ) -> Result<RasterizedGlyph, Error> {
+        let face = self.faces.get(&font_key).ok_or(Error::FontNotLoaded)?;
 
         let size = face.non_scalable.as_ref()
             .map(|v| v.pixelsize as f32)
-            .unwrap_or_else(|| glyph_key.size.as_f32_pts() * self.device_pixel_ratio * 96. / 72.);
+            .unwrap_or_else(|| size.as_f32_pts() * self.device_pixel_ratio * 96. / 72.);
 
         face.ft_face.set_char_size(to_freetype_26_6(size), 0, 0, 0)?;
 
@@ -381,14 +413,14 @@ impl FreeTypeRasterizer {
             freetype::ffi::FT_Library_SetLcdFilter(ft_lib, face.lcd_filter);
         }
 
-        face.ft_face.load_glyph(index as u32, face.load_flags)?;
+        face.ft_face.load_glyph(index, face.load_flags)?;
         let glyph = face.ft_face.glyph();
         glyph.render_glyph(face.render_mode)?;
 
         let (pixel_height, pixel_width, buf) = Self::normalize_buffer(&glyph.bitmap())?;
 
         Ok(RasterizedGlyph {
-            c: glyph_key.c,
+            c,
             top: glyph.bitmap_top(),
             left: glyph.bitmap_left(),
             width: pixel_width,
@@ -591,12 +623,16 @@ pub enum Error {
 
     /// Requested an operation with a FontKey that isn't known to the rasterizer
     FontNotLoaded,
+
+    /// Couldn't load the font file for shaping text
+    Shaping(io::Error),
 }
 
 impl ::std::error::Error for Error {
     fn cause(&self) -> Option<&::std::error::Error> {
         match *self {
             Error::FreeType(ref err) => Some(err),
+            Error::Shaping(ref err) => Some(err),
             _ => None,
         }
     }
@@ -607,6 +643,7 @@ impl ::std::error::Error for Error {
             Error::MissingFont(ref _desc) => "Couldn't find the requested font",
             Error::FontNotLoaded => "Tried to operate on font that hasn't been loaded",
             Error::MissingSizeMetrics => "Tried to get size metrics from a face without a size",
+            Error::Shaping(ref _err) => "Couldn't load the font for shaping text",
         }
     }
 }
@@ -626,7 +663,10 @@ impl ::std::fmt::Display for Error {
             },
             Error::MissingSizeMetrics => {
                 f.write_str("Tried to get size metrics from a face without a size")
-            }
+            },
+            Error::Shaping(ref err) => {
+                write!(f, "Couldn't load the font for shaping text: {}", err)
+            },
         }
     }
 }
@@ -0,0 +1,41 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#version 100
+precision mediump float;
+
+varying vec2 TexCoords;
+varying vec3 fg;
+varying vec4 bg;
+
+// Rendering pass (1 = backgrounds, 2 = glyph masks, 3 = glyph colors)
+uniform int renderingPass;
+uniform sampler2D mask;
+
+void main()
+{
+    if (renderingPass == 1) {
+        if (bg.a == 0.0)
+            discard;
+
+        gl_FragColor = vec4(bg.rgb, 1.0);
+    } else {
+        // Without dual-source blending, the mask and the color are blended in separate passes
+        vec3 textColor = texture2D(mask, TexCoords).rgb;
+        if (renderingPass == 2) {
+            gl_FragColor = vec4(textColor, textColor.r);
+        } else {
+            gl_FragColor = vec4(fg * textColor, textColor.r);
+        }
+    }
+}
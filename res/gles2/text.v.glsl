@@ -0,0 +1,97 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#version 100
+attribute vec2 position;
+
+// Cell properties
+attribute vec2 gridCoords;
+
+// glyph properties
+attribute vec4 glyph;
+
+// uv mapping
+attribute vec4 uv;
+
+// text fg color
+attribute vec3 textColor;
+
+// Background color
+attribute vec4 backgroundColor;
+
+// Line size (0 = single, 1 = double width, 2 = double height top, 3 = double height bottom)
+attribute float lineAttribute;
+
+varying vec2 TexCoords;
+varying vec3 fg;
+varying vec4 bg;
+
+// Terminal properties
+uniform vec2 termDim;
+uniform vec2 cellDim;
+
+// Rendering pass (1 = backgrounds, 2 = glyph masks, 3 = glyph colors)
+uniform int renderingPass;
+
+// Orthographic projection
+uniform mat4 projection;
+
+void main()
+{
+    vec2 glyphOffset = glyph.xy;
+    vec2 glyphSize = glyph.zw;
+    vec2 uvOffset = uv.xy;
+    vec2 uvSize = uv.zw;
+
+    // Cells on double width and double height lines are scaled up
+    vec2 scale = vec2(lineAttribute > 0.5 ? 2.0 : 1.0, lineAttribute > 1.5 ? 2.0 : 1.0);
+
+    // Position of cell from top-left
+    vec2 cellPosition = (cellDim) * gridCoords * vec2(scale.x, 1.0);
+
+    // Invert Y since framebuffer origin is bottom-left
+    cellPosition.y = termDim.y - cellPosition.y - cellDim.y;
+
+    if (renderingPass == 1) {
+        vec2 finalPosition = cellDim * vec2(scale.x, 1.0) * position + cellPosition;
+        gl_Position = projection * vec4(finalPosition.xy, 0.0, 1.0);
+        TexCoords = vec2(0.0, 0.0);
+    } else {
+        // The top half of a double height line is drawn with its baseline one line lower
+        vec2 glyphCell = cellPosition;
+        if (lineAttribute > 1.5 && lineAttribute < 2.5) {
+            glyphCell.y -= cellDim.y;
+        }
+
+        // Glyphs are offset within their cell; account for y-flip
+        vec2 cellOffset = vec2(glyphOffset.x, glyphOffset.y - glyphSize.y) * scale;
+
+        // position coordinates are normalized on [0, 1]
+        vec2 finalPosition = glyphSize * scale * position + glyphCell + cellOffset;
+
+        // Only draw the half of a double height glyph which belongs to this line
+        float glyphBottom = glyphCell.y + cellOffset.y;
+        float glyphHeight = glyphSize.y * scale.y;
+        float texY = position.y;
+        if (scale.y > 1.5 && glyphHeight > 0.0) {
+            finalPosition.y = clamp(finalPosition.y, cellPosition.y, cellPosition.y + cellDim.y);
+            texY = (finalPosition.y - glyphBottom) / glyphHeight;
+        }
+
+        gl_Position = projection * vec4(finalPosition.xy, 0.0, 1.0);
+        TexCoords = uvOffset + vec2(position.x, 1.0 - texY) * uvSize;
+    }
+
+    bg = vec4(backgroundColor.rgb / 255.0, backgroundColor.a);
+    fg = textColor / vec3(255.0, 255.0, 255.0);
+}
@@ -29,6 +29,9 @@ layout (location = 4) in vec3 textColor;
 // Background color
 layout (location = 5) in vec4 backgroundColor;
 
+// Line size (0 = single, 1 = double width, 2 = double height top, 3 = double height bottom)
+layout (location = 6) in float lineAttribute;
+
 out vec2 TexCoords;
 out vec3 fg;
 out vec4 bg;
@@ -37,7 +40,8 @@ out vec4 bg;
 uniform vec2 termDim;
 uniform vec2 cellDim;
 
-uniform int backgroundPass;
+// Rendering pass (0 = glyphs, 1 = backgrounds)
+uniform int renderingPass;
 
 // Orthographic projection
 uniform mat4 projection;
@@ -50,29 +54,46 @@ void main()
     vec2 uvOffset = uv.xy;
     vec2 uvSize = uv.zw;
 
+    // Cells on double width and double height lines are scaled up
+    vec2 scale = vec2(lineAttribute > 0.5 ? 2.0 : 1.0, lineAttribute > 1.5 ? 2.0 : 1.0);
+
     // Position of cell from top-left
-    vec2 cellPosition = (cellDim) * gridCoords;
+    vec2 cellPosition = (cellDim) * gridCoords * vec2(scale.x, 1.0);
 
     // Invert Y since framebuffer origin is bottom-left
     cellPosition.y = termDim.y - cellPosition.y - cellDim.y;
 
-    if (backgroundPass != 0) {
-        cellPosition.y = cellPosition.y;
-        vec2 finalPosition = cellDim * position + cellPosition;
+    if (renderingPass == 1) {
+        vec2 finalPosition = cellDim * vec2(scale.x, 1.0) * position + cellPosition;
         gl_Position = projection * vec4(finalPosition.xy, 0.0, 1.0);
         TexCoords = vec2(0, 0);
     } else {
+        // The top half of a double height line is drawn with its baseline one line lower
+        vec2 glyphCell = cellPosition;
+        if (lineAttribute > 1.5 && lineAttribute < 2.5) {
+            glyphCell.y -= cellDim.y;
+        }
+
         // Glyphs are offset within their cell; account for y-flip
-        vec2 cellOffset = vec2(glyphOffset.x, glyphOffset.y - glyphSize.y);
+        vec2 cellOffset = vec2(glyphOffset.x, glyphOffset.y - glyphSize.y) * scale;
 
         // position coordinates are normalized on [0, 1]
-        vec2 finalPosition = glyphSize * position + cellPosition + cellOffset;
+        vec2 finalPosition = glyphSize * scale * position + glyphCell + cellOffset;
+
+        // Only draw the half of a double height glyph which belongs to this line
+        float glyphBottom = glyphCell.y + cellOffset.y;
+        float glyphHeight = glyphSize.y * scale.y;
+        float texY = position.y;
+        if (scale.y > 1.5 && glyphHeight > 0.0) {
+            finalPosition.y = clamp(finalPosition.y, cellPosition.y, cellPosition.y + cellDim.y);
+            texY = (finalPosition.y - glyphBottom) / glyphHeight;
+        }
 
         gl_Position = projection * vec4(finalPosition.xy, 0.0, 1.0);
-        TexCoords = uvOffset + vec2(position.x, 1 - position.y) * uvSize;
+        TexCoords = uvOffset + vec2(position.x, 1 - texY) * uvSize;
     }
 
-    background = backgroundPass;
+    background = renderingPass;
     bg = vec4(backgroundColor.rgb / 255.0, backgroundColor.a);
     fg = textColor / vec3(255.0, 255.0, 255.0);
 }
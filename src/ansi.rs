@@ -13,12 +13,15 @@
 // limitations under the License.
 //
 //! ANSI Terminal Stream Parsing
+use std::ascii;
 use std::io;
 use std::ops::Range;
+use std::path::PathBuf;
 use std::str;
 
 use vte;
 use base64;
+use url::Url;
 use crate::index::{Column, Line, Contains};
 
 use crate::{MouseCursor, Rgb};
@@ -78,6 +81,43 @@ fn parse_rgb_color(color: &[u8]) -> Option<Rgb> {
     }
 }
 
+// Parse the `file://host/path` URI of the working directory
+//
+// Directories on other hosts, like the ones reported by a shell over ssh, are ignored.
+fn parse_file_uri(uri: &[u8]) -> Option<PathBuf> {
+    let url = Url::parse(str::from_utf8(uri).ok()?).ok()?;
+    if url.scheme() != "file" {
+        return None;
+    }
+
+    let local = match url.host_str() {
+        None | Some("") | Some("localhost") => true,
+        Some(host) => local_hostname().map_or(false, |local| local.eq_ignore_ascii_case(host)),
+    };
+    if !local {
+        return None;
+    }
+
+    // Drop the host, the path of a file URI with a hostname can't be converted on all platforms
+    Url::parse(&format!("file://{}", url.path())).ok()?.to_file_path().ok()
+}
+
+#[cfg(unix)]
+fn local_hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+        return None;
+    }
+
+    let len = buf.iter().position(|&byte| byte == 0).unwrap_or(buf.len());
+    String::from_utf8(buf[..len].to_vec()).ok()
+}
+
+#[cfg(windows)]
+fn local_hostname() -> Option<String> {
+    ::std::env::var("COMPUTERNAME").ok()
+}
+
 fn parse_number(input: &[u8]) -> Option<u8> {
     if input.is_empty() {
         return None;
@@ -97,6 +137,55 @@ fn parse_number(input: &[u8]) -> Option<u8> {
     Some(num)
 }
 
+/// Raw bytes of a CSI or DCS sequence, for `--trace-escapes`
+///
+/// The final byte of a DCS sequence isn't passed to the parser's hook, so it's omitted.
+fn sequence_bytes(
+    introducer: u8,
+    args: &[i64],
+    intermediates: &[u8],
+    action: Option<u8>,
+) -> Vec<u8> {
+    // Private markers like `?` are collected as intermediates, but precede the parameters
+    let (private, intermediates): (Vec<u8>, Vec<u8>) =
+        intermediates.iter().partition(|byte| b"<=>?".contains(byte));
+    let args: Vec<String> = args.iter().map(i64::to_string).collect();
+
+    let mut bytes = vec![0x1b, introducer];
+    bytes.extend(private);
+    bytes.extend(args.join(";").bytes());
+    bytes.extend(intermediates);
+    bytes.extend(action);
+    bytes
+}
+
+/// Raw bytes of an OSC sequence, terminated by BEL
+fn osc_bytes(params: &[&[u8]]) -> Vec<u8> {
+    let mut bytes = b"\x1b]".to_vec();
+    bytes.extend(params.join(&b';'));
+    bytes.push(0x07);
+    bytes
+}
+
+/// Raw bytes of an escape sequence
+fn esc_bytes(intermediates: &[u8], byte: u8) -> Vec<u8> {
+    let mut bytes = vec![0x1b];
+    bytes.extend_from_slice(intermediates);
+    bytes.push(byte);
+    bytes
+}
+
+/// Printable form of raw bytes, with ESC shown as `\e`
+pub fn printable(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| match byte {
+            0x1b => String::from("\\e"),
+            _ => ascii::escape_default(byte).map(char::from).collect(),
+        })
+        .collect()
+}
+
 /// The processor wraps a `vte::Parser` to ultimately call methods on a Handler
 pub struct Processor {
     state: ProcessorState,
@@ -105,7 +194,14 @@ pub struct Processor {
 
 /// Internal state for VTE processor
 struct ProcessorState {
-    preceding_char: Option<char>
+    preceding_char: Option<char>,
+
+    /// Whether the parser is known to be outside of any sequence, so plain text can be written
+    /// without going through the state machine
+    ground: bool,
+
+    /// Log every escape sequence, enabled by `--trace-escapes`
+    trace: bool,
 }
 
 /// Helper type that implements `vte::Perform`.
@@ -119,6 +215,14 @@ struct Performer<'a, H: Handler + TermInfo, W: io::Write> {
 }
 
 impl<'a, H: Handler + TermInfo + 'a, W: io::Write> Performer<'a, H, W> {
+    /// Log a sequence for `--trace-escapes`
+    #[inline]
+    fn trace<F: FnOnce() -> Vec<u8>>(&self, label: &str, bytes: F) {
+        if self._state.trace {
+            info!("[{}] {}", label, printable(&bytes()));
+        }
+    }
+
     /// Create a performer
     #[inline]
     pub fn new<'b>(
@@ -137,7 +241,7 @@ impl<'a, H: Handler + TermInfo + 'a, W: io::Write> Performer<'a, H, W> {
 impl Default for Processor {
     fn default() -> Processor {
         Processor {
-            state: ProcessorState { preceding_char: None },
+            state: ProcessorState { preceding_char: None, ground: true, trace: false },
             parser: vte::Parser::new(),
         }
     }
@@ -148,6 +252,11 @@ impl Processor {
         Default::default()
     }
 
+    /// Log every escape sequence, and the raw bytes of unhandled ones
+    pub fn set_trace(&mut self, trace: bool) {
+        self.state.trace = trace;
+    }
+
     #[inline]
     pub fn advance<H, W>(
         &mut self,
@@ -158,9 +267,56 @@ impl Processor {
         where H: Handler + TermInfo,
               W: io::Write
     {
+        // Only a print or the end of a sequence returns the parser to the ground state
+        self.state.ground = false;
+
         let mut performer = Performer::new(&mut self.state, handler, writer);
         self.parser.advance(&mut performer, byte);
     }
+
+    /// Process a buffer of pty output
+    ///
+    /// Runs of printable ASCII between sequences skip the state machine and are passed to the
+    /// handler at once.
+    pub fn advance_bytes<H, W>(&mut self, handler: &mut H, bytes: &[u8], writer: &mut W)
+    where
+        H: Handler + TermInfo,
+        W: io::Write,
+    {
+        let mut i = 0;
+        while i < bytes.len() {
+            if self.state.ground {
+                let run = printable_run(&bytes[i..]);
+                if run > 0 {
+                    handler.input_ascii(&bytes[i..i + run]);
+                    self.state.preceding_char = Some(char::from(bytes[i + run - 1]));
+                    i += run;
+                    continue;
+                }
+            }
+
+            self.advance(handler, bytes[i], writer);
+            i += 1;
+        }
+    }
+}
+
+/// Length of the run of printable ASCII at the start of `bytes`
+#[inline]
+fn printable_run(bytes: &[u8]) -> usize {
+    let is_printable = |byte: u8| byte.wrapping_sub(0x20) < 0x5f;
+
+    // Whole chunks are checked without branching on every byte, so the check is vectorized
+    let mut len = 0;
+    for chunk in bytes.chunks_exact(16) {
+        if !chunk.iter().fold(true, |all, &byte| all & is_printable(byte)) {
+            break;
+        }
+        len += 16;
+    }
+
+    let rest = &bytes[len..];
+    len + rest.iter().position(|&byte| !is_printable(byte)).unwrap_or(rest.len())
 }
 
 
@@ -178,15 +334,37 @@ pub trait Handler {
     /// OSC to set window title
     fn set_title(&mut self, _: &str) {}
 
+    /// OSC to set the current working directory
+    fn set_working_directory(&mut self, _: PathBuf) {}
+
+    /// Save the window title on the title stack
+    fn push_title(&mut self) {}
+
+    /// Restore the window title from the title stack
+    fn pop_title(&mut self) {}
+
+    /// Report the window title (should write back to the pty stream)
+    fn report_title<W: io::Write>(&mut self, _: &mut W) {}
+
     /// Set the window's mouse cursor
     fn set_mouse_cursor(&mut self, _: MouseCursor) {}
 
     /// Set the cursor style
     fn set_cursor_style(&mut self, _: Option<CursorStyle>) {}
 
+    /// Make the cursor blink or stop blinking, `None` restores the configured blinking
+    fn set_cursor_blinking(&mut self, _: Option<bool>) {}
+
     /// A character to be displayed
     fn input(&mut self, _c: char) {}
 
+    /// A run of printable ASCII characters
+    fn input_ascii(&mut self, text: &[u8]) {
+        for &byte in text {
+            self.input(char::from(byte));
+        }
+    }
+
     /// Set cursor to position
     fn goto(&mut self, _: Line, _: Column) {}
 
@@ -210,6 +388,18 @@ pub trait Handler {
     /// TODO this should probably return an io::Result
     fn identify_terminal<W: io::Write>(&mut self, _: &mut W) {}
 
+    /// Report the terminal type and version (should write back to the pty stream)
+    fn secondary_device_attributes<W: io::Write>(&mut self, _: &mut W) {}
+
+    /// Report the terminal name and version (should write back to the pty stream)
+    fn report_version<W: io::Write>(&mut self, _: &mut W) {}
+
+    /// Report whether a mode is set (should write back to the pty stream)
+    fn report_mode<W: io::Write>(&mut self, _: &mut W, _private: bool, _mode: i64) {}
+
+    /// Report the color at `index`, prefixing the response with the OSC parameters of the query
+    fn report_color<W: io::Write>(&mut self, _: &mut W, _prefix: &str, _index: usize) {}
+
     // Report device status
     fn device_status<W: io::Write>(&mut self, _: &mut W, _: usize) {}
 
@@ -347,6 +537,33 @@ pub trait Handler {
 
     /// Run the dectest routine
     fn dectest(&mut self) {}
+
+    /// Set the size attribute of the cursor line (DECSWL/DECDWL/DECDHL)
+    fn set_line_attribute(&mut self, _: LineAttribute) {}
+
+    /// OSC to mark the prompt and command boundaries of the shell
+    fn shell_mark(&mut self, _: ShellMark) {}
+
+    /// OSC to set the red (0), green (1) or blue (2) channel of the accent color
+    fn set_accent_channel(&mut self, _: usize, _: u8) {}
+
+    /// OSC to reset the accent color to the configured one
+    fn reset_accent_color(&mut self) {}
+
+    /// OSC to switch to a color scheme from the config, `None` selects the configured colors
+    fn set_color_scheme(&mut self, _: Option<&str>) {}
+
+    /// OSC to change the font size by a number of steps, `None` resets it
+    fn adjust_font_size(&mut self, _: Option<i32>) {}
+
+    /// OSC to change the family of the normal font
+    fn set_font_family(&mut self, _: &str) {}
+
+    /// OSC to report the family of the normal font
+    fn report_font<W: io::Write>(&mut self, _: &mut W) {}
+
+    /// OSC to save a screenshot of the next frame
+    fn screenshot(&mut self) {}
 }
 
 /// Describes shape of cursor
@@ -418,10 +635,16 @@ pub enum Mode {
     ReportFocusInOut = 1004,
     /// ?1006
     SgrMouse = 1006,
+    /// ?1015
+    UrxvtMouse = 1015,
+    /// ?1016
+    SgrPixelMouse = 1016,
     /// ?1049
     SwapScreenAndSetRestoreCursor = 1049,
     /// ?2004
     BracketedPaste = 2004,
+    /// ?2027
+    GraphemeClustering = 2027,
 }
 
 impl Mode {
@@ -442,8 +665,11 @@ impl Mode {
                 1003 => Mode::ReportAllMouseMotion,
                 1004 => Mode::ReportFocusInOut,
                 1006 => Mode::SgrMouse,
+                1015 => Mode::UrxvtMouse,
+                1016 => Mode::SgrPixelMouse,
                 1049 => Mode::SwapScreenAndSetRestoreCursor,
                 2004 => Mode::BracketedPaste,
+                2027 => Mode::GraphemeClustering,
                 _ => {
                     trace!("[unimplemented] primitive mode: {}", num);
                     return None
@@ -496,11 +722,51 @@ pub enum TabulationClearMode {
     All,
 }
 
+/// Prompt and command boundaries reported by shell integration (OSC 133)
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ShellMark {
+    /// Start of the prompt
+    PromptStart,
+    /// End of the prompt, where the command line starts
+    CommandStart,
+    /// The command was submitted and its output starts
+    CommandExecuted,
+    /// The command finished, with its exit status if the shell reported it
+    CommandFinished(Option<i32>),
+}
+
+/// Size of the characters on a line
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum LineAttribute {
+    /// Normal single width, single height line
+    SingleWidth,
+    /// Every character is twice as wide
+    DoubleWidth,
+    /// Top half of a double width, double height line
+    DoubleHeightTop,
+    /// Bottom half of a double width, double height line
+    DoubleHeightBottom,
+}
+
+impl Default for LineAttribute {
+    fn default() -> LineAttribute {
+        LineAttribute::SingleWidth
+    }
+}
+
+impl LineAttribute {
+    /// Whether characters on the line take up two columns
+    #[inline]
+    pub fn is_double_width(self) -> bool {
+        self != LineAttribute::SingleWidth
+    }
+}
+
 /// Standard colors
 ///
 /// The order here matters since the enum should be castable to a `usize` for
 /// indexing a color list.
-#[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum NamedColor {
     /// Black
     Black = 0,
@@ -614,7 +880,7 @@ impl NamedColor {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Color {
     Named(NamedColor),
     Spec(Rgb),
@@ -634,9 +900,9 @@ pub enum Attr {
     Italic,
     /// Underscore text
     Underscore,
-    /// Blink cursor slowly
+    /// Blink text slowly
     BlinkSlow,
-    /// Blink cursor fast
+    /// Blink text fast
     BlinkFast,
     /// Invert colors
     Reverse,
@@ -701,6 +967,7 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
 {
     #[inline]
     fn print(&mut self, c: char) {
+        self._state.ground = true;
         self.handler.input(c);
         self._state.preceding_char = Some(c);
     }
@@ -719,7 +986,10 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
             C1::NEL => self.handler.newline(),
             C1::HTS => self.handler.set_horizontal_tabstop(),
             C1::DECID => self.handler.identify_terminal(self.writer),
-            _ => debug!("[unhandled] execute byte={:02x}", byte)
+            _ => {
+                debug!("[unhandled] execute byte={:02x}", byte);
+                self.trace("unhandled", || vec![byte]);
+            },
         }
     }
 
@@ -727,6 +997,7 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
     fn hook(&mut self, params: &[i64], intermediates: &[u8], ignore: bool) {
         debug!("[unhandled hook] params={:?}, ints: {:?}, ignore: {:?}",
                      params, intermediates, ignore);
+        self.trace("unhandled", || sequence_bytes(b'P', params, intermediates, None));
     }
 
     #[inline]
@@ -742,7 +1013,7 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
     // TODO replace OSC parsing with parser combinators
     #[inline]
     fn osc_dispatch(&mut self, params: &[&[u8]]) {
-        fn unhandled(params: &[&[u8]]) {
+        fn unhandled(params: &[&[u8]], trace: bool) {
             let mut buf = String::new();
             for items in params {
                 buf.push_str("[");
@@ -752,8 +1023,14 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                 buf.push_str("],");
             }
             debug!("[unhandled osc_dispatch]: [{}] at line {}", &buf, line!());
+            if trace {
+                info!("[unhandled] {}", printable(&osc_bytes(params)));
+            }
         }
 
+        self.trace("escape", || osc_bytes(params));
+        let trace = self._state.trace;
+
         if params.is_empty() || params[0].is_empty() {
             return;
         }
@@ -767,48 +1044,107 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                         return;
                     }
                 }
-                unhandled(params);
+                unhandled(params, trace);
             },
 
             // Set icon name
             // This is ignored, since alacritty has no concept of tabs
             b"1" => return,
 
+            // Set the accent color, using iTerm2's tab color sequences
+            //
+            // Each channel is set with `OSC 6 ; 1 ; bg ; red|green|blue ; brightness ; N ST` and
+            // the color is reset with `OSC 6 ; 1 ; bg ; * ; default ST`.
+            b"6" => {
+                match params.get(1..5) {
+                    Some([b"1", b"bg", b"*", b"default"]) => {
+                        self.handler.reset_accent_color();
+                        return;
+                    },
+                    Some([b"1", b"bg", channel, b"brightness"]) => {
+                        let index = match *channel {
+                            b"red" => 0,
+                            b"green" => 1,
+                            b"blue" => 2,
+                            _ => return unhandled(params, trace),
+                        };
+                        let value = params
+                            .get(5)
+                            .and_then(|value| str::from_utf8(value).ok())
+                            .and_then(|value| value.parse().ok());
+                        if let Some(value) = value {
+                            self.handler.set_accent_channel(index, value);
+                            return;
+                        }
+                    },
+                    _ => (),
+                }
+                unhandled(params, trace);
+            },
+
+            // Set the current working directory
+            b"7" => {
+                if params.len() >= 2 {
+                    let uri = params[1..].join(&b';');
+                    if let Some(path) = parse_file_uri(&uri) {
+                        self.handler.set_working_directory(path);
+                        return;
+                    }
+                }
+                unhandled(params, trace);
+            },
+
             // Set color index
             b"4" => {
                 if params.len() > 1 && params.len() % 2 != 0 {
                     for chunk in params[1..].chunks(2) {
                         let index = parse_number(chunk[0]);
+                        if let (Some(i), b"?") = (index, chunk[1]) {
+                            let prefix = format!("4;{}", i);
+                            self.handler.report_color(self.writer, &prefix, i as usize);
+                            continue;
+                        }
                         let color = parse_rgb_color(chunk[1]);
-                        if let (Some(i), Some(c)) = (index, color) {
-                            self.handler.set_color(i as usize, c);
-                            return;
+                        match (index, color) {
+                            (Some(i), Some(c)) => self.handler.set_color(i as usize, c),
+                            _ => unhandled(params, trace),
                         }
                     }
+                    return;
                 }
-                unhandled(params);
+                unhandled(params, trace);
             }
 
             // Set foreground color
             b"10" => {
                 if params.len() >= 2 {
+                    if params[1] == b"?" {
+                        let index = NamedColor::Foreground as usize;
+                        self.handler.report_color(self.writer, "10", index);
+                        return;
+                    }
                     if let Some(color) = parse_rgb_color(params[1]) {
                         self.handler.set_color(NamedColor::Foreground as usize, color);
                         return;
                     }
                 }
-                unhandled(params);
+                unhandled(params, trace);
             }
 
             // Set background color
             b"11" => {
                 if params.len() >= 2 {
+                    if params[1] == b"?" {
+                        let index = NamedColor::Background as usize;
+                        self.handler.report_color(self.writer, "11", index);
+                        return;
+                    }
                     if let Some(color) = parse_rgb_color(params[1]) {
                         self.handler.set_color(NamedColor::Background as usize, color);
                         return;
                     }
                 }
-                unhandled(params);
+                unhandled(params, trace);
             }
 
             // Set text cursor color
@@ -819,32 +1155,66 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                         return;
                     }
                 }
-                unhandled(params);
+                unhandled(params, trace);
             }
 
-            // Set cursor style
+            // Change the font size by `#+n` or `#-n` steps like xterm, or reset it with `#`
+            //
+            // Otherwise this sets the cursor style, reports the font with `?` or changes the font
+            // family. Anything after a `:` in the font name, like the size, is ignored.
             b"50" => {
+                if let Some(param) = params.get(1).filter(|param| param.first() == Some(&b'#')) {
+                    let steps = match param.get(1) {
+                        None => None,
+                        Some(&sign) if sign == b'+' || sign == b'-' => {
+                            let count = match &param[2..] {
+                                b"" => 1,
+                                count => match parse_number(count) {
+                                    Some(count) => i32::from(count),
+                                    None => return unhandled(params, trace),
+                                },
+                            };
+                            Some(if sign == b'-' { -count } else { count })
+                        },
+                        Some(_) => return unhandled(params, trace),
+                    };
+                    self.handler.adjust_font_size(steps);
+                    return;
+                }
+
                 if params.len() >= 2 && params[1].len() >= 13 && params[1][0..12] == *b"CursorShape=" {
                     let style = match params[1][12] as char {
                         '0' => CursorStyle::Block,
                         '1' => CursorStyle::Beam,
                         '2' => CursorStyle::Underline,
-                        _ => return unhandled(params),
+                        _ => return unhandled(params, trace),
                     };
                     self.handler.set_cursor_style(Some(style));
                     return;
                 }
-                unhandled(params);
+
+                match params.get(1).map(|param| &param[..]) {
+                    Some(b"?") => self.handler.report_font(self.writer),
+                    Some(name) if !name.is_empty() => {
+                        let name = String::from_utf8_lossy(&params[1..].join(&b';'));
+                        let family = name.split(':').next().unwrap_or("").trim();
+                        if family.is_empty() {
+                            return unhandled(params, trace);
+                        }
+                        self.handler.set_font_family(family);
+                    },
+                    _ => unhandled(params, trace),
+                }
             }
 
             // Set clipboard
             b"52" => {
                 if params.len() < 3 {
-                    return unhandled(params);
+                    return unhandled(params, trace);
                 }
 
                 match params[2] {
-                    b"?" => unhandled(params),
+                    b"?" => unhandled(params, trace),
                     selection => {
                         if let Ok(string) = base64::decode(selection) {
                             if let Ok(utf8_string) = str::from_utf8(&string) {
@@ -869,7 +1239,7 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                 for param in &params[1..] {
                     match parse_number(param) {
                         Some(index) => self.handler.reset_color(index as usize),
-                        None => unhandled(params),
+                        None => unhandled(params, trace),
                     }
                 }
             }
@@ -883,7 +1253,49 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
             // Reset text cursor color
             b"112" => self.handler.reset_color(NamedColor::Cursor as usize),
 
-            _ => unhandled(params),
+            // Switch the color scheme, using iTerm2's `OSC 1337 ; SetColors=preset=name ST`
+            //
+            // An empty name selects the colors of the `colors` section again.
+            b"1337" => {
+                let prefix = b"SetColors=preset=";
+                match params.get(1) {
+                    Some(param) if param.starts_with(prefix) => {
+                        match str::from_utf8(&param[prefix.len()..]) {
+                            Ok("") => self.handler.set_color_scheme(None),
+                            Ok(name) => self.handler.set_color_scheme(Some(name)),
+                            Err(_) => unhandled(params, trace),
+                        }
+                    },
+                    _ => unhandled(params, trace),
+                }
+            },
+
+            // Save a screenshot, using `OSC 1339 ST`
+            b"1339" => self.handler.screenshot(),
+
+            // Shell integration marks
+            b"133" => {
+                let mark = match params.get(1).map(|param| &param[..]) {
+                    Some(b"A") => Some(ShellMark::PromptStart),
+                    Some(b"B") => Some(ShellMark::CommandStart),
+                    Some(b"C") => Some(ShellMark::CommandExecuted),
+                    Some(b"D") => {
+                        let status = params
+                            .get(2)
+                            .and_then(|status| str::from_utf8(status).ok())
+                            .and_then(|status| status.parse().ok());
+                        Some(ShellMark::CommandFinished(status))
+                    },
+                    _ => None,
+                };
+
+                match mark {
+                    Some(mark) => self.handler.shell_mark(mark),
+                    None => unhandled(params, trace),
+                }
+            },
+
+            _ => unhandled(params, trace),
         }
     }
 
@@ -895,6 +1307,11 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
         _ignore: bool,
         action: char
     ) {
+        self._state.ground = true;
+        let bytes = || sequence_bytes(b'[', args, intermediates, Some(action as u8));
+        self.trace("escape", bytes);
+        let trace = self._state.trace;
+
         let private = intermediates.get(0).map(|b| *b == b'?').unwrap_or(false);
         let handler = &mut self.handler;
         let writer = &mut self.writer;
@@ -903,6 +1320,9 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
             () => {{
                 debug!("[Unhandled CSI] action={:?}, args={:?}, intermediates={:?}",
                              action, args, intermediates);
+                if trace {
+                    info!("[unhandled] {}", printable(&bytes()));
+                }
                 return;
             }}
         }
@@ -935,6 +1355,9 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                 }
             },
             'B' | 'e' => handler.move_down(Line(arg_or_default!(idx: 0, default: 1) as usize)),
+            'c' if intermediates.get(0) == Some(&b'>') => {
+                handler.secondary_device_attributes(writer)
+            },
             'c' => handler.identify_terminal(writer),
             'C' | 'a' => handler.move_forward(Column(arg_or_default!(idx: 0, default: 1) as usize)),
             'D' => handler.move_backward(Column(arg_or_default!(idx: 0, default: 1) as usize)),
@@ -1094,6 +1517,9 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                     i += 1; // C-for expr
                 }
             }
+            'p' if intermediates.last() == Some(&b'$') => {
+                handler.report_mode(writer, private, args.get(0).cloned().unwrap_or(0))
+            },
             'n' => handler.device_status(writer, arg_or_default!(idx: 0, default: 0) as usize),
             'r' => {
                 if private {
@@ -1111,9 +1537,19 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                 handler.set_scrolling_region(top..bottom);
             },
             's' => handler.save_cursor_position(),
+            't' => match arg_or_default!(idx: 0, default: 0) {
+                21 => handler.report_title(writer),
+                // Icon names are ignored, since alacritty has no concept of tabs
+                22 | 23 if args.get(1) == Some(&1) => (),
+                22 => handler.push_title(),
+                23 => handler.pop_title(),
+                _ => unhandled!(),
+            },
             'u' => handler.restore_cursor_position(),
+            'q' if intermediates.get(0) == Some(&b'>') => handler.report_version(writer),
             'q' => {
-                let style = match arg_or_default!(idx: 0, default: 0) {
+                let shape = arg_or_default!(idx: 0, default: 0);
+                let style = match shape {
                     0 => None,
                     1 | 2 => Some(CursorStyle::Block),
                     3 | 4 => Some(CursorStyle::Underline),
@@ -1121,7 +1557,14 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                     _ => unhandled!()
                 };
 
+                // Odd shapes blink, even shapes are steady
+                let blinking = match shape {
+                    0 => None,
+                    _ => Some(shape % 2 == 1),
+                };
+
                 handler.set_cursor_style(style);
+                handler.set_cursor_blinking(blinking);
             }
             _ => unhandled!(),
         }
@@ -1135,10 +1578,14 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
         _ignore: bool,
         byte: u8
     ) {
+        self._state.ground = true;
+        self.trace("escape", || esc_bytes(intermediates, byte));
+
         macro_rules! unhandled {
             () => {{
                 debug!("[unhandled] esc_dispatch params={:?}, ints={:?}, byte={:?} ({:02x})",
                              params, intermediates, byte as char, byte);
+                self.trace("unhandled", || esc_bytes(intermediates, byte));
                 return;
             }}
         }
@@ -1168,6 +1615,18 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
             b'Z' => self.handler.identify_terminal(self.writer),
             b'c' => self.handler.reset_state(),
             b'0' => configure_charset!(StandardCharset::SpecialCharacterAndLineDrawing),
+            b'3' if intermediates.first() == Some(&b'#') => {
+                self.handler.set_line_attribute(LineAttribute::DoubleHeightTop)
+            }
+            b'4' if intermediates.first() == Some(&b'#') => {
+                self.handler.set_line_attribute(LineAttribute::DoubleHeightBottom)
+            }
+            b'5' if intermediates.first() == Some(&b'#') => {
+                self.handler.set_line_attribute(LineAttribute::SingleWidth)
+            }
+            b'6' if intermediates.first() == Some(&b'#') => {
+                self.handler.set_line_attribute(LineAttribute::DoubleWidth)
+            }
             b'7' => self.handler.save_cursor_position(),
             b'8' => {
                 if !intermediates.is_empty() && intermediates[0] == b'#' {
@@ -1393,9 +1852,11 @@ pub mod C1 {
 #[cfg(test)]
 mod tests {
     use std::io;
+    use std::path::PathBuf;
     use crate::index::{Line, Column};
-    use super::{Processor, Handler, Attr, TermInfo, Color, StandardCharset, CharsetIndex, parse_rgb_color, parse_number};
+    use super::{Processor, Handler, Attr, TermInfo, Color, StandardCharset, CharsetIndex, parse_rgb_color, parse_number, parse_file_uri, local_hostname};
     use crate::Rgb;
+    use super::{esc_bytes, osc_bytes, printable, sequence_bytes};
 
     /// The /dev/null of `io::Write`
     struct Void;
@@ -1500,6 +1961,72 @@ mod tests {
         }
     }
 
+    #[derive(Default)]
+    struct FontSizeHandler {
+        steps: Vec<Option<i32>>,
+        families: Vec<String>,
+    }
+
+    impl Handler for FontSizeHandler {
+        fn adjust_font_size(&mut self, steps: Option<i32>) {
+            self.steps.push(steps);
+        }
+
+        fn set_font_family(&mut self, family: &str) {
+            self.families.push(family.into());
+        }
+    }
+
+    impl TermInfo for FontSizeHandler {
+        fn lines(&self) -> Line {
+            Line(24)
+        }
+
+        fn cols(&self) -> Column {
+            Column(80)
+        }
+    }
+
+    #[test]
+    fn parse_font_size_osc() {
+        let mut parser = Processor::new();
+        let mut handler = FontSizeHandler::default();
+
+        let bytes = b"\x1b]50;#+\x07\x1b]50;#-3\x07\x1b]50;#\x07\x1b]50;#x\x07";
+        for byte in &bytes[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.steps, vec![Some(1), Some(-3), None]);
+    }
+
+    #[test]
+    fn traced_sequence_bytes() {
+        let csi = sequence_bytes(b'[', &[1049], b"?", Some(b'h'));
+        assert_eq!(printable(&csi), "\\e[?1049h");
+
+        let decscusr = sequence_bytes(b'[', &[5], b" ", Some(b'q'));
+        assert_eq!(printable(&decscusr), "\\e[5 q");
+
+        let osc: &[&[u8]] = &[&b"2"[..], &b"title"[..]];
+        assert_eq!(printable(&osc_bytes(osc)), "\\e]2;title\\x07");
+
+        assert_eq!(printable(&esc_bytes(b"(", b'B')), "\\e(B");
+    }
+
+    #[test]
+    fn parse_font_family_osc() {
+        let mut parser = Processor::new();
+        let mut handler = FontSizeHandler::default();
+
+        let bytes = b"\x1b]50;Fira Code\x07\x1b]50;Hack:size=12\x07\x1b]50;:size=12\x07";
+        for byte in &bytes[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.families, vec![String::from("Fira Code"), String::from("Hack")]);
+    }
+
     struct CharsetHandler {
         index: CharsetIndex,
         charset: StandardCharset,
@@ -1563,6 +2090,73 @@ mod tests {
         assert_eq!(handler.index, CharsetIndex::G1);
     }
 
+    #[derive(Default)]
+    struct TextHandler {
+        text: String,
+        runs: Vec<String>,
+        title: String,
+        attr: Option<Attr>,
+    }
+
+    impl Handler for TextHandler {
+        fn input(&mut self, c: char) {
+            self.text.push(c);
+        }
+
+        fn input_ascii(&mut self, text: &[u8]) {
+            self.runs.push(String::from_utf8(text.to_vec()).unwrap());
+            self.text.extend(text.iter().map(|&byte| char::from(byte)));
+        }
+
+        fn set_title(&mut self, title: &str) {
+            self.title = title.to_owned();
+        }
+
+        fn terminal_attribute(&mut self, attr: Attr) {
+            self.attr = Some(attr);
+        }
+    }
+
+    impl TermInfo for TextHandler {
+        fn lines(&self) -> Line { Line(24) }
+        fn cols(&self) -> Column { Column(80) }
+    }
+
+    #[test]
+    fn plain_text_fast_path() {
+        static BYTES: &'static [u8] =
+            b"plain \x1b]2;a title\x07text \x1b[1mbold \xc3\xa9 and a long run of plain text\r\n";
+
+        let mut parser = Processor::new();
+        let mut slow = TextHandler::default();
+        for byte in &BYTES[..] {
+            parser.advance(&mut slow, *byte, &mut Void);
+        }
+
+        let mut parser = Processor::new();
+        let mut fast = TextHandler::default();
+        parser.advance_bytes(&mut fast, BYTES, &mut Void);
+
+        assert_eq!(fast.text, slow.text);
+        assert_eq!(fast.title, "a title");
+        assert_eq!(fast.attr, Some(Attr::Bold));
+        assert!(slow.runs.is_empty());
+        // The first character after an OSC sequence goes through the parser
+        assert_eq!(fast.runs, vec!["plain ", "ext ", "bold ", " and a long run of plain text"]);
+    }
+
+    #[test]
+    fn fast_path_continues_sequences() {
+        let mut parser = Processor::new();
+        let mut handler = TextHandler::default();
+
+        parser.advance_bytes(&mut handler, b"\x1b]2;half a", &mut Void);
+        parser.advance_bytes(&mut handler, b" title\x07after", &mut Void);
+
+        assert_eq!(handler.title, "half a title");
+        assert_eq!(handler.text, "after");
+    }
+
     #[test]
     fn parse_valid_rgb_color() {
         assert_eq!(parse_rgb_color(b"rgb:11/aa/ff"), Some(Rgb { r: 0x11, g: 0xaa, b: 0xff }));
@@ -1573,6 +2167,18 @@ mod tests {
         assert_eq!(parse_rgb_color(b"#11aaff"), Some(Rgb { r: 0x11, g: 0xaa, b: 0xff }));
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn parse_working_directory_uri() {
+        assert_eq!(parse_file_uri(b"file://localhost/tmp/a%20b"), Some(PathBuf::from("/tmp/a b")));
+        assert_eq!(parse_file_uri(b"file:///tmp"), Some(PathBuf::from("/tmp")));
+        assert_eq!(parse_file_uri(b"http://localhost/tmp"), None);
+
+        let local = format!("file://{}/tmp", local_hostname().unwrap());
+        assert_eq!(parse_file_uri(local.as_bytes()), Some(PathBuf::from("/tmp")));
+        assert_eq!(parse_file_uri(b"file://remote.invalid/tmp"), None);
+    }
+
     #[test]
     fn parse_invalid_number() {
         assert_eq!(parse_number(b"1abc"), None);
@@ -0,0 +1,450 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Light or dark appearance of the OS
+//!
+//! The appearance is read from the freedesktop settings portal, the macOS defaults or the
+//! Windows registry, using the platform's command line tools. Changes are picked up from the
+//! portal's `SettingChanged` signal, the `AppleInterfaceThemeChangedNotification` or a change
+//! notification of the registry key.
+#[cfg(target_os = "macos")]
+use std::os::raw::c_void;
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+#[cfg(not(any(target_os = "macos", windows)))]
+use std::io::{BufRead, BufReader};
+#[cfg(not(any(target_os = "macos", windows)))]
+use std::process::{Child, Stdio};
+use std::io;
+use std::process::Command;
+use std::sync::Arc;
+
+use glutin::EventsLoopProxy;
+use parking_lot::Mutex;
+
+#[cfg(not(target_os = "macos"))]
+use crate::util::thread;
+
+/// Registry key with the personalization settings of the current user
+#[cfg(windows)]
+const PERSONALIZE_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize";
+
+/// Light or dark appearance of the OS
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Appearance {
+    Light,
+    Dark,
+}
+
+/// Watches the appearance of the OS and wakes up the events loop when it changes
+///
+/// Changes are no longer watched once the watcher is dropped.
+pub struct Watcher {
+    appearance: Arc<Mutex<Option<Appearance>>>,
+    _subscription: Option<Subscription>,
+}
+
+impl Watcher {
+    pub fn new(proxy: EventsLoopProxy) -> Watcher {
+        let appearance = system_appearance();
+        info!("System appearance is {:?}", appearance);
+
+        let appearance = Arc::new(Mutex::new(appearance));
+
+        // Don't watch for changes on systems where the appearance can't be detected
+        let mut subscription = None;
+        if appearance.lock().is_some() {
+            match Subscription::new(appearance.clone(), proxy) {
+                Ok(new) => subscription = Some(new),
+                Err(err) => warn!("Unable to watch the system appearance: {}", err),
+            }
+        }
+
+        Watcher { appearance, _subscription: subscription }
+    }
+
+    /// Current appearance of the OS, if it could be detected
+    pub fn appearance(&self) -> Option<Appearance> {
+        *self.appearance.lock()
+    }
+}
+
+/// Read the appearance again after a change notification, waking up the events loop if it changed
+fn update(shared: &Mutex<Option<Appearance>>, proxy: &EventsLoopProxy) {
+    let current = system_appearance();
+    let mut appearance = shared.lock();
+    if current.is_some() && *appearance != current {
+        info!("System appearance changed to {:?}", current);
+        *appearance = current;
+        let _ = proxy.wakeup();
+    }
+}
+
+/// `dbus-monitor` printing the `SettingChanged` signals of the settings portal
+#[cfg(not(any(target_os = "macos", windows)))]
+struct Subscription {
+    monitor: Child,
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+impl Subscription {
+    fn new(shared: Arc<Mutex<Option<Appearance>>>, proxy: EventsLoopProxy) -> io::Result<Self> {
+        let mut monitor = Command::new("dbus-monitor")
+            .args(&[
+                "--session",
+                "type='signal',interface='org.freedesktop.portal.Settings',\
+                 member='SettingChanged',arg0='org.freedesktop.appearance',arg1='color-scheme'",
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        // Every signal has a header line naming its member, followed by its arguments
+        let stdout = monitor.stdout.take().expect("monitor stdout is piped");
+        thread::spawn_named("appearance watcher", move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(ref line) if line.contains("member=SettingChanged") => {
+                        update(&shared, &proxy)
+                    },
+                    Ok(_) => (),
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Ok(Subscription { monitor })
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        // The watcher thread stops once the output of the monitor is closed
+        let _ = self.monitor.kill();
+        let _ = self.monitor.wait();
+    }
+}
+
+/// Observer of the distributed `AppleInterfaceThemeChangedNotification`
+///
+/// Notifications are delivered by the run loop of the main thread.
+#[cfg(target_os = "macos")]
+struct Subscription {
+    observer: *mut objc::runtime::Object,
+}
+
+/// State of the observer, stored as pointer in its `state` instance variable
+#[cfg(target_os = "macos")]
+type ObserverState = (Arc<Mutex<Option<Appearance>>>, EventsLoopProxy);
+
+#[cfg(target_os = "macos")]
+impl Subscription {
+    fn new(shared: Arc<Mutex<Option<Appearance>>>, proxy: EventsLoopProxy) -> io::Result<Self> {
+        use objc::runtime::Object;
+
+        let not_found = |name| io::Error::new(io::ErrorKind::NotFound, name);
+        let center_class = objc::runtime::Class::get("NSDistributedNotificationCenter")
+            .ok_or_else(|| not_found("NSDistributedNotificationCenter"))?;
+        let string_class =
+            objc::runtime::Class::get("NSString").ok_or_else(|| not_found("NSString"))?;
+        let observer_class = observer_class().ok_or_else(|| not_found("NSObject"))?;
+
+        unsafe {
+            let observer: *mut Object = msg_send![observer_class, new];
+            let state: Box<ObserverState> = Box::new((shared, proxy));
+            (*observer).set_ivar("state", Box::into_raw(state) as *mut c_void);
+
+            let name = b"AppleInterfaceThemeChangedNotification\0";
+            let name: *mut Object = msg_send![string_class, stringWithUTF8String: name.as_ptr()];
+            let center: *mut Object = msg_send![center_class, defaultCenter];
+            let _: () = msg_send![center,
+                addObserver: observer
+                selector: sel!(themeChanged:)
+                name: name
+                object: std::ptr::null_mut::<Object>()];
+
+            Ok(Subscription { observer })
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        use objc::runtime::{Class, Object};
+
+        unsafe {
+            if let Some(center_class) = Class::get("NSDistributedNotificationCenter") {
+                let center: *mut Object = msg_send![center_class, defaultCenter];
+                let _: () = msg_send![center, removeObserver: self.observer];
+            }
+
+            let state = *(*self.observer).get_ivar::<*mut c_void>("state");
+            drop(Box::from_raw(state as *mut ObserverState));
+            let _: () = msg_send![self.observer, release];
+        }
+    }
+}
+
+/// Class of the notification observer, registered with the runtime on first use
+#[cfg(target_os = "macos")]
+fn observer_class() -> Option<&'static objc::runtime::Class> {
+    use std::sync::Once;
+
+    use objc::declare::ClassDecl;
+    use objc::runtime::{Class, Object, Sel};
+
+    extern "C" fn theme_changed(this: &Object, _: Sel, _notification: *mut Object) {
+        unsafe {
+            let state = *this.get_ivar::<*mut c_void>("state") as *const ObserverState;
+            update(&(*state).0, &(*state).1);
+        }
+    }
+
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        let superclass = match Class::get("NSObject") {
+            Some(superclass) => superclass,
+            None => return,
+        };
+
+        if let Some(mut decl) = ClassDecl::new("AlacrittyAppearanceObserver", superclass) {
+            decl.add_ivar::<*mut c_void>("state");
+            unsafe {
+                decl.add_method(
+                    sel!(themeChanged:),
+                    theme_changed as extern "C" fn(&Object, Sel, *mut Object),
+                );
+            }
+            decl.register();
+        }
+    });
+
+    Class::get("AlacrittyAppearanceObserver")
+}
+
+/// Change notification of the personalization registry key, until `stop` is signaled
+#[cfg(windows)]
+struct Subscription {
+    stop: Arc<Event>,
+}
+
+/// Event object, closed once it's dropped
+#[cfg(windows)]
+struct Event(winapi::um::winnt::HANDLE);
+
+// Event handles can be signaled and waited for from any thread
+#[cfg(windows)]
+unsafe impl Send for Event {}
+#[cfg(windows)]
+unsafe impl Sync for Event {}
+
+#[cfg(windows)]
+impl Event {
+    fn new() -> io::Result<Event> {
+        use winapi::shared::minwindef::FALSE;
+        use winapi::um::synchapi::CreateEventW;
+
+        let handle = unsafe { CreateEventW(std::ptr::null_mut(), FALSE, FALSE, std::ptr::null()) };
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Event(handle))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Event {
+    fn drop(&mut self) {
+        unsafe { winapi::um::handleapi::CloseHandle(self.0) };
+    }
+}
+
+/// Open registry key, closed once it's dropped
+#[cfg(windows)]
+struct Key(winapi::shared::minwindef::HKEY);
+
+// Registry keys can be used from any thread
+#[cfg(windows)]
+unsafe impl Send for Key {}
+
+#[cfg(windows)]
+impl Drop for Key {
+    fn drop(&mut self) {
+        unsafe { winapi::um::winreg::RegCloseKey(self.0) };
+    }
+}
+
+#[cfg(windows)]
+impl Subscription {
+    fn new(shared: Arc<Mutex<Option<Appearance>>>, proxy: EventsLoopProxy) -> io::Result<Self> {
+        use std::ffi::OsStr;
+        use std::os::windows::ffi::OsStrExt;
+
+        use winapi::shared::minwindef::{FALSE, TRUE};
+        use winapi::shared::winerror::ERROR_SUCCESS;
+        use winapi::um::synchapi::WaitForMultipleObjects;
+        use winapi::um::winbase::{INFINITE, WAIT_OBJECT_0};
+        use winapi::um::winnt::{KEY_NOTIFY, REG_NOTIFY_CHANGE_LAST_SET};
+        use winapi::um::winreg::{RegNotifyChangeKeyValue, RegOpenKeyExW, HKEY_CURRENT_USER};
+
+        let path: Vec<u16> = OsStr::new(PERSONALIZE_KEY).encode_wide().chain(Some(0)).collect();
+        let mut key = std::ptr::null_mut();
+        let result =
+            unsafe { RegOpenKeyExW(HKEY_CURRENT_USER, path.as_ptr(), 0, KEY_NOTIFY, &mut key) };
+        if result != ERROR_SUCCESS as i32 {
+            return Err(io::Error::from_raw_os_error(result));
+        }
+        let key = Key(key);
+
+        let changed = Event::new()?;
+        let stop = Arc::new(Event::new()?);
+        let thread_stop = stop.clone();
+        thread::spawn_named("appearance watcher", move || loop {
+            let handles = [changed.0, thread_stop.0];
+            unsafe {
+                let filter = REG_NOTIFY_CHANGE_LAST_SET;
+                let result = RegNotifyChangeKeyValue(key.0, FALSE, filter, changed.0, TRUE);
+                if result != ERROR_SUCCESS as i32
+                    || WaitForMultipleObjects(2, handles.as_ptr(), FALSE, INFINITE) != WAIT_OBJECT_0
+                {
+                    break;
+                }
+            }
+
+            update(&shared, &proxy);
+        });
+
+        Ok(Subscription { stop })
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        unsafe { winapi::um::synchapi::SetEvent(self.stop.0) };
+    }
+}
+
+/// Read the `color-scheme` setting of the freedesktop settings portal
+#[cfg(not(any(target_os = "macos", windows)))]
+fn system_appearance() -> Option<Appearance> {
+    let output = Command::new("dbus-send")
+        .args(&[
+            "--session",
+            "--print-reply=literal",
+            "--reply-timeout=1000",
+            "--dest=org.freedesktop.portal.Desktop",
+            "/org/freedesktop/portal/desktop",
+            "org.freedesktop.portal.Settings.Read",
+            "string:org.freedesktop.appearance",
+            "string:color-scheme",
+        ])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    parse_portal_reply(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse a reply like `variant       variant          uint32 1`
+///
+/// The portal uses `1` for dark, `2` for light and `0` if there is no preference.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn parse_portal_reply(reply: &str) -> Option<Appearance> {
+    match reply.split_whitespace().last()? {
+        "1" => Some(Appearance::Dark),
+        "0" | "2" => Some(Appearance::Light),
+        _ => None,
+    }
+}
+
+/// Read `AppleInterfaceStyle` from the global defaults, which is only set in dark mode
+#[cfg(target_os = "macos")]
+fn system_appearance() -> Option<Appearance> {
+    let output = Command::new("defaults")
+        .args(&["read", "-g", "AppleInterfaceStyle"])
+        .output()
+        .ok()?;
+
+    if output.status.success() && output.stdout.starts_with(b"Dark") {
+        Some(Appearance::Dark)
+    } else {
+        Some(Appearance::Light)
+    }
+}
+
+/// Read `AppsUseLightTheme` from the personalization settings in the registry
+#[cfg(windows)]
+fn system_appearance() -> Option<Appearance> {
+    // Don't open a console window for `reg`
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let output = Command::new("reg")
+        .arg("query")
+        .arg(format!(r"HKCU\{}", PERSONALIZE_KEY))
+        .args(&["/v", "AppsUseLightTheme"])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    parse_registry_value(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse a value like `AppsUseLightTheme    REG_DWORD    0x0`
+#[cfg(windows)]
+fn parse_registry_value(output: &str) -> Option<Appearance> {
+    let line = output.lines().find(|line| line.contains("AppsUseLightTheme"))?;
+    match line.split_whitespace().last()? {
+        "0x0" => Some(Appearance::Dark),
+        _ => Some(Appearance::Light),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(not(any(target_os = "macos", windows)))]
+    fn portal_reply() {
+        use super::{parse_portal_reply, Appearance};
+
+        let reply = "   variant       variant          uint32 1\n";
+        assert_eq!(parse_portal_reply(reply), Some(Appearance::Dark));
+        assert_eq!(parse_portal_reply("variant variant uint32 0"), Some(Appearance::Light));
+        assert_eq!(parse_portal_reply("variant variant uint32 2"), Some(Appearance::Light));
+        assert_eq!(parse_portal_reply(""), None);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn registry_value() {
+        use super::{parse_registry_value, Appearance};
+
+        let output = "\r\nHKEY_CURRENT_USER\\Software\\Microsoft\\Windows\\CurrentVersion\\Themes\\\
+                      Personalize\r\n    AppsUseLightTheme    REG_DWORD    0x0\r\n\r\n";
+        assert_eq!(parse_registry_value(output), Some(Appearance::Dark));
+        let output = "    AppsUseLightTheme    REG_DWORD    0x1\r\n";
+        assert_eq!(parse_registry_value(output), Some(Appearance::Light));
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Session recordings in the asciicast v2 format
+//!
+//! A recording is a JSON header line followed by one JSON array per event, see
+//! https://github.com/asciinema/asciinema/blob/develop/doc/asciicast-v2.md
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::str;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json as json;
+
+/// First line of a recording
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    version: u8,
+    width: usize,
+    height: usize,
+    #[serde(default)]
+    timestamp: u64,
+}
+
+/// Writes the pty output of a session as an asciicast
+///
+/// Recording stops with an error message when writing fails, it never interrupts the session.
+pub struct Recorder<W: Write = File> {
+    writer: Option<W>,
+    start: Instant,
+
+    /// Terminal size of the last event, as columns and lines
+    size: (usize, usize),
+
+    /// Incomplete UTF-8 sequence at the end of the last output
+    pending: Vec<u8>,
+}
+
+impl Recorder<File> {
+    /// Start recording to the file at `path`, replacing any existing file
+    pub fn create(path: &Path, cols: usize, lines: usize) -> io::Result<Recorder> {
+        Recorder::new(File::create(path)?, cols, lines)
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut writer: W, cols: usize, lines: usize) -> io::Result<Recorder<W>> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        let header = Header { version: 2, width: cols, height: lines, timestamp };
+        writeln!(writer, "{}", json::to_string(&header)?)?;
+
+        Ok(Recorder {
+            writer: Some(writer),
+            start: Instant::now(),
+            size: (cols, lines),
+            pending: Vec::new(),
+        })
+    }
+
+    /// Record output of the child process
+    ///
+    /// Multibyte characters which are split between two reads are written with the second one.
+    pub fn output(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+
+        let complete = match str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            // Invalid bytes are written as replacement characters
+            Err(_) => self.pending.len(),
+        };
+        if complete == 0 {
+            return;
+        }
+
+        let rest = self.pending.split_off(complete);
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending = rest;
+
+        self.event("o", &text);
+    }
+
+    /// Record a change of the terminal size, nothing is written if the size didn't change
+    pub fn resize(&mut self, cols: usize, lines: usize) {
+        if self.size != (cols, lines) {
+            self.size = (cols, lines);
+            self.event("r", &format!("{}x{}", cols, lines));
+        }
+    }
+
+    fn event(&mut self, kind: &str, data: &str) {
+        let time = seconds(self.start.elapsed());
+
+        let result = match self.writer {
+            Some(ref mut writer) => json::to_string(&(time, kind, data))
+                .map_err(io::Error::from)
+                .and_then(|event| writeln!(writer, "{}", event)),
+            None => return,
+        };
+
+        if let Err(err) = result {
+            error!("Stopping session recording: {}", err);
+            self.writer = None;
+        }
+    }
+}
+
+/// Output events of a recording, loaded for playback
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub width: usize,
+    pub height: usize,
+
+    /// Output with its time in seconds since the start of the recording
+    pub events: Vec<(f64, Vec<u8>)>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> io::Result<Recording> {
+        Recording::parse(BufReader::new(File::open(path)?))
+    }
+
+    /// Parse an asciicast v2 recording, events other than output are skipped
+    pub fn parse<R: BufRead>(reader: R) -> io::Result<Recording> {
+        let mut lines = reader.lines();
+
+        let header: Header = match lines.next() {
+            Some(line) => json::from_str(&line?)?,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "empty recording")),
+        };
+        if header.version != 2 {
+            let msg = format!("unsupported asciicast version {}", header.version);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+
+        let mut events = Vec::new();
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (time, kind, data): (f64, String, String) = json::from_str(&line)?;
+            if kind == "o" {
+                events.push((time, data.into_bytes()));
+            }
+        }
+
+        Ok(Recording { width: header.width, height: header.height, events })
+    }
+}
+
+#[inline]
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_micros()) / 1e6
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Recorder, Recording};
+
+    #[test]
+    fn record_split_characters() {
+        let mut recorder = Recorder::new(Vec::new(), 80, 24).unwrap();
+
+        let bytes = "aé".as_bytes();
+        recorder.output(&bytes[..2]);
+        recorder.output(&bytes[2..]);
+        recorder.resize(80, 24);
+        recorder.resize(100, 30);
+
+        let text = String::from_utf8(recorder.writer.take().unwrap()).unwrap();
+        // Timestamps are skipped, since they depend on timing
+        let events: Vec<&str> = text
+            .lines()
+            .skip(1)
+            .map(|line| &line[line.find(',').unwrap()..])
+            .collect();
+        assert!(text.starts_with("{\"version\":2,\"width\":80,\"height\":24,"));
+        assert_eq!(events, vec![",\"o\",\"a\"]", ",\"o\",\"é\"]", ",\"r\",\"100x30\"]"]);
+    }
+
+    #[test]
+    fn parse_recording() {
+        let mut recorder = Recorder::new(Vec::new(), 80, 24).unwrap();
+        recorder.output(b"hello");
+        recorder.resize(100, 30);
+        recorder.output(b"\x1b[1mworld");
+
+        let text = recorder.writer.take().unwrap();
+        let recording = Recording::parse(&text[..]).unwrap();
+        let output: Vec<&[u8]> = recording.events.iter().map(|(_, data)| &data[..]).collect();
+        assert_eq!((recording.width, recording.height), (80, 24));
+        assert_eq!(output, vec![&b"hello"[..], &b"\x1b[1mworld"[..]]);
+    }
+
+    #[test]
+    fn parse_recording_unsupported_version() {
+        let text = "{\"version\":1,\"width\":80,\"height\":24}\n";
+        assert!(Recording::parse(text.as_bytes()).is_err());
+    }
+}
@@ -12,27 +12,107 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use ::log;
-use clap::{Arg, App, crate_name, crate_version, crate_authors, crate_description};
+use clap::{Arg, App, AppSettings, ArgMatches, SubCommand};
+use clap::{crate_name, crate_version, crate_authors, crate_description};
 
 use crate::index::{Line, Column};
-use crate::config::{Dimensions, Shell};
+use crate::config::{Dimensions, RendererBackend, Shell};
+use crate::ipc::Message;
 use crate::window::{DEFAULT_TITLE, DEFAULT_CLASS};
+use std::cmp::max;
+use std::env;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 use std::borrow::Cow;
 
 /// Options specified on the command line
+#[derive(Clone)]
 pub struct Options {
     pub live_config_reload: Option<bool>,
     pub print_events: bool,
+
+    /// Log every escape sequence, and the raw bytes of unhandled ones
+    pub trace_escapes: bool,
     pub ref_test: bool,
+    pub ref_test_replay: Option<PathBuf>,
+
+    /// Store the screenshot of a replayed ref test as its reference
+    pub ref_test_update: bool,
     pub dimensions: Option<Dimensions>,
     pub title: Option<String>,
-    pub class: Option<String>,
+    pub class: Option<Class>,
     pub log_level: log::LevelFilter,
     pub command: Option<Shell<'static>>,
     pub working_dir: Option<PathBuf>,
     pub config: Option<PathBuf>,
+    pub config_options: Vec<String>,
     pub persistent_logging: bool,
+
+    /// Keep the window open after the shell exited
+    pub hold: bool,
+
+    /// X11 window the window is embedded into
+    pub embed: Option<u64>,
+    pub record: Option<PathBuf>,
+    pub play: Option<PathBuf>,
+    pub windows: usize,
+
+    /// Renderer overriding the `renderer` config option
+    pub renderer: Option<RendererBackend>,
+
+    /// Message for a running instance, sent by `alacritty msg`
+    pub message: Option<Message>,
+    pub socket: Option<PathBuf>,
+
+    /// Config conversion requested by `alacritty migrate`
+    pub migrate: Option<Migrate>,
+
+    /// Config printing requested by `--print-config`
+    pub print_config: Option<PrintConfig>,
+}
+
+/// Options of `--print-config`
+#[derive(Clone, Debug, Default)]
+pub struct PrintConfig {
+    /// Print TOML instead of YAML, by default the format of the config file is used
+    pub toml: Option<bool>,
+}
+
+/// Class of the window, the `WM_CLASS` property on X11
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Class {
+    /// Instance name, which is also the app id on Wayland
+    pub instance: String,
+
+    /// General class shared by all instances
+    pub general: String,
+}
+
+impl Class {
+    /// Parse `instance` or `instance,general`, the general class defaults to `Alacritty`
+    pub fn new(class: &str) -> Class {
+        let mut parts = class.splitn(2, ',');
+        Class {
+            instance: parts.next().unwrap_or_default().to_owned(),
+            general: parts.next().unwrap_or(DEFAULT_CLASS).to_owned(),
+        }
+    }
+}
+
+impl Default for Class {
+    fn default() -> Class {
+        Class::new(DEFAULT_CLASS)
+    }
+}
+
+/// Options of the `migrate` subcommand
+#[derive(Clone, Debug, Default)]
+pub struct Migrate {
+    /// YAML config to convert, the installed config is used by default
+    pub config: Option<PathBuf>,
+
+    /// Print the TOML config instead of writing it
+    pub dry_run: bool,
 }
 
 impl Default for Options {
@@ -40,7 +120,10 @@ impl Default for Options {
         Options {
             live_config_reload: None,
             print_events: false,
+            trace_escapes: false,
             ref_test: false,
+            ref_test_replay: None,
+            ref_test_update: false,
             dimensions: None,
             title: None,
             class: None,
@@ -48,7 +131,18 @@ impl Default for Options {
             command: None,
             working_dir: None,
             config: None,
+            config_options: Vec::new(),
             persistent_logging: false,
+            hold: false,
+            embed: None,
+            record: None,
+            play: None,
+            windows: 1,
+            renderer: None,
+            message: None,
+            socket: None,
+            migrate: None,
+            print_config: None,
         }
     }
 }
@@ -56,6 +150,11 @@ impl Default for Options {
 impl Options {
     /// Build `Options` from command line arguments
     pub fn load() -> Options {
+        Options::from_args(env::args_os())
+    }
+
+    /// Build `Options` from `args`, starting with the name of the executable
+    fn from_args<I: IntoIterator<Item = OsString>>(args: I) -> Options {
         let mut options = Options::default();
 
         let matches = App::new(crate_name!())
@@ -65,6 +164,16 @@ impl Options {
             .arg(Arg::with_name("ref-test")
                 .long("ref-test")
                 .help("Generates ref test"))
+            .arg(Arg::with_name("ref-test-replay")
+                .long("ref-test-replay")
+                .takes_value(true)
+                .value_name("dir")
+                .conflicts_with_all(&["ref-test", "play", "command"])
+                .help("Replays a ref test and compares a screenshot of the result"))
+            .arg(Arg::with_name("ref-test-update")
+                .long("ref-test-update")
+                .requires("ref-test-replay")
+                .help("Stores the screenshot of a replayed ref test as its reference"))
             .arg(Arg::with_name("live-config-reload")
                 .long("live-config-reload")
                 .help("Enable automatic config reloading"))
@@ -74,9 +183,25 @@ impl Options {
                  .conflicts_with("live-config-reload"))
             .arg(Arg::with_name("print-events")
                 .long("print-events"))
+            .arg(Arg::with_name("trace-escapes")
+                .long("trace-escapes")
+                .conflicts_with("q")
+                .help("Log every escape sequence, and the raw bytes of unhandled ones"))
             .arg(Arg::with_name("persistent-logging")
                 .long("persistent-logging")
                 .help("Keep the log file after quitting Alacritty"))
+            .arg(Arg::with_name("hold")
+                .long("hold")
+                .help("Keep the window open after the shell or command exited"))
+            .arg(Arg::with_name("embed")
+                .long("embed")
+                .takes_value(true)
+                .value_name("window-id")
+                .validator(|id| match parse_window_id(&id) {
+                    Some(_) => Ok(()),
+                    None => Err(format!("{} is not an X11 window id", id)),
+                })
+                .help("Embed the window into an X11 window, like the tabs of tabbed"))
             .arg(Arg::with_name("dimensions")
                 .long("dimensions")
                 .short("d")
@@ -91,7 +216,11 @@ impl Options {
             .arg(Arg::with_name("class")
                  .long("class")
                  .takes_value(true)
-                 .help(&format!("Defines window class on X11 [default: {}]", DEFAULT_CLASS)))
+                 .value_name("instance> | <instance>,<general")
+                 .help(&format!(
+                     "Defines the window class on X11 and the app id on Wayland [default: {}]",
+                     DEFAULT_CLASS,
+                 )))
             .arg(Arg::with_name("q")
                 .short("q")
                 .multiple(true)
@@ -111,6 +240,48 @@ impl Options {
                  .takes_value(true)
                  .help("Specify alternative configuration file \
                        [default: $XDG_CONFIG_HOME/alacritty/alacritty.yml]"))
+            .arg(Arg::with_name("option")
+                 .long("option")
+                 .short("o")
+                 .multiple(true)
+                 .takes_value(true)
+                 .value_name("key=value")
+                 .help("Override config options, like font.size=14"))
+            .arg(Arg::with_name("record")
+                 .long("record")
+                 .takes_value(true)
+                 .value_name("file")
+                 .help("Record the session to an asciicast file"))
+            .arg(Arg::with_name("play")
+                 .long("play")
+                 .takes_value(true)
+                 .value_name("file")
+                 .conflicts_with("command")
+                 .help("Play an asciicast recording instead of starting a shell"))
+            .arg(Arg::with_name("windows")
+                 .long("windows")
+                 .takes_value(true)
+                 .value_name("count")
+                 .validator(|count| match count.parse::<usize>() {
+                     Ok(count) if count > 0 => Ok(()),
+                     _ => Err(String::from("the number of windows must be at least 1")),
+                 })
+                 .conflicts_with_all(&["ref-test", "ref-test-replay", "record", "play"])
+                 .help("Number of windows to open on startup [default: 1]"))
+            .arg(Arg::with_name("renderer")
+                 .long("renderer")
+                 .takes_value(true)
+                 .possible_values(&["auto", "gl3", "gles2", "software"])
+                 .help("OpenGL version used for drawing, software uses an OpenGL CPU rasterizer"))
+            .arg(Arg::with_name("print-config")
+                .long("print-config")
+                .takes_value(true)
+                .min_values(0)
+                .max_values(1)
+                .value_name("format")
+                .possible_values(&["yaml", "toml"])
+                .help("Print the config with all imports, defaults and command line overrides \
+                       applied, then exit"))
             .arg(Arg::with_name("command")
                 .long("command")
                 .short("e")
@@ -118,13 +289,106 @@ impl Options {
                 .takes_value(true)
                 .min_values(1)
                 .allow_hyphen_values(true)
+                .use_delimiter(false)
                 .help("Command and args to execute (must be last argument)"))
-            .get_matches();
+            .subcommand(SubCommand::with_name("msg")
+                .about("Send a message to a running Alacritty instance")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .arg(Arg::with_name("socket")
+                     .long("socket")
+                     .short("s")
+                     .takes_value(true)
+                     .value_name("path")
+                     .help("Socket of the instance [default: $ALACRITTY_SOCKET]"))
+                .subcommand(SubCommand::with_name("create-window")
+                    .about("Open a new window")
+                    .arg(Arg::with_name("working-directory")
+                         .long("working-directory")
+                         .takes_value(true)
+                         .help("Start the shell in the specified working directory"))
+                    .arg(Arg::with_name("command")
+                        .long("command")
+                        .short("e")
+                        .multiple(true)
+                        .takes_value(true)
+                        .min_values(1)
+                        .allow_hyphen_values(true)
+                        .use_delimiter(false)
+                        .help("Command and args to execute (must be last argument)")))
+                .subcommand(SubCommand::with_name("config")
+                    .about("Change config options of the running instance")
+                    .arg(Arg::with_name("options")
+                         .multiple(true)
+                         .value_name("key=value")
+                         .required_unless("reset")
+                         .help("Config options, like font.size=14"))
+                    .arg(Arg::with_name("reset")
+                         .long("reset")
+                         .short("r")
+                         .help("Drop the options set by earlier messages")))
+                .subcommand(SubCommand::with_name("annotate")
+                    .about("Mark a cell of the terminal")
+                    .arg(Arg::with_name("line")
+                         .required(true)
+                         .validator(is_number)
+                         .help("Line of the cell, counted from 0 at the top"))
+                    .arg(Arg::with_name("column")
+                         .required(true)
+                         .validator(is_number)
+                         .help("Column of the cell, counted from 0"))
+                    .arg(Arg::with_name("label")
+                         .long("label")
+                         .short("l")
+                         .takes_value(true)
+                         .help("Text describing the mark"))
+                    .arg(Arg::with_name("window")
+                         .long("window")
+                         .short("w")
+                         .takes_value(true)
+                         .validator(is_number)
+                         .help("Id of the window [default: the first window]")))
+                .subcommand(SubCommand::with_name("clear-annotations")
+                    .about("Remove all marks and bookmarks of a window")
+                    .arg(Arg::with_name("window")
+                         .long("window")
+                         .short("w")
+                         .takes_value(true)
+                         .validator(is_number)
+                         .help("Id of the window [default: the first window]")))
+                .subcommand(SubCommand::with_name("export-svg")
+                    .about("Write the visible screen to an SVG image in the screenshot directory")
+                    .arg(Arg::with_name("window")
+                         .long("window")
+                         .short("w")
+                         .takes_value(true)
+                         .validator(is_number)
+                         .help("Id of the window [default: the first window]")))
+                .subcommand(SubCommand::with_name("get-state")
+                    .about("Print the state of the instance as JSON")))
+            .subcommand(SubCommand::with_name("migrate")
+                .about("Convert a YAML config to TOML")
+                .arg(Arg::with_name("config-file")
+                     .long("config-file")
+                     .short("c")
+                     .takes_value(true)
+                     .help("YAML config to convert [default: the installed alacritty.yml]"))
+                .arg(Arg::with_name("dry-run")
+                     .long("dry-run")
+                     .help("Print the converted config instead of writing alacritty.toml")))
+            .get_matches_from(args);
 
         if matches.is_present("ref-test") {
             options.ref_test = true;
         }
 
+        if let Some(dir) = matches.value_of("ref-test-replay") {
+            options.ref_test_replay = Some(PathBuf::from(dir.to_string()));
+        }
+
+        if matches.is_present("ref-test-update") {
+            options.ref_test_update = true;
+        }
+
         if matches.is_present("print-events") {
             options.print_events = true;
         }
@@ -139,6 +403,12 @@ impl Options {
             options.persistent_logging = true;
         }
 
+        if matches.is_present("hold") {
+            options.hold = true;
+        }
+
+        options.embed = matches.value_of("embed").and_then(parse_window_id);
+
         if let Some(mut dimensions) = matches.values_of("dimensions") {
             let width = dimensions.next().map(|w| w.parse().map(Column));
             let height = dimensions.next().map(|h| h.parse().map(Line));
@@ -147,7 +417,7 @@ impl Options {
             }
         }
 
-        options.class = matches.value_of("class").map(|c| c.to_owned());
+        options.class = matches.value_of("class").map(Class::new);
         options.title = matches.value_of("title").map(|t| t.to_owned());
 
         match matches.occurrences_of("q") {
@@ -163,26 +433,104 @@ impl Options {
             3 | _ => options.log_level = log::LevelFilter::Trace
         }
 
+        // Traced sequences are logged at the info level
+        if matches.is_present("trace-escapes") {
+            options.trace_escapes = true;
+            options.log_level = max(options.log_level, log::LevelFilter::Info);
+        }
+
+        // Relative directories are resolved before macOS switches to the home directory
         if let Some(dir) = matches.value_of("working-directory") {
-            options.working_dir = Some(PathBuf::from(dir.to_string()));
+            let dir = env::current_dir().map(|cwd| cwd.join(dir)).unwrap_or_else(|_| dir.into());
+            options.working_dir = Some(dir);
         }
 
         if let Some(path) = matches.value_of("config-file") {
             options.config = Some(PathBuf::from(path.to_string()));
         }
 
-        if let Some(mut args) = matches.values_of("command") {
+        if let Some(config_options) = matches.values_of("option") {
+            options.config_options = config_options.map(String::from).collect();
+        }
+
+        if let Some(path) = matches.value_of("record") {
+            options.record = Some(PathBuf::from(path.to_string()));
+        }
+
+        if let Some(path) = matches.value_of("play") {
+            options.play = Some(PathBuf::from(path.to_string()));
+        }
+
+        if let Some(Ok(windows)) = matches.value_of("windows").map(str::parse) {
+            options.windows = windows;
+        }
+
+        options.renderer = matches.value_of("renderer").and_then(RendererBackend::from_name);
+
+        // Clap stops parsing options at `-e`, everything after it is passed to the command as is
+        if let Some(mut args) = matches.values_of_os("command") {
             // The following unwrap is guaranteed to succeed.
             // If 'command' exists it must also have a first item since
             // Arg::min_values(1) is set.
-            let command = String::from(args.next().unwrap());
-            let args = args.map(String::from).collect();
+            let command = args.next().unwrap().to_string_lossy().into_owned();
+            let args: Vec<OsString> = args.map(OsString::from).collect();
             options.command = Some(Shell::new_with_args(command, args));
         }
 
+        if let Some(matches) = matches.subcommand_matches("msg") {
+            options.socket = matches.value_of("socket").map(PathBuf::from);
+            options.message = Some(Options::message(matches));
+        }
+
+        if matches.is_present("print-config") {
+            options.print_config = Some(PrintConfig {
+                toml: matches.value_of("print-config").map(|format| format == "toml"),
+            });
+        }
+
+        if let Some(matches) = matches.subcommand_matches("migrate") {
+            options.migrate = Some(Migrate {
+                config: matches.value_of("config-file").map(PathBuf::from),
+                dry_run: matches.is_present("dry-run"),
+            });
+        }
+
         options
     }
 
+    /// Build the message of the `msg` subcommand
+    fn message(matches: &ArgMatches<'_>) -> Message {
+        match matches.subcommand() {
+            ("create-window", Some(matches)) => Message::CreateWindow {
+                working_directory: matches.value_of("working-directory").map(PathBuf::from),
+                // Messages are sent as JSON, so the command has to be UTF-8
+                command: matches.values_of_os("command").map(|command| {
+                    command.map(|arg| arg.to_string_lossy().into_owned()).collect()
+                }),
+            },
+            ("config", Some(matches)) => Message::Config {
+                options: matches
+                    .values_of("options")
+                    .map(|options| options.map(String::from).collect())
+                    .unwrap_or_default(),
+                reset: matches.is_present("reset"),
+            },
+            ("annotate", Some(matches)) => Message::Annotate {
+                window: matches.value_of("window").and_then(|id| id.parse().ok()),
+                line: matches.value_of("line").and_then(|line| line.parse().ok()).unwrap_or(0),
+                column: matches.value_of("column").and_then(|col| col.parse().ok()).unwrap_or(0),
+                label: matches.value_of("label").map(String::from),
+            },
+            ("clear-annotations", Some(matches)) => Message::ClearAnnotations {
+                window: matches.value_of("window").and_then(|id| id.parse().ok()),
+            },
+            ("export-svg", Some(matches)) => Message::ExportSvg {
+                window: matches.value_of("window").and_then(|id| id.parse().ok()),
+            },
+            _ => Message::GetState,
+        }
+    }
+
     pub fn dimensions(&self) -> Option<Dimensions> {
         self.dimensions
     }
@@ -194,4 +542,98 @@ impl Options {
     pub fn config_path(&self) -> Option<Cow<'_, Path>> {
         self.config.as_ref().map(|p| Cow::Borrowed(p.as_path()))
     }
+
+    /// Options for a window opened with `CreateNewWindow`
+    ///
+    /// New windows start the default shell in `working_dir`, recordings, playbacks, ref tests and
+    /// embedding stay with the first window.
+    pub fn for_new_window(&self, working_dir: Option<PathBuf>) -> Options {
+        Options {
+            ref_test: false,
+            ref_test_replay: None,
+            command: None,
+            working_dir: working_dir.or_else(|| self.working_dir.clone()),
+            record: None,
+            play: None,
+            windows: 1,
+            embed: None,
+            ..self.clone()
+        }
+    }
+}
+
+/// Validate arguments which have to be positive integers or 0
+fn is_number(value: String) -> Result<(), String> {
+    value.parse::<usize>().map(|_| ()).map_err(|_| format!("{} is not a number", value))
+}
+
+/// Parse an X11 window id, in decimal or hexadecimal with a `0x` prefix
+fn parse_window_id(id: &str) -> Option<u64> {
+    if id.starts_with("0x") {
+        u64::from_str_radix(&id[2..], 16).ok()
+    } else {
+        id.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::{parse_window_id, Class, Options};
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn command_args_pass_through() {
+        let options = Options::from_args(args(&[
+            "alacritty", "--hold", "-e", "vim", "-d", "--title", "a,b", "-e",
+        ]));
+        let command = options.command.unwrap();
+        assert!(options.hold && options.title.is_none());
+        assert_eq!(command.program(), "vim");
+        assert_eq!(command.args(), &args(&["-d", "--title", "a,b", "-e"])[..]);
+
+        let options = Options::from_args(args(&["alacritty", "--command", "htop"]));
+        assert!(options.command.unwrap().args().is_empty());
+
+        // Only `-e` parsed as an option starts the command
+        let options = Options::from_args(args(&["alacritty", "--title=-e", "--class", "term"]));
+        assert!(options.command.is_none());
+        assert_eq!(options.title, Some(String::from("-e")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_command_args() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let arg = OsString::from_vec(vec![b'a', 0xff]);
+        let mut args = args(&["alacritty", "-e", "cat"]);
+        args.push(arg.clone());
+
+        let options = Options::from_args(args);
+        assert_eq!(options.command.unwrap().args(), &[arg][..]);
+    }
+
+    #[test]
+    fn parse_class() {
+        assert_eq!(Class::new("term"), Class {
+            instance: String::from("term"),
+            general: String::from("Alacritty"),
+        });
+        assert_eq!(Class::new("term,Terminal"), Class {
+            instance: String::from("term"),
+            general: String::from("Terminal"),
+        });
+    }
+
+    #[test]
+    fn parse_embed_window_id() {
+        assert_eq!(parse_window_id("4194317"), Some(4_194_317));
+        assert_eq!(parse_window_id("0x40000d"), Some(0x0040_000d));
+        assert_eq!(parse_window_id("window"), None);
+    }
 }
@@ -55,10 +55,11 @@ macro_rules! bindings {
 }
 
 pub fn default_mouse_bindings() -> Vec<MouseBinding> {
-    bindings!(
-        MouseBinding;
-        MouseButton::Middle; Action::PasteSelection;
-    )
+    vec![MouseBinding {
+        trigger: MouseButton::Middle.into(),
+        action: Action::PasteSelection,
+        ..Default::default()
+    }]
 }
 
 pub fn default_key_bindings() -> Vec<KeyBinding> {
@@ -68,6 +69,7 @@ pub fn default_key_bindings() -> Vec<KeyBinding> {
         Key::Copy; Action::Copy;
         Key::L, [ctrl: true]; Action::ClearLogNotice;
         Key::L, [ctrl: true]; Action::Esc("\x0c".into());
+        Key::Scroll; Action::ToggleOutputPause;
         Key::Home, +TermMode::APP_CURSOR; Action::Esc("\x1bOH".into());
         Key::Home, ~TermMode::APP_CURSOR; Action::Esc("\x1b[H".into());
         Key::End, +TermMode::APP_CURSOR; Action::Esc("\x1bOF".into());
@@ -198,6 +200,7 @@ pub fn platform_key_bindings() -> Vec<KeyBinding> {
         Key::H, [logo: true]; Action::Hide;
         Key::Q, [logo: true]; Action::Quit;
         Key::W, [logo: true]; Action::Quit;
+        Key::N, [logo: true]; Action::CreateNewWindow;
     )
 }
 
@@ -0,0 +1,202 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Conversion of YAML configs to TOML, used by `alacritty migrate`
+//!
+//! Comments and the order of keys are not preserved, since neither survives parsing.
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde_yaml::{self, Value};
+use toml;
+
+/// Convert a YAML config to TOML
+pub fn yaml_to_toml(yaml: &str) -> Result<String, Error> {
+    to_toml(serde_yaml::from_str(yaml)?)
+}
+
+/// Convert a parsed config document to TOML
+pub fn to_toml(mut document: Value) -> Result<String, Error> {
+    strip_nulls(&mut document);
+
+    // Going through `toml::Value` puts plain values in front of tables, as TOML requires
+    let document = toml::Value::try_from(document)?;
+    Ok(toml::to_string_pretty(&document)?)
+}
+
+/// Convert the YAML config at `path` to a TOML file next to it
+///
+/// Returns the path of the TOML file. An existing file is never overwritten.
+pub fn migrate_file(path: &Path) -> Result<PathBuf, Error> {
+    let toml = yaml_to_toml(&fs::read_to_string(path)?)?;
+
+    let target = path.with_extension("toml");
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&target)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::AlreadyExists => Error::Exists(target.clone()),
+            _ => Error::Io(err),
+        })?;
+    file.write_all(toml.as_bytes())?;
+
+    Ok(target)
+}
+
+/// Remove all null values, since TOML has no representation for them
+///
+/// A null option falls back to its default, just like an option which isn't present.
+fn strip_nulls(value: &mut Value) {
+    match *value {
+        Value::Mapping(ref mut mapping) => {
+            let nulls: Vec<Value> = mapping
+                .iter()
+                .filter(|(_, value)| value.is_null())
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in nulls {
+                mapping.remove(&key);
+            }
+
+            for (_, value) in mapping.iter_mut() {
+                strip_nulls(value);
+            }
+        },
+        Value::Sequence(ref mut sequence) => {
+            sequence.retain(|value| !value.is_null());
+            for value in sequence.iter_mut() {
+                strip_nulls(value);
+            }
+        },
+        _ => (),
+    }
+}
+
+/// Errors occurring while migrating a config
+#[derive(Debug)]
+pub enum Error {
+    /// Error reading the YAML or writing the TOML file
+    Io(io::Error),
+
+    /// Not valid yaml
+    Yaml(serde_yaml::Error),
+
+    /// Value which can't be represented in TOML
+    Toml(toml::ser::Error),
+
+    /// TOML file which already exists
+    Exists(PathBuf),
+}
+
+impl ::std::error::Error for Error {
+    fn cause(&self) -> Option<&dyn (::std::error::Error)> {
+        match *self {
+            Error::Io(ref err) => Some(err),
+            Error::Yaml(ref err) => Some(err),
+            Error::Toml(ref err) => Some(err),
+            Error::Exists(_) => None,
+        }
+    }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(ref err) => err.description(),
+            Error::Yaml(ref err) => err.description(),
+            Error::Toml(ref err) => err.description(),
+            Error::Exists(_) => "TOML config already exists",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "Unable to migrate config: {}", err),
+            Error::Yaml(ref err) => write!(f, "Problem with config: {}", err),
+            Error::Toml(ref err) => write!(f, "Unable to convert config to TOML: {}", err),
+            Error::Exists(ref path) => {
+                write!(f, "Refusing to overwrite existing config {}", path.display())
+            },
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(val: io::Error) -> Error {
+        Error::Io(val)
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(val: serde_yaml::Error) -> Error {
+        Error::Yaml(val)
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(val: toml::ser::Error) -> Error {
+        Error::Toml(val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml;
+    use toml;
+
+    use crate::config::Config;
+
+    use super::yaml_to_toml;
+
+    #[test]
+    fn migrate_nested_options() {
+        let yaml = "scrolling:\n  history: 5000\nwindow:\n  title: Migrated\n  dimensions:\n    \
+                    columns: 80\n    lines: 24\nlive_config_reload: false\n";
+
+        let toml = yaml_to_toml(yaml).unwrap();
+        let value: toml::Value = toml::from_str(&toml).unwrap();
+
+        assert_eq!(value["live_config_reload"].as_bool(), Some(false));
+        assert_eq!(value["scrolling"]["history"].as_integer(), Some(5000));
+        assert_eq!(value["window"]["title"].as_str(), Some("Migrated"));
+        assert_eq!(value["window"]["dimensions"]["lines"].as_integer(), Some(24));
+    }
+
+    #[test]
+    fn migrate_drops_nulls() {
+        let yaml = "colors:\n  cursor:\n    text: '0x000000'\n    cursor: ~\nshell: null\n";
+
+        let toml = yaml_to_toml(yaml).unwrap();
+        let value: toml::Value = toml::from_str(&toml).unwrap();
+
+        assert!(value.get("shell").is_none());
+        assert!(value["colors"]["cursor"].get("cursor").is_none());
+        assert_eq!(value["colors"]["cursor"]["text"].as_str(), Some("0x000000"));
+    }
+
+    #[test]
+    fn migrated_default_config_loads() {
+        let toml = yaml_to_toml(super::super::DEFAULT_ALACRITTY_CONFIG).unwrap();
+        let document: serde_yaml::Value = toml::from_str(&toml).unwrap();
+        let migrated: Config = serde_yaml::from_value(document).unwrap();
+        let default: Config = serde_yaml::from_str(super::super::DEFAULT_ALACRITTY_CONFIG).unwrap();
+
+        assert_eq!(migrated.font().size(), default.font().size());
+        assert_eq!(migrated.key_bindings().len(), default.key_bindings().len());
+        assert_eq!(migrated.mouse_bindings().len(), default.mouse_bindings().len());
+    }
+}
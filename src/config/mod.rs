@@ -5,6 +5,7 @@
 //! the config file will also hold user and platform specific keybindings.
 use std::borrow::Cow;
 use std::{env, fmt};
+use std::ffi::OsString;
 use std::fs::{self, File};
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
@@ -16,20 +17,33 @@ use std::collections::HashMap;
 use crate::Rgb;
 use font::Size;
 use serde_yaml;
+use toml;
 use serde::{self, de, Deserialize};
 use serde::de::Error as SerdeError;
 use serde::de::{Visitor, MapAccess, Unexpected};
 use notify::{Watcher, watcher, DebouncedEvent, RecursiveMode};
+use regex::Regex;
+use time;
 use glutin::ModifiersState;
 
 use crate::cli::Options;
-use crate::input::{Action, Binding, MouseBinding, KeyBinding};
+use crate::input::{Action, Binding, MouseBinding, MouseTrigger, KeyBinding};
 use crate::index::{Line, Column};
-use crate::ansi::{CursorStyle, NamedColor, Color};
+use crate::ansi::CursorStyle;
+
+use self::validate::Validator;
 
 mod bindings;
+pub mod migrate;
+mod validate;
 
 const MAX_SCROLLBACK_LINES: u32 = 100_000;
+
+/// Maximum number of animation steps per tick
+pub const MAX_EFFECT_SPEED: u8 = 8;
+
+/// Maximum density of the characters trailing behind new output
+pub const MAX_EFFECT_DENSITY: u8 = 16;
 static DEFAULT_ALACRITTY_CONFIG: &'static str =
     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/alacritty.yml"));
 
@@ -97,7 +111,7 @@ fn deserialize_duration_ms<'a, D>(deserializer: D) -> ::std::result::Result<Dura
 }
 
 #[serde(default)]
-#[derive(Default, Clone, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 pub struct Mouse {
     #[serde(deserialize_with = "failure_default")]
     pub double_click: ClickHandler,
@@ -107,11 +121,32 @@ pub struct Mouse {
     pub hide_when_typing: bool,
     #[serde(deserialize_with = "failure_default")]
     pub url: Url,
+    #[serde(deserialize_with = "deserialize_true_bool")]
+    pub middle_click_paste: bool,
+    #[serde(deserialize_with = "failure_default")]
+    pub autoscroll: bool,
+    #[serde(default = "default_mouse_bindings", deserialize_with = "deserialize_mouse_bindings")]
+    pub bindings: Vec<MouseBinding>,
 
     // TODO: DEPRECATED
     pub faux_scrollback_lines: Option<usize>,
 }
 
+impl Default for Mouse {
+    fn default() -> Self {
+        Self {
+            double_click: Default::default(),
+            triple_click: Default::default(),
+            hide_when_typing: Default::default(),
+            url: Default::default(),
+            middle_click_paste: true,
+            autoscroll: Default::default(),
+            bindings: default_mouse_bindings(),
+            faux_scrollback_lines: None,
+        }
+    }
+}
+
 #[serde(default)]
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 pub struct Url {
@@ -119,6 +154,10 @@ pub struct Url {
     #[serde(deserialize_with = "failure_default")]
     pub launcher: Option<CommandWrapper>,
 
+    // Program for opening `path:line:column` file locations
+    #[serde(deserialize_with = "failure_default")]
+    pub editor: Option<CommandWrapper>,
+
     // Modifier used to open links
     #[serde(deserialize_with = "deserialize_modifiers")]
     pub modifiers: ModifiersState,
@@ -133,6 +172,7 @@ impl Default for Url {
             launcher: Some(CommandWrapper::Just(String::from("open"))),
             #[cfg(windows)]
             launcher: Some(CommandWrapper::Just(String::from("explorer"))),
+            editor: None,
             modifiers: Default::default(),
         }
     }
@@ -216,12 +256,239 @@ impl VisualBellConfig {
     }
 }
 
+/// Characters trailing behind the real ones in the animation after output changed
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum TrailStyle {
+    /// Random printable ASCII characters
+    Random,
+    /// The real character, switching between upper and lower case and accents
+    CaseSwitcher,
+}
+
+impl Default for TrailStyle {
+    fn default() -> Self {
+        TrailStyle::Random
+    }
+}
+
+/// Regex matching logical lines which are never touched or covered by effects
+#[derive(Debug, Clone)]
+pub struct EffectMask(Regex);
+
+impl EffectMask {
+    #[inline]
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+}
+
+impl PartialEq for EffectMask {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for EffectMask {}
+
+/// Hours, days of the week and months in which effects are active
+///
+/// Every unset part of the schedule matches all times.
+#[serde(default)]
+#[derive(Default, Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct EffectSchedule {
+    /// First hour of the day in which effects are active, in local time
+    #[serde(deserialize_with = "failure_default")]
+    start_hour: Option<u8>,
+
+    /// Hour of the day at which effects stop, wrapping past midnight when before `start_hour`
+    #[serde(deserialize_with = "failure_default")]
+    end_hour: Option<u8>,
+
+    #[serde(deserialize_with = "failure_default_vec")]
+    weekdays: Vec<Weekday>,
+
+    /// Months from 1 for January to 12 for December
+    #[serde(deserialize_with = "failure_default_vec")]
+    months: Vec<u8>,
+}
+
+impl EffectSchedule {
+    /// Check if effects are active at the local time `now`
+    pub fn is_active(&self, now: &time::Tm) -> bool {
+        let hour = now.tm_hour as u8;
+        let hour_matches = match (self.start_hour, self.end_hour) {
+            (Some(start), Some(end)) if start <= end => hour >= start && hour < end,
+            (Some(start), Some(end)) => hour >= start || hour < end,
+            (Some(start), None) => hour >= start,
+            (None, Some(end)) => hour < end,
+            (None, None) => true,
+        };
+
+        let weekday_matches =
+            self.weekdays.is_empty() || self.weekdays.iter().any(|day| *day as i32 == now.tm_wday);
+        let month_matches = self.months.is_empty() || self.months.contains(&(now.tm_mon as u8 + 1));
+
+        hour_matches && weekday_matches && month_matches
+    }
+}
+
+/// Speed, density and colors of the animation
+#[serde(default)]
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub struct EffectStyle {
+    /// Animation steps per tick of trails, fades and sweeps, from 1 to `MAX_EFFECT_SPEED`
+    #[serde(deserialize_with = "deserialize_effect_speed")]
+    pub speed: u8,
+
+    /// Length of the trails behind changed characters, from 1 to `MAX_EFFECT_DENSITY`
+    #[serde(deserialize_with = "deserialize_effect_density")]
+    pub density: u8,
+
+    #[serde(deserialize_with = "failure_default")]
+    pub palette: EffectPalette,
+}
+
+impl Default for EffectStyle {
+    fn default() -> Self {
+        EffectStyle {
+            speed: default_effect_speed(),
+            density: default_effect_density(),
+            palette: Default::default(),
+        }
+    }
+}
+
+fn default_effect_speed() -> u8 {
+    1
+}
+
+fn default_effect_density() -> u8 {
+    8
+}
+
+fn deserialize_effect_speed<'a, D>(deserializer: D) -> ::std::result::Result<u8, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match u8::deserialize(deserializer) {
+        Ok(speed) => Ok(speed.max(1).min(MAX_EFFECT_SPEED)),
+        Err(err) => {
+            error!("Problem with config: {}; using default value", err);
+            Ok(default_effect_speed())
+        },
+    }
+}
+
+fn deserialize_effect_density<'a, D>(deserializer: D) -> ::std::result::Result<u8, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match u8::deserialize(deserializer) {
+        Ok(density) => Ok(density.max(1).min(MAX_EFFECT_DENSITY)),
+        Err(err) => {
+            error!("Problem with config: {}; using default value", err);
+            Ok(default_effect_density())
+        },
+    }
+}
+
+/// Colors of the characters trailing behind new output
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum EffectPalette {
+    Green,
+    Amber,
+    Cyan,
+    White,
+}
+
+impl EffectPalette {
+    /// All palettes, in the order they're cycled through
+    pub const ALL: [EffectPalette; 4] =
+        [EffectPalette::Green, EffectPalette::Amber, EffectPalette::Cyan, EffectPalette::White];
+
+    /// Color of a trailing character with the given brightness
+    pub fn color(self, brightness: u8) -> Rgb {
+        let b = brightness;
+        match self {
+            EffectPalette::Green => Rgb { r: 0, g: b, b: 0 },
+            EffectPalette::Amber => Rgb { r: b, g: (u16::from(b) * 3 / 4) as u8, b: 0 },
+            EffectPalette::Cyan => Rgb { r: 0, g: b, b },
+            EffectPalette::White => Rgb { r: b, g: b, b },
+        }
+    }
+}
+
+impl Default for EffectPalette {
+    fn default() -> Self {
+        EffectPalette::Green
+    }
+}
+
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum Weekday {
+    Sunday = 0,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+}
+
+impl<'de> Deserialize<'de> for EffectMask {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+        where D: de::Deserializer<'de>
+    {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern).map(EffectMask).map_err(D::Error::custom)
+    }
+}
+
+/// Blinking text configuration
+#[serde(default)]
 #[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct TextBlinkConfig {
+    /// Blink text with the slow or rapid blink attribute
+    #[serde(deserialize_with = "deserialize_true_bool")]
+    enabled: bool,
+
+    /// Time blinking text stays visible or hidden in milliseconds
+    #[serde(deserialize_with = "failure_default")]
+    interval: u16,
+}
+
+impl Default for TextBlinkConfig {
+    fn default() -> TextBlinkConfig {
+        TextBlinkConfig {
+            enabled: true,
+            interval: 500,
+        }
+    }
+}
+
+impl TextBlinkConfig {
+    /// Blink interval of slowly blinking text, `None` when blinking is disabled
+    #[inline]
+    pub fn interval(&self) -> Option<Duration> {
+        if self.enabled && self.interval > 0 {
+            Some(Duration::from_millis(u64::from(self.interval)))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
 pub struct Shell<'a> {
+    /// Empty when only the working directory of the default shell is configured
+    #[serde(default)]
     program: Cow<'a, str>,
 
+    /// Arguments are kept as they were passed on the command line, even if they aren't UTF-8
+    #[serde(default, deserialize_with = "deserialize_args")]
+    args: Vec<OsString>,
+
+    /// Directory the shell is started in, unless `--working-directory` is passed
     #[serde(default, deserialize_with = "failure_default")]
-    args: Vec<String>,
+    working_directory: Option<PathBuf>,
 }
 
 impl<'a> Shell<'a> {
@@ -231,15 +498,18 @@ impl<'a> Shell<'a> {
         Shell {
             program: program.into(),
             args: Vec::new(),
+            working_directory: None,
         }
     }
 
-    pub fn new_with_args<S>(program: S, args: Vec<String>) -> Shell<'a>
-        where S: Into<Cow<'a, str>>
+    pub fn new_with_args<S, A>(program: S, args: Vec<A>) -> Shell<'a>
+        where S: Into<Cow<'a, str>>,
+              A: Into<OsString>
     {
         Shell {
             program: program.into(),
-            args,
+            args: args.into_iter().map(Into::into).collect(),
+            working_directory: None,
         }
     }
 
@@ -247,11 +517,18 @@ impl<'a> Shell<'a> {
         &*self.program
     }
 
-    pub fn args(&self) -> &[String] {
+    pub fn args(&self) -> &[OsString] {
         self.args.as_slice()
     }
 }
 
+fn deserialize_args<'a, D>(deserializer: D) -> ::std::result::Result<Vec<OsString>, D::Error>
+    where D: de::Deserializer<'a>
+{
+    let args: Vec<String> = failure_default(deserializer)?;
+    Ok(args.into_iter().map(OsString::from).collect())
+}
+
 /// Wrapper around f32 that represents an alpha value between 0.0 and 1.0
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Alpha(f32);
@@ -378,6 +655,20 @@ impl<'de> Deserialize<'de> for Decorations {
     }
 }
 
+/// Initial state of the window
+#[derive(Debug, Copy, Clone, Deserialize, PartialEq, Eq)]
+pub enum StartupMode {
+    Windowed,
+    Maximized,
+    Fullscreen,
+}
+
+impl Default for StartupMode {
+    fn default() -> Self {
+        StartupMode::Windowed
+    }
+}
+
 #[serde(default)]
 #[derive(Debug, Copy, Clone, Deserialize, PartialEq, Eq)]
 pub struct WindowConfig {
@@ -397,9 +688,25 @@ pub struct WindowConfig {
     #[serde(deserialize_with = "failure_default")]
     dynamic_padding: bool,
 
-    /// Start maximized
+    /// Start maximized, deprecated in favor of `startup_mode`
     #[serde(deserialize_with = "failure_default")]
     start_maximized: bool,
+
+    /// Initial state of the window
+    #[serde(deserialize_with = "failure_default")]
+    startup_mode: StartupMode,
+
+    /// Ask the compositor to blur what's behind the transparent background
+    #[serde(deserialize_with = "failure_default")]
+    blur: bool,
+
+    /// Keep the window above all other windows
+    #[serde(deserialize_with = "failure_default")]
+    always_on_top: bool,
+
+    /// Show the window on all workspaces
+    #[serde(deserialize_with = "failure_default")]
+    sticky: bool,
 }
 
 impl Default for WindowConfig {
@@ -410,6 +717,10 @@ impl Default for WindowConfig {
             decorations: Default::default(),
             dynamic_padding: Default::default(),
             start_maximized: Default::default(),
+            startup_mode: Default::default(),
+            blur: Default::default(),
+            always_on_top: Default::default(),
+            sticky: Default::default(),
         }
     }
 }
@@ -439,8 +750,24 @@ impl WindowConfig {
         self.dynamic_padding
     }
 
-    pub fn start_maximized(&self) -> bool {
-        self.start_maximized
+    pub fn startup_mode(&self) -> StartupMode {
+        if self.start_maximized && self.startup_mode == StartupMode::Windowed {
+            StartupMode::Maximized
+        } else {
+            self.startup_mode
+        }
+    }
+
+    pub fn blur(&self) -> bool {
+        self.blur
+    }
+
+    pub fn always_on_top(&self) -> bool {
+        self.always_on_top
+    }
+
+    pub fn sticky(&self) -> bool {
+        self.sticky
     }
 }
 
@@ -467,6 +794,10 @@ pub struct Config {
     #[serde(default, deserialize_with = "failure_default")]
     render_timer: bool,
 
+    /// OpenGL version used for drawing
+    #[serde(default, deserialize_with = "failure_default")]
+    renderer: RendererBackend,
+
     /// Should draw bold text with brighter colors instead of bold font
     #[serde(default = "default_true_bool", deserialize_with = "deserialize_true_bool")]
     draw_bold_text_with_bright_colors: bool,
@@ -474,10 +805,22 @@ pub struct Config {
     #[serde(default, deserialize_with = "failure_default")]
     colors: Colors,
 
+    /// Named color schemes which can be selected at runtime
+    #[serde(default, deserialize_with = "failure_default_vec")]
+    color_schemes: Vec<ColorScheme>,
+
     /// Background opacity from 0.0 to 1.0
     #[serde(default, deserialize_with = "failure_default")]
     background_opacity: Alpha,
 
+    /// Color which identifies the window
+    #[serde(default, deserialize_with = "failure_default")]
+    accent: Accent,
+
+    /// Colors used while specific programs run in the foreground
+    #[serde(default, deserialize_with = "failure_default_vec")]
+    profiles: Vec<Profile>,
+
     /// Window configuration
     #[serde(default, deserialize_with = "failure_default")]
     window: WindowConfig,
@@ -486,13 +829,21 @@ pub struct Config {
     #[serde(default="default_key_bindings", deserialize_with = "deserialize_key_bindings")]
     key_bindings: Vec<KeyBinding>,
 
-    /// Bindings for the mouse
-    #[serde(default="default_mouse_bindings", deserialize_with = "deserialize_mouse_bindings")]
-    mouse_bindings: Vec<MouseBinding>,
+    // TODO: DEPRECATED
+    #[serde(default, deserialize_with = "deserialize_deprecated_mouse_bindings")]
+    mouse_bindings: Option<Vec<MouseBinding>>,
 
     #[serde(default, deserialize_with = "failure_default")]
     selection: Selection,
 
+    /// Marker for pasted text on the command line
+    #[serde(default, deserialize_with = "failure_default")]
+    paste_marker: PasteMarker,
+
+    /// Glyph marking lines which continue a soft-wrapped line
+    #[serde(default, deserialize_with = "failure_default")]
+    wrap_indicator: Option<char>,
+
     #[serde(default, deserialize_with = "failure_default")]
     mouse: Mouse,
 
@@ -500,22 +851,78 @@ pub struct Config {
     #[serde(default, deserialize_with = "failure_default")]
     shell: Option<Shell<'static>>,
 
+    /// Commands and behavior for when the shell exits
+    #[serde(default, deserialize_with = "failure_default")]
+    hooks: Hooks,
+
+    /// Banner typed into the first window at startup
+    #[serde(default, deserialize_with = "failure_default")]
+    startup_banner: StartupBanner,
+
+    /// Output of the `DumpScreen`, `DumpHistory` and `DumpSelection` actions
+    #[serde(default, deserialize_with = "failure_default")]
+    dump: Dump,
+
+    /// Destination of screenshots and whether escape sequences can take them
+    #[serde(default, deserialize_with = "failure_default")]
+    screenshot: Screenshot,
+
     /// Path where config was loaded from
     #[serde(default, deserialize_with = "failure_default")]
     config_path: Option<PathBuf>,
 
+    /// Files imported by the config, directly or through other imports
+    #[serde(skip)]
+    imports: Vec<PathBuf>,
+
     /// Visual bell configuration
     #[serde(default, deserialize_with = "failure_default")]
     visual_bell: VisualBellConfig,
 
+    /// Characters trailing behind the real ones in the animation
+    #[serde(default, deserialize_with = "failure_default")]
+    trail_style: TrailStyle,
+
+    /// Logical lines which are left alone by effects
+    #[serde(default, deserialize_with = "failure_default_vec")]
+    effect_masks: Vec<EffectMask>,
+
+    /// Times at which effects are active
+    #[serde(default, deserialize_with = "failure_default")]
+    effect_schedule: EffectSchedule,
+
+    /// Speed, density and colors of the animation
+    #[serde(default, deserialize_with = "failure_default")]
+    effect_style: EffectStyle,
+
+    /// Bell command configuration
+    #[serde(default, deserialize_with = "failure_default")]
+    bell: Bell,
+
+    /// Desktop notifications
+    #[serde(default, deserialize_with = "failure_default")]
+    notifications: Notifications,
+
+    /// Blinking text configuration
+    #[serde(default, deserialize_with = "failure_default")]
+    text_blink: TextBlinkConfig,
+
     /// Use dynamic title
     #[serde(default = "default_true_bool", deserialize_with = "deserialize_true_bool")]
     dynamic_title: bool,
 
+    /// Answer requests for the window title
+    #[serde(default, deserialize_with = "failure_default")]
+    report_title: bool,
+
     /// Live config reload
     #[serde(default = "default_true_bool", deserialize_with = "deserialize_true_bool")]
     live_config_reload: bool,
 
+    /// Listen for messages from `alacritty msg`
+    #[serde(default, deserialize_with = "failure_default")]
+    ipc_socket: bool,
+
     /// Number of spaces in one tab
     #[serde(default = "default_tabspaces", deserialize_with = "deserialize_tabspaces")]
     tabspaces: usize,
@@ -524,6 +931,14 @@ pub struct Config {
     #[serde(default, deserialize_with = "failure_default")]
     scrolling: Scrolling,
 
+    /// Flow control for the output of the pty
+    #[serde(default, deserialize_with = "failure_default")]
+    flow_control: FlowControl,
+
+    /// Width of unicode characters
+    #[serde(default, deserialize_with = "failure_default")]
+    unicode: Unicode,
+
     /// Cursor configuration
     #[serde(default, deserialize_with = "failure_default")]
     cursor: Cursor,
@@ -532,6 +947,10 @@ pub struct Config {
     #[serde(default, deserialize_with = "failure_default")]
     persistent_logging: bool,
 
+    /// Size limit of the log file
+    #[serde(default, deserialize_with = "failure_default")]
+    log_rotation: LogRotation,
+
     /// Enable experimental conpty backend instead of using winpty.
     /// Will only take effect on Windows 10 Oct 2018 and later.
     #[cfg(windows)]
@@ -586,6 +1005,14 @@ where
     deserialize_bindings(deserializer, bindings::default_mouse_bindings())
 }
 
+fn deserialize_deprecated_mouse_bindings<'a, D>(deserializer: D)
+    -> ::std::result::Result<Option<Vec<MouseBinding>>, D::Error>
+where
+    D: de::Deserializer<'a>,
+{
+    deserialize_mouse_bindings(deserializer).map(Some)
+}
+
 fn deserialize_bindings<'a, D, T>(deserializer: D, mut default: Vec<Binding<T>>)
     -> ::std::result::Result<Vec<Binding<T>>, D::Error>
 where
@@ -593,7 +1020,12 @@ where
     T: Copy + Eq + std::hash::Hash + std::fmt::Debug,
     Binding<T>: de::Deserialize<'a>,
 {
-    let mut bindings: Vec<Binding<T>> = failure_default_vec(deserializer)?;
+    let parsed: Vec<Binding<T>> = failure_default_vec(deserializer)?;
+
+    let (mut bindings, warnings) = dedup_bindings(parsed);
+    for warning in warnings {
+        warn!("{}", warning);
+    }
 
     for binding in bindings.iter() {
         default.retain(|b| !b.triggers_match(binding));
@@ -604,6 +1036,46 @@ where
     Ok(bindings)
 }
 
+/// Remove duplicate bindings, returning warnings about them and about conflicting bindings
+fn dedup_bindings<T>(parsed: Vec<Binding<T>>) -> (Vec<Binding<T>>, Vec<String>)
+where
+    T: Copy + Eq + std::hash::Hash + std::fmt::Debug,
+{
+    let mut bindings: Vec<Binding<T>> = Vec::new();
+    let mut warnings = Vec::new();
+    for binding in parsed {
+        // Identical bindings would run the same action twice
+        if bindings.iter().any(|b| b.triggers_match(&binding) && b.action == binding.action) {
+            warnings.push(format!(
+                "Ignoring duplicate binding for {:?}: {:?}",
+                binding.trigger, binding.action
+            ));
+            continue;
+        }
+
+        // All bindings for the same trigger are run, which is usually a mistake. An action
+        // paired with chars is intentional, like Ctrl+L clearing the log notice and the screen.
+        let is_chars = |b: &Binding<T>| match b.action {
+            Action::Esc(_) => true,
+            _ => false,
+        };
+        for other in bindings.iter().filter(|b| b.triggers_match(&binding)) {
+            if is_chars(other) != is_chars(&binding) {
+                continue;
+            }
+
+            warnings.push(format!(
+                "Conflicting bindings for {:?} with {:?}: both {:?} and {:?} are run",
+                binding.trigger, binding.mods, other.action, binding.action
+            ));
+        }
+
+        bindings.push(binding);
+    }
+
+    (bindings, warnings)
+}
+
 fn failure_default_vec<'a, D, T>(deserializer: D) -> ::std::result::Result<Vec<T>, D::Error>
     where D: de::Deserializer<'a>,
           T: Deserialize<'a>
@@ -677,6 +1149,58 @@ fn failure_default<'a, D, T>(deserializer: D)
     }
 }
 
+/// Unicode version used to determine the width of characters
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub enum UnicodeVersion {
+    /// Emojis are a single cell wide
+    Unicode8,
+    /// Emojis with emoji presentation are two cells wide
+    Unicode9,
+}
+
+/// Width of characters with an ambiguous East Asian width
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub enum AmbiguousWidth {
+    Narrow,
+    Wide,
+}
+
+/// Settings for the width of unicode characters
+#[serde(default)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Unicode {
+    #[serde(deserialize_with = "failure_default")]
+    pub version: UnicodeVersion,
+    #[serde(deserialize_with = "failure_default")]
+    pub ambiguous_width: AmbiguousWidth,
+}
+
+impl Default for UnicodeVersion {
+    fn default() -> Self {
+        UnicodeVersion::Unicode9
+    }
+}
+
+impl Default for AmbiguousWidth {
+    fn default() -> Self {
+        AmbiguousWidth::Narrow
+    }
+}
+
+/// Flow control for the output of the pty
+#[serde(default)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct FlowControl {
+    #[serde(deserialize_with = "deserialize_true_bool")]
+    pub auto_throttle: bool,
+}
+
+impl Default for FlowControl {
+    fn default() -> Self {
+        FlowControl { auto_throttle: true }
+    }
+}
+
 /// Struct for scrolling related settings
 #[serde(default)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
@@ -689,6 +1213,8 @@ pub struct Scrolling {
     pub faux_multiplier: u8,
     #[serde(deserialize_with = "failure_default")]
     pub auto_scroll: bool,
+    #[serde(deserialize_with = "failure_default")]
+    pub indicator: ScrollIndicator,
 }
 
 impl Default for Scrolling {
@@ -698,6 +1224,38 @@ impl Default for Scrolling {
             multiplier: default_scrolling_multiplier(),
             faux_multiplier: default_scrolling_multiplier(),
             auto_scroll: Default::default(),
+            indicator: Default::default(),
+        }
+    }
+}
+
+/// Scrollbar shown while the viewport is scrolled into the history
+#[serde(default)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct ScrollIndicator {
+    #[serde(deserialize_with = "deserialize_true_bool")]
+    pub enabled: bool,
+
+    /// Time after the last scroll until the indicator fades out
+    #[serde(deserialize_with = "deserialize_duration_ms")]
+    pub fade_delay: Duration,
+
+    /// Color of the thumb, the foreground color by default
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    pub thumb: Option<Rgb>,
+
+    /// Color of the track, which isn't drawn by default
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    pub track: Option<Rgb>,
+}
+
+impl Default for ScrollIndicator {
+    fn default() -> Self {
+        ScrollIndicator {
+            enabled: true,
+            fade_delay: Duration::from_millis(1000),
+            thumb: None,
+            track: None,
         }
     }
 }
@@ -746,104 +1304,473 @@ fn deserialize_scrolling_multiplier<'a, D>(deserializer: D) -> ::std::result::Re
     }
 }
 
-/// Newtype for implementing deserialize on glutin Mods
-///
-/// Our deserialize impl wouldn't be covered by a derive(Deserialize); see the
-/// impl below.
-#[derive(Debug, Copy, Clone, Hash, Default, Eq, PartialEq)]
-struct ModsWrapper(ModifiersState);
+/// Newtype for implementing deserialize on glutin Mods
+///
+/// Our deserialize impl wouldn't be covered by a derive(Deserialize); see the
+/// impl below.
+#[derive(Debug, Copy, Clone, Hash, Default, Eq, PartialEq)]
+struct ModsWrapper(ModifiersState);
+
+impl ModsWrapper {
+    fn into_inner(self) -> ModifiersState {
+        self.0
+    }
+}
+
+impl<'a> de::Deserialize<'a> for ModsWrapper {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+        where D: de::Deserializer<'a>
+    {
+        struct ModsVisitor;
+
+        impl<'a> Visitor<'a> for ModsVisitor {
+            type Value = ModsWrapper;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("Some subset of Command|Shift|Super|Alt|Option|Control")
+            }
+
+            fn visit_str<E>(self, value: &str) -> ::std::result::Result<ModsWrapper, E>
+                where E: de::Error,
+            {
+                let mut res = ModifiersState::default();
+                for modifier in value.split('|') {
+                    match modifier.trim() {
+                        "Command" | "Super" => res.logo = true,
+                        "Shift" => res.shift = true,
+                        "Alt" | "Option" => res.alt = true,
+                        "Control" => res.ctrl = true,
+                        "None" => (),
+                        _ => return Err(E::invalid_value(Unexpected::Str(modifier), &self)),
+                    }
+                }
+
+                Ok(ModsWrapper(res))
+            }
+        }
+
+        deserializer.deserialize_str(ModsVisitor)
+    }
+}
+
+struct ActionWrapper(crate::input::Action);
+
+impl ActionWrapper {
+    fn into_inner(self) -> crate::input::Action {
+        self.0
+    }
+}
+
+impl<'a> de::Deserialize<'a> for ActionWrapper {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+        where D: de::Deserializer<'a>
+    {
+        struct ActionVisitor;
+
+        impl<'a> Visitor<'a> for ActionVisitor {
+            type Value = ActionWrapper;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("Paste, Copy, PasteSelection, IncreaseFontSize, DecreaseFontSize, \
+                            ResetFontSize, IncreaseBackgroundOpacity, DecreaseBackgroundOpacity, \
+                            ResetBackgroundOpacity, ScrollPageUp, ScrollPageDown, ScrollToTop, \
+                            ScrollToBottom, ClearHistory, Hide, ToggleFullscreen, \
+                            ToggleSimpleFullscreen, ToggleDecorations, ToggleAlwaysOnTop, \
+                            ToggleSticky, ClearLogNotice, SpawnNewInstance, \
+                            CreateNewWindow, DumpScreen, DumpHistory, DumpSelection, \
+                            Screenshot, ScreenshotSelection, ToggleOutputPause, \
+                            PlaybackFaster, PlaybackSlower, PlaybackForward, PlaybackBackward, \
+                            CycleColorScheme, ToggleBookmark, PreviousBookmark, NextBookmark, \
+                            ToggleForcedEffects, ToggleEffectTuning, TogglePerformanceHud, \
+                            None, Quit, \
+                            { SendText: text }, { RunCommand: command }, \
+                            { PasteFiltered: filter } or { SetColorScheme: name }")
+            }
+
+            fn visit_str<E>(self, value: &str) -> ::std::result::Result<ActionWrapper, E>
+                where E: de::Error,
+            {
+                Ok(ActionWrapper(match value {
+                    "Paste" => Action::Paste,
+                    "Copy" => Action::Copy,
+                    "PasteSelection" => Action::PasteSelection,
+                    "IncreaseFontSize" => Action::IncreaseFontSize,
+                    "DecreaseFontSize" => Action::DecreaseFontSize,
+                    "ResetFontSize" => Action::ResetFontSize,
+                    "IncreaseBackgroundOpacity" => Action::IncreaseBackgroundOpacity,
+                    "DecreaseBackgroundOpacity" => Action::DecreaseBackgroundOpacity,
+                    "ResetBackgroundOpacity" => Action::ResetBackgroundOpacity,
+                    "ScrollPageUp" => Action::ScrollPageUp,
+                    "ScrollPageDown" => Action::ScrollPageDown,
+                    "ScrollToTop" => Action::ScrollToTop,
+                    "ScrollToBottom" => Action::ScrollToBottom,
+                    "ClearHistory" => Action::ClearHistory,
+                    "Hide" => Action::Hide,
+                    "ToggleFullscreen" => Action::ToggleFullscreen,
+                    "ToggleSimpleFullscreen" => Action::ToggleSimpleFullscreen,
+                    "ToggleDecorations" => Action::ToggleDecorations,
+                    "ToggleAlwaysOnTop" => Action::ToggleAlwaysOnTop,
+                    "ToggleSticky" => Action::ToggleSticky,
+                    "Quit" => Action::Quit,
+                    "ClearLogNotice" => Action::ClearLogNotice,
+                    "SpawnNewInstance" => Action::SpawnNewInstance,
+                    "CreateNewWindow" => Action::CreateNewWindow,
+                    "DumpScreen" => Action::DumpScreen,
+                    "DumpHistory" => Action::DumpHistory,
+                    "DumpSelection" => Action::DumpSelection,
+                    "Screenshot" => Action::Screenshot,
+                    "ScreenshotSelection" => Action::ScreenshotSelection,
+                    "ToggleOutputPause" => Action::ToggleOutputPause,
+                    "PlaybackFaster" => Action::PlaybackFaster,
+                    "PlaybackSlower" => Action::PlaybackSlower,
+                    "PlaybackForward" => Action::PlaybackForward,
+                    "PlaybackBackward" => Action::PlaybackBackward,
+                    "CycleColorScheme" => Action::CycleColorScheme,
+                    "ToggleBookmark" => Action::ToggleBookmark,
+                    "PreviousBookmark" => Action::PreviousBookmark,
+                    "NextBookmark" => Action::NextBookmark,
+                    "ToggleForcedEffects" => Action::ToggleForcedEffects,
+                    "ToggleEffectTuning" => Action::ToggleEffectTuning,
+                    "TogglePerformanceHud" => Action::TogglePerformanceHud,
+                    "None" => Action::None,
+                    "SendText" | "RunCommand" | "PasteFiltered" | "SetColorScheme" => {
+                        return Err(E::custom(format!("action {} requires a parameter", value)));
+                    },
+                    _ => return Err(E::invalid_value(Unexpected::Str(value), &self)),
+                }))
+            }
+
+            /// Actions with a parameter, like `{ SendText: "text" }`
+            fn visit_map<V>(self, mut map: V) -> ::std::result::Result<ActionWrapper, V::Error>
+                where V: MapAccess<'a>,
+            {
+                use ::serde::de::Error;
+
+                let name = match map.next_key::<String>()? {
+                    Some(name) => name,
+                    None => return Err(V::Error::invalid_length(0, &self)),
+                };
+
+                let action = match name.as_str() {
+                    "SendText" => Action::Esc(map.next_value()?),
+                    "RunCommand" => map.next_value::<CommandWrapper>()?.into(),
+                    "PasteFiltered" => Action::PasteFiltered(map.next_value()?),
+                    "SetColorScheme" => Action::SetColorScheme(map.next_value()?),
+                    _ => {
+                        // Report unknown actions like those without parameters
+                        ActionVisitor.visit_str::<V::Error>(&name)?;
+                        let msg = format!("action {} doesn't take a parameter", name);
+                        return Err(V::Error::custom(msg));
+                    },
+                };
+
+                if map.next_key::<String>()?.is_some() {
+                    return Err(V::Error::custom("only a single action can be specified"));
+                }
+
+                Ok(ActionWrapper(action))
+            }
+        }
+        deserializer.deserialize_any(ActionVisitor)
+    }
+}
+
+/// Rotation of the log file once it grows too large
+#[serde(default)]
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct LogRotation {
+    /// Size in MiB at which the log file is rotated, 0 disables the rotation
+    #[serde(deserialize_with = "failure_default")]
+    pub max_size: u32,
+
+    /// Number of rotated log files which are kept
+    #[serde(deserialize_with = "failure_default")]
+    pub files: u8,
+}
+
+impl Default for LogRotation {
+    fn default() -> LogRotation {
+        LogRotation {
+            max_size: 10,
+            files: 3,
+        }
+    }
+}
+
+/// Commands and behavior for when the shell exits
+#[serde(default)]
+#[derive(Default, Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Hooks {
+    /// Command run when the shell exits, with the exit status in `ALACRITTY_EXIT_STATUS`
+    #[serde(deserialize_with = "failure_default")]
+    pub on_child_exit: Option<CommandWrapper>,
+
+    /// Start a new shell in the same window instead of exiting
+    #[serde(deserialize_with = "failure_default")]
+    pub respawn: bool,
+
+    /// Keep the window open until a key is pressed, unless the shell is respawned
+    #[serde(deserialize_with = "failure_default")]
+    pub hold: bool,
+}
+
+/// Transformations of the clipboard contents pasted with the `PasteFiltered` action
+///
+/// The transformations are applied in the order of the fields.
+#[serde(default, deny_unknown_fields)]
+#[derive(Default, Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct PasteFilter {
+    /// Remove escape sequences, like colors copied from another terminal
+    pub strip_escapes: bool,
+
+    /// Replace every run of whitespace, including line breaks, with a single space
+    pub collapse_whitespace: bool,
+
+    /// Program which reads the text from stdin and writes the text to paste to stdout
+    pub command: Option<CommandWrapper>,
+}
+
+/// Actions when the bell rings, in addition to the visual bell
+#[serde(default)]
+#[derive(Default, Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Bell {
+    /// Command run when the bell rings
+    #[serde(deserialize_with = "failure_default")]
+    pub command: Option<CommandWrapper>,
+}
+
+/// Desktop notifications shown while the window is unfocused
+#[serde(default)]
+#[derive(Default, Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Notifications {
+    /// Notify when the bell rings
+    #[serde(deserialize_with = "failure_default")]
+    pub bell: bool,
+
+    /// Notify when a command which ran for at least this many seconds finishes, 0 disables it
+    #[serde(deserialize_with = "failure_default")]
+    command_threshold: u64,
+}
+
+impl Notifications {
+    /// Minimum runtime of commands which trigger a notification, `None` if disabled
+    #[inline]
+    pub fn command_threshold(&self) -> Option<Duration> {
+        if self.command_threshold == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(self.command_threshold))
+        }
+    }
+}
+
+/// Color which identifies the window, drawn as a border and mixed into the background
+#[serde(default)]
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Accent {
+    /// Accent color, applications can also set it with an escape sequence
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    pub color: Option<Rgb>,
+
+    /// Width of the border in pixels
+    #[serde(deserialize_with = "failure_default")]
+    pub border: u16,
+
+    /// Amount of the accent color mixed into the background
+    #[serde(deserialize_with = "failure_default")]
+    pub background: Alpha,
+}
+
+impl Default for Accent {
+    fn default() -> Accent {
+        Accent {
+            color: None,
+            border: 2,
+            background: Alpha::new(0.),
+        }
+    }
+}
+
+/// Named set of colors which replaces the `colors` section while it is selected
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct ColorScheme {
+    pub name: String,
 
-impl ModsWrapper {
-    fn into_inner(self) -> ModifiersState {
-        self.0
-    }
+    #[serde(flatten)]
+    pub colors: Colors,
 }
 
-impl<'a> de::Deserialize<'a> for ModsWrapper {
-    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
-        where D: de::Deserializer<'a>
-    {
-        struct ModsVisitor;
+/// Colors which replace the configured ones while a matching process runs in the foreground
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// Name of the foreground process, `*` matches any number of characters
+    pub process: String,
 
-        impl<'a> Visitor<'a> for ModsVisitor {
-            type Value = ModsWrapper;
+    #[serde(default, deserialize_with = "deserialize_optional_color")]
+    pub background: Option<Rgb>,
 
-            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                f.write_str("Some subset of Command|Shift|Super|Alt|Option|Control")
-            }
+    #[serde(default, deserialize_with = "deserialize_optional_color")]
+    pub foreground: Option<Rgb>,
 
-            fn visit_str<E>(self, value: &str) -> ::std::result::Result<ModsWrapper, E>
-                where E: de::Error,
-            {
-                let mut res = ModifiersState::default();
-                for modifier in value.split('|') {
-                    match modifier.trim() {
-                        "Command" | "Super" => res.logo = true,
-                        "Shift" => res.shift = true,
-                        "Alt" | "Option" => res.alt = true,
-                        "Control" => res.ctrl = true,
-                        "None" => (),
-                        _ => error!("Unknown modifier {:?}", modifier),
-                    }
-                }
+    #[serde(default, deserialize_with = "deserialize_optional_color")]
+    pub accent: Option<Rgb>,
+}
 
-                Ok(ModsWrapper(res))
+impl Profile {
+    /// Check if the profile applies to the process `name`
+    pub fn matches(&self, name: &str) -> bool {
+        let mut parts = self.process.split('*');
+
+        // The first part has to match the start of the name
+        let mut rest = match parts.next() {
+            Some(first) if name.starts_with(first) => &name[first.len()..],
+            _ => return false,
+        };
+
+        let mut last_part = None;
+        for part in parts {
+            if let Some(prev) = last_part {
+                match rest.find(prev) {
+                    Some(index) => rest = &rest[index + prev.len()..],
+                    None => return false,
+                }
             }
+            last_part = Some(part);
         }
 
-        deserializer.deserialize_str(ModsVisitor)
+        // Without wildcards the name has to match exactly, otherwise the last part is the end
+        match last_part {
+            Some(last) => rest.ends_with(last),
+            None => rest.is_empty(),
+        }
     }
 }
 
-struct ActionWrapper(crate::input::Action);
+/// OpenGL version the renderer is created for
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub enum RendererBackend {
+    /// OpenGL 3.3, falling back to OpenGL ES 2.0 when it isn't supported
+    Auto,
+    /// OpenGL 3.3 core
+    Gl3,
+    /// OpenGL ES 2.0
+    Gles2,
+    /// OpenGL 3.3 drawn by the CPU rasterizer of the OpenGL implementation
+    Software,
+}
 
-impl ActionWrapper {
-    fn into_inner(self) -> crate::input::Action {
-        self.0
+impl RendererBackend {
+    /// Parse the name used on the command line, which is the lowercase variant
+    pub fn from_name(name: &str) -> Option<RendererBackend> {
+        match name {
+            "auto" => Some(RendererBackend::Auto),
+            "gl3" => Some(RendererBackend::Gl3),
+            "gles2" => Some(RendererBackend::Gles2),
+            "software" => Some(RendererBackend::Software),
+            _ => None,
+        }
     }
 }
 
-impl<'a> de::Deserialize<'a> for ActionWrapper {
-    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
-        where D: de::Deserializer<'a>
-    {
-        struct ActionVisitor;
+impl Default for RendererBackend {
+    fn default() -> RendererBackend {
+        RendererBackend::Auto
+    }
+}
 
-        impl<'a> Visitor<'a> for ActionVisitor {
-            type Value = ActionWrapper;
+/// Marker for lines of the command line which contain pasted text
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub enum PasteMarker {
+    None,
+    /// Underline the pasted text
+    Underline,
+    /// Draw a tick at the left edge of the window
+    Gutter,
+}
 
-            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                f.write_str("Paste, Copy, PasteSelection, IncreaseFontSize, DecreaseFontSize, \
-                            ResetFontSize, ScrollPageUp, ScrollPageDown, ScrollToTop, \
-                            ScrollToBottom, ClearHistory, Hide, ClearLogNotice, SpawnNewInstance, \
-                            None or Quit")
-            }
+impl Default for PasteMarker {
+    fn default() -> PasteMarker {
+        PasteMarker::Gutter
+    }
+}
 
-            fn visit_str<E>(self, value: &str) -> ::std::result::Result<ActionWrapper, E>
-                where E: de::Error,
-            {
-                Ok(ActionWrapper(match value {
-                    "Paste" => Action::Paste,
-                    "Copy" => Action::Copy,
-                    "PasteSelection" => Action::PasteSelection,
-                    "IncreaseFontSize" => Action::IncreaseFontSize,
-                    "DecreaseFontSize" => Action::DecreaseFontSize,
-                    "ResetFontSize" => Action::ResetFontSize,
-                    "ScrollPageUp" => Action::ScrollPageUp,
-                    "ScrollPageDown" => Action::ScrollPageDown,
-                    "ScrollToTop" => Action::ScrollToTop,
-                    "ScrollToBottom" => Action::ScrollToBottom,
-                    "ClearHistory" => Action::ClearHistory,
-                    "Hide" => Action::Hide,
-                    "Quit" => Action::Quit,
-                    "ClearLogNotice" => Action::ClearLogNotice,
-                    "SpawnNewInstance" => Action::SpawnNewInstance,
-                    "None" => Action::None,
-                    _ => return Err(E::invalid_value(Unexpected::Str(value), &self)),
-                }))
-            }
+/// Destination of the `DumpScreen`, `DumpHistory` and `DumpSelection` actions
+#[serde(default)]
+#[derive(Default, Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Dump {
+    /// Keep colors and text attributes as escape sequences
+    #[serde(deserialize_with = "failure_default")]
+    pub format: DumpFormat,
+
+    /// Command which receives the text on its stdin, instead of writing it to a temporary file
+    #[serde(deserialize_with = "failure_default")]
+    pub command: Option<CommandWrapper>,
+
+    /// Copy the text to the clipboard, instead of writing it to a temporary file
+    #[serde(deserialize_with = "failure_default")]
+    pub clipboard: bool,
+}
+
+/// Screenshots saved by the `Screenshot` actions and the `OSC 1339` escape sequence
+#[serde(default)]
+#[derive(Default, Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    /// Directory of the PNG images, the temporary directory by default
+    #[serde(deserialize_with = "failure_default")]
+    directory: Option<PathBuf>,
+
+    /// Allow applications to take screenshots with an escape sequence
+    #[serde(deserialize_with = "failure_default")]
+    pub escape_sequence: bool,
+}
+
+impl Screenshot {
+    /// Directory of the PNG images
+    pub fn directory(&self) -> PathBuf {
+        self.directory.clone().unwrap_or_else(env::temp_dir)
+    }
+}
+
+/// Banner typed into the first window at startup, before the shell prints its prompt
+#[serde(default)]
+#[derive(Default, Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct StartupBanner {
+    #[serde(deserialize_with = "failure_default")]
+    enabled: bool,
+
+    /// Text of the banner, the version of Alacritty by default
+    #[serde(deserialize_with = "failure_default")]
+    text: Option<String>,
+}
+
+impl StartupBanner {
+    /// Text to type, `None` if the banner is disabled
+    pub fn text(&self) -> Option<String> {
+        if !self.enabled {
+            return None;
         }
-        deserializer.deserialize_str(ActionVisitor)
+
+        let version = || format!("Alacritty {}", env!("CARGO_PKG_VERSION"));
+        Some(self.text.clone().unwrap_or_else(version))
+    }
+}
+
+/// Format of terminal dumps
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// Only the text
+    Plain,
+    /// Text with SGR escape sequences for colors and text attributes
+    Ansi,
+    /// SVG image, drawn with the normal font
+    Svg,
+    /// Standalone HTML document with inline styles
+    Html,
+}
+
+impl Default for DumpFormat {
+    fn default() -> Self {
+        DumpFormat::Plain
     }
 }
 
@@ -874,6 +1801,15 @@ impl CommandWrapper {
     }
 }
 
+impl From<CommandWrapper> for Action {
+    fn from(command: CommandWrapper) -> Action {
+        match command {
+            CommandWrapper::Just(program) => Action::Command(program, vec![]),
+            CommandWrapper::WithArgs { program, args } => Action::Command(program, args),
+        }
+    }
+}
+
 use crate::term::{mode, TermMode};
 
 struct ModeWrapper {
@@ -891,7 +1827,9 @@ impl<'a> de::Deserialize<'a> for ModeWrapper {
             type Value = ModeWrapper;
 
             fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                f.write_str("Combination of AppCursor | AppKeypad, possibly with negation (~)")
+                f.write_str(
+                    "Combination of AppCursor | AppKeypad | AltScreen, possibly with negation (~)",
+                )
             }
 
             fn visit_str<E>(self, value: &str) -> ::std::result::Result<ModeWrapper, E>
@@ -908,10 +1846,17 @@ impl<'a> de::Deserialize<'a> for ModeWrapper {
                         "~AppCursor" => res.not_mode |= mode::TermMode::APP_CURSOR,
                         "AppKeypad" => res.mode |= mode::TermMode::APP_KEYPAD,
                         "~AppKeypad" => res.not_mode |= mode::TermMode::APP_KEYPAD,
-                        _ => error!("Unknown mode {:?}", modifier),
+                        "AltScreen" => res.mode |= mode::TermMode::ALT_SCREEN,
+                        "~AltScreen" => res.not_mode |= mode::TermMode::ALT_SCREEN,
+                        _ => return Err(E::invalid_value(Unexpected::Str(modifier), &self)),
                     }
                 }
 
+                // A binding which requires and excludes the same mode could never be triggered
+                if res.mode.intersects(res.not_mode) {
+                    return Err(E::custom(format!("mode {:?} is required and excluded", value)));
+                }
+
                 Ok(res)
             }
         }
@@ -919,6 +1864,14 @@ impl<'a> de::Deserialize<'a> for ModeWrapper {
     }
 }
 
+/// Numbers of the back and forward side buttons, which differ between platforms
+#[cfg(not(any(target_os = "macos", windows)))]
+const SIDE_BUTTONS: (u8, u8) = (8, 9);
+#[cfg(target_os = "macos")]
+const SIDE_BUTTONS: (u8, u8) = (3, 4);
+#[cfg(windows)]
+const SIDE_BUTTONS: (u8, u8) = (1, 2);
+
 struct MouseButton(::glutin::MouseButton);
 
 impl MouseButton {
@@ -937,16 +1890,19 @@ impl<'a> de::Deserialize<'a> for MouseButton {
             type Value = MouseButton;
 
             fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                f.write_str("Left, Right, Middle, or a number")
+                f.write_str("Left, Right, Middle, Back, Forward, or a number")
             }
 
             fn visit_str<E>(self, value: &str) -> ::std::result::Result<MouseButton, E>
                 where E: de::Error,
             {
+                let (back, forward) = SIDE_BUTTONS;
                 match value {
                     "Left" => Ok(MouseButton(::glutin::MouseButton::Left)),
                     "Right" => Ok(MouseButton(::glutin::MouseButton::Right)),
                     "Middle" => Ok(MouseButton(::glutin::MouseButton::Middle)),
+                    "Back" => Ok(MouseButton(::glutin::MouseButton::Other(back))),
+                    "Forward" => Ok(MouseButton(::glutin::MouseButton::Other(forward))),
                     _ => {
                         if let Ok(index) = u8::from_str(value) {
                             Ok(MouseButton(::glutin::MouseButton::Other(index)))
@@ -968,6 +1924,7 @@ impl<'a> de::Deserialize<'a> for MouseButton {
 struct RawBinding {
     key: Option<Key>,
     mouse: Option<::glutin::MouseButton>,
+    clicks: Option<u8>,
     mods: ModifiersState,
     mode: TermMode,
     notmode: TermMode,
@@ -976,9 +1933,9 @@ struct RawBinding {
 
 impl RawBinding {
     fn into_mouse_binding(self) -> ::std::result::Result<MouseBinding, Self> {
-        if let Some(mouse) = self.mouse {
+        if let Some(button) = self.mouse {
             Ok(Binding {
-                trigger: mouse,
+                trigger: MouseTrigger { button, clicks: self.clicks },
                 mods: self.mods,
                 action: self.action,
                 mode: self.mode,
@@ -1015,6 +1972,7 @@ impl<'a> de::Deserialize<'a> for RawBinding {
             Action,
             Chars,
             Mouse,
+            Clicks,
             Command,
         }
 
@@ -1025,7 +1983,7 @@ impl<'a> de::Deserialize<'a> for RawBinding {
                 struct FieldVisitor;
 
                 static FIELDS: &'static [&'static str] = &[
-                        "key", "mods", "mode", "action", "chars", "mouse", "command",
+                        "key", "mods", "mode", "action", "chars", "mouse", "clicks", "command",
                 ];
 
                 impl<'a> Visitor<'a> for FieldVisitor {
@@ -1045,6 +2003,7 @@ impl<'a> de::Deserialize<'a> for RawBinding {
                             "action" => Ok(Field::Action),
                             "chars" => Ok(Field::Chars),
                             "mouse" => Ok(Field::Mouse),
+                            "clicks" => Ok(Field::Clicks),
                             "command" => Ok(Field::Command),
                             _ => Err(E::unknown_field(value, FIELDS)),
                         }
@@ -1076,6 +2035,7 @@ impl<'a> de::Deserialize<'a> for RawBinding {
                 let mut mode: Option<TermMode> = None;
                 let mut not_mode: Option<TermMode> = None;
                 let mut mouse: Option<::glutin::MouseButton> = None;
+                let mut clicks: Option<u8> = None;
                 let mut command: Option<CommandWrapper> = None;
 
                 use ::serde::de::Error;
@@ -1134,12 +2094,24 @@ impl<'a> de::Deserialize<'a> for RawBinding {
                             chars = Some(map.next_value()?);
                         },
                         Field::Mouse => {
-                            if chars.is_some() {
+                            if mouse.is_some() {
                                 return Err(<V::Error as Error>::duplicate_field("mouse"));
                             }
 
                             mouse = Some(map.next_value::<MouseButton>()?.into_inner());
                         },
+                        Field::Clicks => {
+                            if clicks.is_some() {
+                                return Err(<V::Error as Error>::duplicate_field("clicks"));
+                            }
+
+                            match map.next_value::<u8>()? {
+                                count @ 1..=3 => clicks = Some(count),
+                                count => return Err(V::Error::custom(format!(
+                                    "clicks must be 1, 2 or 3, got {}", count
+                                ))),
+                            }
+                        },
                         Field::Command => {
                             if command.is_some() {
                                 return Err(<V::Error as Error>::duplicate_field("command"));
@@ -1153,16 +2125,7 @@ impl<'a> de::Deserialize<'a> for RawBinding {
                 let action = match (action, chars, command) {
                     (Some(action), None, None) => action,
                     (None, Some(chars), None) => Action::Esc(chars),
-                    (None, None, Some(cmd)) => {
-                        match cmd {
-                            CommandWrapper::Just(program) => {
-                                Action::Command(program, vec![])
-                            },
-                            CommandWrapper::WithArgs { program, args } => {
-                                Action::Command(program, args)
-                            },
-                        }
-                    },
+                    (None, None, Some(cmd)) => cmd.into(),
                     (None, None, None) => return Err(V::Error::custom("must specify chars, action or command")),
                     _ => return Err(V::Error::custom("must specify only chars, action or command")),
                 };
@@ -1171,8 +2134,18 @@ impl<'a> de::Deserialize<'a> for RawBinding {
                 let not_mode = not_mode.unwrap_or_else(TermMode::empty);
                 let mods = mods.unwrap_or_else(ModifiersState::default);
 
-                if mouse.is_none() && key.is_none() {
-                    return Err(V::Error::custom("bindings require mouse button or key"));
+                match (&key, &mouse) {
+                    (None, None) => {
+                        return Err(V::Error::custom("bindings require mouse button or key"));
+                    },
+                    (Some(_), Some(_)) => {
+                        return Err(V::Error::custom("bindings can't have a mouse button and a key"));
+                    },
+                    _ => (),
+                }
+
+                if key.is_some() && clicks.is_some() {
+                    return Err(V::Error::custom("clicks are only supported by mouse bindings"));
                 }
 
                 Ok(RawBinding {
@@ -1181,13 +2154,14 @@ impl<'a> de::Deserialize<'a> for RawBinding {
                     action,
                     key,
                     mouse,
+                    clicks,
                     mods,
                 })
             }
         }
 
         const FIELDS: &[&str] = &[
-            "key", "mods", "mode", "action", "chars", "mouse", "command",
+            "key", "mods", "mode", "action", "chars", "mouse", "clicks", "command",
         ];
 
         deserializer.deserialize_struct("RawBinding", FIELDS, RawBindingVisitor)
@@ -1241,15 +2215,23 @@ pub enum Error {
 
     /// Not valid yaml or missing parameters
     Yaml(serde_yaml::Error),
+
+    /// Not valid toml
+    Toml(toml::de::Error),
+
+    /// Config option which is not of the form `key.path=value`
+    InvalidOption(String),
 }
 
 #[serde(default)]
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Deserialize, PartialEq)]
 pub struct Colors {
     #[serde(deserialize_with = "failure_default")]
     pub primary: PrimaryColors,
     #[serde(deserialize_with = "failure_default")]
     pub cursor: CursorColors,
+    #[serde(deserialize_with = "failure_default")]
+    pub selection: SelectionColors,
     #[serde(deserialize_with = "deserialize_normal_colors")]
     pub normal: AnsiColors,
     #[serde(deserialize_with = "deserialize_bright_colors")]
@@ -1258,6 +2240,18 @@ pub struct Colors {
     pub dim: Option<AnsiColors>,
     #[serde(deserialize_with = "failure_default_vec")]
     pub indexed_colors: Vec<IndexedColor>,
+    /// Allow applications to change colors with escape sequences
+    #[serde(deserialize_with = "deserialize_true_bool")]
+    pub dynamic_colors: bool,
+    /// Color scheme used while the OS has a light appearance
+    #[serde(deserialize_with = "failure_default")]
+    pub light: Option<String>,
+    /// Color scheme used while the OS has a dark appearance
+    #[serde(deserialize_with = "failure_default")]
+    pub dark: Option<String>,
+    /// Factor for all cell colors while the window is unfocused
+    #[serde(deserialize_with = "failure_default")]
+    pub dim_on_unfocus: Alpha,
 }
 
 impl Default for Colors {
@@ -1265,14 +2259,27 @@ impl Default for Colors {
         Colors {
             primary: Default::default(),
             cursor: Default::default(),
+            selection: Default::default(),
             normal: default_normal_colors(),
             bright: default_bright_colors(),
             dim: Default::default(),
             indexed_colors: Default::default(),
+            dynamic_colors: true,
+            light: None,
+            dark: None,
+            dim_on_unfocus: Default::default(),
         }
     }
 }
 
+impl Colors {
+    /// Check if the colors follow the light or dark appearance of the OS
+    #[inline]
+    pub fn follows_appearance(&self) -> bool {
+        self.light.is_some() || self.dark.is_some()
+    }
+}
+
 fn default_normal_colors() -> AnsiColors {
     AnsiColors {
         black: Rgb {r: 0x00, g: 0x00, b: 0x00},
@@ -1360,12 +2367,24 @@ fn deserialize_color_index<'a, D>(deserializer: D) -> ::std::result::Result<u8,
 }
 
 #[serde(default)]
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
 pub struct Cursor {
     #[serde(deserialize_with = "failure_default")]
     pub style: CursorStyle,
     #[serde(deserialize_with = "deserialize_true_bool")]
     pub unfocused_hollow: bool,
+
+    /// Thickness of the beam, underline and hollow block, relative to the cell width
+    #[serde(deserialize_with = "deserialize_cursor_thickness")]
+    pub thickness: f32,
+
+    /// When the cursor blinks
+    #[serde(deserialize_with = "failure_default")]
+    pub blinking: CursorBlinking,
+
+    /// Time the blinking cursor stays visible or hidden in milliseconds
+    #[serde(deserialize_with = "failure_default")]
+    pub blink_interval: u16,
 }
 
 impl Default for Cursor {
@@ -1373,6 +2392,56 @@ impl Default for Cursor {
         Self {
             style: Default::default(),
             unfocused_hollow: true,
+            thickness: default_cursor_thickness(),
+            blinking: Default::default(),
+            blink_interval: 750,
+        }
+    }
+}
+
+fn default_cursor_thickness() -> f32 {
+    0.15
+}
+
+fn deserialize_cursor_thickness<'a, D>(deserializer: D) -> ::std::result::Result<f32, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match f32::deserialize(deserializer) {
+        Ok(thickness) => Ok(thickness.max(0.).min(1.)),
+        Err(err) => {
+            error!("Problem with config: {}; using default value", err);
+            Ok(default_cursor_thickness())
+        },
+    }
+}
+
+/// When the cursor blinks
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+pub enum CursorBlinking {
+    /// Never blink, even when requested by applications
+    Never,
+    /// Only blink when requested by applications
+    Off,
+    /// Blink unless applications request a steady cursor
+    On,
+    /// Always blink, even when applications request a steady cursor
+    Always,
+}
+
+impl Default for CursorBlinking {
+    fn default() -> Self {
+        CursorBlinking::Off
+    }
+}
+
+impl CursorBlinking {
+    /// Check if the cursor blinks, `requested` is the blinking state set by applications
+    pub fn blinks(self, requested: Option<bool>) -> bool {
+        match self {
+            CursorBlinking::Never => false,
+            CursorBlinking::Off => requested.unwrap_or(false),
+            CursorBlinking::On => requested.unwrap_or(true),
+            CursorBlinking::Always => true,
         }
     }
 }
@@ -1386,6 +2455,16 @@ pub struct CursorColors {
     pub cursor: Option<Rgb>,
 }
 
+/// Colors of selected cells, which are inverted when unset
+#[serde(default)]
+#[derive(Debug, Copy, Clone, Default, Deserialize, PartialEq, Eq)]
+pub struct SelectionColors {
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    pub text: Option<Rgb>,
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    pub background: Option<Rgb>,
+}
+
 #[serde(default)]
 #[derive(Debug, Deserialize, PartialEq, Eq)]
 pub struct PrimaryColors {
@@ -1528,10 +2607,11 @@ impl FromStr for Rgb {
 impl ::std::error::Error for Error {
     fn cause(&self) -> Option<&dyn (::std::error::Error)> {
         match *self {
-            Error::NotFound | Error::Empty => None,
+            Error::NotFound | Error::Empty | Error::InvalidOption(_) => None,
             Error::ReadingEnvHome(ref err) => Some(err),
             Error::Io(ref err) => Some(err),
             Error::Yaml(ref err) => Some(err),
+            Error::Toml(ref err) => Some(err),
         }
     }
 
@@ -1542,6 +2622,8 @@ impl ::std::error::Error for Error {
             Error::ReadingEnvHome(ref err) => err.description(),
             Error::Io(ref err) => err.description(),
             Error::Yaml(ref err) => err.description(),
+            Error::Toml(ref err) => err.description(),
+            Error::InvalidOption(_) => "Invalid config option",
         }
     }
 }
@@ -1555,6 +2637,10 @@ impl ::std::fmt::Display for Error {
             },
             Error::Io(ref err) => write!(f, "Error reading config file: {}", err),
             Error::Yaml(ref err) => write!(f, "Problem with config: {}", err),
+            Error::Toml(ref err) => write!(f, "Problem with config: {}", err),
+            Error::InvalidOption(ref option) => {
+                write!(f, "Invalid config option {:?}, expected key.path=value", option)
+            },
         }
     }
 }
@@ -1581,37 +2667,190 @@ impl From<serde_yaml::Error> for Error {
     }
 }
 
+impl From<toml::de::Error> for Error {
+    fn from(val: toml::de::Error) -> Error {
+        Error::Toml(val)
+    }
+}
+
 /// Result from config loading
 pub type Result<T> = ::std::result::Result<T, Error>;
 
+/// Check if the config at `path` is written in TOML instead of YAML
+fn is_toml(path: &Path) -> bool {
+    path.extension().map_or(false, |extension| extension == "toml")
+}
+
+/// Path of an import, relative paths start at the directory of the importing config
+fn resolve_import(import: &str, config_path: &Path) -> PathBuf {
+    if import == "~" || import.starts_with("~/") {
+        if let Some(home) = home_dir() {
+            return home.join(import.trim_start_matches('~').trim_start_matches('/'));
+        }
+    }
+
+    match config_path.parent() {
+        Some(parent) => parent.join(import),
+        None => PathBuf::from(import),
+    }
+}
+
+/// Replace a leading `~` of `path` with the home directory
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(not(windows))]
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(PathBuf::from)
+}
+
+#[cfg(windows)]
+fn home_dir() -> Option<PathBuf> {
+    dirs::home_dir()
+}
+
+/// Merge a `key.path=value` option into a YAML document
+fn merge_option(document: &mut serde_yaml::Value, option: &str) -> Result<()> {
+    let mut parts = option.splitn(2, '=');
+    let (key, value) = match (parts.next(), parts.next()) {
+        (Some(key), Some(value)) if !key.is_empty() => (key, value),
+        _ => return Err(Error::InvalidOption(option.to_owned())),
+    };
+    let value: serde_yaml::Value = serde_yaml::from_str(value)
+        .map_err(|_| Error::InvalidOption(option.to_owned()))?;
+
+    merge_key(document, key, value);
+
+    Ok(())
+}
+
+/// Merge the command line flags which override config options into `document`
+///
+/// The window title and class have no config options, they aren't part of the document.
+fn merge_cli_overrides(document: &mut serde_yaml::Value, options: &Options) {
+    fn merge<T: serde::Serialize>(document: &mut serde_yaml::Value, key: &str, value: T) {
+        if let Ok(value) = serde_yaml::to_value(value) {
+            merge_key(document, key, value);
+        }
+    }
+
+    if let Some(dimensions) = options.dimensions {
+        merge(document, "window.dimensions.columns", dimensions.columns_u32());
+        merge(document, "window.dimensions.lines", dimensions.lines_u32());
+    }
+
+    if options.title.is_some() && !dynamic_title_overridden(&options.config_options) {
+        merge(document, "dynamic_title", false);
+    }
+
+    if let Some(live_config_reload) = options.live_config_reload {
+        merge(document, "live_config_reload", live_config_reload);
+    }
+    if options.persistent_logging {
+        merge(document, "persistent_logging", true);
+    }
+    if options.hold {
+        merge(document, "hooks.hold", true);
+    }
+    if let Some(renderer) = options.renderer {
+        merge(document, "renderer", format!("{:?}", renderer));
+    }
+
+    if let Some(ref command) = options.command {
+        merge(document, "shell.program", command.program());
+        let args: Vec<_> = command.args().iter().map(|arg| arg.to_string_lossy()).collect();
+        merge(document, "shell.args", args);
+    }
+    if let Some(ref working_dir) = options.working_dir {
+        merge(document, "shell.working_directory", working_dir);
+    }
+}
+
+/// Check if `dynamic_title` is set by one of the `config_options`
+fn dynamic_title_overridden(config_options: &[String]) -> bool {
+    config_options
+        .iter()
+        .any(|option| option.split('=').next().map(str::trim) == Some("dynamic_title"))
+}
+
+/// Merge `value` into the node of `document` at the dot separated `key`
+fn merge_key(document: &mut serde_yaml::Value, key: &str, value: serde_yaml::Value) {
+    let mut node = document;
+    for segment in key.split('.') {
+        if !node.is_mapping() {
+            *node = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
+        }
+
+        let mapping = match node {
+            serde_yaml::Value::Mapping(mapping) => mapping,
+            _ => unreachable!(),
+        };
+        let segment = serde_yaml::Value::String(segment.to_owned());
+        if !mapping.contains_key(&segment) {
+            mapping.insert(segment.clone(), serde_yaml::Value::Null);
+        }
+        node = mapping.get_mut(&segment).unwrap();
+    }
+
+    merge_value(node, value);
+}
+
+/// Deep-merge `value` into `node`, replacing everything but mappings
+fn merge_value(node: &mut serde_yaml::Value, value: serde_yaml::Value) {
+    match (node, value) {
+        (serde_yaml::Value::Mapping(node), serde_yaml::Value::Mapping(value)) => {
+            for (key, value) in value {
+                match node.get_mut(&key) {
+                    Some(child) => merge_value(child, value),
+                    None => {
+                        node.insert(key, value);
+                    },
+                }
+            }
+        },
+        (node, value) => *node = value,
+    }
+}
+
 impl Config {
-    /// Get the location of the first found default config file paths
+    /// Get the location of the installed config, preferring `alacritty.toml` over
+    /// `alacritty.yml`
+    pub fn installed_config<'a>() -> Option<Cow<'a, Path>> {
+        Config::installed_config_file("alacritty.toml")
+            .or_else(|| Config::installed_config_file("alacritty.yml"))
+    }
+
+    /// Get the location of the first found config file called `name`
     /// according to the following order:
     ///
-    /// 1. $XDG_CONFIG_HOME/alacritty/alacritty.yml
-    /// 2. $XDG_CONFIG_HOME/alacritty.yml
-    /// 3. $HOME/.config/alacritty/alacritty.yml
-    /// 4. $HOME/.alacritty.yml
+    /// 1. $XDG_CONFIG_HOME/alacritty/`name`
+    /// 2. $XDG_CONFIG_HOME/`name`
+    /// 3. $HOME/.config/alacritty/`name`
+    /// 4. $HOME/.`name`
     #[cfg(not(windows))]
-    pub fn installed_config<'a>() -> Option<Cow<'a, Path>> {
+    pub fn installed_config_file<'a>(name: &str) -> Option<Cow<'a, Path>> {
         // Try using XDG location by default
         ::xdg::BaseDirectories::with_prefix("alacritty")
             .ok()
-            .and_then(|xdg| xdg.find_config_file("alacritty.yml"))
+            .and_then(|xdg| xdg.find_config_file(name))
             .or_else(|| {
                 ::xdg::BaseDirectories::new().ok().and_then(|fallback| {
-                    fallback.find_config_file("alacritty.yml")
+                    fallback.find_config_file(name)
                 })
             })
             .or_else(|| {
                 if let Ok(home) = env::var("HOME") {
-                    // Fallback path: $HOME/.config/alacritty/alacritty.yml
-                    let fallback = PathBuf::from(&home).join(".config/alacritty/alacritty.yml");
+                    // Fallback path: $HOME/.config/alacritty/`name`
+                    let fallback = PathBuf::from(&home).join(".config/alacritty").join(name);
                     if fallback.exists() {
                         return Some(fallback);
                     }
-                    // Fallback path: $HOME/.alacritty.yml
-                    let fallback = PathBuf::from(&home).join(".alacritty.yml");
+                    // Fallback path: $HOME/.`name`
+                    let fallback = PathBuf::from(&home).join(format!(".{}", name));
                     if fallback.exists() {
                         return Some(fallback);
                     }
@@ -1623,11 +2862,11 @@ impl Config {
 
     // TODO: Remove old configuration location warning (Deprecated 03/12/2018)
     #[cfg(windows)]
-    pub fn installed_config<'a>() -> Option<Cow<'a, Path>> {
+    pub fn installed_config_file<'a>(name: &str) -> Option<Cow<'a, Path>> {
         let old = dirs::home_dir()
-            .map(|path| path.join("alacritty.yml"));
+            .map(|path| path.join(name));
         let new = dirs::config_dir()
-            .map(|path| path.join("alacritty\\alacritty.yml"));
+            .map(|path| path.join("alacritty").join(name));
 
         if let Some(old_path) = old.as_ref().filter(|old| old.exists()) {
             warn!(
@@ -1683,12 +2922,30 @@ impl Config {
         self.background_opacity
     }
 
+    /// Window accent color configuration
+    #[inline]
+    pub fn accent(&self) -> &Accent {
+        &self.accent
+    }
+
+    /// Color schemes which can be selected at runtime
+    #[inline]
+    pub fn color_schemes(&self) -> &[ColorScheme] {
+        &self.color_schemes
+    }
+
+    /// Foreground process profiles
+    #[inline]
+    pub fn profiles(&self) -> &[Profile] {
+        &self.profiles
+    }
+
     pub fn key_bindings(&self) -> &[KeyBinding] {
         &self.key_bindings[..]
     }
 
     pub fn mouse_bindings(&self) -> &[MouseBinding] {
-        &self.mouse_bindings[..]
+        self.mouse_bindings.as_ref().unwrap_or(&self.mouse.bindings)
     }
 
     pub fn mouse(&self) -> &Mouse {
@@ -1699,6 +2956,18 @@ impl Config {
         &self.selection
     }
 
+    /// Marker for pasted text on the command line
+    #[inline]
+    pub fn paste_marker(&self) -> PasteMarker {
+        self.paste_marker
+    }
+
+    /// Glyph marking lines which continue a soft-wrapped line
+    #[inline]
+    pub fn wrap_indicator(&self) -> Option<char> {
+        self.wrap_indicator
+    }
+
     pub fn tabspaces(&self) -> usize {
         self.tabspaces
     }
@@ -1737,12 +3006,48 @@ impl Config {
         &self.visual_bell
     }
 
+    /// Characters trailing behind the real ones in the animation
+    #[inline]
+    pub fn trail_style(&self) -> TrailStyle {
+        self.trail_style
+    }
+
+    /// Logical lines which are left alone by effects
+    #[inline]
+    pub fn effect_masks(&self) -> &[EffectMask] {
+        &self.effect_masks
+    }
+
+    /// Times at which effects are active
+    #[inline]
+    pub fn effect_schedule(&self) -> &EffectSchedule {
+        &self.effect_schedule
+    }
+
+    /// Speed, density and colors of the animation
+    #[inline]
+    pub fn effect_style(&self) -> EffectStyle {
+        self.effect_style
+    }
+
+    /// Get blinking text config
+    #[inline]
+    pub fn text_blink(&self) -> &TextBlinkConfig {
+        &self.text_blink
+    }
+
     /// Should show render timer
     #[inline]
     pub fn render_timer(&self) -> bool {
         self.render_timer
     }
 
+    /// OpenGL version used for drawing
+    #[inline]
+    pub fn renderer(&self) -> RendererBackend {
+        self.renderer
+    }
+
     #[cfg(target_os = "macos")]
     #[inline]
     pub fn use_thin_strokes(&self) -> bool {
@@ -1755,6 +3060,11 @@ impl Config {
         false
     }
 
+    /// Files imported by the config
+    pub fn imports(&self) -> &[PathBuf] {
+        &self.imports
+    }
+
     pub fn path(&self) -> Option<&Path> {
         self.config_path
             .as_ref()
@@ -1762,7 +3072,12 @@ impl Config {
     }
 
     pub fn shell(&self) -> Option<&Shell<'_>> {
-        self.shell.as_ref()
+        self.shell.as_ref().filter(|shell| !shell.program.is_empty())
+    }
+
+    /// Directory the shell is started in, when none is passed on the command line
+    pub fn working_directory(&self) -> Option<&Path> {
+        self.shell.as_ref().and_then(|shell| shell.working_directory.as_ref()).map(PathBuf::as_path)
     }
 
     pub fn env(&self) -> &HashMap<String, String> {
@@ -1787,33 +3102,100 @@ impl Config {
         self.unfocused_hollow_cursor.unwrap_or(self.cursor.unfocused_hollow)
     }
 
+    /// Thickness of the beam, underline and hollow block cursors, relative to the cell width
+    #[inline]
+    pub fn cursor_thickness(&self) -> f32 {
+        self.cursor.thickness
+    }
+
+    #[inline]
+    pub fn cursor_blinking(&self) -> CursorBlinking {
+        self.cursor.blinking
+    }
+
+    /// Blink interval of the cursor, `None` when the interval is zero
+    #[inline]
+    pub fn cursor_blink_interval(&self) -> Option<Duration> {
+        match self.cursor.blink_interval {
+            0 => None,
+            interval => Some(Duration::from_millis(u64::from(interval))),
+        }
+    }
+
     /// Live config reload
     #[inline]
     pub fn live_config_reload(&self) -> bool {
         self.live_config_reload
     }
 
+    /// Listen for messages from `alacritty msg`
+    #[inline]
+    pub fn ipc_socket(&self) -> bool {
+        self.ipc_socket
+    }
+
+    /// Shell exit hooks
+    #[inline]
+    pub fn hooks(&self) -> &Hooks {
+        &self.hooks
+    }
+
+    /// Banner typed into the first window at startup
+    #[inline]
+    pub fn startup_banner(&self) -> &StartupBanner {
+        &self.startup_banner
+    }
+
+    /// Output of terminal dumps
+    #[inline]
+    pub fn dump(&self) -> &Dump {
+        &self.dump
+    }
+
+    /// Screenshot configuration
+    #[inline]
+    pub fn screenshot(&self) -> &Screenshot {
+        &self.screenshot
+    }
+
+    /// Bell command configuration
+    #[inline]
+    pub fn bell(&self) -> &Bell {
+        &self.bell
+    }
+
+    /// Desktop notification configuration
+    #[inline]
+    pub fn notifications(&self) -> &Notifications {
+        &self.notifications
+    }
+
     #[inline]
     pub fn dynamic_title(&self) -> bool {
         self.dynamic_title
     }
 
+    #[inline]
+    pub fn report_title(&self) -> bool {
+        self.report_title
+    }
+
     /// Scrolling settings
     #[inline]
     pub fn scrolling(&self) -> Scrolling {
         self.scrolling
     }
 
-    /// Cursor foreground color
+    /// Flow control settings
     #[inline]
-    pub fn cursor_text_color(&self) -> Option<Color> {
-        self.colors.cursor.text.map(|_| Color::Named(NamedColor::CursorText))
+    pub fn flow_control(&self) -> FlowControl {
+        self.flow_control
     }
 
-    /// Cursor background color
+    /// Unicode width settings
     #[inline]
-    pub fn cursor_cursor_color(&self) -> Option<Color> {
-        self.colors.cursor.cursor.map(|_| Color::Named(NamedColor::Cursor))
+    pub fn unicode(&self) -> Unicode {
+        self.unicode
     }
 
     /// Enable experimental conpty backend (Windows only)
@@ -1834,25 +3216,165 @@ impl Config {
         self.scrolling.history = history;
     }
 
+    // Update the window dimensions, used in ref test replays
+    pub fn set_dimensions(&mut self, dimensions: Dimensions) {
+        self.dimensions = Some(dimensions);
+    }
+
     /// Keep the log file after quitting Alacritty
     #[inline]
     pub fn persistent_logging(&self) -> bool {
         self.persistent_logging
     }
 
+    /// Size limit of the log file
+    #[inline]
+    pub fn log_rotation(&self) -> LogRotation {
+        self.log_rotation
+    }
+
     pub fn load_from<P: Into<PathBuf>>(path: P) -> Result<Config> {
         let path = path.into();
-        let raw = Config::read_file(path.as_path())?;
-        let mut config: Config = serde_yaml::from_str(&raw)?;
-        config.config_path = Some(path);
-        config.print_deprecation_warnings();
+        let mut imports = Vec::new();
+        let document = Config::read_document(&path, &mut Vec::new(), &mut imports)?;
+        Config::from_document(document, Some(path), imports)
+    }
+
+    /// Load the config with `key.path=value` options merged over it
+    ///
+    /// Without a path, or with an empty file, the options are merged over the defaults.
+    pub fn load_with_options(path: Option<&Path>, options: &[String]) -> Result<Config> {
+        let mut imports = Vec::new();
+        let document = path.map(|path| Config::read_document(path, &mut Vec::new(), &mut imports));
+        let mut document = match document {
+            Some(Ok(document)) => document,
+            Some(Err(Error::Empty)) | None => serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG)?,
+            Some(Err(err)) => return Err(err),
+        };
+
+        for option in options {
+            merge_option(&mut document, option)?;
+        }
+
+        Config::from_document(document, path.map(Path::to_path_buf), imports)
+    }
+
+    /// Merge the config file, its imports and the command line `options` over the defaults
+    ///
+    /// This is the config printed by `--print-config`. It's deserialized like a loaded config to
+    /// report errors, the warnings about its options are returned with it.
+    pub fn effective_document(
+        path: Option<&Path>,
+        options: &Options,
+    ) -> Result<(serde_yaml::Value, Vec<String>)> {
+        let mut document: serde_yaml::Value = serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG)?;
+        if let Some(path) = path {
+            match Config::read_document(path, &mut Vec::new(), &mut Vec::new()) {
+                Ok(file) => merge_value(&mut document, file),
+                Err(Error::Empty) => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        for option in &options.config_options {
+            merge_option(&mut document, option)?;
+        }
+        merge_cli_overrides(&mut document, options);
+
+        let source = path.and_then(|path| fs::read_to_string(path).ok());
+        let validator = Validator::new(source, path.map_or(false, is_toml));
+        validator.deserialize::<Config>(document.clone())?;
+
+        Ok((document, validator.warnings()))
+    }
+
+    fn from_document(
+        document: serde_yaml::Value,
+        path: Option<PathBuf>,
+        imports: Vec<PathBuf>,
+    ) -> Result<Config> {
+        // Options are found in the file itself, not in its imports
+        let source = path.as_ref().and_then(|path| fs::read_to_string(path).ok());
+        let validator = Validator::new(source, path.as_ref().map_or(false, |path| is_toml(path)));
+        let mut config: Config = validator.deserialize(document)?;
+        for warning in validator.warnings() {
+            warn!("{}", warning);
+        }
+
+        config.config_path = path;
+        config.imports = imports;
+        config.apply_deprecated_options();
 
         Ok(config)
     }
 
-    /// Overrides the `dynamic_title` configuration based on `--title`.
-    pub fn update_dynamic_title(mut self, options: &Options) -> Self {
-        if options.title.is_some() {
+    /// Read a config file and merge it over the files listed in its `import` section
+    ///
+    /// Imports are merged in order, so later imports and the file itself take precedence.
+    /// `stack` holds the files currently being read to break import cycles, every imported
+    /// file is added to `imports`.
+    fn read_document(
+        path: &Path,
+        stack: &mut Vec<PathBuf>,
+        imports: &mut Vec<PathBuf>,
+    ) -> Result<serde_yaml::Value> {
+        let raw = Config::read_file(path)?;
+        let mut document: serde_yaml::Value = if is_toml(path) {
+            toml::from_str(&raw)?
+        } else {
+            serde_yaml::from_str(&raw)?
+        };
+
+        let import_key = serde_yaml::Value::String(String::from("import"));
+        let import_paths = match document.as_mapping_mut().and_then(|m| m.remove(&import_key)) {
+            Some(serde_yaml::Value::Sequence(paths)) => paths,
+            Some(serde_yaml::Value::Null) | None => return Ok(document),
+            Some(_) => {
+                error!("Problem with config {}: import must be a list of paths", path.display());
+                return Ok(document);
+            },
+        };
+
+        stack.push(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+
+        let mut merged = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
+        for import_path in import_paths {
+            let import_path = match import_path.as_str() {
+                Some(import_path) => resolve_import(import_path, path),
+                None => {
+                    error!("Problem with config {}: import must be a path", path.display());
+                    continue;
+                },
+            };
+
+            let canonical = fs::canonicalize(&import_path).unwrap_or_else(|_| import_path.clone());
+            if stack.contains(&canonical) {
+                error!("Ignoring import of {}: import cycle", import_path.display());
+                continue;
+            }
+
+            // Watch imports for changes even while they are broken
+            imports.push(import_path.clone());
+
+            match Config::read_document(&import_path, stack, imports) {
+                Ok(import) => merge_value(&mut merged, import),
+                Err(Error::Empty) => (),
+                Err(err) => error!("Unable to import {}: {}", import_path.display(), err),
+            }
+        }
+
+        stack.pop();
+
+        merge_value(&mut merged, document);
+        Ok(merged)
+    }
+
+    /// Disables dynamic titles when `--title` is passed
+    ///
+    /// Setting `dynamic_title` in `config_options`, with `--option` or `alacritty msg config`,
+    /// takes precedence over the title.
+    pub fn update_dynamic_title(mut self, options: &Options, config_options: &[String]) -> Self {
+        if options.title.is_some() && !dynamic_title_overridden(config_options) {
             self.dynamic_title = false;
         }
         self
@@ -1869,44 +3391,13 @@ impl Config {
         Ok(contents)
     }
 
-    fn print_deprecation_warnings(&mut self) {
-        if self.dimensions.is_some() {
-            warn!("Config dimensions is deprecated; \
-                  please use window.dimensions instead");
-        }
-
-        if self.padding.is_some() {
-            warn!("Config padding is deprecated; \
-                  please use window.padding instead");
-        }
-
-        if self.mouse.faux_scrollback_lines.is_some() {
-            warn!("Config mouse.faux_scrollback_lines is deprecated; \
-                  please use mouse.faux_scrolling_lines instead");
-        }
-
-        if let Some(custom_cursor_colors) = self.custom_cursor_colors {
-            warn!("Config custom_cursor_colors is deprecated");
-
-            if !custom_cursor_colors {
-                self.colors.cursor.cursor = None;
-                self.colors.cursor.text = None;
-            }
-        }
-
-        if self.cursor_style.is_some() {
-            warn!("Config cursor_style is deprecated; \
-                  please use cursor.style instead");
-        }
-
-        if self.hide_cursor_when_typing.is_some() {
-            warn!("Config hide_cursor_when_typing is deprecated; \
-                  please use mouse.hide_when_typing instead");
-        }
-
-        if self.unfocused_hollow_cursor.is_some() {
-            warn!("Config unfocused_hollow_cursor is deprecated; \
-                  please use cursor.unfocused_hollow instead");
+    /// Apply deprecated options which aren't read through their replacement
+    ///
+    /// Warnings for deprecated options are printed by the validator.
+    fn apply_deprecated_options(&mut self) {
+        if self.custom_cursor_colors == Some(false) {
+            self.colors.cursor.cursor = None;
+            self.colors.cursor.text = None;
         }
     }
 }
@@ -2019,7 +3510,7 @@ impl DeserializeSize for Size {
 /// each value independently. Alternatively, maybe erroring when the user
 /// doesn't provide complete config is Ok.
 #[serde(default)]
-#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct Font {
     /// Normal font face
     #[serde(deserialize_with = "failure_default")]
@@ -2045,6 +3536,22 @@ pub struct Font {
     #[serde(deserialize_with = "failure_default")]
     glyph_offset: Delta<i8>,
 
+    /// Draw box drawing, block element and Powerline characters without the font
+    #[serde(deserialize_with = "deserialize_true_bool")]
+    builtin_box_drawing: bool,
+
+    /// Shape text to draw the ligatures of the font
+    #[serde(deserialize_with = "failure_default")]
+    ligatures: bool,
+
+    /// Underline position and thickness, replacing the ones of the font
+    #[serde(deserialize_with = "failure_default")]
+    underline: LineOverrides,
+
+    /// Strikeout position and thickness, replacing the ones of the font
+    #[serde(deserialize_with = "failure_default")]
+    strikeout: LineOverrides,
+
     #[cfg(target_os = "macos")]
     #[serde(deserialize_with = "deserialize_true_bool")]
     use_thin_strokes: bool,
@@ -2066,6 +3573,10 @@ impl Default for Font {
             scale_with_dpi: Default::default(),
             glyph_offset: Default::default(),
             offset: Default::default(),
+            builtin_box_drawing: true,
+            ligatures: Default::default(),
+            underline: Default::default(),
+            strikeout: Default::default(),
         }
     }
 }
@@ -2089,6 +3600,31 @@ impl Font {
         &self.glyph_offset
     }
 
+    /// Should box drawing characters be drawn without the font
+    #[inline]
+    pub fn builtin_box_drawing(&self) -> bool {
+        self.builtin_box_drawing
+    }
+
+    /// Should text be shaped to draw ligatures
+    #[inline]
+    pub fn ligatures(&self) -> bool {
+        self.ligatures
+    }
+
+    /// Replace the underline and strikeout metrics of the font with the configured ones
+    ///
+    /// Sizes in pixels are multiplied by `dpr`, since the metrics are in physical pixels.
+    pub fn apply_line_overrides(&self, metrics: &mut font::Metrics, dpr: f32) {
+        let underline = &self.underline;
+        metrics.underline_position = underline.position(metrics.underline_position, dpr);
+        metrics.underline_thickness = underline.thickness(metrics.underline_thickness, dpr);
+
+        let strikeout = &self.strikeout;
+        metrics.strikeout_position = strikeout.position(metrics.strikeout_position, dpr);
+        metrics.strikeout_thickness = strikeout.thickness(metrics.strikeout_thickness, dpr);
+    }
+
     /// Get a font clone with a size modification
     pub fn with_size(self, size: Size) -> Font {
         Font {
@@ -2097,6 +3633,12 @@ impl Font {
         }
     }
 
+    /// Get a font clone with a different family of the normal font
+    pub fn with_family(mut self, family: &str) -> Font {
+        self.normal.family = family.to_owned();
+        self
+    }
+
     // Get normal font description
     pub fn normal(&self) -> &FontDescription {
         &self.normal
@@ -2113,6 +3655,69 @@ impl Font {
     }
 }
 
+/// Position and thickness of underlines or strikeouts
+#[serde(default)]
+#[derive(Default, Debug, Deserialize, Copy, Clone, PartialEq)]
+pub struct LineOverrides {
+    /// Height of the line
+    #[serde(deserialize_with = "failure_default")]
+    thickness: Option<LineSize>,
+
+    /// Distance of the center of the line above the baseline
+    #[serde(deserialize_with = "failure_default")]
+    position: Option<LineSize>,
+}
+
+impl LineOverrides {
+    #[inline]
+    fn thickness(&self, font_thickness: f32, dpr: f32) -> f32 {
+        self.thickness.map_or(font_thickness, |size| size.apply(font_thickness, dpr))
+    }
+
+    #[inline]
+    fn position(&self, font_position: f32, dpr: f32) -> f32 {
+        self.position.map_or(font_position, |size| size.apply(font_position, dpr))
+    }
+}
+
+/// Size of a line, in logical pixels or relative to the size used by the font
+///
+/// Numbers are pixels, while strings like `1.5x` are multipliers.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LineSize {
+    Pixels(f32),
+    Multiplier(f32),
+}
+
+impl LineSize {
+    #[inline]
+    fn apply(self, font_size: f32, dpr: f32) -> f32 {
+        match self {
+            LineSize::Pixels(pixels) => pixels * dpr,
+            LineSize::Multiplier(multiplier) => font_size * multiplier,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LineSize {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+        where D: de::Deserializer<'de>
+    {
+        match serde_yaml::Value::deserialize(deserializer)? {
+            serde_yaml::Value::Number(number) => number
+                .as_f64()
+                .map(|pixels| LineSize::Pixels(pixels as f32))
+                .ok_or_else(|| D::Error::custom("invalid line size")),
+            serde_yaml::Value::String(ref text) if text.ends_with('x') => text[..text.len() - 1]
+                .trim()
+                .parse()
+                .map(LineSize::Multiplier)
+                .map_err(|_| D::Error::custom(format!("invalid line size multiplier {:?}", text))),
+            _ => Err(D::Error::custom("expected pixels or a multiplier like `1.5x`")),
+        }
+    }
+}
+
 fn default_font_size() -> Size {
     Size::new(11.)
 }
@@ -2196,11 +3801,13 @@ impl Monitor {
 
         config
     }
-    pub fn new<H, P>(path: P, mut handler: H) -> Monitor
+    /// Watch the config at `path` and the files it imports
+    pub fn new<H, P>(path: P, imports: &[PathBuf], mut handler: H) -> Monitor
         where H: OnConfigReload + Send + 'static,
               P: Into<PathBuf>
     {
         let path = path.into();
+        let imports = imports.to_vec();
 
         let (config_tx, config_rx) = mpsc::channel();
 
@@ -2213,27 +3820,35 @@ impl Monitor {
                 let config_path = ::std::fs::canonicalize(path)
                     .expect("canonicalize config path");
 
-                // Get directory of config
-                let mut parent = config_path.clone();
-                parent.pop();
-
-                // Watch directory
-                watcher.watch(&parent, RecursiveMode::NonRecursive)
-                    .expect("watch alacritty.yml dir");
+                // Watch the directories of the config and all imports
+                let mut watched_dirs = Vec::new();
+                let mut files =
+                    watch_files(&mut watcher, &mut watched_dirs, &config_path, &imports);
 
                 loop {
                     match rx.recv().expect("watcher event") {
                         DebouncedEvent::Rename(_, _) => continue,
                         DebouncedEvent::Write(path) | DebouncedEvent::Create(path)
                          | DebouncedEvent::Chmod(path) => {
-                            // Reload file
-                            if path == config_path {
-                                match Config::load_from(path) {
+                            // Reload the config when it or one of its imports changed
+                            if files.contains(&path) {
+                                match Config::load_from(&config_path) {
                                     Ok(config) => {
+                                        files = watch_files(
+                                            &mut watcher,
+                                            &mut watched_dirs,
+                                            &config_path,
+                                            config.imports(),
+                                        );
                                         let _ = config_tx.send(config);
                                         handler.on_config_reload();
                                     },
-                                    Err(err) => error!("Ignoring invalid config: {}", err),
+                                    Err(err) => {
+                                        error!("Ignoring invalid config: {}", err);
+
+                                        // Show the error in the message bar
+                                        handler.on_config_reload();
+                                    },
                                 }
                              }
                         }
@@ -2246,6 +3861,33 @@ impl Monitor {
     }
 }
 
+/// Watch the directories of the config and its imports
+///
+/// Returns the canonical paths of all files which trigger a reload.
+fn watch_files<W: Watcher>(
+    watcher: &mut W,
+    watched_dirs: &mut Vec<PathBuf>,
+    config_path: &Path,
+    imports: &[PathBuf],
+) -> Vec<PathBuf> {
+    let mut files = vec![config_path.to_path_buf()];
+    files.extend(imports.iter().filter_map(|import| fs::canonicalize(import).ok()));
+
+    for file in &files {
+        let parent = match file.parent() {
+            Some(parent) if !watched_dirs.iter().any(|dir| dir == parent) => parent,
+            _ => continue,
+        };
+
+        match watcher.watch(parent, RecursiveMode::NonRecursive) {
+            Ok(()) => watched_dirs.push(parent.to_path_buf()),
+            Err(err) => warn!("Unable to watch {} for changes: {}", parent.display(), err),
+        }
+    }
+
+    files
+}
+
 #[derive(Deserialize, Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Key {
     Scancode(u32),
@@ -2584,8 +4226,23 @@ impl Key {
 
 #[cfg(test)]
 mod tests {
+    use std::{env, fs, process};
+    use std::path::{Path, PathBuf};
+
+    use glutin::ModifiersState;
+    use time;
+
     use crate::cli::Options;
-    use super::{Config, DEFAULT_ALACRITTY_CONFIG};
+    use crate::index::{Column, Line};
+    use crate::input::{Action, KeyBinding, MouseBinding, MouseTrigger};
+    use crate::term::TermMode;
+    use super::{CommandWrapper, Config, Key, PasteFilter, Profile, DEFAULT_ALACRITTY_CONFIG};
+    use super::{dedup_bindings, merge_option, resolve_import, EffectSchedule, SIDE_BUTTONS};
+    use super::{Dimensions, Shell, StartupMode, WindowConfig};
+
+    fn key_binding(yaml: &str) -> Result<KeyBinding, serde_yaml::Error> {
+        serde_yaml::from_str(yaml)
+    }
 
     #[test]
     fn parse_config() {
@@ -2593,7 +4250,7 @@ mod tests {
             .expect("deserialize config");
 
         // Sanity check that mouse bindings are being parsed
-        assert!(!config.mouse_bindings.is_empty());
+        assert!(!config.mouse_bindings().is_empty());
 
         // Sanity check that key bindings are being parsed
         assert!(!config.key_bindings.is_empty());
@@ -2605,7 +4262,7 @@ mod tests {
             .expect("deserialize config");
         let old_dynamic_title = config.dynamic_title;
         let options = Options::default();
-        let config = config.update_dynamic_title(&options);
+        let config = config.update_dynamic_title(&options, &[]);
         assert_eq!(old_dynamic_title, config.dynamic_title);
     }
 
@@ -2615,8 +4272,22 @@ mod tests {
             .expect("deserialize config");
         let mut options = Options::default();
         options.title = Some("foo".to_owned());
-        let config = config.update_dynamic_title(&options);
+        let config = config.update_dynamic_title(&options, &[]);
+        assert!(!config.dynamic_title);
+    }
+
+    #[test]
+    fn dynamic_title_option_overrides_title() {
+        let config = Config::load_with_options(None, &[String::from("dynamic_title=true")])
+            .expect("deserialize config");
+        let mut options = Options::default();
+        options.title = Some("foo".to_owned());
+        let config = config.update_dynamic_title(&options, &options.config_options);
         assert!(!config.dynamic_title);
+
+        options.config_options = vec![String::from("dynamic_title=true")];
+        let config = Config::default().update_dynamic_title(&options, &options.config_options);
+        assert!(config.dynamic_title);
     }
 
     #[test]
@@ -2627,4 +4298,376 @@ mod tests {
 
         assert_eq!(default, empty);
     }
+
+    #[test]
+    fn profile_process_patterns() {
+        let profile = |process: &str| Profile {
+            process: process.into(),
+            background: None,
+            foreground: None,
+            accent: None,
+        };
+
+        assert!(profile("ssh").matches("ssh"));
+        assert!(!profile("ssh").matches("sshd"));
+        assert!(profile("ssh*").matches("sshd"));
+        assert!(profile("*vim").matches("nvim"));
+        assert!(profile("m*sql*").matches("mysql-client"));
+        assert!(!profile("m*sql*").matches("psql"));
+    }
+
+    #[test]
+    fn effect_schedule() {
+        let schedule = |yaml| serde_yaml::from_str::<Config>(yaml).unwrap().effect_schedule;
+        let time = |hour, wday, mon| time::Tm {
+            tm_hour: hour,
+            tm_wday: wday,
+            tm_mon: mon,
+            ..time::empty_tm()
+        };
+
+        // Evenings, wrapping past midnight
+        let evenings = schedule("effect_schedule: { start_hour: 18, end_hour: 2 }\n");
+        assert!(evenings.is_active(&time(23, 0, 0)));
+        assert!(evenings.is_active(&time(1, 0, 0)));
+        assert!(!evenings.is_active(&time(2, 0, 0)));
+        assert!(!evenings.is_active(&time(12, 0, 0)));
+
+        let weekends =
+            schedule("effect_schedule: { weekdays: [Saturday, Sunday], months: [12] }\n");
+        assert!(weekends.is_active(&time(12, 6, 11)));
+        assert!(weekends.is_active(&time(12, 0, 11)));
+        assert!(!weekends.is_active(&time(12, 1, 11)));
+        assert!(!weekends.is_active(&time(12, 6, 10)));
+
+        assert!(EffectSchedule::default().is_active(&time(0, 0, 0)));
+    }
+
+    #[test]
+    fn skip_invalid_effect_masks() {
+        let config: Config =
+            serde_yaml::from_str("effect_masks: ['PASS(WORD)?:', '(unclosed']\n").unwrap();
+
+        assert_eq!(config.effect_masks().len(), 1);
+        assert!(config.effect_masks()[0].is_match("Enter PASSWORD: "));
+        assert!(!config.effect_masks()[0].is_match("password"));
+    }
+
+    #[test]
+    fn line_overrides() {
+        let yaml = "font:\n  underline: { thickness: 2, position: 1.5x }\n  \
+                    strikeout: { thickness: a }\n";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let mut metrics = font::Metrics {
+            average_advance: 8.,
+            line_height: 16.,
+            descent: -4.,
+            underline_position: -2.,
+            underline_thickness: 1.,
+            strikeout_position: 4.,
+            strikeout_thickness: 1.,
+        };
+
+        config.font().apply_line_overrides(&mut metrics, 2.);
+        assert_eq!(metrics.underline_thickness, 4.);
+        assert_eq!(metrics.underline_position, -3.);
+        assert_eq!(metrics.strikeout_thickness, 1.);
+        assert_eq!(metrics.strikeout_position, 4.);
+    }
+
+    #[test]
+    fn parse_binding_actions() {
+        let action = |yaml| key_binding(yaml).unwrap().action;
+
+        assert_eq!(action("{ key: A, action: Paste }"), Action::Paste);
+        assert_eq!(action("{ key: A, action: { SendText: foo } }"), Action::Esc("foo".into()));
+        assert_eq!(action("{ key: A, chars: foo }"), Action::Esc("foo".into()));
+        assert_eq!(
+            action("{ key: A, action: { RunCommand: htop } }"),
+            Action::Command("htop".into(), vec![])
+        );
+        assert_eq!(
+            action("{ key: A, action: { RunCommand: { program: ls, args: [-l] } } }"),
+            Action::Command("ls".into(), vec!["-l".into()])
+        );
+        assert_eq!(
+            action("{ key: A, command: { program: ls, args: [-l] } }"),
+            Action::Command("ls".into(), vec!["-l".into()])
+        );
+        assert_eq!(
+            action("{ key: A, action: { PasteFiltered: { strip_escapes: true, command: tr } } }"),
+            Action::PasteFiltered(PasteFilter {
+                strip_escapes: true,
+                collapse_whitespace: false,
+                command: Some(CommandWrapper::Just("tr".into())),
+            })
+        );
+        assert_eq!(
+            action("{ key: A, action: { SetColorScheme: light } }"),
+            Action::SetColorScheme("light".into())
+        );
+        assert_eq!(action("{ key: A, action: CycleColorScheme }"), Action::CycleColorScheme);
+        assert_eq!(action("{ key: A, action: ToggleFullscreen }"), Action::ToggleFullscreen);
+        assert_eq!(action("{ key: A, action: ToggleSticky }"), Action::ToggleSticky);
+        assert_eq!(
+            action("{ key: A, action: TogglePerformanceHud }"),
+            Action::TogglePerformanceHud
+        );
+    }
+
+    #[test]
+    fn deprecated_start_maximized() {
+        let window = |yaml| serde_yaml::from_str::<WindowConfig>(yaml).unwrap().startup_mode();
+
+        assert_eq!(window("startup_mode: Fullscreen"), StartupMode::Fullscreen);
+        assert_eq!(window("start_maximized: true"), StartupMode::Maximized);
+        let both = "{ start_maximized: true, startup_mode: Fullscreen }";
+        assert_eq!(window(both), StartupMode::Fullscreen);
+    }
+
+    #[test]
+    fn shell_working_directory() {
+        let config: Config = serde_yaml::from_str("shell: { working_directory: /tmp }").unwrap();
+        assert!(config.shell().is_none());
+        assert_eq!(config.working_directory(), Some(Path::new("/tmp")));
+
+        let config: Config = serde_yaml::from_str("shell: { program: fish }").unwrap();
+        assert_eq!(config.shell().map(|shell| shell.program()), Some("fish"));
+        assert_eq!(config.working_directory(), None);
+    }
+
+    #[test]
+    fn expand_tilde_of_home() {
+        let home = super::home_dir().unwrap();
+        assert_eq!(super::expand_tilde(Path::new("~")), home);
+        assert_eq!(super::expand_tilde(Path::new("~/src")), home.join("src"));
+        assert_eq!(super::expand_tilde(Path::new("~src")), Path::new("~src"));
+        assert_eq!(super::expand_tilde(Path::new("/tmp/~")), Path::new("/tmp/~"));
+    }
+
+    #[test]
+    fn reject_invalid_binding_actions() {
+        // Unknown actions
+        assert!(key_binding("{ key: A, action: Pasta }").is_err());
+        assert!(key_binding("{ key: A, action: { Pasta: foo } }").is_err());
+
+        // Missing, unexpected or multiple parameters
+        assert!(key_binding("{ key: A, action: SendText }").is_err());
+        assert!(key_binding("{ key: A, action: RunCommand }").is_err());
+        assert!(key_binding("{ key: A, action: PasteFiltered }").is_err());
+        assert!(key_binding("{ key: A, action: { PasteFiltered: { strip: true } } }").is_err());
+        assert!(key_binding("{ key: A, action: { Paste: foo } }").is_err());
+        assert!(key_binding("{ key: A, action: { SendText: [foo] } }").is_err());
+        assert!(key_binding("{ key: A, action: {} }").is_err());
+        assert!(key_binding("{ key: A, action: { SendText: foo, RunCommand: ls } }").is_err());
+
+        // Exactly one of action, chars and command
+        assert!(key_binding("{ key: A }").is_err());
+        assert!(key_binding("{ key: A, action: Paste, chars: foo }").is_err());
+        assert!(key_binding("{ key: A, chars: foo, command: ls }").is_err());
+    }
+
+    #[test]
+    fn parse_binding_mods() {
+        let mods = |mods| {
+            key_binding(&format!("{{ key: A, mods: {}, action: Paste }}", mods)).map(|b| b.mods)
+        };
+
+        let control_shift = ModifiersState { ctrl: true, shift: true, ..Default::default() };
+        assert_eq!(mods("Control|Shift").unwrap(), control_shift);
+        assert_eq!(mods("Shift | Control").unwrap(), control_shift);
+
+        let logo = ModifiersState { logo: true, ..Default::default() };
+        assert_eq!(mods("Command").unwrap(), logo);
+        assert_eq!(mods("Super").unwrap(), logo);
+
+        let alt = ModifiersState { alt: true, ..Default::default() };
+        assert_eq!(mods("Alt").unwrap(), alt);
+        assert_eq!(mods("Option").unwrap(), alt);
+
+        assert_eq!(mods("None").unwrap(), ModifiersState::default());
+        assert!(mods("Hyper").is_err());
+        assert!(mods("Control|Ctrl").is_err());
+    }
+
+    #[test]
+    fn parse_binding_modes() {
+        let modes = |mode| {
+            key_binding(&format!("{{ key: A, mode: \"{}\", action: Paste }}", mode))
+                .map(|b| (b.mode, b.notmode))
+        };
+
+        assert_eq!(modes("AppCursor").unwrap(), (TermMode::APP_CURSOR, TermMode::empty()));
+        assert_eq!(
+            modes("AppCursor|~AppKeypad").unwrap(),
+            (TermMode::APP_CURSOR, TermMode::APP_KEYPAD)
+        );
+        assert_eq!(modes("~AltScreen").unwrap(), (TermMode::empty(), TermMode::ALT_SCREEN));
+        assert!(modes("Insert").is_err());
+
+        // Bindings which could never be triggered
+        assert!(modes("AppCursor|~AppCursor").is_err());
+    }
+
+    #[test]
+    fn parse_binding_triggers() {
+        assert_eq!(key_binding("{ key: Up, action: Paste }").unwrap().trigger, Key::Up);
+        assert_eq!(key_binding("{ key: 36, action: Paste }").unwrap().trigger, Key::Scancode(36));
+        assert!(key_binding("{ key: Hyper, action: Paste }").is_err());
+        assert!(key_binding("{ mods: Control, action: Paste }").is_err());
+        assert!(key_binding("{ key: A, mouse: Left, action: Paste }").is_err());
+
+        let mouse: MouseBinding = serde_yaml::from_str("{ mouse: Middle, action: Paste }").unwrap();
+        assert_eq!(mouse.trigger, ::glutin::MouseButton::Middle.into());
+        let mouse: MouseBinding =
+            serde_yaml::from_str("{ mouse: Back, clicks: 2, action: ScrollToTop }").unwrap();
+        assert_eq!(mouse.trigger, MouseTrigger {
+            button: ::glutin::MouseButton::Other(SIDE_BUTTONS.0),
+            clicks: Some(2),
+        });
+        assert!(serde_yaml::from_str::<MouseBinding>("{ mouse: Left, clicks: 4, action: Paste }")
+            .is_err());
+        assert!(key_binding("{ key: A, clicks: 2, action: Paste }").is_err());
+        assert!(serde_yaml::from_str::<MouseBinding>("{ key: A, action: Paste }").is_err());
+        assert!(serde_yaml::from_str::<MouseBinding>("{ mouse: Left, mouse: Right, action: Paste }").is_err());
+    }
+
+    #[test]
+    fn binding_conflicts() {
+        let config: Config = serde_yaml::from_str(
+            "key_bindings:
+              - { key: A, mods: Control, action: Paste }
+              - { key: A, mods: Control, action: { SendText: foo } }
+              - { key: A, mods: Control, action: Paste }
+              - { key: A, mods: Control, mode: AppCursor, action: Paste }
+              - { key: Pasta, action: Paste }
+              - { key: Paste, action: Copy }",
+        ).unwrap();
+
+        let bindings_for = |key| {
+            config.key_bindings.iter().filter(|b| b.trigger == key).collect::<Vec<_>>()
+        };
+
+        // Duplicates are removed, bindings with different actions or modes are kept
+        let control_a = bindings_for(Key::A);
+        assert_eq!(control_a.len(), 3);
+        assert_eq!(control_a[0].action, Action::Paste);
+        assert_eq!(control_a[1].action, Action::Esc("foo".into()));
+        assert_eq!(control_a[2].mode, TermMode::APP_CURSOR);
+
+        // Configured bindings replace the defaults with the same trigger
+        let paste = bindings_for(Key::Paste);
+        assert_eq!(paste.len(), 1);
+        assert_eq!(paste[0].action, Action::Copy);
+    }
+
+    #[test]
+    fn merge_config_options() {
+        let mut document: serde_yaml::Value = serde_yaml::from_str(
+            "font:
+               size: 11.0
+               offset: { x: 0, y: 0 }",
+        ).unwrap();
+
+        merge_option(&mut document, "font.size=14").unwrap();
+        merge_option(&mut document, "font.offset={ y: 1 }").unwrap();
+        merge_option(&mut document, "window.padding.x=2").unwrap();
+
+        let expected: serde_yaml::Value = serde_yaml::from_str(
+            "font:
+               size: 14
+               offset: { x: 0, y: 1 }
+             window:
+               padding: { x: 2 }",
+        ).unwrap();
+        assert_eq!(document, expected);
+
+        assert!(merge_option(&mut document, "font.size").is_err());
+        assert!(merge_option(&mut document, "=14").is_err());
+    }
+
+    #[test]
+    fn default_bindings_have_no_warnings() {
+        let document: serde_yaml::Value = serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG).unwrap();
+        let keys: Vec<KeyBinding> =
+            serde_yaml::from_value(document["key_bindings"].clone()).unwrap();
+        let mouse: Vec<MouseBinding> =
+            serde_yaml::from_value(document["mouse"]["bindings"].clone()).unwrap();
+
+        assert_eq!(dedup_bindings(keys).1, Vec::<String>::new());
+        assert_eq!(dedup_bindings(mouse).1, Vec::<String>::new());
+    }
+
+    #[test]
+    fn conflicting_bindings() {
+        let copy = key_binding("{ key: C, mods: Control, action: Copy }").unwrap();
+        let paste = key_binding("{ key: C, mods: Control, action: Paste }").unwrap();
+        let chars = key_binding("{ key: C, mods: Control, chars: \"\\x03\" }").unwrap();
+
+        let (bindings, warnings) = dedup_bindings(vec![copy.clone(), chars, copy.clone()]);
+        assert_eq!(bindings.len(), 2);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("Ignoring duplicate binding"));
+
+        let (_, warnings) = dedup_bindings(vec![copy, paste]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("Conflicting bindings"));
+    }
+
+    #[test]
+    fn effective_document() {
+        let mut options = Options::default();
+        options.config_options = vec![String::from("font.size=14")];
+        let (document, warnings) = Config::effective_document(None, &options).unwrap();
+        let yaml = |yaml| serde_yaml::from_str::<serde_yaml::Value>(yaml).unwrap();
+        assert_eq!(document["font"]["size"], yaml("14"));
+        assert_eq!(document["dynamic_title"], yaml("true"));
+        assert!(warnings.is_empty());
+
+        // Command line flags override the config
+        options.title = Some(String::from("title"));
+        options.dimensions = Some(Dimensions::new(Column(100), Line(30)));
+        options.command = Some(Shell::new_with_args("sh", vec![String::from("-l")]));
+        let (document, _) = Config::effective_document(None, &options).unwrap();
+        assert_eq!(document["dynamic_title"], yaml("false"));
+        assert_eq!(document["window"]["dimensions"], yaml("{ columns: 100, lines: 30 }"));
+        assert_eq!(document["shell"], yaml("{ program: sh, args: [-l] }"));
+
+        options.config_options = vec![String::from("font.size")];
+        assert!(Config::effective_document(None, &options).is_err());
+    }
+
+    #[test]
+    fn config_imports() {
+        let dir = env::temp_dir().join(format!("alacritty-imports-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // The config overrides its imports, the import cycle back to the config is ignored
+        let config_path = dir.join("alacritty.yml");
+        fs::write(&config_path, "import: [colors.yml]\ntabspaces: 4\n").unwrap();
+        fs::write(
+            dir.join("colors.yml"),
+            "import: [alacritty.yml]\ntabspaces: 2\nalt_send_esc: false\n",
+        ).unwrap();
+
+        let config = Config::load_from(&config_path).unwrap();
+        assert_eq!(config.tabspaces(), 4);
+        assert!(!config.alt_send_esc());
+        assert_eq!(config.imports(), &[dir.join("colors.yml")]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn import_paths() {
+        let config_path = Path::new("/etc/alacritty/alacritty.yml");
+        assert_eq!(
+            resolve_import("colors.yml", config_path),
+            PathBuf::from("/etc/alacritty/colors.yml")
+        );
+        assert_eq!(
+            resolve_import("/tmp/colors.yml", config_path),
+            PathBuf::from("/tmp/colors.yml")
+        );
+    }
 }
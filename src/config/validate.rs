@@ -0,0 +1,436 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Validation of config documents
+//!
+//! The document is deserialized through a wrapper which keeps track of the option being
+//! deserialized. Structs report keys which aren't one of their fields and deprecated options,
+//! errors of individual values are prefixed with the option and its line in the config file.
+use std::cell::RefCell;
+use std::vec;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Deserializer, Visitor};
+use serde::de::Error as SerdeError;
+use serde_yaml::{self, Error, Mapping, Value};
+
+/// Deprecated options and the options replacing them
+const DEPRECATED: &[(&str, Option<&str>)] = &[
+    ("dimensions", Some("window.dimensions")),
+    ("padding", Some("window.padding")),
+    ("mouse.faux_scrollback_lines", Some("mouse.faux_scrolling_lines")),
+    ("custom_cursor_colors", None),
+    ("cursor_style", Some("cursor.style")),
+    ("hide_cursor_when_typing", Some("mouse.hide_when_typing")),
+    ("unfocused_hollow_cursor", Some("cursor.unfocused_hollow")),
+    ("mouse_bindings", Some("mouse.bindings")),
+    ("window.start_maximized", Some("window.startup_mode")),
+];
+
+/// Deserializes config documents and collects warnings about their options
+pub struct Validator {
+    /// Contents of the config file, used to find the line of an option
+    source: Option<String>,
+    toml: bool,
+    warnings: RefCell<Vec<String>>,
+}
+
+impl Validator {
+    /// Create a validator for a document read from `source`
+    pub fn new(source: Option<String>, toml: bool) -> Validator {
+        Validator { source, toml, warnings: RefCell::new(Vec::new()) }
+    }
+
+    /// Deserialize the document, recording warnings for unknown and deprecated options
+    pub fn deserialize<T: DeserializeOwned>(&self, document: Value) -> Result<T, Error> {
+        T::deserialize(Tracked { value: document, path: String::new(), validator: self })
+    }
+
+    /// Warnings about unknown and deprecated options, in the order they were found
+    pub fn warnings(&self) -> Vec<String> {
+        self.warnings.borrow().clone()
+    }
+
+    /// Check the keys of a struct against its fields
+    fn check_keys(&self, mapping: &Mapping, path: &str, fields: &[&str]) {
+        let mut warnings = self.warnings.borrow_mut();
+        for key in mapping.iter().filter_map(|(key, _)| key.as_str()) {
+            let option = join(path, key);
+            if !fields.contains(&key) {
+                warnings.push(format!("Unknown config option {}", self.location(&option)));
+                continue;
+            }
+
+            match DEPRECATED.iter().find(|(deprecated, _)| *deprecated == option) {
+                Some((_, Some(replacement))) => warnings.push(format!(
+                    "Config {} is deprecated; please use {} instead",
+                    self.location(&option),
+                    replacement,
+                )),
+                Some((_, None)) => {
+                    warnings.push(format!("Config {} is deprecated", self.location(&option)))
+                },
+                None => (),
+            }
+        }
+    }
+
+    /// Prefix an error with the option it occurred in
+    fn locate(&self, path: &str, err: Error) -> Error {
+        Error::custom(format!("{}: {}", self.location(path), err))
+    }
+
+    /// Option name, followed by its line in the config file if it can be found
+    fn location(&self, path: &str) -> String {
+        let line = self.source.as_ref().and_then(|source| {
+            // Lines are only looked up for the list itself, not for its elements
+            let keys: Vec<&str> = path.split('[').next().unwrap_or("").split('.').collect();
+            if self.toml {
+                toml_line(source, &keys)
+            } else {
+                yaml_line(source, &keys)
+            }
+        });
+
+        match line {
+            Some(line) => format!("{} (line {})", path, line),
+            None => path.to_owned(),
+        }
+    }
+}
+
+/// Append `key` to the option `path`
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Find the line of a YAML option through the indentation of its parent options
+fn yaml_line(source: &str, keys: &[&str]) -> Option<usize> {
+    let mut parents: Vec<(usize, &str)> = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+
+        // Keys of list elements are indented by the dash
+        let mut indent = line.len() - content.len();
+        let mut content = content;
+        while content.starts_with("- ") {
+            indent += 2;
+            content = content[2..].trim_start();
+        }
+
+        let key = match content.find(':') {
+            Some(end) => content[..end].trim().trim_matches(|c| c == '"' || c == '\''),
+            None => continue,
+        };
+
+        while parents.last().map_or(false, |(parent, _)| *parent >= indent) {
+            parents.pop();
+        }
+        parents.push((indent, key));
+
+        if parents.len() == keys.len() && parents.iter().map(|(_, key)| key).eq(keys.iter()) {
+            return Some(number + 1);
+        }
+    }
+
+    None
+}
+
+/// Find the line of a TOML option through the table headers above it
+fn toml_line(source: &str, keys: &[&str]) -> Option<usize> {
+    let mut table: Vec<&str> = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let content = line.trim();
+        if content.starts_with('[') {
+            table = content
+                .trim_matches(|c| c == '[' || c == ']')
+                .split('.')
+                .map(str::trim)
+                .collect();
+            if table[..] == keys[..] {
+                return Some(number + 1);
+            }
+            continue;
+        }
+
+        let key = match content.find('=') {
+            Some(end) if !content.starts_with('#') => &content[..end],
+            _ => continue,
+        };
+
+        let key = key.split('.').map(|key| key.trim().trim_matches('"'));
+        if table.iter().cloned().chain(key).eq(keys.iter().cloned()) {
+            return Some(number + 1);
+        }
+    }
+
+    None
+}
+
+/// Deserializer for a value of the option at `path`
+struct Tracked<'a> {
+    value: Value,
+    path: String,
+    validator: &'a Validator,
+}
+
+/// Forward to the value, prefixing errors with the option
+macro_rules! forward_value {
+    ($($method:ident($($arg:ident: $ty:ty),*)),* $(,)*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                let Tracked { value, path, validator } = self;
+                value.$method($($arg,)* visitor).map_err(|err| validator.locate(&path, err))
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> Deserializer<'de> for Tracked<'a> {
+    type Error = Error;
+
+    forward_value! {
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+        deserialize_ignored_any(),
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Sequence(sequence) => visitor.visit_seq(TrackedSeq {
+                iter: sequence.into_iter(),
+                index: 0,
+                path: self.path,
+                validator: self.validator,
+            }),
+            value => {
+                value.deserialize_seq(visitor).map_err(|err| self.validator.locate(&self.path, err))
+            },
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Mapping(mapping) => visitor.visit_map(TrackedMap {
+                iter: mapping.into_iter(),
+                next: None,
+                path: self.path,
+                validator: self.validator,
+            }),
+            value => {
+                value.deserialize_map(visitor).map_err(|err| self.validator.locate(&self.path, err))
+            },
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Mapping(mapping) => {
+                self.validator.check_keys(&mapping, &self.path, fields);
+                visitor.visit_map(TrackedMap {
+                    iter: mapping.into_iter(),
+                    next: None,
+                    path: self.path,
+                    validator: self.validator,
+                })
+            },
+            value => value
+                .deserialize_struct(name, fields, visitor)
+                .map_err(|err| self.validator.locate(&self.path, err)),
+        }
+    }
+}
+
+/// Elements of a list, tracked with their index
+struct TrackedSeq<'a> {
+    iter: vec::IntoIter<Value>,
+    index: usize,
+    path: String,
+    validator: &'a Validator,
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for TrackedSeq<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let value = match self.iter.next() {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let path = format!("{}[{}]", self.path, self.index);
+        self.index += 1;
+        seed.deserialize(Tracked { value, path, validator: self.validator }).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// Entries of a mapping, with values tracked by their key
+struct TrackedMap<'a> {
+    iter: <Mapping as IntoIterator>::IntoIter,
+    next: Option<(String, Value)>,
+    path: String,
+    validator: &'a Validator,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for TrackedMap<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let (key, value) = match self.iter.next() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        let name = match key {
+            Value::String(ref key) => key.clone(),
+            ref key => serde_yaml::to_string(key).unwrap_or_default(),
+        };
+        self.next = Some((join(&self.path, &name), value));
+
+        seed.deserialize(key).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.next.take() {
+            Some((path, value)) => {
+                seed.deserialize(Tracked { value, path, validator: self.validator })
+            },
+            None => Err(Error::custom("value requested before key")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml;
+
+    use crate::config::{Config, DEFAULT_ALACRITTY_CONFIG};
+
+    use super::Validator;
+
+    #[derive(Debug, Deserialize)]
+    struct Window {
+        title: String,
+        size: f32,
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        let document = serde_yaml::from_str(DEFAULT_ALACRITTY_CONFIG).unwrap();
+        let validator = Validator::new(Some(DEFAULT_ALACRITTY_CONFIG.to_owned()), false);
+        let _: Config = validator.deserialize(document).unwrap();
+
+        assert_eq!(validator.warnings(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn unknown_and_deprecated_options() {
+        let source = "font:\n  size: 12.0\n  sise: 14.0\ncursor_style: Beam\n";
+        let document = serde_yaml::from_str(source).unwrap();
+        let validator = Validator::new(Some(source.to_owned()), false);
+        let _: Config = validator.deserialize(document).unwrap();
+
+        // Options are checked before the options nested in them
+        assert_eq!(validator.warnings(), vec![
+            String::from(
+                "Config cursor_style (line 4) is deprecated; please use cursor.style instead"
+            ),
+            String::from("Unknown config option font.sise (line 3)"),
+        ]);
+    }
+
+    #[test]
+    fn type_mismatch_location() {
+        let source = "# Window\nwindow:\n  title: Alacritty\n  size: big\n";
+        let document = serde_yaml::from_str(source).unwrap();
+        let validator = Validator::new(Some(source.to_owned()), false);
+        let result: Result<::std::collections::HashMap<String, Window>, _> =
+            validator.deserialize(document);
+
+        let err = result.unwrap_err().to_string();
+        assert!(err.starts_with("window.size (line 4): invalid type"), "{}", err);
+    }
+
+    #[test]
+    fn toml_location() {
+        let source = "live_config_reload = true\n\n[window.padding]\nx = 2\nz = 3\n";
+        let validator = Validator::new(Some(source.to_owned()), true);
+
+        assert_eq!(validator.location("window.padding.z"), "window.padding.z (line 5)");
+        assert_eq!(validator.location("live_config_reload"), "live_config_reload (line 1)");
+    }
+}
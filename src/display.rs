@@ -14,24 +14,35 @@
 
 //! The display subsystem including window management, font rasterization, and
 //! GPU drawing.
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
 use std::sync::mpsc;
 use std::f64;
+use std::time::Instant;
 
-use parking_lot::MutexGuard;
+use glutin::{EventsLoop, WindowId};
 use glutin::dpi::{LogicalPosition, PhysicalSize};
 
 use crate::cli;
-use crate::config::Config;
-use font::{self, Rasterize};
+use crate::config::{Config, Font, PasteMarker, StartupMode};
 use crate::meter::Meter;
-use crate::renderer::{self, GlyphCache, QuadRenderer};
+use crate::index::{Column, Line, Point};
+use crate::renderer::{self, GlyphCache, Glyphs, QuadRenderer, Rect};
+use crate::ref_test::Screenshot;
 use crate::renderer::lines::Lines;
-use crate::term::{Term, SizeInfo, RenderableCell};
-use crate::sync::FairMutex;
+use crate::term::{cell, Term, SizeInfo, RenderableCell};
+use crate::term::damage::{self, DamageTracker};
+use crate::term::perf_hud::FrameTimes;
+use crate::sync::{FairMutex, FairMutexGuard};
 use crate::window::{self, Window};
 use crate::logging::LoggerProxy;
+use crate::message_bar::Message;
 use crate::Rgb;
 
+/// Width of the scroll indicator in logical pixels
+const SCROLL_INDICATOR_WIDTH: f32 = 4.;
+
 #[derive(Debug)]
 pub enum Error {
     /// Error with window management
@@ -94,17 +105,32 @@ impl From<renderer::Error> for Error {
 pub struct Display {
     window: Window,
     renderer: QuadRenderer,
-    glyph_cache: GlyphCache,
+    glyphs: Rc<RefCell<Glyphs>>,
     render_timer: bool,
     rx: mpsc::Receiver<PhysicalSize>,
     tx: mpsc::Sender<PhysicalSize>,
     meter: Meter,
+    /// Time spent waiting for the terminal lock before drawing
+    lock_meter: Meter,
+    /// Time between frames
+    frame_meter: Meter,
+    last_frame: Option<Instant>,
     font_size: font::Size,
+    /// Font of the glyph cache, with the family changed at runtime
+    font: Font,
     size_info: SizeInfo,
     logger_proxy: LoggerProxy,
+    /// Message shown in the message bar of the last frame
+    message: Option<Message>,
+    screenshot: Option<Screenshot>,
+    capture_frame: bool,
+    /// Configured `window.always_on_top` and `window.sticky`, which can be toggled at runtime
+    always_on_top: bool,
+    sticky: bool,
 }
 
 /// Can wakeup the render loop from other threads
+#[derive(Clone)]
 pub struct Notifier(window::Proxy);
 
 /// Types that are interested in when the display is resized
@@ -123,8 +149,26 @@ impl Display {
         Notifier(self.window.create_window_proxy())
     }
 
+    pub fn logger_proxy(&self) -> &LoggerProxy {
+        &self.logger_proxy
+    }
+
     pub fn update_config(&mut self, config: &Config) {
         self.render_timer = config.render_timer();
+        self.window.set_blur(config.window().blur());
+        self.glyphs.borrow_mut().cache.set_cursor_thickness(config.cursor_thickness());
+
+        // Keep the state toggled at runtime, unless the config itself changed
+        let always_on_top = config.window().always_on_top();
+        if always_on_top != self.always_on_top {
+            self.always_on_top = always_on_top;
+            self.window.set_always_on_top(always_on_top);
+        }
+        let sticky = config.window().sticky();
+        if sticky != self.sticky {
+            self.sticky = sticky;
+            self.window.set_sticky(sticky);
+        }
     }
 
     /// Get size info about the display
@@ -132,16 +176,22 @@ impl Display {
         &self.size_info
     }
 
+    /// Create a display, which shares glyphs with the `shared` display when possible
     pub fn new(
+        event_loop: &EventsLoop,
         config: &Config,
         options: &cli::Options,
-        logger_proxy: LoggerProxy
+        logger_proxy: LoggerProxy,
+        shared: Option<&Display>,
     ) -> Result<Display, Error> {
         // Extract some properties from config
         let render_timer = config.render_timer();
 
         // Create the window where Alacritty will be displayed
-        let mut window = Window::new(&options, config.window())?;
+        let backend = options.renderer.unwrap_or_else(|| config.renderer());
+        let shared_window = shared.map(|display| &display.window);
+        let mut window =
+            Window::new(event_loop, &options, config.window(), backend, shared_window)?;
 
         let dpr = window.hidpi_factor();
         info!("Device pixel ratio: {}", dpr);
@@ -151,53 +201,54 @@ impl Display {
             .expect("glutin returns window size").to_physical(dpr);
 
         // Create renderer
-        let mut renderer = QuadRenderer::new(viewport_size)?;
+        let mut renderer = QuadRenderer::new(viewport_size, backend)?;
 
-        let (glyph_cache, cell_width, cell_height) =
-            Self::new_glyph_cache(dpr, &mut renderer, config)?;
+        // Glyphs can only be shared if the OpenGL contexts share their textures
+        let font = config.font().clone();
+        let glyphs = match shared.filter(|_| window.shares_textures()) {
+            Some(display) if display.glyphs.borrow().matches(&font, dpr) => {
+                Rc::clone(&display.glyphs)
+            },
+            _ => Rc::new(RefCell::new(Self::new_glyphs(&mut renderer, font, dpr, config)?)),
+        };
+        let (cell_width, cell_height) = cell_size(&glyphs.borrow().cache, config.font());
 
         let dimensions = options.dimensions()
             .unwrap_or_else(|| config.dimensions());
 
-        let mut padding_x = f64::from(config.padding().x) * dpr;
-        let mut padding_y = f64::from(config.padding().y) * dpr;
-
         if dimensions.columns_u32() > 0
             && dimensions.lines_u32() > 0
-            && !config.window().start_maximized()
+            && config.window().startup_mode() == StartupMode::Windowed
         {
             // Calculate new size based on cols/lines specified in config
             let width = cell_width as u32 * dimensions.columns_u32();
             let height = cell_height as u32 * dimensions.lines_u32();
-            padding_x = padding_x.floor();
-            padding_y = padding_y.floor();
+            let padding_x = (f64::from(config.padding().x) * dpr).floor();
+            let padding_y = (f64::from(config.padding().y) * dpr).floor();
 
             viewport_size = PhysicalSize::new(
                 f64::from(width) + 2. * padding_x,
                 f64::from(height) + 2. * padding_y,
             );
-        } else if config.window().dynamic_padding() {
-            // Make sure additional padding is spread evenly
-            let cw = f64::from(cell_width);
-            let ch = f64::from(cell_height);
-            padding_x = (padding_x + (viewport_size.width - 2. * padding_x) % cw / 2.).floor();
-            padding_y = (padding_y + (viewport_size.height - 2. * padding_y) % ch / 2.).floor();
         }
 
+        let (padding_x, padding_y) = padding(config, dpr, viewport_size, cell_width, cell_height);
+
         window.set_inner_size(viewport_size.to_logical(dpr));
-        renderer.resize(viewport_size, padding_x as f32, padding_y as f32);
+        renderer.resize(viewport_size, padding_x, padding_y);
 
         info!("Cell Size: ({} x {})", cell_width, cell_height);
         info!("Padding: ({} x {})", padding_x, padding_y);
 
         let size_info = SizeInfo {
             dpr,
+            reserved_lines: 0,
             width: viewport_size.width as f32,
             height: viewport_size.height as f32,
             cell_width: cell_width as f32,
             cell_height: cell_height as f32,
-            padding_x: padding_x as f32,
-            padding_y: padding_y as f32,
+            padding_x,
+            padding_y,
         };
 
         // Channel for resize events
@@ -211,76 +262,77 @@ impl Display {
 
         // Clear screen
         let background_color = config.colors().primary.background;
+        let background_opacity = config.background_opacity().get();
         renderer.with_api(
             config,
             &size_info,
+            &mut glyphs.borrow_mut().atlas,
             |api| {
-                api.clear(background_color);
+                api.clear(background_color, background_opacity);
             },
         );
 
         Ok(Display {
             window,
             renderer,
-            glyph_cache,
+            glyphs,
             render_timer,
             tx,
             rx,
             meter: Meter::new(),
+            lock_meter: Meter::new(),
+            frame_meter: Meter::new(),
+            last_frame: None,
             font_size: font::Size::new(0.),
+            font: config.font().clone(),
             size_info,
             logger_proxy,
+            message: None,
+            screenshot: None,
+            capture_frame: false,
+            always_on_top: config.window().always_on_top(),
+            sticky: config.window().sticky(),
         })
     }
 
-    fn new_glyph_cache(dpr: f64, renderer: &mut QuadRenderer, config: &Config)
-        -> Result<(GlyphCache, f32, f32), Error>
+    fn new_glyphs(renderer: &mut QuadRenderer, font: Font, dpr: f64, config: &Config)
+        -> Result<Glyphs, Error>
     {
-        let font = config.font().clone();
-        let rasterizer = font::Rasterizer::new(dpr as f32, config.use_thin_strokes())?;
-
-        // Initialize glyph cache
-        let glyph_cache = {
-            info!("Initializing glyph cache...");
-            let init_start = ::std::time::Instant::now();
-
-            let cache =
-                renderer.with_loader(|mut api| GlyphCache::new(rasterizer, &font, &mut api))?;
-
-            let stop = init_start.elapsed();
-            let stop_f = stop.as_secs() as f64 +
-                         f64::from(stop.subsec_nanos()) / 1_000_000_000f64;
-            info!("... finished initializing glyph cache in {}s", stop_f);
-
-            cache
-        };
+        info!("Initializing glyph cache...");
+        let init_start = ::std::time::Instant::now();
 
-        // Need font metrics to resize the window properly. This suggests to me the
-        // font metrics should be computed before creating the window in the first
-        // place so that a resize is not needed.
-        let metrics = glyph_cache.font_metrics();
-        let cell_width = metrics.average_advance as f32 + f32::from(font.offset().x);
-        let cell_height = metrics.line_height as f32 + f32::from(font.offset().y);
+        let mut glyphs = Glyphs::new(renderer, font, dpr, config.use_thin_strokes())?;
+        glyphs.cache.set_cursor_thickness(config.cursor_thickness());
 
-        // Prevent invalid cell sizes
-        if cell_width < 1. || cell_height < 1. {
-            panic!("font offset is too small");
-        }
+        let stop = init_start.elapsed();
+        let stop_f = stop.as_secs() as f64 +
+                     f64::from(stop.subsec_nanos()) / 1_000_000_000f64;
+        info!("... finished initializing glyph cache in {}s", stop_f);
 
-        Ok((glyph_cache, cell_width.floor(), cell_height.floor()))
+        Ok(glyphs)
     }
 
+    /// Rasterize the glyphs for the current font, font size and DPR
+    ///
+    /// Glyphs which are still used by other windows are left alone, this window gets its own.
     pub fn update_glyph_cache(&mut self, config: &Config) {
         let dpr = self.size_info.dpr;
-        let cache = &mut self.glyph_cache;
-        let size = self.font_size;
-        self.renderer.with_loader(|mut api| {
-            let _ = cache.update_font_size(config.font(), size, dpr, &mut api);
-        });
+        let font = self.font.clone().with_size(self.font_size);
+
+        if !self.glyphs.borrow().matches(&font, dpr) {
+            if Rc::strong_count(&self.glyphs) == 1 {
+                self.glyphs.borrow_mut().update(&mut self.renderer, font, dpr);
+            } else {
+                match Self::new_glyphs(&mut self.renderer, font, dpr, config) {
+                    Ok(glyphs) => self.glyphs = Rc::new(RefCell::new(glyphs)),
+                    Err(err) => error!("Unable to load glyphs: {}", err),
+                }
+            }
+        }
 
-        let metrics = cache.font_metrics();
-        self.size_info.cell_width = ((metrics.average_advance + f64::from(config.font().offset().x)) as f32).floor();
-        self.size_info.cell_height = ((metrics.line_height + f64::from(config.font().offset().y)) as f32).floor();
+        let (cell_width, cell_height) = cell_size(&self.glyphs.borrow().cache, &self.font);
+        self.size_info.cell_width = cell_width;
+        self.size_info.cell_height = cell_height;
     }
 
     #[inline]
@@ -295,7 +347,7 @@ impl Display {
     /// Process pending resize events
     pub fn handle_resize(
         &mut self,
-        terminal: &mut MutexGuard<'_, Term>,
+        terminal: &mut FairMutexGuard<'_, Term>,
         config: &Config,
         items: &mut [&mut dyn OnResize],
     ) {
@@ -309,11 +361,18 @@ impl Display {
             new_size = Some(size);
         }
 
+        // Glyphs and the viewport belong to the context of this window
+        self.make_current();
+
         // Update the DPR
         let dpr = self.window.hidpi_factor();
 
-        // Font size/DPI factor modification detected
-        if terminal.font_size != self.font_size || (dpr - self.size_info.dpr).abs() > f64::EPSILON {
+        // Font, font size or DPI factor modification detected
+        let font = config.font().clone().with_family(terminal.font_family());
+        if terminal.font_size != self.font_size
+            || font != self.font
+            || (dpr - self.size_info.dpr).abs() > f64::EPSILON
+        {
             if new_size == None {
                 // Force a resize to refresh things
                 new_size = Some(PhysicalSize::new(
@@ -323,30 +382,42 @@ impl Display {
             }
 
             self.font_size = terminal.font_size;
+            self.font = font;
             self.size_info.dpr = dpr;
 
             self.update_glyph_cache(config);
         }
 
-        if let Some(psize) = new_size.take() {
-            let width = psize.width as f32;
-            let height = psize.height as f32;
-            let cell_width = self.size_info.cell_width;
-            let cell_height = self.size_info.cell_height;
+        // Padding changed with a config reload
+        let size_info = self.size_info;
+        let (cell_width, cell_height) = (size_info.cell_width, size_info.cell_height);
+        let size = PhysicalSize::new(f64::from(size_info.width), f64::from(size_info.height));
+        let current = (size_info.padding_x, size_info.padding_y);
+        if new_size.is_none() && padding(config, dpr, size, cell_width, cell_height) != current {
+            new_size = Some(size);
+        }
 
-            self.size_info.width = width;
-            self.size_info.height = height;
+        let resized = new_size.is_some();
+        if let Some(psize) = new_size.take() {
+            let (padding_x, padding_y) = padding(config, dpr, psize, cell_width, cell_height);
+            self.size_info.width = psize.width as f32;
+            self.size_info.height = psize.height as f32;
+            self.size_info.padding_x = padding_x;
+            self.size_info.padding_y = padding_y;
 
-            let mut padding_x = f32::from(config.padding().x) * dpr as f32;
-            let mut padding_y = f32::from(config.padding().y) * dpr as f32;
+            self.window.resize(psize);
+            self.renderer.resize(psize, self.size_info.padding_x, self.size_info.padding_y);
+        }
 
-            if config.window().dynamic_padding() {
-                padding_x = padding_x + ((width - 2. * padding_x) % cell_width) / 2.;
-                padding_y = padding_y + ((height - 2. * padding_y) % cell_height) / 2.;
-            }
+        // The message bar takes lines from the bottom of the terminal
+        let reserved_lines = self
+            .logger_proxy
+            .messages()
+            .message()
+            .map_or(0, |message| message.text(&self.size_info).len());
 
-            self.size_info.padding_x = padding_x.floor();
-            self.size_info.padding_y = padding_y.floor();
+        if resized || reserved_lines != self.size_info.reserved_lines {
+            self.size_info.reserved_lines = reserved_lines;
 
             let size = &self.size_info;
             terminal.resize(size);
@@ -354,9 +425,6 @@ impl Display {
             for item in items {
                 item.on_resize(size)
             }
-
-            self.window.resize(psize);
-            self.renderer.resize(psize, self.size_info.padding_x, self.size_info.padding_y);
         }
     }
 
@@ -366,23 +434,163 @@ impl Display {
     ///
     /// This call may block if vsync is enabled
     pub fn draw(&mut self, terminal: &FairMutex<Term>, config: &Config) {
-        let mut terminal = terminal.lock();
+        self.make_current();
+
+        let now = Instant::now();
+        let interval = self.last_frame.map(|last| now - last).unwrap_or_default();
+        self.frame_meter.add_sample(interval);
+        self.last_frame = Some(now);
+
+        let mut terminal = {
+            let _sampler = self.lock_meter.sampler();
+            terminal.lock()
+        };
         let size_info = *terminal.size_info();
         let visual_bell_intensity = terminal.visual_bell.intensity();
-        let background_color = terminal.background_color();
+        let accent = config.accent();
+        let accent_color = terminal.accent_color().or(accent.color);
 
+        // Dim everything while the window is unfocused, so the active terminal stands out
         let window_focused = self.window.is_focused;
-        let grid_cells: Vec<RenderableCell> = terminal
+        let dim = if window_focused { 1. } else { config.colors().dim_on_unfocus.get() };
+
+        let background_color = match accent_color {
+            Some(color) => terminal.background_color().blend(color, accent.background.get()),
+            None => terminal.background_color(),
+        } * dim;
+        let autoscroll = terminal.autoscroll.clone();
+        let indicator_config = config.scrolling().indicator;
+        let scroll_indicator = if indicator_config.enabled {
+            terminal.scroll_indicator(indicator_config.fade_delay)
+        } else {
+            None
+        };
+        let primary_colors = (terminal.background_color(), terminal.foreground_color());
+        let output_paused = terminal.output_paused();
+        let background_opacity = terminal.background_opacity().get();
+        let effect_tuning = terminal
+            .effect_tuning
+            .as_ref()
+            .map(|tuning| tuning.lines(&terminal.undo.style));
+        let frame_times = FrameTimes {
+            interval: interval.as_secs() as f64 * 1e6 + f64::from(interval.subsec_micros()),
+            frame: self.frame_meter.average(),
+            draw: self.meter.average(),
+            lock_wait: self.lock_meter.average(),
+        };
+        let perf_hud = terminal.perf_hud.as_mut().map(|hud| hud.lines(frame_times));
+
+        // Mark soft-wrapped lines with a faint glyph in the first column
+        let wrap_indicator = config.wrap_indicator().map(|indicator| {
+            let color = primary_colors.0.blend(primary_colors.1, 0.5) * dim;
+            (indicator, color, terminal.wrapped_lines())
+        });
+
+        let mut grid_cells: Vec<RenderableCell> = terminal
             .renderable_cells(config, window_focused)
             .collect();
 
-        // Clear dirty flag
-        terminal.dirty = !terminal.visual_bell.completed();
+        // Columns a refresh sweep hasn't reached yet still show the previous screen
+        if let Some((column, cells)) = terminal.sweep_cells() {
+            grid_cells.retain(|cell| cell.column > column || terminal.is_masked(cell.line));
+            grid_cells.extend(cells);
+            grid_cells.sort_by_key(|cell| (cell.line, cell.column));
+        }
+
+        // Cover cells restored from an animation, so they fade in instead of snapping back
+        let mut overlay_rects: Vec<(Rect<f32>, Rgb, f32)> = terminal
+            .fading_cells()
+            .into_iter()
+            .map(|(point, color, alpha)| {
+                let rect = Rect::new(
+                    size_info.padding_x + point.col.0 as f32 * size_info.cell_width,
+                    size_info.padding_y + point.line.0 as f32 * size_info.cell_height,
+                    size_info.cell_width,
+                    size_info.cell_height,
+                );
+                (rect, color, alpha)
+            })
+            .collect();
+
+        // Tick lines of the command line which contain pasted text
+        if config.paste_marker() == PasteMarker::Gutter {
+            let width = (size_info.padding_x / 2.).max(2.);
+            let mut last_line = None;
+            for cell in grid_cells.iter().filter(|cell| cell.flags.contains(cell::Flags::PASTED)) {
+                if last_line != Some(cell.line) {
+                    last_line = Some(cell.line);
+                    let y = size_info.padding_y + cell.line.0 as f32 * size_info.cell_height;
+                    let rect = Rect::new(0., y, width, size_info.cell_height);
+                    overlay_rects.push((rect, cell.fg, 1.));
+                }
+            }
+        }
+
+        // Tick annotated lines at the right edge of the window
+        let width = (size_info.padding_x / 2.).max(2.);
+        for line in terminal.annotated_lines() {
+            let y = size_info.padding_y + line.0 as f32 * size_info.cell_height;
+            let rect = Rect::new(size_info.width - width, y, width, size_info.cell_height);
+            overlay_rects.push((rect, primary_colors.1, 1.));
+        }
+
+        // Show the position of the viewport in the history along the right edge
+        if let Some(indicator) = scroll_indicator {
+            let width = SCROLL_INDICATOR_WIDTH * size_info.dpr as f32;
+            let x = size_info.width - width;
+            if let Some(track) = indicator_config.track {
+                let rect = Rect::new(x, 0., width, size_info.height);
+                overlay_rects.push((rect, track, indicator.alpha / 2.));
+            }
+
+            let height = (indicator.height * size_info.height).max(width);
+            let y = (indicator.top * size_info.height).min(size_info.height - height);
+            let thumb = indicator_config.thumb.unwrap_or(primary_colors.1);
+            overlay_rects.push((Rect::new(x, y, width, height), thumb, indicator.alpha));
+        }
+
+        // Frame the window with the accent color
+        if let Some(color) = accent_color {
+            let border = f32::from(accent.border);
+            let (width, height) = (size_info.width, size_info.height);
+            overlay_rects.extend(&[
+                (Rect::new(0., 0., width, border), color, 1.),
+                (Rect::new(0., height - border, width, border), color, 1.),
+                (Rect::new(0., 0., border, height), color, 1.),
+                (Rect::new(width - border, 0., border, height), color, 1.),
+            ]);
+        }
+
+        if dim < 1. {
+            dim_frame(dim, &mut grid_cells, &mut overlay_rects);
+        }
+
+        // Everything has been drawn, the visual bell keeps the terminal dirty until it's done,
+        // blinking text and the blinking cursor keep it dirty while they're visible, autoscrolling
+        // while it's active and the scroll indicator until it has faded out
+        let blinking = (terminal.text_blinks()
+            && grid_cells.iter().any(|cell| cell.flags.intersects(cell::Flags::BLINK)))
+            || (window_focused && terminal.cursor_blinks());
+        terminal.flush_damage();
+        let damage = terminal.damage.take(DamageTracker::RENDERER);
+        let damaged_lines = damage::damaged_lines(&damage, size_info.lines());
+        let animating = autoscroll.is_some() || scroll_indicator.is_some();
+        if !terminal.visual_bell.completed() || blinking || animating {
+            terminal.damage.damage_all();
+        }
 
         if let Some(title) = terminal.get_next_title() {
             self.window.set_title(&title);
         }
 
+        // Commands are only started once the terminal has been released
+        let notifications = mem::replace(&mut terminal.notifications, Vec::new());
+        let title = if notifications.is_empty() {
+            String::new()
+        } else {
+            terminal.title().to_owned()
+        };
+
         if let Some(mouse_cursor) = terminal.get_next_mouse_cursor() {
             self.window.set_mouse_cursor(mouse_cursor);
         }
@@ -406,86 +614,205 @@ impl Display {
         // handling and rendering.
         drop(terminal);
 
-        self.renderer.with_api(config, &size_info, |api| {
-            api.clear(background_color);
-        });
+        for notification in notifications {
+            notification.deliver(config, &title, self.window.is_focused);
+        }
 
         {
-            let glyph_cache = &mut self.glyph_cache;
-            let metrics = glyph_cache.font_metrics();
+            let mut glyphs = self.glyphs.borrow_mut();
+            let renderer::Glyphs { cache: ref mut glyph_cache, ref mut atlas, .. } = *glyphs;
+            let mut metrics = glyph_cache.font_metrics();
+            config.font().apply_line_overrides(&mut metrics, size_info.dpr as f32);
             let mut cell_line_rects = Lines::new(&metrics, &size_info);
+            for cell in &grid_cells {
+                cell_line_rects.update_lines(cell);
+            }
 
             // Draw grid
             {
                 let _sampler = self.meter.sampler();
 
-                self.renderer.with_api(config, &size_info, |mut api| {
-                    // Iterate over all non-empty cells in the grid
-                    for cell in grid_cells {
-                        // Update underline/strikeout
-                        cell_line_rects.update_lines(&cell);
+                // Lines which haven't changed are kept from the previous frame
+                let damaged_lines = self.renderer.begin_grid(
+                    &size_info,
+                    damaged_lines,
+                    background_color,
+                    background_opacity,
+                );
+                match damaged_lines {
+                    Some(damaged_lines) => {
+                        for lines in damaged_lines {
+                            // Glyphs of the neighbouring lines can reach into the cleared lines,
+                            // the scissor keeps everything else of them as it was
+                            let cells = grid_cells
+                                .iter()
+                                .filter(|cell| {
+                                    cell.line.0 + 1 >= lines.start.0 && cell.line <= lines.end
+                                })
+                                .cloned();
+                            self.renderer.scissor_lines(&size_info, Some(lines));
+                            self.renderer.with_api(config, &size_info, atlas, |mut api| {
+                                api.render_cells(cells, glyph_cache);
+                            });
+                        }
+                    },
+                    None => {
+                        self.renderer.with_api(config, &size_info, atlas, |mut api| {
+                            api.render_cells(grid_cells, glyph_cache);
+                        });
+                    },
+                }
+                self.renderer.finish_grid(&size_info);
+
+                self.renderer.with_api(config, &size_info, atlas, |mut api| {
+                    if let Some((indicator, color, lines)) = wrap_indicator {
+                        for line in lines {
+                            let point = Point::new(line, Column(0));
+                            api.render_transparent_char(indicator, point, glyph_cache, color);
+                        }
+                    }
 
-                        // Draw the cell
-                        api.render_cell(cell, glyph_cache);
+                    // Show the autoscroll direction where the middle button was pressed
+                    if let Some(autoscroll) = autoscroll {
+                        api.render_overlay_char(
+                            autoscroll.indicator(),
+                            autoscroll.origin,
+                            glyph_cache,
+                            primary_colors.1,
+                            primary_colors.0,
+                        );
                     }
                 });
             }
 
             // Draw rectangles
-            self.renderer.draw_rects(config, &size_info, visual_bell_intensity, cell_line_rects);
+            self.renderer.draw_rects(
+                config,
+                &size_info,
+                visual_bell_intensity,
+                cell_line_rects,
+                overlay_rects,
+            );
+
+            // Overlay text is shortened to the width of the window
+            let cols = size_info.cols();
+
+            // Remind the user that the terminal isn't updating
+            if output_paused {
+                let color = Rgb {
+                    r: 0xff,
+                    g: 0xff,
+                    b: 0x00,
+                };
+                self.renderer.with_api(config, &size_info, atlas, |mut api| {
+                    api.render_string(" OUTPUT PAUSED ", Line(0), cols, glyph_cache, color);
+                });
+            }
+
+            // Show the effect style while it's being adjusted
+            if let Some(lines) = effect_tuning {
+                let color = Rgb {
+                    r: 0x00,
+                    g: 0x80,
+                    b: 0x40,
+                };
+                self.renderer.with_api(config, &size_info, atlas, |mut api| {
+                    for (i, line) in lines.iter().enumerate() {
+                        api.render_string(line, Line(1 + i), cols, glyph_cache, color);
+                    }
+                });
+            }
 
             // Draw render timer
             if self.render_timer {
-                let timing = format!("{:.3} usec", self.meter.average());
+                let timing = format!(
+                    "{:.3} usec, {:.3} usec lock wait",
+                    self.meter.average(),
+                    self.lock_meter.average(),
+                );
                 let color = Rgb {
                     r: 0xd5,
                     g: 0x4e,
                     b: 0x53,
                 };
-                self.renderer.with_api(config, &size_info, |mut api| {
-                    api.render_string(&timing[..], size_info.lines() - 2, glyph_cache, color);
+                self.renderer.with_api(config, &size_info, atlas, |mut api| {
+                    api.render_string(&timing[..], size_info.lines() - 2, cols, glyph_cache, color);
                 });
             }
 
-            // Display errors and warnings
-            if self.logger_proxy.errors() {
-                let msg = match self.logger_proxy.log_path() {
-                    Some(path) => format!(" ERROR! See log at {} ", path),
-                    None => " ERROR! See log in stderr ".into(),
-                };
+            // Draw the performance HUD in the top right corner
+            if let Some(lines) = perf_hud {
                 let color = Rgb {
-                    r: 0xff,
-                    g: 0x00,
-                    b: 0x00,
+                    r: 0x53,
+                    g: 0x8c,
+                    b: 0xd5,
                 };
-                self.renderer.with_api(config, &size_info, |mut api| {
-                    api.render_string(&msg, size_info.lines() - 1, glyph_cache, color);
+                self.renderer.with_api(config, &size_info, atlas, |mut api| {
+                    for (i, line) in lines.iter().take(size_info.lines().0).enumerate() {
+                        let start = Column(cols.0.saturating_sub(line.len()));
+                        let point = Point::new(Line(i), start);
+                        api.render_string_at(line, point, cols - start, glyph_cache, color);
+                    }
                 });
-            } else if self.logger_proxy.warnings() {
-                let msg = match self.logger_proxy.log_path() {
-                    Some(path) => format!(" WARNING! See log at {} ", path),
-                    None => " WARNING! See log in stderr ".into(),
-                };
-                let color = Rgb {
-                    r: 0xff,
-                    g: 0xff,
-                    b: 0x00,
-                };
-                self.renderer.with_api(config, &size_info, |mut api| {
-                    api.render_string(&msg, size_info.lines() - 1, glyph_cache, color);
+            }
+
+            // Show the oldest error or warning in the message bar
+            self.message = self.logger_proxy.messages().message();
+            if let Some(ref message) = self.message {
+                let text = message.text(&size_info);
+                let start = Line(size_info.window_lines() - text.len());
+                let color = message.color();
+                self.renderer.with_api(config, &size_info, atlas, |mut api| {
+                    for (i, line) in text.iter().enumerate() {
+                        api.render_string(line, start + i, cols, glyph_cache, color);
+                    }
                 });
             }
         }
 
+        if self.capture_frame {
+            self.capture_frame = false;
+            let (width, height) = (size_info.width as usize, size_info.height as usize);
+            let pixels = self.renderer.read_pixels(width, height);
+            self.screenshot = Some(Screenshot { width, height, pixels });
+        }
+
         self.window
             .swap_buffers()
             .expect("swap buffers");
     }
 
+    /// Check if the message bar has to be redrawn
+    pub fn message_changed(&self) -> bool {
+        self.logger_proxy.messages().message() != self.message
+    }
+
+    /// Take a screenshot of the next frame which is drawn
+    pub fn capture_frame(&mut self) {
+        self.capture_frame = true;
+    }
+
+    /// Screenshot requested with `capture_frame`, once the frame has been drawn
+    pub fn take_screenshot(&mut self) -> Option<Screenshot> {
+        self.screenshot.take()
+    }
+
     pub fn get_window_id(&self) -> Option<usize> {
         self.window.get_window_id()
     }
 
+    /// Identifier of the window in glutin events
+    pub fn window_id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    /// Direct all OpenGL calls to this display's window
+    fn make_current(&self) {
+        if let Err(err) = self.window.make_current() {
+            error!("Unable to make window context current: {}", err);
+        }
+    }
+
     /// Adjust the IME editor position according to the new location of the cursor
     pub fn update_ime_position(&mut self, terminal: &Term) {
         use crate::index::{Column, Line, Point};
@@ -500,3 +827,111 @@ impl Display {
         self.window().set_ime_spot(LogicalPosition::from((nspot_x, nspot_y)));
     }
 }
+
+/// Size of a cell with the font of the glyph cache, including the extra spacing of `font`
+fn cell_size(glyph_cache: &GlyphCache, font: &Font) -> (f32, f32) {
+    let metrics = glyph_cache.font_metrics();
+    let cell_width = metrics.average_advance as f32 + f32::from(font.offset().x);
+    let cell_height = metrics.line_height as f32 + f32::from(font.offset().y);
+
+    // Prevent invalid cell sizes
+    if cell_width < 1. || cell_height < 1. {
+        panic!("font offset is too small");
+    }
+
+    (cell_width.floor(), cell_height.floor())
+}
+
+/// Darken the cells and rectangles of a frame by `dim`
+///
+/// This has to run once the frame is complete, so the cells of sweeps and the covers of fading
+/// cells are dimmed like the rest of the grid.
+fn dim_frame(dim: f32, cells: &mut [RenderableCell], rects: &mut [(Rect<f32>, Rgb, f32)]) {
+    for cell in cells {
+        cell.fg = cell.fg * dim;
+        cell.bg = cell.bg * dim;
+    }
+
+    for (_, color, _) in rects {
+        *color = *color * dim;
+    }
+}
+
+/// Padding around the grid in physical pixels
+///
+/// With `window.dynamic_padding` the space which doesn't fit a whole cell is spread evenly around
+/// the grid, instead of being added to the right and bottom edge.
+fn padding(
+    config: &Config,
+    dpr: f64,
+    size: PhysicalSize,
+    cell_width: f32,
+    cell_height: f32,
+) -> (f32, f32) {
+    // The remainder is only correct for the padding which is actually used
+    let mut padding_x = (f32::from(config.padding().x) * dpr as f32).floor();
+    let mut padding_y = (f32::from(config.padding().y) * dpr as f32).floor();
+
+    if config.window().dynamic_padding() {
+        padding_x += ((size.width as f32 - 2. * padding_x) % cell_width) / 2.;
+        padding_y += ((size.height as f32 - 2. * padding_y) % cell_height) / 2.;
+    }
+
+    (padding_x.floor(), padding_y.floor())
+}
+
+#[cfg(test)]
+mod tests {
+    use glutin::dpi::PhysicalSize;
+
+    use crate::config::Config;
+    use crate::index::{Column, Line};
+    use crate::renderer::Rect;
+    use crate::ansi::LineAttribute;
+    use crate::term::cell::{Flags, MAX_ZEROWIDTH_CHARS};
+    use crate::term::RenderableCell;
+    use crate::Rgb;
+
+    use super::{dim_frame, padding};
+
+    #[test]
+    fn dynamic_padding_centers_grid() {
+        let size = PhysicalSize::new(108., 70.);
+
+        let config: Config = serde_yaml::from_str("window: { padding: { x: 2, y: 2 } }").unwrap();
+        assert_eq!(padding(&config, 1., size, 10., 20.), (2., 2.));
+
+        let config: Config = serde_yaml::from_str(
+            "window: { padding: { x: 2, y: 2 }, dynamic_padding: true }",
+        ).unwrap();
+        assert_eq!(padding(&config, 1., size, 10., 20.), (4., 5.));
+        assert_eq!(padding(&config, 2., size, 10., 20.), (4., 5.));
+
+        // Fractional padding is dropped before the remainder is spread
+        let config: Config = serde_yaml::from_str(
+            "window: { padding: { x: 3, y: 3 }, dynamic_padding: true }",
+        ).unwrap();
+        assert_eq!(padding(&config, 1.5, size, 10., 20.), (4., 5.));
+    }
+
+    #[test]
+    fn dim_frame_darkens_cells_and_rects() {
+        let white = Rgb { r: 200, g: 200, b: 200 };
+        let mut cells = vec![RenderableCell {
+            line: Line(0),
+            column: Column(0),
+            chars: ['a'; MAX_ZEROWIDTH_CHARS + 1],
+            fg: white,
+            bg: Rgb { r: 100, g: 0, b: 50 },
+            bg_alpha: 1.,
+            flags: Flags::empty(),
+            line_attribute: LineAttribute::default(),
+        }];
+        let mut rects = vec![(Rect::new(0., 0., 1., 1.), white, 0.5)];
+
+        dim_frame(0.5, &mut cells, &mut rects);
+        assert_eq!(cells[0].fg, Rgb { r: 100, g: 100, b: 100 });
+        assert_eq!(cells[0].bg, Rgb { r: 50, g: 0, b: 25 });
+        assert_eq!(rects[0].1, Rgb { r: 100, g: 100, b: 100 });
+    }
+}
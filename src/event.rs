@@ -3,31 +3,36 @@
 use std::fs;
 use std::borrow::Cow;
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
 use std::sync::mpsc;
-use std::time::{Instant};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use std::env;
 
 use serde_json as json;
-use parking_lot::MutexGuard;
-use glutin::{self, ModifiersState, Event, ElementState, MouseButton};
+use glutin::{self, ModifiersState, Event, ElementState, MouseButton, VirtualKeyCode};
 use copypasta::{Clipboard, Load, Store, Buffer as ClipboardBuffer};
 use glutin::dpi::PhysicalSize;
 
-#[cfg(unix)]
-use crate::tty;
 use crate::ansi::{Handler, ClearMode};
 use crate::grid::Scroll;
 use crate::config::{self, Config};
 use crate::cli::Options;
-use crate::display::OnResize;
+use crate::display::{self, OnResize};
 use crate::index::{Line, Column, Side, Point};
 use crate::input::{self, MouseBinding, KeyBinding};
+use crate::message_bar::Message;
+use crate::playback::Control;
+use crate::screenshot::Region;
 use crate::selection::Selection;
-use crate::sync::FairMutex;
+use crate::sync::{FairMutex, FairMutexGuard};
 use crate::term::{Term, SizeInfo, TermMode, Search};
+use crate::term::autoscroll::Autoscroll;
+use crate::term::dump::DumpSource;
 use crate::term::cell::Cell;
-use crate::util::{limit, start_daemon};
+use crate::url::FileLocation;
+use crate::util::{limit, start_daemon, thread};
 use crate::util::fmt::Red;
 use crate::window::Window;
 
@@ -37,6 +42,16 @@ pub trait Notify {
     ///
     /// TODO this needs to be able to error somehow
     fn notify<B: Into<Cow<'static, [u8]>>>(&mut self, _: B);
+
+    /// Notify that bulk data, like a paste, should be written to the pty
+    ///
+    /// This is written with a lower priority than `notify`, so it doesn't delay keystrokes.
+    fn notify_bulk<B: Into<Cow<'static, [u8]>>>(&mut self, bytes: B) {
+        self.notify(bytes);
+    }
+
+    /// Notify that applying the pty output to the terminal should be paused or resumed
+    fn toggle_pause(&mut self);
 }
 
 pub struct ActionContext<'a, N> {
@@ -48,6 +63,11 @@ pub struct ActionContext<'a, N> {
     pub suppress_chars: &'a mut bool,
     pub last_modifiers: &'a mut ModifiersState,
     pub window_changes: &'a mut WindowChanges,
+    pub dump_config: &'a config::Dump,
+    pub playback: Option<&'a mpsc::Sender<Control>>,
+    pub shell_pid: Option<u32>,
+    pub filtered_pastes: &'a mpsc::Sender<io::Result<String>>,
+    pub wakeup: &'a display::Notifier,
 }
 
 impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
@@ -55,6 +75,24 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
         self.notifier.notify(val);
     }
 
+    fn write_bulk_to_pty<B: Into<Cow<'static, [u8]>>>(&mut self, val: B) {
+        self.notifier.notify_bulk(val);
+    }
+
+    fn filter_paste(&mut self, filter: &config::PasteFilter, contents: String) {
+        let filter = filter.clone();
+        let filtered_pastes = self.filtered_pastes.clone();
+        let wakeup = self.wakeup.clone();
+        thread::spawn_named("paste filter", move || {
+            let _ = filtered_pastes.send(input::filter_paste(&filter, contents));
+            wakeup.notify();
+        });
+    }
+
+    fn pasted(&mut self, text: &str) {
+        self.terminal.pasted(text);
+    }
+
     fn terminal_mode(&self) -> TermMode {
         *self.terminal.mode()
     }
@@ -100,7 +138,7 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
 
     fn clear_selection(&mut self) {
         *self.terminal.selection_mut() = None;
-        self.terminal.dirty = true;
+        self.terminal.damage.damage_all();
     }
 
     fn update_selection(&mut self, point: Point, side: Side) {
@@ -111,29 +149,54 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
             selection.update(point, side);
         }
 
-        self.terminal.dirty = true;
+        self.terminal.damage.damage_all();
     }
 
     fn simple_selection(&mut self, point: Point, side: Side) {
         let point = self.terminal.visible_to_buffer(point);
         *self.terminal.selection_mut() = Some(Selection::simple(point, side));
-        self.terminal.dirty = true;
+        self.terminal.damage.damage_all();
+    }
+
+    fn block_selection(&mut self, point: Point, side: Side) {
+        let point = self.terminal.visible_to_buffer(point);
+        *self.terminal.selection_mut() = Some(Selection::block(point, side));
+        self.terminal.damage.damage_all();
     }
 
     fn semantic_selection(&mut self, point: Point) {
         let point = self.terminal.visible_to_buffer(point);
         *self.terminal.selection_mut() = Some(Selection::semantic(point));
-        self.terminal.dirty = true;
+        self.terminal.damage.damage_all();
     }
 
     fn url(&self, point: Point<usize>) -> Option<String> {
         self.terminal.url_search(point)
     }
 
+    fn file_location(&self, point: Point<usize>) -> Option<FileLocation> {
+        let mut location = self.terminal.file_location_search(point)?;
+
+        // Relative paths are relative to the shell, not to Alacritty
+        if location.path.is_relative() {
+            let directory = match self.terminal.working_directory() {
+                Some(directory) => directory.to_path_buf(),
+                None => shell_working_directory(self.shell_pid?)?,
+            };
+            location.path = directory.join(&location.path);
+        }
+
+        if location.path.is_file() {
+            Some(location)
+        } else {
+            None
+        }
+    }
+
     fn line_selection(&mut self, point: Point) {
         let point = self.terminal.visible_to_buffer(point);
         *self.terminal.selection_mut() = Some(Selection::lines(point));
-        self.terminal.dirty = true;
+        self.terminal.damage.damage_all();
     }
 
     fn mouse_coords(&self) -> Option<Point> {
@@ -148,6 +211,14 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
         self.terminal.reset_font_size();
     }
 
+    fn change_background_opacity(&mut self, delta: f32) {
+        self.terminal.change_background_opacity(delta);
+    }
+
+    fn reset_background_opacity(&mut self) {
+        self.terminal.reset_background_opacity();
+    }
+
     #[inline]
     fn mouse_mut(&mut self) -> &mut Mouse {
         self.mouse
@@ -178,43 +249,276 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
         self.window_changes.hide = true;
     }
 
+    #[inline]
+    fn toggle_fullscreen(&mut self) {
+        self.window_changes.toggle_fullscreen = !self.window_changes.toggle_fullscreen;
+    }
+
+    #[inline]
+    fn toggle_simple_fullscreen(&mut self) {
+        self.window_changes.toggle_simple_fullscreen =
+            !self.window_changes.toggle_simple_fullscreen;
+    }
+
+    #[inline]
+    fn toggle_decorations(&mut self) {
+        self.window_changes.toggle_decorations = !self.window_changes.toggle_decorations;
+    }
+
+    #[inline]
+    fn toggle_always_on_top(&mut self) {
+        self.window_changes.toggle_always_on_top = !self.window_changes.toggle_always_on_top;
+    }
+
+    #[inline]
+    fn toggle_sticky(&mut self) {
+        self.window_changes.toggle_sticky = !self.window_changes.toggle_sticky;
+    }
+
+    #[inline]
+    fn quit(&mut self) {
+        self.window_changes.quit = true;
+    }
+
     #[inline]
     fn clear_log(&mut self) {
         self.terminal.clear_log();
     }
 
+    #[inline]
+    fn message(&self) -> Option<Message> {
+        self.terminal.message()
+    }
+
+    #[inline]
+    fn close_message(&mut self) {
+        self.terminal.close_message();
+    }
+
+    fn dump(&mut self, source: DumpSource) {
+        let no_selection = self.terminal.selection().as_ref().map_or(true, |s| s.is_empty());
+        if source == DumpSource::Selection && no_selection {
+            return;
+        }
+
+        let (text, extension) = match self.dump_config.format {
+            config::DumpFormat::Plain => (self.terminal.dump(source, false), "txt"),
+            config::DumpFormat::Ansi => (self.terminal.dump(source, true), "txt"),
+            config::DumpFormat::Svg => (self.terminal.svg(source), "svg"),
+            config::DumpFormat::Html => (self.terminal.html(source), "html"),
+        };
+
+        let result = match self.dump_config.command {
+            Some(ref command) => pipe_to_command(command, text),
+            None if self.dump_config.clipboard => Clipboard::new()
+                .and_then(|mut clipboard| clipboard.store(text, ClipboardBuffer::Primary))
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err)),
+            None => write_temp_file(&text, extension).map(|path| {
+                info!("Dumped terminal contents to {}", path.display());
+            }),
+        };
+
+        if let Err(err) = result {
+            warn!("Unable to dump terminal contents: {}", err);
+        }
+    }
+
+    fn screenshot(&mut self, selection: bool) {
+        let cells = if selection { self.terminal.selection_cells() } else { None };
+        self.terminal.screenshot = Some(match cells {
+            Some((lines, cols)) => Region::Cells(lines, cols),
+            None => Region::Window,
+        });
+    }
+
+    fn start_autoscroll(&mut self, autoscroll: Autoscroll) {
+        self.terminal.autoscroll = Some(autoscroll);
+
+        // Redraw to show the direction indicator
+        self.terminal.damage.damage_all();
+    }
+
+    #[inline]
+    fn autoscroll(&mut self) -> &mut Option<Autoscroll> {
+        &mut self.terminal.autoscroll
+    }
+
+    fn toggle_output_pause(&mut self) {
+        self.notifier.toggle_pause();
+    }
+
+    fn set_color_scheme(&mut self, name: &str) {
+        self.terminal.select_color_scheme(Some(name));
+    }
+
+    fn cycle_color_scheme(&mut self) {
+        self.terminal.cycle_color_scheme();
+    }
+
+    fn toggle_bookmark(&mut self) {
+        self.terminal.toggle_bookmark();
+    }
+
+    fn toggle_forced_effects(&mut self) {
+        self.terminal.toggle_forced_effects();
+    }
+
+    fn toggle_effect_tuning(&mut self) {
+        if let Some(snippet) = self.terminal.toggle_effect_tuning() {
+            keep_effect_style(snippet);
+        }
+    }
+
+    fn toggle_perf_hud(&mut self) {
+        self.terminal.toggle_perf_hud();
+    }
+
+    fn tune_effects(&mut self, key: VirtualKeyCode) -> bool {
+        let terminal = &mut *self.terminal;
+        let tuning = match terminal.effect_tuning {
+            Some(ref mut tuning) => tuning,
+            None => return false,
+        };
+
+        match key {
+            VirtualKeyCode::Up => tuning.select(false),
+            VirtualKeyCode::Down => tuning.select(true),
+            VirtualKeyCode::Left => tuning.adjust(&mut terminal.undo.style, false),
+            VirtualKeyCode::Right => tuning.adjust(&mut terminal.undo.style, true),
+            VirtualKeyCode::Escape => {
+                if let Some(snippet) = terminal.toggle_effect_tuning() {
+                    keep_effect_style(snippet);
+                }
+            },
+            _ => return false,
+        }
+
+        terminal.damage.damage_all();
+        true
+    }
+
+    fn jump_to_annotation(&mut self, forward: bool) {
+        self.terminal.jump_to_annotation(forward);
+    }
+
+    fn playback(&mut self, control: Control) {
+        if let Some(playback) = self.playback {
+            let _ = playback.send(control);
+        }
+    }
+
     fn spawn_new_instance(&mut self) {
         let alacritty = env::args().next().unwrap();
 
-        #[cfg(unix)]
-        let args = {
-            if let Ok(path) = fs::read_link(format!("/proc/{}/cwd", unsafe { tty::PID })) {
-                vec!["--working-directory".into(), path]
-            } else {
-                Vec::new()
-            }
+        let args = match self.shell_pid.and_then(shell_working_directory) {
+            Some(path) => vec!["--working-directory".into(), path],
+            None => Vec::new(),
         };
-        #[cfg(not(unix))]
-        let args: Vec<String> = Vec::new();
 
         match start_daemon(&alacritty, &args) {
             Ok(_) => debug!("Started new Alacritty process: {} {:?}", alacritty, args),
             Err(_) => warn!("Unable to start new Alacritty process: {} {:?}", alacritty, args),
         }
     }
+
+    #[cfg(not(windows))]
+    fn create_new_window(&mut self) {
+        self.window_changes.new_window = true;
+    }
+
+    /// The pty of each window would clobber the agent handle of the others on Windows
+    #[cfg(windows)]
+    fn create_new_window(&mut self) {
+        self.spawn_new_instance();
+    }
+}
+
+/// Current working directory of the shell
+#[cfg(unix)]
+pub fn shell_working_directory(pid: u32) -> Option<PathBuf> {
+    fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+#[cfg(not(unix))]
+pub fn shell_working_directory(_pid: u32) -> Option<PathBuf> {
+    None
+}
+
+/// Copy the config snippet of an adjusted effect style and show it in the message bar
+fn keep_effect_style(snippet: String) {
+    let copied = Clipboard::new()
+        .and_then(|mut clipboard| clipboard.store(snippet.clone(), ClipboardBuffer::Primary));
+    let note = if copied.is_ok() { " (copied to the clipboard)" } else { "" };
+    warn!("Effect style adjusted, add this to the config to keep it{}:\n{}", note, snippet);
+}
+
+/// Write `text` to the stdin of `command` without waiting for it to be read
+fn pipe_to_command(command: &config::CommandWrapper, text: String) -> io::Result<()> {
+    let mut child = Command::new(command.program())
+        .args(command.args())
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().expect("child stdin is piped");
+    thread::spawn_named("dump writer", move || {
+        if let Err(err) = stdin.write_all(text.as_bytes()) {
+            warn!("Unable to write terminal contents to command: {}", err);
+        }
+
+        // Close stdin so the command sees the end of the text
+        drop(stdin);
+        let _ = child.wait();
+    });
+
+    debug!("Writing terminal contents to {} {:?}", command.program(), command.args());
+    Ok(())
+}
+
+/// Write `text` to a new file with the extension `extension` in the temporary directory
+fn write_temp_file(text: &str, extension: &str) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs() * 1000 + u64::from(time.subsec_millis()))
+        .unwrap_or(0);
+    let name = format!("alacritty-{}-{}.{}", process::id(), timestamp, extension);
+    let path = env::temp_dir().join(name);
+
+    File::create(&path)?.write_all(text.as_bytes())?;
+
+    Ok(path)
 }
 
 /// The ActionContext can't really have direct access to the Window
 /// with the current design. Event handlers that want to change the
 /// window must set these flags instead. The processor will trigger
 /// the actual changes.
+///
+/// Closing the window and opening new ones is left to the owner of all windows.
 pub struct WindowChanges {
     pub hide: bool,
+    pub close: bool,
+
+    /// Close all windows
+    pub quit: bool,
+    pub new_window: bool,
+    pub toggle_fullscreen: bool,
+    pub toggle_simple_fullscreen: bool,
+    pub toggle_decorations: bool,
+    pub toggle_always_on_top: bool,
+    pub toggle_sticky: bool,
 }
 
 impl WindowChanges {
     fn clear(&mut self) {
         self.hide = false;
+        self.close = false;
+        self.quit = false;
+        self.new_window = false;
+        self.toggle_fullscreen = false;
+        self.toggle_simple_fullscreen = false;
+        self.toggle_decorations = false;
+        self.toggle_always_on_top = false;
+        self.toggle_sticky = false;
     }
 }
 
@@ -222,6 +526,14 @@ impl Default for WindowChanges {
     fn default() -> WindowChanges {
         WindowChanges {
             hide: false,
+            close: false,
+            quit: false,
+            new_window: false,
+            toggle_fullscreen: false,
+            toggle_simple_fullscreen: false,
+            toggle_decorations: false,
+            toggle_always_on_top: false,
+            toggle_sticky: false,
         }
     }
 }
@@ -281,8 +593,8 @@ pub struct Processor<N> {
     mouse_bindings: Vec<MouseBinding>,
     mouse_config: config::Mouse,
     scrolling_config: config::Scrolling,
+    dump_config: config::Dump,
     print_events: bool,
-    wait_for_event: bool,
     notifier: N,
     mouse: Mouse,
     resize_tx: mpsc::Sender<PhysicalSize>,
@@ -293,10 +605,13 @@ pub struct Processor<N> {
     received_count: usize,
     suppress_chars: bool,
     last_modifiers: ModifiersState,
-    pending_events: Vec<Event>,
     window_changes: WindowChanges,
     save_to_clipboard: bool,
     alt_send_esc: bool,
+    playback: Option<mpsc::Sender<Control>>,
+    shell_pid: Option<u32>,
+    filtered_pastes: (mpsc::Sender<io::Result<String>>, mpsc::Receiver<io::Result<String>>),
+    wakeup: display::Notifier,
 }
 
 /// Notify that the terminal was resized
@@ -315,6 +630,7 @@ impl<N: Notify> Processor<N> {
     /// pty.
     pub fn new(
         notifier: N,
+        wakeup: display::Notifier,
         resize_tx: mpsc::Sender<PhysicalSize>,
         options: &Options,
         config: &Config,
@@ -326,8 +642,8 @@ impl<N: Notify> Processor<N> {
             mouse_bindings: config.mouse_bindings().to_vec(),
             mouse_config: config.mouse().to_owned(),
             scrolling_config: config.scrolling(),
+            dump_config: config.dump().to_owned(),
             print_events: options.print_events,
-            wait_for_event: true,
             notifier,
             resize_tx,
             ref_test,
@@ -338,13 +654,31 @@ impl<N: Notify> Processor<N> {
             received_count: 0,
             suppress_chars: false,
             last_modifiers: Default::default(),
-            pending_events: Vec::with_capacity(4),
             window_changes: Default::default(),
             save_to_clipboard: config.selection().save_to_clipboard,
             alt_send_esc: config.alt_send_esc(),
+            playback: None,
+            shell_pid: None,
+            filtered_pastes: mpsc::channel(),
+            wakeup,
         }
     }
 
+    /// Set the process ID of the shell, used to find its working directory
+    pub fn set_shell_pid(&mut self, pid: Option<u32>) {
+        self.shell_pid = pid;
+    }
+
+    /// Changes requested while processing the last events which concern all windows
+    pub fn window_changes(&self) -> &WindowChanges {
+        &self.window_changes
+    }
+
+    /// Send playback controls to a running playback of a recording
+    pub fn set_playback(&mut self, playback: mpsc::Sender<Control>) {
+        self.playback = Some(playback);
+    }
+
     /// Handle events from glutin
     ///
     /// Doesn't take self mutably due to borrow checking. Kinda uggo but w/e.
@@ -393,8 +727,7 @@ impl<N: Notify> Processor<N> {
                                 .expect("write config.json");
                         }
 
-                        // FIXME should do a more graceful shutdown
-                        ::std::process::exit(0);
+                        processor.ctx.window_changes.close = true;
                     },
                     Resized(lsize) => {
                         // Resize events are emitted via glutin/winit with logical sizes
@@ -403,13 +736,16 @@ impl<N: Notify> Processor<N> {
                         resize_tx
                             .send(lsize.to_physical(processor.ctx.size_info.dpr))
                             .expect("send new size");
-                        processor.ctx.terminal.dirty = true;
+                        processor.ctx.terminal.damage.damage_all();
                     },
                     KeyboardInput { input, .. } => {
                         processor.process_key(input);
                         if input.state == ElementState::Pressed {
                             // Hide cursor while typing
                             *hide_mouse = true;
+
+                            // Keep the text cursor visible while typing
+                            processor.ctx.terminal.reset_cursor_blink();
                         }
                     },
                     ReceivedCharacter(c) => {
@@ -419,7 +755,7 @@ impl<N: Notify> Processor<N> {
                         if !cfg!(target_os = "macos") || *window_is_focused {
                             *hide_mouse = false;
                             processor.mouse_input(state, button, modifiers);
-                            processor.ctx.terminal.dirty = true;
+                            processor.ctx.terminal.damage.damage_all();
                         }
                     },
                     CursorMoved { position: lpos, modifiers, .. } => {
@@ -435,16 +771,16 @@ impl<N: Notify> Processor<N> {
                         processor.on_mouse_wheel(delta, phase, modifiers);
                     },
                     Refresh => {
-                        processor.ctx.terminal.dirty = true;
+                        processor.ctx.terminal.damage.damage_all();
                     },
                     Focused(is_focused) => {
                         *window_is_focused = is_focused;
 
                         if is_focused {
-                            processor.ctx.terminal.dirty = true;
+                            processor.ctx.terminal.damage.damage_all();
                             processor.ctx.terminal.next_is_urgent = Some(false);
                         } else {
-                            processor.ctx.terminal.dirty = true;
+                            processor.ctx.terminal.damage.damage_all();
                             *hide_mouse = false;
                         }
 
@@ -457,55 +793,29 @@ impl<N: Notify> Processor<N> {
                     },
                     HiDpiFactorChanged(new_dpr) => {
                         processor.ctx.size_info.dpr = new_dpr;
-                        processor.ctx.terminal.dirty = true;
+                        processor.ctx.terminal.damage.damage_all();
                     },
                     _ => (),
                 }
             },
-            Event::Awakened => {
-                processor.ctx.terminal.dirty = true;
-            }
+            // Wakeups are shared by all windows, the terminals track their own damage
+            Event::Awakened => (),
         }
     }
 
-    /// Process events. When `wait_for_event` is set, this method is guaranteed
-    /// to process at least one event.
+    /// Process the events which were received for this processor's window
     pub fn process_events<'a>(
         &mut self,
         term: &'a FairMutex<Term>,
-        window: &mut Window
-    ) -> MutexGuard<'a, Term> {
-        // Terminal is lazily initialized the first time an event is returned
-        // from the blocking WaitEventsIterator. Otherwise, the pty reader would
-        // be blocked the entire time we wait for input!
-        let mut terminal;
+        window: &mut Window,
+        events: Vec<Event>,
+    ) -> FairMutexGuard<'a, Term> {
+        let mut terminal = term.lock();
 
-        self.pending_events.clear();
+        self.window_changes.clear();
 
         {
-            // Ditto on lazy initialization for context and processor.
-            let context;
-            let mut processor: input::Processor<'_, ActionContext<'_, N>>;
-
-            let print_events = self.print_events;
-
-            let ref_test = self.ref_test;
-            let resize_tx = &self.resize_tx;
-
-            if self.wait_for_event {
-                // A Vec is used here since wait_events can potentially yield
-                // multiple events before the interrupt is handled. For example,
-                // Resize and Moved events.
-                let pending_events = &mut self.pending_events;
-                window.wait_events(|e| {
-                    pending_events.push(e);
-                    glutin::ControlFlow::Break
-                });
-            }
-
-            terminal = term.lock();
-
-            context = ActionContext {
+            let context = ActionContext {
                 terminal: &mut terminal,
                 notifier: &mut self.notifier,
                 mouse: &mut self.mouse,
@@ -514,9 +824,14 @@ impl<N: Notify> Processor<N> {
                 suppress_chars: &mut self.suppress_chars,
                 last_modifiers: &mut self.last_modifiers,
                 window_changes: &mut self.window_changes,
+                dump_config: &self.dump_config,
+                playback: self.playback.as_ref(),
+                shell_pid: self.shell_pid,
+                filtered_pastes: &self.filtered_pastes.0,
+                wakeup: &self.wakeup,
             };
 
-            processor = input::Processor {
+            let mut processor = input::Processor {
                 ctx: context,
                 scrolling_config: &self.scrolling_config,
                 mouse_config: &self.mouse_config,
@@ -526,33 +841,28 @@ impl<N: Notify> Processor<N> {
                 alt_send_esc: self.alt_send_esc,
             };
 
+            for filtered in self.filtered_pastes.1.try_iter() {
+                processor.paste_filtered(filtered);
+            }
+
             let mut window_is_focused = window.is_focused;
 
-            // Scope needed to that hide_mouse isn't borrowed after the scope
-            // ends.
-            {
-                let hide_mouse = &mut self.hide_mouse;
-                let mut process = |event| {
-                    if print_events {
-                        info!("glutin event: {:?}", event);
-                    }
-                    Processor::handle_event(
-                        &mut processor,
-                        event,
-                        ref_test,
-                        resize_tx,
-                        hide_mouse,
-                        &mut window_is_focused,
-                    );
-                };
-
-                for event in self.pending_events.drain(..) {
-                    process(event);
+            for event in events {
+                if self.print_events {
+                    info!("glutin event: {:?}", event);
                 }
-
-                window.poll_events(process);
+                Processor::handle_event(
+                    &mut processor,
+                    event,
+                    self.ref_test,
+                    &self.resize_tx,
+                    &mut self.hide_mouse,
+                    &mut window_is_focused,
+                );
             }
 
+            processor.autoscroll();
+
             if self.hide_mouse_when_typing {
                 window.set_mouse_visible(!self.hide_mouse);
             }
@@ -564,12 +874,25 @@ impl<N: Notify> Processor<N> {
             window.hide();
         }
 
-        if self.window_changes.hide {
-            window.hide();
+        if self.window_changes.toggle_fullscreen {
+            window.toggle_fullscreen();
         }
 
-        self.window_changes.clear();
-        self.wait_for_event = !terminal.dirty;
+        if self.window_changes.toggle_simple_fullscreen {
+            window.toggle_simple_fullscreen();
+        }
+
+        if self.window_changes.toggle_decorations {
+            window.toggle_decorations();
+        }
+
+        if self.window_changes.toggle_always_on_top {
+            window.toggle_always_on_top();
+        }
+
+        if self.window_changes.toggle_sticky {
+            window.toggle_sticky();
+        }
 
         terminal
     }
@@ -578,6 +901,7 @@ impl<N: Notify> Processor<N> {
         self.key_bindings = config.key_bindings().to_vec();
         self.mouse_bindings = config.mouse_bindings().to_vec();
         self.mouse_config = config.mouse().to_owned();
+        self.dump_config = config.dump().to_owned();
         self.save_to_clipboard = config.selection().save_to_clipboard;
         self.alt_send_esc = config.alt_send_esc();
     }
@@ -1,10 +1,13 @@
 //! The main event loop which performs I/O on the pseudoterminal
 use std::borrow::Cow;
+use std::cmp::min;
 use std::collections::VecDeque;
 use std::io::{self, ErrorKind, Read, Write};
-use std::fs::File;
+use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::marker::Send;
+use std::time::{Duration, Instant};
 
 use mio::{self, Events, PollOpt, Ready};
 use mio_extras::channel::{self, Receiver, Sender};
@@ -13,8 +16,11 @@ use mio_extras::channel::{self, Receiver, Sender};
 use mio::unix::UnixReady;
 
 use crate::ansi;
+use crate::asciicast::Recorder;
 use crate::display;
 use crate::event;
+use crate::logging::OutputHistory;
+use crate::ref_test;
 use crate::tty;
 use crate::term::Term;
 use crate::util::thread;
@@ -26,6 +32,14 @@ pub enum Msg {
     /// Data that should be written to the pty
     Input(Cow<'static, [u8]>),
 
+    /// Bulk data like pastes, written in chunks whenever there is no pending `Input`
+    ///
+    /// `Input` is only written between items, never in the middle of one.
+    BulkInput(Cow<'static, [u8]>),
+
+    /// Stop applying pty output to the terminal, or apply the held output and resume
+    TogglePause,
+
     /// Indicates that the `EventLoop` should shut down, as Alacritty is shutting down
     Shutdown,
 }
@@ -42,6 +56,28 @@ pub struct EventLoop<T: tty::EventedReadWrite> {
     terminal: Arc<FairMutex<Term>>,
     display: display::Notifier,
     ref_test: bool,
+    recorder: Option<Recorder>,
+
+    /// Log every escape sequence read from the pty
+    trace_escapes: bool,
+
+    /// Recent output of the pty, for crash reports
+    output: OutputHistory,
+
+    /// Last time the renderer was woken up for new output
+    last_wakeup: Option<Instant>,
+
+    /// Time at which a wakeup which was delayed to the next frame is due
+    pending_wakeup: Option<Instant>,
+
+    /// Time until which the pty isn't read, while the renderer catches up with the output
+    throttled_until: Option<Instant>,
+
+    /// Process group in the foreground of the pty when output was last read
+    foreground: Option<u32>,
+
+    /// Set once the shell has exited and the loop stopped without being asked to shut down
+    exited: Arc<AtomicBool>,
 }
 
 /// Helper type which tracks how much of a buffer has been written.
@@ -70,28 +106,70 @@ impl DrainResult {
     }
 }
 
+/// List of items to write and the current write state
+#[derive(Default)]
+struct WriteQueue {
+    write_list: VecDeque<Cow<'static, [u8]>>,
+    writing: Option<Writing>,
+}
+
 /// All of the mutable state needed to run the event loop
 ///
 /// Contains list of items to write, current write state, etc. Anything that
 /// would otherwise be mutated on the `EventLoop` goes here.
 pub struct State {
-    write_list: VecDeque<Cow<'static, [u8]>>,
-    writing: Option<Writing>,
+    /// Interactive input, always written before `bulk`
+    input: WriteQueue,
+    bulk: WriteQueue,
     parser: ansi::Processor,
+
+    /// Output read while the terminal is paused, `None` if it isn't paused
+    hold: Option<Vec<u8>>,
+
+    /// Output read while the terminal was locked, which hasn't been parsed yet
+    backlog: Vec<u8>,
+}
+
+/// Changes to the terminal by the output of a `pty_read`
+#[derive(Default, Copy, Clone)]
+struct ReadProgress {
+    /// Output was parsed
+    parsed: bool,
+    /// The renderer has to be woken up
+    send_wakeup: bool,
+    /// The renderer hadn't drawn the previous output yet
+    throttle: bool,
 }
 
 pub struct Notifier(pub Sender<Msg>);
 
-impl event::Notify for Notifier {
-    fn notify<B>(&mut self, bytes: B)
-    where B: Into<Cow<'static, [u8]>>,
-    {
-        let bytes = bytes.into();
+impl Notifier {
+    fn send(&mut self, bytes: Cow<'static, [u8]>, msg: fn(Cow<'static, [u8]>) -> Msg) {
         // terminal hangs if we send 0 bytes through.
         if bytes.len() == 0 {
             return
         }
-        if self.0.send(Msg::Input(bytes)).is_err() {
+        if self.0.send(msg(bytes)).is_err() {
+            panic!("expected send event loop msg");
+        }
+    }
+}
+
+impl event::Notify for Notifier {
+    fn notify<B>(&mut self, bytes: B)
+    where B: Into<Cow<'static, [u8]>>,
+    {
+        self.send(bytes.into(), Msg::Input);
+    }
+
+    fn notify_bulk<B>(&mut self, bytes: B)
+    where B: Into<Cow<'static, [u8]>>,
+    {
+        self.send(bytes.into(), Msg::BulkInput);
+    }
+
+    fn toggle_pause(&mut self) {
+        if self.0.send(Msg::TogglePause).is_err() {
             panic!("expected send event loop msg");
         }
     }
@@ -100,14 +178,29 @@ impl event::Notify for Notifier {
 impl Default for State {
     fn default() -> State {
         State {
-            write_list: VecDeque::new(),
+            input: WriteQueue::default(),
+            bulk: WriteQueue::default(),
             parser: ansi::Processor::new(),
-            writing: None,
+            hold: None,
+            backlog: Vec::new(),
         }
     }
 }
 
 impl State {
+    #[inline]
+    fn needs_write(&self) -> bool {
+        self.input.needs_write() || self.bulk.needs_write()
+    }
+
+    /// Whether the output is paused and no more of it can be held
+    #[inline]
+    fn hold_full(&self) -> bool {
+        self.hold.as_ref().map_or(false, |hold| hold.len() >= MAX_HOLD)
+    }
+}
+
+impl WriteQueue {
     #[inline]
     fn ensure_next(&mut self) {
         if self.writing.is_none() {
@@ -163,6 +256,98 @@ impl Writing {
 /// `mio::Token` for the event loop channel
 const CHANNEL: mio::Token = mio::Token(0);
 
+/// Time the renderer is given to catch up before more output is read
+const THROTTLE_DURATION: Duration = Duration::from_millis(4);
+
+/// Time of a frame at 60 frames per second
+///
+/// Output is read for at most a frame before the lock is released for the renderer, which is
+/// woken up at most once per frame. A burst of output is drawn once instead of after every read.
+const FRAME_INTERVAL: Duration = Duration::from_micros(16_667);
+
+/// Maximum number of bytes held while the output is paused
+///
+/// Once this is reached, nothing is read from the pty anymore and the child blocks on its writes
+/// until the output is resumed.
+const MAX_HOLD: usize = 0x40_0000;
+
+/// Maximum number of bytes read while the terminal is locked, before reading waits for the lock
+const MAX_BACKLOG: usize = 0x10_0000;
+
+/// Time after which parsing the back buffer is tried again, if no more output arrives
+const BACKLOG_RETRY: Duration = Duration::from_millis(1);
+
+/// Parse and record the output in the back buffer
+fn parse_backlog<W: Write>(
+    terminal: &mut Term,
+    state: &mut State,
+    recorder: Option<&mut Recorder>,
+    writer: &mut W,
+) {
+    if state.backlog.is_empty() {
+        return;
+    }
+
+    if let Some(recorder) = recorder {
+        let size = terminal.size_info();
+        recorder.resize(size.cols().0, size.lines().0);
+        recorder.output(&state.backlog);
+    }
+
+    terminal.finish_banner();
+    let parse_start = Instant::now();
+    state.parser.advance_bytes(terminal, &state.backlog, writer);
+    if let Some(ref mut perf_hud) = terminal.perf_hud {
+        perf_hud.parsed(state.backlog.len(), parse_start.elapsed());
+    }
+
+    state.backlog.clear();
+}
+
+/// Write items from `queue` until it is empty, the writer would block or `max` bytes were written
+///
+/// Returns `true` if there is data left in the queue. The remaining writes are picked up again
+/// once the pty is writable, giving the child a chance to respond before sending more.
+fn write_queue<W: Write>(writer: &mut W, queue: &mut WriteQueue, max: usize) -> io::Result<bool> {
+    let mut written = 0;
+
+    queue.ensure_next();
+
+    'write_many: while let Some(mut current) = queue.take_current() {
+        'write_one: loop {
+            if written >= max {
+                queue.set_current(Some(current));
+                break 'write_many;
+            }
+
+            let len = min(current.remaining_bytes().len(), max - written);
+            match writer.write(&current.remaining_bytes()[..len]) {
+                Ok(0) => {
+                    queue.set_current(Some(current));
+                    break 'write_many;
+                }
+                Ok(n) => {
+                    written += n;
+                    current.advance(n);
+                    if current.finished() {
+                        queue.goto_next();
+                        break 'write_one;
+                    }
+                }
+                Err(err) => {
+                    queue.set_current(Some(current));
+                    match err.kind() {
+                        ErrorKind::Interrupted | ErrorKind::WouldBlock => break 'write_many,
+                        _ => return Err(err),
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(queue.needs_write())
+}
+
 impl<T> EventLoop<T>
     where
         T: tty::EventedReadWrite + Send + 'static,
@@ -173,6 +358,8 @@ impl<T> EventLoop<T>
         display: display::Notifier,
         pty: T,
         ref_test: bool,
+        trace_escapes: bool,
+        recorder: Option<Recorder>,
     ) -> EventLoop<T> {
         let (tx, rx) = channel::channel();
         EventLoop {
@@ -183,9 +370,34 @@ impl<T> EventLoop<T>
             terminal,
             display,
             ref_test,
+            recorder,
+            trace_escapes,
+            output: OutputHistory::default(),
+            last_wakeup: None,
+            pending_wakeup: None,
+            throttled_until: None,
+            foreground: None,
+            exited: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Flag which is raised when the shell exits
+    ///
+    /// The display is woken up after the flag was raised, so the window can be closed.
+    pub fn exited(&self) -> Arc<AtomicBool> {
+        self.exited.clone()
+    }
+
+    /// Recent output of the pty
+    pub fn output_history(&self) -> OutputHistory {
+        self.output.clone()
+    }
+
+    /// Take the session recording, so it can be continued by the next event loop
+    pub fn into_recorder(self) -> Option<Recorder> {
+        self.recorder
+    }
+
     pub fn channel(&self) -> Sender<Msg> {
         self.tx.clone()
     }
@@ -194,13 +406,19 @@ impl<T> EventLoop<T>
     //
     // Returns a `DrainResult` indicating the result of receiving from the channel
     //
-    fn drain_recv_channel(&self, state: &mut State) -> DrainResult {
+    fn drain_recv_channel(&mut self, state: &mut State) -> DrainResult {
         let mut received_item = false;
         while let Ok(msg) = self.rx.try_recv() {
             received_item = true;
             match msg {
                 Msg::Input(input) => {
-                    state.write_list.push_back(input);
+                    state.input.write_list.push_back(input);
+                }
+                Msg::BulkInput(input) => {
+                    state.bulk.write_list.push_back(input);
+                }
+                Msg::TogglePause => {
+                    self.toggle_pause(state);
                 }
                 Msg::Shutdown => {
                     return DrainResult::Shutdown;
@@ -229,6 +447,32 @@ impl<T> EventLoop<T>
         true
     }
 
+    /// Pause the output, or apply everything which was held back and resume
+    ///
+    /// Requests from the child, like device attribute queries, are only answered once the output
+    /// is resumed.
+    fn toggle_pause(&mut self, state: &mut State) {
+        let mut terminal = self.terminal.lock();
+
+        // Output read before the pause is applied before the held output
+        parse_backlog(&mut terminal, state, self.recorder.as_mut(), &mut self.pty.writer());
+
+        match state.hold.take() {
+            Some(hold) => {
+                debug!("Resuming output with {} held bytes", hold.len());
+                state.parser.advance_bytes(&mut *terminal, &hold, &mut self.pty.writer());
+            },
+            None => {
+                debug!("Pausing output");
+                state.hold = Some(Vec::new());
+            },
+        }
+
+        terminal.set_output_paused(state.hold.is_some());
+        terminal.damage.damage_all();
+        self.display.notify();
+    }
+
     #[inline]
     fn pty_read<X>(
         &mut self,
@@ -239,19 +483,24 @@ impl<T> EventLoop<T>
         where
             X: Write,
     {
-        const MAX_READ: usize = 0x1_0000;
-        let mut processed = 0;
-        let mut terminal = None;
-
-        // Flag to keep track if wakeup has already been sent
-        let mut send_wakeup = false;
+        let started = Instant::now();
+        let mut budget_exceeded = false;
+        let mut progress = ReadProgress::default();
 
         loop {
-            match self.pty.reader().read(&mut buf[..]) {
+            // Stop reading once the hold buffer is full
+            let len = match state.hold {
+                Some(ref hold) => min(buf.len(), MAX_HOLD - hold.len()),
+                None => buf.len(),
+            };
+            if len == 0 {
+                break;
+            }
+
+            match self.pty.reader().read(&mut buf[..len]) {
                 Ok(0) => break,
                 Ok(got) => {
-                    // Record bytes read; used to limit time spent in pty_read.
-                    processed += got;
+                    self.output.push(&buf[..got]);
 
                     // Send a copy of bytes read to a subscriber. Used for
                     // example with ref test recording.
@@ -260,27 +509,21 @@ impl<T> EventLoop<T>
                         w
                     });
 
-                    // Get reference to terminal. Lock is acquired on initial
-                    // iteration and held until there's no bytes left to parse
-                    // or we've reached MAX_READ.
-                    let terminal = if terminal.is_none() {
-                        terminal = Some(self.terminal.lock());
-                        let terminal = terminal.as_mut().unwrap();
-                        send_wakeup = !terminal.dirty;
-                        terminal
+                    // Keep the output away from the terminal while it is paused
+                    if let Some(ref mut hold) = state.hold {
+                        if let Some(ref mut recorder) = self.recorder {
+                            recorder.output(&buf[..got]);
+                        }
+
+                        hold.extend_from_slice(&buf[..got]);
                     } else {
-                        terminal.as_mut().unwrap()
-                    };
-
-                    // Run the parser
-                    for byte in &buf[..got] {
-                        state
-                            .parser
-                            .advance(&mut **terminal, *byte, &mut self.pty.writer());
+                        state.backlog.extend_from_slice(&buf[..got]);
+                        self.try_parse_backlog(state, &mut progress, false);
                     }
 
-                    // Exit if we've processed enough bytes
-                    if processed > MAX_READ {
+                    // Let the renderer draw what was parsed within this frame
+                    if started.elapsed() >= FRAME_INTERVAL {
+                        budget_exceeded = true;
                         break;
                     }
                 }
@@ -293,44 +536,112 @@ impl<T> EventLoop<T>
             }
         }
 
+        self.finish_read(progress);
+
+        // The previous output still hasn't been drawn and there is more to read. Instead of
+        // holding on to the terminal, stop reading to give the renderer a chance to catch up so
+        // the window stays responsive while a program is flooding the terminal. Input is still
+        // written in the meantime, so the program can be interrupted.
+        if progress.throttle && budget_exceeded {
+            trace!("Throttling pty reads");
+            self.throttled_until = Some(Instant::now() + THROTTLE_DURATION);
+        }
+
+        Ok(())
+    }
+
+    /// Parse the back buffer, unless the renderer or the animation hold the terminal
+    ///
+    /// The pty is drained into the back buffer while the terminal is locked, so the child never
+    /// waits for a frame to be drawn. Once the back buffer is full or if `wait` is set, this waits
+    /// for the lock.
+    fn try_parse_backlog(&mut self, state: &mut State, progress: &mut ReadProgress, wait: bool) {
+        let terminal = if wait || state.backlog.len() >= MAX_BACKLOG {
+            Some(self.terminal.lock())
+        } else {
+            self.terminal.try_lock()
+        };
+        let mut terminal = match terminal {
+            Some(terminal) => terminal,
+            None => return,
+        };
+
+        // A wakeup is only needed if no draw is pending, either because none was
+        // requested yet or because the renderer drew since the last read
+        if !terminal.needs_draw() {
+            progress.send_wakeup = true;
+        }
+        if !progress.parsed {
+            progress.parsed = true;
+            progress.throttle = terminal.auto_throttle() && !progress.send_wakeup;
+        }
+
+        parse_backlog(&mut terminal, state, self.recorder.as_mut(), &mut self.pty.writer());
+    }
+
+    /// Update the terminal after output was parsed and wake up the renderer
+    fn finish_read(&mut self, progress: ReadProgress) {
         // Only request a draw if one hasn't already been requested.
-        if let Some(mut terminal) = terminal {
-            if send_wakeup {
-                self.display.notify();
-                terminal.dirty = true;
+        if progress.parsed {
+            let mut terminal = self.terminal.lock();
+            // Switch profiles when a job was started or stopped by the shell
+            let foreground = self.pty.foreground_process_group();
+            if foreground != self.foreground {
+                self.foreground = foreground;
+                terminal.set_foreground_process(foreground.and_then(tty::process_name));
+            }
+
+            // Only the written lines have to be redrawn
+            terminal.flush_damage();
+            drop(terminal);
+            if progress.send_wakeup {
+                self.schedule_wakeup();
             }
         }
+    }
 
-        Ok(())
+    /// Wake up the renderer, or delay the wakeup until a frame has passed since the last one
+    fn schedule_wakeup(&mut self) {
+        let now = Instant::now();
+        match self.last_wakeup.map(|last| last + FRAME_INTERVAL) {
+            Some(due) if due > now => self.pending_wakeup = Some(due),
+            _ => {
+                self.last_wakeup = Some(now);
+                self.display.notify();
+            },
+        }
+    }
+
+    /// Send a delayed wakeup once it's due, returns how long it's still delayed otherwise
+    fn flush_wakeup(&mut self) -> Option<Duration> {
+        let due = self.pending_wakeup?;
+        let now = Instant::now();
+        if due > now {
+            return Some(due - now);
+        }
+
+        self.pending_wakeup = None;
+        self.last_wakeup = Some(now);
+        self.display.notify();
+        None
     }
 
     #[inline]
     fn pty_write(&mut self, state: &mut State) -> io::Result<()> {
-        state.ensure_next();
-
-        'write_many: while let Some(mut current) = state.take_current() {
-            'write_one: loop {
-                match self.pty.writer().write(current.remaining_bytes()) {
-                    Ok(0) => {
-                        state.set_current(Some(current));
-                        break 'write_many;
-                    }
-                    Ok(n) => {
-                        current.advance(n);
-                        if current.finished() {
-                            state.goto_next();
-                            break 'write_one;
-                        }
-                    }
-                    Err(err) => {
-                        state.set_current(Some(current));
-                        match err.kind() {
-                            ErrorKind::Interrupted | ErrorKind::WouldBlock => break 'write_many,
-                            _ => return Err(err),
-                        }
-                    }
-                }
-            }
+        const MAX_WRITE: usize = 0x1_0000;
+
+        // Bulk data is only written in small chunks, so keystrokes which arrive during a large
+        // paste are picked up on the next wakeup instead of waiting for the entire paste
+        const MAX_BULK_WRITE: usize = 0x1000;
+
+        let writer = self.pty.writer();
+
+        // A bulk item is finished once it was started, so keystrokes can't end up inside of a
+        // bracketed paste
+        if state.bulk.writing.as_ref().map_or(false, |writing| writing.written > 0) {
+            write_queue(writer, &mut state.bulk, MAX_BULK_WRITE)?;
+        } else if !write_queue(writer, &mut state.input, MAX_WRITE)? {
+            write_queue(writer, &mut state.bulk, MAX_BULK_WRITE)?;
         }
 
         Ok(())
@@ -339,6 +650,7 @@ impl<T> EventLoop<T>
     pub fn spawn(mut self, state: Option<State>) -> thread::JoinHandle<(Self, State)> {
         thread::spawn_named("pty reader", move || {
             let mut state = state.unwrap_or_else(Default::default);
+            state.parser.set_trace(self.trace_escapes);
             let mut buf = [0u8; 0x1000];
 
             let poll_opts = PollOpt::edge() | PollOpt::oneshot();
@@ -356,13 +668,32 @@ impl<T> EventLoop<T>
             let mut events = Events::with_capacity(1024);
 
             let mut pipe = if self.ref_test {
-                Some(File::create("./alacritty.recording").expect("create alacritty recording"))
+                Some(ref_test::Writer::create(Path::new(".")).expect("create alacritty recording"))
             } else {
                 None
             };
 
+            let mut shutdown = false;
+
             'event_loop: loop {
-                if let Err(err) = self.poll.poll(&mut events, None) {
+                // Retry parsing output which was read while the terminal was locked
+                if !state.backlog.is_empty() {
+                    let mut progress = ReadProgress::default();
+                    self.try_parse_backlog(&mut state, &mut progress, false);
+                    self.finish_read(progress);
+                }
+
+                // Wait for the next frame at most, if the renderer still has to be woken up
+                let mut timeout = self.flush_wakeup();
+                if !state.backlog.is_empty() {
+                    timeout = Some(timeout.map_or(BACKLOG_RETRY, |t| min(t, BACKLOG_RETRY)));
+                }
+                if let Some(until) = self.throttled_until {
+                    let now = Instant::now();
+                    let throttle = if until > now { until - now } else { Duration::from_secs(0) };
+                    timeout = Some(timeout.map_or(throttle, |t| min(t, throttle)));
+                }
+                if let Err(err) = self.poll.poll(&mut events, timeout) {
                     match err.kind() {
                         ErrorKind::Interrupted => continue,
                         _ => panic!("EventLoop polling error: {:?}", err),
@@ -372,6 +703,7 @@ impl<T> EventLoop<T>
                 for event in events.iter() {
                     match event.token() {
                         CHANNEL => if !self.channel_event(&mut state) {
+                            shutdown = true;
                             break 'event_loop;
                         },
                         token if token == self.pty.read_token() || token == self.pty.write_token() => {
@@ -393,7 +725,7 @@ impl<T> EventLoop<T>
                                         break 'event_loop;
                                     }
 
-                                if crate::tty::process_should_exit() {
+                                if self.pty.child_exited() {
                                     break 'event_loop;
                                 }
                             }
@@ -414,8 +746,17 @@ impl<T> EventLoop<T>
                     }
                 }
 
-                // Register write interest if necessary
-                let mut interest = Ready::readable();
+                if self.throttled_until.map_or(false, |until| until <= Instant::now()) {
+                    self.throttled_until = None;
+                }
+
+                // Register write interest if necessary, reading stops while the hold buffer is full
+                // or the renderer is catching up
+                let mut interest = if state.hold_full() || self.throttled_until.is_some() {
+                    Ready::empty()
+                } else {
+                    Ready::readable()
+                };
                 if state.needs_write() {
                     interest.insert(Ready::writable());
                 }
@@ -423,11 +764,23 @@ impl<T> EventLoop<T>
                 self.pty.reregister(&self.poll, interest, poll_opts).unwrap();
             }
 
+            // The last output before the shell exited must not be lost
+            if !state.backlog.is_empty() {
+                let mut progress = ReadProgress::default();
+                self.try_parse_backlog(&mut state, &mut progress, true);
+                self.finish_read(progress);
+            }
+
             // The evented instances are not dropped here so deregister them explicitly
             // TODO: Is this still necessary?
             let _ = self.poll.deregister(&self.rx);
             let _ = self.pty.deregister(&self.poll);
 
+            if !shutdown {
+                self.exited.store(true, Ordering::Relaxed);
+                self.display.notify();
+            }
+
             (self, state)
         })
     }
@@ -15,6 +15,8 @@
 //! A specialized 2d grid implementation optimized for use in a terminal.
 
 use std::cmp::{min, max, Ordering};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::ops::{Deref, Range, Index, IndexMut, RangeTo, RangeFrom, RangeFull};
 
 use crate::index::{self, Point, Line, Column, IndexRange};
@@ -36,6 +38,37 @@ pub trait BidirectionalIterator: Iterator {
     fn prev(&mut self) -> Option<Self::Item>;
 }
 
+/// Cell state required by the grid for rewrapping lines on resize
+pub trait GridCell {
+    /// Check if the cell has no content
+    fn is_empty(&self) -> bool;
+
+    /// Check if the line continues on the next row after this cell
+    fn is_wrap(&self) -> bool;
+
+    /// Set or clear the line wrapping flag
+    fn set_wrap(&mut self, wrap: bool);
+
+    /// Check if the cell holds a character which is two cells wide
+    fn is_wide(&self) -> bool;
+
+    /// Check if the cell pads the end of a row, since the wide char after it didn't fit
+    fn is_leading_spacer(&self) -> bool;
+
+    /// Turn the cell into padding for a wide char which was moved to the next row
+    fn set_leading_spacer(&mut self);
+}
+
+/// Annotation of a cell, like a bookmark or a mark set by an external tool
+///
+/// Annotations move along with their cell when the grid scrolls or is reflowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// Position in the buffer, lines are counted from the bottom of the screen
+    pub point: Point<usize>,
+    pub label: Option<String>,
+}
+
 /// An item in the grid along with its Line and Column.
 pub struct Indexed<T> {
     pub inner: T,
@@ -97,6 +130,21 @@ pub struct Grid<T> {
 
     #[serde(default)]
     max_scroll_limit: usize,
+
+    /// Counter bumped on every resize
+    ///
+    /// Writers outside of the terminal remember this to detect that coordinates they computed
+    /// earlier might not be valid for the current dimensions anymore.
+    #[serde(skip)]
+    generation: usize,
+
+    /// Annotated cells, in no particular order
+    #[serde(skip)]
+    annotations: Vec<Annotation>,
+
+    /// Lines of the active region which were written since the damage was last taken
+    #[serde(skip)]
+    damaged_lines: Vec<bool>,
 }
 
 pub struct GridIterator<'a, T> {
@@ -123,7 +171,7 @@ pub enum ViewportPosition {
     Below,
 }
 
-impl<T: Copy + Clone> Grid<T> {
+impl<T: Copy + Clone + PartialEq> Grid<T> {
     pub fn new(lines: index::Line, cols: index::Column, scrollback: usize, template: T) -> Grid<T> {
         let raw = Storage::with_capacity(lines, Row::new(cols, &template));
         Grid {
@@ -134,6 +182,9 @@ impl<T: Copy + Clone> Grid<T> {
             scroll_limit: 0,
             selection: None,
             max_scroll_limit: scrollback,
+            generation: 0,
+            annotations: Vec::new(),
+            damaged_lines: vec![false; lines.0],
         }
     }
 
@@ -167,6 +218,8 @@ impl<T: Copy + Clone> Grid<T> {
     }
 
     pub fn scroll_display(&mut self, scroll: Scroll) {
+        let old_offset = self.display_offset;
+
         match scroll {
             Scroll::Lines(count) => {
                 self.display_offset = min(
@@ -189,29 +242,30 @@ impl<T: Copy + Clone> Grid<T> {
             Scroll::Top => self.display_offset = self.scroll_limit,
             Scroll::Bottom => self.display_offset = 0,
         }
-    }
 
-    pub fn resize(
-        &mut self,
-        lines: index::Line,
-        cols: index::Column,
-        template: &T,
-    ) {
-        // Check that there's actually work to do and return early if not
-        if lines == self.lines && cols == self.cols {
-            return;
-        }
+        self.unpack_viewport();
+        self.pack_history(old_offset..old_offset + *self.lines);
+    }
 
-        match self.lines.cmp(&lines) {
-            Ordering::Less => self.grow_lines(lines, template),
-            Ordering::Greater => self.shrink_lines(lines),
-            Ordering::Equal => (),
+    /// Unpack the rows in the viewport, rows are unpacked while they're visible
+    fn unpack_viewport(&mut self) {
+        for i in self.display_offset..self.display_offset + *self.lines {
+            self.raw[i].unpack();
         }
+    }
 
-        match self.cols.cmp(&cols) {
-            Ordering::Less => self.grow_cols(cols, template),
-            Ordering::Greater => self.shrink_cols(cols),
-            Ordering::Equal => (),
+    /// Pack the rows of `range` which are history more than a screen above the terminal
+    ///
+    /// Rows in the viewport are left alone, they're unpacked once they're written to.
+    fn pack_history(&mut self, range: Range<usize>) {
+        let lines = *self.lines;
+        let start = max(range.start, 2 * lines);
+        let end = min(range.end, lines + self.scroll_limit);
+
+        for i in start..end {
+            if i < self.display_offset || i >= self.display_offset + lines {
+                self.raw[i].pack();
+            }
         }
     }
 
@@ -288,6 +342,7 @@ impl<T: Copy + Clone> Grid<T> {
 
         self.selection = None;
         self.raw.rotate(*prev as isize - *target as isize);
+        self.move_annotations(*target as isize - *prev as isize);
         self.raw.shrink_visible_lines(target);
         self.lines = target;
     }
@@ -310,6 +365,8 @@ impl<T: Copy + Clone> Grid<T> {
         positions: index::Line,
         template: &T,
     ) {
+        self.damage_lines(region.clone());
+
         // Whether or not there is a scrolling region active, as long as it
         // starts at the top, we can do a full rotation which just involves
         // changing the start index.
@@ -322,6 +379,7 @@ impl<T: Copy + Clone> Grid<T> {
             if let Some(ref mut selection) = self.selection {
                 selection.rotate(-(*positions as isize));
             }
+            self.move_annotations(-(*positions as isize));
 
             self.decrease_scroll_limit(*positions);
 
@@ -356,6 +414,8 @@ impl<T: Copy + Clone> Grid<T> {
         positions: index::Line,
         template: &T
     ) {
+        self.damage_lines(region.clone());
+
         if region.start == Line(0) {
             // Update display offset when not pinned to active area
             if self.display_offset != 0 {
@@ -373,6 +433,7 @@ impl<T: Copy + Clone> Grid<T> {
             if let Some(ref mut selection) = self.selection {
                 selection.rotate(*positions as isize);
             }
+            self.move_annotations(*positions as isize);
 
             // // This next loop swaps "fixed" lines outside of a scroll region
             // // back into place after the rotation. The work is done in buffer-
@@ -390,6 +451,10 @@ impl<T: Copy + Clone> Grid<T> {
             for i in 0..*positions {
                 self.raw[i + fixed_lines].reset(&template);
             }
+
+            // Rows which scrolled a screen above the terminal are packed
+            let start = 2 * *self.lines;
+            self.pack_history(start..start + *positions);
         } else {
             // Subregion rotation
             for line in IndexRange(region.start..(region.end - positions)) {
@@ -404,6 +469,206 @@ impl<T: Copy + Clone> Grid<T> {
     }
 }
 
+impl<T: GridCell + Copy + Clone + PartialEq> Grid<T> {
+    /// Resize the grid to the new dimensions
+    ///
+    /// When `reflow` is set, wrapped lines are rewrapped to fit the new number of columns
+    /// and `cursor_pos` is moved along with the content it's positioned on.
+    pub fn resize(
+        &mut self,
+        reflow: bool,
+        lines: index::Line,
+        cols: index::Column,
+        cursor_pos: &mut Point,
+        template: &T,
+    ) {
+        // Check that there's actually work to do and return early if not
+        if lines == self.lines && cols == self.cols {
+            return;
+        }
+
+        self.generation = self.generation.wrapping_add(1);
+
+        match self.lines.cmp(&lines) {
+            Ordering::Less => self.grow_lines(lines, template),
+            Ordering::Greater => self.shrink_lines(lines),
+            Ordering::Equal => (),
+        }
+
+        if reflow && cols != self.cols {
+            self.reflow(cols, cursor_pos, template);
+        } else {
+            match self.cols.cmp(&cols) {
+                Ordering::Less => self.grow_cols(cols, template),
+                Ordering::Greater => self.shrink_cols(cols),
+                Ordering::Equal => (),
+            }
+        }
+
+        // History rows might have entered the viewport or moved up into the packed history
+        self.unpack_viewport();
+        let end = *self.lines + self.scroll_limit;
+        self.pack_history(0..end);
+    }
+
+    /// Rewrap all lines in the buffer to fit into `cols` columns
+    ///
+    /// Rows which have been wrapped are joined back into their logical line and split again
+    /// at the new width. This includes all lines in the scrollback history.
+    fn reflow(&mut self, cols: index::Column, cursor_pos: &mut Point, template: &T) {
+        let old_cols = self.cols;
+        let history = self.scroll_limit;
+
+        // Collect all rows in use, from the top of the history to the bottom of the screen
+        let total = *self.lines + history;
+        let rows: Vec<Row<T>> = (0..total).rev().map(|i| self.raw[i].clone()).collect();
+
+        let cursor_line = min(cursor_pos.line, self.lines - 1);
+        let cursor_row = history + *cursor_line;
+
+        // Empty rows below the cursor are just padding and don't need to be rewrapped
+        let last_row = rows.iter()
+            .rposition(|row| row.iter().any(|cell| !cell.is_empty()))
+            .map_or(cursor_row, |row| max(row, cursor_row));
+
+        // Annotations move like the cursor, through their offset within their logical line
+        let annotations: Vec<Annotation> = self.annotations.drain(..).collect();
+        let mut positions: Vec<Option<(usize, usize)>> = vec![None; annotations.len()];
+
+        // Join wrapped rows into logical lines and keep track of the cursor within them
+        let mut logical_lines: Vec<Vec<T>> = Vec::new();
+        let mut cursor = (0, cursor_pos.col.0);
+        let mut wrapped = false;
+        for (i, row) in rows.into_iter().take(last_row + 1).enumerate() {
+            if !wrapped {
+                logical_lines.push(Vec::with_capacity(*old_cols));
+            }
+
+            let index = logical_lines.len() - 1;
+            let line = &mut logical_lines[index];
+
+            if i == cursor_row {
+                cursor = (index, line.len() + min(*cursor_pos.col, *old_cols - 1));
+            }
+
+            for (annotation, position) in annotations.iter().zip(&mut positions) {
+                if annotation.point.line < total && total - 1 - annotation.point.line == i {
+                    let offset = line.len() + min(*annotation.point.col, *old_cols - 1);
+                    *position = Some((index, offset));
+                }
+            }
+
+            wrapped = row[old_cols - 1].is_wrap();
+            line.extend(row.iter().cloned());
+            if let Some(cell) = line.last_mut() {
+                cell.set_wrap(false);
+            }
+
+            // Padding in front of a wrapped wide char is recreated when the line is split again
+            if wrapped && line.last().map_or(false, |cell| cell.is_leading_spacer()) {
+                line.pop();
+            }
+        }
+
+        // Split logical lines at the new width
+        let mut new_rows: Vec<Row<T>> = Vec::with_capacity(total);
+        let mut new_cursor = (0, 0);
+        let mut points: Vec<Option<Point<usize>>> = vec![None; annotations.len()];
+        for (index, mut line) in logical_lines.into_iter().enumerate() {
+            // Trailing empty cells would only produce empty rows, but keep everything up to
+            // the cursor so it doesn't end up outside of its line
+            let min_len = if index == cursor.0 { cursor.1 + 1 } else { 1 };
+            while line.len() > min_len && line.last().map_or(false, |cell| cell.is_empty()) {
+                line.pop();
+            }
+
+            // Offsets in the line at which each of its rows starts
+            let start = new_rows.len();
+            let mut breaks = Vec::new();
+            let mut offset = 0;
+            while offset < line.len() {
+                breaks.push(offset);
+                let mut end = min(offset + *cols, line.len());
+
+                // A wide char in the last column would be split from its spacer, so it's moved
+                // to the next row and the row is padded with a leading spacer instead
+                let split_wide = end < line.len() && end - offset > 1 && line[end - 1].is_wide();
+                if split_wide {
+                    end -= 1;
+                }
+
+                let mut cells = line[offset..end].to_vec();
+                if split_wide {
+                    let mut spacer = *template;
+                    spacer.set_leading_spacer();
+                    cells.push(spacer);
+                }
+                cells.resize(*cols, *template);
+
+                if end < line.len() {
+                    cells[*cols - 1].set_wrap(true);
+                }
+
+                new_rows.push(Row::from_vec(cells, end - offset));
+                offset = end;
+            }
+
+            // Position of the cell at `offset` in the line, after it was split
+            let locate = |offset: usize| {
+                let row = breaks.iter().rposition(|&start| start <= offset).unwrap_or(0);
+                (start + row, min(offset - breaks[row], *cols - 1))
+            };
+
+            if index == cursor.0 {
+                new_cursor = locate(cursor.1);
+            }
+
+            // Annotations after the trimmed end of their line stay on its last row
+            for (position, point) in positions.iter().zip(&mut points) {
+                match *position {
+                    Some((line_index, offset)) if line_index == index => {
+                        let (row, col) = locate(offset);
+                        *point = Some(Point::new(row, Column(col)));
+                    },
+                    _ => (),
+                }
+            }
+        }
+
+        // Fill the visible area with empty rows
+        while new_rows.len() < *self.lines {
+            new_rows.push(Row::new(cols, template));
+        }
+
+        // Drop rows which don't fit into the scrollback history anymore
+        let history = min(new_rows.len() - *self.lines, self.max_scroll_limit);
+        let overflow = new_rows.len() - *self.lines - history;
+        new_rows.drain(..overflow);
+
+        cursor_pos.line = Line(new_cursor.0.saturating_sub(overflow + history));
+        cursor_pos.col = Column(new_cursor.1);
+
+        let rows = new_rows.len();
+        self.annotations = annotations
+            .into_iter()
+            .zip(points)
+            .filter_map(|(mut annotation, point)| {
+                let point = point.filter(|point| point.line >= overflow)?;
+                annotation.point = Point::new(rows - 1 - (point.line - overflow), point.col);
+                Some(annotation)
+            })
+            .collect();
+
+        // Storage expects the bottom line of the screen to come first
+        new_rows.reverse();
+        self.raw.replace_inner(new_rows);
+
+        self.scroll_limit = history;
+        self.display_offset = min(self.display_offset, history);
+        self.cols = cols;
+    }
+}
+
 #[allow(clippy::len_without_is_empty)]
 impl<T> Grid<T> {
     #[inline]
@@ -422,6 +687,75 @@ impl<T> Grid<T> {
 
     pub fn clear_history(&mut self) {
         self.scroll_limit = 0;
+        self.move_annotations(0);
+    }
+
+    #[inline]
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    /// Annotate a cell, replacing an earlier annotation of the same cell
+    pub fn annotate(&mut self, annotation: Annotation) {
+        self.annotations.retain(|old| old.point != annotation.point);
+        self.annotations.push(annotation);
+    }
+
+    /// Remove all annotations matching `predicate`
+    pub fn remove_annotations<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&Annotation) -> bool,
+    {
+        self.annotations.retain(|annotation| !predicate(annotation));
+    }
+
+    /// Move annotations by `delta` lines, dropping the ones which left the buffer
+    fn move_annotations(&mut self, delta: isize) {
+        let end = (*self.lines + self.scroll_limit) as isize;
+        self.annotations.retain(|annotation| {
+            let line = annotation.point.line as isize + delta;
+            line >= 0 && line < end
+        });
+
+        for annotation in &mut self.annotations {
+            annotation.point.line = (annotation.point.line as isize + delta) as usize;
+        }
+    }
+
+    #[inline]
+    pub fn display_offset(&self) -> usize {
+        self.display_offset
+    }
+
+    /// Mark lines of the active region as written
+    #[inline]
+    fn damage_lines(&mut self, lines: Range<Line>) {
+        let num_lines = self.lines.0;
+        self.damaged_lines.resize(num_lines, false);
+
+        let end = min(lines.end.0, num_lines);
+        for damaged in &mut self.damaged_lines[min(lines.start.0, end)..end] {
+            *damaged = true;
+        }
+    }
+
+    /// Take the lines of the active region written since the last call
+    ///
+    /// Adjacent lines are merged into a single range.
+    pub fn take_damage(&mut self) -> Vec<Range<Line>> {
+        let mut damage: Vec<Range<Line>> = Vec::new();
+        for (line, damaged) in self.damaged_lines.iter_mut().enumerate() {
+            if !*damaged {
+                continue;
+            }
+            *damaged = false;
+
+            match damage.last_mut() {
+                Some(last) if last.end == Line(line) => last.end += 1,
+                _ => damage.push(Line(line)..Line(line + 1)),
+            }
+        }
+        damage
     }
 
     #[inline]
@@ -429,6 +763,12 @@ impl<T> Grid<T> {
         self.scroll_limit
     }
 
+    /// Current resize generation of the grid
+    #[inline]
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
     /// Total number of lines in the buffer, this includes scrollback + visible lines
     #[inline]
     pub fn len(&self) -> usize {
@@ -465,6 +805,163 @@ impl<T> Grid<T> {
     pub fn contains(&self, point: &Point) -> bool {
         self.lines > point.line && self.cols > point.col
     }
+
+    /// Get a cell of the active region, `None` if it is outside of the grid
+    #[inline]
+    pub fn get(&self, line: Line, col: Column) -> Option<&T> {
+        if self.contains(&Point::new(line, col)) {
+            Some(&self[line][col])
+        } else {
+            None
+        }
+    }
+
+    /// Get a mutable cell of the active region, `None` if it is outside of the grid
+    #[inline]
+    pub fn get_mut(&mut self, line: Line, col: Column) -> Option<&mut T>
+    where
+        T: Copy,
+    {
+        if self.contains(&Point::new(line, col)) {
+            Some(&mut self[line][col])
+        } else {
+            None
+        }
+    }
+
+    /// Call `func` for every cell of the active region within `lines` and `cols`
+    ///
+    /// The region is clamped to the grid dimensions.
+    pub fn region_map<F>(&mut self, lines: Range<Line>, cols: Range<Column>, mut func: F)
+    where
+        T: Copy,
+        F: FnMut(Point, &mut T),
+    {
+        let lines = lines.start..min(lines.end, self.lines);
+        let cols = cols.start..min(cols.end, self.cols);
+
+        for line in IndexRange(lines) {
+            let row = &mut self[line];
+            for col in IndexRange(cols.clone()) {
+                func(Point::new(line, col), &mut row[col]);
+            }
+        }
+    }
+
+    /// View of a single column of the active region, `None` if it is outside of the grid
+    pub fn column(&self, col: Column) -> Option<GridColumn<'_, T>> {
+        if col < self.cols {
+            Some(GridColumn { grid: self, col })
+        } else {
+            None
+        }
+    }
+
+    /// Content hash of a line in the active region
+    ///
+    /// Hashes are cached per row until it is modified, so comparing them is a cheap way to find
+    /// lines which changed.
+    #[inline]
+    pub fn line_hash(&self, line: Line) -> u64
+    where
+        T: Hash,
+    {
+        self[line].content_hash()
+    }
+
+    /// Content hashes of all lines in the active region, from top to bottom
+    pub fn line_hashes(&self) -> Vec<u64>
+    where
+        T: Hash,
+    {
+        IndexRange(Line(0)..self.lines).map(|line| self.line_hash(line)).collect()
+    }
+
+    /// Iterate over the logical lines of the active region, from top to bottom
+    ///
+    /// A logical line is the range of lines joined by soft wraps. A line continuing a line above
+    /// the active region starts a new logical line.
+    pub fn logical_lines(&self) -> impl Iterator<Item = Range<Line>> + '_
+    where
+        T: GridCell,
+    {
+        let last_col = self.cols - 1;
+        let mut start = Line(0);
+        IndexRange(Line(0)..self.lines).filter_map(move |line| {
+            if line + 1 < self.lines && self[line][last_col].is_wrap() {
+                return None;
+            }
+
+            let logical_line = start..line + 1;
+            start = line + 1;
+            Some(logical_line)
+        })
+    }
+
+    /// Iterate over all columns of the active region, from left to right
+    pub fn columns(&self) -> impl Iterator<Item = GridColumn<'_, T>> {
+        IndexRange(Column(0)..self.cols).map(move |col| GridColumn { grid: self, col })
+    }
+
+    /// Overwrite every cell of the active region within `lines` and `cols`
+    ///
+    /// The region is clamped to the grid dimensions.
+    pub fn region_fill(&mut self, lines: Range<Line>, cols: Range<Column>, template: &T)
+    where
+        T: Copy,
+    {
+        self.region_map(lines, cols, |_, cell| *cell = *template);
+    }
+}
+
+/// A single column of the active region
+///
+/// May be constructed using Grid::column(..) or Grid::columns(). Cells are borrowed from the
+/// grid, so this is a cheap alternative to copying the grid in column-major order.
+pub struct GridColumn<'a, T> {
+    grid: &'a Grid<T>,
+    col: Column,
+}
+
+#[allow(clippy::len_without_is_empty)]
+impl<'a, T> GridColumn<'a, T> {
+    /// Column of the grid this is a view of
+    #[inline]
+    pub fn col(&self) -> Column {
+        self.col
+    }
+
+    /// Number of cells in the column
+    #[inline]
+    pub fn len(&self) -> usize {
+        *self.grid.num_lines()
+    }
+
+    /// Get the cell at `line`, `None` if it is outside of the grid
+    #[inline]
+    pub fn get(&self, line: Line) -> Option<&'a T> {
+        self.grid.get(line, self.col)
+    }
+
+    /// Iterate over the cells of the column, from the top line to the bottom line
+    pub fn iter(&self) -> impl Iterator<Item = &'a T> + 'a {
+        let (grid, col) = (self.grid, self.col);
+        IndexRange(Line(0)..grid.num_lines()).map(move |line| &grid[line][col])
+    }
+
+    /// Hash of all cells in the column
+    ///
+    /// This is not stable across versions and should only be used to detect changes.
+    pub fn hash_value(&self) -> u64
+    where
+        T: Hash,
+    {
+        let mut hasher = DefaultHasher::new();
+        for cell in self.iter() {
+            cell.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
 }
 
 impl<'a, T> Iterator for GridIterator<'a, T> {
@@ -530,6 +1027,7 @@ impl<T> Index<usize> for Grid<T> {
 impl<T> IndexMut<index::Line> for Grid<T> {
     #[inline]
     fn index_mut(&mut self, index: index::Line) -> &mut Row<T> {
+        self.damage_lines(index..index + 1);
         &mut self.raw[index]
     }
 }
@@ -543,7 +1041,7 @@ impl<'point, T> Index<&'point Point> for Grid<T> {
     }
 }
 
-impl<'point, T> IndexMut<&'point Point> for Grid<T> {
+impl<'point, T: Copy> IndexMut<&'point Point> for Grid<T> {
     #[inline]
     fn index_mut<'a, 'b>(&'a mut self, point: &'b Point) -> &'a mut T {
         &mut self[point.line][point.col]
@@ -572,7 +1070,7 @@ pub struct RegionMut<'a, T> {
     raw: &'a mut Storage<T>,
 }
 
-impl<'a, T> RegionMut<'a, T> {
+impl<'a, T: Copy> RegionMut<'a, T> {
     /// Call the provided function for every item in this region
     pub fn each<F: Fn(&mut T)>(self, func: F) {
         for row in self {
@@ -606,6 +1104,7 @@ impl<T> IndexRegion<Range<Line>, T> for Grid<T> {
         assert!(index.start < self.num_lines());
         assert!(index.end <= self.num_lines());
         assert!(index.start <= index.end);
+        self.damage_lines(index.clone());
         RegionMut {
             start: index.start,
             end: index.end,
@@ -625,6 +1124,7 @@ impl<T> IndexRegion<RangeTo<Line>, T> for Grid<T> {
     }
     fn region_mut(&mut self, index: RangeTo<Line>) -> RegionMut<'_, T> {
         assert!(index.end <= self.num_lines());
+        self.damage_lines(Line(0)..index.end);
         RegionMut {
             start: Line(0),
             end: index.end,
@@ -644,6 +1144,8 @@ impl<T> IndexRegion<RangeFrom<Line>, T> for Grid<T> {
     }
     fn region_mut(&mut self, index: RangeFrom<Line>) -> RegionMut<'_, T> {
         assert!(index.start < self.num_lines());
+        let lines = self.num_lines();
+        self.damage_lines(index.start..lines);
         RegionMut {
             start: index.start,
             end: self.num_lines(),
@@ -662,6 +1164,8 @@ impl<T> IndexRegion<RangeFull, T> for Grid<T> {
     }
 
     fn region_mut(&mut self, _: RangeFull) -> RegionMut<'_, T> {
+        let lines = self.num_lines();
+        self.damage_lines(Line(0)..lines);
         RegionMut {
             start: Line(0),
             end: self.num_lines(),
@@ -13,19 +13,35 @@
 // limitations under the License.
 
 //! Defines the Row type which makes up lines in the grid
-
-use std::ops::{Index, IndexMut};
-use std::ops::{Range, RangeTo, RangeFrom, RangeFull, RangeToInclusive};
+//!
+//! Rows deep in the scrollback history are packed into runs of equal cells, which takes a
+//! fraction of the memory for mostly empty lines. Packed rows are read like any other row and
+//! unpacked when they're modified or scrolled into view.
+
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::iter;
+use std::ops::{Bound, Index, IndexMut, RangeBounds};
 use std::cmp::{max, min};
 use std::slice;
 
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::ansi::LineAttribute;
 use crate::index::Column;
 
 /// A row in the grid
-#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+#[derive(Default, Clone, Debug, Deserialize)]
 pub struct Row<T> {
+    /// Cells of the row, empty while the row is packed
     inner: Vec<T>,
 
+    /// Runs of equal cells with the column after their last cell, while the row is packed
+    #[serde(skip)]
+    packed: Option<Box<[(T, usize)]>>,
+
     /// occupied entries
     ///
     /// Semantically, this value can be understood as the **end** of an
@@ -35,11 +51,43 @@ pub struct Row<T> {
     /// - 1 means there is a value at index zero, but nowhere else
     /// - `occ == inner.len` means every value is occupied
     pub(crate) occ: usize,
+
+    /// Size of the characters on this row
+    #[serde(default)]
+    attribute: LineAttribute,
+
+    /// Cached hash of the row's content
+    ///
+    /// Cleared whenever the row is accessed mutably and recomputed on demand.
+    #[serde(skip)]
+    hash: Cell<Option<u64>>,
 }
 
 impl<T: PartialEq> PartialEq for Row<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.inner == other.inner
+        self.len() == other.len()
+            && self.attribute == other.attribute
+            && self.iter().eq(other.iter())
+    }
+}
+
+/// Packed rows are written like other rows, so the format doesn't depend on packing
+impl<T: Serialize> Serialize for Row<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut row = serializer.serialize_struct("Row", 3)?;
+        row.serialize_field("inner", &Cells(self))?;
+        row.serialize_field("occ", &self.occ)?;
+        row.serialize_field("attribute", &self.attribute)?;
+        row.end()
+    }
+}
+
+/// Cells of a row, serialized as a sequence
+struct Cells<'a, T>(&'a Row<T>);
+
+impl<'a, T: Serialize> Serialize for Cells<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter())
     }
 }
 
@@ -47,12 +95,28 @@ impl<T: Copy + Clone> Row<T> {
     pub fn new(columns: Column, template: &T) -> Row<T> {
         Row {
             inner: vec![*template; *columns],
+            packed: None,
             occ: 0,
+            attribute: LineAttribute::default(),
+            hash: Cell::new(None),
+        }
+    }
+
+    /// Create a row from its cells, with the first `occ` of them occupied
+    pub fn from_vec(inner: Vec<T>, occ: usize) -> Row<T> {
+        Row {
+            inner,
+            packed: None,
+            occ,
+            attribute: LineAttribute::default(),
+            hash: Cell::new(None),
         }
     }
 
     pub fn grow(&mut self, cols: Column, template: &T) {
         assert!(self.len() < * cols);
+        self.hash.set(None);
+        self.unpack();
 
         while self.len() != *cols {
             self.inner.push(*template);
@@ -62,6 +126,9 @@ impl<T: Copy + Clone> Row<T> {
     /// Resets contents to the contents of `other`
     #[inline(never)]
     pub fn reset(&mut self, other: &T) {
+        self.hash.set(None);
+        self.attribute = LineAttribute::default();
+        self.unpack();
         let occ = self.occ;
         for item in &mut self.inner[..occ] {
             *item = *other;
@@ -69,11 +136,60 @@ impl<T: Copy + Clone> Row<T> {
 
         self.occ = 0;
     }
+
+    /// Store the cells individually again, after the row was packed
+    pub fn unpack(&mut self) {
+        if let Some(runs) = self.packed.take() {
+            let mut start = 0;
+            self.inner = Vec::with_capacity(runs.last().map_or(0, |run| run.1));
+            for &(cell, end) in runs.iter() {
+                self.inner.extend(iter::repeat(cell).take(end - start));
+                start = end;
+            }
+        }
+    }
+}
+
+impl<T: Copy + PartialEq> Row<T> {
+    /// Store the cells as runs of equal cells, unless that would take more memory
+    ///
+    /// Packed rows can't be sliced, all other ways to read a row work for them.
+    pub fn pack(&mut self) {
+        if self.packed.is_some() {
+            return;
+        }
+
+        let mut runs: Vec<(T, usize)> = Vec::new();
+        for (column, cell) in self.inner.iter().enumerate() {
+            match runs.last_mut() {
+                Some(run) if run.0 == *cell => run.1 = column + 1,
+                _ => runs.push((*cell, column + 1)),
+            }
+        }
+
+        // A run is slightly larger than a single cell
+        if runs.len() * 2 > self.inner.len() {
+            return;
+        }
+
+        self.packed = Some(runs.into_boxed_slice());
+        self.inner = Vec::new();
+    }
 }
 
 #[allow(clippy::len_without_is_empty)]
 impl<T> Row<T> {
     pub fn shrink(&mut self, cols: Column) {
+        self.hash.set(None);
+        if let Some(runs) = self.packed.take() {
+            let mut runs = runs.into_vec();
+            if let Some(last) = runs.iter().position(|run| run.1 >= *cols) {
+                runs.truncate(last + 1);
+                runs[last].1 = *cols;
+            }
+            self.packed = Some(runs.into_boxed_slice());
+        }
+
         while self.len() != *cols {
             self.inner.pop();
         }
@@ -82,138 +198,214 @@ impl<T> Row<T> {
     }
 
     pub fn len(&self) -> usize {
-        self.inner.len()
+        match self.packed {
+            Some(ref runs) => runs.last().map_or(0, |run| run.1),
+            None => self.inner.len(),
+        }
     }
 
-    pub fn iter(&self) -> slice::Iter<'_, T> {
-        self.inner.iter()
+    pub fn iter(&self) -> RowIter<'_, T> {
+        match self.packed {
+            Some(ref runs) => RowIter::Runs { runs, index: 0, column: 0 },
+            None => RowIter::Cells(self.inner.iter()),
+        }
     }
-}
-
-
-impl<'a, T> IntoIterator for &'a Row<T> {
-    type Item = &'a T;
-    type IntoIter = slice::Iter<'a, T>;
 
+    /// Whether the cells are stored as runs of equal cells
     #[inline]
-    fn into_iter(self) -> slice::Iter<'a, T> {
-        self.iter()
+    pub fn is_packed(&self) -> bool {
+        self.packed.is_some()
     }
-}
-
-impl<'a, T> IntoIterator for &'a mut Row<T> {
-    type Item = &'a mut T;
-    type IntoIter = slice::IterMut<'a, T>;
 
+    /// Size of the characters on this row
     #[inline]
-    fn into_iter(self) -> slice::IterMut<'a, T> {
-        self.occ = self.len();
-        self.inner.iter_mut()
+    pub fn attribute(&self) -> LineAttribute {
+        self.attribute
     }
-}
 
-impl<T> Index<Column> for Row<T> {
-    type Output = T;
-
-    #[inline]
-    fn index(&self, index: Column) -> &T {
-        &self.inner[index.0]
-    }
-}
-
-impl<T> IndexMut<Column> for Row<T> {
     #[inline]
-    fn index_mut(&mut self, index: Column) -> &mut T {
-        self.occ = max(self.occ, *index + 1);
-        &mut self.inner[index.0]
+    pub fn set_attribute(&mut self, attribute: LineAttribute) {
+        self.hash.set(None);
+        self.attribute = attribute;
     }
-}
 
-// -----------------------------------------------------------------------------
-// Index ranges of columns
-// -----------------------------------------------------------------------------
-
-impl<T> Index<Range<Column>> for Row<T> {
-    type Output = [T];
+    /// Hash of all cells in the row
+    ///
+    /// The hash is cached until the row is modified, so checking unchanged rows is cheap. It is
+    /// not stable across versions and should only be used to detect changes.
+    pub fn content_hash(&self) -> u64
+    where
+        T: Hash,
+    {
+        if let Some(hash) = self.hash.get() {
+            return hash;
+        }
 
-    #[inline]
-    fn index(&self, index: Range<Column>) -> &[T] {
-        &self.inner[(index.start.0)..(index.end.0)]
+        let mut hasher = DefaultHasher::new();
+        self.len().hash(&mut hasher);
+        for cell in self.iter() {
+            cell.hash(&mut hasher);
+        }
+        self.attribute.hash(&mut hasher);
+        let hash = hasher.finish();
+        self.hash.set(Some(hash));
+        hash
     }
 }
 
-impl<T> IndexMut<Range<Column>> for Row<T> {
-    #[inline]
-    fn index_mut(&mut self, index: Range<Column>) -> &mut [T] {
-        self.occ = max(self.occ, *index.end);
-        &mut self.inner[(index.start.0)..(index.end.0)]
-    }
+
+/// Iterator over the cells of a packed or unpacked row
+pub enum RowIter<'a, T> {
+    Cells(slice::Iter<'a, T>),
+    Runs { runs: &'a [(T, usize)], index: usize, column: usize },
 }
 
-impl<T> Index<RangeTo<Column>> for Row<T> {
-    type Output = [T];
+impl<'a, T> Iterator for RowIter<'a, T> {
+    type Item = &'a T;
 
     #[inline]
-    fn index(&self, index: RangeTo<Column>) -> &[T] {
-        &self.inner[..(index.end.0)]
+    fn next(&mut self) -> Option<&'a T> {
+        match *self {
+            RowIter::Cells(ref mut cells) => cells.next(),
+            RowIter::Runs { runs, ref mut index, ref mut column } => {
+                while *index < runs.len() && runs[*index].1 <= *column {
+                    *index += 1;
+                }
+
+                let run = runs.get(*index)?;
+                *column += 1;
+                Some(&run.0)
+            },
+        }
     }
-}
 
-impl<T> IndexMut<RangeTo<Column>> for Row<T> {
     #[inline]
-    fn index_mut(&mut self, index: RangeTo<Column>) -> &mut [T] {
-        self.occ = max(self.occ, *index.end);
-        &mut self.inner[..(index.end.0)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match *self {
+            RowIter::Cells(ref cells) => cells.len(),
+            RowIter::Runs { ref runs, column, .. } => {
+                runs.last().map_or(0, |run| run.1).saturating_sub(column)
+            },
+        };
+        (len, Some(len))
     }
 }
 
-impl<T> Index<RangeFrom<Column>> for Row<T> {
-    type Output = [T];
+impl<'a, T> ExactSizeIterator for RowIter<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a Row<T> {
+    type Item = &'a T;
+    type IntoIter = RowIter<'a, T>;
 
     #[inline]
-    fn index(&self, index: RangeFrom<Column>) -> &[T] {
-        &self.inner[(index.start.0)..]
+    fn into_iter(self) -> RowIter<'a, T> {
+        self.iter()
     }
 }
 
-impl<T> IndexMut<RangeFrom<Column>> for Row<T> {
+impl<'a, T: Copy> IntoIterator for &'a mut Row<T> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+
     #[inline]
-    fn index_mut(&mut self, index: RangeFrom<Column>) -> &mut [T] {
+    fn into_iter(self) -> slice::IterMut<'a, T> {
+        self.hash.set(None);
+        self.unpack();
         self.occ = self.len();
-        &mut self.inner[(index.start.0)..]
+        self.inner.iter_mut()
     }
 }
 
-impl<T> Index<RangeFull> for Row<T> {
-    type Output = [T];
+impl<T> Index<Column> for Row<T> {
+    type Output = T;
 
     #[inline]
-    fn index(&self, _: RangeFull) -> &[T] {
-        &self.inner[..]
+    fn index(&self, index: Column) -> &T {
+        match self.packed {
+            Some(ref runs) => {
+                // Runs are never equal to a column, the search ends at the run containing it
+                let run = runs
+                    .binary_search_by(|run| {
+                        if run.1 <= index.0 { Ordering::Less } else { Ordering::Greater }
+                    })
+                    .unwrap_or_else(|run| run);
+                &runs[run].0
+            },
+            None => &self.inner[index.0],
+        }
     }
 }
 
-impl<T> IndexMut<RangeFull> for Row<T> {
+impl<T: Copy> IndexMut<Column> for Row<T> {
     #[inline]
-    fn index_mut(&mut self, _: RangeFull) -> &mut [T] {
-        self.occ = self.len();
-        &mut self.inner[..]
+    fn index_mut(&mut self, index: Column) -> &mut T {
+        self.hash.set(None);
+        self.unpack();
+        self.occ = max(self.occ, *index + 1);
+        &mut self.inner[index.0]
     }
 }
 
-impl<T> Index<RangeToInclusive<Column>> for Row<T> {
-    type Output = [T];
-
-    #[inline]
-    fn index(&self, index: RangeToInclusive<Column>) -> &[T] {
-        &self.inner[..=(index.end.0)]
+impl<T: Copy> Row<T> {
+    /// Cells in `range` of the columns, for modifying them
+    ///
+    /// This unpacks the row. Packed rows can't be sliced, so cells are only ever borrowed as a
+    /// slice mutably.
+    pub fn cells_mut<R: RangeBounds<Column>>(&mut self, range: R) -> &mut [T] {
+        let start = match range.start_bound() {
+            Bound::Included(start) => start.0,
+            Bound::Excluded(start) => start.0 + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => end.0 + 1,
+            Bound::Excluded(end) => end.0,
+            Bound::Unbounded => self.len(),
+        };
+
+        self.hash.set(None);
+        self.unpack();
+        self.occ = max(self.occ, end);
+        &mut self.inner[start..end]
     }
 }
 
-impl<T> IndexMut<RangeToInclusive<Column>> for Row<T> {
-    #[inline]
-    fn index_mut(&mut self, index: RangeToInclusive<Column>) -> &mut [T] {
-        self.occ = max(self.occ, *index.end);
-        &mut self.inner[..=(index.end.0)]
+#[cfg(test)]
+mod tests {
+    use super::Row;
+    use crate::index::Column;
+
+    #[test]
+    fn pack_and_unpack() {
+        let mut row = Row::new(Column(8), &' ');
+        row[Column(1)] = 'a';
+        row[Column(2)] = 'a';
+        let cells: Vec<char> = row.iter().cloned().collect();
+        let json = serde_json::to_string(&row).unwrap();
+
+        row.pack();
+        assert!(row.is_packed());
+        assert_eq!(row.len(), 8);
+        assert_eq!(row[Column(2)], 'a');
+        assert_eq!(row[Column(7)], ' ');
+        assert_eq!(row.iter().cloned().collect::<Vec<_>>(), cells);
+        assert_eq!(serde_json::to_string(&row).unwrap(), json);
+
+        row.shrink(Column(2));
+        assert_eq!(row.iter().cloned().collect::<Vec<_>>(), vec![' ', 'a']);
+
+        row[Column(0)] = 'b';
+        assert!(!row.is_packed());
+        assert_eq!(row.iter().cloned().collect::<Vec<_>>(), vec!['b', 'a']);
+    }
+
+    #[test]
+    fn pack_only_saves_memory() {
+        let mut row = Row::new(Column(4), &' ');
+        row[Column(1)] = 'a';
+        row[Column(3)] = 'a';
+
+        row.pack();
+        assert!(!row.is_packed());
     }
 }
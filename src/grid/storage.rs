@@ -14,8 +14,6 @@
 use std::ops::{Index, IndexMut};
 use std::slice;
 
-use static_assertions::assert_eq_size;
-
 use crate::index::Line;
 use super::Row;
 
@@ -191,6 +189,17 @@ impl<T> Storage<T> {
         self.len += num_rows;
     }
 
+    /// Replace all lines in the buffer, discarding hidden lines
+    ///
+    /// The first row of `inner` becomes the bottom line of the visible area.
+    pub fn replace_inner(&mut self, inner: Vec<Row<T>>) {
+        assert!(inner.len() > *self.visible_lines);
+
+        self.len = inner.len();
+        self.inner = inner;
+        self.zero = 0;
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.len
@@ -217,37 +226,11 @@ impl<T> Storage<T> {
         self.inner.swap(a, b);
     }
 
-    /// Swap implementation for Row<T>.
-    ///
-    /// Exploits the known size of Row<T> to produce a slightly more efficient
-    /// swap than going through slice::swap.
-    ///
-    /// The default implementation from swap generates 8 movups and 4 movaps
-    /// instructions. This implementation achieves the swap in only 8 movups
-    /// instructions.
+    /// Swap two rows, given their index relative to the zero line
     pub fn swap(&mut self, a: usize, b: usize) {
-        assert_eq_size!(Row<T>, [usize; 4]);
-
         let a = self.compute_index(a);
         let b = self.compute_index(b);
-
-        unsafe {
-            // Cast to a qword array to opt out of copy restrictions and avoid
-            // drop hazards. Byte array is no good here since for whatever
-            // reason LLVM won't optimized it.
-            let a_ptr = self.inner.as_mut_ptr().add(a) as *mut usize;
-            let b_ptr = self.inner.as_mut_ptr().add(b) as *mut usize;
-
-            // Copy 1 qword at a time
-            //
-            // The optimizer unrolls this loop and vectorizes it.
-            let mut tmp: usize;
-            for i in 0..4 {
-                tmp = *a_ptr.offset(i);
-                *a_ptr.offset(i) = *b_ptr.offset(i);
-                *b_ptr.offset(i) = tmp;
-            }
-        }
+        self.inner.swap(a, b);
     }
 
     /// Iterate over *all* entries in the underlying buffer
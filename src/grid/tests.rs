@@ -14,8 +14,9 @@
 
 //! Tests for the Gird
 
-use super::{Grid, BidirectionalIterator};
+use super::{Annotation, Grid, BidirectionalIterator, GridCell, Scroll};
 use crate::index::{Point, Line, Column};
+use crate::term::cell::{Cell, Flags};
 
 // Scroll up moves lines upwards
 #[test]
@@ -123,3 +124,359 @@ fn test_iter() {
     assert_eq!(None, final_iter.next());
     assert_eq!(Some(&23), final_iter.prev());
 }
+
+fn cell(c: char) -> Cell {
+    let mut cell = Cell::default();
+    cell.c = c;
+    cell
+}
+
+fn wrap_cell(c: char) -> Cell {
+    let mut cell = cell(c);
+    cell.flags.insert(Flags::WRAPLINE);
+    cell
+}
+
+// Shrinking columns wraps lines into the history
+#[test]
+fn shrink_reflow() {
+    let mut grid = Grid::new(Line(1), Column(5), 2, cell(' '));
+    grid[Line(0)][Column(0)] = cell('1');
+    grid[Line(0)][Column(1)] = cell('2');
+    grid[Line(0)][Column(2)] = cell('3');
+    grid[Line(0)][Column(3)] = cell('4');
+    grid[Line(0)][Column(4)] = cell('5');
+
+    let mut cursor = Point::new(Line(0), Column(4));
+    grid.resize(true, Line(1), Column(2), &mut cursor, &Cell::default());
+
+    assert_eq!(grid.len(), 3);
+    assert_eq!(grid.scroll_limit(), 2);
+
+    assert_eq!(grid[2][Column(0)], cell('1'));
+    assert_eq!(grid[2][Column(1)], wrap_cell('2'));
+    assert_eq!(grid[1][Column(0)], cell('3'));
+    assert_eq!(grid[1][Column(1)], wrap_cell('4'));
+    assert_eq!(grid[0][Column(0)], cell('5'));
+    assert_eq!(grid[0][Column(1)], cell(' '));
+
+    assert_eq!(cursor, Point::new(Line(0), Column(0)));
+}
+
+// Lines which don't fit into the scrollback anymore are dropped
+#[test]
+fn shrink_reflow_without_history() {
+    let mut grid = Grid::new(Line(1), Column(4), 0, cell(' '));
+    grid[Line(0)][Column(0)] = cell('1');
+    grid[Line(0)][Column(1)] = cell('2');
+    grid[Line(0)][Column(2)] = cell('3');
+
+    let mut cursor = Point::new(Line(0), Column(2));
+    grid.resize(true, Line(1), Column(2), &mut cursor, &Cell::default());
+
+    assert_eq!(grid.len(), 1);
+    assert_eq!(grid[Line(0)][Column(0)], cell('3'));
+    assert_eq!(cursor, Point::new(Line(0), Column(0)));
+}
+
+// Annotations scroll with their line until they leave the scrollback
+#[test]
+fn scroll_moves_annotations() {
+    let mut grid = Grid::new(Line(3), Column(1), 1, 0);
+    grid.annotate(Annotation { point: Point::new(0, Column(0)), label: None });
+
+    grid.scroll_up(&(Line(0)..Line(3)), Line(1), &0);
+    assert_eq!(grid.annotations()[0].point, Point::new(1, Column(0)));
+
+    grid.scroll_up(&(Line(0)..Line(3)), Line(3), &0);
+    assert!(grid.annotations().is_empty());
+}
+
+// Annotations stay on their cell when lines are rewrapped
+#[test]
+fn reflow_moves_annotations() {
+    let mut grid = Grid::new(Line(1), Column(5), 2, cell(' '));
+    for (i, c) in "12345".chars().enumerate() {
+        grid[Line(0)][Column(i)] = cell(c);
+    }
+    let label = Some(String::from("four"));
+    grid.annotate(Annotation { point: Point::new(0, Column(3)), label: label.clone() });
+
+    let mut cursor = Point::new(Line(0), Column(4));
+    grid.resize(true, Line(1), Column(2), &mut cursor, &Cell::default());
+
+    assert_eq!(grid.annotations(), &[Annotation { point: Point::new(1, Column(1)), label }]);
+    assert_eq!(grid[1][Column(1)].c, '4');
+}
+
+// Growing columns joins wrapped lines back together
+#[test]
+fn grow_reflow() {
+    let mut grid = Grid::new(Line(2), Column(2), 0, cell(' '));
+    grid[Line(0)][Column(0)] = cell('1');
+    grid[Line(0)][Column(1)] = wrap_cell('2');
+    grid[Line(1)][Column(0)] = cell('3');
+
+    let mut cursor = Point::new(Line(1), Column(0));
+    grid.resize(true, Line(2), Column(3), &mut cursor, &Cell::default());
+
+    assert_eq!(grid.len(), 2);
+    assert_eq!(grid[Line(0)][Column(0)], cell('1'));
+    assert_eq!(grid[Line(0)][Column(1)], cell('2'));
+    assert_eq!(grid[Line(0)][Column(2)], cell('3'));
+    assert_eq!(grid[Line(1)][Column(0)], cell(' '));
+    assert!(!grid[Line(0)][Column(2)].is_wrap());
+
+    assert_eq!(cursor, Point::new(Line(0), Column(2)));
+}
+
+// Growing columns pulls lines back out of the history
+#[test]
+fn grow_reflow_from_history() {
+    let mut grid = Grid::new(Line(1), Column(2), 2, cell(' '));
+    grid[Line(0)][Column(0)] = cell('1');
+    grid[Line(0)][Column(1)] = wrap_cell('2');
+    grid.scroll_up(&(Line(0)..Line(1)), Line(1), &cell(' '));
+    grid[Line(0)][Column(0)] = cell('3');
+
+    let mut cursor = Point::new(Line(0), Column(1));
+    grid.resize(true, Line(1), Column(4), &mut cursor, &Cell::default());
+
+    assert_eq!(grid.scroll_limit(), 0);
+    assert_eq!(grid[Line(0)][Column(0)], cell('1'));
+    assert_eq!(grid[Line(0)][Column(1)], cell('2'));
+    assert_eq!(grid[Line(0)][Column(2)], cell('3'));
+    assert_eq!(grid[Line(0)][Column(3)], cell(' '));
+
+    assert_eq!(cursor, Point::new(Line(0), Column(3)));
+}
+
+// Wide chars which don't fit at the end of a row are moved to the next one
+#[test]
+fn reflow_wide_char() {
+    let mut grid = Grid::new(Line(1), Column(4), 2, cell(' '));
+    grid[Line(0)][Column(0)] = cell('a');
+    grid[Line(0)][Column(1)] = cell('b');
+    grid[Line(0)][Column(2)] = cell('中');
+    grid[Line(0)][Column(2)].flags.insert(Flags::WIDE_CHAR);
+    grid[Line(0)][Column(3)].flags.insert(Flags::WIDE_CHAR_SPACER);
+
+    let mut cursor = Point::new(Line(0), Column(3));
+    grid.resize(true, Line(1), Column(3), &mut cursor, &Cell::default());
+
+    assert_eq!(grid.scroll_limit(), 1);
+    assert_eq!(grid[1][Column(1)], cell('b'));
+    assert!(grid[1][Column(2)].flags.contains(Flags::LEADING_WIDE_CHAR_SPACER));
+    assert!(grid[1][Column(2)].is_wrap());
+    assert_eq!(grid[0][Column(0)].c, '中');
+    assert!(grid[0][Column(0)].flags.contains(Flags::WIDE_CHAR));
+    assert!(grid[0][Column(1)].flags.contains(Flags::WIDE_CHAR_SPACER));
+    assert_eq!(cursor, Point::new(Line(0), Column(1)));
+
+    // The padding is dropped again once the wide char fits
+    grid.resize(true, Line(1), Column(4), &mut cursor, &Cell::default());
+
+    assert_eq!(grid.scroll_limit(), 0);
+    assert_eq!(grid[Line(0)][Column(1)], cell('b'));
+    assert_eq!(grid[Line(0)][Column(2)].c, '中');
+    assert!(grid[Line(0)][Column(3)].flags.contains(Flags::WIDE_CHAR_SPACER));
+    assert_eq!(cursor, Point::new(Line(0), Column(3)));
+}
+
+// Lines which haven't been wrapped are never joined
+#[test]
+fn reflow_keeps_separate_lines() {
+    let mut grid = Grid::new(Line(2), Column(2), 0, cell(' '));
+    grid[Line(0)][Column(0)] = cell('a');
+    grid[Line(0)][Column(1)] = cell('b');
+    grid[Line(1)][Column(0)] = cell('c');
+    grid[Line(1)][Column(1)] = cell('d');
+
+    let mut cursor = Point::new(Line(1), Column(1));
+    grid.resize(true, Line(2), Column(4), &mut cursor, &Cell::default());
+
+    assert_eq!(grid[Line(0)][Column(0)], cell('a'));
+    assert_eq!(grid[Line(0)][Column(1)], cell('b'));
+    assert_eq!(grid[Line(0)][Column(2)], cell(' '));
+    assert_eq!(grid[Line(1)][Column(0)], cell('c'));
+    assert_eq!(grid[Line(1)][Column(1)], cell('d'));
+
+    assert_eq!(cursor, Point::new(Line(1), Column(1)));
+}
+
+// Without reflow, columns are truncated
+#[test]
+fn resize_without_reflow() {
+    let mut grid = Grid::new(Line(1), Column(3), 0, cell(' '));
+    grid[Line(0)][Column(0)] = cell('1');
+    grid[Line(0)][Column(1)] = cell('2');
+    grid[Line(0)][Column(2)] = cell('3');
+
+    let mut cursor = Point::new(Line(0), Column(2));
+    grid.resize(false, Line(1), Column(2), &mut cursor, &Cell::default());
+
+    assert_eq!(grid.len(), 1);
+    assert_eq!(grid[Line(0)][Column(0)], cell('1'));
+    assert_eq!(grid[Line(0)][Column(1)], cell('2'));
+    assert_eq!(cursor, Point::new(Line(0), Column(2)));
+}
+
+// Every resize invalidates coordinates computed for the previous dimensions
+#[test]
+fn resize_bumps_generation() {
+    let mut grid = Grid::new(Line(2), Column(2), 0, cell(' '));
+    let mut cursor = Point::new(Line(0), Column(0));
+    assert_eq!(grid.generation(), 0);
+
+    grid.resize(true, Line(2), Column(2), &mut cursor, &Cell::default());
+    assert_eq!(grid.generation(), 0);
+
+    grid.resize(true, Line(3), Column(2), &mut cursor, &Cell::default());
+    assert_eq!(grid.generation(), 1);
+
+    grid.resize(false, Line(3), Column(4), &mut cursor, &Cell::default());
+    assert_eq!(grid.generation(), 2);
+}
+
+// Checked access returns nothing outside of the grid
+#[test]
+fn checked_access() {
+    let mut grid = Grid::new(Line(2), Column(3), 0, 0);
+    grid[Line(1)][Column(2)] = 7;
+
+    assert_eq!(grid.get(Line(1), Column(2)), Some(&7));
+    assert_eq!(grid.get(Line(2), Column(0)), None);
+    assert_eq!(grid.get(Line(0), Column(3)), None);
+
+    *grid.get_mut(Line(0), Column(0)).unwrap() = 1;
+    assert_eq!(grid[Line(0)][Column(0)], 1);
+    assert!(grid.get_mut(Line(5), Column(5)).is_none());
+}
+
+// Region operations are clamped to the grid
+#[test]
+fn region_fill_and_map() {
+    let mut grid = Grid::new(Line(3), Column(3), 0, 0);
+
+    grid.region_fill(Line(1)..Line(10), Column(1)..Column(10), &5);
+    assert_eq!(grid[Line(0)][Column(1)], 0);
+    assert_eq!(grid[Line(1)][Column(0)], 0);
+    assert_eq!(grid[Line(1)][Column(1)], 5);
+    assert_eq!(grid[Line(2)][Column(2)], 5);
+
+    grid.region_map(Line(0)..Line(2), Column(0)..Column(2), |point, cell| {
+        *cell += point.line.0 * 10 + point.col.0;
+    });
+    assert_eq!(grid[Line(0)][Column(1)], 1);
+    assert_eq!(grid[Line(1)][Column(1)], 16);
+    assert_eq!(grid[Line(2)][Column(1)], 5);
+}
+
+// Columns can be viewed and hashed without copying the grid
+#[test]
+fn column_view() {
+    let mut grid = Grid::new(Line(3), Column(2), 0, 0);
+    for i in 0..3 {
+        grid[Line(i)][Column(0)] = i;
+        grid[Line(i)][Column(1)] = i * 10;
+    }
+
+    let column = grid.column(Column(1)).unwrap();
+    assert_eq!(column.len(), 3);
+    assert_eq!(column.get(Line(2)), Some(&20));
+    assert_eq!(column.iter().cloned().collect::<Vec<_>>(), vec![0, 10, 20]);
+    assert!(grid.column(Column(2)).is_none());
+
+    assert_eq!(grid.columns().count(), 2);
+    let hashes: Vec<u64> = grid.columns().map(|column| column.hash_value()).collect();
+    assert_ne!(hashes[0], hashes[1]);
+
+    grid[Line(1)][Column(0)] = 5;
+    assert_ne!(grid.column(Column(0)).unwrap().hash_value(), hashes[0]);
+    assert_eq!(grid.column(Column(1)).unwrap().hash_value(), hashes[1]);
+}
+
+// Line hashes only change for modified lines
+#[test]
+fn line_hashes() {
+    let mut grid = Grid::new(Line(3), Column(2), 0, 0);
+    let hashes = grid.line_hashes();
+    assert_eq!(hashes.len(), 3);
+    assert_eq!(hashes[0], hashes[1]);
+
+    grid[Line(1)][Column(0)] = 1;
+    assert_eq!(grid.line_hash(Line(0)), hashes[0]);
+    assert_ne!(grid.line_hash(Line(1)), hashes[1]);
+    assert_eq!(grid.line_hash(Line(2)), hashes[2]);
+
+    grid[Line(1)][Column(0)] = 0;
+    assert_eq!(grid.line_hashes(), hashes);
+}
+
+// Soft wrapped lines are joined into one logical line
+#[test]
+fn logical_lines() {
+    let mut grid = Grid::new(Line(4), Column(2), 0, cell(' '));
+    grid[Line(1)][Column(1)] = wrap_cell('a');
+    grid[Line(2)][Column(1)] = wrap_cell('b');
+    grid[Line(3)][Column(1)] = wrap_cell('c');
+
+    let lines: Vec<_> = grid.logical_lines().collect();
+    assert_eq!(lines, vec![Line(0)..Line(1), Line(1)..Line(4)]);
+}
+
+#[test]
+fn damage_from_writes_and_scrolling() {
+    let mut grid = Grid::new(Line(10), Column(1), 0, 0);
+    assert!(grid.take_damage().is_empty());
+
+    grid[Line(2)][Column(0)] = 1;
+    grid[Line(3)][Column(0)] = 1;
+    grid[Line(7)][Column(0)] = 1;
+    assert_eq!(grid.take_damage(), vec![Line(2)..Line(4), Line(7)..Line(8)]);
+    assert!(grid.take_damage().is_empty());
+
+    grid.scroll_up(&(Line(5)..Line(10)), Line(1), &0);
+    assert_eq!(grid.take_damage(), vec![Line(5)..Line(10)]);
+}
+
+// History rows far above the terminal are packed, without changing their content
+#[test]
+fn packed_history() {
+    let mut grid = Grid::new(Line(2), Column(8), 10, 0);
+    for i in 1..9 {
+        grid[Line(1)][Column(0)] = i;
+        grid.scroll_up(&(Line(0)..Line(2)), Line(1), &0);
+    }
+
+    let packed: Vec<bool> = (0..10).map(|i| grid[i].is_packed()).collect();
+    assert_eq!(packed, vec![false, false, false, false, true, true, true, true, true, true]);
+    for i in 1..10 {
+        assert_eq!(grid[i][Column(0)], 9 - i);
+        assert_eq!(grid[i].iter().filter(|&&cell| cell != 0).count(), if i < 9 { 1 } else { 0 });
+    }
+
+    // Rows are unpacked while they're in view
+    grid.scroll_display(Scroll::Top);
+    assert!(!grid[8].is_packed() && !grid[9].is_packed());
+    grid.scroll_display(Scroll::Bottom);
+    assert!(grid[8].is_packed() && grid[9].is_packed());
+}
+
+// Packed rows which enter the viewport when the grid grows are unpacked
+#[test]
+fn grow_lines_unpacks_history() {
+    let mut grid = Grid::new(Line(2), Column(8), 10, Cell::default());
+    for c in "12345678".chars() {
+        grid[Line(1)][Column(0)].c = c;
+        grid.scroll_up(&(Line(0)..Line(2)), Line(1), &Cell::default());
+    }
+    assert!(grid[4].is_packed() && grid[5].is_packed());
+
+    let mut cursor = Point::new(Line(1), Column(0));
+    grid.resize(false, Line(6), Column(8), &mut cursor, &Cell::default());
+
+    let packed: Vec<bool> = (0..10).map(|i| grid[i].is_packed()).collect();
+    assert_eq!(packed, vec![false, false, false, false, false, false, true, true, true, true]);
+    assert_eq!(grid[5][Column(0)].c, '4');
+}
@@ -19,23 +19,40 @@
 //! needs to be tracked. Additionally, we need a bit of a state machine to
 //! determine what to do when a non-modifier key is pressed.
 use std::borrow::Cow;
+use std::io::{self, Read, Write};
 use std::mem;
-use std::time::Instant;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
 
 use copypasta::{Clipboard, Load, Buffer as ClipboardBuffer};
 use glutin::{ElementState, MouseButton, TouchPhase, MouseScrollDelta, ModifiersState, KeyboardInput};
+use glutin::VirtualKeyCode;
 
 use crate::config::{self, Key};
 use crate::grid::Scroll;
 use crate::event::{ClickState, Mouse};
 use crate::index::{Line, Column, Side, Point};
+use crate::message_bar::{self, Message};
+use crate::playback::Control;
 use crate::term::SizeInfo;
+use crate::term::autoscroll::Autoscroll;
+use crate::term::dump::DumpSource;
 use crate::term::mode::TermMode;
+use crate::url::FileLocation;
 use crate::util::fmt::Red;
-use crate::util::start_daemon;
+use crate::util::{start_daemon, thread};
 
 pub const FONT_SIZE_STEP: f32 = 0.5;
 
+/// Change of the background opacity per increase or decrease
+pub const OPACITY_STEP: f32 = 0.1;
+
+/// Maximum number of bytes sent to the pty in a single message when pasting
+const PASTE_CHUNK_SIZE: usize = 0x1000;
+
+/// Time the command of a `PasteFiltered` binding may take before it is killed
+const PASTE_FILTER_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Processes input from glutin.
 ///
 /// An escape sequence may be emitted in case specific keys or key combinations
@@ -54,12 +71,17 @@ pub struct Processor<'a, A: 'a> {
 
 pub trait ActionContext {
     fn write_to_pty<B: Into<Cow<'static, [u8]>>>(&mut self, _: B);
+    fn write_bulk_to_pty<B: Into<Cow<'static, [u8]>>>(&mut self, _: B);
+    fn pasted(&mut self, _: &str);
+    /// Run `filter_paste` in the background, the result is passed to `Processor::paste_filtered`
+    fn filter_paste(&mut self, filter: &config::PasteFilter, contents: String);
     fn terminal_mode(&self) -> TermMode;
     fn size_info(&self) -> SizeInfo;
     fn copy_selection(&self, _: ClipboardBuffer);
     fn clear_selection(&mut self);
     fn update_selection(&mut self, point: Point, side: Side);
     fn simple_selection(&mut self, point: Point, side: Side);
+    fn block_selection(&mut self, point: Point, side: Side);
     fn semantic_selection(&mut self, point: Point);
     fn line_selection(&mut self, point: Point);
     fn selection_is_empty(&self) -> bool;
@@ -71,12 +93,41 @@ pub trait ActionContext {
     fn last_modifiers(&mut self) -> &mut ModifiersState;
     fn change_font_size(&mut self, delta: f32);
     fn reset_font_size(&mut self);
+    fn change_background_opacity(&mut self, delta: f32);
+    fn reset_background_opacity(&mut self);
     fn scroll(&mut self, scroll: Scroll);
     fn clear_history(&mut self);
     fn hide_window(&mut self);
+    fn toggle_fullscreen(&mut self);
+    /// Toggle fullscreen without a separate space on macOS, like `toggle_fullscreen` elsewhere
+    fn toggle_simple_fullscreen(&mut self);
+    fn toggle_decorations(&mut self);
+    fn toggle_always_on_top(&mut self);
+    fn toggle_sticky(&mut self);
     fn url(&self, _: Point<usize>) -> Option<String>;
+    fn file_location(&self, _: Point<usize>) -> Option<FileLocation>;
     fn clear_log(&mut self);
+    fn message(&self) -> Option<Message>;
+    fn close_message(&mut self);
     fn spawn_new_instance(&mut self);
+    fn create_new_window(&mut self);
+    fn quit(&mut self);
+    fn toggle_output_pause(&mut self);
+    fn playback(&mut self, _: Control);
+    fn set_color_scheme(&mut self, name: &str);
+    fn cycle_color_scheme(&mut self);
+    fn toggle_bookmark(&mut self);
+    fn toggle_forced_effects(&mut self);
+    fn toggle_effect_tuning(&mut self);
+    fn toggle_perf_hud(&mut self);
+    /// Use `key` in the effect tuning overlay, returns false if the overlay isn't open
+    fn tune_effects(&mut self, key: VirtualKeyCode) -> bool;
+    fn jump_to_annotation(&mut self, forward: bool);
+    fn start_autoscroll(&mut self, _: Autoscroll);
+    fn dump(&mut self, source: DumpSource);
+    /// Save the next frame as PNG image, only the cells of the selection with `selection`
+    fn screenshot(&mut self, selection: bool);
+    fn autoscroll(&mut self) -> &mut Option<Autoscroll>;
 }
 
 /// Describes a state and action to take in that state
@@ -106,7 +157,22 @@ pub struct Binding<T> {
 pub type KeyBinding = Binding<Key>;
 
 /// Bindings that are triggered by a mouse button
-pub type MouseBinding = Binding<MouseButton>;
+pub type MouseBinding = Binding<MouseTrigger>;
+
+/// Mouse button and number of clicks triggering a mouse binding
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MouseTrigger {
+    pub button: MouseButton,
+
+    /// Clicks in a row, bindings without a count are triggered by every click
+    pub clicks: Option<u8>,
+}
+
+impl From<MouseButton> for MouseTrigger {
+    fn from(button: MouseButton) -> MouseTrigger {
+        MouseTrigger { button, clicks: None }
+    }
+}
 
 impl Default for KeyBinding {
     fn default() -> KeyBinding {
@@ -127,7 +193,7 @@ impl Default for MouseBinding {
             action: Action::Esc(String::new()),
             mode: TermMode::NONE,
             notmode: TermMode::NONE,
-            trigger: MouseButton::Left,
+            trigger: MouseButton::Left.into(),
         }
     }
 }
@@ -159,6 +225,24 @@ impl<T: Eq> Binding<T> {
     }
 }
 
+impl Binding<MouseTrigger> {
+    /// Check if the `clicks`th click in a row of `button` triggers this binding
+    #[inline]
+    fn is_triggered_by_click(
+        &self,
+        mode: TermMode,
+        mods: ModifiersState,
+        button: MouseButton,
+        clicks: u8,
+    ) -> bool {
+        self.trigger.button == button
+            && self.trigger.clicks.map_or(true, |count| count == clicks)
+            && self.mode_matches(mode)
+            && self.not_mode_matches(mode)
+            && self.mods_match(mods, true)
+    }
+}
+
 impl<T> Binding<T> {
     /// Execute the action associate with this binding
     #[inline]
@@ -201,6 +285,9 @@ pub enum Action {
     /// Paste contents of selection buffer
     PasteSelection,
 
+    /// Paste contents of system clipboard after transforming them
+    PasteFiltered(config::PasteFilter),
+
     /// Increase font size
     IncreaseFontSize,
 
@@ -210,6 +297,15 @@ pub enum Action {
     /// Reset font size to the config value
     ResetFontSize,
 
+    /// Make the background more opaque
+    IncreaseBackgroundOpacity,
+
+    /// Make the background more transparent
+    DecreaseBackgroundOpacity,
+
+    /// Reset the background opacity to the config value
+    ResetBackgroundOpacity,
+
     /// Scroll exactly one page up
     ScrollPageUp,
 
@@ -231,15 +327,87 @@ pub enum Action {
     /// Hides the Alacritty window
     Hide,
 
+    /// Toggle fullscreen
+    ToggleFullscreen,
+
+    /// Toggle fullscreen without a separate space on macOS, normal fullscreen elsewhere
+    ToggleSimpleFullscreen,
+
+    /// Show or hide the title bar and borders
+    ToggleDecorations,
+
+    /// Keep the window above all other windows
+    ToggleAlwaysOnTop,
+
+    /// Show the window on all workspaces
+    ToggleSticky,
+
     /// Quits Alacritty.
     Quit,
 
-    /// Clears warning and error notices.
+    /// Close all errors and warnings of the message bar.
     ClearLogNotice,
 
     /// Spawn a new instance of Alacritty.
     SpawnNewInstance,
 
+    /// Open a new window in this instance of Alacritty.
+    CreateNewWindow,
+
+    /// Write the visible screen to a file or command.
+    DumpScreen,
+
+    /// Write the entire scrollback history to a file or command.
+    DumpHistory,
+
+    /// Write the selection to a file or command.
+    DumpSelection,
+
+    /// Save the window as PNG image.
+    Screenshot,
+
+    /// Save the cells of the selection as PNG image.
+    ScreenshotSelection,
+
+    /// Stop updating the terminal with new output, or resume it.
+    ToggleOutputPause,
+
+    /// Double the speed of a playback started with `--play`.
+    PlaybackFaster,
+
+    /// Halve the speed of a playback started with `--play`.
+    PlaybackSlower,
+
+    /// Skip five seconds of a playback started with `--play`.
+    PlaybackForward,
+
+    /// Go back five seconds in a playback started with `--play`.
+    PlaybackBackward,
+
+    /// Switch to the named color scheme.
+    SetColorScheme(String),
+
+    /// Switch to the next color scheme, or back to the configured colors after the last one.
+    CycleColorScheme,
+
+    /// Add or remove a bookmark on the line of the cursor.
+    ToggleBookmark,
+
+    /// Scroll to the previous bookmark or mark in the scrollback history.
+    PreviousBookmark,
+
+    /// Scroll to the next bookmark or mark in the scrollback history.
+    NextBookmark,
+
+    /// Run effects outside of the `effect_schedule`, or follow the schedule again.
+    ToggleForcedEffects,
+
+    /// Open the overlay for adjusting the speed, density and palette of effects, or close it.
+    ToggleEffectTuning,
+
+    /// Show the overlay with frame rate, draw time and throughput measurements, or hide it.
+    TogglePerformanceHud,
+
     /// No action.
     None,
 }
@@ -269,6 +437,14 @@ impl Action {
                         error!("Error loading data from clipboard: {}", Red(err));
                     });
             },
+            Action::PasteFiltered(ref filter) => {
+                Clipboard::new()
+                    .and_then(|clipboard| clipboard.load_primary() )
+                    .map(|contents| ctx.filter_paste(filter, contents))
+                    .unwrap_or_else(|err| {
+                        error!("Error loading data from clipboard: {}", Red(err));
+                    });
+            },
             Action::PasteSelection => {
                 // Only paste if mouse events are not captured by an application
                 if !mouse_mode {
@@ -295,10 +471,12 @@ impl Action {
             Action::Hide => {
                 ctx.hide_window();
             },
-            Action::Quit => {
-                // FIXME should do a more graceful shutdown
-                ::std::process::exit(0);
-            },
+            Action::ToggleFullscreen => ctx.toggle_fullscreen(),
+            Action::ToggleSimpleFullscreen => ctx.toggle_simple_fullscreen(),
+            Action::ToggleDecorations => ctx.toggle_decorations(),
+            Action::ToggleAlwaysOnTop => ctx.toggle_always_on_top(),
+            Action::ToggleSticky => ctx.toggle_sticky(),
+            Action::Quit => ctx.quit(),
             Action::IncreaseFontSize => {
                ctx.change_font_size(FONT_SIZE_STEP);
             },
@@ -308,6 +486,9 @@ impl Action {
             Action::ResetFontSize => {
                ctx.reset_font_size();
             },
+            Action::IncreaseBackgroundOpacity => ctx.change_background_opacity(OPACITY_STEP),
+            Action::DecreaseBackgroundOpacity => ctx.change_background_opacity(-OPACITY_STEP),
+            Action::ResetBackgroundOpacity => ctx.reset_background_opacity(),
             Action::ScrollPageUp => {
                 ctx.scroll(Scroll::PageUp);
             },
@@ -329,15 +510,51 @@ impl Action {
             Action::SpawnNewInstance => {
                 ctx.spawn_new_instance();
             },
+            Action::CreateNewWindow => {
+                ctx.create_new_window();
+            },
+            Action::DumpScreen => {
+                ctx.dump(DumpSource::Screen);
+            },
+            Action::DumpHistory => {
+                ctx.dump(DumpSource::History);
+            },
+            Action::DumpSelection => {
+                ctx.dump(DumpSource::Selection);
+            },
+            Action::Screenshot => ctx.screenshot(false),
+            Action::ScreenshotSelection => ctx.screenshot(true),
+            Action::ToggleOutputPause => {
+                ctx.toggle_output_pause();
+            },
+            Action::PlaybackFaster => ctx.playback(Control::Faster),
+            Action::PlaybackSlower => ctx.playback(Control::Slower),
+            Action::PlaybackForward => ctx.playback(Control::Forward),
+            Action::PlaybackBackward => ctx.playback(Control::Backward),
+            Action::SetColorScheme(ref name) => ctx.set_color_scheme(name),
+            Action::CycleColorScheme => ctx.cycle_color_scheme(),
+            Action::ToggleBookmark => ctx.toggle_bookmark(),
+            Action::PreviousBookmark => ctx.jump_to_annotation(false),
+            Action::NextBookmark => ctx.jump_to_annotation(true),
+            Action::ToggleForcedEffects => ctx.toggle_forced_effects(),
+            Action::ToggleEffectTuning => ctx.toggle_effect_tuning(),
+            Action::TogglePerformanceHud => ctx.toggle_perf_hud(),
             Action::None => (),
         }
     }
 
     fn paste<A: ActionContext>(&self, ctx: &mut A, contents: &str) {
+        // Everything is sent as bulk input, otherwise the brackets could overtake the contents
         if ctx.terminal_mode().contains(TermMode::BRACKETED_PASTE) {
-            ctx.write_to_pty(&b"\x1b[200~"[..]);
-            ctx.write_to_pty(contents.replace("\x1b","").into_bytes());
-            ctx.write_to_pty(&b"\x1b[201~"[..]);
+            let contents = contents.replace("\x1b","");
+            ctx.pasted(&contents);
+
+            // The paste is a single write, so no keystrokes can end up between the brackets
+            let mut bytes = Vec::with_capacity(contents.len() + 12);
+            bytes.extend_from_slice(b"\x1b[200~");
+            bytes.extend_from_slice(contents.as_bytes());
+            bytes.extend_from_slice(b"\x1b[201~");
+            ctx.write_bulk_to_pty(bytes);
         } else {
             // In non-bracketed (ie: normal) mode, terminal applications cannot distinguish
             // pasted data from keystrokes.
@@ -345,11 +562,176 @@ impl Action {
             // pasting... since that's neither practical nor sensible (and probably an impossible
             // task to solve in a general way), we'll just replace line breaks (windows and unix
             // style) with a singe carriage return (\r, which is what the Enter key produces).
-            ctx.write_to_pty(contents.replace("\r\n","\r").replace("\n","\r").into_bytes());
+            let contents = sanitize_paste(&contents.replace("\r\n","\r").replace("\n","\r"));
+            ctx.pasted(&contents);
+            write_chunked(ctx, contents.into_bytes());
+        }
+    }
+}
+
+/// Remove control characters which would be interpreted as keystrokes, like `^C` or escapes
+///
+/// Only tabs and carriage returns are kept, since they are part of regular text.
+fn sanitize_paste(contents: &str) -> String {
+    contents.chars().filter(|&c| !c.is_control() || c == '\t' || c == '\r').collect()
+}
+
+/// Apply the transformations of a `PasteFiltered` binding
+pub fn filter_paste(filter: &config::PasteFilter, mut contents: String) -> io::Result<String> {
+    if filter.strip_escapes {
+        contents = strip_escapes(&contents);
+    }
+
+    if filter.collapse_whitespace {
+        contents = contents.split_whitespace().collect::<Vec<_>>().join(" ");
+    }
+
+    match filter.command {
+        Some(ref command) => run_paste_filter(command, contents),
+        None => Ok(contents),
+    }
+}
+
+/// Remove escape sequences, keeping the text around them
+fn strip_escapes(contents: &str) -> String {
+    let mut stripped = String::with_capacity(contents.len());
+    let mut chars = contents.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                Some('[') => skip_csi(&mut chars),
+                Some(']') | Some('P') | Some('X') | Some('^') | Some('_') => {
+                    skip_string(&mut chars)
+                },
+                // Intermediate byte of a sequence like `ESC ( B`, followed by its final byte
+                Some(c) if c >= ' ' && c <= '/' => {
+                    chars.next();
+                },
+                _ => (),
+            },
+            '\u{9b}' => skip_csi(&mut chars),
+            '\u{90}' | '\u{98}' | '\u{9d}' | '\u{9e}' | '\u{9f}' => skip_string(&mut chars),
+            c => stripped.push(c),
+        }
+    }
+
+    stripped
+}
+
+/// Skip the parameters of a CSI sequence up to its final byte
+fn skip_csi<I: Iterator<Item = char>>(chars: &mut I) {
+    for c in chars {
+        if c >= '@' && c <= '~' {
+            break;
         }
     }
 }
 
+/// Skip the contents of an OSC, DCS, SOS, PM or APC string up to its terminator
+fn skip_string<I: Iterator<Item = char>>(chars: &mut I) {
+    while let Some(c) = chars.next() {
+        match c {
+            '\x07' | '\u{9c}' => break,
+            // String terminator `ESC \`
+            '\x1b' => {
+                chars.next();
+                break;
+            },
+            _ => (),
+        }
+    }
+}
+
+/// Pass the text through the filter command of a `PasteFiltered` binding
+///
+/// The filter has to succeed within `PASTE_FILTER_TIMEOUT`, otherwise nothing is pasted.
+fn run_paste_filter(command: &config::CommandWrapper, contents: String) -> io::Result<String> {
+    let mut child = Command::new(command.program())
+        .args(command.args())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    // Write and read from other threads, so a filter which doesn't read all of its input or
+    // fills the pipe can't block us
+    let mut stdin = child.stdin.take().expect("child stdin is piped");
+    let writer = thread::spawn_named("paste filter writer", move || {
+        stdin.write_all(contents.as_bytes())
+    });
+    let mut stdout = child.stdout.take().expect("child stdout is piped");
+    let reader = thread::spawn_named("paste filter reader", move || {
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).map(|_| output)
+    });
+
+    let deadline = Instant::now() + PASTE_FILTER_TIMEOUT;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            let msg = format!(
+                "{} didn't finish within {} seconds",
+                command.program(),
+                PASTE_FILTER_TIMEOUT.as_secs()
+            );
+            return Err(io::Error::new(io::ErrorKind::TimedOut, msg));
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    writer.join().unwrap_or(Ok(()))?;
+    let output = reader.join().unwrap_or_else(|_| Ok(Vec::new()))?;
+
+    if !status.success() {
+        let msg = format!("{} failed with {}", command.program(), status);
+        return Err(io::Error::new(io::ErrorKind::Other, msg));
+    }
+
+    String::from_utf8(output).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Send large writes to the pty in multiple messages
+///
+/// This allows the event loop to interleave reading from the pty while the child is still
+/// processing a big paste.
+fn write_chunked<A: ActionContext>(ctx: &mut A, bytes: Vec<u8>) {
+    if bytes.len() <= PASTE_CHUNK_SIZE {
+        ctx.write_bulk_to_pty(bytes);
+        return;
+    }
+
+    for chunk in bytes.chunks(PASTE_CHUNK_SIZE) {
+        ctx.write_bulk_to_pty(chunk.to_vec());
+    }
+}
+
+/// Arguments for opening a file location in the editor
+///
+/// The `{file}`, `{line}` and `{column}` placeholders are replaced with the location, the file is
+/// appended to the arguments when there is no `{file}` placeholder.
+fn editor_args(args: &[String], location: &FileLocation) -> Vec<String> {
+    let file = location.path.to_string_lossy();
+    let line = location.line.to_string();
+    let column = location.column.unwrap_or(1).to_string();
+
+    let mut editor_args: Vec<String> = args
+        .iter()
+        .map(|arg| arg.replace("{file}", &file).replace("{line}", &line).replace("{column}", &column))
+        .collect();
+
+    if !args.iter().any(|arg| arg.contains("{file}")) {
+        editor_args.push(file.into_owned());
+    }
+
+    editor_args
+}
+
 trait RelaxedEq<T: ?Sized = Self> {
     fn relaxed_eq(&self, other: T) -> bool;
 }
@@ -372,11 +754,26 @@ impl From<&'static str> for Action {
 }
 
 impl<'a, A: ActionContext + 'a> Processor<'a, A> {
+    /// Paste the clipboard contents of a `PasteFiltered` binding once its filter finished
+    pub fn paste_filtered(&mut self, filtered: io::Result<String>) {
+        match filtered {
+            Ok(contents) => Action::Paste.paste(&mut self.ctx, &contents),
+            Err(err) => error!("Unable to filter clipboard contents: {}", err),
+        }
+    }
+
     #[inline]
     pub fn mouse_moved(&mut self, x: usize, y: usize, modifiers: ModifiersState) {
         self.ctx.mouse_mut().x = x;
         self.ctx.mouse_mut().y = y;
 
+        // While autoscrolling the mouse only controls the scroll speed
+        let cell_height = self.ctx.size_info().cell_height;
+        if let Some(autoscroll) = self.ctx.autoscroll().as_mut() {
+            autoscroll.mouse_moved(y, cell_height);
+            return;
+        }
+
         let size_info = self.ctx.size_info();
         let point = size_info.pixels_to_coords(x, y);
 
@@ -388,11 +785,12 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         let motion_mode = TermMode::MOUSE_MOTION | TermMode::MOUSE_DRAG;
         let report_mode = TermMode::MOUSE_REPORT_CLICK | motion_mode;
 
-        // Don't launch URLs if mouse has moved
-        if prev_line != self.ctx.mouse().line
+        let cell_changed = prev_line != self.ctx.mouse().line
             || prev_col != self.ctx.mouse().column
-            || prev_side != cell_side
-        {
+            || prev_side != cell_side;
+
+        // Don't launch URLs if mouse has moved
+        if cell_changed {
             self.ctx.mouse_mut().block_url_launcher = true;
         }
 
@@ -406,9 +804,17 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
                 },
                 cell_side,
             );
+
+            // Keep the primary selection up to date while dragging, other platforms only have
+            // a single clipboard which shouldn't be overwritten before the button is released
+            if cfg!(not(any(target_os = "macos", windows))) && cell_changed {
+                self.ctx.copy_selection(ClipboardBuffer::Selection);
+            }
         } else if self.ctx.terminal_mode().intersects(motion_mode)
-            // Only report motion when changing cells
-            && (prev_line != self.ctx.mouse().line || prev_col != self.ctx.mouse().column)
+            // Only report motion when changing cells, unless pixels are reported
+            && (prev_line != self.ctx.mouse().line
+                || prev_col != self.ctx.mouse().column
+                || self.ctx.terminal_mode().contains(TermMode::SGR_PIXEL_MOUSE))
             && size_info.contains_point(x, y)
         {
             if self.ctx.mouse().left_button_state == ElementState::Pressed {
@@ -462,12 +868,30 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
 
     pub fn sgr_mouse_report(&mut self, button: u8, state: ElementState) {
         let (line, column) = (self.ctx.mouse().line, self.ctx.mouse().column);
+        self.sgr_report(button, column.0 + 1, line.0 + 1, state);
+    }
+
+    /// Report the mouse position in pixels relative to the top left of the text area
+    pub fn sgr_pixel_mouse_report(&mut self, button: u8, state: ElementState) {
+        let size_info = self.ctx.size_info();
+        let x = self.ctx.mouse().x.saturating_sub(size_info.padding_x as usize);
+        let y = self.ctx.mouse().y.saturating_sub(size_info.padding_y as usize);
+        self.sgr_report(button, x + 1, y + 1, state);
+    }
+
+    fn sgr_report(&mut self, button: u8, x: usize, y: usize, state: ElementState) {
         let c = match state {
             ElementState::Pressed => 'M',
             ElementState::Released => 'm',
         };
 
-        let msg = format!("\x1b[<{};{};{}{}", button, column + 1, line + 1, c);
+        let msg = format!("\x1b[<{};{};{}{}", button, x, y, c);
+        self.ctx.write_to_pty(msg.into_bytes());
+    }
+
+    pub fn urxvt_mouse_report(&mut self, button: u8) {
+        let (line, column) = (self.ctx.mouse().line, self.ctx.mouse().column);
+        let msg = format!("\x1b[{};{};{}M", 32 + u32::from(button), column + 1, line + 1);
         self.ctx.write_to_pty(msg.into_bytes());
     }
 
@@ -484,11 +908,17 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
             mods += 16;
         }
 
-        // Report mouse events
-        if self.ctx.terminal_mode().contains(TermMode::SGR_MOUSE) {
+        // Report mouse events, the SGR encodings can report releases of specific buttons
+        let mode = self.ctx.terminal_mode();
+        let sgr = mode.intersects(TermMode::SGR_MOUSE | TermMode::SGR_PIXEL_MOUSE);
+        let button = if state == ElementState::Released && !sgr { 3 } else { button };
+
+        if mode.contains(TermMode::SGR_PIXEL_MOUSE) {
+            self.sgr_pixel_mouse_report(button + mods, state);
+        } else if mode.contains(TermMode::SGR_MOUSE) {
             self.sgr_mouse_report(button + mods, state);
-        } else if let ElementState::Released = state {
-            self.normal_mouse_report(3 + mods);
+        } else if mode.contains(TermMode::URXVT_MOUSE) {
+            self.urxvt_mouse_report(button + mods);
         } else {
             self.normal_mouse_report(button + mods);
         }
@@ -507,23 +937,22 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
     }
 
     pub fn on_mouse_press(&mut self, button: MouseButton, modifiers: ModifiersState) {
-        let now = Instant::now();
-        let elapsed = self.ctx.mouse().last_click_timestamp.elapsed();
-        self.ctx.mouse_mut().last_click_timestamp = now;
+        // Clicks on the close button of the message bar don't reach the terminal
+        if button == MouseButton::Left && self.message_close_at_cursor() {
+            self.ctx.close_message();
+            return;
+        }
 
-        let button_changed = self.ctx.mouse().last_button != button;
+        let click_state = self.next_click_state(button);
+        self.ctx.mouse_mut().last_click_timestamp = Instant::now();
 
-        self.ctx.mouse_mut().click_state = match self.ctx.mouse().click_state {
-            ClickState::Click
-                if !button_changed && elapsed < self.mouse_config.double_click.threshold =>
-            {
+        self.ctx.mouse_mut().click_state = match click_state {
+            ClickState::DoubleClick => {
                 self.ctx.mouse_mut().block_url_launcher = true;
                 self.on_mouse_double_click(button);
                 ClickState::DoubleClick
             },
-            ClickState::DoubleClick
-                if !button_changed && elapsed < self.mouse_config.triple_click.threshold =>
-            {
+            ClickState::TripleClick => {
                 self.ctx.mouse_mut().block_url_launcher = true;
                 self.on_mouse_triple_click(button);
                 ClickState::TripleClick
@@ -534,10 +963,14 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
 
                 self.ctx.clear_selection();
 
-                // Start new empty selection
+                // Start new empty selection, holding alt selects a block
                 if let Some(point) = self.ctx.mouse_coords() {
                     let side = self.ctx.mouse().cell_side;
-                    self.ctx.simple_selection(point, side);
+                    if modifiers.alt {
+                        self.ctx.block_selection(point, side);
+                    } else {
+                        self.ctx.simple_selection(point, side);
+                    }
                 }
 
                 let report_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
@@ -557,6 +990,43 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         };
     }
 
+    /// Click state after pressing `button` now
+    fn next_click_state(&self, button: MouseButton) -> ClickState {
+        let mouse = self.ctx.mouse();
+        let elapsed = mouse.last_click_timestamp.elapsed();
+        let button_changed = mouse.last_button != button;
+
+        match mouse.click_state {
+            ClickState::Click
+                if !button_changed && elapsed < self.mouse_config.double_click.threshold =>
+            {
+                ClickState::DoubleClick
+            },
+            ClickState::DoubleClick
+                if !button_changed && elapsed < self.mouse_config.triple_click.threshold =>
+            {
+                ClickState::TripleClick
+            },
+            _ => ClickState::Click,
+        }
+    }
+
+    /// Check if the mouse is on the close button of the message bar
+    fn message_close_at_cursor(&self) -> bool {
+        let size_info = self.ctx.size_info();
+        if size_info.reserved_lines == 0 || self.ctx.message().is_none() {
+            return false;
+        }
+
+        // The mouse line stops at the terminal, the message bar starts right below it
+        let mouse = self.ctx.mouse();
+        let line = mouse.y.saturating_sub(size_info.padding_y as usize)
+            / size_info.cell_height as usize;
+
+        line == size_info.lines().0
+            && mouse.column + message_bar::CLOSE_BUTTON_TEXT.len() >= size_info.cols()
+    }
+
     pub fn on_mouse_release(&mut self, button: MouseButton, modifiers: ModifiersState) {
         let report_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
         if !modifiers.shift && self.ctx.terminal_mode().intersects(report_modes)
@@ -579,7 +1049,7 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         self.ctx.copy_selection(ClipboardBuffer::Selection);
     }
 
-    // Spawn URL launcher when clicking on URLs
+    // Spawn URL launcher when clicking on URLs and the editor when clicking on file locations
     fn launch_url(&self, modifiers: ModifiersState) -> Option<()> {
         if !self.mouse_config.url.modifiers.relaxed_eq(modifiers)
             || self.ctx.mouse().block_url_launcher
@@ -588,15 +1058,24 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
 
         let point = self.ctx.mouse_coords()?;
-        let text = self.ctx.url(point.into())?;
 
-        let launcher = self.mouse_config.url.launcher.as_ref()?;
-        let mut args = launcher.args().to_vec();
-        args.push(text);
+        let (program, args) = match self.ctx.url(point.into()) {
+            Some(text) => {
+                let launcher = self.mouse_config.url.launcher.as_ref()?;
+                let mut args = launcher.args().to_vec();
+                args.push(text);
+                (launcher.program(), args)
+            },
+            None => {
+                let location = self.ctx.file_location(point.into())?;
+                let editor = self.mouse_config.url.editor.as_ref()?;
+                (editor.program(), editor_args(editor.args(), &location))
+            },
+        };
 
-        match start_daemon(launcher.program(), &args) {
-            Ok(_) => debug!("Launched {} with args {:?}", launcher.program(), args),
-            Err(_) => warn!("Unable to launch {} with args {:?}", launcher.program(), args),
+        match start_daemon(program, &args) {
+            Ok(_) => debug!("Launched {} with args {:?}", program, args),
+            Err(_) => warn!("Unable to launch {} with args {:?}", program, args),
         }
 
         Some(())
@@ -693,16 +1172,66 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
 
         match state {
+            ElementState::Pressed if self.starts_autoscroll(button, modifiers) => {
+                let mouse = self.ctx.mouse();
+                let origin = Point::new(mouse.line, mouse.column);
+                let y = mouse.y;
+                self.ctx.start_autoscroll(Autoscroll::new(origin, y));
+            },
             ElementState::Pressed => {
-                self.process_mouse_bindings(modifiers, button);
+                let clicks = match self.next_click_state(button) {
+                    ClickState::DoubleClick => 2,
+                    ClickState::TripleClick => 3,
+                    _ => 1,
+                };
+                self.process_mouse_bindings(modifiers, button, clicks);
                 self.on_mouse_press(button, modifiers);
             },
-            ElementState::Released => self.on_mouse_release(button, modifiers),
+            ElementState::Released => {
+                let autoscroll = if button == MouseButton::Middle {
+                    self.ctx.autoscroll().take()
+                } else {
+                    None
+                };
+
+                match autoscroll {
+                    // Clicking without dragging runs the regular middle click bindings
+                    Some(autoscroll) => {
+                        if !autoscroll.dragged() {
+                            self.process_mouse_bindings(modifiers, button, 1);
+                        }
+                    },
+                    None => self.on_mouse_release(button, modifiers),
+                }
+            },
         }
 
         self.ctx.mouse_mut().last_button = button;
     }
 
+    /// Check if pressing `button` starts autoscrolling
+    ///
+    /// Autoscrolling is only possible with the middle button, while the mouse isn't reported to
+    /// the application.
+    fn starts_autoscroll(&self, button: MouseButton, modifiers: ModifiersState) -> bool {
+        let report_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
+        button == MouseButton::Middle
+            && self.mouse_config.autoscroll
+            && (modifiers.shift || !self.ctx.terminal_mode().intersects(report_modes))
+    }
+
+    /// Scroll the viewport while autoscrolling with the middle mouse button
+    pub fn autoscroll(&mut self) {
+        let lines = match self.ctx.autoscroll() {
+            Some(autoscroll) => autoscroll.tick(),
+            None => return,
+        };
+
+        if lines != 0 {
+            self.ctx.scroll(Scroll::Lines(lines));
+        }
+    }
+
     /// Process key input
     ///
     /// If a keybinding was run, returns true. Otherwise returns false.
@@ -713,7 +1242,9 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
                 *self.ctx.received_count() = 0;
                 *self.ctx.suppress_chars() = false;
 
-                if self.process_key_bindings(input) {
+                // The effect tuning overlay takes the keys it uses away from the bindings
+                let tuned = input.virtual_keycode.map_or(false, |key| self.ctx.tune_effects(key));
+                if tuned || self.process_key_bindings(input) {
                     *self.ctx.suppress_chars() = true;
                 }
             },
@@ -788,10 +1319,23 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
     /// for its action to be executed.
     ///
     /// Returns true if an action is executed.
-    fn process_mouse_bindings(&mut self, mods: ModifiersState, button: MouseButton) -> bool {
+    fn process_mouse_bindings(
+        &mut self,
+        mods: ModifiersState,
+        button: MouseButton,
+        clicks: u8,
+    ) -> bool {
         let mut has_binding = false;
         for binding in self.mouse_bindings {
-            if binding.is_triggered_by(self.ctx.terminal_mode(), mods, &button, true) {
+            if binding.is_triggered_by_click(self.ctx.terminal_mode(), mods, button, clicks) {
+                // Middle click pasting can be disabled without changing the bindings
+                if button == MouseButton::Middle
+                    && binding.action == Action::PasteSelection
+                    && !self.mouse_config.middle_click_paste
+                {
+                    continue;
+                }
+
                 // binding was triggered; run the action
                 let mouse_mode = !mods.shift && self.ctx.terminal_mode().intersects(
                     TermMode::MOUSE_REPORT_CLICK
@@ -815,13 +1359,19 @@ mod tests {
     use glutin::{VirtualKeyCode, Event, WindowEvent, ElementState, MouseButton, ModifiersState};
 
     use crate::term::{SizeInfo, Term, TermMode};
+    use crate::term::autoscroll::Autoscroll;
+    use crate::term::dump::DumpSource;
     use crate::event::{Mouse, ClickState, WindowChanges};
     use crate::config::{self, Config, ClickHandler};
     use crate::index::{Point, Side};
+    use crate::message_bar::Message;
     use crate::selection::Selection;
     use crate::grid::Scroll;
+    use crate::url::FileLocation;
+    use crate::playback::Control;
 
-    use super::{Action, Binding, Processor};
+    use super::{Action, Binding, Processor, sanitize_paste, editor_args};
+    use super::{filter_paste, strip_escapes};
     use copypasta::Buffer as ClipboardBuffer;
 
     const KEY: VirtualKeyCode = VirtualKeyCode::Key0;
@@ -847,16 +1397,51 @@ mod tests {
 
     impl <'a>super::ActionContext for ActionContext<'a> {
         fn write_to_pty<B: Into<Cow<'static, [u8]>>>(&mut self, _val: B) {}
+        fn write_bulk_to_pty<B: Into<Cow<'static, [u8]>>>(&mut self, _val: B) {}
+        fn pasted(&mut self, _text: &str) {}
         fn update_selection(&mut self, _point: Point, _side: Side) {}
         fn simple_selection(&mut self, _point: Point, _side: Side) {}
+        fn block_selection(&mut self, _point: Point, _side: Side) {}
         fn copy_selection(&self, _buffer: ClipboardBuffer) {}
         fn clear_selection(&mut self) {}
         fn change_font_size(&mut self, _delta: f32) {}
         fn reset_font_size(&mut self) {}
+        fn change_background_opacity(&mut self, _delta: f32) {}
+        fn reset_background_opacity(&mut self) {}
         fn clear_history(&mut self) {}
         fn clear_log(&mut self) {}
+        fn message(&self) -> Option<Message> { None }
+        fn close_message(&mut self) {}
         fn hide_window(&mut self) {}
+        fn toggle_fullscreen(&mut self) {}
+        fn toggle_simple_fullscreen(&mut self) {}
+        fn toggle_decorations(&mut self) {}
+        fn toggle_always_on_top(&mut self) {}
+        fn toggle_sticky(&mut self) {}
         fn spawn_new_instance(&mut self) {}
+        fn create_new_window(&mut self) {}
+        fn quit(&mut self) {}
+        fn filter_paste(&mut self, _: &config::PasteFilter, _: String) {}
+        fn toggle_output_pause(&mut self) {}
+        fn set_color_scheme(&mut self, _name: &str) {}
+        fn cycle_color_scheme(&mut self) {}
+        fn toggle_bookmark(&mut self) {}
+        fn toggle_forced_effects(&mut self) {}
+        fn toggle_effect_tuning(&mut self) {}
+        fn toggle_perf_hud(&mut self) {}
+        fn tune_effects(&mut self, _key: VirtualKeyCode) -> bool { false }
+        fn jump_to_annotation(&mut self, _forward: bool) {}
+        fn playback(&mut self, _: Control) {}
+        fn dump(&mut self, _source: DumpSource) {}
+        fn screenshot(&mut self, _selection: bool) {}
+
+        fn start_autoscroll(&mut self, autoscroll: Autoscroll) {
+            self.terminal.autoscroll = Some(autoscroll);
+        }
+
+        fn autoscroll(&mut self) -> &mut Option<Autoscroll> {
+            &mut self.terminal.autoscroll
+        }
 
         fn terminal_mode(&self) -> TermMode {
             *self.terminal.mode()
@@ -901,6 +1486,10 @@ mod tests {
             None
         }
 
+        fn file_location(&self, _: Point<usize>) -> Option<FileLocation> {
+            None
+        }
+
         fn received_count(&mut self) -> &mut usize {
             &mut self.received_count
         }
@@ -934,6 +1523,7 @@ mod tests {
                     padding_x: 0.0,
                     padding_y: 0.0,
                     dpr: 1.0,
+                    reserved_lines: 0,
                 };
 
                 let mut terminal = Term::new(&config, size);
@@ -968,6 +1558,9 @@ mod tests {
                         hide_when_typing: false,
                         faux_scrollback_lines: None,
                         url: Default::default(),
+                        middle_click_paste: true,
+                        autoscroll: false,
+                        bindings: Vec::new(),
                     },
                     scrolling_config: &config::Scrolling::default(),
                     key_bindings: &config.key_bindings()[..],
@@ -1138,4 +1731,40 @@ mod tests {
         mode: TermMode::NONE,
         mods: ModifiersState { shift: false, ctrl: false, alt: true, logo: true }
     }
+
+    #[test]
+    fn sanitize_paste_strips_controls() {
+        assert_eq!(sanitize_paste("ls\x03 -l\x1b[A\t\r\u{9b}x"), "ls -l[A\t\rx");
+    }
+
+    #[test]
+    fn strip_escapes_keeps_text() {
+        assert_eq!(strip_escapes("\x1b[1;31mred\x1b[0m text"), "red text");
+        assert_eq!(strip_escapes("\x1b]0;title\x07a\x1b]8;;url\x1b\\b"), "ab");
+        assert_eq!(strip_escapes("\x1b(Bc\x1b=d\u{9b}2Je"), "cde");
+        assert_eq!(strip_escapes("unterminated\x1b[1"), "unterminated");
+    }
+
+    #[test]
+    fn filter_paste_collapses_whitespace() {
+        let filter = config::PasteFilter {
+            strip_escapes: true,
+            collapse_whitespace: true,
+            command: None,
+        };
+
+        let contents = " git \x1b[32mstatus\x1b[0m\n\t--short \r\n".to_owned();
+        assert_eq!(filter_paste(&filter, contents).unwrap(), "git status --short");
+    }
+
+    #[test]
+    fn editor_args_substitute_location() {
+        let location = FileLocation { path: "/src/main.rs".into(), line: 10, column: None };
+
+        let args = vec!["--goto".to_owned(), "{file}:{line}:{column}".to_owned()];
+        assert_eq!(editor_args(&args, &location), vec!["--goto", "/src/main.rs:10:1"]);
+
+        let args = vec!["+{line}".to_owned()];
+        assert_eq!(editor_args(&args, &location), vec!["+10", "/src/main.rs"]);
+    }
 }
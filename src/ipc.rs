@@ -0,0 +1,413 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Messages between `alacritty msg` and a running instance
+//!
+//! Every instance listens on a Unix socket, or a named pipe on Windows. A client sends one
+//! JSON encoded `Message` terminated by a newline and receives one `Reply` in the same format.
+use std::env;
+#[cfg(windows)]
+use std::fs::OpenOptions;
+#[cfg(not(windows))]
+use std::fs::{self, DirBuilder, Permissions};
+use std::io::{self, BufRead, BufReader, Write};
+#[cfg(not(windows))]
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+#[cfg(not(windows))]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc;
+#[cfg(not(windows))]
+use std::time::Duration;
+
+use glutin::EventsLoopProxy;
+#[cfg(windows)]
+use miow::pipe::{NamedPipe, NamedPipeBuilder};
+use serde_json as json;
+
+use crate::util::thread;
+
+/// Environment variable with the socket path of the instance a shell runs in
+pub const SOCKET_ENV: &str = "ALACRITTY_SOCKET";
+
+/// Time `alacritty msg` waits for a reply
+#[cfg(not(windows))]
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time a running instance waits for a client to send its message
+#[cfg(not(windows))]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Request to a running instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum Message {
+    /// Open a new window
+    CreateWindow {
+        working_directory: Option<PathBuf>,
+        command: Option<Vec<String>>,
+    },
+
+    /// Merge `key.path=value` options over the config, optionally dropping earlier options
+    Config {
+        options: Vec<String>,
+        #[serde(default)]
+        reset: bool,
+    },
+
+    /// Annotate a cell of the active area, lines and columns are counted from 0
+    ///
+    /// Without a window id, the first window is annotated.
+    Annotate {
+        window: Option<usize>,
+        line: usize,
+        column: usize,
+        label: Option<String>,
+    },
+
+    /// Remove all annotations of a window
+    ClearAnnotations {
+        window: Option<usize>,
+    },
+
+    /// Write the visible screen of a window to an SVG image in the screenshot directory
+    ExportSvg {
+        window: Option<usize>,
+    },
+
+    /// Report the state of the instance
+    GetState,
+}
+
+/// Answer of a running instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reply {
+    Ok,
+    State(InstanceState),
+    /// File written for the message
+    Path(PathBuf),
+    Error(String),
+}
+
+/// State reported for `Message::GetState`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstanceState {
+    pub pid: u32,
+    pub socket: PathBuf,
+    pub windows: Vec<WindowState>,
+}
+
+/// State of a single window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowState {
+    /// Platform window id, as exported in `WINDOWID`
+    pub id: Option<usize>,
+    pub title: String,
+    pub columns: usize,
+    pub lines: usize,
+    pub shell_pid: Option<u32>,
+    pub working_directory: Option<PathBuf>,
+}
+
+/// Message received by the socket, waiting for the main loop to reply
+pub struct Request {
+    message: Message,
+    reply_tx: mpsc::Sender<Reply>,
+}
+
+impl Request {
+    #[inline]
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    /// Send the reply to the client
+    pub fn reply(self, reply: Reply) {
+        // The client might have disconnected already
+        let _ = self.reply_tx.send(reply);
+    }
+}
+
+/// Socket of this instance
+///
+/// Received messages wake up the events loop and are picked up with `pending_requests`.
+pub struct Socket {
+    path: PathBuf,
+    rx: mpsc::Receiver<Request>,
+    _thread: thread::JoinHandle<()>,
+}
+
+impl Socket {
+    /// Start listening on the socket of this instance
+    #[cfg(not(windows))]
+    pub fn new(proxy: EventsLoopProxy) -> io::Result<Socket> {
+        let path = socket_dir()?.join(format!("Alacritty-{}.sock", process::id()));
+
+        // A previous instance with the same pid might not have cleaned up
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        fs::set_permissions(&path, Permissions::from_mode(0o600))?;
+
+        let (tx, rx) = mpsc::channel();
+        let _thread = thread::spawn_named("ipc socket", move || {
+            for stream in listener.incoming() {
+                let result = stream
+                    .and_then(|stream| {
+                        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+                        Ok(stream)
+                    })
+                    .map_err(Error::from)
+                    .and_then(|mut stream| handle_connection(&mut stream, &tx, &proxy));
+                if let Err(err) = result {
+                    warn!("Unable to handle IPC message: {}", err);
+                }
+            }
+        });
+
+        Ok(Socket { path, rx, _thread })
+    }
+
+    /// Start listening on the named pipe of this instance
+    #[cfg(windows)]
+    pub fn new(proxy: EventsLoopProxy) -> io::Result<Socket> {
+        let path = PathBuf::from(format!(r"\\.\pipe\Alacritty-{}", process::id()));
+
+        // Create the first instance of the pipe here, so errors are reported to the caller
+        let mut pipe = NamedPipeBuilder::new(&path).create()?;
+
+        let (tx, rx) = mpsc::channel();
+        let pipe_path = path.clone();
+        let _thread = thread::spawn_named("ipc socket", move || loop {
+            let result = pipe
+                .connect()
+                .map_err(Error::from)
+                .and_then(|_| handle_connection(&mut pipe, &tx, &proxy));
+            if let Err(err) = result {
+                warn!("Unable to handle IPC message: {}", err);
+            }
+            let _ = pipe.disconnect();
+
+            pipe = match NamedPipeBuilder::new(&pipe_path).first(false).create() {
+                Ok(pipe) => pipe,
+                Err(err) => {
+                    error!("Unable to recreate IPC pipe: {}", err);
+                    break;
+                },
+            };
+        });
+
+        Ok(Socket { path, rx, _thread })
+    }
+
+    /// Path clients connect to
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the messages received since the last call
+    pub fn pending_requests(&self) -> Vec<Request> {
+        self.rx.try_iter().collect()
+    }
+}
+
+/// Directory of the socket, only accessible by the current user
+///
+/// Without `XDG_RUNTIME_DIR`, a directory is created in the temporary directory. An existing
+/// directory which belongs to another user or is accessible by others is refused.
+#[cfg(not(windows))]
+fn socket_dir() -> io::Result<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+
+    let uid = unsafe { libc::getuid() };
+    let dir = env::temp_dir().join(format!("alacritty-{}", uid));
+    match DirBuilder::new().mode(0o700).create(&dir) {
+        Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => (),
+        result => result?,
+    }
+
+    // Not following symlinks, another user could point one at a directory of ours
+    let metadata = fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        let msg = format!("{} is not a private directory of the current user", dir.display());
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, msg));
+    }
+
+    Ok(dir)
+}
+
+#[cfg(not(windows))]
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Read one message, pass it to the main loop and write its reply
+fn handle_connection<S: io::Read + Write>(
+    stream: &mut S,
+    tx: &mpsc::Sender<Request>,
+    proxy: &EventsLoopProxy,
+) -> Result<(), Error> {
+    let mut line = String::new();
+    BufReader::new(&mut *stream).read_line(&mut line)?;
+
+    let reply = match json::from_str(&line) {
+        Ok(message) => {
+            let (reply_tx, reply_rx) = mpsc::channel();
+            if tx.send(Request { message, reply_tx }).is_err() {
+                return Ok(());
+            }
+            let _ = proxy.wakeup();
+
+            reply_rx
+                .recv()
+                .unwrap_or_else(|_| Reply::Error(String::from("Alacritty is shutting down")))
+        },
+        Err(err) => Reply::Error(format!("Invalid message: {}", err)),
+    };
+
+    writeln!(stream, "{}", json::to_string(&reply)?)?;
+
+    Ok(())
+}
+
+/// Send a message to a running instance and wait for its reply
+///
+/// Without a socket path the instance in `ALACRITTY_SOCKET` is used.
+pub fn send_message(socket: Option<&Path>, message: &Message) -> Result<Reply, Error> {
+    let path = match socket {
+        Some(path) => path.to_path_buf(),
+        None => env::var_os(SOCKET_ENV).map(PathBuf::from).ok_or(Error::NoSocket)?,
+    };
+
+    let mut stream = connect(&path)?;
+    writeln!(stream, "{}", json::to_string(message)?)?;
+
+    let mut line = String::new();
+    BufReader::new(&mut stream).read_line(&mut line)?;
+
+    Ok(json::from_str(&line)?)
+}
+
+#[cfg(not(windows))]
+fn connect(path: &Path) -> io::Result<UnixStream> {
+    let stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    Ok(stream)
+}
+
+#[cfg(windows)]
+fn connect(path: &Path) -> io::Result<::std::fs::File> {
+    // Another client might be connected, all instances of the pipe are busy until it's done
+    NamedPipe::wait(path, None)?;
+    OpenOptions::new().read(true).write(true).open(path)
+}
+
+/// Errors occurring while sending or handling messages
+#[derive(Debug)]
+pub enum Error {
+    /// Neither a socket path was given, nor is `ALACRITTY_SOCKET` set
+    NoSocket,
+
+    /// Error communicating through the socket
+    Io(io::Error),
+
+    /// Message or reply which is not valid JSON
+    Json(json::Error),
+}
+
+impl ::std::error::Error for Error {
+    fn cause(&self) -> Option<&dyn (::std::error::Error)> {
+        match *self {
+            Error::NoSocket => None,
+            Error::Io(ref err) => Some(err),
+            Error::Json(ref err) => Some(err),
+        }
+    }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::NoSocket => "No socket given and ALACRITTY_SOCKET is not set",
+            Error::Io(ref err) => err.description(),
+            Error::Json(ref err) => err.description(),
+        }
+    }
+}
+
+impl ::std::fmt::Display for Error {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        match *self {
+            Error::NoSocket => write!(f, "{}", ::std::error::Error::description(self)),
+            Error::Io(ref err) => write!(f, "Unable to communicate with Alacritty: {}", err),
+            Error::Json(ref err) => write!(f, "Invalid IPC message: {}", err),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(val: io::Error) -> Error {
+        Error::Io(val)
+    }
+}
+
+impl From<json::Error> for Error {
+    fn from(val: json::Error) -> Error {
+        Error::Json(val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json as json;
+
+    use super::{Message, Reply};
+
+    #[test]
+    fn message_format() {
+        let message: Message = json::from_str(
+            r#"{"type":"create_window","working_directory":"/tmp","command":["htop"]}"#,
+        ).unwrap();
+        assert_eq!(message, Message::CreateWindow {
+            working_directory: Some("/tmp".into()),
+            command: Some(vec![String::from("htop")]),
+        });
+
+        let message = Message::Config { options: vec![String::from("font.size=14")], reset: true };
+        let serialized = json::to_string(&message).unwrap();
+        assert_eq!(json::from_str::<Message>(&serialized).unwrap(), message);
+
+        let message: Message =
+            json::from_str(r#"{"type":"annotate","line":2,"column":5,"label":"error"}"#).unwrap();
+        assert_eq!(message, Message::Annotate {
+            window: None,
+            line: 2,
+            column: 5,
+            label: Some(String::from("error")),
+        });
+    }
+
+    #[test]
+    fn reply_format() {
+        assert_eq!(json::to_string(&Reply::Ok).unwrap(), r#""ok""#);
+        assert_eq!(
+            json::to_string(&Reply::Error(String::from("failed"))).unwrap(),
+            r#"{"error":"failed"}"#
+        );
+    }
+}
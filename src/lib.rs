@@ -27,6 +27,8 @@ extern crate objc;
 #[macro_use]
 pub mod macros;
 pub mod ansi;
+pub mod appearance;
+pub mod asciicast;
 pub mod cli;
 pub mod config;
 pub mod display;
@@ -35,18 +37,24 @@ pub mod event_loop;
 pub mod grid;
 pub mod index;
 pub mod input;
+pub mod ipc;
 pub mod locale;
 pub mod logging;
+pub mod message_bar;
 pub mod meter;
+pub mod notification;
 pub mod panic;
+pub mod playback;
+pub mod ref_test;
 pub mod renderer;
+pub mod screenshot;
 pub mod selection;
 pub mod sync;
 pub mod term;
 pub mod tty;
+pub mod url;
 pub mod util;
 pub mod window;
-mod url;
 
 use std::ops::Mul;
 
@@ -60,7 +68,7 @@ pub enum MouseCursor {
     Text,
 }
 
-#[derive(Debug, Eq, PartialEq, Copy, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default, Hash, Serialize, Deserialize)]
 pub struct Rgb {
     pub r: u8,
     pub g: u8,
@@ -84,6 +92,21 @@ impl Mul<f32> for Rgb {
     }
 }
 
+impl Rgb {
+    /// Mix `amount` of `other` into this color, `0.0` keeps the color unchanged
+    pub fn blend(self, other: Rgb, amount: f32) -> Rgb {
+        let mix = |a: u8, b: u8| {
+            (f32::from(a) + (f32::from(b) - f32::from(a)) * amount).round().max(0.0).min(255.0) as u8
+        };
+
+        Rgb {
+            r: mix(self.r, other.r),
+            g: mix(self.g, other.g),
+            b: mix(self.b, other.b),
+        }
+    }
+}
+
 
 pub mod gl {
     #![allow(clippy::all)]
@@ -16,52 +16,69 @@
 //!
 //! The main executable is supposed to call `initialize()` exactly once during
 //! startup. All logging messages are written to stdout, given that their
-//! log-level is sufficient for the level configured in `cli::Options`.
+//! log-level is sufficient for the level configured in `cli::Options`. Errors and warnings
+//! are also shown in the message bar.
+//!
+//! When alacritty panics, a crash report with the screen and the recent output of every terminal
+//! is written to the temporary directory.
+use crate::ansi;
 use crate::cli;
+use crate::config::LogRotation;
+use crate::message_bar::{Message, MessageBuffer, MessageType};
+use crate::sync::FairMutex;
+use crate::term::dump::DumpSource;
+use crate::term::Term;
 use log::{self, Level};
 use time;
 
+use std::collections::VecDeque;
 use std::env;
+use std::ffi::OsString;
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, LineWriter, Stdout, Write};
-use std::path::PathBuf;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::panic::{self, PanicInfo};
+use std::path::{Path, PathBuf};
 use std::process;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+/// Number of bytes read from a pty which are kept for crash reports
+const OUTPUT_HISTORY_SIZE: usize = 0x4000;
 
 pub fn initialize(options: &cli::Options) -> Result<LoggerProxy, log::SetLoggerError> {
     // Use env_logger if RUST_LOG environment variable is defined. Otherwise,
     // use the alacritty-only logger.
-    if ::std::env::var("RUST_LOG").is_ok() {
+    let proxy = if ::std::env::var("RUST_LOG").is_ok() {
         ::env_logger::try_init()?;
-        Ok(LoggerProxy::default())
+        LoggerProxy::default()
     } else {
         let logger = Logger::new(options.log_level);
         let proxy = logger.proxy();
 
         log::set_boxed_logger(Box::new(logger))?;
 
-        Ok(proxy)
-    }
+        proxy
+    };
+
+    attach_crash_report(proxy.terminals.clone());
+
+    Ok(proxy)
 }
 
 /// Proxy object for bidirectional communicating with the global logger.
 #[derive(Clone, Default)]
 pub struct LoggerProxy {
-    errors: Arc<AtomicBool>,
-    warnings: Arc<AtomicBool>,
+    messages: MessageBuffer,
     logfile_proxy: OnDemandLogFileProxy,
+    terminals: Arc<parking_lot::Mutex<Vec<WatchedTerminal>>>,
 }
 
 impl LoggerProxy {
-    /// Check for new logged errors.
-    pub fn errors(&self) -> bool {
-        self.errors.load(Ordering::Relaxed)
-    }
-
-    /// Check for new logged warnings.
-    pub fn warnings(&self) -> bool {
-        self.warnings.load(Ordering::Relaxed)
+    /// Logged errors and warnings which haven't been closed yet.
+    pub fn messages(&self) -> &MessageBuffer {
+        &self.messages
     }
 
     /// Get the path of the log file if it has been created.
@@ -73,23 +90,119 @@ impl LoggerProxy {
         }
     }
 
-    /// Clear log warnings/errors from the Alacritty UI.
-    pub fn clear(&mut self) {
-        self.errors.store(false, Ordering::Relaxed);
-        self.warnings.store(false, Ordering::Relaxed);
-    }
-
     pub fn delete_log(&mut self) {
         self.logfile_proxy.delete_log();
     }
+
+    /// Update the size limit of the log file
+    pub fn set_rotation(&self, rotation: LogRotation) {
+        self.logfile_proxy.rotation.set(rotation);
+    }
+
+    /// Include the screen and the recent output of `terminal` in crash reports
+    pub fn watch_terminal(&self, terminal: &Arc<FairMutex<Term>>, output: OutputHistory) {
+        let mut terminals = self.terminals.lock();
+
+        // Forget closed terminals and the previous output of this one
+        terminals.retain(|watched| {
+            watched.terminal.upgrade().map_or(false, |watched| !Arc::ptr_eq(&watched, terminal))
+        });
+
+        terminals.push(WatchedTerminal { terminal: Arc::downgrade(terminal), output });
+    }
+}
+
+/// Most recent output read from a pty
+#[derive(Clone, Default)]
+pub struct OutputHistory(Arc<parking_lot::Mutex<VecDeque<u8>>>);
+
+impl OutputHistory {
+    /// Remember `bytes`, dropping the oldest output beyond `OUTPUT_HISTORY_SIZE`
+    pub fn push(&self, bytes: &[u8]) {
+        let bytes = &bytes[bytes.len().saturating_sub(OUTPUT_HISTORY_SIZE)..];
+
+        let mut history = self.0.lock();
+        let overflow = (history.len() + bytes.len()).saturating_sub(OUTPUT_HISTORY_SIZE);
+        history.drain(..overflow);
+        history.extend(bytes);
+    }
+
+    /// Copy of the remembered output, empty if it's currently being updated
+    pub fn bytes(&self) -> Vec<u8> {
+        self.0.try_lock().map(|history| history.iter().cloned().collect()).unwrap_or_default()
+    }
+}
+
+struct WatchedTerminal {
+    terminal: Weak<FairMutex<Term>>,
+    output: OutputHistory,
+}
+
+/// Write a crash report after the default panic message has been printed
+fn attach_crash_report(terminals: Arc<parking_lot::Mutex<Vec<WatchedTerminal>>>) {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        match write_crash_report(info, &terminals) {
+            Ok(path) => eprintln!("Wrote crash report to {:?}", path),
+            Err(err) => eprintln!("Unable to write crash report: {}", err),
+        }
+    }));
+}
+
+fn write_crash_report(
+    info: &PanicInfo<'_>,
+    terminals: &parking_lot::Mutex<Vec<WatchedTerminal>>,
+) -> Result<PathBuf, io::Error> {
+    let mut path = env::temp_dir();
+    path.push(format!("Alacritty-{}-crash.log", process::id()));
+
+    let mut file = create_private_file(&path)?;
+    writeln!(file, "{}", info)?;
+
+    // Locks might be held by the panicking thread, so they're never waited for
+    let terminals = match terminals.try_lock() {
+        Some(terminals) => terminals,
+        None => return Ok(path),
+    };
+
+    for (index, watched) in terminals.iter().enumerate() {
+        let terminal = match watched.terminal.upgrade() {
+            Some(terminal) => terminal,
+            None => continue,
+        };
+
+        writeln!(file, "\nScreen of terminal {}:", index + 1)?;
+        match terminal.peek(|terminal| terminal.dump(DumpSource::Screen, false)) {
+            Some(screen) => writeln!(file, "{}", screen)?,
+            None => writeln!(file, "Unavailable while the terminal is locked")?,
+        }
+
+        writeln!(file, "\nRecent output of terminal {}:", index + 1)?;
+        writeln!(file, "{}", ansi::printable(&watched.output.bytes()))?;
+    }
+
+    Ok(path)
+}
+
+/// Create a file only the current user can read, replacing an existing file or symlink
+fn create_private_file(path: &Path) -> Result<File, io::Error> {
+    let _ = fs::remove_file(path);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    options.open(path)
 }
 
 struct Logger {
     level: log::LevelFilter,
     logfile: Mutex<OnDemandLogFile>,
     stdout: Mutex<LineWriter<Stdout>>,
-    errors: Arc<AtomicBool>,
-    warnings: Arc<AtomicBool>,
+    messages: MessageBuffer,
 }
 
 impl Logger {
@@ -105,16 +218,15 @@ impl Logger {
             level,
             logfile,
             stdout,
-            errors: Arc::new(AtomicBool::new(false)),
-            warnings: Arc::new(AtomicBool::new(false)),
+            messages: MessageBuffer::default(),
         }
     }
 
     fn proxy(&self) -> LoggerProxy {
         LoggerProxy {
-            errors: self.errors.clone(),
-            warnings: self.warnings.clone(),
+            messages: self.messages.clone(),
             logfile_proxy: self.logfile.lock().expect("").proxy(),
+            terminals: Default::default(),
         }
     }
 }
@@ -154,20 +266,43 @@ impl log::Log for Logger {
                 let _ = stdout.write_all(msg.as_ref());
             }
 
-            match record.level() {
-                Level::Error => self.errors.store(true, Ordering::Relaxed),
-                Level::Warn => self.warnings.store(true, Ordering::Relaxed),
-                _ => (),
-            }
+            let kind = match record.level() {
+                Level::Error => MessageType::Error,
+                Level::Warn => MessageType::Warning,
+                _ => return,
+            };
+            self.messages.push(Message::new(record.args().to_string(), kind));
         }
     }
 
     fn flush(&self) {}
 }
 
+/// Size limit of the log file, shared with the logger
+#[derive(Default)]
+struct Rotation {
+    max_size: AtomicUsize,
+    files: AtomicUsize,
+}
+
+impl Rotation {
+    fn set(&self, rotation: LogRotation) {
+        self.max_size.store(rotation.max_size as usize * 1024 * 1024, Ordering::Relaxed);
+        self.files.store(rotation.files as usize, Ordering::Relaxed);
+    }
+}
+
+/// Path of the `generation`th rotated log file
+fn rotated_path(path: &Path, generation: usize) -> PathBuf {
+    let mut rotated = OsString::from(path);
+    rotated.push(format!(".{}", generation));
+    PathBuf::from(rotated)
+}
+
 #[derive(Clone, Default)]
 struct OnDemandLogFileProxy {
     created: Arc<AtomicBool>,
+    rotation: Arc<Rotation>,
     path: String,
 }
 
@@ -177,12 +312,20 @@ impl OnDemandLogFileProxy {
             let _ = writeln!(io::stdout(), "Deleted log file at {:?}", self.path);
             self.created.store(false, Ordering::Relaxed);
         }
+
+        let path = Path::new(&self.path);
+        let mut generation = 1;
+        while fs::remove_file(rotated_path(path, generation)).is_ok() {
+            generation += 1;
+        }
     }
 }
 
 struct OnDemandLogFile {
     file: Option<LineWriter<File>>,
     created: Arc<AtomicBool>,
+    rotation: Arc<Rotation>,
+    written: usize,
     path: PathBuf,
 }
 
@@ -191,11 +334,39 @@ impl OnDemandLogFile {
         let mut path = env::temp_dir();
         path.push(format!("Alacritty-{}.log", process::id()));
 
+        Self::with_path(path)
+    }
+
+    fn with_path(path: PathBuf) -> Self {
+        let rotation = Rotation::default();
+        rotation.set(LogRotation::default());
+
         OnDemandLogFile {
             path,
             file: None,
             created: Arc::new(AtomicBool::new(false)),
+            rotation: Arc::new(rotation),
+            written: 0,
+        }
+    }
+
+    /// Move the log file to the first rotated file, shifting the older ones
+    fn rotate(&mut self) {
+        self.file = None;
+        self.written = 0;
+
+        let files = self.rotation.files.load(Ordering::Relaxed);
+        if files == 0 {
+            let _ = fs::remove_file(&self.path);
+            return;
         }
+
+        let _ = fs::remove_file(rotated_path(&self.path, files));
+        for generation in (1..files).rev() {
+            let from = rotated_path(&self.path, generation);
+            let _ = fs::rename(from, rotated_path(&self.path, generation + 1));
+        }
+        let _ = fs::rename(&self.path, rotated_path(&self.path, 1));
     }
 
     fn file(&mut self) -> Result<&mut LineWriter<File>, io::Error> {
@@ -213,9 +384,13 @@ impl OnDemandLogFile {
 
             match file {
                 Ok(file) => {
+                    self.written = file.metadata().map(|meta| meta.len() as usize).unwrap_or(0);
                     self.file = Some(io::LineWriter::new(file));
-                    self.created.store(true, Ordering::Relaxed);
-                    let _ = writeln!(io::stdout(), "Created log file at {:?}", self.path);
+
+                    // Rotated log files are replaced silently
+                    if !self.created.swap(true, Ordering::Relaxed) {
+                        let _ = writeln!(io::stdout(), "Created log file at {:?}", self.path);
+                    }
                 }
                 Err(e) => {
                     let _ = writeln!(io::stdout(), "Unable to create log file: {}", e);
@@ -230,6 +405,7 @@ impl OnDemandLogFile {
     fn proxy(&self) -> OnDemandLogFileProxy {
         OnDemandLogFileProxy {
             created: self.created.clone(),
+            rotation: self.rotation.clone(),
             path: self.path.to_string_lossy().to_string(),
         }
     }
@@ -237,10 +413,63 @@ impl OnDemandLogFile {
 
 impl Write for OnDemandLogFile {
     fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
-        self.file()?.write(buf)
+        let max_size = self.rotation.max_size.load(Ordering::Relaxed);
+        if max_size > 0 && self.file.is_some() && self.written + buf.len() > max_size {
+            self.rotate();
+        }
+
+        let written = self.file()?.write(buf)?;
+        self.written += written;
+        Ok(written)
     }
 
     fn flush(&mut self) -> Result<(), io::Error> {
         self.file()?.flush()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+    use std::process;
+    use std::sync::atomic::Ordering;
+
+    use super::{rotated_path, OnDemandLogFile, OutputHistory, OUTPUT_HISTORY_SIZE};
+
+    #[test]
+    fn log_rotation() {
+        let mut path = env::temp_dir();
+        path.push(format!("Alacritty-{}-rotation-test.log", process::id()));
+
+        let mut logfile = OnDemandLogFile::with_path(path.clone());
+        logfile.rotation.max_size.store(8, Ordering::Relaxed);
+        logfile.rotation.files.store(2, Ordering::Relaxed);
+
+        for line in &["first\n", "second\n", "third\n", "fourth\n"] {
+            logfile.write_all(line.as_bytes()).unwrap();
+        }
+        logfile.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 1)).unwrap(), "third\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 2)).unwrap(), "second\n");
+        assert!(!rotated_path(&path, 3).exists());
+
+        logfile.proxy().delete_log();
+        assert!(!path.exists());
+        assert!(!rotated_path(&path, 1).exists());
+    }
+
+    #[test]
+    fn output_history_size() {
+        let history = OutputHistory::default();
+        history.push(&[b'a'; OUTPUT_HISTORY_SIZE]);
+        history.push(b"bc");
+
+        let bytes = history.bytes();
+        assert_eq!(bytes.len(), OUTPUT_HISTORY_SIZE);
+        assert_eq!(&bytes[OUTPUT_HISTORY_SIZE - 3..], b"abc");
+    }
+}
@@ -29,13 +29,23 @@ use dirs;
 #[cfg(windows)]
 use winapi::um::wincon::{AttachConsole, FreeConsole, ATTACH_PARENT_PROCESS};
 
-use log::{info, error};
+use log::{info, warn, error};
 
-use std::error::Error;
-use std::sync::Arc;
+use glutin::{ControlFlow, ElementState, Event, EventsLoop, VirtualKeyCode, WindowEvent, WindowId};
+use mio_extras::channel::Sender;
 
-#[cfg(target_os = "macos")]
+use std::borrow::Cow;
 use std::env;
+use std::error::Error;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(not(windows))]
+use std::sync::mpsc;
+use std::thread::JoinHandle;
 
 #[cfg(not(windows))]
 use std::os::unix::io::AsRawFd;
@@ -43,16 +53,28 @@ use std::os::unix::io::AsRawFd;
 #[cfg(target_os = "macos")]
 use alacritty::locale;
 use alacritty::{cli, event, die};
-use alacritty::config::{self, Config, Error as ConfigError};
-use alacritty::display::Display;
-use alacritty::event_loop::{self, EventLoop, Msg};
+use alacritty::ansi::Handler;
+use alacritty::appearance::{self, Appearance};
+use alacritty::asciicast::{Recorder, Recording};
+use alacritty::config::{self, migrate, Config, Dimensions, Error as ConfigError, RendererBackend};
+use alacritty::display::{Display, OnResize};
+use alacritty::event_loop::{self, EventLoop, Msg, State};
+use alacritty::index::{Column, Line, Point};
+use alacritty::ipc::{self, InstanceState, Message, Reply, WindowState};
 use alacritty::logging::{self, LoggerProxy};
 use alacritty::panic;
+#[cfg(not(windows))]
+use alacritty::playback;
+use alacritty::ref_test::{self, Replay};
+use alacritty::screenshot::{self, Region};
 use alacritty::sync::FairMutex;
 use alacritty::term::Term;
+use alacritty::term::dump::DumpSource;
 use alacritty::term::animation::start_animation_thread;
-use alacritty::tty::{self, process_should_exit};
-use alacritty::util::fmt::Red;
+use alacritty::tty::{self, EventedReadWrite};
+use alacritty::util::fmt::{Red, Yellow};
+use alacritty::util::start_daemon_with_env;
+use alacritty::window;
 
 fn main() {
     panic::attach_handler();
@@ -66,31 +88,173 @@ fn main() {
     // Load command line options
     let options = cli::Options::load();
 
+    // Send a message to a running instance instead of starting a new one
+    if let Some(ref message) = options.message {
+        process::exit(send_message(options.socket.as_ref(), message));
+    }
+
+    // Convert a YAML config to TOML instead of starting a new instance
+    if let Some(ref migrate) = options.migrate {
+        process::exit(migrate_config(migrate));
+    }
+
+    // Print the effective config instead of starting a new instance
+    if let Some(ref print_config) = options.print_config {
+        process::exit(print_effective_config(&options, print_config));
+    }
+
     // Initialize the logger as soon as possible as to capture output from other subsystems
     let logger_proxy = logging::initialize(&options).expect("Unable to initialize logger");
 
     // Load configuration file
-    let config = load_config(&options).update_dynamic_title(&options);
+    let config = load_config(&options).update_dynamic_title(&options, &options.config_options);
 
-    // Switch to home directory
+    // Switch to home directory, where the shell starts unless a working directory is set
     #[cfg(target_os = "macos")]
     env::set_current_dir(dirs::home_dir().unwrap()).unwrap();
     // Set locale
     #[cfg(target_os = "macos")]
     locale::set_locale_environment();
 
+    // The environment can only be changed safely while there are no other threads
+    if options.renderer.unwrap_or_else(|| config.renderer()) == RendererBackend::Software {
+        window::use_software_rendering();
+    }
+
     // Run alacritty
     if let Err(err) = run(config, &options, logger_proxy) {
         die!("Alacritty encountered an unrecoverable error:\n\n\t{}\n", Red(err));
     }
 }
 
+/// Send the message of `alacritty msg` and print the reply
+///
+/// Returns the exit code of the process.
+fn send_message(socket: Option<&PathBuf>, message: &Message) -> i32 {
+    match ipc::send_message(socket.map(PathBuf::as_path), message) {
+        Ok(Reply::Ok) => 0,
+        Ok(Reply::State(state)) => {
+            println!("{}", serde_json::to_string_pretty(&state).unwrap_or_default());
+            0
+        },
+        Ok(Reply::Path(path)) => {
+            println!("{}", path.display());
+            0
+        },
+        Ok(Reply::Error(err)) => {
+            eprintln!("{}", Red(err));
+            1
+        },
+        Err(err) => {
+            eprintln!("{}", Red(err));
+            1
+        },
+    }
+}
+
+/// Convert the YAML config of `alacritty migrate` to TOML
+///
+/// Returns the exit code of the process.
+fn migrate_config(options: &cli::Migrate) -> i32 {
+    let path = match options
+        .config
+        .clone()
+        .or_else(|| Config::installed_config_file("alacritty.yml").map(Cow::into_owned))
+    {
+        Some(path) => path,
+        None => {
+            eprintln!("{}", Red("No alacritty.yml found, use --config-file to select one"));
+            return 1;
+        },
+    };
+
+    let result = if options.dry_run {
+        fs::read_to_string(&path)
+            .map_err(migrate::Error::from)
+            .and_then(|yaml| migrate::yaml_to_toml(&yaml))
+            .map(|toml| print!("{}", toml))
+    } else {
+        migrate::migrate_file(&path)
+            .map(|target| println!("Converted {} to {}", path.display(), target.display()))
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}", Red(err));
+            1
+        },
+    }
+}
+
+/// Print the config of `--print-config`, with its imports and options merged over the defaults
+///
+/// Returns the exit code of the process.
+fn print_effective_config(options: &cli::Options, print_config: &cli::PrintConfig) -> i32 {
+    let path = options.config_path().or_else(Config::installed_config);
+    let path = path.as_ref().map(|path| &**path);
+    let toml = print_config.toml.unwrap_or_else(|| {
+        path.and_then(Path::extension).map_or(false, |extension| extension == "toml")
+    });
+
+    let document = match Config::effective_document(path, options) {
+        Ok((document, warnings)) => {
+            for warning in warnings {
+                eprintln!("{}", Yellow(warning));
+            }
+            document
+        },
+        Err(err) => {
+            eprintln!("{}", Red(err));
+            return 1;
+        },
+    };
+
+    let text = if toml {
+        migrate::to_toml(document).map_err(|err| err.to_string())
+    } else {
+        serde_yaml::to_string(&document).map_err(|err| err.to_string())
+    };
+
+    match text {
+        Ok(text) => {
+            // The window title and class have no config options, they're listed as comments
+            if let Some(ref title) = options.title {
+                println!("# --title {}", title);
+            }
+            if let Some(ref class) = options.class {
+                println!("# --class {},{}", class.instance, class.general);
+            }
+            println!("{}", text);
+            0
+        },
+        Err(err) => {
+            eprintln!("{}", Red(err));
+            1
+        },
+    }
+}
+
 /// Load configuration
 ///
 /// If a configuration file is given as a command line argument we don't
 /// generate a default file. If an empty configuration file is given, i.e.
 /// /dev/null, we load the compiled-in defaults.)
+///
+/// Options passed with `--option` are merged over the loaded config.
 fn load_config(options: &cli::Options) -> Config {
+    let config = load_config_file(options);
+    if options.config_options.is_empty() {
+        return config;
+    }
+
+    Config::load_with_options(config.path(), &options.config_options).unwrap_or_else(|err| {
+        error!("Unable to apply config options: {}", err);
+        config
+    })
+}
+
+fn load_config_file(options: &cli::Options) -> Config {
     let config_path = options.config_path()
         .or_else(Config::installed_config)
         .or_else(|| Config::write_defaults().ok());
@@ -110,10 +274,31 @@ fn load_config(options: &cli::Options) -> Config {
     }
 }
 
+/// The pty of the platform, which borrows the agent on Windows
+#[cfg(not(windows))]
+type Pty = tty::Pty;
+#[cfg(windows)]
+type Pty = tty::Pty<'static>;
+
+/// Run the `hooks.on_child_exit` command
+///
+/// The exit status of the shell is passed in the `ALACRITTY_EXIT_STATUS` environment variable.
+fn run_exit_hook(command: &config::CommandWrapper, status: Option<i32>) {
+    let status = status
+        .map(|status| status.to_string())
+        .unwrap_or_default();
+
+    info!("Running exit hook {}", command.program());
+    let env = [("ALACRITTY_EXIT_STATUS", status)];
+    if let Err(err) = start_daemon_with_env(command.program(), command.args(), env.iter().cloned()) {
+        error!("Unable to run exit hook {}: {}", command.program(), err);
+    }
+}
+
 /// Run Alacritty
 ///
-/// Creates a window, the terminal state, pty, I/O event loop, input processor,
-/// config change monitor, and runs the main display loop.
+/// Creates the windows, the config change monitor, and runs the main loop which
+/// dispatches window events and draws every window.
 fn run(
     mut config: Config,
     options: &cli::Options,
@@ -123,79 +308,88 @@ fn run(
     if let Some(config_path) = config.path() {
         info!("Configuration loaded from {:?}", config_path.display());
     };
+    logger_proxy.set_rotation(config.log_rotation());
 
-    // Set environment variables
-    tty::setup_env(&config);
-
-    // Create a display.
-    //
-    // The display manages a window and can draw the terminal
-    let mut display = Display::new(&config, options, logger_proxy.clone())?;
-
-    info!(
-        "PTY Dimensions: {:?} x {:?}",
-        display.size().lines(),
-        display.size().cols()
-    );
+    // Load the recording first, so a broken file is reported before a window is opened
+    let recording = match options.play {
+        Some(ref path) => match Recording::load(path) {
+            // Recordings are played in a window with the recorded number of lines and columns
+            Ok(recording) => {
+                let (cols, lines) = (Column(recording.width), Line(recording.height));
+                config.set_dimensions(Dimensions::new(cols, lines));
+                Some(recording)
+            },
+            Err(err) => {
+                return Err(format!("Unable to load recording {}: {}", path.display(), err).into())
+            },
+        },
+        None => None,
+    };
 
-    // Create the terminal
-    //
-    // This object contains all of the state about what's being displayed. It's
-    // wrapped in a clonable mutex since both the I/O loop and display need to
-    // access it.
-    let mut terminal = Term::new(&config, display.size().to_owned());
-    terminal.set_logger_proxy(logger_proxy.clone());
-    let terminal = Arc::new(FairMutex::new(terminal));
+    // Ref tests are replayed in a window with the recorded number of lines and columns
+    let replay_recording = match options.ref_test_replay {
+        Some(ref dir) => match ref_test::load(dir) {
+            Ok(recording) => {
+                let (cols, lines) = (Column(recording.width), Line(recording.height));
+                config.set_dimensions(Dimensions::new(cols, lines));
+                Some(recording)
+            },
+            Err(err) => {
+                return Err(format!("Unable to load ref test {}: {}", dir.display(), err).into())
+            },
+        },
+        None => None,
+    };
 
+    // Set environment variables
+    tty::setup_env(&config);
 
-    // Find the window ID for setting $WINDOWID
-    let window_id = display.get_window_id();
+    // All windows receive their events through the same events loop
+    let mut events_loop = EventsLoop::new();
 
-    // Create the pty
-    //
-    // The pty forks a process to run the shell on the slave side of the
-    // pseudoterminal. A file descriptor for the master side is retained for
-    // reading/writing to the shell.
-    let pty = tty::new(&config, options, &display.size(), window_id);
+    // Show errors and warnings logged by other threads right away
+    logger_proxy.messages().set_wakeup(events_loop.create_proxy());
 
-    // Get a reference to something that we can resize
-    //
-    // This exists because rust doesn't know the interface is thread-safe
-    // and we need to be able to resize the PTY from the main thread while the IO
-    // thread owns the EventedRW object.
-    #[cfg(windows)]
-    let mut resize_handle = pty.resize_handle();
-    #[cfg(not(windows))]
-    let mut resize_handle = pty.fd.as_raw_fd();
+    // Listen for `alacritty msg`, shells find the socket through `ALACRITTY_SOCKET`
+    let socket = if config.ipc_socket() {
+        match ipc::Socket::new(events_loop.create_proxy()) {
+            Ok(socket) => {
+                env::set_var(ipc::SOCKET_ENV, socket.path());
+                Some(socket)
+            },
+            Err(err) => {
+                warn!("Unable to create IPC socket: {}", err);
+                None
+            },
+        }
+    } else {
+        None
+    };
 
-    // Create the pseudoterminal I/O loop
-    //
-    // pty I/O is ran on another thread as to not occupy cycles used by the
-    // renderer and input processing. Note that access to the terminal state is
-    // synchronized since the I/O loop updates the state, and the display
-    // consumes it periodically.
-    let event_loop = EventLoop::new(
-        Arc::clone(&terminal),
-        display.notifier(),
-        pty,
-        options.ref_test,
-    );
-
-    // The event loop channel allows write requests from the event processor
-    // to be sent to the loop and ultimately written to the pty.
-    let loop_tx = event_loop.channel();
-
-    // Event processor
-    //
-    // Need the Rc<RefCell<_>> here since a ref is shared in the resize callback
-    let mut processor = event::Processor::new(
-        event_loop::Notifier(event_loop.channel()),
-        display.resize_channel(),
-        options,
+    let mut windows = vec![WindowContext::new(
+        &events_loop,
         &config,
-        options.ref_test,
-        display.size().to_owned(),
-    );
+        options,
+        logger_proxy.clone(),
+        recording,
+        replay_recording,
+        config.startup_banner().text(),
+        None,
+    )?];
+
+    for _ in 1..options.windows {
+        let window = WindowContext::new(
+            &events_loop,
+            &config,
+            options,
+            logger_proxy.clone(),
+            None,
+            None,
+            None,
+            Some(&windows[0].display),
+        )?;
+        windows.push(window);
+    }
 
     // Create a config monitor when config was loaded from path
     //
@@ -205,79 +399,715 @@ fn run(
         // Start monitor if CLI flag says yes
         (Some(true), _) |
         // Or if no CLI flag was passed and the config says yes
-        (None, true) => config.path()
-                .map(|path| config::Monitor::new(path, display.notifier())),
+        (None, true) => config.path().map(|path| {
+            config::Monitor::new(path, config.imports(), windows[0].display.notifier())
+        }),
         // Otherwise, don't start the monitor
         _ => None,
     };
 
-    // Kick off the I/O thread
-    let _io_thread = event_loop.spawn(None);
-
     info!("Initialisation complete");
 
-    let c_term = terminal.clone();
-    let notifier = display.notifier();
+    // Config options set with `--option` or `alacritty msg config`, kept across config reloads
+    let mut runtime_options = options.config_options.clone();
+
+    // Watches the appearance of the OS, once `colors.light` or `colors.dark` are configured
+    let mut appearance_watcher = None;
 
-    start_animation_thread(c_term, notifier);
+    // Main loop, running until the last window was closed
+    let mut events = Vec::new();
+    while !windows.is_empty() {
+        // Block waiting for events, unless a window still has to be drawn
+        if windows.iter().all(|window| window.wait_for_event) {
+            events_loop.run_forever(|event| {
+                events.push(event);
+                ControlFlow::Break
+            });
+        }
+        events_loop.poll_events(|event| events.push(event));
 
-    // Main display loop
-    loop {
-        // Process input and window events
-        let mut terminal_lock = processor.process_events(&terminal, display.window());
         // Handle config reloads
-        if let Some(new_config) = config_monitor
-            .as_ref()
-            .and_then(|monitor| monitor.pending_config())
-        {
-            config = new_config.update_dynamic_title(options);
-            display.update_config(&config);
-            processor.update_config(&config);
-            terminal_lock.update_config(&config);
-            terminal_lock.dirty = true;
+        let mut config_changed = match config_monitor.as_ref().and_then(|m| m.pending_config()) {
+            Some(new_config) => {
+                let new_config = if runtime_options.is_empty() {
+                    new_config
+                } else {
+                    Config::load_with_options(new_config.path(), &runtime_options)
+                        .unwrap_or(new_config)
+                };
+                config = new_config.update_dynamic_title(options, &runtime_options);
+                true
+            },
+            None => false,
+        };
+
+        // Handle messages sent with `alacritty msg`
+        for request in socket.iter().flat_map(ipc::Socket::pending_requests) {
+            let reply = match *request.message() {
+                Message::CreateWindow { ref working_directory, ref command } => {
+                    let mut window_options = options.for_new_window(working_directory.clone());
+                    if let Some((program, args)) = command.as_ref().and_then(|c| c.split_first()) {
+                        window_options.command =
+                            Some(config::Shell::new_with_args(program.clone(), args.to_vec()));
+                    }
+
+                    let window = WindowContext::new(
+                        &events_loop,
+                        &config,
+                        &window_options,
+                        logger_proxy.clone(),
+                        None,
+                        None,
+                        None,
+                        windows.first().map(|window| &window.display),
+                    );
+
+                    match window {
+                        Ok(window) => {
+                            windows.push(window);
+                            Reply::Ok
+                        },
+                        Err(err) => Reply::Error(format!("Unable to create new window: {}", err)),
+                    }
+                },
+                Message::Config { options: ref new_options, reset } => {
+                    let mut merged_options = if reset {
+                        options.config_options.clone()
+                    } else {
+                        runtime_options.clone()
+                    };
+                    merged_options.extend(new_options.iter().cloned());
+
+                    match Config::load_with_options(config.path(), &merged_options) {
+                        Ok(new_config) => {
+                            config = new_config.update_dynamic_title(options, &merged_options);
+                            runtime_options = merged_options;
+                            config_changed = true;
+                            Reply::Ok
+                        },
+                        Err(err) => Reply::Error(err.to_string()),
+                    }
+                },
+                Message::Annotate { window, line, column, ref label } => {
+                    match target_window(&windows, window) {
+                        Some(window) => {
+                            window.annotate(Point::new(Line(line), Column(column)), label.clone())
+                        },
+                        None => Reply::Error(String::from("No such window")),
+                    }
+                },
+                Message::ClearAnnotations { window } => match target_window(&windows, window) {
+                    Some(window) => {
+                        window.terminal.lock().clear_annotations();
+                        Reply::Ok
+                    },
+                    None => Reply::Error(String::from("No such window")),
+                },
+                Message::ExportSvg { window } => match target_window(&windows, window) {
+                    Some(window) => {
+                        // Clients can't choose the path, they might not be allowed to write it
+                        let svg = window.terminal.lock().svg(DumpSource::Screen);
+                        match screenshot::save_svg(&svg, &config.screenshot().directory()) {
+                            Ok(path) => Reply::Path(path),
+                            Err(err) => Reply::Error(format!("Unable to write SVG image: {}", err)),
+                        }
+                    },
+                    None => Reply::Error(String::from("No such window")),
+                },
+                Message::GetState => Reply::State(InstanceState {
+                    pid: process::id(),
+                    socket: socket.as_ref().map(|s| s.path().to_path_buf()).unwrap_or_default(),
+                    windows: windows.iter().map(WindowContext::state).collect(),
+                }),
+            };
+            request.reply(reply);
+        }
+
+        if config_changed {
+            logger_proxy.set_rotation(config.log_rotation());
+        }
+
+        // Follow the light or dark appearance of the OS, only while a scheme is configured for it
+        if !config.colors().follows_appearance() {
+            appearance_watcher = None;
+        } else if appearance_watcher.is_none() {
+            appearance_watcher = Some(appearance::Watcher::new(events_loop.create_proxy()));
+        }
+        let appearance = appearance_watcher.as_ref().and_then(appearance::Watcher::appearance);
+
+        // Process input and window events and maybe draw the terminal of every window
+        let mut new_windows = Vec::new();
+        for window in &mut windows {
+            let window_events = take_window_events(&mut events, window.display.window_id());
+            window.process_events(window_events, &config, config_changed, appearance);
+
+            if window.shell.processor.window_changes().new_window {
+                new_windows.push(window.working_directory());
+            }
+        }
+
+        // Events of windows which were closed in the meantime
+        events.clear();
+
+        // Close windows whose shell exited, unless it's respawned
+        let quit = windows.iter().any(|window| window.shell.processor.window_changes().quit);
+        for mut window in mem::replace(&mut windows, Vec::new()) {
+            if window.update_shell(&config, quit) {
+                windows.push(window);
+            }
+        }
+
+        for working_dir in new_windows {
+            let window_options = options.for_new_window(working_dir);
+            let window = WindowContext::new(
+                &events_loop,
+                &config,
+                &window_options,
+                logger_proxy.clone(),
+                None,
+                None,
+                None,
+                windows.first().map(|window| &window.display),
+            );
+
+            match window {
+                Ok(window) => windows.push(window),
+                Err(err) => error!("Unable to create new window: {}", err),
+            }
+        }
+    }
+
+    // FIXME patch notify library to have a shutdown method
+    // config_reloader.join().ok();
+
+    // Without explicitly detaching the console cmd won't redraw it's prompt
+    #[cfg(windows)]
+    unsafe { FreeConsole(); }
+
+    info!("Goodbye");
+
+    if !options.persistent_logging && !config.persistent_logging() {
+        logger_proxy.delete_log();
+    }
+
+    Ok(())
+}
+
+/// Remove the events of the window `id` from `events`
+fn take_window_events(events: &mut Vec<Event>, id: WindowId) -> Vec<Event> {
+    let (window_events, other_events): (Vec<Event>, Vec<Event>) =
+        events.drain(..).partition(|event| match *event {
+            Event::WindowEvent { window_id, .. } => window_id == id,
+            _ => false,
+        });
+    *events = other_events;
+    window_events
+}
+
+/// Window with the platform id `id`, or the first window if no id is given
+fn target_window(windows: &[WindowContext], id: Option<usize>) -> Option<&WindowContext> {
+    match id {
+        Some(id) => windows.iter().find(|window| window.display.get_window_id() == Some(id)),
+        None => windows.first(),
+    }
+}
+
+/// Check if `key` is a modifier, which doesn't release a held window on its own
+fn is_modifier(key: VirtualKeyCode) -> bool {
+    match key {
+        VirtualKeyCode::LShift
+        | VirtualKeyCode::RShift
+        | VirtualKeyCode::LControl
+        | VirtualKeyCode::RControl
+        | VirtualKeyCode::LAlt
+        | VirtualKeyCode::RAlt
+        | VirtualKeyCode::LWin
+        | VirtualKeyCode::RWin => true,
+        _ => false,
+    }
+}
+
+/// A window with its terminal and the shell running in it
+struct WindowContext {
+    display: Display,
+    terminal: Arc<FairMutex<Term>>,
+    options: cli::Options,
+    shell: Shell,
+
+    /// Asciicast recording which is played instead of running a shell
+    recording: Option<Recording>,
+
+    /// Ref test which is replayed instead of running a shell
+    replay_recording: Option<Recording>,
+
+    /// Whether nothing has to be drawn until the next event
+    wait_for_event: bool,
+
+    /// Region of the screenshot which is taken of the next frame
+    screenshot: Option<Region>,
+
+    /// Whether the shell exited and the window is kept open by `--hold`
+    held: bool,
+
+    /// Whether a key was pressed to close the held window
+    hold_released: bool,
+}
+
+impl WindowContext {
+    /// Create a window with a terminal and start its shell
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        events_loop: &EventsLoop,
+        config: &Config,
+        options: &cli::Options,
+        logger_proxy: LoggerProxy,
+        recording: Option<Recording>,
+        replay_recording: Option<Recording>,
+        banner: Option<String>,
+        shared: Option<&Display>,
+    ) -> Result<WindowContext, Box<dyn Error>> {
+        // Create a display.
+        //
+        // The display manages a window and can draw the terminal, it shares glyphs with the
+        // `shared` display when possible
+        let display = Display::new(events_loop, config, options, logger_proxy.clone(), shared)?;
+
+        info!(
+            "PTY Dimensions: {:?} x {:?}",
+            display.size().lines(),
+            display.size().cols()
+        );
+
+        // Create the terminal
+        //
+        // This object contains all of the state about what's being displayed. It's
+        // wrapped in a clonable mutex since both the I/O loop and display need to
+        // access it.
+        let mut terminal = Term::new(config, display.size().to_owned());
+        terminal.set_logger_proxy(logger_proxy);
+        if let Some(banner) = banner {
+            terminal.start_banner(&banner);
         }
+        let terminal = Arc::new(FairMutex::new(terminal));
+
+        start_animation_thread(terminal.clone(), display.notifier());
 
+        // Start recording the session, the recording is continued by respawned shells
+        let recorder = match options.record {
+            Some(ref path) => {
+                let size = display.size();
+                Some(Recorder::create(path, size.cols().0, size.lines().0)?)
+            },
+            None => None,
+        };
+
+        let shell = Shell::spawn(
+            &display,
+            &terminal,
+            config,
+            options,
+            recorder,
+            recording.as_ref(),
+            replay_recording.as_ref(),
+        )?;
+
+        Ok(WindowContext {
+            display,
+            terminal,
+            options: options.clone(),
+            shell,
+            recording,
+            replay_recording,
+            wait_for_event: true,
+            screenshot: None,
+            held: false,
+            hold_released: false,
+        })
+    }
+
+    /// Process the events of this window, then draw it if the terminal changed
+    fn process_events(
+        &mut self,
+        events: Vec<Event>,
+        config: &Config,
+        config_changed: bool,
+        appearance: Option<Appearance>,
+    ) {
+        let events = if self.held { self.filter_held_input(events) } else { events };
+
+        let mut terminal_lock = self.shell.processor.process_events(
+            &self.terminal,
+            self.display.window(),
+            events,
+        );
+
+        // Handle config reloads
+        if config_changed {
+            self.display.update_config(config);
+            self.shell.processor.update_config(config);
+            terminal_lock.update_config(config);
+            terminal_lock.damage.damage_all();
+        }
+
+        // Switch between the light and dark color schemes
+        terminal_lock.set_appearance(appearance);
+
+        // Show new messages and hide closed ones
+        if self.display.message_changed() {
+            terminal_lock.damage.damage_all();
+        }
+
+        // Capture the first frame after a ref test replay has been processed
+        if self.shell.replay.as_ref().map_or(false, Replay::finished) {
+            self.display.capture_frame();
+            terminal_lock.damage.damage_all();
+        }
+
+        // Capture the next frame for a screenshot requested by an action or escape sequence
+        if let Some(region) = terminal_lock.screenshot.take() {
+            self.screenshot = Some(region);
+            self.display.capture_frame();
+            terminal_lock.damage.damage_all();
+        }
+
+        self.wait_for_event = !terminal_lock.needs_draw();
 
         // Maybe draw the terminal
         if terminal_lock.needs_draw() {
             // Try to update the position of the input method editor
             #[cfg(not(windows))]
-            display.update_ime_position(&terminal_lock);
+            self.display.update_ime_position(&terminal_lock);
 
             // Handle pending resize events
             //
             // The second argument is a list of types that want to be notified
             // of display size changes.
-            display.handle_resize(&mut terminal_lock, &config, &mut [&mut resize_handle, &mut processor]);
+            self.display.handle_resize(
+                &mut terminal_lock,
+                config,
+                &mut [&mut *self.shell.resize_handle, &mut self.shell.processor],
+            );
 
             drop(terminal_lock);
 
             // Draw the current state of the terminal
-            display.draw(&terminal, &config);
+            self.display.draw(&self.terminal, config);
         }
 
-        // Begin shutdown if the flag was raised.
-        if process_should_exit() {
-            break;
+        let screenshot = match self.display.take_screenshot() {
+            Some(screenshot) => screenshot,
+            None => return,
+        };
+
+        if let Some(replay) = self.shell.replay.as_ref().filter(|replay| replay.finished()) {
+            let matches = replay.check(&screenshot).unwrap_or_else(|err| {
+                error!("Unable to compare ref test screenshot: {}", err);
+                false
+            });
+            process::exit(if matches { 0 } else { 1 });
+        }
+
+        if let Some(region) = self.screenshot.take() {
+            let screenshot = screenshot::crop(&screenshot, &region, self.display.size());
+            match screenshot::save(&screenshot, &config.screenshot().directory()) {
+                Ok(path) => info!("Saved screenshot to {}", path.display()),
+                Err(err) => warn!("Unable to save screenshot: {}", err),
+            }
         }
     }
 
-    loop_tx
-        .send(Msg::Shutdown)
-        .expect("Error sending shutdown to event loop");
+    /// Working directory of the shell, used for new windows
+    fn working_directory(&self) -> Option<PathBuf> {
+        let terminal = self.terminal.lock();
+        terminal
+            .working_directory()
+            .map(|directory| directory.to_path_buf())
+            .or_else(|| self.shell.pid.and_then(event::shell_working_directory))
+    }
 
-    // FIXME patch notify library to have a shutdown method
-    // config_reloader.join().ok();
+    /// State reported to `alacritty msg get-state`
+    fn state(&self) -> WindowState {
+        let working_directory = self.working_directory();
+        let terminal = self.terminal.lock();
+        WindowState {
+            id: self.display.get_window_id(),
+            title: terminal.title().to_owned(),
+            columns: terminal.grid().num_cols().0,
+            lines: terminal.grid().num_lines().0,
+            shell_pid: self.shell.pid,
+            working_directory,
+        }
+    }
 
-    // Without explicitly detaching the console cmd won't redraw it's prompt
-    #[cfg(windows)]
-    unsafe { FreeConsole(); }
+    /// Annotate a cell of the active area, as requested by `alacritty msg annotate`
+    fn annotate(&self, point: Point, label: Option<String>) -> Reply {
+        let mut terminal = self.terminal.lock();
+        let (lines, cols) = (terminal.grid().num_lines(), terminal.grid().num_cols());
+        if point.line >= lines || point.col >= cols {
+            return Reply::Error(format!(
+                "Cell {}:{} is outside of the {}x{} terminal",
+                point.line, point.col, cols, lines
+            ));
+        }
 
-    info!("Goodbye");
+        terminal.annotate(point, label);
+        Reply::Ok
+    }
 
-    if !options.persistent_logging && !config.persistent_logging() {
-        logger_proxy.delete_log();
+    /// Drop the input of a held window, since no shell is left to receive it
+    ///
+    /// Pressing any key other than a modifier releases the window, so it is closed.
+    fn filter_held_input(&mut self, events: Vec<Event>) -> Vec<Event> {
+        let mut hold_released = false;
+        let events = events
+            .into_iter()
+            .filter(|event| match event {
+                Event::WindowEvent { event: WindowEvent::KeyboardInput { input, .. }, .. } => {
+                    let key = input.virtual_keycode;
+                    if input.state == ElementState::Pressed && !key.map_or(false, is_modifier) {
+                        hold_released = true;
+                    }
+                    false
+                },
+                Event::WindowEvent { event, .. } => match event {
+                    WindowEvent::ReceivedCharacter(_)
+                    | WindowEvent::MouseInput { .. }
+                    | WindowEvent::CursorMoved { .. }
+                    | WindowEvent::MouseWheel { .. }
+                    | WindowEvent::DroppedFile(_) => false,
+                    _ => true,
+                },
+                _ => true,
+            })
+            .collect();
+
+        self.hold_released |= hold_released;
+        events
     }
 
-    Ok(())
+    /// Respawn the shell once it has exited, when the config asks for it
+    ///
+    /// Returns `false` if the window should be closed, because its shell exited or the user
+    /// closed it or quit Alacritty.
+    fn update_shell(&mut self, config: &Config, quit: bool) -> bool {
+        let close_requested = quit || self.shell.processor.window_changes().close;
+        if self.held {
+            return !self.hold_released && !close_requested;
+        }
+
+        if !close_requested && !self.shell.exited() {
+            return true;
+        }
+
+        let (recorder, exit_status) = self.shell.shutdown();
+        if close_requested {
+            return false;
+        }
+
+        let hooks = config.hooks();
+        if let Some(command) = &hooks.on_child_exit {
+            run_exit_hook(command, exit_status);
+        }
+
+        if !hooks.respawn {
+            if !self.options.hold && !hooks.hold {
+                return false;
+            }
+
+            info!("Holding window after shell exited");
+            self.terminal.lock().show_exit_message(exit_status);
+            self.held = true;
+            self.wait_for_event = false;
+            return true;
+        }
+
+        info!("Respawning shell");
+        {
+            let mut terminal_lock = self.terminal.lock();
+            terminal_lock.reset_state();
+            terminal_lock.damage.damage_all();
+        }
+
+        let shell = Shell::spawn(
+            &self.display,
+            &self.terminal,
+            config,
+            &self.options,
+            recorder,
+            self.recording.as_ref(),
+            self.replay_recording.as_ref(),
+        );
+
+        match shell {
+            Ok(shell) => {
+                self.shell = shell;
+                true
+            },
+            Err(err) => {
+                error!("Unable to respawn shell: {}", err);
+                false
+            },
+        }
+    }
+}
+
+/// The shell of a window, with the I/O thread of its pty
+struct Shell {
+    processor: event::Processor<event_loop::Notifier>,
+    loop_tx: Sender<Msg>,
+    io_thread: Option<JoinHandle<(EventLoop<Pty>, State)>>,
+    resize_handle: Box<dyn OnResize>,
+    exited: Arc<AtomicBool>,
+    pid: Option<u32>,
+    child: Option<tty::ChildExit>,
+    replay: Option<Replay>,
+}
+
+impl Shell {
+    /// Create the pty and start the I/O thread
+    fn spawn(
+        display: &Display,
+        terminal: &Arc<FairMutex<Term>>,
+        config: &Config,
+        options: &cli::Options,
+        recorder: Option<Recorder>,
+        recording: Option<&Recording>,
+        replay_recording: Option<&Recording>,
+    ) -> Result<Shell, Box<dyn Error>> {
+        // Find the window ID for setting $WINDOWID
+        let window_id = display.get_window_id();
+
+        // Create the pty
+        //
+        // The pty forks a process to run the shell on the slave side of the
+        // pseudoterminal. A file descriptor for the master side is retained for
+        // reading/writing to the shell.
+        #[cfg(not(windows))]
+        let mut playback = None;
+        #[cfg(not(windows))]
+        let mut replay = None;
+        #[cfg(not(windows))]
+        let pty = if let Some(recording) = recording {
+            // Play the recording instead of spawning a shell, the pty only receives the input
+            let (pty, input) = tty::new_playback(&display.size());
+            let output = playback::Output::new(Arc::clone(terminal), display.notifier());
+            let (playback_tx, playback_rx) = mpsc::channel();
+            playback::spawn(recording.clone(), output, input, playback_rx);
+            playback = Some(playback_tx);
+            pty
+        } else if let (Some(recording), Some(dir)) = (replay_recording, &options.ref_test_replay) {
+            let (pty, writer) = tty::new_playback(&display.size());
+            let update = options.ref_test_update;
+            replay =
+                Some(Replay::spawn(dir, recording.clone(), writer, display.notifier(), update));
+            pty
+        } else {
+            tty::new(config, options, &display.size(), window_id)
+        };
+        #[cfg(windows)]
+        let replay: Option<Replay> = None;
+        #[cfg(windows)]
+        let pty = match (recording, replay_recording) {
+            (None, None) => tty::new(config, options, &display.size(), window_id),
+            _ => return Err("Playback of recordings is not supported on Windows".into()),
+        };
+
+        let pid = pty.child_pid();
+        let child = pty.child_exit();
+
+        // Get a reference to something that we can resize
+        //
+        // This exists because rust doesn't know the interface is thread-safe
+        // and we need to be able to resize the PTY from the main thread while the IO
+        // thread owns the EventedRW object.
+        #[cfg(windows)]
+        let resize_handle: Box<dyn OnResize> = Box::new(pty.resize_handle());
+        #[cfg(not(windows))]
+        let resize_handle: Box<dyn OnResize> = Box::new(pty.fd.as_raw_fd());
+
+        // Create the pseudoterminal I/O loop
+        //
+        // pty I/O is ran on another thread as to not occupy cycles used by the
+        // renderer and input processing. Note that access to the terminal state is
+        // synchronized since the I/O loop updates the state, and the display
+        // consumes it periodically.
+        let event_loop = EventLoop::new(
+            Arc::clone(terminal),
+            display.notifier(),
+            pty,
+            options.ref_test,
+            options.trace_escapes,
+            recorder,
+        );
+
+        // Include the recent output of the new shell in crash reports
+        display.logger_proxy().watch_terminal(terminal, event_loop.output_history());
+
+        // The event loop channel allows write requests from the event processor
+        // to be sent to the loop and ultimately written to the pty.
+        let loop_tx = event_loop.channel();
+        let exited = event_loop.exited();
+
+        // Event processor
+        let mut processor = event::Processor::new(
+            event_loop::Notifier(event_loop.channel()),
+            display.notifier(),
+            display.resize_channel(),
+            options,
+            config,
+            options.ref_test,
+            display.size().to_owned(),
+        );
+        processor.set_shell_pid(pid);
+        #[cfg(not(windows))]
+        {
+            if let Some(playback) = playback {
+                processor.set_playback(playback);
+            }
+        }
+
+        // Kick off the I/O thread
+        let io_thread = event_loop.spawn(None);
+
+        Ok(Shell {
+            processor,
+            loop_tx,
+            io_thread: Some(io_thread),
+            resize_handle,
+            exited,
+            pid,
+            child,
+            replay,
+        })
+    }
+
+    /// Whether the shell has exited
+    fn exited(&self) -> bool {
+        self.exited.load(Ordering::Relaxed)
+            || self.child.as_ref().map_or(false, tty::ChildExit::exited)
+    }
+
+    /// Stop the I/O thread and wait for it to release the pty
+    ///
+    /// Returns the session recording, so it can be continued by the next shell, and the exit
+    /// status of the shell.
+    fn shutdown(&mut self) -> (Option<Recorder>, Option<i32>) {
+        // The loop might have stopped already, after the shell exited
+        let _ = self.loop_tx.send(Msg::Shutdown);
+
+        let recorder = match self.io_thread.take().map(JoinHandle::join) {
+            Some(Ok((event_loop, _))) => event_loop.into_recorder(),
+            _ => None,
+        };
+
+        (recorder, self.child.as_ref().and_then(tty::ChildExit::exit_status))
+    }
+}
+
+impl Drop for Shell {
+    fn drop(&mut self) {
+        if self.io_thread.is_some() {
+            self.shutdown();
+        }
+    }
 }
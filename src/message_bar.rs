@@ -0,0 +1,267 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Bar at the bottom of the window showing errors and warnings
+//!
+//! Messages are queued by the logger and shown one at a time, the oldest first. Every message
+//! has a close button at the end of its first line.
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::Arc;
+
+use glutin::EventsLoopProxy;
+use parking_lot::Mutex;
+use unicode_width::UnicodeWidthChar;
+
+use crate::term::SizeInfo;
+use crate::Rgb;
+
+/// Text of the button closing the current message
+pub const CLOSE_BUTTON_TEXT: &str = "[X]";
+
+/// Last line of messages which don't fit into the window
+const TRUNCATED_MESSAGE: &str = "[MESSAGE TRUNCATED]";
+
+/// Lines of the terminal which are never covered by the message bar
+const MIN_FREE_LINES: usize = 3;
+
+/// Maximum number of queued messages, the oldest waiting ones are dropped first
+const MAX_MESSAGES: usize = 32;
+
+/// Severity of a message
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MessageType {
+    Error,
+    Warning,
+}
+
+/// Error or warning shown in the message bar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    text: String,
+    kind: MessageType,
+}
+
+impl Message {
+    pub fn new(text: String, kind: MessageType) -> Message {
+        Message { text, kind }
+    }
+
+    #[inline]
+    pub fn kind(&self) -> MessageType {
+        self.kind
+    }
+
+    /// Background color of the message bar
+    pub fn color(&self) -> Rgb {
+        match self.kind {
+            MessageType::Error => Rgb { r: 0xff, g: 0x00, b: 0x00 },
+            MessageType::Warning => Rgb { r: 0xff, g: 0xff, b: 0x00 },
+        }
+    }
+
+    /// Lines of the message wrapped to the width of the window
+    ///
+    /// Every line is padded to the width of the window and the first one ends with the close
+    /// button. Messages which would cover most of the terminal are truncated.
+    pub fn text(&self, size_info: &SizeInfo) -> Vec<String> {
+        let cols = size_info.cols().0;
+        let max_lines = size_info.window_lines().saturating_sub(MIN_FREE_LINES);
+        let button_width = CLOSE_BUTTON_TEXT.len();
+        if max_lines == 0 || cols <= button_width {
+            return Vec::new();
+        }
+
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        let mut width = 0;
+        for c in self.text.trim_end().chars() {
+            let c = if c == '\t' { ' ' } else { c };
+            if c != '\n' && c.is_control() {
+                continue;
+            }
+
+            // The first line leaves room for the close button
+            let available = if lines.is_empty() { cols - button_width - 1 } else { cols };
+            let c_width = c.width().unwrap_or(0);
+            if c == '\n' || width + c_width > available {
+                lines.push(mem::replace(&mut line, String::new()));
+                width = 0;
+
+                if c == '\n' {
+                    continue;
+                }
+            }
+
+            line.push(c);
+            width += c_width;
+        }
+        lines.push(line);
+
+        if lines.len() > max_lines {
+            lines.truncate(max_lines);
+            if let Some(last) = lines.last_mut().filter(|_| max_lines > 1) {
+                *last = TRUNCATED_MESSAGE.into();
+            }
+        }
+
+        for (i, line) in lines.iter_mut().enumerate() {
+            let line_width: usize = line.chars().filter_map(|c| c.width()).sum();
+            let end = if i == 0 { cols - button_width } else { cols };
+            line.extend((line_width..end).map(|_| ' '));
+
+            if i == 0 {
+                line.push_str(CLOSE_BUTTON_TEXT);
+            }
+        }
+
+        lines
+    }
+}
+
+/// Queue of messages, shared by the logger and all windows
+#[derive(Clone, Default)]
+pub struct MessageBuffer {
+    messages: Arc<Mutex<VecDeque<Message>>>,
+    wakeup: Arc<Mutex<Option<EventsLoopProxy>>>,
+}
+
+impl MessageBuffer {
+    /// Wake up the events loop of the windows whenever a message is queued
+    pub fn set_wakeup(&self, proxy: EventsLoopProxy) {
+        *self.wakeup.lock() = Some(proxy);
+    }
+
+    /// Queue a message, unless the same message is already waiting to be shown
+    ///
+    /// The message which is currently shown is kept when the queue is full.
+    pub fn push(&self, message: Message) {
+        {
+            let mut messages = self.messages.lock();
+            if messages.contains(&message) {
+                return;
+            }
+
+            if messages.len() >= MAX_MESSAGES {
+                messages.remove(1);
+            }
+            messages.push_back(message);
+        }
+
+        // Messages can be logged by any thread, while the windows wait for events
+        if let Some(ref proxy) = *self.wakeup.lock() {
+            let _ = proxy.wakeup();
+        }
+    }
+
+    /// Message which is currently shown
+    pub fn message(&self) -> Option<Message> {
+        self.messages.lock().front().cloned()
+    }
+
+    /// Close the current message and show the next one
+    pub fn pop(&self) {
+        self.messages.lock().pop_front();
+    }
+
+    /// Close all messages
+    pub fn clear(&self) {
+        self.messages.lock().clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.lock().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::term::SizeInfo;
+
+    use super::{Message, MessageBuffer, MessageType, MAX_MESSAGES};
+
+    fn size(cols: usize, lines: usize) -> SizeInfo {
+        SizeInfo {
+            width: cols as f32,
+            height: lines as f32,
+            cell_width: 1.,
+            cell_height: 1.,
+            padding_x: 0.,
+            padding_y: 0.,
+            dpr: 1.,
+            reserved_lines: 0,
+        }
+    }
+
+    #[test]
+    fn close_button_on_first_line() {
+        let message = Message::new(String::from("abc"), MessageType::Error);
+
+        assert_eq!(message.text(&size(10, 10)), vec![String::from("abc    [X]")]);
+    }
+
+    #[test]
+    fn wrap_long_lines_and_newlines() {
+        let message = Message::new(String::from("hello world\nbye"), MessageType::Warning);
+
+        assert_eq!(message.text(&size(10, 10)), vec![
+            String::from("hello  [X]"),
+            String::from("world     "),
+            String::from("bye       "),
+        ]);
+    }
+
+    #[test]
+    fn truncate_long_messages() {
+        let message = Message::new(String::from("a\nb\nc\nd\ne"), MessageType::Error);
+
+        assert_eq!(message.text(&size(22, 6)), vec![
+            String::from("a                  [X]"),
+            String::from("b                     "),
+            String::from("[MESSAGE TRUNCATED]   "),
+        ]);
+    }
+
+    #[test]
+    fn ignore_duplicates() {
+        let buffer = MessageBuffer::default();
+        let error = Message::new(String::from("error"), MessageType::Error);
+        let warning = Message::new(String::from("warning"), MessageType::Warning);
+
+        buffer.push(error.clone());
+        buffer.push(warning.clone());
+        buffer.push(error.clone());
+        assert_eq!(buffer.message(), Some(error));
+
+        buffer.pop();
+        assert_eq!(buffer.message(), Some(warning));
+
+        buffer.pop();
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn drop_oldest_waiting_messages() {
+        let buffer = MessageBuffer::default();
+        for i in 0..=MAX_MESSAGES {
+            buffer.push(Message::new(i.to_string(), MessageType::Warning));
+        }
+
+        assert_eq!(buffer.messages.lock().len(), MAX_MESSAGES);
+        assert_eq!(buffer.message(), Some(Message::new(String::from("0"), MessageType::Warning)));
+
+        buffer.pop();
+        assert_eq!(buffer.message(), Some(Message::new(String::from("2"), MessageType::Warning)));
+    }
+}
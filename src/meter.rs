@@ -97,8 +97,8 @@ impl Meter {
 
     /// Add a sample
     ///
-    /// Used by Sampler::drop.
-    fn add_sample(&mut self, sample: Duration) {
+    /// Used by Sampler::drop, and for durations measured while the meter can't be borrowed.
+    pub fn add_sample(&mut self, sample: Duration) {
         let mut usec = 0f64;
 
         usec += f64::from(sample.subsec_nanos()) / 1e3;
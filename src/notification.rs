@@ -0,0 +1,102 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Bell commands and desktop notifications
+//!
+//! Desktop notifications are shown with the notification tool of the platform, `notify-send` on
+//! Linux and BSD and `osascript` on macOS.
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::util::start_daemon;
+
+/// Events which can run the bell command or show a desktop notification
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Notification {
+    /// The bell rang
+    Bell,
+
+    /// A command reported by shell integration finished
+    CommandFinished {
+        duration: Duration,
+        status: Option<i32>,
+    },
+}
+
+impl Notification {
+    /// Run the configured actions for the notification
+    ///
+    /// Desktop notifications are only shown while the window is unfocused, `title` is the
+    /// window title.
+    pub fn deliver(self, config: &Config, title: &str, focused: bool) {
+        let notifications = config.notifications();
+
+        match self {
+            Notification::Bell => {
+                if let Some(command) = &config.bell().command {
+                    match start_daemon(command.program(), command.args()) {
+                        Ok(_) => debug!("Ran bell command {}", command.program()),
+                        Err(_) => warn!("Unable to run bell command {}", command.program()),
+                    }
+                }
+
+                if notifications.bell && !focused {
+                    show(title, "Bell");
+                }
+            },
+            Notification::CommandFinished { duration, status } => {
+                let threshold = match notifications.command_threshold() {
+                    Some(threshold) => threshold,
+                    None => return,
+                };
+
+                if duration >= threshold && !focused {
+                    let seconds = duration.as_secs();
+                    let body = match status {
+                        Some(status) if status != 0 => {
+                            format!("Command failed with status {} after {}s", status, seconds)
+                        },
+                        _ => format!("Command finished after {}s", seconds),
+                    };
+                    show(title, &body);
+                }
+            },
+        }
+    }
+}
+
+/// Show a desktop notification
+#[cfg(not(any(target_os = "macos", windows)))]
+fn show(summary: &str, body: &str) {
+    let args = ["--app-name=Alacritty", summary, body];
+    if start_daemon("notify-send", &args).is_err() {
+        warn!("Unable to show desktop notification with notify-send");
+    }
+}
+
+/// Show a desktop notification
+#[cfg(target_os = "macos")]
+fn show(summary: &str, body: &str) {
+    // Debug formatting quotes and escapes the strings for AppleScript
+    let script = format!("display notification {:?} with title {:?}", body, summary);
+    if start_daemon("osascript", &["-e", &script]).is_err() {
+        warn!("Unable to show desktop notification with osascript");
+    }
+}
+
+/// Show a desktop notification
+#[cfg(windows)]
+fn show(_summary: &str, _body: &str) {
+    debug!("Desktop notifications are not supported on Windows");
+}
@@ -0,0 +1,236 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Playback of asciicast recordings
+//!
+//! The recorded output is fed to the parser with the original timing, without going through a
+//! pty like the output of a child process.
+use std::io::{self, Write};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::ansi;
+use crate::asciicast::Recording;
+use crate::display;
+use crate::sync::FairMutex;
+use crate::term::Term;
+use crate::util::thread;
+
+/// Fastest and slowest playback speed
+const MAX_SPEED: f64 = 16.;
+
+/// Distance of a single seek, in seconds of the recording
+const SEEK: f64 = 5.;
+
+/// Reset the terminal and clear the scrollback history
+const RESET: &[u8] = b"\x1bc\x1b[3J";
+
+/// Commands for a running playback
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Control {
+    Faster,
+    Slower,
+    Forward,
+    Backward,
+}
+
+/// Parses the output of a recording straight into the terminal
+pub struct Output {
+    terminal: Arc<FairMutex<Term>>,
+    parser: ansi::Processor,
+    notifier: display::Notifier,
+}
+
+impl Output {
+    pub fn new(terminal: Arc<FairMutex<Term>>, notifier: display::Notifier) -> Output {
+        Output { terminal, parser: ansi::Processor::new(), notifier }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut terminal = self.terminal.lock();
+        terminal.finish_banner();
+
+        // Nothing is listening for responses to the queries in a recording
+        self.parser.advance_bytes(&mut *terminal, buf, &mut io::sink());
+        terminal.flush_damage();
+        drop(terminal);
+
+        self.notifier.notify();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Player<W> {
+    recording: Recording,
+    writer: W,
+
+    /// Index of the next event
+    index: usize,
+
+    /// Position in the recording at `last`, in seconds
+    clock: f64,
+    last: Instant,
+    speed: f64,
+}
+
+impl<W: Write> Player<W> {
+    fn new(recording: Recording, writer: W) -> Player<W> {
+        Player { recording, writer, index: 0, clock: 0., last: Instant::now(), speed: 1. }
+    }
+
+    /// Advance the clock to the current time
+    fn tick(&mut self) {
+        let now = Instant::now();
+        let elapsed = now - self.last;
+        self.clock += (elapsed.as_secs() as f64 + f64::from(elapsed.subsec_micros()) / 1e6)
+            * self.speed;
+        self.last = now;
+    }
+
+    fn control(&mut self, control: Control) {
+        self.tick();
+
+        match control {
+            Control::Faster => self.speed = (self.speed * 2.).min(MAX_SPEED),
+            Control::Slower => self.speed = (self.speed / 2.).max(1. / MAX_SPEED),
+            Control::Forward => self.clock += SEEK,
+            Control::Backward => {
+                // Output can't be undone, so everything up to the new position is replayed
+                self.clock = (self.clock - SEEK).max(0.);
+                self.index = 0;
+                self.write(RESET);
+            },
+        }
+    }
+
+    /// Write all events which are due, returns the time until the next event
+    ///
+    /// Returns `None` once the recording is finished.
+    fn play(&mut self) -> Option<Duration> {
+        self.tick();
+
+        while let Some((time, data)) = self.recording.events.get(self.index) {
+            if *time > self.clock {
+                let wait = (time - self.clock) / self.speed;
+                return Some(Duration::from_micros((wait * 1e6) as u64));
+            }
+
+            let data = data.clone();
+            self.write(&data);
+            self.index += 1;
+        }
+
+        None
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        if let Err(err) = self.writer.write_all(data) {
+            warn!("Unable to write recording to the terminal: {}", err);
+        }
+    }
+}
+
+/// Play the recording on a separate thread
+///
+/// The `input` side of the pty is kept open until playback stops once the sending side of `rx`
+/// is dropped, since the terminal keeps writing keystrokes to it.
+pub fn spawn<W, I>(recording: Recording, writer: W, input: I, rx: Receiver<Control>)
+where
+    W: Write + Send + 'static,
+    I: Send + 'static,
+{
+    thread::spawn_named("playback", move || {
+        let _input = input;
+        let mut player = Player::new(recording, writer);
+
+        loop {
+            let control = match player.play() {
+                Some(wait) => match rx.recv_timeout(wait) {
+                    Ok(control) => control,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                },
+                None => match rx.recv() {
+                    Ok(control) => control,
+                    Err(_) => break,
+                },
+            };
+
+            player.control(control);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Control, Player, RESET};
+    use crate::asciicast::Recording;
+
+    fn recording() -> Recording {
+        let events = vec![(0., b"a".to_vec()), (1., b"b".to_vec()), (7., b"c".to_vec())];
+        Recording { width: 80, height: 24, events }
+    }
+
+    fn output(player: &Player<Vec<u8>>) -> String {
+        String::from_utf8(player.writer.clone()).unwrap()
+    }
+
+    #[test]
+    fn play_due_events() {
+        let mut player = Player::new(recording(), Vec::new());
+
+        assert!(player.play().is_some());
+        assert_eq!(output(&player), "a");
+
+        player.control(Control::Forward);
+        assert!(player.play().is_some());
+        assert_eq!(output(&player), "ab");
+
+        player.control(Control::Forward);
+        assert!(player.play().is_none());
+        assert_eq!(output(&player), "abc");
+    }
+
+    #[test]
+    fn seek_backward_replays() {
+        let mut player = Player::new(recording(), Vec::new());
+        player.control(Control::Forward);
+        player.play();
+
+        player.control(Control::Backward);
+        player.play();
+        assert_eq!(output(&player), format!("ab{}a", String::from_utf8_lossy(RESET)));
+    }
+
+    #[test]
+    fn speed_is_clamped() {
+        let mut player = Player::new(recording(), Vec::new());
+        for _ in 0..10 {
+            player.control(Control::Faster);
+        }
+        assert!((player.speed - 16.).abs() < f64::EPSILON);
+
+        for _ in 0..20 {
+            player.control(Control::Slower);
+        }
+        assert!((player.speed - 1. / 16.).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,410 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Recording and replay of ref tests
+//!
+//! With `--ref-test` the pty output is written to `alacritty.recording`, and the timing of every
+//! read to `alacritty.timing`. The timing uses the format of `script(1)`, every line has the
+//! delay since the previous read in seconds and the number of bytes which were read.
+//!
+//! With `--ref-test-replay` a ref test is played with its original timing, then a screenshot of
+//! the next frame is compared with `screenshot.ppm`. This catches regressions in rendering,
+//! including animations which are still running at the end of the recording.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use serde_json as json;
+
+use crate::asciicast::Recording;
+use crate::display;
+use crate::term::SizeInfo;
+use crate::util::thread;
+
+pub const RECORDING: &str = "alacritty.recording";
+pub const TIMING: &str = "alacritty.timing";
+pub const SCREENSHOT: &str = "screenshot.ppm";
+
+/// Screenshot of a failed replay, next to the expected one
+pub const ACTUAL_SCREENSHOT: &str = "screenshot.actual.ppm";
+
+/// Smallest difference of a color channel which counts as a changed pixel
+const CHANNEL_TOLERANCE: i16 = 8;
+
+/// Fraction of pixels which may change before screenshots are considered different
+const MAX_DIFFERENCE: f64 = 0.001;
+
+/// Writes the pty output of a ref test, together with its timing
+pub struct Writer<W: Write = File> {
+    recording: W,
+    timing: W,
+    last: Instant,
+}
+
+impl Writer<File> {
+    /// Create the recording and timing files in `dir`, replacing existing ones
+    pub fn create(dir: &Path) -> io::Result<Writer> {
+        Ok(Writer::new(File::create(dir.join(RECORDING))?, File::create(dir.join(TIMING))?))
+    }
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(recording: W, timing: W) -> Writer<W> {
+        Writer { recording, timing, last: Instant::now() }
+    }
+}
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = Instant::now();
+        let delay = seconds(now - self.last);
+        self.last = now;
+
+        self.recording.write_all(buf)?;
+        writeln!(self.timing, "{:.6} {}", delay, buf.len())?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.recording.flush()?;
+        self.timing.flush()
+    }
+}
+
+/// Load the recording of a ref test
+///
+/// Ref tests without timing are output all at once.
+pub fn load(dir: &Path) -> io::Result<Recording> {
+    let mut bytes = Vec::new();
+    File::open(dir.join(RECORDING))?.read_to_end(&mut bytes)?;
+
+    let timing = match File::open(dir.join(TIMING)) {
+        Ok(file) => parse_timing(BufReader::new(file))?,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => vec![(0., bytes.len())],
+        Err(err) => return Err(err),
+    };
+
+    let size: SizeInfo = json::from_reader(File::open(dir.join("size.json"))?)?;
+
+    Ok(Recording {
+        width: size.cols().0,
+        height: size.lines().0,
+        events: split(bytes, &timing)?,
+    })
+}
+
+/// Parse the lines of a timing file into delays and lengths
+fn parse_timing<R: BufRead>(reader: R) -> io::Result<Vec<(f64, usize)>> {
+    let invalid = |line: &str| {
+        io::Error::new(ErrorKind::InvalidData, format!("invalid timing \"{}\"", line))
+    };
+
+    let mut timing = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let mut fields = line.split_whitespace();
+        match (fields.next(), fields.next(), fields.next()) {
+            (Some(delay), Some(len), None) => {
+                let delay = delay.parse().map_err(|_| invalid(&line))?;
+                let len = len.parse().map_err(|_| invalid(&line))?;
+                timing.push((delay, len));
+            },
+            (None, ..) => (),
+            _ => return Err(invalid(&line)),
+        }
+    }
+
+    Ok(timing)
+}
+
+/// Split the recorded bytes into chunks, each with its time since the start of the recording
+fn split(mut bytes: Vec<u8>, timing: &[(f64, usize)]) -> io::Result<Vec<(f64, Vec<u8>)>> {
+    let mut time = 0.;
+    let mut events = Vec::with_capacity(timing.len());
+    for &(delay, len) in timing {
+        if len > bytes.len() {
+            let msg = "timing is longer than the recording";
+            return Err(io::Error::new(ErrorKind::InvalidData, msg));
+        }
+
+        let rest = bytes.split_off(len);
+        time += delay;
+        events.push((time, bytes));
+        bytes = rest;
+    }
+
+    if !bytes.is_empty() {
+        let msg = "timing is shorter than the recording";
+        return Err(io::Error::new(ErrorKind::InvalidData, msg));
+    }
+
+    Ok(events)
+}
+
+/// Replay of a ref test, started with `--ref-test-replay`
+pub struct Replay {
+    dir: PathBuf,
+    finished: Arc<AtomicBool>,
+
+    /// Store the screenshot as the reference instead of comparing it
+    update: bool,
+}
+
+impl Replay {
+    /// Write the recording to the slave side of a pty with its original timing
+    ///
+    /// Once everything has been written the terminal is asked for its status. Escape sequences
+    /// are processed in order, so the response means that the whole recording has been parsed.
+    pub fn spawn(
+        dir: &Path,
+        recording: Recording,
+        mut pty: File,
+        notifier: display::Notifier,
+        update: bool,
+    ) -> Replay {
+        let finished = Arc::new(AtomicBool::new(false));
+        let replay = Replay { dir: dir.to_path_buf(), finished: finished.clone(), update };
+
+        thread::spawn_named("ref test replay", move || {
+            let start = Instant::now();
+            for (time, data) in recording.events {
+                let elapsed = seconds(start.elapsed());
+                if time > elapsed {
+                    thread::sleep(Duration::from_micros(((time - elapsed) * 1e6) as u64));
+                }
+
+                if let Err(err) = pty.write_all(&data) {
+                    error!("Unable to replay ref test: {}", err);
+                    return;
+                }
+            }
+
+            // Responses to queries in the recording may arrive before the status report
+            let mut response = Vec::new();
+            let mut byte = [0];
+            let result = pty.write_all(b"\x1b[5n").and_then(|_| {
+                while !response.ends_with(b"\x1b[0n") {
+                    pty.read_exact(&mut byte)?;
+                    response.push(byte[0]);
+                }
+                Ok(())
+            });
+            if let Err(err) = result {
+                error!("Unable to wait for the ref test replay: {}", err);
+                return;
+            }
+
+            finished.store(true, Ordering::Relaxed);
+            notifier.notify();
+        });
+
+        replay
+    }
+
+    /// Check if the terminal has processed the whole recording
+    pub fn finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    /// Compare a screenshot with the one stored in the ref test
+    ///
+    /// Returns `false` if the screenshots differ or there is no stored screenshot, the new one is
+    /// then stored as `screenshot.actual.ppm`. With `--ref-test-update` the new screenshot
+    /// becomes the reference instead.
+    pub fn check(&self, screenshot: &Screenshot) -> io::Result<bool> {
+        let path = self.dir.join(SCREENSHOT);
+        if self.update {
+            screenshot.write_ppm(File::create(&path)?)?;
+            info!("Stored reference screenshot {}", path.display());
+            return Ok(true);
+        }
+
+        let actual = self.dir.join(ACTUAL_SCREENSHOT);
+        let expected = match File::open(&path) {
+            Ok(file) => Screenshot::read_ppm(BufReader::new(file))?,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => {
+                screenshot.write_ppm(File::create(&actual)?)?;
+                error!(
+                    "Missing reference screenshot {}, stored the screenshot as {}; \
+                     use --ref-test-update to make it the reference",
+                    path.display(),
+                    actual.display()
+                );
+                return Ok(false);
+            },
+            Err(err) => return Err(err),
+        };
+
+        let difference = expected.difference(screenshot);
+        if difference <= MAX_DIFFERENCE {
+            return Ok(true);
+        }
+
+        screenshot.write_ppm(File::create(&actual)?)?;
+        error!(
+            "Screenshot differs from {} in {:.2}% of pixels, stored it as {}",
+            path.display(),
+            difference * 100.,
+            actual.display()
+        );
+
+        Ok(false)
+    }
+}
+
+/// RGB pixels of a frame, row by row from the top left
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Screenshot {
+    /// Write the screenshot as binary PPM image
+    pub fn write_ppm<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        writer.write_all(&self.pixels)
+    }
+
+    /// Read a binary PPM image, as written by `write_ppm`
+    ///
+    /// Comments in the header are not supported.
+    pub fn read_ppm<R: BufRead>(mut reader: R) -> io::Result<Screenshot> {
+        let invalid = || io::Error::new(ErrorKind::InvalidData, "invalid PPM image");
+
+        let mut header = String::new();
+        for _ in 0..3 {
+            reader.read_line(&mut header)?;
+        }
+
+        let fields: Vec<&str> = header.split_whitespace().collect();
+        let (width, height) = match fields[..] {
+            ["P6", width, height, "255"] => (
+                width.parse().map_err(|_| invalid())?,
+                height.parse().map_err(|_| invalid())?,
+            ),
+            _ => return Err(invalid()),
+        };
+
+        let mut pixels = vec![0; width * height * 3];
+        reader.read_exact(&mut pixels)?;
+
+        Ok(Screenshot { width, height, pixels })
+    }
+
+    /// Fraction of pixels which differ noticeably, screenshots of different sizes differ entirely
+    pub fn difference(&self, other: &Screenshot) -> f64 {
+        if (self.width, self.height) != (other.width, other.height) {
+            return 1.;
+        }
+
+        let changed = self.pixels
+            .chunks(3)
+            .zip(other.pixels.chunks(3))
+            .filter(|(pixel, other)| {
+                pixel.iter().zip(other.iter()).any(|(channel, other)| {
+                    (i16::from(*channel) - i16::from(*other)).abs() >= CHANNEL_TOLERANCE
+                })
+            })
+            .count();
+
+        changed as f64 / (self.width * self.height).max(1) as f64
+    }
+}
+
+#[inline]
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_micros()) / 1e6
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+    use std::process;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use super::{parse_timing, split, Replay, Screenshot, Writer, ACTUAL_SCREENSHOT, SCREENSHOT};
+
+    #[test]
+    fn split_recording_by_timing() {
+        let mut writer = Writer::new(Vec::new(), Vec::new());
+        writer.write_all(b"hello").unwrap();
+        writer.write_all(b" world").unwrap();
+
+        let timing = parse_timing(&writer.timing[..]).unwrap();
+        let events = split(writer.recording, &timing).unwrap();
+        let chunks: Vec<&[u8]> = events.iter().map(|(_, chunk)| &chunk[..]).collect();
+        assert_eq!(chunks, vec![&b"hello"[..], &b" world"[..]]);
+        assert!(events[0].0 <= events[1].0);
+    }
+
+    #[test]
+    fn split_recording_timing_mismatch() {
+        assert!(split(b"hello".to_vec(), &[(0., 6)]).is_err());
+        assert!(split(b"hello".to_vec(), &[(0., 4)]).is_err());
+        assert!(parse_timing(&b"0.5 five\n"[..]).is_err());
+    }
+
+    #[test]
+    fn screenshot_ppm() {
+        let screenshot = Screenshot { width: 2, height: 1, pixels: vec![0, 10, 20, 255, 128, 0] };
+
+        let mut ppm = Vec::new();
+        screenshot.write_ppm(&mut ppm).unwrap();
+        assert!(ppm.starts_with(b"P6\n2 1\n255\n"));
+        assert_eq!(Screenshot::read_ppm(&ppm[..]).unwrap(), screenshot);
+    }
+
+    #[test]
+    fn screenshot_difference() {
+        let screenshot = Screenshot { width: 2, height: 1, pixels: vec![0, 10, 20, 255, 128, 0] };
+
+        let mut similar = screenshot.clone();
+        similar.pixels[0] = 7;
+        assert!(screenshot.difference(&similar) < std::f64::EPSILON);
+
+        let mut changed = screenshot.clone();
+        changed.pixels[5] = 200;
+        assert!((screenshot.difference(&changed) - 0.5).abs() < std::f64::EPSILON);
+
+        let smaller = Screenshot { width: 1, height: 1, pixels: vec![0, 10, 20] };
+        assert!((screenshot.difference(&smaller) - 1.).abs() < std::f64::EPSILON);
+    }
+    #[test]
+    fn missing_reference_screenshot() {
+        let dir = env::temp_dir().join(format!("alacritty-ref-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let screenshot = Screenshot { width: 1, height: 1, pixels: vec![0, 10, 20] };
+
+        let finished = Arc::new(AtomicBool::new(true));
+        let replay = Replay { dir: dir.clone(), finished, update: false };
+        assert!(!replay.check(&screenshot).unwrap());
+        assert!(!dir.join(SCREENSHOT).exists());
+        assert!(dir.join(ACTUAL_SCREENSHOT).exists());
+
+        let update = Replay { update: true, ..replay };
+        assert!(update.check(&screenshot).unwrap());
+        assert!(update.dir.join(SCREENSHOT).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
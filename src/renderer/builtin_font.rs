@@ -0,0 +1,495 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Box drawing, block element and Powerline glyphs drawn to fill their cell
+//!
+//! Fonts rarely size these characters to match the cell exactly, which leaves gaps between lines
+//! in neighbouring cells. Drawing them here keeps them connected at every font size.
+use std::cmp::{max, min};
+use std::ops::Range;
+
+use font::{Metrics, RasterizedGlyph};
+
+use crate::config::Delta;
+
+/// Weights of box drawing lines
+const NONE: u8 = 0;
+const LIGHT: u8 = 1;
+const HEAVY: u8 = 2;
+const DOUBLE: u8 = 3;
+
+/// Lines of U+2500 to U+257F, as the weights of the left, up, right and down arm
+///
+/// Dashed lines, arcs and diagonals have no arms and are drawn separately.
+#[rustfmt::skip]
+const BOX_LINES: [[u8; 4]; 128] = {
+    const N: u8 = NONE;
+    const L: u8 = LIGHT;
+    const H: u8 = HEAVY;
+    const D: u8 = DOUBLE;
+    [
+        // U+2500
+        [L, N, L, N], [H, N, H, N], [N, L, N, L], [N, H, N, H],
+        [N, N, N, N], [N, N, N, N], [N, N, N, N], [N, N, N, N],
+        [N, N, N, N], [N, N, N, N], [N, N, N, N], [N, N, N, N],
+        [N, N, L, L], [N, N, H, L], [N, N, L, H], [N, N, H, H],
+        // U+2510
+        [L, N, N, L], [H, N, N, L], [L, N, N, H], [H, N, N, H],
+        [N, L, L, N], [N, L, H, N], [N, H, L, N], [N, H, H, N],
+        [L, L, N, N], [H, L, N, N], [L, H, N, N], [H, H, N, N],
+        [N, L, L, L], [N, L, H, L], [N, H, L, L], [N, L, L, H],
+        // U+2520
+        [N, H, L, H], [N, H, H, L], [N, L, H, H], [N, H, H, H],
+        [L, L, N, L], [H, L, N, L], [L, H, N, L], [L, L, N, H],
+        [L, H, N, H], [H, H, N, L], [H, L, N, H], [H, H, N, H],
+        [L, N, L, L], [H, N, L, L], [L, N, H, L], [H, N, H, L],
+        // U+2530
+        [L, N, L, H], [H, N, L, H], [L, N, H, H], [H, N, H, H],
+        [L, L, L, N], [H, L, L, N], [L, L, H, N], [H, L, H, N],
+        [L, H, L, N], [H, H, L, N], [L, H, H, N], [H, H, H, N],
+        [L, L, L, L], [H, L, L, L], [L, L, H, L], [H, L, H, L],
+        // U+2540
+        [L, H, L, L], [L, L, L, H], [L, H, L, H], [H, H, L, L],
+        [L, H, H, L], [H, L, L, H], [L, L, H, H], [H, H, H, L],
+        [H, L, H, H], [H, H, L, H], [L, H, H, H], [H, H, H, H],
+        [N, N, N, N], [N, N, N, N], [N, N, N, N], [N, N, N, N],
+        // U+2550
+        [D, N, D, N], [N, D, N, D], [N, N, D, L], [N, N, L, D],
+        [N, N, D, D], [D, N, N, L], [L, N, N, D], [D, N, N, D],
+        [N, L, D, N], [N, D, L, N], [N, D, D, N], [D, L, N, N],
+        [L, D, N, N], [D, D, N, N], [N, L, D, L], [N, D, L, D],
+        // U+2560
+        [N, D, D, D], [D, L, N, L], [L, D, N, D], [D, D, N, D],
+        [D, N, D, L], [L, N, L, D], [D, N, D, D], [D, L, D, N],
+        [L, D, L, N], [D, D, D, N], [D, L, D, L], [L, D, L, D],
+        [D, D, D, D], [N, N, N, N], [N, N, N, N], [N, N, N, N],
+        // U+2570
+        [N, N, N, N], [N, N, N, N], [N, N, N, N], [N, N, N, N],
+        [L, N, N, N], [N, L, N, N], [N, N, L, N], [N, N, N, L],
+        [H, N, N, N], [N, H, N, N], [N, N, H, N], [N, N, N, H],
+        [L, N, H, N], [N, L, N, H], [H, N, L, N], [N, H, N, L],
+    ]
+};
+
+/// Rasterize `c` to cover its whole cell, `None` if it isn't drawn by Alacritty
+pub fn builtin_glyph(c: char, metrics: &Metrics, offset: &Delta<i8>) -> Option<RasterizedGlyph> {
+    let width = (metrics.average_advance + f64::from(offset.x)).floor();
+    let height = (metrics.line_height + f64::from(offset.y)).floor();
+    if width < 1. || height < 1. {
+        return None;
+    }
+
+    let mut canvas = Canvas::new(width as usize, height as usize);
+    let code = c as u32;
+    match code {
+        0x2500..=0x257f => box_drawing(&mut canvas, code),
+        0x2580..=0x259f => block_element(&mut canvas, code),
+        0xe0b0..=0xe0b3 => powerline(&mut canvas, code),
+        _ => return None,
+    }
+
+    Some(canvas.into_glyph(c))
+}
+
+/// Coverage of every pixel of a cell
+struct Canvas {
+    width: usize,
+    height: usize,
+    alpha: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas { width, height, alpha: vec![0; width * height] }
+    }
+
+    /// Thickness of light lines, heavy lines are twice as thick
+    fn light(&self) -> usize {
+        max(1, (self.width as f32 / 8.).round() as usize)
+    }
+
+    /// Cover all pixels within `x` and `y`
+    fn rect(&mut self, x: Range<usize>, y: Range<usize>) {
+        for row in y.start..min(y.end, self.height) {
+            for col in x.start..min(x.end, self.width) {
+                self.alpha[row * self.width + col] = 255;
+            }
+        }
+    }
+
+    /// Cover a rectangle which is swapped to run down the cell when `vertical` is set
+    fn oriented_rect(&mut self, vertical: bool, along: Range<usize>, across: Range<usize>) {
+        if vertical {
+            self.rect(across, along);
+        } else {
+            self.rect(along, across);
+        }
+    }
+
+    /// Cover every pixel partially
+    fn shade(&mut self, alpha: u8) {
+        for pixel in &mut self.alpha {
+            *pixel = alpha;
+        }
+    }
+
+    /// Cover the area for which `inside` is true, with smooth edges
+    fn fill<F: Fn(f32, f32) -> bool>(&mut self, inside: F) {
+        const SAMPLES: usize = 4;
+
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let mut covered = 0;
+                for sample_y in 0..SAMPLES {
+                    for sample_x in 0..SAMPLES {
+                        let x = col as f32 + (sample_x as f32 + 0.5) / SAMPLES as f32;
+                        let y = row as f32 + (sample_y as f32 + 0.5) / SAMPLES as f32;
+                        if inside(x, y) {
+                            covered += 1;
+                        }
+                    }
+                }
+
+                let pixel = &mut self.alpha[row * self.width + col];
+                *pixel = max(*pixel, (covered * 255 / (SAMPLES * SAMPLES)) as u8);
+            }
+        }
+    }
+
+    /// Glyph covering the cell from its bottom left corner
+    fn into_glyph(self, c: char) -> RasterizedGlyph {
+        RasterizedGlyph {
+            c,
+            width: self.width as i32,
+            height: self.height as i32,
+            top: self.height as i32,
+            left: 0,
+            buf: self.alpha.iter().flat_map(|&alpha| vec![alpha; 3]).collect(),
+        }
+    }
+}
+
+/// Pixels covered by a line of `weight` in the middle of `size`, including the gap of doubles
+fn stroke(size: usize, light: usize, weight: u8) -> Range<usize> {
+    let thickness = match weight {
+        LIGHT => light,
+        HEAVY => 2 * light,
+        DOUBLE => 3 * light,
+        _ => 0,
+    };
+
+    let start = size.saturating_sub(thickness) / 2;
+    start..start + thickness
+}
+
+fn box_drawing(canvas: &mut Canvas, code: u32) {
+    let light = canvas.light();
+    match code {
+        0x2504..=0x250b | 0x254c..=0x254f => dashes(canvas, light, code),
+        0x256d..=0x2570 => arc(canvas, light, code),
+        0x2571..=0x2573 => diagonals(canvas, light, code),
+        _ => {
+            let [left, up, right, down] = BOX_LINES[(code - 0x2500) as usize];
+            arms(canvas, light, false, [left, right], [up, down]);
+            arms(canvas, light, true, [up, down], [left, right]);
+        },
+    }
+}
+
+/// Draw the arms of a box drawing character along one direction
+///
+/// The horizontal arms are drawn from the left and right weights, with `cross` being the up and
+/// down weights. Vertical arms swap both. Arms stop where they meet the crossing lines, so the
+/// strokes of double lines form corners instead of crossing each other.
+fn arms(canvas: &mut Canvas, light: usize, vertical: bool, weights: [u8; 2], cross: [u8; 2]) {
+    let (along_size, across_size) = if vertical {
+        (canvas.height, canvas.width)
+    } else {
+        (canvas.width, canvas.height)
+    };
+
+    let crossing = stroke(along_size, light, max(cross[0], cross[1]));
+    let double_crossing = stroke(along_size, light, DOUBLE);
+    let crosses_double = cross[0] == DOUBLE && cross[1] == DOUBLE;
+
+    for (index, &weight) in weights.iter().enumerate() {
+        let before = index == 0;
+        let other = weights[1 - index];
+
+        // Part of the arm's axis which is covered, ending or starting at `edge`
+        let span = |edge: usize| if before { 0..edge } else { edge..along_size };
+
+        if weight == DOUBLE {
+            let outer = stroke(across_size, light, DOUBLE);
+            let strokes = [outer.start..outer.start + light, outer.end - light..outer.end];
+            for (stroke, &blocked) in strokes.iter().zip(cross.iter()) {
+                let edge = match (before, blocked != NONE) {
+                    (true, true) => double_crossing.start + light,
+                    (false, true) => double_crossing.end - light,
+                    (true, false) => crossing.end,
+                    (false, false) => crossing.start,
+                };
+                canvas.oriented_rect(vertical, span(edge), stroke.clone());
+            }
+        } else if weight != NONE {
+            // Lines joining a double line from one side stop at its nearest stroke
+            let edge = match (before, crosses_double && other == NONE) {
+                (true, true) => double_crossing.start + light,
+                (false, true) => double_crossing.end - light,
+                (true, false) => crossing.end,
+                (false, false) => crossing.start,
+            };
+            canvas.oriented_rect(vertical, span(edge), stroke(across_size, light, weight));
+        }
+    }
+}
+
+/// Dashed lines, with the gaps split between both ends of every dash to continue across cells
+fn dashes(canvas: &mut Canvas, light: usize, code: u32) {
+    let (count, weight, vertical) = match code {
+        0x2504 => (3, LIGHT, false),
+        0x2505 => (3, HEAVY, false),
+        0x2506 => (3, LIGHT, true),
+        0x2507 => (3, HEAVY, true),
+        0x2508 => (4, LIGHT, false),
+        0x2509 => (4, HEAVY, false),
+        0x250a => (4, LIGHT, true),
+        0x250b => (4, HEAVY, true),
+        0x254c => (2, LIGHT, false),
+        0x254d => (2, HEAVY, false),
+        0x254e => (2, LIGHT, true),
+        _ => (2, HEAVY, true),
+    };
+
+    let (along_size, across_size) = if vertical {
+        (canvas.height, canvas.width)
+    } else {
+        (canvas.width, canvas.height)
+    };
+    let across = stroke(across_size, light, weight);
+    let gap = max(1, along_size / count / 4);
+
+    for index in 0..count {
+        let start = index * along_size / count + gap / 2;
+        let end = ((index + 1) * along_size / count).saturating_sub(gap - gap / 2);
+        canvas.oriented_rect(vertical, start..max(start, end), across.clone());
+    }
+}
+
+/// Rounded corners, continued with straight lines to the edges of the cell
+fn arc(canvas: &mut Canvas, light: usize, code: u32) {
+    let (width, height) = (canvas.width, canvas.height);
+    let stroke_x = stroke(width, light, LIGHT);
+    let stroke_y = stroke(height, light, LIGHT);
+
+    let middle_x = (stroke_x.start + stroke_x.end) as f32 / 2.;
+    let middle_y = (stroke_y.start + stroke_y.end) as f32 / 2.;
+    let radius = middle_x
+        .min(width as f32 - middle_x)
+        .min(middle_y)
+        .min(height as f32 - middle_y);
+
+    let (right, down) = match code {
+        0x256d => (true, true),
+        0x256e => (false, true),
+        0x256f => (false, false),
+        _ => (true, false),
+    };
+    let center_x = if right { middle_x + radius } else { middle_x - radius };
+    let center_y = if down { middle_y + radius } else { middle_y - radius };
+
+    let half = light as f32 / 2.;
+    canvas.fill(|x, y| {
+        let distance = ((x - center_x).powi(2) + (y - center_y).powi(2)).sqrt();
+        (x <= center_x) == right && (y <= center_y) == down && (distance - radius).abs() <= half
+    });
+
+    let end_x = center_x.round() as usize;
+    let end_y = center_y.round() as usize;
+    if right {
+        canvas.rect(end_x..width, stroke_y);
+    } else {
+        canvas.rect(0..end_x, stroke_y);
+    }
+    if down {
+        canvas.rect(stroke_x, end_y..height);
+    } else {
+        canvas.rect(stroke_x, 0..end_y);
+    }
+}
+
+/// Diagonals from corner to corner
+fn diagonals(canvas: &mut Canvas, light: usize, code: u32) {
+    let (width, height) = (canvas.width as f32, canvas.height as f32);
+    let length = (width * width + height * height).sqrt();
+    let half = light as f32 / 2.;
+
+    let falling = move |x: f32, y: f32| (x * height - y * width).abs() / length <= half;
+    let rising = move |x: f32, y: f32| ((width - x) * height - y * width).abs() / length <= half;
+
+    match code {
+        0x2571 => canvas.fill(rising),
+        0x2572 => canvas.fill(falling),
+        _ => canvas.fill(|x, y| rising(x, y) || falling(x, y)),
+    }
+}
+
+fn block_element(canvas: &mut Canvas, code: u32) {
+    let (width, height) = (canvas.width, canvas.height);
+
+    // Position `eighths` of the way through `size`, rounded the same way for all elements
+    let eighths = |size: usize, eighths: u32| (size * eighths as usize + 4) / 8;
+
+    match code {
+        0x2580 => canvas.rect(0..width, 0..eighths(height, 4)),
+        0x2581..=0x2588 => canvas.rect(0..width, eighths(height, 0x2588 - code)..height),
+        0x2589..=0x258f => canvas.rect(0..eighths(width, 0x2590 - code), 0..height),
+        0x2590 => canvas.rect(eighths(width, 4)..width, 0..height),
+        0x2591 => canvas.shade(0x40),
+        0x2592 => canvas.shade(0x80),
+        0x2593 => canvas.shade(0xc0),
+        0x2594 => canvas.rect(0..width, 0..eighths(height, 1)),
+        0x2595 => canvas.rect(eighths(width, 7)..width, 0..height),
+        _ => {
+            // Upper left, upper right, lower left and lower right quadrant as bits
+            let quadrants = match code {
+                0x2596 => 0b0100,
+                0x2597 => 0b1000,
+                0x2598 => 0b0001,
+                0x2599 => 0b1101,
+                0x259a => 0b1001,
+                0x259b => 0b0111,
+                0x259c => 0b1011,
+                0x259d => 0b0010,
+                0x259e => 0b0110,
+                _ => 0b1110,
+            };
+
+            let (middle_x, middle_y) = (eighths(width, 4), eighths(height, 4));
+            if quadrants & 0b0001 != 0 {
+                canvas.rect(0..middle_x, 0..middle_y);
+            }
+            if quadrants & 0b0010 != 0 {
+                canvas.rect(middle_x..width, 0..middle_y);
+            }
+            if quadrants & 0b0100 != 0 {
+                canvas.rect(0..middle_x, middle_y..height);
+            }
+            if quadrants & 0b1000 != 0 {
+                canvas.rect(middle_x..width, middle_y..height);
+            }
+        },
+    }
+}
+
+/// Powerline separators, pointing right or left
+fn powerline(canvas: &mut Canvas, code: u32) {
+    let (width, height) = (canvas.width as f32, canvas.height as f32);
+    let half = canvas.light() as f32 / 2.;
+    let length = (width * width + height * height / 4.).sqrt();
+
+    // Horizontal distance from the flat side of the arrow and distance from the tip's height
+    let pointing_right = code == 0xe0b0 || code == 0xe0b1;
+    let position = move |x: f32, y: f32| {
+        let x = if pointing_right { x } else { width - x };
+        (x, (height / 2. - y).abs())
+    };
+
+    if code == 0xe0b0 || code == 0xe0b2 {
+        canvas.fill(|x, y| {
+            let (x, from_tip) = position(x, y);
+            x <= width * (1. - 2. * from_tip / height)
+        });
+    } else {
+        canvas.fill(|x, y| {
+            let (x, from_tip) = position(x, y);
+            let y = height / 2. - from_tip;
+            (x * height / 2. - y * width).abs() / length <= half
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use font::Metrics;
+
+    use crate::config::Delta;
+
+    use super::builtin_glyph;
+
+    fn metrics() -> Metrics {
+        Metrics {
+            average_advance: 8.,
+            line_height: 16.,
+            descent: -4.,
+            underline_position: -2.,
+            underline_thickness: 1.,
+            strikeout_position: 4.,
+            strikeout_thickness: 1.,
+        }
+    }
+
+    /// Coverage of every pixel of a glyph, by row
+    fn glyph(c: char) -> Vec<Vec<u8>> {
+        let glyph = builtin_glyph(c, &metrics(), &Delta::default()).unwrap();
+        assert_eq!((glyph.width, glyph.height, glyph.top), (8, 16, 16));
+
+        let alpha: Vec<u8> = glyph.buf.chunks(3).map(|pixel| pixel[0]).collect();
+        alpha.chunks(8).map(|row| row.to_vec()).collect()
+    }
+
+    #[test]
+    fn only_builtin_characters() {
+        assert!(builtin_glyph('a', &metrics(), &Delta::default()).is_none());
+        assert!(builtin_glyph('\u{e0b0}', &metrics(), &Delta::default()).is_some());
+    }
+
+    #[test]
+    fn lines_reach_the_edges() {
+        let horizontal = glyph('─');
+        assert!(horizontal[7].iter().all(|&alpha| alpha == 255));
+        assert!(horizontal[6].iter().all(|&alpha| alpha == 0));
+
+        let vertical = glyph('│');
+        assert!(vertical.iter().all(|row| row[3] == 255 && row[2] == 0));
+    }
+
+    #[test]
+    fn double_corner() {
+        let corner = glyph('╔');
+
+        // Outer stroke reaches from the corner to the right edge and bottom
+        assert!(corner[6][2..].iter().all(|&alpha| alpha == 255));
+        assert!(corner[6..].iter().all(|row| row[2] == 255));
+
+        // The inner stroke doesn't cross the outer one
+        assert_eq!(corner[8][2], 255);
+        assert_eq!(corner[8][3], 0);
+        assert!(corner[8][4..].iter().all(|&alpha| alpha == 255));
+    }
+
+    #[test]
+    fn halves_fill_the_cell() {
+        let (upper, lower) = (glyph('▀'), glyph('▄'));
+        let (left, right) = (glyph('▌'), glyph('▐'));
+
+        for row in 0..16 {
+            for col in 0..8 {
+                assert_eq!(u16::from(upper[row][col]) + u16::from(lower[row][col]), 255);
+                assert_eq!(u16::from(left[row][col]) + u16::from(right[row][col]), 255);
+            }
+        }
+        assert!(glyph('█').iter().all(|row| row.iter().all(|&alpha| alpha == 255)));
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Glyphs of the beam, underline and hollow block cursors
+//!
+//! The cursor cells carry characters of the private use area, which are drawn here to cover the
+//! cell instead of being loaded from the font. The block cursor only swaps the cell's colors.
+use std::cmp::{max, min};
+
+use font::{Metrics, RasterizedGlyph, BEAM_CURSOR_CHAR, BOX_CURSOR_CHAR, UNDERLINE_CURSOR_CHAR};
+
+use crate::config::Delta;
+
+/// Check if `c` is drawn as a cursor
+pub fn is_cursor(c: char) -> bool {
+    c == BEAM_CURSOR_CHAR || c == UNDERLINE_CURSOR_CHAR || c == BOX_CURSOR_CHAR
+}
+
+/// Rasterize the cursor `c`, with lines of `thickness` relative to the cell width
+pub fn cursor_glyph(
+    c: char,
+    metrics: &Metrics,
+    offset: &Delta<i8>,
+    thickness: f32,
+) -> Option<RasterizedGlyph> {
+    if !is_cursor(c) {
+        return None;
+    }
+
+    let width = (metrics.average_advance + f64::from(offset.x)).floor().max(1.) as usize;
+    let height = (metrics.line_height + f64::from(offset.y)).floor().max(1.) as usize;
+    let line = min(max(1, (width as f32 * thickness).round() as usize), min(width, height));
+
+    let covered = |x: usize, y: usize| match c {
+        BEAM_CURSOR_CHAR => x < line,
+        UNDERLINE_CURSOR_CHAR => y >= height - line,
+        _ => x < line || y < line || x >= width - line || y >= height - line,
+    };
+
+    // Glyphs cover the whole cell, from its bottom left corner
+    let mut buf = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let alpha = if covered(x, y) { 255 } else { 0 };
+            buf.extend_from_slice(&[alpha; 3]);
+        }
+    }
+
+    Some(RasterizedGlyph {
+        c,
+        width: width as i32,
+        height: height as i32,
+        top: height as i32,
+        left: 0,
+        buf,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use font::{Metrics, BEAM_CURSOR_CHAR, BOX_CURSOR_CHAR, UNDERLINE_CURSOR_CHAR};
+
+    use crate::config::Delta;
+
+    use super::cursor_glyph;
+
+    /// Coverage of every pixel of a cursor, by row
+    fn glyph(c: char, thickness: f32) -> Vec<Vec<u8>> {
+        let metrics = Metrics {
+            average_advance: 10.,
+            line_height: 20.,
+            descent: -4.,
+            underline_position: -2.,
+            underline_thickness: 1.,
+            strikeout_position: 4.,
+            strikeout_thickness: 1.,
+        };
+        let glyph = cursor_glyph(c, &metrics, &Delta::default(), thickness).unwrap();
+        assert_eq!((glyph.width, glyph.height, glyph.top), (10, 20, 20));
+
+        let alpha: Vec<u8> = glyph.buf.chunks(3).map(|pixel| pixel[0]).collect();
+        alpha.chunks(10).map(|row| row.to_vec()).collect()
+    }
+
+    #[test]
+    fn cursor_thickness() {
+        let beam = glyph(BEAM_CURSOR_CHAR, 0.2);
+        assert!(beam.iter().all(|row| row[..2] == [255, 255] && row[2] == 0));
+
+        let underline = glyph(UNDERLINE_CURSOR_CHAR, 0.3);
+        assert!(underline[..17].iter().all(|row| row.iter().all(|&alpha| alpha == 0)));
+        assert!(underline[17..].iter().all(|row| row.iter().all(|&alpha| alpha == 255)));
+
+        // Lines are never thinner than a pixel
+        let hollow = glyph(BOX_CURSOR_CHAR, 0.);
+        assert_eq!(hollow[0], vec![255; 10]);
+        assert_eq!(hollow[1][..2], [255, 0]);
+        assert_eq!(hollow[1][9], 255);
+    }
+}
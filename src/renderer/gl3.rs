@@ -0,0 +1,457 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! OpenGL 3.3 backend
+//!
+//! Every cell is an instance of a single quad, and glyphs are blended with dual-source blending,
+//! so a batch only needs one draw call for the backgrounds and one for each atlas.
+use std::mem::{self, size_of};
+use std::ptr;
+
+use glutin::dpi::PhysicalSize;
+
+use crate::gl;
+use crate::gl::types::*;
+use crate::renderer::{
+    rect_vertices, Batch, Error, InstanceData, PackedVertex, Rect, RectShaderProgram, Renderer,
+    RenderingPass, ShaderVersion, TextShaderProgram, BATCH_MAX,
+};
+use crate::term::SizeInfo;
+use crate::Rgb;
+
+/// Number of batches which can be in flight before waiting for the GPU
+const INSTANCE_SEGMENTS: usize = 3;
+
+/// Time to wait for the GPU to finish drawing a batch, before warning about it
+const FENCE_TIMEOUT_NS: u64 = 1_000_000_000;
+
+#[derive(Debug)]
+pub struct Gl3Renderer {
+    program: TextShaderProgram,
+    rect_program: RectShaderProgram,
+    vao: GLuint,
+    ebo: GLuint,
+    instances: InstanceBuffer,
+    rect_vao: GLuint,
+    rect_vbo: GLuint,
+}
+
+impl Gl3Renderer {
+    pub fn new(size: PhysicalSize) -> Result<Gl3Renderer, Error> {
+        let program = TextShaderProgram::new(size, ShaderVersion::Glsl3)?;
+        let rect_program = RectShaderProgram::new(ShaderVersion::Glsl3)?;
+
+        let mut vao: GLuint = 0;
+        let mut vbo: GLuint = 0;
+        let mut ebo: GLuint = 0;
+
+        let mut rect_vao: GLuint = 0;
+        let mut rect_vbo: GLuint = 0;
+        let mut rect_ebo: GLuint = 0;
+
+        let instances;
+
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC1_COLOR, gl::ONE_MINUS_SRC1_COLOR);
+            gl::Enable(gl::MULTISAMPLE);
+
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut vbo);
+            gl::GenBuffers(1, &mut ebo);
+            gl::BindVertexArray(vao);
+
+            // ----------------------------
+            // setup vertex position buffer
+            // ----------------------------
+            // Top right, Bottom right, Bottom left, Top left
+            let vertices = [
+                PackedVertex { x: 1.0, y: 1.0 },
+                PackedVertex { x: 1.0, y: 0.0 },
+                PackedVertex { x: 0.0, y: 0.0 },
+                PackedVertex { x: 0.0, y: 1.0 },
+            ];
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+            gl::VertexAttribPointer(
+                0,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                size_of::<PackedVertex>() as i32,
+                ptr::null(),
+            );
+            gl::EnableVertexAttribArray(0);
+
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (size_of::<PackedVertex>() * vertices.len()) as GLsizeiptr,
+                vertices.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+
+            // ---------------------
+            // Set up element buffer
+            // ---------------------
+            let indices: [u32; 6] = [0, 1, 3, 1, 2, 3];
+
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (6 * size_of::<u32>()) as isize,
+                indices.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+
+            // ----------------------------
+            // Setup vertex instance buffer
+            // ----------------------------
+            instances = InstanceBuffer::new();
+            set_instance_attributes(0);
+            for index in 1..7 {
+                gl::EnableVertexAttribArray(index);
+                gl::VertexAttribDivisor(index, 1);
+            }
+
+            // Rectangle setup
+            gl::GenVertexArrays(1, &mut rect_vao);
+            gl::GenBuffers(1, &mut rect_vbo);
+            gl::GenBuffers(1, &mut rect_ebo);
+            gl::BindVertexArray(rect_vao);
+            let indices: [i32; 6] = [
+                0, 1, 3,
+                1, 2, 3,
+            ];
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, rect_ebo);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (size_of::<i32>() * indices.len()) as _,
+                indices.as_ptr() as *const _,
+                gl::STATIC_DRAW
+            );
+
+            // Cleanup
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+        }
+
+        Ok(Gl3Renderer { program, rect_program, vao, ebo, instances, rect_vao, rect_vbo })
+    }
+
+    // Render a rectangle
+    //
+    // This requires the rectangle program to be activated
+    fn render_rect(&mut self, rect: &Rect<f32>, color: Rgb, alpha: f32, size: &SizeInfo) {
+        let vertices = rect_vertices(rect, size);
+
+        unsafe {
+            // Load vertex data into array buffer
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (size_of::<f32>() * vertices.len()) as _,
+                vertices.as_ptr() as *const _,
+                gl::STATIC_DRAW
+            );
+
+            // Color
+            self.rect_program.set_color(color, alpha);
+
+            // Draw the rectangle
+            gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, ptr::null());
+        }
+    }
+}
+
+impl Renderer for Gl3Renderer {
+    fn begin_batches(&mut self, props: &SizeInfo) {
+        unsafe {
+            gl::UseProgram(self.program.id);
+            self.program.set_term_uniforms(props);
+
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.instances.vbo);
+        }
+    }
+
+    fn draw_batch(&mut self, batch: &Batch, active_tex: &mut GLuint) {
+        unsafe {
+            let first = self.instances.upload(batch);
+
+            // Backgrounds don't use the atlas, so they're all drawn at once
+            set_instance_attributes(first);
+            self.program.set_rendering_pass(RenderingPass::Background);
+            gl::DrawElementsInstanced(
+                gl::TRIANGLES,
+                6,
+                gl::UNSIGNED_INT,
+                ptr::null(),
+                batch.len() as GLsizei,
+            );
+            self.program.set_rendering_pass(RenderingPass::Glyphs);
+
+            // Glyphs are drawn with one call per atlas
+            let mut offset = first;
+            for group in batch.groups.iter().filter(|group| !group.instances.is_empty()) {
+                if *active_tex != group.tex {
+                    gl::BindTexture(gl::TEXTURE_2D, group.tex);
+                    *active_tex = group.tex;
+                }
+
+                set_instance_attributes(offset);
+                gl::DrawElementsInstanced(
+                    gl::TRIANGLES,
+                    6,
+                    gl::UNSIGNED_INT,
+                    ptr::null(),
+                    group.instances.len() as GLsizei,
+                );
+                offset += group.instances.len();
+            }
+
+            self.instances.finish();
+        }
+    }
+
+    fn end_batches(&mut self) {
+        unsafe {
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+
+            gl::UseProgram(0);
+        }
+    }
+
+    fn draw_rects(&mut self, props: &SizeInfo, rects: &[(Rect<f32>, Rgb, f32)]) {
+        // Swap to rectangle rendering program
+        unsafe {
+            // Swap program
+            gl::UseProgram(self.rect_program.id);
+
+            // Change blending strategy
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+            // Setup data and buffers
+            gl::BindVertexArray(self.rect_vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.rect_vbo);
+
+            // Position
+            let stride = (size_of::<f32>() * 3) as _;
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, ptr::null());
+            gl::EnableVertexAttribArray(0);
+        }
+
+        for (rect, color, alpha) in rects {
+            self.render_rect(rect, *color, *alpha, props);
+        }
+
+        // Deactivate rectangle program again
+        unsafe {
+            // Reset blending strategy
+            gl::BlendFunc(gl::SRC1_COLOR, gl::ONE_MINUS_SRC1_COLOR);
+
+            // Reset data and buffers
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+
+            // Disable program
+            gl::UseProgram(0);
+        }
+    }
+
+    fn resize(&mut self, width: f32, height: f32, padding_x: f32, padding_y: f32) {
+        unsafe {
+            gl::UseProgram(self.program.id);
+            self.program.update_projection(width, height, padding_x, padding_y);
+            gl::UseProgram(0);
+        }
+    }
+
+    fn reload_shaders(&mut self, size: PhysicalSize) -> Result<(), Error> {
+        let program = TextShaderProgram::new(size, ShaderVersion::Glsl3)?;
+        let rect_program = RectShaderProgram::new(ShaderVersion::Glsl3)?;
+
+        self.program = program;
+        self.rect_program = rect_program;
+        Ok(())
+    }
+
+    fn supports_blit(&self) -> bool {
+        true
+    }
+}
+
+/// Buffer the instances of a batch are uploaded to
+///
+/// With OpenGL 4.4 the buffer is mapped once and batches are copied straight into it, cycling
+/// through segments which are fenced until the GPU has drawn them. Otherwise every batch is
+/// uploaded with `glBufferSubData`.
+#[derive(Debug)]
+struct InstanceBuffer {
+    vbo: GLuint,
+
+    /// Persistently mapped storage for all segments, null without buffer storage support
+    mapped: *mut InstanceData,
+
+    /// Segment the next batch is written to
+    segment: usize,
+
+    /// Fences for the draw calls reading from each segment
+    fences: [GLsync; INSTANCE_SEGMENTS],
+}
+
+impl InstanceBuffer {
+    /// Create the buffer, leaving it bound to `GL_ARRAY_BUFFER`
+    unsafe fn new() -> InstanceBuffer {
+        let mut vbo: GLuint = 0;
+        gl::GenBuffers(1, &mut vbo);
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+        let mut mapped = ptr::null_mut();
+        if supports_buffer_storage() {
+            let size = (INSTANCE_SEGMENTS * BATCH_MAX * size_of::<InstanceData>()) as GLsizeiptr;
+            let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+            gl::BufferStorage(gl::ARRAY_BUFFER, size, ptr::null(), flags);
+            mapped = gl::MapBufferRange(gl::ARRAY_BUFFER, 0, size, flags) as *mut InstanceData;
+
+            // Storage of the buffer can't be changed anymore, so start over with a new one
+            if mapped.is_null() {
+                warn!("Unable to map the instance buffer, falling back to glBufferSubData");
+                gl::DeleteBuffers(1, &vbo);
+                gl::GenBuffers(1, &mut vbo);
+                gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            }
+        }
+
+        if mapped.is_null() {
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (BATCH_MAX * size_of::<InstanceData>()) as isize,
+                ptr::null(),
+                gl::STREAM_DRAW,
+            );
+        }
+
+        InstanceBuffer { vbo, mapped, segment: 0, fences: [ptr::null(); INSTANCE_SEGMENTS] }
+    }
+
+    /// Copy the instances of every group of the batch into the buffer, one group after another
+    ///
+    /// Returns the index of the first instance in the buffer.
+    unsafe fn upload(&mut self, batch: &Batch) -> usize {
+        let groups = batch.groups.iter().filter(|group| !group.instances.is_empty());
+
+        if self.mapped.is_null() {
+            let mut offset = 0;
+            for group in groups {
+                let size = group.instances.len() * size_of::<InstanceData>();
+                gl::BufferSubData(
+                    gl::ARRAY_BUFFER,
+                    offset as isize,
+                    size as isize,
+                    group.instances.as_ptr() as *const _,
+                );
+                offset += size;
+            }
+            return 0;
+        }
+
+        // Wait until the GPU is done with the previous contents of the segment
+        let fence = mem::replace(&mut self.fences[self.segment], ptr::null());
+        if !fence.is_null() {
+            wait_for_fence(fence);
+            gl::DeleteSync(fence);
+        }
+
+        let first = self.segment * BATCH_MAX;
+        let mut target = self.mapped.add(first);
+        for group in groups {
+            ptr::copy_nonoverlapping(group.instances.as_ptr(), target, group.instances.len());
+            target = target.add(group.instances.len());
+        }
+        first
+    }
+
+    /// Fence the segment written by the last upload, once its draw calls have been issued
+    unsafe fn finish(&mut self) {
+        if !self.mapped.is_null() {
+            self.fences[self.segment] = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            self.segment = (self.segment + 1) % INSTANCE_SEGMENTS;
+        }
+    }
+}
+
+/// Block until the GPU has passed `fence`
+///
+/// The segment behind the fence must not be overwritten while the GPU could still read it, so
+/// the wait starts over after a timeout, and a failed wait falls back to finishing all commands.
+unsafe fn wait_for_fence(fence: GLsync) {
+    let mut flags = gl::SYNC_FLUSH_COMMANDS_BIT;
+    loop {
+        match gl::ClientWaitSync(fence, flags, FENCE_TIMEOUT_NS) {
+            gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => return,
+            gl::TIMEOUT_EXPIRED => {
+                warn!(
+                    "GPU took more than {} ms to draw a batch, still waiting",
+                    FENCE_TIMEOUT_NS / 1_000_000,
+                );
+
+                // The commands have been flushed by the first wait
+                flags = 0;
+            },
+            status => {
+                let error = gl::GetError();
+                error!(
+                    "Waiting for the GPU failed (status {:#x}, error {:#x}), finishing commands",
+                    status, error,
+                );
+                gl::Finish();
+                return;
+            },
+        }
+    }
+}
+
+/// Check if buffers can be mapped persistently, which is part of OpenGL 4.4
+unsafe fn supports_buffer_storage() -> bool {
+    let (mut major, mut minor) = (0, 0);
+    gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+    gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    (major, minor) >= (4, 4) && gl::BufferStorage::is_loaded()
+}
+
+/// Point the instance attributes at the instances starting with `first`
+unsafe fn set_instance_attributes(first: usize) {
+    let stride = size_of::<InstanceData>();
+    let attribute = |index: GLuint, size: GLint, offset: usize| {
+        let pointer = (first * stride + offset * size_of::<f32>()) as *const _;
+        gl::VertexAttribPointer(index, size, gl::FLOAT, gl::FALSE, stride as i32, pointer);
+    };
+
+    // coords
+    attribute(1, 2, 0);
+    // glyph offset
+    attribute(2, 4, 2);
+    // uv
+    attribute(3, 4, 6);
+    // color
+    attribute(4, 3, 10);
+    // background color
+    attribute(5, 4, 13);
+    // line attribute
+    attribute(6, 1, 17);
+}
@@ -0,0 +1,299 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! OpenGL ES 2.0 backend
+//!
+//! Without instancing every cell is expanded to four vertices, which carry the properties of the
+//! cell. Dual-source blending isn't available either, so glyphs are drawn twice: first the glyph
+//! mask darkens the background, then the glyph color is added to it.
+use std::mem::size_of;
+use std::ops::Range;
+use std::ptr;
+
+use glutin::dpi::PhysicalSize;
+
+use crate::gl;
+use crate::gl::types::*;
+use crate::renderer::{
+    rect_vertices, Batch, Error, InstanceData, Rect, RectShaderProgram, Renderer, RenderingPass,
+    ShaderVersion, TextShaderProgram,
+};
+use crate::term::SizeInfo;
+use crate::Rgb;
+
+/// Maximum number of quads per draw call, limited by the 16 bit indices
+const QUADS_PER_DRAW: usize = 0x4000;
+
+/// Position of the corners of a quad; top right, bottom right, bottom left, top left
+const CORNERS: [(f32, f32); 4] = [(1., 1.), (1., 0.), (0., 0.), (0., 1.)];
+
+/// Attributes of the text program with their size and offset in floats
+const TEXT_ATTRIBUTES: [(&[u8], GLint, usize); 7] = [
+    (b"position\0", 2, 0),
+    (b"gridCoords\0", 2, 2),
+    (b"glyph\0", 4, 4),
+    (b"uv\0", 4, 8),
+    (b"textColor\0", 3, 12),
+    (b"backgroundColor\0", 4, 15),
+    (b"lineAttribute\0", 1, 19),
+];
+
+/// Corner of a cell's quad
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct Vertex {
+    x: f32,
+    y: f32,
+    instance: InstanceData,
+}
+
+#[derive(Debug)]
+pub struct Gles2Renderer {
+    program: TextShaderProgram,
+    rect_program: RectShaderProgram,
+    vbo: GLuint,
+    ebo: GLuint,
+    rect_vbo: GLuint,
+
+    /// Vertices of the batch which is being drawn
+    vertices: Vec<Vertex>,
+
+    /// Location, size and offset of the text program's attributes
+    attributes: Vec<(GLuint, GLint, usize)>,
+
+    /// Location of the rectangle program's position attribute
+    rect_position: GLuint,
+}
+
+impl Gles2Renderer {
+    pub fn new(size: PhysicalSize) -> Result<Gles2Renderer, Error> {
+        let program = TextShaderProgram::new(size, ShaderVersion::Gles2)?;
+        let rect_program = RectShaderProgram::new(ShaderVersion::Gles2)?;
+
+        let mut vbo: GLuint = 0;
+        let mut ebo: GLuint = 0;
+        let mut rect_vbo: GLuint = 0;
+
+        unsafe {
+            gl::Enable(gl::BLEND);
+
+            gl::GenBuffers(1, &mut vbo);
+            gl::GenBuffers(1, &mut ebo);
+            gl::GenBuffers(1, &mut rect_vbo);
+
+            // Indices for the largest draw call, the vertices are moved for every call
+            let mut indices: Vec<u16> = Vec::with_capacity(QUADS_PER_DRAW * 6);
+            for quad in 0..QUADS_PER_DRAW as u16 {
+                let first = quad * 4;
+                indices.extend_from_slice(&[first, first + 1, first + 3]);
+                indices.extend_from_slice(&[first + 1, first + 2, first + 3]);
+            }
+
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (size_of::<u16>() * indices.len()) as isize,
+                indices.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+        }
+
+        let attributes = text_attributes(&program);
+        let rect_position = attrib_location(rect_program.id, b"aPos\0").unwrap_or(0);
+
+        Ok(Gles2Renderer {
+            program,
+            rect_program,
+            vbo,
+            ebo,
+            rect_vbo,
+            vertices: Vec::new(),
+            attributes,
+            rect_position,
+        })
+    }
+
+    /// Draw the quads of the uploaded vertices, in chunks which fit the index buffer
+    unsafe fn draw_quads(&self, quads: Range<usize>) {
+        let stride = size_of::<Vertex>();
+        let mut start = quads.start;
+        while start < quads.end {
+            let count = (quads.end - start).min(QUADS_PER_DRAW);
+
+            for &(location, size, offset) in &self.attributes {
+                let pointer = (start * 4 * stride + offset * size_of::<f32>()) as *const _;
+                gl::VertexAttribPointer(
+                    location,
+                    size,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride as i32,
+                    pointer,
+                );
+            }
+
+            let len = (count * 6) as GLsizei;
+            gl::DrawElements(gl::TRIANGLES, len, gl::UNSIGNED_SHORT, ptr::null());
+            start += count;
+        }
+    }
+}
+
+impl Renderer for Gles2Renderer {
+    fn begin_batches(&mut self, props: &SizeInfo) {
+        unsafe {
+            gl::UseProgram(self.program.id);
+            self.program.set_term_uniforms(props);
+
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            for &(location, ..) in &self.attributes {
+                gl::EnableVertexAttribArray(location);
+            }
+        }
+    }
+
+    fn draw_batch(&mut self, batch: &Batch, active_tex: &mut GLuint) {
+        self.vertices.clear();
+        for group in &batch.groups {
+            for instance in &group.instances {
+                let vertices = CORNERS.iter().map(|&(x, y)| Vertex { x, y, instance: *instance });
+                self.vertices.extend(vertices);
+            }
+        }
+
+        unsafe {
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (self.vertices.len() * size_of::<Vertex>()) as isize,
+                self.vertices.as_ptr() as *const _,
+                gl::STREAM_DRAW,
+            );
+
+            // Backgrounds don't use the atlas, so they're all drawn at once
+            gl::BlendFunc(gl::ONE, gl::ZERO);
+            self.program.set_rendering_pass(RenderingPass::Background);
+            self.draw_quads(0..batch.len());
+
+            let mut start = 0;
+            for group in batch.groups.iter().filter(|group| !group.instances.is_empty()) {
+                if *active_tex != group.tex {
+                    gl::BindTexture(gl::TEXTURE_2D, group.tex);
+                    *active_tex = group.tex;
+                }
+
+                let quads = start..start + group.instances.len();
+
+                // Darken the background with the glyph mask, then add the masked glyph color
+                gl::BlendFunc(gl::ZERO, gl::ONE_MINUS_SRC_COLOR);
+                self.program.set_rendering_pass(RenderingPass::GlyphMasks);
+                self.draw_quads(quads.clone());
+
+                gl::BlendFunc(gl::ONE, gl::ONE);
+                self.program.set_rendering_pass(RenderingPass::GlyphColors);
+                self.draw_quads(quads);
+
+                start += group.instances.len();
+            }
+        }
+    }
+
+    fn end_batches(&mut self) {
+        unsafe {
+            for &(location, ..) in &self.attributes {
+                gl::DisableVertexAttribArray(location);
+            }
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+
+            gl::UseProgram(0);
+        }
+    }
+
+    fn draw_rects(&mut self, props: &SizeInfo, rects: &[(Rect<f32>, Rgb, f32)]) {
+        unsafe {
+            gl::UseProgram(self.rect_program.id);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.rect_vbo);
+            let position = self.rect_position;
+            let stride = (size_of::<f32>() * 3) as _;
+            gl::VertexAttribPointer(position, 3, gl::FLOAT, gl::FALSE, stride, ptr::null());
+            gl::EnableVertexAttribArray(position);
+
+            for (rect, color, alpha) in rects {
+                let vertices = rect_vertices(rect, props);
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    (size_of::<f32>() * vertices.len()) as _,
+                    vertices.as_ptr() as *const _,
+                    gl::STREAM_DRAW,
+                );
+
+                self.rect_program.set_color(*color, *alpha);
+
+                // The corners are in order around the rectangle
+                gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+            }
+
+            gl::DisableVertexAttribArray(self.rect_position);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::UseProgram(0);
+        }
+    }
+
+    fn resize(&mut self, width: f32, height: f32, padding_x: f32, padding_y: f32) {
+        unsafe {
+            gl::UseProgram(self.program.id);
+            self.program.update_projection(width, height, padding_x, padding_y);
+            gl::UseProgram(0);
+        }
+    }
+
+    fn reload_shaders(&mut self, size: PhysicalSize) -> Result<(), Error> {
+        let program = TextShaderProgram::new(size, ShaderVersion::Gles2)?;
+        let rect_program = RectShaderProgram::new(ShaderVersion::Gles2)?;
+
+        self.attributes = text_attributes(&program);
+        self.rect_position = attrib_location(rect_program.id, b"aPos\0").unwrap_or(0);
+        self.program = program;
+        self.rect_program = rect_program;
+        Ok(())
+    }
+
+    fn supports_blit(&self) -> bool {
+        false
+    }
+}
+
+/// Locations of the text program's attributes, skipping those which aren't used by the shaders
+fn text_attributes(program: &TextShaderProgram) -> Vec<(GLuint, GLint, usize)> {
+    TEXT_ATTRIBUTES
+        .iter()
+        .filter_map(|&(name, size, offset)| {
+            attrib_location(program.id, name).map(|location| (location, size, offset))
+        })
+        .collect()
+}
+
+/// Location of a vertex attribute, `name` has to be null terminated
+fn attrib_location(program: GLuint, name: &[u8]) -> Option<GLuint> {
+    let location = unsafe { gl::GetAttribLocation(program, name.as_ptr() as *const _) };
+    if location < 0 {
+        None
+    } else {
+        Some(location as GLuint)
+    }
+}
@@ -13,6 +13,7 @@
 // limitations under the License.
 use std::collections::HashMap;
 
+use crate::ansi::LineAttribute;
 use crate::renderer::Rect;
 use crate::term::cell::Flags;
 use crate::term::{RenderableCell, SizeInfo};
@@ -74,7 +75,7 @@ impl<'a> Lines<'a> {
 
                     // No change in line
                     if cell.line == start.line
-                        && cell.flags.contains(flag)
+                        && has_line(cell, flag)
                         && cell.fg == start.fg
                         && cell.column == last_cell.column + 1
                     {
@@ -90,14 +91,14 @@ impl<'a> Lines<'a> {
                     ));
 
                     // Start a new line if the flag is present
-                    if cell.flags.contains(flag) {
+                    if has_line(cell, flag) {
                         Some(*cell)
                     } else {
                         None
                     }
                 }
                 // Check for new start of line
-                None => if cell.flags.contains(flag) {
+                None => if has_line(cell, flag) {
                     Some(*cell)
                 } else {
                     None
@@ -109,6 +110,16 @@ impl<'a> Lines<'a> {
     }
 }
 
+/// Check if a line for `flag` should be drawn below `cell`
+///
+/// Double height lines only draw their lines in the bottom half, since that's where the scaled
+/// baseline is. Concealed cells hide their lines together with the text.
+fn has_line(cell: &RenderableCell, flag: Flags) -> bool {
+    cell.flags.contains(flag)
+        && !cell.flags.contains(Flags::HIDDEN)
+        && cell.line_attribute != LineAttribute::DoubleHeightTop
+}
+
 /// Create a rectangle that starts on the left of `start` and ends on the right
 /// of `end`, based on the given flag and size metrics.
 fn create_rect(
@@ -118,8 +129,11 @@ fn create_rect(
     metrics: &Metrics,
     size: &SizeInfo,
 ) -> (Rect<f32>, Rgb) {
-    let start_x = start.column.0 as f32 * size.cell_width;
-    let end_x = (end.column.0 + 1) as f32 * size.cell_width;
+    let scale_x = if start.line_attribute.is_double_width() { 2. } else { 1. };
+    let scale_y = if start.line_attribute == LineAttribute::DoubleHeightBottom { 2. } else { 1. };
+
+    let start_x = start.column.0 as f32 * size.cell_width * scale_x;
+    let end_x = (end.column.0 + 1) as f32 * size.cell_width * scale_x;
     let width = end_x - start_x;
 
     let (position, mut height) = match flag {
@@ -127,12 +141,13 @@ fn create_rect(
         Flags::STRIKEOUT => (metrics.strikeout_position, metrics.strikeout_thickness),
         _ => unimplemented!("Invalid flag for cell line drawing specified"),
     };
+    let position = position * scale_y;
 
     // Make sure lines are always visible
-    height = height.max(1.);
+    height = (height * scale_y).max(1.);
 
     let cell_bottom = (start.line.0 as f32 + 1.) * size.cell_height;
-    let baseline = cell_bottom + metrics.descent;
+    let baseline = cell_bottom + metrics.descent * scale_y;
 
     let mut y = baseline - position - height / 2.;
     let max_y = cell_bottom - height;
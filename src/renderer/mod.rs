@@ -12,10 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use std::collections::HashMap;
+use std::ffi::CStr;
+use std::fmt;
 use std::fs::File;
 use std::hash::BuildHasherDefault;
 use std::io::{self, Read};
-use std::mem::size_of;
+use std::iter;
+use std::ops::Range;
 use std::path::PathBuf;
 use std::ptr;
 use std::sync::mpsc;
@@ -26,16 +29,27 @@ use fnv::FnvHasher;
 use glutin::dpi::PhysicalSize;
 use font::{self, FontDesc, FontKey, GlyphKey, Rasterize, RasterizedGlyph, Rasterizer};
 use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+use unicode_width::UnicodeWidthChar;
 
 use crate::gl::types::*;
 use crate::gl;
-use crate::index::{Column, Line, RangeInclusive};
+use crate::index::{Column, Line, Point, RangeInclusive};
 use crate::Rgb;
-use crate::config::{self, Config, Delta};
+use crate::config::{self, Config, Delta, RendererBackend};
+use crate::ansi::LineAttribute;
 use crate::term::{self, cell, RenderableCell};
+use crate::renderer::gl3::Gl3Renderer;
+use crate::renderer::gles2::Gles2Renderer;
 use crate::renderer::lines::Lines;
+use crate::renderer::shaping::ShapedCell;
+use crate::util;
 
+pub mod builtin_font;
+pub mod cursor;
+pub mod gl3;
+pub mod gles2;
 pub mod lines;
+pub mod shaping;
 
 // Shader paths for live reload
 static TEXT_SHADER_F_PATH: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.f.glsl");
@@ -53,6 +67,24 @@ static RECT_SHADER_F: &'static str =
 static RECT_SHADER_V: &'static str =
     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/rect.v.glsl"));
 
+// GLSL ES shaders for the OpenGL ES 2.0 backend
+static GLES2_TEXT_SHADER_F_PATH: &'static str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/res/gles2/text.f.glsl");
+static GLES2_TEXT_SHADER_V_PATH: &'static str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/res/gles2/text.v.glsl");
+static GLES2_RECT_SHADER_F_PATH: &'static str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/res/gles2/rect.f.glsl");
+static GLES2_RECT_SHADER_V_PATH: &'static str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/res/gles2/rect.v.glsl");
+static GLES2_TEXT_SHADER_F: &'static str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/gles2/text.f.glsl"));
+static GLES2_TEXT_SHADER_V: &'static str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/gles2/text.v.glsl"));
+static GLES2_RECT_SHADER_F: &'static str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/gles2/rect.f.glsl"));
+static GLES2_RECT_SHADER_V: &'static str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/gles2/rect.v.glsl"));
+
 /// `LoadGlyph` allows for copying a rasterized glyph into graphics memory
 pub trait LoadGlyph {
     /// Load the rasterized glyph into GPU memory
@@ -62,6 +94,62 @@ pub trait LoadGlyph {
     ///
     /// This can, for instance, be used to reset the texture Atlas.
     fn clear(&mut self);
+
+    /// Mark a loaded glyph as used, returns `false` if it was evicted since it was loaded
+    fn touch(&mut self, glyph: &Glyph) -> bool;
+}
+
+/// Backend drawing batches and rectangles with a specific version of OpenGL
+///
+/// The backend owns the shader programs and buffers, while atlases, batches and the grid
+/// framebuffer are shared by all of them.
+pub trait Renderer: fmt::Debug {
+    /// Prepare drawing batches, the atlas texture unit is already active
+    fn begin_batches(&mut self, props: &term::SizeInfo);
+
+    /// Draw the backgrounds of all instances of the batch, then their glyphs
+    fn draw_batch(&mut self, batch: &Batch, active_tex: &mut GLuint);
+
+    /// Restore the state changed by `begin_batches`
+    fn end_batches(&mut self);
+
+    /// Draw rectangles in window coordinates, blended with their alpha
+    fn draw_rects(&mut self, props: &term::SizeInfo, rects: &[(Rect<f32>, Rgb, f32)]);
+
+    /// Update the projection for a new window size
+    fn resize(&mut self, width: f32, height: f32, padding_x: f32, padding_y: f32);
+
+    /// Recreate the shader programs, keeping the old ones if that fails
+    fn reload_shaders(&mut self, size: PhysicalSize) -> Result<(), Error>;
+
+    /// Check if framebuffers can be copied to the window, which is needed for partial redraws
+    fn supports_blit(&self) -> bool;
+}
+
+/// GLSL dialect the shaders are written in
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShaderVersion {
+    /// GLSL 3.30, for OpenGL 3.3
+    Glsl3,
+
+    /// GLSL ES 1.00, for OpenGL ES 2.0
+    Gles2,
+}
+
+/// What the text program draws for each instance
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum RenderingPass {
+    /// Glyphs, blended with dual-source blending
+    Glyphs = 0,
+
+    /// Cell backgrounds
+    Background = 1,
+
+    /// Glyph masks, darkening the background where the glyph is drawn
+    GlyphMasks = 2,
+
+    /// Glyph colors, added to the darkened background
+    GlyphColors = 3,
 }
 
 enum Msg {
@@ -120,10 +208,10 @@ pub struct TextShaderProgram {
     /// Cell dimensions (pixels)
     u_cell_dim: GLint,
 
-    /// Background pass flag
+    /// Rendering pass
     ///
-    /// Rendering is split into two passes; 1 for backgrounds, and one for text
-    u_background: GLint,
+    /// Backgrounds are drawn in a separate pass before the text
+    u_rendering_pass: GLint,
 }
 
 /// Rectangle drawing program
@@ -140,6 +228,8 @@ pub struct RectShaderProgram {
 #[derive(Copy, Debug, Clone)]
 pub struct Glyph {
     tex_id: GLuint,
+    page: usize,
+    generation: u32,
     top: f32,
     left: f32,
     width: f32,
@@ -176,7 +266,25 @@ pub struct GlyphCache {
     /// glyph offset
     glyph_offset: Delta<i8>,
 
+    /// Extra spacing per character, which is part of the cell filled by builtin glyphs
+    font_offset: Delta<i8>,
+
+    /// Draw box drawing and block element characters without the font
+    builtin_box_drawing: bool,
+
+    /// Shape runs of cells with the same style, to draw the ligatures of the font
+    ligatures: bool,
+
+    /// Glyphs substituted while shaping, by their index in the font
+    shaped_glyphs: HashMap<(FontKey, u32), Glyph, BuildHasherDefault<FnvHasher>>,
+
+    /// Results of shaping the text of runs per font, `None` for runs drawn without ligatures
+    shaped_runs: HashMap<FontKey, HashMap<String, Option<Vec<ShapedCell>>>>,
+
     metrics: ::font::Metrics,
+
+    /// Thickness of the beam, underline and hollow block cursors, relative to the cell width
+    cursor_thickness: f32,
 }
 
 impl GlyphCache {
@@ -205,9 +313,19 @@ impl GlyphCache {
             bold_key: bold,
             italic_key: italic,
             glyph_offset: *font.glyph_offset(),
+            font_offset: *font.offset(),
+            builtin_box_drawing: font.builtin_box_drawing(),
+            ligatures: font.ligatures(),
+            shaped_glyphs: HashMap::default(),
+            shaped_runs: HashMap::new(),
             metrics,
+            cursor_thickness: 0.15,
         };
 
+        if font.ligatures() && cfg!(any(target_os = "macos", windows)) {
+            warn!("Ligatures are only supported on Linux and BSD");
+        }
+
         cache.load_glyphs_for_font(regular, loader);
         cache.load_glyphs_for_font(bold, loader);
         cache.load_glyphs_for_font(italic, loader);
@@ -281,25 +399,124 @@ impl GlyphCache {
             .expect("metrics load since font is loaded at glyph cache creation")
     }
 
-    pub fn get<'a, L>(&'a mut self, glyph_key: GlyphKey, loader: &mut L) -> &'a Glyph
+    /// Get a glyph, loading it again if it was evicted from the atlas
+    pub fn get<L>(&mut self, glyph_key: GlyphKey, loader: &mut L) -> Glyph
         where L: LoadGlyph
     {
-        let glyph_offset = self.glyph_offset;
-        let rasterizer = &mut self.rasterizer;
-        let metrics = &self.metrics;
-        self.cache
-            .entry(glyph_key)
-            .or_insert_with(|| {
-                let mut rasterized = rasterizer.get_glyph(glyph_key)
-                    .unwrap_or_else(|_| Default::default());
-
-                rasterized.left += i32::from(glyph_offset.x);
-                rasterized.top += i32::from(glyph_offset.y);
-                rasterized.top -= metrics.descent as i32;
-
-                loader.load_glyph(&rasterized)
-        })
+        if let Some(glyph) = self.cache.get(&glyph_key) {
+            if loader.touch(glyph) {
+                return *glyph;
+            }
+        }
+
+        let glyph = self.load(glyph_key, loader);
+        self.cache.insert(glyph_key, glyph);
+        glyph
+    }
+
+    fn load<L: LoadGlyph>(&mut self, glyph_key: GlyphKey, loader: &mut L) -> Glyph {
+        let thickness = self.cursor_thickness;
+        if let Some(rasterized) =
+            cursor::cursor_glyph(glyph_key.c, &self.metrics, &self.font_offset, thickness)
+        {
+            return loader.load_glyph(&rasterized);
+        }
+
+        if self.builtin_box_drawing {
+            let builtin =
+                builtin_font::builtin_glyph(glyph_key.c, &self.metrics, &self.font_offset);
+            if let Some(rasterized) = builtin {
+                return loader.load_glyph(&rasterized);
+            }
+        }
+
+        let mut rasterized = self.rasterizer.get_glyph(glyph_key)
+            .unwrap_or_else(|_| Default::default());
+
+        rasterized.left += i32::from(self.glyph_offset.x);
+        rasterized.top += i32::from(self.glyph_offset.y);
+        rasterized.top -= self.metrics.descent as i32;
+
+        loader.load_glyph(&rasterized)
+    }
+
+    /// Font used for cells with `flags`
+    fn font_key(&self, flags: cell::Flags) -> FontKey {
+        if flags.contains(cell::Flags::BOLD) {
+            self.bold_key
+        } else if flags.contains(cell::Flags::ITALIC) {
+            self.italic_key
+        } else {
+            self.font_key
+        }
+    }
+
+    /// Glyphs for every character of `text`, `None` if it's drawn without ligatures
+    fn shape(&mut self, font_key: FontKey, text: &str) -> Option<Vec<ShapedCell>> {
+        if let Some(cells) = self.shaped_runs.get(&font_key).and_then(|runs| runs.get(text)) {
+            return cells.clone();
+        }
+
+        let cells = self.shape_uncached(font_key, text);
+
+        // Runs change with the terminal contents, so old ones are dropped eventually
+        let runs = self.shaped_runs.entry(font_key).or_insert_with(HashMap::new);
+        if runs.len() >= MAX_SHAPED_RUNS {
+            runs.clear();
+        }
+        runs.insert(text.to_owned(), cells.clone());
+
+        cells
+    }
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    fn shape_uncached(&mut self, font_key: FontKey, text: &str) -> Option<Vec<ShapedCell>> {
+        match self.rasterizer.shape(font_key, text) {
+            Ok(glyphs) => shaping::shaped_cells(&glyphs, text.chars().count()),
+            Err(err) => {
+                error!("Unable to shape text, disabling ligatures: {}", err);
+                self.ligatures = false;
+                None
+            },
+        }
+    }
+
+    #[cfg(any(target_os = "macos", windows))]
+    fn shape_uncached(&mut self, _: FontKey, _: &str) -> Option<Vec<ShapedCell>> {
+        None
+    }
+
+    /// Get the glyph at `index` in the font, substituted for `c` while shaping
+    fn get_shaped<L>(&mut self, font_key: FontKey, index: u32, c: char, loader: &mut L) -> Glyph
+        where L: LoadGlyph
+    {
+        if let Some(glyph) = self.shaped_glyphs.get(&(font_key, index)) {
+            if loader.touch(glyph) {
+                return *glyph;
+            }
+        }
+
+        let size = self.font_size;
+        let mut rasterized = get_glyph_by_index(&mut self.rasterizer, font_key, size, index, c)
+            .unwrap_or_else(|_| Default::default());
+
+        rasterized.left += i32::from(self.glyph_offset.x);
+        rasterized.top += i32::from(self.glyph_offset.y);
+        rasterized.top -= self.metrics.descent as i32;
+
+        let glyph = loader.load_glyph(&rasterized);
+        self.shaped_glyphs.insert((font_key, index), glyph);
+        glyph
+    }
+
+    /// Change the thickness of the cursors, which are redrawn the next time they're used
+    pub fn set_cursor_thickness(&mut self, thickness: f32) {
+        if (thickness - self.cursor_thickness).abs() > ::std::f32::EPSILON {
+            self.cursor_thickness = thickness;
+            self.cache.retain(|key, _| !cursor::is_cursor(key.c));
+        }
     }
+
     pub fn update_font_size<L: LoadGlyph>(
         &mut self,
         font: &config::Font,
@@ -310,6 +527,8 @@ impl GlyphCache {
         // Clear currently cached data in both GL and the registry
         loader.clear();
         self.cache = HashMap::default();
+        self.shaped_glyphs = HashMap::default();
+        self.shaped_runs = HashMap::new();
 
         // Update dpi scaling
         self.rasterizer.update_dpr(dpr as f32);
@@ -328,6 +547,9 @@ impl GlyphCache {
         self.bold_key = bold;
         self.italic_key = italic;
         self.metrics = metrics;
+        self.font_offset = *font.offset();
+        self.builtin_box_drawing = font.builtin_box_drawing();
+        self.ligatures = font.ligatures();
 
         self.load_glyphs_for_font(regular, loader);
         self.load_glyphs_for_font(bold, loader);
@@ -337,7 +559,7 @@ impl GlyphCache {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 #[repr(C)]
 struct InstanceData {
     // coords
@@ -364,39 +586,133 @@ struct InstanceData {
     bg_g: f32,
     bg_b: f32,
     bg_a: f32,
+    // double width/height line
+    line_attribute: f32,
+}
+
+/// Glyph cache with the atlas its glyphs are stored in
+///
+/// Windows whose OpenGL contexts share textures also share their glyphs, as long as they use the
+/// same font.
+pub struct Glyphs {
+    pub cache: GlyphCache,
+    pub atlas: GlyphAtlas,
+
+    /// Font the glyphs are rasterized for, including its size
+    font: config::Font,
+    dpr: f64,
+}
+
+impl Glyphs {
+    pub fn new(
+        renderer: &mut QuadRenderer,
+        font: config::Font,
+        dpr: f64,
+        thin_strokes: bool,
+    ) -> Result<Glyphs, font::Error> {
+        let rasterizer = Rasterizer::new(dpr as f32, thin_strokes)?;
+        let mut atlas = GlyphAtlas::new();
+        let cache = renderer
+            .with_loader(&mut atlas, |mut api| GlyphCache::new(rasterizer, &font, &mut api))?;
+
+        Ok(Glyphs { cache, atlas, font, dpr })
+    }
+
+    /// Whether the glyphs are rasterized for `font` at `dpr`
+    pub fn matches(&self, font: &config::Font, dpr: f64) -> bool {
+        self.font == *font && (self.dpr - dpr).abs() < f64::EPSILON
+    }
+
+    /// Rasterize the glyphs for another font, font size or DPR
+    pub fn update(&mut self, renderer: &mut QuadRenderer, font: config::Font, dpr: f64) {
+        let (cache, atlas) = (&mut self.cache, &mut self.atlas);
+        renderer.with_loader(atlas, |mut api| {
+            let _ = cache.update_font_size(&font, font.size(), dpr, &mut api);
+        });
+
+        self.font = font;
+        self.dpr = dpr;
+    }
 }
 
 #[derive(Debug)]
 pub struct QuadRenderer {
-    program: TextShaderProgram,
-    rect_program: RectShaderProgram,
-    vao: GLuint,
-    ebo: GLuint,
-    vbo_instance: GLuint,
-    rect_vao: GLuint,
-    rect_vbo: GLuint,
-    atlas: Vec<Atlas>,
-    current_atlas: usize,
+    backend: Box<dyn Renderer>,
     active_tex: GLuint,
     batch: Batch,
     rx: mpsc::Receiver<Msg>,
+
+    /// Grid of the previous frame, so only damaged lines have to be redrawn
+    grid_framebuffer: Option<GridFramebuffer>,
+
+    /// Disabled when the framebuffer can't be created, the grid is then always redrawn
+    partial_redraws: bool,
+}
+
+/// Offscreen framebuffer the size of the window, which is copied to the window after drawing
+#[derive(Debug)]
+struct GridFramebuffer {
+    fbo: GLuint,
+    rbo: GLuint,
+    width: i32,
+    height: i32,
+}
+
+impl GridFramebuffer {
+    fn new(width: i32, height: i32) -> Option<GridFramebuffer> {
+        let mut fbo: GLuint = 0;
+        let mut rbo: GLuint = 0;
+        let status = unsafe {
+            gl::GenRenderbuffers(1, &mut rbo);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, rbo);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, width, height);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                rbo,
+            );
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            status
+        };
+
+        let framebuffer = GridFramebuffer { fbo, rbo, width, height };
+        if status == gl::FRAMEBUFFER_COMPLETE {
+            Some(framebuffer)
+        } else {
+            warn!("Unable to create grid framebuffer ({:#x}), redrawing every line", status);
+            None
+        }
+    }
+}
+
+impl Drop for GridFramebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteRenderbuffers(1, &self.rbo);
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct RenderApi<'a> {
     active_tex: &'a mut GLuint,
     batch: &'a mut Batch,
-    atlas: &'a mut Vec<Atlas>,
-    current_atlas: &'a mut usize,
-    program: &'a mut TextShaderProgram,
+    backend: &'a mut dyn Renderer,
+    atlas: &'a mut GlyphAtlas,
     config: &'a Config,
 }
 
 #[derive(Debug)]
 pub struct LoaderApi<'a> {
     active_tex: &'a mut GLuint,
-    atlas: &'a mut Vec<Atlas>,
-    current_atlas: &'a mut usize,
+    atlas: &'a mut GlyphAtlas,
 }
 
 #[derive(Debug)]
@@ -405,27 +721,41 @@ pub struct PackedVertex {
     y: f32,
 }
 
-#[derive(Debug, Default)]
-pub struct Batch {
+/// Instances of a batch which use the same atlas texture
+#[derive(Debug)]
+struct BatchGroup {
     tex: GLuint,
     instances: Vec<InstanceData>,
 }
 
+/// Instances which are drawn together, grouped by their atlas texture
+///
+/// All backgrounds of a batch are drawn with a single call and the glyphs with one call per
+/// atlas, so switching between atlases doesn't split the batch.
+#[derive(Debug, Default)]
+pub struct Batch {
+    groups: Vec<BatchGroup>,
+    len: usize,
+}
+
 impl Batch {
     #[inline]
     pub fn new() -> Batch {
-        Batch {
-            tex: 0,
-            instances: Vec::with_capacity(BATCH_MAX),
-        }
+        Default::default()
     }
 
     pub fn add_item(&mut self, cell: &RenderableCell, glyph: &Glyph) {
-        if self.is_empty() {
-            self.tex = glyph.tex_id;
-        }
+        // Groups are kept when the batch is cleared, since the atlas textures stay the same
+        let index = match self.groups.iter().rposition(|group| group.tex == glyph.tex_id) {
+            Some(index) => index,
+            None => {
+                self.groups.push(BatchGroup { tex: glyph.tex_id, instances: Vec::new() });
+                self.groups.len() - 1
+            },
+        };
+        let group = &mut self.groups[index];
 
-        self.instances.push(InstanceData {
+        group.instances.push(InstanceData {
             col: cell.column.0 as f32,
             row: cell.line.0 as f32,
 
@@ -447,7 +777,15 @@ impl Batch {
             bg_g: f32::from(cell.bg.g),
             bg_b: f32::from(cell.bg.b),
             bg_a: cell.bg_alpha,
+
+            line_attribute: match cell.line_attribute {
+                LineAttribute::SingleWidth => 0.,
+                LineAttribute::DoubleWidth => 1.,
+                LineAttribute::DoubleHeightTop => 2.,
+                LineAttribute::DoubleHeightBottom => 3.,
+            },
         });
+        self.len += 1;
     }
 
     #[inline]
@@ -457,7 +795,7 @@ impl Batch {
 
     #[inline]
     pub fn len(&self) -> usize {
-        self.instances.len()
+        self.len
     }
 
     #[inline]
@@ -470,180 +808,48 @@ impl Batch {
         self.len() == 0
     }
 
+    /// Number of draw calls needed for the glyphs, one for every atlas in use
     #[inline]
-    pub fn size(&self) -> usize {
-        self.len() * size_of::<InstanceData>()
+    pub fn atlas_count(&self) -> usize {
+        self.groups.iter().filter(|group| !group.instances.is_empty()).count()
     }
 
     pub fn clear(&mut self) {
-        self.tex = 0;
-        self.instances.clear();
+        for group in &mut self.groups {
+            group.instances.clear();
+        }
+        self.len = 0;
     }
 }
 
 /// Maximum items to be drawn in a batch.
 const BATCH_MAX: usize = 0x1_0000;
-const ATLAS_SIZE: i32 = 1024;
-
-impl QuadRenderer {
-    // TODO should probably hand this a transform instead of width/height
-    pub fn new(size: PhysicalSize) -> Result<QuadRenderer, Error> {
-        let program = TextShaderProgram::new(size)?;
-        let rect_program = RectShaderProgram::new()?;
 
-        let mut vao: GLuint = 0;
-        let mut vbo: GLuint = 0;
-        let mut ebo: GLuint = 0;
-
-        let mut vbo_instance: GLuint = 0;
-
-        let mut rect_vao: GLuint = 0;
-        let mut rect_vbo: GLuint = 0;
-        let mut rect_ebo: GLuint = 0;
-
-        unsafe {
-            gl::Enable(gl::BLEND);
-            gl::BlendFunc(gl::SRC1_COLOR, gl::ONE_MINUS_SRC1_COLOR);
-            gl::Enable(gl::MULTISAMPLE);
-
-            gl::GenVertexArrays(1, &mut vao);
-            gl::GenBuffers(1, &mut vbo);
-            gl::GenBuffers(1, &mut ebo);
-            gl::GenBuffers(1, &mut vbo_instance);
-            gl::BindVertexArray(vao);
-
-            // ----------------------------
-            // setup vertex position buffer
-            // ----------------------------
-            // Top right, Bottom right, Bottom left, Top left
-            let vertices = [
-                PackedVertex { x: 1.0, y: 1.0 },
-                PackedVertex { x: 1.0, y: 0.0 },
-                PackedVertex { x: 0.0, y: 0.0 },
-                PackedVertex { x: 0.0, y: 1.0 },
-            ];
-
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-
-            gl::VertexAttribPointer(
-                0,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<PackedVertex>() as i32,
-                ptr::null(),
-            );
-            gl::EnableVertexAttribArray(0);
+const ATLAS_SIZE: i32 = 1024;
 
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (size_of::<PackedVertex>() * vertices.len()) as GLsizeiptr,
-                vertices.as_ptr() as *const _,
-                gl::STATIC_DRAW,
-            );
+/// Maximum number of atlas pages, the least recently used one is evicted afterwards
+const MAX_ATLAS_PAGES: usize = 8;
 
-            // ---------------------
-            // Set up element buffer
-            // ---------------------
-            let indices: [u32; 6] = [0, 1, 3, 1, 2, 3];
-
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
-            gl::BufferData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                (6 * size_of::<u32>()) as isize,
-                indices.as_ptr() as *const _,
-                gl::STATIC_DRAW,
-            );
-
-            // ----------------------------
-            // Setup vertex instance buffer
-            // ----------------------------
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo_instance);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (BATCH_MAX * size_of::<InstanceData>()) as isize,
-                ptr::null(),
-                gl::STREAM_DRAW,
-            );
-            // coords
-            gl::VertexAttribPointer(
-                1,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                ptr::null(),
-            );
-            gl::EnableVertexAttribArray(1);
-            gl::VertexAttribDivisor(1, 1);
-            // glyphoffset
-            gl::VertexAttribPointer(
-                2,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                (2 * size_of::<f32>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(2);
-            gl::VertexAttribDivisor(2, 1);
-            // uv
-            gl::VertexAttribPointer(
-                3,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                (6 * size_of::<f32>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(3);
-            gl::VertexAttribDivisor(3, 1);
-            // color
-            gl::VertexAttribPointer(
-                4,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                (10 * size_of::<f32>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(4);
-            gl::VertexAttribDivisor(4, 1);
-            // color
-            gl::VertexAttribPointer(
-                5,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                size_of::<InstanceData>() as i32,
-                (13 * size_of::<f32>()) as *const _,
-            );
-            gl::EnableVertexAttribArray(5);
-            gl::VertexAttribDivisor(5, 1);
-
-            // Rectangle setup
-            gl::GenVertexArrays(1, &mut rect_vao);
-            gl::GenBuffers(1, &mut rect_vbo);
-            gl::GenBuffers(1, &mut rect_ebo);
-            gl::BindVertexArray(rect_vao);
-            let indices: [i32; 6] = [
-                0, 1, 3,
-                1, 2, 3,
-            ];
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, rect_ebo);
-            gl::BufferData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                (size_of::<i32>() * indices.len()) as _,
-                indices.as_ptr() as *const _,
-                gl::STATIC_DRAW
-            );
-
-            // Cleanup
-            gl::BindVertexArray(0);
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
-        }
+/// Number of shaped runs which are cached before the cache is cleared
+const MAX_SHAPED_RUNS: usize = 4096;
 
+impl QuadRenderer {
+    // TODO should probably hand this a transform instead of width/height
+    pub fn new(size: PhysicalSize, backend: RendererBackend) -> Result<QuadRenderer, Error> {
+        let backend: Box<dyn Renderer> = match backend {
+            RendererBackend::Gl3 => Box::new(Gl3Renderer::new(size)?),
+            RendererBackend::Gles2 => Box::new(Gles2Renderer::new(size)?),
+            RendererBackend::Auto | RendererBackend::Software if is_gles_context() => {
+                Box::new(Gles2Renderer::new(size)?)
+            },
+            RendererBackend::Auto | RendererBackend::Software => match Gl3Renderer::new(size) {
+                Ok(renderer) => Box::new(renderer),
+                Err(err) => {
+                    warn!("Unable to use OpenGL 3.3, falling back to OpenGL ES 2.0: {}", err);
+                    Box::new(Gles2Renderer::new(size)?)
+                },
+            },
+        };
         let (msg_tx, msg_rx) = mpsc::channel();
 
         if cfg!(feature = "live-shader-reload") {
@@ -675,25 +881,106 @@ impl QuadRenderer {
             });
         }
 
-        let mut renderer = QuadRenderer {
-            program,
-            rect_program,
-            vao,
-            ebo,
-            vbo_instance,
-            rect_vao,
-            rect_vbo,
-            atlas: Vec::new(),
-            current_atlas: 0,
+        let partial_redraws = backend.supports_blit();
+        Ok(QuadRenderer {
+            backend,
             active_tex: 0,
             batch: Batch::new(),
             rx: msg_rx,
+            grid_framebuffer: None,
+            partial_redraws,
+        })
+    }
+
+    /// Start drawing the grid into the framebuffer which is kept between frames
+    ///
+    /// Only the `damaged` lines are cleared, or everything if they're `None`. Returns the lines
+    /// which have to be drawn, which are all of them when the framebuffer is new.
+    pub fn begin_grid(
+        &mut self,
+        props: &term::SizeInfo,
+        damaged: Option<Vec<Range<Line>>>,
+        color: Rgb,
+        alpha: f32,
+    ) -> Option<Vec<Range<Line>>> {
+        let (width, height) = (props.width as i32, props.height as i32);
+        let reused = match self.grid_framebuffer {
+            Some(ref framebuffer) => framebuffer.width == width && framebuffer.height == height,
+            None => false,
         };
+        if !reused && self.partial_redraws {
+            self.grid_framebuffer = None;
+            self.grid_framebuffer = GridFramebuffer::new(width, height);
+            self.partial_redraws = self.grid_framebuffer.is_some();
+        }
+        let damaged = damaged.filter(|_| reused);
+
+        unsafe {
+            let fbo = self.grid_framebuffer.as_ref().map_or(0, |framebuffer| framebuffer.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            set_clear_color(color, alpha);
+        }
+
+        match damaged {
+            Some(ref damaged) => {
+                for lines in damaged {
+                    self.scissor_lines(props, Some(lines.clone()));
+                    unsafe { gl::Clear(gl::COLOR_BUFFER_BIT) };
+                }
+                self.scissor_lines(props, None);
+            },
+            None => unsafe { gl::Clear(gl::COLOR_BUFFER_BIT) },
+        }
 
-        let atlas = Atlas::new(ATLAS_SIZE);
-        renderer.atlas.push(atlas);
+        damaged
+    }
 
-        Ok(renderer)
+    /// Restrict drawing to the full width of `lines`, or remove the restriction
+    pub fn scissor_lines(&self, props: &term::SizeInfo, lines: Option<Range<Line>>) {
+        unsafe {
+            match lines {
+                Some(lines) => {
+                    let top = (props.padding_y + lines.start.0 as f32 * props.cell_height).floor();
+                    let bottom = (props.padding_y + lines.end.0 as f32 * props.cell_height).ceil();
+                    gl::Enable(gl::SCISSOR_TEST);
+                    gl::Scissor(
+                        0,
+                        props.height as i32 - bottom as i32,
+                        props.width as i32,
+                        (bottom - top) as i32,
+                    );
+                },
+                None => gl::Disable(gl::SCISSOR_TEST),
+            }
+        }
+    }
+
+    /// Copy the grid to the window, everything drawn afterwards is drawn over it
+    pub fn finish_grid(&mut self, props: &term::SizeInfo) {
+        let framebuffer = match self.grid_framebuffer {
+            Some(ref framebuffer) => framebuffer,
+            None => return,
+        };
+
+        let (width, height) = (props.width as i32, props.height as i32);
+        unsafe {
+            gl::Disable(gl::SCISSOR_TEST);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer.fbo);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+            gl::BlitFramebuffer(
+                0,
+                0,
+                width,
+                height,
+                0,
+                0,
+                width,
+                height,
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
     }
 
     // Draw all rectangles simultaneously to prevent excessive program swaps
@@ -703,54 +990,36 @@ impl QuadRenderer {
         props: &term::SizeInfo,
         visual_bell_intensity: f64,
         cell_line_rects: Lines,
+        overlay_rects: Vec<(Rect<f32>, Rgb, f32)>,
     ) {
-        // Swap to rectangle rendering program
+        // Remove padding from viewport
         unsafe {
-            // Swap program
-            gl::UseProgram(self.rect_program.id);
-
-            // Remove padding from viewport
             gl::Viewport(0, 0, props.width as i32, props.height as i32);
-
-            // Change blending strategy
-            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
-
-            // Setup data and buffers
-            gl::BindVertexArray(self.rect_vao);
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.rect_vbo);
-
-            // Position
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, (size_of::<f32>() * 3) as _, ptr::null());
-            gl::EnableVertexAttribArray(0);
         }
 
+        // Draw translucent overlays over individual cells
+        let mut rects = overlay_rects;
+
         // Draw visual bell
         let color = config.visual_bell().color();
         let rect = Rect::new(0., 0., props.width, props.height);
-        self.render_rect(&rect, color, visual_bell_intensity as f32, props);
+        rects.push((rect, color, visual_bell_intensity as f32));
 
         // Draw underlines and strikeouts
         for cell_line_rect in cell_line_rects.rects() {
-            self.render_rect(&cell_line_rect.0, cell_line_rect.1, 255., props);
+            rects.push((cell_line_rect.0, cell_line_rect.1, 255.));
         }
 
-        // Deactivate rectangle program again
-        unsafe {
-            // Reset blending strategy
-            gl::BlendFunc(gl::SRC1_COLOR, gl::ONE_MINUS_SRC1_COLOR);
-
-            // Reset data and buffers
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::BindVertexArray(0);
+        // Do nothing for fully transparent rectangles
+        rects.retain(|&(_, _, alpha)| alpha != 0.);
+        self.backend.draw_rects(props, &rects);
 
+        unsafe {
             let padding_x = props.padding_x as i32;
             let padding_y = props.padding_y as i32;
             let width = props.width as i32;
             let height = props.height as i32;
             gl::Viewport(padding_x, padding_y, width - 2 * padding_x, height - 2 * padding_y);
-
-            // Disable program
-            gl::UseProgram(0);
         }
     }
 
@@ -758,6 +1027,7 @@ impl QuadRenderer {
         &mut self,
         config: &Config,
         props: &term::SizeInfo,
+        atlas: &mut GlyphAtlas,
         func: F,
     ) -> T
     where
@@ -771,36 +1041,24 @@ impl QuadRenderer {
         while let Ok(_) = self.rx.try_recv() {}
 
         unsafe {
-            gl::UseProgram(self.program.id);
-            self.program.set_term_uniforms(props);
-
-            gl::BindVertexArray(self.vao);
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo_instance);
             gl::ActiveTexture(gl::TEXTURE0);
         }
+        self.backend.begin_batches(props);
 
         let res = func(RenderApi {
             active_tex: &mut self.active_tex,
             batch: &mut self.batch,
-            atlas: &mut self.atlas,
-            current_atlas: &mut self.current_atlas,
-            program: &mut self.program,
+            backend: &mut *self.backend,
+            atlas,
             config,
         });
 
-        unsafe {
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::BindVertexArray(0);
-
-            gl::UseProgram(0);
-        }
+        self.backend.end_batches();
 
         res
     }
 
-    pub fn with_loader<F, T>(&mut self, func: F) -> T
+    pub fn with_loader<F, T>(&mut self, atlas: &mut GlyphAtlas, func: F) -> T
     where
         F: FnOnce(LoaderApi<'_>) -> T,
     {
@@ -810,93 +1068,63 @@ impl QuadRenderer {
 
         func(LoaderApi {
             active_tex: &mut self.active_tex,
-            atlas: &mut self.atlas,
-            current_atlas: &mut self.current_atlas,
+            atlas,
         })
     }
 
     pub fn reload_shaders(&mut self, size: PhysicalSize) {
         warn!("Reloading shaders...");
-        let result = (TextShaderProgram::new(size), RectShaderProgram::new());
-        let (program, rect_program) = match result {
-            (Ok(program), Ok(rect_program)) => {
-                info!("... successfully reloaded shaders");
-                (program, rect_program)
-            }
-            (Err(err), _) | (_, Err(err)) => {
+        match self.backend.reload_shaders(size) {
+            Ok(()) => info!("... successfully reloaded shaders"),
+            Err(err) => {
                 error!("{}", err);
                 return;
             }
-        };
+        }
 
         self.active_tex = 0;
-        self.program = program;
-        self.rect_program = rect_program;
     }
 
     pub fn resize(&mut self, size: PhysicalSize, padding_x: f32, padding_y: f32) {
         let (width, height): (u32, u32) = size.into();
 
+        let width = width as i32;
+        let height = height as i32;
+        let padding_x = padding_x as i32;
+        let padding_y = padding_y as i32;
+
         // viewport
         unsafe {
-            let width = width as i32;
-            let height = height as i32;
-            let padding_x = padding_x as i32;
-            let padding_y = padding_y as i32;
             gl::Viewport(padding_x, padding_y, width - 2 * padding_x, height - 2 * padding_y);
-
-            // update projection
-            gl::UseProgram(self.program.id);
-            self.program.update_projection(
-                width as f32,
-                height as f32,
-                padding_x as f32,
-                padding_y as f32,
-            );
-            gl::UseProgram(0);
         }
-    }
 
-    // Render a rectangle
-    //
-    // This requires the rectangle program to be activated
-    fn render_rect(&mut self, rect: &Rect<f32>, color: Rgb, alpha: f32, size: &term::SizeInfo) {
-        // Do nothing when alpha is fully transparent
-        if alpha == 0. {
-            return;
-        }
-
-        // Calculate rectangle position
-        let center_x = size.width / 2.;
-        let center_y = size.height / 2.;
-        let x = (rect.x - center_x) / center_x;
-        let y = -(rect.y - center_y) / center_y;
-        let width = rect.width / center_x;
-        let height = rect.height / center_y;
+        // update projection
+        self.backend.resize(width as f32, height as f32, padding_x as f32, padding_y as f32);
+    }
 
+    /// Read the RGB pixels of the frame which is being drawn, row by row from the top
+    pub fn read_pixels(&self, width: usize, height: usize) -> Vec<u8> {
+        // RGBA is the only format OpenGL ES is guaranteed to support
+        let mut pixels = vec![0u8; width * height * 4];
         unsafe {
-            // Setup vertices
-            let vertices: [f32; 12] = [
-                x + width, y         , 0.0,
-                x + width, y - height, 0.0,
-                x        , y - height, 0.0,
-                x        , y         , 0.0,
-            ];
-
-            // Load vertex data into array buffer
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (size_of::<f32>() * vertices.len()) as _,
-                vertices.as_ptr() as *const _,
-                gl::STATIC_DRAW
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
             );
-
-            // Color
-            self.rect_program.set_color(color, alpha);
-
-            // Draw the rectangle
-            gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, ptr::null());
         }
+
+        // OpenGL starts at the bottom row
+        pixels
+            .chunks(width * 4)
+            .rev()
+            .flat_map(|row| row.chunks(4).flat_map(|pixel| pixel[..3].iter().cloned()))
+            .collect()
     }
 }
 
@@ -914,88 +1142,100 @@ impl<T> Rect<T> {
     }
 }
 
+/// Corners of a rectangle in normalized device coordinates
+///
+/// The corners are top right, bottom right, bottom left and top left.
+fn rect_vertices(rect: &Rect<f32>, size: &term::SizeInfo) -> [f32; 12] {
+    // Calculate rectangle position
+    let center_x = size.width / 2.;
+    let center_y = size.height / 2.;
+    let x = (rect.x - center_x) / center_x;
+    let y = -(rect.y - center_y) / center_y;
+    let width = rect.width / center_x;
+    let height = rect.height / center_y;
+
+    [
+        x + width, y         , 0.0,
+        x + width, y - height, 0.0,
+        x        , y - height, 0.0,
+        x        , y         , 0.0,
+    ]
+}
+
 impl<'a> RenderApi<'a> {
-    pub fn clear(&self, color: Rgb) {
-        let alpha = self.config.background_opacity().get();
+    /// Clear the window with the background color, which is premultiplied with `alpha`
+    pub fn clear(&self, color: Rgb, alpha: f32) {
         unsafe {
-            gl::ClearColor(
-                (f32::from(color.r) / 255.0).min(1.0) * alpha,
-                (f32::from(color.g) / 255.0).min(1.0) * alpha,
-                (f32::from(color.b) / 255.0).min(1.0) * alpha,
-                alpha,
-            );
+            set_clear_color(color, alpha);
             gl::Clear(gl::COLOR_BUFFER_BIT);
         }
     }
 
     fn render_batch(&mut self) {
-        unsafe {
-            gl::BufferSubData(
-                gl::ARRAY_BUFFER,
-                0,
-                self.batch.size() as isize,
-                self.batch.instances.as_ptr() as *const _,
-            );
-        }
-
-        // Bind texture if necessary
-        if *self.active_tex != self.batch.tex {
-            unsafe {
-                gl::BindTexture(gl::TEXTURE_2D, self.batch.tex);
-            }
-            *self.active_tex = self.batch.tex;
-        }
-
-        unsafe {
-            self.program.set_background_pass(true);
-            gl::DrawElementsInstanced(
-                gl::TRIANGLES,
-                6,
-                gl::UNSIGNED_INT,
-                ptr::null(),
-                self.batch.len() as GLsizei,
-            );
-            self.program.set_background_pass(false);
-            gl::DrawElementsInstanced(
-                gl::TRIANGLES,
-                6,
-                gl::UNSIGNED_INT,
-                ptr::null(),
-                self.batch.len() as GLsizei,
-            );
-        }
-
+        self.backend.draw_batch(self.batch, self.active_tex);
         self.batch.clear();
     }
 
     /// Render a string in a variable location. Used for printing the render timer, warnings and
     /// errors.
+    ///
+    /// Strings wider than `columns` are shortened with an ellipsis in the middle.
     pub fn render_string(
         &mut self,
         string: &str,
         line: Line,
+        columns: Column,
         glyph_cache: &mut GlyphCache,
         color: Rgb
     ) {
-        let col = Column(0);
-
-        let cells = string
-            .chars()
-            .enumerate()
-            .map(|(i, c)| RenderableCell {
-                line,
-                column: col + i,
-                chars: {
-                    let mut chars = [' '; cell::MAX_ZEROWIDTH_CHARS + 1];
-                    chars[0] = c;
-                    chars
-                },
-                bg: color,
-                fg: Rgb { r: 0, g: 0, b: 0 },
-                flags: cell::Flags::empty(),
-                bg_alpha: 1.0,
-            })
-            .collect::<Vec<_>>();
+        self.render_string_at(string, Point::new(line, Column(0)), columns, glyph_cache, color);
+    }
+
+    /// Render a string starting at `start`, shortened to `columns` like `render_string`
+    pub fn render_string_at(
+        &mut self,
+        string: &str,
+        start: Point,
+        columns: Column,
+        glyph_cache: &mut GlyphCache,
+        color: Rgb
+    ) {
+        let string = util::truncate_middle(string, columns.0);
+
+        let mut cells: Vec<RenderableCell> = Vec::new();
+        let line = start.line;
+        let mut column = start.col;
+        for c in string.chars() {
+            let width = c.width().unwrap_or(0);
+
+            // Zero width characters are drawn on top of the previous character
+            if width == 0 {
+                let slot = cells
+                    .last_mut()
+                    .and_then(|cell| cell.chars[1..].iter_mut().find(|slot| **slot == ' '));
+                if let Some(slot) = slot {
+                    *slot = c;
+                }
+                continue;
+            }
+
+            // Wide characters are followed by an empty cell, like in the grid
+            for (i, c) in iter::once(c).chain(iter::repeat(' ')).take(width).enumerate() {
+                let mut chars = [' '; cell::MAX_ZEROWIDTH_CHARS + 1];
+                chars[0] = c;
+                cells.push(RenderableCell {
+                    line,
+                    column: column + i,
+                    chars,
+                    bg: color,
+                    fg: Rgb { r: 0, g: 0, b: 0 },
+                    flags: cell::Flags::empty(),
+                    bg_alpha: 1.0,
+                    line_attribute: LineAttribute::SingleWidth,
+                });
+            }
+            column += width;
+        }
 
         for cell in cells {
             self.render_cell(cell, glyph_cache);
@@ -1004,11 +1244,6 @@ impl<'a> RenderApi<'a> {
 
     #[inline]
     fn add_render_item(&mut self, cell: &RenderableCell, glyph: &Glyph) {
-        // Flush batch if tex changing
-        if !self.batch.is_empty() && self.batch.tex != glyph.tex_id {
-            self.render_batch();
-        }
-
         self.batch.add_item(cell, glyph);
 
         // Render batch and clear if it's full
@@ -1017,17 +1252,124 @@ impl<'a> RenderApi<'a> {
         }
     }
 
-    pub fn render_cell(&mut self, cell: RenderableCell, glyph_cache: &mut GlyphCache) {
-        // Get font key for cell
-        // FIXME this is super inefficient.
-        let font_key = if cell.flags.contains(cell::Flags::BOLD) {
-            glyph_cache.bold_key
-        } else if cell.flags.contains(cell::Flags::ITALIC) {
-            glyph_cache.italic_key
+    /// Render a single character on top of the grid, used for indicators like autoscrolling
+    pub fn render_overlay_char(
+        &mut self,
+        c: char,
+        point: Point,
+        glyph_cache: &mut GlyphCache,
+        fg: Rgb,
+        bg: Rgb,
+    ) {
+        let mut chars = [' '; cell::MAX_ZEROWIDTH_CHARS + 1];
+        chars[0] = c;
+
+        self.render_cell(
+            RenderableCell {
+                line: point.line,
+                column: point.col,
+                chars,
+                fg,
+                bg,
+                flags: cell::Flags::empty(),
+                bg_alpha: 1.0,
+                line_attribute: LineAttribute::SingleWidth,
+            },
+            glyph_cache,
+        );
+    }
+
+    /// Render a character on top of the grid, keeping the cell below it visible
+    pub fn render_transparent_char(
+        &mut self,
+        c: char,
+        point: Point,
+        glyph_cache: &mut GlyphCache,
+        fg: Rgb,
+    ) {
+        let mut chars = [' '; cell::MAX_ZEROWIDTH_CHARS + 1];
+        chars[0] = c;
+
+        self.render_cell(
+            RenderableCell {
+                line: point.line,
+                column: point.col,
+                chars,
+                fg,
+                bg: fg,
+                flags: cell::Flags::empty(),
+                bg_alpha: 0.0,
+                line_attribute: LineAttribute::SingleWidth,
+            },
+            glyph_cache,
+        );
+    }
+
+    /// Render cells, drawing the ligatures of the font when they're enabled
+    pub fn render_cells<I>(&mut self, cells: I, glyph_cache: &mut GlyphCache)
+        where I: IntoIterator<Item = RenderableCell>
+    {
+        let mut run: Vec<RenderableCell> = Vec::new();
+        for cell in cells {
+            if !glyph_cache.ligatures {
+                self.render_cell(cell, glyph_cache);
+                continue;
+            }
+
+            let continues = run.last().map_or(false, |last| shaping::continues_run(last, &cell));
+            if !continues {
+                self.render_run(&mut run, glyph_cache);
+            }
+            run.push(cell);
+        }
+
+        self.render_run(&mut run, glyph_cache);
+    }
+
+    /// Render and clear a run of cells with the same style
+    fn render_run(&mut self, run: &mut Vec<RenderableCell>, glyph_cache: &mut GlyphCache) {
+        let font_key = match run.first() {
+            Some(cell) => glyph_cache.font_key(cell.flags),
+            None => return,
+        };
+
+        // Ligatures need at least two characters
+        let shaped = if run.len() > 1 {
+            let text: String = run.iter().map(|cell| cell.chars[0]).collect();
+            glyph_cache.shape(font_key, &text)
         } else {
-            glyph_cache.font_key
+            None
+        };
+
+        let shaped = match shaped {
+            Some(shaped) => shaped,
+            None => {
+                for cell in run.drain(..) {
+                    self.render_cell(cell, glyph_cache);
+                }
+                return;
+            },
         };
 
+        for (mut cell, shaped_cell) in run.drain(..).zip(shaped) {
+            match shaped_cell {
+                ShapedCell::Unchanged => self.render_cell(cell, glyph_cache),
+                ShapedCell::Glyph(index) => {
+                    let glyph = glyph_cache.get_shaped(font_key, index, cell.chars[0], self);
+                    self.add_render_item(&cell, &glyph);
+                },
+                ShapedCell::Covered => {
+                    // Only the background is drawn, the ligature covers the character
+                    cell.chars = [' '; cell::MAX_ZEROWIDTH_CHARS + 1];
+                    self.render_cell(cell, glyph_cache);
+                },
+            }
+        }
+    }
+
+    pub fn render_cell(&mut self, cell: RenderableCell, glyph_cache: &mut GlyphCache) {
+        let font_key = glyph_cache.font_key(cell.flags);
+
         // Don't render text of HIDDEN cells
         let mut chars = if cell.flags.contains(cell::Flags::HIDDEN) {
             [' '; cell::MAX_ZEROWIDTH_CHARS + 1]
@@ -1048,89 +1390,114 @@ impl<'a> RenderApi<'a> {
 
         // Add cell to batch
         let glyph = glyph_cache.get(glyph_key, self);
-        self.add_render_item(&cell, glyph);
+        self.add_render_item(&cell, &glyph);
 
-        // Render zero-width characters
-        for c in (&chars[1..]).iter().filter(|c| **c != ' ') {
+        // Render the rest of the grapheme cluster
+        for c in (&chars[1..]).iter().filter(|c| **c != ' ' && !is_format_char(**c)) {
             glyph_key.c = *c;
-            let mut glyph = *glyph_cache.get(glyph_key, self);
+            let mut glyph = glyph_cache.get(glyph_key, self);
 
             // The metrics of zero-width characters are based on rendering
             // the character after the current cell, with the anchor at the
             // right side of the preceding character. Since we render the
             // zero-width characters inside the preceding character, the
             // anchor has been moved to the right by one cell.
-            glyph.left += glyph_cache.metrics.average_advance as f32;
+            if c.width() == Some(0) {
+                glyph.left += glyph_cache.metrics.average_advance as f32;
+            }
 
             self.add_render_item(&cell, &glyph);
         }
     }
 }
 
-/// Load a glyph into a texture atlas
+#[cfg(not(any(target_os = "macos", windows)))]
+#[inline]
+fn get_glyph_by_index(
+    rasterizer: &mut Rasterizer,
+    font_key: FontKey,
+    size: font::Size,
+    index: u32,
+    c: char,
+) -> Result<RasterizedGlyph, font::Error> {
+    rasterizer.get_glyph_by_index(font_key, size, index, c)
+}
+
+/// Shaping is never used without FreeType, so the character's own glyph is the best guess
+#[cfg(any(target_os = "macos", windows))]
+#[inline]
+fn get_glyph_by_index(
+    rasterizer: &mut Rasterizer,
+    font_key: FontKey,
+    size: font::Size,
+    _: u32,
+    c: char,
+) -> Result<RasterizedGlyph, font::Error> {
+    rasterizer.get_glyph(GlyphKey { font_key, size, c })
+}
+
+/// Check if `c` only controls how a grapheme cluster is formed, without a glyph of its own
 ///
-/// If the current atlas is full, a new one will be created.
+/// These are zero width joiners and variation selectors, which would otherwise be drawn as
+/// missing glyphs.
 #[inline]
-fn load_glyph(
-    active_tex: &mut GLuint,
-    atlas: &mut Vec<Atlas>,
-    current_atlas: &mut usize,
-    rasterized: &RasterizedGlyph
-) -> Glyph {
-    // At least one atlas is guaranteed to be in the `self.atlas` list; thus
-    // the unwrap.
-    match atlas[*current_atlas].insert(rasterized, active_tex) {
-        Ok(glyph) => glyph,
-        Err(AtlasInsertError::Full) => {
-            *current_atlas += 1;
-            if *current_atlas == atlas.len() {
-                let new = Atlas::new(ATLAS_SIZE);
-                *active_tex = 0; // Atlas::new binds a texture. Ugh this is sloppy.
-                atlas.push(new);
-            }
-            load_glyph(active_tex, atlas, current_atlas, rasterized)
-        }
-        Err(AtlasInsertError::GlyphTooLarge) => {
-            Glyph {
-                tex_id: atlas[*current_atlas].id,
-                top: 0.0,
-                left: 0.0,
-                width: 0.0,
-                height: 0.0,
-                uv_bot: 0.0,
-                uv_left: 0.0,
-                uv_width: 0.0,
-                uv_height: 0.0,
-            }
-        }
+fn is_format_char(c: char) -> bool {
+    match c {
+        '\u{200c}' | '\u{200d}' | '\u{fe00}'..='\u{fe0f}' => true,
+        _ => false,
     }
 }
 
+/// Set the color used for clearing, premultiplied with `alpha`
 #[inline]
-fn clear_atlas(atlas: &mut Vec<Atlas>, current_atlas: &mut usize) {
-    for atlas in atlas.iter_mut() {
-        atlas.clear();
+unsafe fn set_clear_color(color: Rgb, alpha: f32) {
+    gl::ClearColor(
+        (f32::from(color.r) / 255.0).min(1.0) * alpha,
+        (f32::from(color.g) / 255.0).min(1.0) * alpha,
+        (f32::from(color.b) / 255.0).min(1.0) * alpha,
+        alpha,
+    );
+}
+
+/// Check if the current context is OpenGL ES, which reports its version as "OpenGL ES x.y"
+fn is_gles_context() -> bool {
+    unsafe {
+        let version = gl::GetString(gl::VERSION);
+        !version.is_null()
+            && CStr::from_ptr(version as *const _).to_bytes().starts_with(b"OpenGL ES")
     }
-    *current_atlas = 0;
 }
 
 impl<'a> LoadGlyph for LoaderApi<'a> {
     fn load_glyph(&mut self, rasterized: &RasterizedGlyph) -> Glyph {
-        load_glyph(self.active_tex, self.atlas, self.current_atlas, rasterized)
+        self.atlas.insert(rasterized, self.active_tex)
     }
 
     fn clear(&mut self) {
-        clear_atlas(self.atlas, self.current_atlas)
+        self.atlas.clear()
+    }
+
+    fn touch(&mut self, glyph: &Glyph) -> bool {
+        self.atlas.touch(glyph)
     }
 }
 
 impl<'a> LoadGlyph for RenderApi<'a> {
     fn load_glyph(&mut self, rasterized: &RasterizedGlyph) -> Glyph {
-        load_glyph(self.active_tex, self.atlas, self.current_atlas, rasterized)
+        // Glyphs of the evicted page might still be waiting in the batch
+        if self.atlas.evicts(rasterized) && !self.batch.is_empty() {
+            self.render_batch();
+        }
+
+        self.atlas.insert(rasterized, self.active_tex)
     }
 
     fn clear(&mut self) {
-        clear_atlas(self.atlas, self.current_atlas)
+        self.atlas.clear()
+    }
+
+    fn touch(&mut self, glyph: &Glyph) -> bool {
+        self.atlas.touch(glyph)
     }
 }
 
@@ -1143,14 +1510,28 @@ impl<'a> Drop for RenderApi<'a> {
 }
 
 impl TextShaderProgram {
-    pub fn new(size: PhysicalSize) -> Result<TextShaderProgram, ShaderCreationError> {
+    pub fn new(
+        size: PhysicalSize,
+        version: ShaderVersion,
+    ) -> Result<TextShaderProgram, ShaderCreationError> {
+        let (vertex_path, fragment_path, vertex_src, fragment_src) = match version {
+            ShaderVersion::Glsl3 => {
+                (TEXT_SHADER_V_PATH, TEXT_SHADER_F_PATH, TEXT_SHADER_V, TEXT_SHADER_F)
+            },
+            ShaderVersion::Gles2 => (
+                GLES2_TEXT_SHADER_V_PATH,
+                GLES2_TEXT_SHADER_F_PATH,
+                GLES2_TEXT_SHADER_V,
+                GLES2_TEXT_SHADER_F,
+            ),
+        };
         let (vertex_src, fragment_src) = if cfg!(feature = "live-shader-reload") {
             (None, None)
         } else {
-            (Some(TEXT_SHADER_V), Some(TEXT_SHADER_F))
+            (Some(vertex_src), Some(fragment_src))
         };
-        let vertex_shader = create_shader(TEXT_SHADER_V_PATH, gl::VERTEX_SHADER, vertex_src)?;
-        let fragment_shader = create_shader(TEXT_SHADER_F_PATH, gl::FRAGMENT_SHADER, fragment_src)?;
+        let vertex_shader = create_shader(vertex_path, gl::VERTEX_SHADER, vertex_src)?;
+        let fragment_shader = create_shader(fragment_path, gl::FRAGMENT_SHADER, fragment_src)?;
         let program = create_program(vertex_shader, fragment_shader)?;
 
         unsafe {
@@ -1174,12 +1555,12 @@ impl TextShaderProgram {
         }
 
         // get uniform locations
-        let (projection, term_dim, cell_dim, background) = unsafe {
+        let (projection, term_dim, cell_dim, rendering_pass) = unsafe {
             (
                 gl::GetUniformLocation(program, cptr!(b"projection\0")),
                 gl::GetUniformLocation(program, cptr!(b"termDim\0")),
                 gl::GetUniformLocation(program, cptr!(b"cellDim\0")),
-                gl::GetUniformLocation(program, cptr!(b"backgroundPass\0")),
+                gl::GetUniformLocation(program, cptr!(b"renderingPass\0")),
             )
         };
 
@@ -1190,7 +1571,7 @@ impl TextShaderProgram {
             u_projection: projection,
             u_term_dim: term_dim,
             u_cell_dim: cell_dim,
-            u_background: background,
+            u_rendering_pass: rendering_pass,
         };
 
         shader.update_projection(size.width as f32, size.height as f32, 0., 0.);
@@ -1242,11 +1623,9 @@ impl TextShaderProgram {
         }
     }
 
-    fn set_background_pass(&self, background_pass: bool) {
-        let value = if background_pass { 1 } else { 0 };
-
+    fn set_rendering_pass(&self, pass: RenderingPass) {
         unsafe {
-            gl::Uniform1i(self.u_background, value);
+            gl::Uniform1i(self.u_rendering_pass, pass as i32);
         }
     }
 }
@@ -1260,19 +1639,30 @@ impl Drop for TextShaderProgram {
 }
 
 impl RectShaderProgram {
-    pub fn new() -> Result<Self, ShaderCreationError> {
+    pub fn new(version: ShaderVersion) -> Result<Self, ShaderCreationError> {
+        let (vertex_path, fragment_path, vertex_src, fragment_src) = match version {
+            ShaderVersion::Glsl3 => {
+                (RECT_SHADER_V_PATH, RECT_SHADER_F_PATH, RECT_SHADER_V, RECT_SHADER_F)
+            },
+            ShaderVersion::Gles2 => (
+                GLES2_RECT_SHADER_V_PATH,
+                GLES2_RECT_SHADER_F_PATH,
+                GLES2_RECT_SHADER_V,
+                GLES2_RECT_SHADER_F,
+            ),
+        };
         let (vertex_src, fragment_src) = if cfg!(feature = "live-shader-reload") {
             (None, None)
         } else {
-            (Some(RECT_SHADER_V), Some(RECT_SHADER_F))
+            (Some(vertex_src), Some(fragment_src))
         };
         let vertex_shader = create_shader(
-            RECT_SHADER_V_PATH,
+            vertex_path,
             gl::VERTEX_SHADER,
             vertex_src
         )?;
         let fragment_shader = create_shader(
-            RECT_SHADER_F_PATH,
+            fragment_path,
             gl::FRAGMENT_SHADER,
             fragment_src
         )?;
@@ -1491,6 +1881,92 @@ impl From<io::Error> for ShaderCreationError {
     }
 }
 
+/// Texture atlas pages holding all loaded glyphs
+///
+/// Pages are added when the current one is full, until there are `MAX_ATLAS_PAGES` of them.
+/// Afterwards the page whose glyphs haven't been used for the longest time is cleared, so its
+/// glyphs are loaded again the next time they're drawn.
+#[derive(Debug)]
+pub struct GlyphAtlas {
+    pages: Vec<Atlas>,
+
+    /// Page new glyphs are inserted into
+    current: usize,
+
+    /// Incremented whenever a glyph is used, to find the least recently used page
+    clock: u64,
+}
+
+impl GlyphAtlas {
+    fn new() -> GlyphAtlas {
+        GlyphAtlas { pages: vec![Atlas::new(ATLAS_SIZE, 0)], current: 0, clock: 0 }
+    }
+
+    /// Insert a glyph into the current page, moving on to another page if it's full
+    fn insert(&mut self, rasterized: &RasterizedGlyph, active_tex: &mut GLuint) -> Glyph {
+        let glyph = match self.pages[self.current].insert(rasterized, active_tex) {
+            Ok(glyph) => glyph,
+            Err(AtlasInsertError::Full) => {
+                self.current = self.next_page();
+                let page = &mut self.pages[self.current];
+                page.insert(rasterized, active_tex).unwrap_or_else(|_| page.empty_glyph())
+            },
+            Err(AtlasInsertError::GlyphTooLarge) => self.pages[self.current].empty_glyph(),
+        };
+
+        self.touch(&glyph);
+        glyph
+    }
+
+    /// Check if inserting the glyph evicts a page, which invalidates the glyphs on it
+    fn evicts(&self, rasterized: &RasterizedGlyph) -> bool {
+        !self.pages[self.current].fits(rasterized)
+            && self.pages.len() >= MAX_ATLAS_PAGES
+            && !self.pages.iter().any(Atlas::is_empty)
+    }
+
+    /// Page for new glyphs after the current one is full
+    fn next_page(&mut self) -> usize {
+        if let Some(index) = self.pages.iter().position(Atlas::is_empty) {
+            return index;
+        }
+
+        if self.pages.len() < MAX_ATLAS_PAGES {
+            let index = self.pages.len();
+            self.pages.push(Atlas::new(ATLAS_SIZE, index));
+            return index;
+        }
+
+        let current = self.current;
+        let index = (0..self.pages.len())
+            .filter(|index| *index != current)
+            .min_by_key(|index| self.pages[*index].last_used)
+            .unwrap_or(current);
+        debug!("Evicting glyphs of atlas page {}", index);
+        self.pages[index].clear();
+        index
+    }
+
+    /// Mark the page of a glyph as used, returns `false` if the page was cleared since
+    fn touch(&mut self, glyph: &Glyph) -> bool {
+        match self.pages.get_mut(glyph.page) {
+            Some(ref mut page) if page.generation == glyph.generation => {
+                self.clock += 1;
+                page.last_used = self.clock;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn clear(&mut self) {
+        for page in &mut self.pages {
+            page.clear();
+        }
+        self.current = 0;
+    }
+}
+
 /// Manages a single texture atlas
 ///
 /// The strategy for filling an atlas looks roughly like this:
@@ -1514,6 +1990,15 @@ struct Atlas {
     /// Texture id for this atlas
     id: GLuint,
 
+    /// Index of the atlas in the pages of the `GlyphAtlas`
+    page: usize,
+
+    /// Incremented whenever the atlas is cleared, invalidating its glyphs
+    generation: u32,
+
+    /// Value of the `GlyphAtlas` clock when one of the glyphs was last used
+    last_used: u64,
+
     /// Width of atlas
     width: i32,
 
@@ -1545,7 +2030,7 @@ enum AtlasInsertError {
 }
 
 impl Atlas {
-    fn new(size: i32) -> Atlas {
+    fn new(size: i32, page: usize) -> Atlas {
         let mut id: GLuint = 0;
         unsafe {
             gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
@@ -1573,6 +2058,9 @@ impl Atlas {
 
         Atlas {
             id,
+            page,
+            generation: 0,
+            last_used: 0,
             width: size,
             height: size,
             row_extent: 0,
@@ -1585,6 +2073,40 @@ impl Atlas {
         self.row_extent = 0;
         self.row_baseline = 0;
         self.row_tallest = 0;
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// Check if no glyphs have been inserted since the atlas was cleared
+    fn is_empty(&self) -> bool {
+        self.row_extent == 0 && self.row_baseline == 0
+    }
+
+    /// Check if the glyph can be inserted without the atlas being full
+    fn fits(&self, glyph: &RasterizedGlyph) -> bool {
+        if self.room_in_row(glyph) {
+            return true;
+        }
+
+        // The next row starts above the tallest glyph of the current one
+        let row_baseline = self.row_baseline + self.row_tallest;
+        glyph.width as i32 <= self.width && (glyph.height as i32) < self.height - row_baseline
+    }
+
+    /// Glyph without any size, used for glyphs which don't fit into the atlas
+    fn empty_glyph(&self) -> Glyph {
+        Glyph {
+            tex_id: self.id,
+            page: self.page,
+            generation: self.generation,
+            top: 0.0,
+            left: 0.0,
+            width: 0.0,
+            height: 0.0,
+            uv_bot: 0.0,
+            uv_left: 0.0,
+            uv_width: 0.0,
+            uv_height: 0.0,
+        }
     }
 
     /// Insert a RasterizedGlyph into the texture atlas
@@ -1656,6 +2178,8 @@ impl Atlas {
 
         Glyph {
             tex_id: self.id,
+            page: self.page,
+            generation: self.generation,
             top: glyph.top as f32,
             width: width as f32,
             height: height as f32,
@@ -1690,3 +2214,158 @@ impl Atlas {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::LineAttribute;
+    use crate::index::{Column, Line};
+    use crate::term::{cell, RenderableCell, SizeInfo};
+    use crate::Rgb;
+
+    use super::{rect_vertices, Atlas, Batch, Glyph, GlyphAtlas, Rect, MAX_ATLAS_PAGES};
+
+    /// Atlas page without a texture, filled up to the last row
+    fn full_page(page: usize, last_used: u64) -> Atlas {
+        Atlas {
+            id: 0,
+            page,
+            generation: 0,
+            last_used,
+            width: 16,
+            height: 16,
+            row_extent: 16,
+            row_baseline: 15,
+            row_tallest: 1,
+        }
+    }
+
+    pub fn cell(line: usize, column: usize) -> RenderableCell {
+        RenderableCell {
+            line: Line(line),
+            column: Column(column),
+            chars: ['a'; cell::MAX_ZEROWIDTH_CHARS + 1],
+            fg: Rgb { r: 0xff, g: 0xff, b: 0xff },
+            bg: Rgb { r: 0, g: 0, b: 0 },
+            bg_alpha: 0.,
+            flags: cell::Flags::empty(),
+            line_attribute: LineAttribute::SingleWidth,
+        }
+    }
+
+    pub fn glyph(tex_id: u32) -> Glyph {
+        Glyph {
+            tex_id,
+            page: 0,
+            generation: 0,
+            top: 0.,
+            left: 0.,
+            width: 1.,
+            height: 1.,
+            uv_bot: 0.,
+            uv_left: 0.,
+            uv_width: 1.,
+            uv_height: 1.,
+        }
+    }
+
+    #[test]
+    fn batch_groups_by_atlas() {
+        let mut batch = Batch::new();
+        for column in 0..10 {
+            batch.add_item(&cell(0, column), &glyph(1 + column as u32 % 2));
+        }
+        assert_eq!(batch.len(), 10);
+        assert_eq!(batch.atlas_count(), 2);
+
+        // Groups of atlases which aren't used anymore don't cause draw calls
+        batch.clear();
+        batch.add_item(&cell(0, 0), &glyph(2));
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch.atlas_count(), 1);
+    }
+
+    #[test]
+    fn evict_least_recently_used_page() {
+        let pages = (0..MAX_ATLAS_PAGES).map(|page| full_page(page, 10 - page as u64)).collect();
+        let mut atlas = GlyphAtlas { pages, current: 0, clock: 10 };
+
+        // The current page is never evicted, even if it's the least recently used one
+        atlas.pages[0].last_used = 0;
+        let last = MAX_ATLAS_PAGES - 1;
+        assert_eq!(atlas.next_page(), last);
+        assert_eq!(atlas.pages.len(), MAX_ATLAS_PAGES);
+        assert!(atlas.pages[last].is_empty());
+        assert_eq!(atlas.pages[last].generation, 1);
+    }
+
+    #[test]
+    fn evicted_glyphs_are_reloaded() {
+        let pages = vec![full_page(0, 0), full_page(1, 0)];
+        let mut atlas = GlyphAtlas { pages, current: 1, clock: 0 };
+        let mut glyph = glyph(0);
+
+        assert!(atlas.touch(&glyph));
+        assert_eq!(atlas.pages[0].last_used, 1);
+
+        atlas.pages[0].clear();
+        assert!(!atlas.touch(&glyph));
+        glyph.generation = 1;
+        assert!(atlas.touch(&glyph));
+
+        // Cleared pages are reused before adding new ones
+        assert_eq!(atlas.next_page(), 0);
+    }
+
+    #[test]
+    fn rect_vertices_in_device_coordinates() {
+        let size = SizeInfo {
+            width: 200.,
+            height: 100.,
+            cell_width: 10.,
+            cell_height: 20.,
+            padding_x: 0.,
+            padding_y: 0.,
+            dpr: 1.,
+            reserved_lines: 0,
+        };
+
+        // Bottom right quarter of the window
+        let vertices = rect_vertices(&Rect::new(100., 50., 100., 50.), &size);
+        assert_eq!(vertices, [
+            1., 0., 0.,
+            1., -1., 0.,
+            0., -1., 0.,
+            0., 0., 0.,
+        ]);
+    }
+}
+
+#[cfg(all(test, feature = "bench"))]
+mod benches {
+    extern crate test;
+
+    use super::tests::{cell, glyph};
+    use super::{Batch, BATCH_MAX};
+
+    /// Batching the cells of a full 4K window, with glyphs spread over two atlases
+    ///
+    /// Every change of the atlas used to flush the batch, so this measures the CPU side of
+    /// drawing a grid whose glyphs alternate between atlases with a single batch.
+    #[bench]
+    fn batch_4k_grid(b: &mut test::Bencher) {
+        let (lines, columns) = (135, 480);
+        assert!(lines * columns <= BATCH_MAX);
+
+        let mut batch = Batch::new();
+        b.iter(|| {
+            for line in 0..lines {
+                for column in 0..columns {
+                    let tex_id = if column % 7 == 0 { 2 } else { 1 };
+                    batch.add_item(&cell(line, column), &glyph(tex_id));
+                }
+            }
+            test::black_box(batch.atlas_count());
+            batch.clear();
+        })
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Ligatures in runs of cells with the same style
+//!
+//! Every cell of a run contributes a single character to the shaped text. The glyphs returned by
+//! the font are mapped back to the cells they were shaped from, so a ligature is drawn in the cell
+//! of its first character and the cursor and selection keep working on the individual cells.
+use font::ShapedGlyph;
+
+use crate::term::cell::Flags;
+use crate::term::RenderableCell;
+
+/// Glyph drawn in a cell of a shaped run
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShapedCell {
+    /// The glyph of the cell's own character
+    Unchanged,
+
+    /// Glyph substituted by the font, like a ligature
+    Glyph(u32),
+
+    /// Covered by the ligature of a previous cell
+    Covered,
+}
+
+/// Glyphs for each of the `len` cells a run was shaped from
+///
+/// Returns `None` if the font didn't substitute anything, or if the glyphs can't be mapped back
+/// to the cells, like a character split into multiple glyphs or one missing from the font.
+pub fn shaped_cells(glyphs: &[ShapedGlyph], len: usize) -> Option<Vec<ShapedCell>> {
+    if !glyphs.iter().any(|glyph| glyph.substituted) {
+        return None;
+    }
+
+    let mut cells = vec![ShapedCell::Covered; len];
+    let mut last_cluster = None;
+    for glyph in glyphs {
+        if glyph.index == 0 || glyph.cluster >= len || last_cluster == Some(glyph.cluster) {
+            return None;
+        }
+        last_cluster = Some(glyph.cluster);
+
+        cells[glyph.cluster] = if glyph.substituted {
+            ShapedCell::Glyph(glyph.index)
+        } else {
+            ShapedCell::Unchanged
+        };
+    }
+
+    Some(cells)
+}
+
+/// Check if `cell` continues the run ending with `last`
+///
+/// Runs only contain adjacent cells with the same style and a single narrow character each.
+pub fn continues_run(last: &RenderableCell, cell: &RenderableCell) -> bool {
+    cell.line == last.line
+        && cell.column == last.column + 1
+        && cell.fg == last.fg
+        && cell.bg == last.bg
+        && cell.flags == last.flags
+        && cell.line_attribute == last.line_attribute
+        && is_shapeable(cell)
+        && is_shapeable(last)
+}
+
+#[inline]
+fn is_shapeable(cell: &RenderableCell) -> bool {
+    !cell.flags.intersects(Flags::WIDE_CHAR | Flags::WIDE_CHAR_SPACER | Flags::HIDDEN)
+        && cell.chars[1..].iter().all(|c| *c == ' ')
+}
+
+#[cfg(test)]
+mod tests {
+    use font::ShapedGlyph;
+
+    use super::{shaped_cells, ShapedCell};
+
+    fn glyph(cluster: usize, index: u32, substituted: bool) -> ShapedGlyph {
+        ShapedGlyph { cluster, index, substituted }
+    }
+
+    #[test]
+    fn unchanged_runs_are_not_shaped() {
+        let glyphs = [glyph(0, 10, false), glyph(1, 11, false)];
+
+        assert_eq!(shaped_cells(&glyphs, 2), None);
+    }
+
+    #[test]
+    fn ligature_covers_following_cells() {
+        let glyphs = [glyph(0, 10, false), glyph(1, 50, true), glyph(3, 12, false)];
+
+        assert_eq!(shaped_cells(&glyphs, 4), Some(vec![
+            ShapedCell::Unchanged,
+            ShapedCell::Glyph(50),
+            ShapedCell::Covered,
+            ShapedCell::Unchanged,
+        ]));
+    }
+
+    #[test]
+    fn unmappable_glyphs() {
+        // Character split into two glyphs
+        let split = [glyph(0, 50, true), glyph(0, 51, true)];
+        assert_eq!(shaped_cells(&split, 1), None);
+
+        // Character missing from the font
+        let missing = [glyph(0, 50, true), glyph(1, 0, true)];
+        assert_eq!(shaped_cells(&missing, 2), None);
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Screenshots saved as PNG images
+//!
+//! The `Screenshot` action and the `OSC 1339 ST` escape sequence save the next frame which is
+//! drawn, `ScreenshotSelection` only saves the cells covered by the selection.
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use png::HasParameters;
+
+use crate::index::{Column, Line};
+use crate::ref_test::Screenshot;
+use crate::term::SizeInfo;
+
+/// Part of the window which is saved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Region {
+    /// The entire window, including the padding
+    Window,
+
+    /// Lines and columns of the visible area
+    Cells(Range<Line>, Range<Column>),
+}
+
+/// Pixels of `screenshot` inside the region, clipped to the window
+pub fn crop(screenshot: &Screenshot, region: &Region, size: &SizeInfo) -> Screenshot {
+    let (lines, cols) = match region {
+        Region::Window => return screenshot.clone(),
+        Region::Cells(lines, cols) => (lines, cols),
+    };
+
+    let pixel = |cell: usize, cell_size: f32, padding: f32, max: usize| {
+        ((padding + cell as f32 * cell_size) as usize).min(max)
+    };
+    let left = pixel(cols.start.0, size.cell_width, size.padding_x, screenshot.width);
+    let right = pixel(cols.end.0, size.cell_width, size.padding_x, screenshot.width);
+    let top = pixel(lines.start.0, size.cell_height, size.padding_y, screenshot.height);
+    let bottom = pixel(lines.end.0, size.cell_height, size.padding_y, screenshot.height);
+
+    let (width, height) = (right.saturating_sub(left), bottom.saturating_sub(top));
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for row in top..top + height {
+        let start = (row * screenshot.width + left) * 3;
+        pixels.extend_from_slice(&screenshot.pixels[start..start + width * 3]);
+    }
+
+    Screenshot { width, height, pixels }
+}
+
+/// Save the screenshot in `directory`, named after the current time
+pub fn save(screenshot: &Screenshot, directory: &Path) -> io::Result<PathBuf> {
+    let (file, path) = create_file(directory, "png")?;
+    write_png(screenshot, BufWriter::new(file))?;
+    Ok(path)
+}
+
+/// Save an SVG image of the terminal in `directory`, named after the current time
+pub fn save_svg(svg: &str, directory: &Path) -> io::Result<PathBuf> {
+    let (mut file, path) = create_file(directory, "svg")?;
+    file.write_all(svg.as_bytes())?;
+    Ok(path)
+}
+
+/// Create a new file in `directory`, named after the current time
+fn create_file(directory: &Path, extension: &str) -> io::Result<(File, PathBuf)> {
+    let timestamp = time::strftime("%Y-%m-%d_%H-%M-%S", &time::now()).unwrap_or_default();
+
+    // Files created within the same second get a number appended
+    let mut suffix = 0;
+    loop {
+        let name = match suffix {
+            0 => format!("alacritty-{}.{}", timestamp, extension),
+            _ => format!("alacritty-{}-{}.{}", timestamp, suffix, extension),
+        };
+        let path = directory.join(name);
+
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(ref err) if err.kind() == ErrorKind::AlreadyExists => suffix += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn write_png<W: Write>(screenshot: &Screenshot, writer: W) -> io::Result<()> {
+    let invalid = |err: png::EncodingError| io::Error::new(ErrorKind::Other, err);
+
+    let mut encoder = png::Encoder::new(writer, screenshot.width as u32, screenshot.height as u32);
+    encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(invalid)?;
+    writer.write_image_data(&screenshot.pixels).map_err(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::{Column, Line};
+    use crate::ref_test::Screenshot;
+    use crate::term::SizeInfo;
+
+    use super::{crop, Region};
+
+    #[test]
+    fn crop_to_cells() {
+        // Every pixel has the value of its column in all channels
+        let pixels = (0..4).flat_map(|_| (0..6).flat_map(|x| vec![x; 3])).collect();
+        let screenshot = Screenshot { width: 6, height: 4, pixels };
+        let size = SizeInfo {
+            width: 6.,
+            height: 4.,
+            cell_width: 2.,
+            cell_height: 1.,
+            padding_x: 1.,
+            padding_y: 1.,
+            dpr: 1.,
+            reserved_lines: 0,
+        };
+
+        let region = Region::Cells(Line(1)..Line(3), Column(1)..Column(3));
+        let cropped = crop(&screenshot, &region, &size);
+        assert_eq!((cropped.width, cropped.height), (3, 2));
+        assert_eq!(&cropped.pixels[..9], &[3, 3, 3, 4, 4, 4, 5, 5, 5]);
+
+        assert_eq!(crop(&screenshot, &Region::Window, &size), screenshot);
+    }
+}
@@ -26,10 +26,12 @@ use crate::term::Search;
 
 /// Describes a region of a 2-dimensional area
 ///
-/// Used to track a text selection. There are three supported modes, each with its own constructor:
-/// [`simple`], [`semantic`], and [`lines`]. The [`simple`] mode precisely tracks which cells are
-/// selected without any expansion. [`semantic`] mode expands the initial selection to the nearest
-/// semantic escape char in either direction. [`lines`] will always select entire lines.
+/// Used to track a text selection. There are four supported modes, each with its own constructor:
+/// [`simple`], [`block`], [`semantic`], and [`lines`]. The [`simple`] mode precisely tracks which
+/// cells are selected without any expansion. [`block`] mode selects the rectangle spanned by the
+/// start and end points instead of the text between them. [`semantic`] mode expands the initial
+/// selection to the nearest semantic escape char in either direction. [`lines`] will always select
+/// entire lines.
 ///
 /// Calls to [`update`] operate different based on the selection kind. The [`simple`] mode does
 /// nothing special, simply tracks points and sides. [`semantic`] will continue to expand out to
@@ -37,6 +39,7 @@ use crate::term::Search;
 /// new point to encompass entire lines.
 ///
 /// [`simple`]: enum.Selection.html#method.simple
+/// [`block`]: enum.Selection.html#method.block
 /// [`semantic`]: enum.Selection.html#method.semantic
 /// [`lines`]: enum.Selection.html#method.lines
 #[derive(Debug, Clone, PartialEq)]
@@ -45,6 +48,10 @@ pub enum Selection {
         /// The region representing start and end of cursor movement
         region: Range<Anchor>,
     },
+    Block {
+        /// The region representing start and end of cursor movement
+        region: Range<Anchor>,
+    },
     Semantic {
         /// The region representing start and end of cursor movement
         region: Range<Point<isize>>,
@@ -88,9 +95,18 @@ impl Selection {
         }
     }
 
+    pub fn block(location: Point<usize>, side: Side) -> Selection {
+        Selection::Block {
+            region: Range {
+                start: Anchor::new(location.into(), side),
+                end: Anchor::new(location.into(), side)
+            }
+        }
+    }
+
     pub fn rotate(&mut self, offset: isize) {
         match *self {
-            Selection::Simple { ref mut region } => {
+            Selection::Simple { ref mut region } | Selection::Block { ref mut region } => {
                 region.start.point.line += offset;
                 region.end.point.line += offset;
             },
@@ -128,7 +144,7 @@ impl Selection {
     pub fn update(&mut self, location: Point<usize>, side: Side) {
         // Always update the `end`; can normalize later during span generation.
         match *self {
-            Selection::Simple { ref mut region } => {
+            Selection::Simple { ref mut region } | Selection::Block { ref mut region } => {
                 region.end = Anchor::new(location.into(), side);
             },
             Selection::Semantic { ref mut region } |
@@ -147,6 +163,9 @@ impl Selection {
             Selection::Simple { ref region } => {
                 Selection::span_simple(grid, region, alt_screen)
             },
+            Selection::Block { ref region } => {
+                Selection::span_block(grid, region, alt_screen)
+            },
             Selection::Semantic { ref region } => {
                 Selection::span_semantic(grid, region, alt_screen)
             },
@@ -159,7 +178,7 @@ impl Selection {
     pub fn is_empty(&self) -> bool
     {
         match *self {
-            Selection::Simple { ref region } => {
+            Selection::Simple { ref region } | Selection::Block { ref region } => {
                 region.start == region.end && region.start.side == region.end.side
             },
             Selection::Semantic { .. } | Selection::Lines { .. } => {
@@ -204,6 +223,7 @@ impl Selection {
             front: start,
             tail: end,
             ty: SpanType::Inclusive,
+            is_block: false,
         })
     }
 
@@ -249,7 +269,8 @@ impl Selection {
             cols,
             front: start.into(),
             tail: end.into(),
-            ty: SpanType::Inclusive
+            ty: SpanType::Inclusive,
+            is_block: false,
         })
     }
 
@@ -308,6 +329,68 @@ impl Selection {
             front: front.into(),
             tail: tail.into(),
             ty: SpanType::Inclusive,
+            is_block: false,
+        })
+    }
+
+    fn span_block<G>(grid: &G, region: &Range<Anchor>, alt_screen: bool) -> Option<Span>
+    where
+        G: Dimensions
+    {
+        let cols = grid.dimensions().col;
+        let lines = grid.dimensions().line.0 as isize;
+
+        // Front is always the bottom right corner and tail the top left one
+        let (mut front, mut tail) = (region.start.point, region.end.point);
+        let (mut front_side, mut tail_side) = (region.start.side, region.end.side);
+        if front.line > tail.line {
+            ::std::mem::swap(&mut front.line, &mut tail.line);
+        }
+        if front.col < tail.col {
+            ::std::mem::swap(&mut front.col, &mut tail.col);
+            ::std::mem::swap(&mut front_side, &mut tail_side);
+        }
+
+        // No selection for a single column with identical sides or two columns with right+left sides
+        if (front.col == tail.col && front_side == tail_side)
+            || (tail_side == Side::Right && front_side == Side::Left && front.col == tail.col + 1)
+        {
+            return None;
+        }
+
+        // Remove last column if selection ends to the left of a cell
+        if front_side == Side::Left && front.col > tail.col {
+            front.col -= 1;
+        }
+
+        // Remove first column if selection starts at the right of a cell
+        if tail_side == Side::Right && front.col > tail.col {
+            tail.col += 1;
+        }
+
+        // Only the lines are clamped, the columns of a block are independent of them
+        if alt_screen {
+            if tail.line >= lines {
+                if front.line >= lines {
+                    return None;
+                }
+                tail.line = lines - 1;
+            }
+
+            if front.line < 0 {
+                if tail.line < 0 {
+                    return None;
+                }
+                front.line = 0;
+            }
+        }
+
+        Some(Span {
+            cols,
+            front: front.into(),
+            tail: tail.into(),
+            ty: SpanType::Inclusive,
+            is_block: true,
         })
     }
 
@@ -369,6 +452,9 @@ pub struct Span {
 
     /// The type says whether ends are included or not.
     ty: SpanType,
+
+    /// Whether only the columns between front and tail are selected on every line
+    is_block: bool,
 }
 
 #[derive(Debug)]
@@ -377,6 +463,8 @@ pub struct Locations {
     pub start: Point<usize>,
     /// End point towards top of buffer
     pub end: Point<usize>,
+    /// Rectangular selection between the columns of `end` and `start`
+    pub is_block: bool,
 }
 
 impl Span {
@@ -390,7 +478,7 @@ impl Span {
             SpanType::ExcludeTail => (self.front, Span::wrap_end(self.tail, self.cols))
         };
 
-        Locations { start, end }
+        Locations { start, end, is_block: self.is_block }
     }
 
     fn wrap_start(mut start: Point<usize>, cols: Column) -> Point<usize> {
@@ -471,7 +559,8 @@ mod test {
             cols: Column(1),
             ty: SpanType::Inclusive,
             front: location,
-            tail: location
+            tail: location,
+            is_block: false,
         });
     }
 
@@ -490,7 +579,8 @@ mod test {
             cols: Column(1),
             ty: SpanType::Inclusive,
             front: location,
-            tail: location
+            tail: location,
+            is_block: false,
         });
     }
 
@@ -539,6 +629,7 @@ mod test {
             front: Point::new(0, Column(1)),
             tail: Point::new(1, Column(2)),
             ty: SpanType::Inclusive,
+            is_block: false,
         });
     }
 
@@ -564,6 +655,7 @@ mod test {
             front: Point::new(0, Column(1)),
             tail: Point::new(1, Column(1)),
             ty: SpanType::Inclusive,
+            is_block: false,
         });
     }
 
@@ -578,6 +670,7 @@ mod test {
             front: Point::new(0, Column(4)),
             tail: Point::new(2, Column(0)),
             ty: SpanType::Inclusive,
+            is_block: false,
         });
     }
 
@@ -592,6 +685,7 @@ mod test {
             front: Point::new(0, Column(4)),
             tail: Point::new(2, Column(3)),
             ty: SpanType::Inclusive,
+            is_block: false,
         });
     }
 
@@ -606,6 +700,72 @@ mod test {
             front: Point::new(0, Column(4)),
             tail: Point::new(2, Column(4)),
             ty: SpanType::Inclusive,
+            is_block: false,
+        });
+    }
+
+    /// Test block selection across multiple lines
+    ///
+    /// 1.  [  ][  ][  ][  ][  ]
+    ///     [  ][  ][  ][  ][  ]
+    ///     [  ][  ][  ][  ][  ]
+    /// 2.  [  ][B ][  ][  ][  ]
+    ///     [  ][  ][  ][  ][  ]
+    ///     [  ][  ][  ][  ][  ]
+    /// 3.  [  ][BX][XX][XX][  ]
+    ///     [  ][XX][XX][XX][  ]
+    ///     [  ][XX][XX][XE][  ]
+    #[test]
+    fn block_selection() {
+        let mut selection = Selection::block(Point::new(2, Column(1)), Side::Left);
+        selection.update(Point::new(0, Column(3)), Side::Right);
+
+        assert_eq!(selection.to_span(&Dimensions::new(3, 5), false).unwrap(), Span {
+            cols: Column(5),
+            front: Point::new(0, Column(3)),
+            tail: Point::new(2, Column(1)),
+            ty: SpanType::Inclusive,
+            is_block: true,
+        });
+    }
+
+    /// Test block selection with partially covered columns at both edges
+    ///
+    /// 1.  [  ][  ][  ][  ][  ]
+    ///     [  ][  ][  ][  ][  ]
+    ///     [  ][  ][  ][  ][  ]
+    /// 2.  [  ][  ][  ][B ][  ]
+    ///     [  ][  ][  ][  ][  ]
+    ///     [  ][  ][  ][  ][  ]
+    /// 3.  [  ][  ][XX][B ][  ]
+    ///     [  ][  ][XX][  ][  ]
+    ///     [  ][ E][XX][  ][  ]
+    #[test]
+    fn block_selection_partial_columns() {
+        let mut selection = Selection::block(Point::new(2, Column(3)), Side::Left);
+        selection.update(Point::new(0, Column(1)), Side::Right);
+
+        assert_eq!(selection.to_span(&Dimensions::new(3, 5), false).unwrap(), Span {
+            cols: Column(5),
+            front: Point::new(0, Column(2)),
+            tail: Point::new(2, Column(2)),
+            ty: SpanType::Inclusive,
+            is_block: true,
+        });
+    }
+
+    #[test]
+    fn alt_screen_block() {
+        let mut selection = Selection::block(Point::new(0, Column(1)), Side::Left);
+        selection.update(Point::new(5, Column(3)), Side::Right);
+        selection.rotate(-3);
+
+        assert_eq!(selection.to_span(&Dimensions::new(10, 5), true).unwrap(), Span {
+            cols: Column(5),
+            front: Point::new(0, Column(3)),
+            tail: Point::new(2, Column(1)),
+            ty: SpanType::Inclusive,
+            is_block: true,
         });
     }
 }
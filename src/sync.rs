@@ -15,6 +15,9 @@
 //! Synchronization types
 //!
 //! Most importantly, a fair mutex is included
+use std::ops::{Deref, DerefMut};
+use std::thread::{self, ThreadId};
+
 use parking_lot::{Mutex, MutexGuard};
 
 /// A fair mutex
@@ -26,22 +29,215 @@ pub struct FairMutex<T> {
     data: Mutex<T>,
     /// Next-to-access
     next: Mutex<()>,
+    /// Thread holding the lock
+    owner: Mutex<Option<Owner<T>>>,
+}
+
+/// Thread holding the lock of a `FairMutex` and the data it guards
+struct Owner<T> {
+    thread: ThreadId,
+    data: *const T,
 }
 
+// The pointer is only dereferenced by the owning thread
+unsafe impl<T: Send> Send for Owner<T> {}
+
 impl<T> FairMutex<T> {
     /// Create a new fair mutex
     pub fn new(data: T) -> FairMutex<T> {
         FairMutex {
             data: Mutex::new(data),
             next: Mutex::new(()),
+            owner: Mutex::new(None),
         }
     }
 
     /// Lock the mutex
-    pub fn lock(&self) -> MutexGuard<'_, T> {
+    pub fn lock(&self) -> FairMutexGuard<'_, T> {
         // Must bind to a temporary or the lock will be freed before going
         // into data.lock()
         let _next = self.next.lock();
-        self.data.lock()
+        self.guard(self.data.lock())
+    }
+
+    /// Lock the mutex, unless it is already locked or another thread is waiting for it
+    pub fn try_lock(&self) -> Option<FairMutexGuard<'_, T>> {
+        let _next = self.next.try_lock()?;
+        self.data.try_lock().map(|data| self.guard(data))
+    }
+
+    /// Read the data, unless another thread holds the lock
+    ///
+    /// Unlike `try_lock`, this also succeeds when the current thread holds the lock, which is
+    /// the case when it panics while locked. The data might be halfway through a change then.
+    pub fn peek<R, F: FnOnce(&T) -> R>(&self, f: F) -> Option<R> {
+        if let Some(data) = self.data.try_lock() {
+            return Some(f(&data));
+        }
+
+        let owner = self.owner.lock();
+        match *owner {
+            // Safe since the guard of the current thread can't be used while `f` runs
+            Some(ref owner) if owner.thread == thread::current().id() => {
+                Some(f(unsafe { &*owner.data }))
+            },
+            _ => None,
+        }
+    }
+
+    fn guard<'a>(&'a self, data: MutexGuard<'a, T>) -> FairMutexGuard<'a, T> {
+        *self.owner.lock() = Some(Owner { thread: thread::current().id(), data: &*data });
+        FairMutexGuard { data, owner: &self.owner }
+    }
+}
+
+/// Guard of a locked `FairMutex`
+pub struct FairMutexGuard<'a, T> {
+    data: MutexGuard<'a, T>,
+    owner: &'a Mutex<Option<Owner<T>>>,
+}
+
+impl<'a, T> Deref for FairMutexGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.data
+    }
+}
+
+impl<'a, T> DerefMut for FairMutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+}
+
+impl<'a, T> Drop for FairMutexGuard<'a, T> {
+    fn drop(&mut self) {
+        // Runs before the data is unlocked, so no other thread can be the owner yet
+        *self.owner.lock() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::FairMutex;
+
+    #[test]
+    fn peek_while_locked() {
+        let mutex = Arc::new(FairMutex::new(1));
+        assert_eq!(mutex.peek(|data| *data), Some(1));
+
+        let mut guard = mutex.lock();
+        *guard = 2;
+        assert_eq!(mutex.peek(|data| *data), Some(2));
+
+        // Other threads don't read the data while it's locked
+        let other = mutex.clone();
+        assert_eq!(thread::spawn(move || other.peek(|data| *data)).join().unwrap(), None);
+
+        drop(guard);
+        let other = mutex.clone();
+        assert_eq!(thread::spawn(move || other.peek(|data| *data)).join().unwrap(), Some(2));
+    }
+}
+
+#[cfg(all(test, feature = "bench"))]
+mod benches {
+    extern crate test;
+
+    use std::io;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::ansi;
+    use crate::config::Config;
+    use crate::term::{SizeInfo, Term};
+
+    use super::FairMutex;
+
+    /// Bytes of output parsed by `pty_read` while it holds the lock, before and after it released
+    /// the lock between reads
+    const MAX_READ: usize = 0x1_0000;
+    const READ_SIZE: usize = 0x1000;
+
+    /// Benchmark for drawing while the terminal is flooded with output and an effect is running
+    ///
+    /// One thread parses colored output in chunks of `chunk` bytes, holding the lock for each
+    /// chunk, and another locks the terminal every 40ms like the animation thread. The iteration
+    /// measures how long the renderer waits for the lock and collects the renderable cells.
+    fn render_under_load(b: &mut test::Bencher, chunk: usize) {
+        let size = SizeInfo {
+            width: 800.0,
+            height: 480.0,
+            cell_width: 10.0,
+            cell_height: 20.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0,
+        };
+        let config = Config::default();
+        let terminal = Arc::new(FairMutex::new(Term::new(&config, size)));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let output: Vec<u8> = (0..10_000)
+            .map(|i| format!("\x1b[3{}mline {} of the output\x1b[0m\r\n", i % 8, i))
+            .flat_map(String::into_bytes)
+            .collect();
+        let pty = {
+            let terminal = terminal.clone();
+            let running = running.clone();
+            thread::spawn(move || {
+                let mut parser = ansi::Processor::new();
+                let mut writer = io::sink();
+                for chunk in output.chunks(chunk).cycle() {
+                    if !running.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    let mut terminal = terminal.lock();
+                    parser.advance_bytes(&mut *terminal, chunk, &mut writer);
+                }
+            })
+        };
+
+        let effect = {
+            let terminal = terminal.clone();
+            let running = running.clone();
+            thread::spawn(move || {
+                while running.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(40));
+                    let mut terminal = terminal.lock();
+                    test::black_box(terminal.grid().line_hashes());
+                    terminal.damage.damage_all();
+                }
+            })
+        };
+
+        b.iter(|| {
+            let terminal = terminal.lock();
+            for cell in terminal.renderable_cells(&config, true) {
+                test::black_box(cell);
+            }
+        });
+
+        running.store(false, Ordering::Relaxed);
+        pty.join().unwrap();
+        effect.join().unwrap();
+    }
+
+    #[bench]
+    fn render_under_load_max_read(b: &mut test::Bencher) {
+        render_under_load(b, MAX_READ);
+    }
+
+    #[bench]
+    fn render_under_load_single_read(b: &mut test::Bencher) {
+        render_under_load(b, READ_SIZE);
     }
 }
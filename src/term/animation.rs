@@ -1,102 +1,336 @@
+use std::cmp::{max, min};
+use std::mem;
 use std::thread;
 use std::sync::Arc;
+use std::time::Instant;
 
 use rand::Rng;
+use time;
 
 use super::{Term, Cell, Line, Column, Grid};
+use super::super::index::Point;
+use super::perf_hud;
 
 use super::super::display::Notifier;
 use super::super::sync::FairMutex;
 use super::super::term::cell::*;
-use super::super::ansi::Color;
-use super::super::Rgb;
+use super::super::ansi::{Color, Handler};
+use super::super::config::{Config, EffectMask, EffectSchedule, EffectStyle, TrailStyle};
+
+/// Number of animation ticks it takes a restored cell to fade back in.
+const FADE_TICKS: u8 = 6;
+
+/// Number of animation ticks it takes a refresh sweep to cross the screen.
+const SWEEP_TICKS: usize = 20;
+
+/// Share of columns which have to change at once to sweep instead of raining.
+const SWEEP_THRESHOLD: f32 = 0.5;
+
+/// Accented variants of lowercase letters, used by the case switcher trail.
+const ACCENTS: [(char, &str); 8] = [
+    ('a', "àáâãäå"),
+    ('c', "çćč"),
+    ('e', "èéêë"),
+    ('i', "ìíîï"),
+    ('n', "ñń"),
+    ('o', "òóôõöø"),
+    ('u', "ùúûü"),
+    ('y', "ýÿ"),
+];
+
+/// Bar sweeping from right to left across the screen after a large change.
+///
+/// Columns to the left of the bar still show the screen from before the change, the bar itself
+/// is drawn with the underline flag and reveals the new content behind it.
+#[derive(Clone, Debug)]
+pub struct Sweep {
+    pub column: usize,
+    /// Screen before the change, as columns of cells.
+    pub previous: Vec<Vec<Cell>>,
+}
+
+/// Number of banner characters typed per animation tick.
+const BANNER_CHARS_PER_TICK: usize = 1;
+
+/// Banner typed into the grid at startup, before the shell wrote anything.
+#[derive(Clone, Debug)]
+pub struct Banner {
+    text: Vec<char>,
+    typed: usize,
+}
+
+impl Banner {
+    pub fn new(text: &str) -> Self {
+        Banner { text: text.chars().collect(), typed: 0 }
+    }
+}
+
+/// A cell which has been restored by `undo` and is still fading back in.
+#[derive(Clone, Copy, Debug)]
+pub struct Fade {
+    pub point: Point,
+    pub ticks_left: u8,
+}
+
+impl Fade {
+    /// Opacity of the overlay covering the restored cell.
+    pub fn alpha(&self) -> f32 {
+        f32::from(self.ticks_left) / f32::from(FADE_TICKS)
+    }
+}
 
 #[derive(Clone)]
 pub struct MatrixUndo {
     pub tick: u64,
     pub last_change_detected: u64,
     pub original_columns: Vec<Vec<Cell>>,
+    /// Hash of every column in `original_columns`.
+    pub column_hashes: Vec<u64>,
+    /// Hash of every line in `original_columns`.
+    pub line_hashes: Vec<u64>,
     pub columns: Vec<Vec<(Cell, bool)>>,
+    pub fades: Vec<Fade>,
+    /// Refresh sweep which is drawn over the grid instead of the rain.
+    pub sweep: Option<Sweep>,
+    /// Grid generation the columns were generated for.
+    pub generation: usize,
+    pub trail_style: TrailStyle,
+    pub masks: Vec<EffectMask>,
+    /// Lines of the grid matching one of the `masks` when the effect was generated.
+    pub masked_lines: Vec<bool>,
+    pub schedule: EffectSchedule,
+    /// Run effects even outside of the `schedule`.
+    pub forced: bool,
+    pub style: EffectStyle,
 }
 
 impl MatrixUndo {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
         MatrixUndo {
             tick: 0,
             last_change_detected: 0,
             original_columns: vec![],
+            column_hashes: vec![],
+            line_hashes: vec![],
             columns: vec![],
+            fades: vec![],
+            sweep: None,
+            generation: 0,
+            trail_style: config.trail_style(),
+            masks: config.effect_masks().to_vec(),
+            masked_lines: vec![],
+            schedule: config.effect_schedule().clone(),
+            forced: false,
+            style: config.effect_style(),
         }
     }
+
+    pub fn update_config(&mut self, config: &Config) {
+        self.trail_style = config.trail_style();
+        self.masks = config.effect_masks().to_vec();
+        self.schedule = config.effect_schedule().clone();
+        self.style = config.effect_style();
+    }
+
+    /// Check if new effects may start, according to the schedule and its override.
+    pub fn is_active(&self) -> bool {
+        self.forced || self.schedule.is_active(&time::now())
+    }
+
+    /// Check if effects have to leave `line` alone.
+    #[inline]
+    pub fn is_masked(&self, line: usize) -> bool {
+        self.masked_lines.get(line).cloned().unwrap_or(false)
+    }
 }
 
 pub fn undo(term: &mut Term)
 {
+    // The sweep never touches the grid, so new content only has to stop being covered
+    if term.undo.sweep.take().is_some() {
+        term.damage.damage_all();
+    }
+
     if term.undo.columns.is_empty() {
         return;
     }
+    if is_stale(term) {
+        // The frame was computed for other dimensions, so restoring it could write out of bounds.
+        term.undo.columns.clear();
+        term.undo.original_columns.clear();
+        term.undo.column_hashes.clear();
+        term.undo.line_hashes.clear();
+        return;
+    }
     term.undo.last_change_detected = term.undo.tick;
     let orig = &term.undo.original_columns.clone();
     let columns = &term.undo.columns.clone();
+    let masked_lines = term.undo.masked_lines.clone();
+    let mut fades = Vec::new();
     let grid = term.grid_mut();
     let height = grid.num_lines().0;
     let width = grid.num_cols().0;
     if !orig.is_empty() {
         for col_index in 0..width {
             let col = &columns[col_index];
-            for row_index in 0..height {
+            for row_index in (0..height).filter(|&row| masked_lines.get(row) != Some(&true)) {
                 let relative_index = std::cmp::max(col.len() - height, 0) + row_index;
 
                 let (matrix_ch, _real) = columns[col_index][relative_index];
-                let current_screen_buffer_ch = grid[Line(row_index)][Column(col_index)].c;
+                let current_screen_buffer_ch = grid.get(Line(row_index), Column(col_index))
+                    .map(|cell| cell.c);
                 let original_ch = orig[col_index][row_index];
 
-                if current_screen_buffer_ch == matrix_ch.c && matrix_ch.c != original_ch.c {
+                if current_screen_buffer_ch == Some(matrix_ch.c) && matrix_ch.c != original_ch.c {
                     //This char hasn't changed other than by us (probably?)
                     // - we should change it back to what it was...
-                    grid[Line(row_index)][Column(col_index)] = orig[col_index][row_index];
+                    if let Some(cell) = grid.get_mut(Line(row_index), Column(col_index)) {
+                        *cell = original_ch;
+                    }
+                    fades.push(Fade {
+                        point: Point::new(Line(row_index), Column(col_index)),
+                        ticks_left: FADE_TICKS,
+                    });
                 }
             }
         }
     }
 
+    term.undo.fades.append(&mut fades);
     term.undo.columns.clear();
 }
 
+/// Check if the grid has been resized since the animation frames were generated.
+fn is_stale(term: &Term) -> bool {
+    term.undo.generation != term.grid().generation()
+}
+
+/// Advance the crossfade of cells restored by `undo`, dropping finished fades.
+fn fade_step(term: &mut Term) {
+    for fade in &mut term.undo.fades {
+        fade.ticks_left -= 1;
+    }
+    term.undo.fades.retain(|fade| fade.ticks_left > 0);
+}
+
+/// Type the next `count` characters of the startup banner, or all of the remaining ones.
+///
+/// The cursor is moved to the start of the next line once the banner is complete, so the shell
+/// prompt shows up below it.
+pub fn type_banner(term: &mut Term, count: Option<usize>) {
+    let mut banner = match term.banner.take() {
+        Some(banner) => banner,
+        None => return,
+    };
+
+    let end = count.map_or(banner.text.len(), |count| min(banner.typed + count, banner.text.len()));
+    for &c in &banner.text[banner.typed..end] {
+        if c == '\n' {
+            term.carriage_return();
+            term.linefeed();
+        } else {
+            term.input(c);
+        }
+    }
+    banner.typed = end;
+
+    if banner.typed == banner.text.len() {
+        term.carriage_return();
+        term.linefeed();
+    } else {
+        term.banner = Some(banner);
+    }
+}
+
+/// Move the refresh sweep to the left, ending it once it crossed the first column.
+fn sweep_step(term: &mut Term) {
+    let step = max(term.grid().num_cols().0 / SWEEP_TICKS, 1);
+    let finished = match term.undo.sweep {
+        Some(ref mut sweep) if sweep.column >= step => {
+            sweep.column -= step;
+            false
+        },
+        Some(_) => true,
+        None => false,
+    };
+
+    if finished {
+        term.undo.sweep = None;
+    }
+}
+
+/// Check if enough columns changed since the last snapshot to sweep over the screen.
+fn starts_sweep(term: &Term, hashes: &[u64]) -> bool {
+    let changed = hashes
+        .iter()
+        .zip(&term.undo.column_hashes)
+        .filter(|(hash, previous)| hash != previous)
+        .count();
+
+    !term.undo.original_columns.is_empty()
+        && hashes.len() == term.undo.column_hashes.len()
+        && changed as f32 >= hashes.len() as f32 * SWEEP_THRESHOLD
+}
+
 
 /// Trail styles that could be?:
 ///    * random alphanumerics (actual char at end)
-///    * case switcher
+///    * case switcher (see `TrailStyle::CaseSwitcher`)
 ///    * lazer left-right art deco criss cross????
-///    * left to right refresh using underscore flag as a line that goes across....
+///    * right to left refresh using underline flag as a line that goes across (see `Sweep`)
 ///
 fn screen_shot(grid: &Grid<Cell>) -> Vec<Vec<Cell>> {
-    let mut original_columns = vec![];
-    let width = grid.num_cols().0;
-    let height = grid.num_lines().0;
+    grid.columns().map(|column| column.iter().cloned().collect()).collect()
+}
 
-    for col_index in 0..width {
-        let mut column = Vec::new();
-        for row in 0..height {
-            column.push(grid[Line(row)][Column(col_index)].clone());
+/// Find the lines of the grid which belong to a logical line matching one of the masks.
+fn masked_lines(grid: &Grid<Cell>, masks: &[EffectMask]) -> Vec<bool> {
+    let mut masked = vec![false; grid.num_lines().0];
+    if masks.is_empty() {
+        return masked;
+    }
+
+    for logical_line in grid.logical_lines() {
+        let mut text = String::new();
+        for line in logical_line.start.0..logical_line.end.0 {
+            let row = &grid[Line(line)];
+            let cells = (0..row.line_length().0).map(|col| &row[Column(col)]);
+            let cells = cells.filter(|cell| !cell.flags.contains(Flags::WIDE_CHAR_SPACER));
+            text.extend(cells.map(|cell| cell.c));
+        }
+
+        if masks.iter().any(|mask| mask.is_match(&text)) {
+            for line in logical_line.start.0..logical_line.end.0 {
+                masked[line] = true;
+            }
         }
-        original_columns.push(column);
     }
-    original_columns
+
+    masked
+}
+
+/// Hash every column of the grid to cheaply detect which ones changed since the snapshot.
+fn column_hashes(grid: &Grid<Cell>) -> Vec<u64> {
+    grid.columns().map(|column| column.hash_value()).collect()
 }
 
 /// Compare a previous snapshot to the current grid and find the lowest row for each column where
 /// there is a difference.
-fn calc_lowest_char_changed_per_col(grid: &Grid<Cell>, orig: &Vec<Vec<Cell>>) -> Vec<usize> {
+fn calc_lowest_char_changed_per_col(grid: &Grid<Cell>, orig: &Vec<Vec<Cell>>, hashes: &[u64])
+    -> Vec<usize>
+{
     let height = grid.num_lines().0;
     let width = grid.num_cols().0;
     let mut lowest_char_changed_per_col = Vec::with_capacity(width);
-    for col_index in 0..width {
+    for column in grid.columns() {
+        let col_index = column.col().0;
         let mut index = height - 1;
-        if !orig.is_empty() {
+        let unchanged = hashes.get(col_index) == Some(&column.hash_value());
+        if !orig.is_empty() && !unchanged {
             let col = &orig[col_index];
             for row_index in (0..col.len()).rev() {
-                if grid[Line(row_index)][Column(col_index)].c != col[row_index].c {
+                let current = column.get(Line(row_index)).map(|cell| cell.c);
+                if current != Some(col[row_index].c) {
                     index = row_index;
                     break;//todo: functional style
                 }
@@ -111,44 +345,109 @@ pub fn start_animation_thread(c_term: Arc<FairMutex<Term>>, notifier: Notifier)
     thread::spawn(move || {
         loop {
             thread::sleep(std::time::Duration::from_millis(40));//lower this as height increases...
+
+            // Stop animating once the window of the terminal was closed
+            if Arc::strong_count(&c_term) == 1 {
+                break;
+            }
+
             // Process input and window events
             {
                 let mut term = (*c_term).lock();
+                let step_start = Instant::now();
                 term.undo.tick += 1;
-                let width = term.grid().num_cols().0;
-                let height = term.grid().num_lines().0;
-
-                if !term.undo.columns.is_empty() {
-                    let has_been_resized = term.undo.columns.len() != width ||
-                        term.undo.columns[0].iter().filter(|(_ch, real)| *real).count() != height;
-
-                    if has_been_resized {
-                        //RESET
-                        //term_lock.undo(); - would be nice but undo would need to deal with resize.
-                        term.undo.columns.clear();
-                        term.undo.original_columns = screen_shot(term.grid());
-                    }
+
+                if is_stale(&term) {
+                    // Discard the stale frame, its indices don't match the grid anymore
+                    term.undo.columns.clear();
+                    term.undo.fades.clear();
+                    term.undo.sweep = None;
+                    term.undo.original_columns = screen_shot(term.grid());
+                    term.undo.column_hashes = column_hashes(term.grid());
+                    term.undo.line_hashes = term.grid().line_hashes();
+                    term.undo.generation = term.grid().generation();
                 }
 
-                if term.undo.columns.is_empty() && term.undo.last_change_detected + 4 <= term.undo.tick {
-                    let lowest_char_changed_per_col = calc_lowest_char_changed_per_col(
-                        term.grid(), &term.undo.original_columns);
+                if term.undo.columns.is_empty()
+                    && term.undo.sweep.is_none()
+                    && term.undo.last_change_detected + 4 <= term.undo.tick
+                {
+                    let line_hashes = term.grid().line_hashes();
+                    let hashes = column_hashes(term.grid());
+                    let unchanged = line_hashes == term.undo.line_hashes;
+                    let sweep = !unchanged && starts_sweep(&term, &hashes);
+                    let lowest_char_changed_per_col = if unchanged {
+                        // Nothing changed since the last snapshot
+                        vec![term.grid().num_lines().0 - 1; term.grid().num_cols().0]
+                    } else {
+                        calc_lowest_char_changed_per_col(
+                            term.grid(), &term.undo.original_columns, &term.undo.column_hashes)
+                    };
 
                     //Must be set after calc lowest char......
-                    term.undo.original_columns = screen_shot(term.grid());
+                    let previous = mem::replace(
+                        &mut term.undo.original_columns, screen_shot(term.grid()));
+                    term.undo.column_hashes = hashes;
+                    term.undo.line_hashes = line_hashes;
+
+                    term.undo.masked_lines = masked_lines(term.grid(), &term.undo.masks);
+                    if !term.undo.is_active() {
+                        // The snapshot is still updated, so changes aren't replayed later
+                    } else if sweep {
+                        let column = term.grid().num_cols().0 - 1;
+                        term.undo.sweep = Some(Sweep { column, previous });
+                    } else {
+                        term.undo.columns =
+                            generate_animation_script(&mut term, &lowest_char_changed_per_col);
+                    }
+                }
 
-                    term.undo.columns = generate_animation_script(&mut term, &lowest_char_changed_per_col)
+                // Overlays which end during this tick still have to be removed from the screen
+                let sweeping = term.undo.sweep.is_some();
+                let fading: Vec<Line> =
+                    term.undo.fades.iter().map(|fade| fade.point.line).collect();
+
+                type_banner(&mut term, Some(BANNER_CHARS_PER_TICK));
+                for _ in 0..term.undo.style.speed {
+                    step(&mut term);
+                    fade_step(&mut term);
+                    sweep_step(&mut term);
                 }
 
-                step(&mut term);
+                if let Some(ref mut perf_hud) = term.perf_hud {
+                    perf_hud.animated(step_start.elapsed());
+                }
 
-                notifier.notify();
-                term.dirty = true;
+                damage_tick(&mut term, sweeping, &fading);
+                if term.needs_draw() {
+                    notifier.notify();
+                }
             }
         }
     });
 }
 
+/// Damage what an animation tick changed, ticks without visible changes don't cause a redraw
+fn damage_tick(term: &mut Term, sweeping: bool, fading: &[Line]) {
+    // Trails, restored cells and the banner are written to the grid
+    term.flush_grid_damage();
+
+    // The sweep bar and the covers of fading cells are drawn over the grid
+    if sweeping || (!fading.is_empty() && term.grid().display_offset() != 0) {
+        term.damage.damage_all();
+        return;
+    }
+    for &line in fading {
+        term.damage.damage_line(line);
+    }
+
+    // Keep the performance HUD updating
+    if term.perf_hud.is_some() {
+        let lines = min(perf_hud::LINES, term.grid().num_lines().0);
+        term.damage.damage_lines(Line(0)..Line(lines));
+    }
+}
+
 //
 // Below are functions specific to the matrix effect.
 //
@@ -156,33 +455,30 @@ pub fn start_animation_thread(c_term: Arc<FairMutex<Term>>, notifier: Notifier)
 fn generate_animation_script(term: &Term, lowest_char_changed_per_col: &Vec<usize>)
                              -> Vec<Vec<(Cell, bool)>>
 {
-    let width = term.grid().num_cols().0;
-    let height = term.grid().num_lines().0;
     let mut results = vec![];
-    for col_index in 0..width {
+    for grid_column in term.grid().columns() {
+        let col_index = grid_column.col().0;
         let mut column = Vec::new();
 
-        for row_index in 0..height {
-            let cell = term.grid()[Line(row_index)][Column(col_index)];
-            column.push((cell.clone(), true));
+        for (row_index, &cell) in grid_column.iter().enumerate() {
+            column.push((cell, true));
 
             //Add random chars...
             if cell.c != ' ' && row_index < lowest_char_changed_per_col[col_index]
             {
                 //TODO less random chars if many chars on that column relative to spaces....
-                let ran_char_count = rand::thread_rng().gen_range(2, 10);
+                let density = usize::from(term.undo.style.density);
+                let ran_char_count = rand::thread_rng().gen_range(2, 2 + density);
                 for i in 0..ran_char_count
                     {
-                        let ch_int: u8 = rand::thread_rng()
-                            .gen_range(31, 126);
+                        let ch = match term.undo.trail_style {
+                            TrailStyle::Random => rand::thread_rng().gen_range(31u8, 126) as char,
+                            TrailStyle::CaseSwitcher => switch_case(cell.c, i),
+                        };
 
-                        let mut rnd_char = Cell::new(ch_int as char,
-                                                     Color::Spec(Rgb {
-                                                         r: 0,
-                                                         g: (150 + (ran_char_count - i) * 10),
-                                                         b: 0,
-                                                     }),
-                                                     cell.bg);
+                        let brightness = min(255, 150 + (ran_char_count - i) * 10) as u8;
+                        let color = term.undo.style.palette.color(brightness);
+                        let mut rnd_char = Cell::new(ch, Color::Spec(color), cell.bg);
 
                         if rand::thread_rng().gen_bool(0.2) {
                             rnd_char.flags = rnd_char.flags | Flags::BOLD;
@@ -203,6 +499,49 @@ fn generate_animation_script(term: &Term, lowest_char_changed_per_col: &Vec<usiz
     results
 }
 
+/// Variant of `c` in the case switcher trail, `index` cells above the real character
+///
+/// Letters alternate between upper and lower case, with a random accent on every other one
+/// which has accented variants. Characters without case stay the same.
+fn switch_case(c: char, index: usize) -> char {
+    if !c.is_lowercase() && !c.is_uppercase() {
+        return c;
+    }
+
+    let lower = to_case(c, false);
+    let accents = ACCENTS
+        .iter()
+        .find(|(base, accented)| *base == lower || accented.contains(lower));
+    let variant = match accents {
+        Some(&(base, accented)) => {
+            let mut rng = rand::thread_rng();
+            if rng.gen_bool(0.5) {
+                let count = accented.chars().count();
+                accented.chars().nth(rng.gen_range(0, count)).unwrap_or(base)
+            } else {
+                base
+            }
+        },
+        None => lower,
+    };
+
+    to_case(variant, index % 2 == 0)
+}
+
+/// Convert `c` to upper or lower case, keeping it if the conversion isn't a single character.
+fn to_case(c: char, upper: bool) -> char {
+    let mut converted: Vec<char> = if upper {
+        c.to_uppercase().collect()
+    } else {
+        c.to_lowercase().collect()
+    };
+
+    match converted.len() {
+        1 => converted.remove(0),
+        _ => c,
+    }
+}
+
 fn step(term: &mut Term) -> () {
     let width = term.grid().num_cols().0;
     let height = term.grid().num_lines().0;
@@ -229,13 +568,203 @@ fn step(term: &mut Term) -> () {
         for col_index in 0..width {
             let col_len = &term.undo.columns[col_index].len();
             for row in 0..height {
+                if term.undo.is_masked(row) {
+                    continue;
+                }
+
                 let relative_index = (col_len - height) + row;
                 let (ch, _real) = term.undo.columns[col_index][relative_index];
-                let cell = &term.grid()[Line(row)][Column(col_index)];
-                if cell.c != ch.c {
-                    term.grid_mut()[Line(row)][Column(col_index)] = ch;
+                let changed = term.grid().get(Line(row), Column(col_index))
+                    .map_or(false, |cell| cell.c != ch.c);
+                if changed {
+                    if let Some(cell) = term.grid_mut().get_mut(Line(row), Column(col_index)) {
+                        *cell = ch;
+                    }
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::index::{Column, Line, Point};
+    use crate::term::cell::Flags;
+    use crate::term::damage::{self, DamageTracker};
+    use crate::term::{SizeInfo, Term};
+
+    use super::{column_hashes, damage_tick, screen_shot, starts_sweep, sweep_step, switch_case};
+    use super::type_banner;
+    use super::{Sweep, ACCENTS};
+
+    /// Terminal with 20 columns and 5 lines.
+    fn term() -> Term {
+        let size = SizeInfo {
+            width: 60.0,
+            height: 15.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0,
+        };
+        Term::new(&Config::default(), size)
+    }
+
+    /// Remember the current grid like the animation thread does after a change.
+    fn snapshot(term: &mut Term) {
+        term.undo.original_columns = screen_shot(term.grid());
+        term.undo.column_hashes = column_hashes(term.grid());
+    }
+
+    #[test]
+    fn idle_tick_needs_no_redraw() {
+        let mut term = term();
+        term.flush_damage();
+        term.damage.reset(DamageTracker::RENDERER);
+
+        damage_tick(&mut term, false, &[]);
+        assert!(!term.needs_draw());
+
+        // Only lines written by the animation are redrawn
+        term.grid_mut()[Line(3)][Column(2)].c = 'x';
+        damage_tick(&mut term, false, &[Line(1)]);
+        let taken = term.damage.take(DamageTracker::RENDERER);
+        let lines = damage::damaged_lines(&taken, Line(5));
+        assert_eq!(lines, Some(vec![Line(1)..Line(2), Line(3)..Line(4)]));
+
+        // The sweep changes every line
+        damage_tick(&mut term, true, &[]);
+        let taken = term.damage.take(DamageTracker::RENDERER);
+        assert_eq!(damage::damaged_lines(&taken, Line(5)), None);
+    }
+
+    #[test]
+    fn sweep_moves_left_until_first_column() {
+        let mut term = term();
+        term.undo.sweep = Some(Sweep { column: 2, previous: vec![] });
+
+        sweep_step(&mut term);
+        assert_eq!(term.undo.sweep.as_ref().map(|sweep| sweep.column), Some(1));
+        sweep_step(&mut term);
+        assert_eq!(term.undo.sweep.as_ref().map(|sweep| sweep.column), Some(0));
+        sweep_step(&mut term);
+        assert!(term.undo.sweep.is_none());
+
+        // Stepping without a sweep does nothing
+        sweep_step(&mut term);
+        assert!(term.undo.sweep.is_none());
+    }
+
+    #[test]
+    fn sweep_starts_when_most_columns_changed() {
+        let mut term = term();
+
+        // There is nothing to sweep away without a snapshot
+        term.grid_mut()[Line(0)][Column(0)].c = 'x';
+        assert!(!starts_sweep(&term, &column_hashes(term.grid())));
+
+        snapshot(&mut term);
+        assert!(!starts_sweep(&term, &column_hashes(term.grid())));
+
+        for col in 0..5 {
+            term.grid_mut()[Line(1)][Column(col)].c = 'y';
+        }
+        assert!(!starts_sweep(&term, &column_hashes(term.grid())));
+
+        for col in 5..10 {
+            term.grid_mut()[Line(1)][Column(col)].c = 'y';
+        }
+        let hashes = column_hashes(term.grid());
+        assert!(starts_sweep(&term, &hashes));
+
+        // Hashes of a resized grid can't be compared
+        assert!(!starts_sweep(&term, &hashes[1..]));
+    }
+
+    #[test]
+    fn sweep_cells_show_previous_screen_left_of_bar() {
+        let mut term = term();
+        assert!(term.sweep_cells().is_none());
+
+        term.grid_mut()[Line(0)][Column(0)].c = 'a';
+        let previous = screen_shot(term.grid());
+        for col in 0..20 {
+            term.grid_mut()[Line(0)][Column(col)].c = 'b';
+        }
+        term.undo.sweep = Some(Sweep { column: 1, previous });
+
+        let (column, cells) = term.sweep_cells().unwrap();
+        assert_eq!(column, Column(1));
+
+        // Empty cells of the previous screen are left to the grid
+        let left: Vec<_> = cells.iter().filter(|cell| cell.column == Column(0)).collect();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].chars[0], 'a');
+
+        // The bar covers every line and shows the new content
+        let bar: Vec<_> = cells.iter().filter(|cell| cell.column == Column(1)).collect();
+        assert_eq!(bar.len(), 5);
+        assert!(bar.iter().all(|cell| cell.flags.contains(Flags::UNDERLINE)));
+        assert_eq!(bar[0].chars[0], 'b');
+        assert!(cells.iter().all(|cell| cell.column <= Column(1)));
+
+        // Masked lines are not swept
+        term.undo.masked_lines = vec![true; 5];
+        let (_, cells) = term.sweep_cells().unwrap();
+        assert!(cells.is_empty());
+    }
+
+    #[test]
+    fn output_finishes_banner() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0,
+        };
+        let mut term = Term::new(&Config::default(), size);
+        term.start_banner("ab\ncd");
+
+        type_banner(&mut term, Some(2));
+        assert_eq!(term.grid()[Line(0)][Column(1)].c, 'b');
+        assert_eq!(term.grid()[Line(1)][Column(0)].c, ' ');
+
+        term.finish_banner();
+        assert_eq!(term.grid()[Line(1)][Column(1)].c, 'd');
+        assert_eq!(term.cursor().point, Point::new(Line(2), Column(0)));
+
+        // The banner is only typed once
+        term.finish_banner();
+        assert_eq!(term.cursor().point, Point::new(Line(2), Column(0)));
+    }
+
+    #[test]
+    fn case_switcher_keeps_characters_without_case() {
+        for &c in &['1', '#', ' ', '─', '漢'] {
+            assert_eq!(switch_case(c, 0), c);
+            assert_eq!(switch_case(c, 1), c);
+        }
+    }
+
+    #[test]
+    fn case_switcher_alternates_case() {
+        for _ in 0..20 {
+            assert_eq!(switch_case('x', 0), 'X');
+            assert_eq!(switch_case('X', 1), 'x');
+            assert_eq!(switch_case('ß', 0), 'ß');
+
+            let (_, accented) = ACCENTS[0];
+            let upper = switch_case('a', 0);
+            let lower = switch_case('A', 1);
+            assert!(upper == 'A' || accented.to_uppercase().contains(upper));
+            assert!(lower == 'a' || accented.contains(lower));
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Scrolling the viewport by dragging with the middle mouse button
+//!
+//! The further the mouse is dragged away from where the button was pressed, the faster the
+//! viewport scrolls in that direction.
+use std::time::Instant;
+
+use crate::index::Point;
+
+/// Lines scrolled per second for every line the mouse is away from the origin
+const LINES_PER_SECOND: f32 = 4.;
+
+/// Direction the viewport is being scrolled in
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    /// The mouse is too close to the origin to scroll
+    None,
+    Up,
+    Down,
+}
+
+/// Middle button autoscroll state
+#[derive(Debug, Clone)]
+pub struct Autoscroll {
+    /// Cell the mouse was in when the button was pressed
+    pub origin: Point,
+
+    /// Vertical mouse position when the button was pressed, in pixels
+    origin_y: usize,
+
+    /// Scroll speed in lines per second, positive values scroll up into the history
+    velocity: f32,
+
+    /// Fraction of a line which hasn't been scrolled yet
+    pending: f32,
+
+    /// Set once the mouse was dragged out of the origin cell
+    dragged: bool,
+
+    last_update: Instant,
+}
+
+impl Autoscroll {
+    pub fn new(origin: Point, origin_y: usize) -> Autoscroll {
+        Autoscroll {
+            origin,
+            origin_y,
+            velocity: 0.,
+            pending: 0.,
+            dragged: false,
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Update the scroll speed for the current vertical mouse position
+    ///
+    /// Positions within half a cell of the origin don't scroll at all.
+    pub fn mouse_moved(&mut self, y: usize, cell_height: f32) {
+        let distance = (self.origin_y as f32 - y as f32) / cell_height;
+        let dead_zone = 0.5;
+
+        self.velocity = if distance.abs() <= dead_zone {
+            0.
+        } else {
+            self.dragged = true;
+            (distance - dead_zone * distance.signum()) * LINES_PER_SECOND
+        };
+    }
+
+    /// Number of lines to scroll since the last tick
+    pub fn tick(&mut self) -> isize {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update);
+        self.last_update = now;
+
+        let seconds = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1e9;
+        self.pending += self.velocity * seconds;
+
+        let lines = self.pending.trunc();
+        self.pending -= lines;
+        lines as isize
+    }
+
+    #[inline]
+    pub fn direction(&self) -> Direction {
+        if self.velocity > 0. {
+            Direction::Up
+        } else if self.velocity < 0. {
+            Direction::Down
+        } else {
+            Direction::None
+        }
+    }
+
+    /// Whether the mouse was moved away from the origin since the button was pressed
+    #[inline]
+    pub fn dragged(&self) -> bool {
+        self.dragged
+    }
+
+    /// Character drawn at the origin to indicate the scroll direction
+    pub fn indicator(&self) -> char {
+        match self.direction() {
+            Direction::None => '↕',
+            Direction::Up => '↑',
+            Direction::Down => '↓',
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Autoscroll, Direction};
+
+    use crate::index::{Column, Line, Point};
+
+    #[test]
+    fn velocity_follows_drag_distance() {
+        let mut autoscroll = Autoscroll::new(Point::new(Line(5), Column(3)), 100);
+        assert_eq!(autoscroll.direction(), Direction::None);
+
+        // Moving within the dead zone doesn't scroll
+        autoscroll.mouse_moved(104, 10.);
+        assert_eq!(autoscroll.direction(), Direction::None);
+        assert!(!autoscroll.dragged());
+
+        autoscroll.mouse_moved(60, 10.);
+        assert_eq!(autoscroll.direction(), Direction::Up);
+        assert_eq!(autoscroll.velocity, 3.5 * super::LINES_PER_SECOND);
+
+        autoscroll.mouse_moved(200, 10.);
+        assert_eq!(autoscroll.direction(), Direction::Down);
+        assert!(autoscroll.dragged());
+    }
+}
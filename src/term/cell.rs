@@ -14,7 +14,7 @@
 use bitflags::bitflags;
 
 use crate::ansi::{NamedColor, Color};
-use crate::grid;
+use crate::grid::{self, GridCell};
 use crate::index::Column;
 
 // Maximum number of zerowidth characters which will be stored per cell.
@@ -23,17 +23,22 @@ pub const MAX_ZEROWIDTH_CHARS: usize = 5;
 bitflags! {
     #[derive(Serialize, Deserialize)]
     pub struct Flags: u16 {
-        const INVERSE           = 0b00_0000_0001;
-        const BOLD              = 0b00_0000_0010;
-        const ITALIC            = 0b00_0000_0100;
-        const UNDERLINE         = 0b00_0000_1000;
-        const WRAPLINE          = 0b00_0001_0000;
-        const WIDE_CHAR         = 0b00_0010_0000;
-        const WIDE_CHAR_SPACER  = 0b00_0100_0000;
-        const DIM               = 0b00_1000_0000;
-        const DIM_BOLD          = 0b00_1000_0010;
-        const HIDDEN            = 0b01_0000_0000;
-        const STRIKEOUT         = 0b10_0000_0000;
+        const INVERSE           = 0b0000_0000_0001;
+        const BOLD              = 0b0000_0000_0010;
+        const ITALIC            = 0b0000_0000_0100;
+        const UNDERLINE         = 0b0000_0000_1000;
+        const WRAPLINE          = 0b0000_0001_0000;
+        const WIDE_CHAR         = 0b0000_0010_0000;
+        const WIDE_CHAR_SPACER  = 0b0000_0100_0000;
+        const DIM               = 0b0000_1000_0000;
+        const DIM_BOLD          = 0b0000_1000_0010;
+        const HIDDEN            = 0b0001_0000_0000;
+        const STRIKEOUT         = 0b0010_0000_0000;
+        const BLINK_SLOW        = 0b0100_0000_0000;
+        const BLINK_FAST        = 0b1000_0000_0000;
+        const BLINK             = 0b1100_0000_0000;
+        const PASTED            = 0b1_0000_0000_0000;
+        const LEADING_WIDE_CHAR_SPACER = 0b10_0000_0000_0000;
     }
 }
 
@@ -41,7 +46,7 @@ const fn default_extra() -> [char; MAX_ZEROWIDTH_CHARS] {
     [' '; MAX_ZEROWIDTH_CHARS]
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub struct Cell {
     pub c: char,
     pub fg: Color,
@@ -62,6 +67,39 @@ impl Default for Cell {
 
 }
 
+impl GridCell for Cell {
+    #[inline]
+    fn is_empty(&self) -> bool {
+        Cell::is_empty(self)
+    }
+
+    #[inline]
+    fn is_wrap(&self) -> bool {
+        self.flags.contains(Flags::WRAPLINE)
+    }
+
+    #[inline]
+    fn set_wrap(&mut self, wrap: bool) {
+        self.flags.set(Flags::WRAPLINE, wrap);
+    }
+
+    #[inline]
+    fn is_wide(&self) -> bool {
+        self.flags.contains(Flags::WIDE_CHAR)
+    }
+
+    #[inline]
+    fn is_leading_spacer(&self) -> bool {
+        self.flags.contains(Flags::LEADING_WIDE_CHAR_SPACER)
+    }
+
+    #[inline]
+    fn set_leading_spacer(&mut self) {
+        self.c = ' ';
+        self.flags.insert(Flags::LEADING_WIDE_CHAR_SPACER);
+    }
+}
+
 /// Get the length of occupied cells in a line
 pub trait LineLength {
     /// Calculate the occupied line length
@@ -76,10 +114,10 @@ impl LineLength for grid::Row<Cell> {
             return Column(self.len());
         }
 
-        for (index, cell) in self[..].iter().rev().enumerate() {
+        // Packed rows can't be iterated in reverse
+        for (index, cell) in self.iter().enumerate() {
             if cell.c != ' ' || cell.extra[0] != ' ' {
-                length = Column(self.len() - index);
-                break;
+                length = Column(index + 1);
             }
         }
 
@@ -141,6 +179,12 @@ impl Cell {
         }
     }
 
+    /// Last character of the grapheme cluster stored in this cell
+    #[inline]
+    pub fn last_char(&self) -> char {
+        self.extra.iter().rev().find(|c| **c != ' ').cloned().unwrap_or(self.c)
+    }
+
     #[inline]
     pub fn push_extra(&mut self, c: char) {
         for elem in self.extra.iter_mut() {
@@ -2,10 +2,14 @@ use std::ops::{Index, IndexMut};
 use std::fmt;
 
 use crate::{Rgb, ansi};
-use crate::config::Colors;
+use crate::ansi::{Color, NamedColor};
+use crate::config::{Colors, CursorColors, SelectionColors};
 
 pub const COUNT: usize = 270;
 
+/// Factor for faint colors which aren't part of the dim palette
+pub const DIM_FACTOR: f32 = 0.66;
+
 /// List of indexed colors
 ///
 /// The first 16 entries are the standard ansi named colors. Items 16..232 are
@@ -17,6 +21,38 @@ pub const COUNT: usize = 270;
 #[derive(Copy, Clone)]
 pub struct List([Rgb; COUNT]);
 
+/// Colors of the config or a color scheme which are only used when they're set
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Optional {
+    pub cursor: CursorColors,
+    pub selection: SelectionColors,
+    pub bright_foreground: Option<Rgb>,
+}
+
+impl<'a> From<&'a Colors> for Optional {
+    fn from(colors: &Colors) -> Optional {
+        Optional {
+            cursor: colors.cursor,
+            selection: colors.selection,
+            bright_foreground: colors.primary.bright_foreground,
+        }
+    }
+}
+
+impl Optional {
+    /// Cursor foreground color
+    #[inline]
+    pub fn cursor_text_color(&self) -> Option<Color> {
+        self.cursor.text.map(|_| Color::Named(NamedColor::CursorText))
+    }
+
+    /// Cursor background color
+    #[inline]
+    pub fn cursor_cursor_color(&self) -> Option<Color> {
+        self.cursor.cursor.map(|_| Color::Named(NamedColor::Cursor))
+    }
+}
+
 impl<'a> From<&'a Colors> for List {
     fn from(colors: &Colors) -> List {
         // Type inference fails without this annotation
@@ -68,7 +104,7 @@ impl List {
         self[ansi::NamedColor::DimForeground] = colors
             .primary
             .dim_foreground
-            .unwrap_or(colors.primary.foreground * 0.66);
+            .unwrap_or(colors.primary.foreground * DIM_FACTOR);
         match colors.dim {
             Some(ref dim) => {
                 trace!("Using config-provided dim colors");
@@ -83,14 +119,14 @@ impl List {
             }
             None => {
                 trace!("Deriving dim colors from normal colors");
-                self[ansi::NamedColor::DimBlack]   = colors.normal.black   * 0.66;
-                self[ansi::NamedColor::DimRed]     = colors.normal.red     * 0.66;
-                self[ansi::NamedColor::DimGreen]   = colors.normal.green   * 0.66;
-                self[ansi::NamedColor::DimYellow]  = colors.normal.yellow  * 0.66;
-                self[ansi::NamedColor::DimBlue]    = colors.normal.blue    * 0.66;
-                self[ansi::NamedColor::DimMagenta] = colors.normal.magenta * 0.66;
-                self[ansi::NamedColor::DimCyan]    = colors.normal.cyan    * 0.66;
-                self[ansi::NamedColor::DimWhite]   = colors.normal.white   * 0.66;
+                self[ansi::NamedColor::DimBlack]   = colors.normal.black   * DIM_FACTOR;
+                self[ansi::NamedColor::DimRed]     = colors.normal.red     * DIM_FACTOR;
+                self[ansi::NamedColor::DimGreen]   = colors.normal.green   * DIM_FACTOR;
+                self[ansi::NamedColor::DimYellow]  = colors.normal.yellow  * DIM_FACTOR;
+                self[ansi::NamedColor::DimBlue]    = colors.normal.blue    * DIM_FACTOR;
+                self[ansi::NamedColor::DimMagenta] = colors.normal.magenta * DIM_FACTOR;
+                self[ansi::NamedColor::DimCyan]    = colors.normal.cyan    * DIM_FACTOR;
+                self[ansi::NamedColor::DimWhite]   = colors.normal.white   * DIM_FACTOR;
             }
         }
     }
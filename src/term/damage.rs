@@ -0,0 +1,252 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Tracking of changed screen regions
+//!
+//! Every consumer of the damage subscribes once and keeps its own position in the stream of
+//! damaged regions, so one consumer taking the damage doesn't hide it from the others.
+use std::cmp::{max, min};
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use crate::index::Line;
+
+/// Number of regions kept before lagging subscribers fall back to full damage
+const MAX_REGIONS: usize = 1024;
+
+/// Part of the screen which has changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Damage {
+    /// Lines of the active region
+    Lines(Range<Line>),
+
+    /// The entire screen
+    Full,
+}
+
+/// Handle of a damage consumer, created by `DamageTracker::subscribe`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subscriber(usize);
+
+#[derive(Debug)]
+struct Cursor {
+    name: &'static str,
+
+    /// Sequence number of the first region which hasn't been taken yet
+    next: u64,
+
+    /// Everything changed since the damage was last taken
+    full: bool,
+}
+
+/// Stream of damaged regions with an independent cursor for every subscriber
+#[derive(Debug)]
+pub struct DamageTracker {
+    /// Damaged regions and their sequence numbers, oldest first
+    regions: VecDeque<(u64, Range<Line>)>,
+
+    /// Sequence number of the next region
+    next: u64,
+
+    subscribers: Vec<Cursor>,
+}
+
+impl Default for DamageTracker {
+    fn default() -> DamageTracker {
+        let mut tracker = DamageTracker {
+            regions: VecDeque::new(),
+            next: 0,
+            subscribers: Vec::new(),
+        };
+        tracker.subscribe("renderer");
+        tracker
+    }
+}
+
+impl DamageTracker {
+    /// The renderer, which is always subscribed
+    pub const RENDERER: Subscriber = Subscriber(0);
+
+    pub fn new() -> DamageTracker {
+        Default::default()
+    }
+
+    /// Register a new consumer, it starts out with the entire screen damaged
+    pub fn subscribe(&mut self, name: &'static str) -> Subscriber {
+        debug!("Subscribing {} to terminal damage", name);
+
+        self.subscribers.push(Cursor { name, next: self.next, full: true });
+        Subscriber(self.subscribers.len() - 1)
+    }
+
+    /// Mark lines of the active region as changed
+    pub fn damage_lines(&mut self, lines: Range<Line>) {
+        // Everyone will redraw everything anyways
+        if self.subscribers.iter().all(|cursor| cursor.full) {
+            return;
+        }
+
+        // Repeated writes to the same lines only need to be recorded once
+        if self.regions.back().map(|(_, last)| *last == lines).unwrap_or(false) {
+            return;
+        }
+
+        self.regions.push_back((self.next, lines));
+        self.next += 1;
+
+        if self.regions.len() > MAX_REGIONS {
+            trace!("Too many damaged regions, falling back to full damage");
+            self.damage_all();
+        }
+    }
+
+    /// Mark a single line of the active region as changed
+    #[inline]
+    pub fn damage_line(&mut self, line: Line) {
+        self.damage_lines(line..line + 1);
+    }
+
+    /// Mark the entire screen as changed
+    pub fn damage_all(&mut self) {
+        for cursor in &mut self.subscribers {
+            cursor.full = true;
+            cursor.next = self.next;
+        }
+        self.regions.clear();
+    }
+
+    /// Check if anything changed since `subscriber` last took the damage
+    pub fn is_dirty(&self, subscriber: Subscriber) -> bool {
+        let cursor = &self.subscribers[subscriber.0];
+        cursor.full || cursor.next < self.next
+    }
+
+    /// Take everything which changed since `subscriber` last took the damage
+    pub fn take(&mut self, subscriber: Subscriber) -> Vec<Damage> {
+        let cursor = &self.subscribers[subscriber.0];
+        let damage = if cursor.full {
+            vec![Damage::Full]
+        } else {
+            self.regions.iter()
+                .filter(|(seq, _)| *seq >= cursor.next)
+                .map(|(_, lines)| Damage::Lines(lines.clone()))
+                .collect()
+        };
+
+        self.reset(subscriber);
+        damage
+    }
+
+    /// Discard the damage for `subscriber` without looking at it
+    pub fn reset(&mut self, subscriber: Subscriber) {
+        {
+            let cursor = &mut self.subscribers[subscriber.0];
+            trace!("Resetting damage of {}", cursor.name);
+            cursor.full = false;
+            cursor.next = self.next;
+        }
+
+        // Drop regions which have been taken by every subscriber
+        let oldest = self.subscribers.iter().map(|cursor| cursor.next).min().unwrap_or(self.next);
+        while self.regions.front().map(|(seq, _)| *seq < oldest).unwrap_or(false) {
+            self.regions.pop_front();
+        }
+    }
+}
+
+/// Sorted and merged lines of the `damage`, or `None` if the entire screen has to be redrawn
+///
+/// Lines outside of the first `num_lines` are dropped.
+pub fn damaged_lines(damage: &[Damage], num_lines: Line) -> Option<Vec<Range<Line>>> {
+    let mut lines = Vec::with_capacity(damage.len());
+    for damage in damage {
+        match damage {
+            Damage::Lines(range) => lines.push(range.start..min(range.end, num_lines)),
+            Damage::Full => return None,
+        }
+    }
+    lines.retain(|range| range.start < range.end);
+    lines.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<Line>> = Vec::with_capacity(lines.len());
+    for range in lines {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = max(last.end, range.end),
+            _ => merged.push(range),
+        }
+    }
+    Some(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{damaged_lines, Damage, DamageTracker};
+    use crate::index::Line;
+
+    #[test]
+    fn subscribers_are_independent() {
+        let mut tracker = DamageTracker::new();
+        let effects = tracker.subscribe("effects");
+        tracker.reset(DamageTracker::RENDERER);
+        assert_eq!(tracker.take(effects), vec![Damage::Full]);
+
+        tracker.damage_line(Line(2));
+        tracker.damage_line(Line(2));
+        tracker.damage_lines(Line(4)..Line(6));
+
+        assert!(tracker.is_dirty(DamageTracker::RENDERER));
+        assert_eq!(tracker.take(DamageTracker::RENDERER), vec![
+            Damage::Lines(Line(2)..Line(3)),
+            Damage::Lines(Line(4)..Line(6)),
+        ]);
+        assert!(!tracker.is_dirty(DamageTracker::RENDERER));
+
+        // Taking the damage for the renderer doesn't hide it from the effects
+        assert!(tracker.is_dirty(effects));
+        tracker.damage_line(Line(0));
+        assert_eq!(tracker.take(effects).len(), 3);
+        assert_eq!(tracker.take(DamageTracker::RENDERER), vec![Damage::Lines(Line(0)..Line(1))]);
+        assert!(tracker.regions.is_empty());
+    }
+
+    #[test]
+    fn damage_all_overrides_regions() {
+        let mut tracker = DamageTracker::new();
+        tracker.reset(DamageTracker::RENDERER);
+
+        tracker.damage_line(Line(1));
+        tracker.damage_all();
+        tracker.damage_line(Line(3));
+
+        assert_eq!(tracker.take(DamageTracker::RENDERER), vec![Damage::Full]);
+        assert!(!tracker.is_dirty(DamageTracker::RENDERER));
+    }
+
+    #[test]
+    fn merge_damaged_lines() {
+        let damage = [
+            Damage::Lines(Line(4)..Line(6)),
+            Damage::Lines(Line(0)..Line(1)),
+            Damage::Lines(Line(5)..Line(7)),
+            Damage::Lines(Line(1)..Line(2)),
+            Damage::Lines(Line(9)..Line(12)),
+        ];
+        assert_eq!(
+            damaged_lines(&damage, Line(10)),
+            Some(vec![Line(0)..Line(2), Line(4)..Line(7), Line(9)..Line(10)]),
+        );
+
+        assert_eq!(damaged_lines(&[Damage::Lines(Line(0)..Line(1)), Damage::Full], Line(10)), None);
+    }
+}
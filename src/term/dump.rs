@@ -0,0 +1,341 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Conversion of the grid contents to text
+//!
+//! Used by the `DumpScreen`, `DumpHistory` and `DumpSelection` actions, the text can optionally
+//! keep colors and text attributes as SGR escape sequences.
+use std::cmp::{max, min};
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::ansi::Color;
+use crate::grid::Row;
+use crate::index::{Column, IndexRange};
+use crate::selection::Locations;
+use crate::term::cell::{Cell, Flags, LineLength};
+use crate::term::{Term, TermMode};
+
+/// Part of the terminal which is dumped
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DumpSource {
+    /// The visible screen
+    Screen,
+
+    /// The entire scrollback history
+    History,
+
+    /// The cells of the selection, or the visible screen without a selection
+    Selection,
+}
+
+/// Buffer lines and columns of a dump
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) struct DumpRegion {
+    /// Top buffer line
+    pub top: usize,
+
+    /// Bottom buffer line
+    pub bottom: usize,
+
+    /// First column of the top line, or of every line in a block
+    start: Column,
+
+    /// Last column of the bottom line, or of every line in a block
+    end: Column,
+
+    /// Only the columns between `start` and `end` are part of every line
+    block: bool,
+}
+
+impl DumpRegion {
+    /// Buffer lines from top to bottom
+    pub fn lines(&self) -> impl Iterator<Item = usize> {
+        (self.bottom..=self.top).rev()
+    }
+
+    /// Columns of a line which are part of the region
+    pub fn cols(&self, line: usize, num_cols: Column) -> Range<Column> {
+        let start = if self.block || line == self.top { self.start } else { Column(0) };
+        let end = if self.block || line == self.bottom { self.end + 1 } else { num_cols };
+        start..min(end, num_cols)
+    }
+
+    /// Columns of the widest line
+    pub fn bounds(&self, num_cols: Column) -> Range<Column> {
+        if self.block {
+            self.cols(self.top, num_cols)
+        } else {
+            Column(0)..num_cols
+        }
+    }
+}
+
+impl Term {
+    /// Text of the screen, the scrollback history or the selection
+    ///
+    /// When `ansi` is set, colors and text attributes are written as SGR escape sequences.
+    pub fn dump(&self, source: DumpSource, ansi: bool) -> String {
+        let region = self.dump_region(source);
+        let num_cols = self.grid.num_cols();
+
+        let default_style = Cell::default();
+        let mut style = default_style;
+        let mut text = String::new();
+
+        for line in region.lines() {
+            let row = &self.grid[line];
+            let cols = region.cols(line, num_cols);
+            let length = min(row.line_length(), cols.end);
+
+            for col in IndexRange(cols.start..length) {
+                let cell = &row[col];
+                let spacer = Flags::WIDE_CHAR_SPACER | Flags::LEADING_WIDE_CHAR_SPACER;
+                if cell.flags.intersects(spacer) {
+                    continue;
+                }
+
+                if ansi && !same_style(cell, &style) {
+                    write_sgr(&mut text, cell);
+                    style = *cell;
+                }
+
+                text.push(cell.c);
+                for c in cell.extra.iter().take_while(|c| **c != ' ') {
+                    text.push(*c);
+                }
+            }
+
+            // Wrapped lines continue on the next row
+            if length.0 == row.len()
+                && !region.block
+                && row[length - 1].flags.contains(Flags::WRAPLINE)
+            {
+                continue;
+            }
+
+            if ansi && !same_style(&style, &default_style) {
+                text.push_str("\x1b[0m");
+                style = default_style;
+            }
+
+            text.push('\n');
+        }
+
+        // Empty lines at the bottom of the screen aren't part of the output
+        let length = text.trim_end_matches('\n').len();
+        text.truncate(length);
+        text.push('\n');
+
+        text
+    }
+
+    /// Buffer lines and columns of the dumped part of the terminal
+    pub(super) fn dump_region(&self, source: DumpSource) -> DumpRegion {
+        let num_lines = self.grid.num_lines().0;
+        let last_col = self.grid.num_cols() - 1;
+        let lines = |top, bottom| DumpRegion {
+            top,
+            bottom,
+            start: Column(0),
+            end: last_col,
+            block: false,
+        };
+
+        let offset = self.grid.display_offset();
+        match source {
+            DumpSource::History => {
+                lines(min(self.grid.scroll_limit() + num_lines, self.grid.len()) - 1, 0)
+            },
+            DumpSource::Selection => {
+                let alt_screen = self.mode.contains(TermMode::ALT_SCREEN);
+                let span = self.grid.selection.as_ref().and_then(|s| s.to_span(self, alt_screen));
+                match span.map(|span| span.to_locations()) {
+                    Some(Locations { start, end, is_block }) => {
+                        let (bottom, top) = (min(start, end), max(start, end));
+                        let (start, end) = if is_block || top.line == bottom.line {
+                            (min(start.col, end.col), max(start.col, end.col))
+                        } else {
+                            (top.col, bottom.col)
+                        };
+                        let end = min(end, last_col);
+                        let (top, bottom) = (top.line, bottom.line);
+                        DumpRegion { top, bottom, start, end, block: is_block }
+                    },
+                    None => lines(offset + num_lines - 1, offset),
+                }
+            },
+            DumpSource::Screen => lines(offset + num_lines - 1, offset),
+        }
+    }
+}
+
+/// Runs of cells with the same style within `cols`
+pub(super) fn style_runs(row: &Row<Cell>, cols: Range<Column>) -> Vec<Range<Column>> {
+    let mut runs = Vec::new();
+    let mut start = cols.start;
+    while start < cols.end {
+        let end = IndexRange(start + 1..cols.end)
+            .find(|col| !same_style(&row[*col], &row[start]))
+            .unwrap_or(cols.end);
+        runs.push(start..end);
+        start = end;
+    }
+    runs
+}
+
+/// Characters of a run of cells, including zero-width characters
+pub(super) fn run_text(row: &Row<Cell>, cols: Range<Column>) -> String {
+    let mut text = String::new();
+    for col in IndexRange(cols) {
+        let cell = &row[col];
+        if !cell.flags.intersects(Flags::WIDE_CHAR_SPACER | Flags::LEADING_WIDE_CHAR_SPACER) {
+            text.push(cell.c);
+            text.extend(cell.extra.iter().take_while(|c| **c != ' '));
+        }
+    }
+    text
+}
+
+/// Check if two cells look the same, ignoring their content and layout flags
+#[inline]
+pub(super) fn same_style(a: &Cell, b: &Cell) -> bool {
+    let layout = Flags::WRAPLINE
+        | Flags::WIDE_CHAR
+        | Flags::WIDE_CHAR_SPACER
+        | Flags::LEADING_WIDE_CHAR_SPACER;
+    a.fg == b.fg && a.bg == b.bg && (a.flags - layout) == (b.flags - layout)
+}
+
+/// Write the SGR sequence which changes the text style to the one of `cell`
+fn write_sgr(text: &mut String, cell: &Cell) {
+    text.push_str("\x1b[0");
+
+    let attributes = [
+        (Flags::BOLD, 1),
+        (Flags::DIM, 2),
+        (Flags::ITALIC, 3),
+        (Flags::UNDERLINE, 4),
+        (Flags::BLINK_SLOW, 5),
+        (Flags::BLINK_FAST, 6),
+        (Flags::INVERSE, 7),
+        (Flags::HIDDEN, 8),
+        (Flags::STRIKEOUT, 9),
+    ];
+    for (flag, attribute) in attributes.iter() {
+        if cell.flags.contains(*flag) {
+            let _ = write!(text, ";{}", attribute);
+        }
+    }
+
+    write_color(text, cell.fg, 30);
+    write_color(text, cell.bg, 40);
+
+    text.push('m');
+}
+
+/// Write the SGR parameters for a color, `base` is 30 for the foreground and 40 for the background
+fn write_color(text: &mut String, color: Color, base: usize) {
+    let _ = match color {
+        Color::Named(color) if (color as usize) < 8 => write!(text, ";{}", base + color as usize),
+        Color::Named(color) if (color as usize) < 16 => {
+            write!(text, ";{}", base + 60 + color as usize - 8)
+        },
+        // Default, cursor and dim colors all use the default color
+        Color::Named(_) => Ok(()),
+        Color::Indexed(index) => write!(text, ";{};5;{}", base + 8, index),
+        Color::Spec(rgb) => write!(text, ";{};2;{};{};{}", base + 8, rgb.r, rgb.g, rgb.b),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::{Attr, Color, Handler, NamedColor};
+    use crate::config::Config;
+    use crate::index::{Column, Point, Side};
+    use crate::selection::Selection;
+    use crate::term::{SizeInfo, Term};
+
+    use super::DumpSource;
+
+    fn term() -> Term {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        Term::new(&Config::default(), size)
+    }
+
+    #[test]
+    fn dump_plain_text() {
+        let mut term = term();
+
+        // The first line wraps into the second one
+        for c in "abcdefgh".chars() {
+            term.input(c);
+        }
+        term.carriage_return();
+        term.linefeed();
+        term.input('i');
+
+        assert_eq!(term.dump(DumpSource::Screen, false), "abcdefgh\ni\n");
+    }
+
+    #[test]
+    fn dump_ansi_text() {
+        let mut term = term();
+
+        term.input('a');
+        term.terminal_attribute(Attr::Bold);
+        term.terminal_attribute(Attr::Foreground(Color::Named(NamedColor::Red)));
+        term.input('b');
+        term.terminal_attribute(Attr::Reset);
+        term.input('c');
+
+        assert_eq!(term.dump(DumpSource::Screen, true), "a\x1b[0;1;31mb\x1b[0mc\n");
+    }
+
+    #[test]
+    fn dump_selection() {
+        let mut term = term();
+
+        for c in "abcdefg".chars() {
+            term.input(c);
+        }
+        term.carriage_return();
+        term.linefeed();
+        for c in "hijklmn".chars() {
+            term.input(c);
+        }
+
+        // Buffer lines count up from the bottom of the screen
+        let top = term.grid().num_lines().0 - 1;
+        let start = Point::new(top, Column(2));
+        let mut selection = Selection::simple(start, Side::Left);
+        selection.update(Point::new(top - 1, Column(3)), Side::Right);
+        *term.selection_mut() = Some(selection);
+        assert_eq!(term.dump(DumpSource::Selection, false), "cdefg\nhijk\n");
+
+        let mut selection = Selection::block(start, Side::Left);
+        selection.update(Point::new(top - 1, Column(3)), Side::Right);
+        *term.selection_mut() = Some(selection);
+        assert_eq!(term.dump(DumpSource::Selection, false), "cd\njk\n");
+    }
+}
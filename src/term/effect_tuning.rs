@@ -0,0 +1,137 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Overlay for adjusting the effect style while the animation is running
+//!
+//! Up and down select a setting, left and right change it. Changes apply to the next animation
+//! tick, and the configuration with the adjusted style is returned when the overlay is closed.
+use crate::config::{EffectPalette, EffectStyle, MAX_EFFECT_DENSITY, MAX_EFFECT_SPEED};
+
+/// Settings which can be adjusted, in the order they're shown
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Setting {
+    Speed,
+    Density,
+    Palette,
+}
+
+const SETTINGS: [Setting; 3] = [Setting::Speed, Setting::Density, Setting::Palette];
+
+/// First line of the overlay, explaining the keys
+const HEADER: &str = " EFFECT TUNING   Up/Down: select   Left/Right: change   Escape: close ";
+
+/// State of the effect tuning overlay
+#[derive(Debug, Default, Clone)]
+pub struct EffectTuning {
+    /// Index of the selected setting
+    selected: usize,
+}
+
+impl EffectTuning {
+    /// Select the next or previous setting, wrapping around at the ends
+    pub fn select(&mut self, forward: bool) {
+        let count = SETTINGS.len();
+        self.selected = if forward {
+            (self.selected + 1) % count
+        } else {
+            (self.selected + count - 1) % count
+        };
+    }
+
+    /// Increase or decrease the selected setting of `style`
+    pub fn adjust(&self, style: &mut EffectStyle, increase: bool) {
+        let step = |value: u8, max: u8| if increase {
+            value.saturating_add(1).min(max)
+        } else {
+            value.saturating_sub(1).max(1)
+        };
+
+        match SETTINGS[self.selected] {
+            Setting::Speed => style.speed = step(style.speed, MAX_EFFECT_SPEED),
+            Setting::Density => style.density = step(style.density, MAX_EFFECT_DENSITY),
+            Setting::Palette => {
+                let palettes = &EffectPalette::ALL;
+                let index = palettes.iter().position(|p| *p == style.palette).unwrap_or(0);
+                let next = if increase { index + 1 } else { index + palettes.len() - 1 };
+                style.palette = palettes[next % palettes.len()];
+            },
+        }
+    }
+
+    /// Lines of the overlay, with a marker in front of the selected setting
+    ///
+    /// All lines are padded to the same width.
+    pub fn lines(&self, style: &EffectStyle) -> Vec<String> {
+        let mut lines = vec![String::from(HEADER)];
+        for (index, setting) in SETTINGS.iter().enumerate() {
+            let value = match setting {
+                Setting::Speed => format!("{} / {}", style.speed, MAX_EFFECT_SPEED),
+                Setting::Density => format!("{} / {}", style.density, MAX_EFFECT_DENSITY),
+                Setting::Palette => format!("{:?}", style.palette),
+            };
+            let marker = if index == self.selected { '>' } else { ' ' };
+            let line = format!(" {} {:<8} {}", marker, format!("{:?}", setting), value);
+            lines.push(format!("{:<1$}", line, HEADER.len()));
+        }
+        lines
+    }
+}
+
+/// Configuration snippet reproducing `style`
+pub fn config_snippet(style: &EffectStyle) -> String {
+    format!(
+        "effect_style:\n  speed: {}\n  density: {}\n  palette: {:?}\n",
+        style.speed, style.density, style.palette,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{EffectPalette, EffectStyle, MAX_EFFECT_SPEED};
+
+    use super::{config_snippet, EffectTuning};
+
+    #[test]
+    fn adjust_within_limits() {
+        let mut tuning = EffectTuning::default();
+        let mut style = EffectStyle::default();
+
+        tuning.adjust(&mut style, false);
+        assert_eq!(style.speed, 1);
+        for _ in 0..20 {
+            tuning.adjust(&mut style, true);
+        }
+        assert_eq!(style.speed, MAX_EFFECT_SPEED);
+
+        tuning.select(false);
+        tuning.adjust(&mut style, true);
+        assert_eq!(style.palette, EffectPalette::Amber);
+        tuning.adjust(&mut style, false);
+        tuning.adjust(&mut style, false);
+        assert_eq!(style.palette, EffectPalette::White);
+    }
+
+    #[test]
+    fn snippet_matches_config() {
+        let mut style = EffectStyle::default();
+        style.density = 3;
+        style.palette = EffectPalette::Cyan;
+
+        let snippet = config_snippet(&style);
+        assert_eq!(snippet, "effect_style:\n  speed: 1\n  density: 3\n  palette: Cyan\n");
+
+        let config: crate::config::Config = serde_yaml::from_str(&snippet).unwrap();
+        assert_eq!(config.effect_style(), style);
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Conversion of the grid contents to an HTML document
+//!
+//! Every run of cells with the same style becomes a `span` with inline styles, so the document
+//! can be pasted into blog posts or issues without a separate stylesheet. Every row of the grid
+//! is a line of the `pre` element, wrapped lines are not joined.
+use std::cmp::min;
+use std::fmt::Write;
+
+use crate::ansi::NamedColor;
+use crate::term::cell::{Cell, Flags, LineLength};
+use crate::term::dump::{run_text, style_runs, DumpSource};
+use crate::term::svg::{escape, hex};
+use crate::term::Term;
+use crate::Rgb;
+
+impl Term {
+    /// HTML document of the screen, the scrollback history or the selection
+    pub fn html(&self, source: DumpSource) -> String {
+        let region = self.dump_region(source);
+        let num_cols = self.grid.num_cols();
+        let foreground = self.colors[NamedColor::Foreground];
+        let background = self.colors[NamedColor::Background];
+
+        let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        let _ = writeln!(html, "<title>{}</title>", escape(&self.title));
+        html.push_str("</head>\n<body>\n");
+        let _ = write!(
+            html,
+            "<pre style=\"color: {}; background-color: {}; font-family: {}\">",
+            hex(foreground),
+            hex(background),
+            escape(&format!("'{}', monospace", self.font_family)),
+        );
+
+        for line in region.lines() {
+            let row = &self.grid[line];
+            let cols = region.cols(line, num_cols);
+            let length = min(row.line_length(), cols.end);
+
+            for run in style_runs(row, cols.start..length) {
+                let style = &row[run.start];
+                let properties = css(style, self.cell_rgb(style), (foreground, background));
+                let text = escape(&run_text(row, run));
+                if properties.is_empty() {
+                    html.push_str(&text);
+                } else {
+                    let _ = write!(html, "<span style=\"{}\">{}</span>", properties, text);
+                }
+            }
+
+            html.push('\n');
+        }
+
+        // Empty lines at the bottom of the screen aren't part of the output
+        let length = html.trim_end_matches('\n').len();
+        html.truncate(length);
+        html.push_str("\n</pre>\n</body>\n</html>\n");
+
+        html
+    }
+}
+
+/// Inline style of a cell, empty if it looks like the default cell
+fn css(cell: &Cell, (fg, bg): (Rgb, Rgb), (foreground, background): (Rgb, Rgb)) -> String {
+    let mut properties = Vec::new();
+
+    if fg != foreground {
+        properties.push(format!("color: {}", hex(fg)));
+    }
+
+    if bg != background {
+        properties.push(format!("background-color: {}", hex(bg)));
+    }
+
+    if cell.flags.contains(Flags::BOLD) {
+        properties.push(String::from("font-weight: bold"));
+    }
+
+    if cell.flags.contains(Flags::ITALIC) {
+        properties.push(String::from("font-style: italic"));
+    }
+
+    let underline = cell.flags.contains(Flags::UNDERLINE);
+    let strikeout = cell.flags.contains(Flags::STRIKEOUT);
+    let decoration = match (underline, strikeout) {
+        (true, true) => Some("underline line-through"),
+        (true, false) => Some("underline"),
+        (false, true) => Some("line-through"),
+        (false, false) => None,
+    };
+    if let Some(decoration) = decoration {
+        properties.push(format!("text-decoration: {}", decoration));
+    }
+
+    if cell.flags.contains(Flags::HIDDEN) {
+        properties.push(String::from("visibility: hidden"));
+    }
+
+    properties.join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::{Attr, Color, Handler, NamedColor};
+    use crate::config::Config;
+    use crate::term::dump::DumpSource;
+    use crate::term::svg::hex;
+    use crate::term::{SizeInfo, Term};
+
+    #[test]
+    fn html_spans() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let mut term = Term::new(&Config::default(), size);
+
+        term.input('a');
+        term.input('<');
+        term.terminal_attribute(Attr::Bold);
+        term.terminal_attribute(Attr::Foreground(Color::Named(NamedColor::Red)));
+        term.input('b');
+
+        let html = term.html(DumpSource::Screen);
+        let red = hex(term.colors[NamedColor::Red]);
+        let line = format!("a&lt;<span style=\"color: {}; font-weight: bold\">b</span>\n", red);
+        assert!(html.starts_with("<!DOCTYPE html>\n"));
+        assert!(html.contains(&line));
+    }
+}
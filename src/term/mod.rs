@@ -14,6 +14,7 @@
 //
 //! Exports the `Term` type which is a high-level API for the Grid
 use std::ops::{Range, Index, IndexMut};
+use std::path::{Path, PathBuf};
 use std::{ptr, io, mem};
 use std::cmp::{min, max};
 use std::time::{Duration, Instant};
@@ -22,24 +23,61 @@ use arraydeque::ArrayDeque;
 use unicode_width::UnicodeWidthChar;
 
 use font::{self, Size};
-use crate::ansi::{self, Color, NamedColor, Attr, Handler, CharsetIndex, StandardCharset, CursorStyle};
-use crate::grid::{BidirectionalIterator, Grid, Indexed, IndexRegion, DisplayIter, Scroll, ViewportPosition};
+use crate::ansi::{
+    self, Color, NamedColor, Attr, Handler, CharsetIndex, StandardCharset, CursorStyle, LineAttribute,
+    ShellMark,
+};
+use crate::grid::{Annotation, BidirectionalIterator, Grid, Indexed, IndexRegion, DisplayIter, Scroll, ViewportPosition};
 use crate::index::{self, Point, Column, Line, IndexRange, Contains, RangeInclusive, Linear};
 use crate::selection::{self, Selection, Locations};
-use crate::config::{Config, VisualBellAnimation};
+use crate::config::{
+    Alpha, AmbiguousWidth, Config, CursorBlinking, PasteMarker, Profile, Unicode, UnicodeVersion,
+    VisualBellAnimation,
+};
 use crate::{MouseCursor, Rgb};
+use crate::appearance::Appearance;
 use copypasta::{Clipboard, Load, Store};
 use crate::input::FONT_SIZE_STEP;
 use crate::logging::LoggerProxy;
-use crate::url::UrlParser;
+use crate::message_bar::Message;
+use crate::notification::Notification;
+use crate::screenshot;
+use crate::url::{FileLocation, UrlParser};
+use crate::window::DEFAULT_TITLE;
 
 pub mod cell;
 pub mod color;
 pub mod animation;
-use self::animation::{MatrixUndo, undo};
+pub mod damage;
+pub mod autoscroll;
+pub mod dump;
+pub mod effect_tuning;
+pub mod perf_hud;
+pub mod svg;
+pub mod html;
+pub mod scroll_indicator;
+pub mod snapshot;
+use self::animation::{Banner, MatrixUndo, type_banner, undo};
+use self::autoscroll::Autoscroll;
+use self::scroll_indicator::{fade, ScrollIndicator};
+use self::effect_tuning::{config_snippet, EffectTuning};
+use self::perf_hud::PerfHud;
+use self::damage::DamageTracker;
 pub use self::cell::Cell;
 use self::cell::LineLength;
 
+/// Joins emojis into a single grapheme cluster
+const ZERO_WIDTH_JOINER: char = '\u{200d}';
+
+/// Variation selector requesting the emoji presentation of the preceding character
+const EMOJI_PRESENTATION_SELECTOR: char = '\u{fe0f}';
+
+/// Maximum number of titles kept on the title stack
+const TITLE_STACK_MAX_DEPTH: usize = 4096;
+
+/// Maximum number of characters in a window title
+const MAX_TITLE_LENGTH: usize = 2048;
+
 /// A type that can expand a given point to a region
 ///
 /// Usually this is implemented for some 2-D array type since
@@ -51,6 +89,8 @@ pub trait Search {
     fn semantic_search_right(&self, _: Point<usize>) -> Point<usize>;
     /// Find the nearest URL boundary in both directions.
     fn url_search(&self, _: Point<usize>) -> Option<String>;
+    /// Find a `path:line:column` file location in both directions.
+    fn file_location_search(&self, _: Point<usize>) -> Option<FileLocation>;
 }
 
 impl Search for Term {
@@ -98,7 +138,18 @@ impl Search for Term {
         point
     }
 
-    fn url_search(&self, mut point: Point<usize>) -> Option<String> {
+    fn url_search(&self, point: Point<usize>) -> Option<String> {
+        self.word_parser(point).url()
+    }
+
+    fn file_location_search(&self, point: Point<usize>) -> Option<FileLocation> {
+        self.word_parser(point).file_location()
+    }
+}
+
+impl Term {
+    /// Feed the characters around a visible point into a `UrlParser`
+    fn word_parser(&self, mut point: Point<usize>) -> UrlParser {
         point.line = self.grid.num_lines().0 - point.line - 1;
 
         // Limit the starting point to the last line in the history
@@ -109,19 +160,18 @@ impl Search for Term {
         point.col += 1;
         let mut iterb = self.grid.iter_from(point);
 
-        // Find URLs
-        let mut url_parser = UrlParser::new();
+        let mut parser = UrlParser::new();
         while let Some(cell) = iterb.prev() {
-            if url_parser.advance_left(cell.c) {
+            if parser.advance_left(cell.c) {
                 break;
             }
         }
         for cell in iterf {
-            if url_parser.advance_right(cell.c) {
+            if parser.advance_right(cell.c) {
                 break;
             }
         }
-        url_parser.url()
+        parser
     }
 }
 
@@ -150,8 +200,11 @@ pub struct RenderableCellsIter<'a> {
     mode: TermMode,
     config: &'a Config,
     colors: &'a color::List,
+    optional_colors: &'a color::Optional,
     selection: Option<RangeInclusive<index::Linear>>,
+    selection_columns: Option<RangeInclusive<Column>>,
     cursor_cells: ArrayDeque<[Indexed<Cell>; 3]>,
+    hidden_blink: cell::Flags,
 }
 
 impl<'a> RenderableCellsIter<'a> {
@@ -163,15 +216,18 @@ impl<'a> RenderableCellsIter<'a> {
         grid: &'b Grid<Cell>,
         cursor: &'b Point,
         colors: &'b color::List,
+        optional_colors: &'b color::Optional,
         mode: TermMode,
         config: &'b Config,
         selection: Option<Locations>,
-        cursor_style: CursorStyle,
+        cursor_style: Option<CursorStyle>,
+        hidden_blink: cell::Flags,
     ) -> RenderableCellsIter<'b> {
         let cursor_offset = grid.line_to_offset(cursor.line);
         let inner = grid.display_iter();
 
         let mut selection_range = None;
+        let mut selection_columns = None;
         if let Some(loc) = selection {
             // Get on-screen lines of the selection's locations
             let start_line = grid.buffer_line_to_visible(loc.start.line);
@@ -194,7 +250,14 @@ impl<'a> RenderableCellsIter<'a> {
                 _ => None,
             };
 
-            if let Some((start_line, start_col, end_line, end_col)) = locations {
+            if let Some((start_line, mut start_col, end_line, mut end_col)) = locations {
+                // Block selections keep their columns on every line, even when clamped
+                if loc.is_block {
+                    start_col = loc.start.col;
+                    end_col = loc.end.col;
+                    selection_columns = Some(RangeInclusive::new(end_col, start_col));
+                }
+
                 // start and end *lines* are swapped as we switch from buffer to
                 // Line coordinates.
                 let mut end = Point {
@@ -226,9 +289,12 @@ impl<'a> RenderableCellsIter<'a> {
             inner,
             mode,
             selection: selection_range,
+            selection_columns,
             config,
             colors,
+            optional_colors,
             cursor_cells: ArrayDeque::new(),
+            hidden_blink,
         }.initialize(cursor_style)
     }
 
@@ -260,8 +326,8 @@ impl<'a> RenderableCellsIter<'a> {
 
     fn populate_block_cursor(&mut self) {
         let cell = &self.grid[self.cursor];
-        let text_color = self.config.cursor_text_color().unwrap_or(cell.bg);
-        let cursor_color = self.config.cursor_cursor_color().unwrap_or(cell.fg);
+        let text_color = self.optional_colors.cursor_text_color().unwrap_or(cell.bg);
+        let cursor_color = self.optional_colors.cursor_cursor_color().unwrap_or(cell.fg);
 
         let original_cell = self.grid[self.cursor];
 
@@ -279,7 +345,7 @@ impl<'a> RenderableCellsIter<'a> {
         let original_cell = self.grid[self.cursor];
 
         let mut cursor_cell = self.grid[self.cursor];
-        let cursor_color = self.config.cursor_cursor_color().unwrap_or(cursor_cell.fg);
+        let cursor_color = self.optional_colors.cursor_cursor_color().unwrap_or(cursor_cell.fg);
         cursor_cell.c = cursor_cell_char;
         cursor_cell.fg = cursor_color;
 
@@ -315,24 +381,24 @@ impl<'a> RenderableCellsIter<'a> {
         }).expect("won't exceed capacity");
     }
 
-    fn initialize(mut self, cursor_style: CursorStyle) -> Self {
-        if self.cursor_is_visible() {
-            match cursor_style {
-                CursorStyle::HollowBlock => {
-                    self.populate_box_cursor();
-                },
-                CursorStyle::Block => {
-                    self.populate_block_cursor();
-                },
-                CursorStyle::Beam => {
-                    self.populate_beam_cursor();
-                },
-                CursorStyle::Underline => {
-                    self.populate_underline_cursor();
-                }
-            }
-        } else {
-            self.populate_no_cursor();
+    /// Add the cursor cells, `None` hides the cursor
+    fn initialize(mut self, cursor_style: Option<CursorStyle>) -> Self {
+        match cursor_style.filter(|_| self.cursor_is_visible()) {
+            Some(CursorStyle::HollowBlock) => {
+                self.populate_box_cursor();
+            },
+            Some(CursorStyle::Block) => {
+                self.populate_block_cursor();
+            },
+            Some(CursorStyle::Beam) => {
+                self.populate_beam_cursor();
+            },
+            Some(CursorStyle::Underline) => {
+                self.populate_underline_cursor();
+            },
+            None => {
+                self.populate_no_cursor();
+            },
         }
         self
     }
@@ -345,14 +411,24 @@ impl<'a> RenderableCellsIter<'a> {
 
     fn compute_fg_rgb(&self, fg: Color, cell: &Cell) -> Rgb {
         use self::cell::Flags;
+
+        // Faint text which isn't drawn in bright colors, like faint bold text without them
+        let bright_bold = self.config.draw_bold_text_with_bright_colors();
+        let dim = match (bright_bold, cell.flags & Flags::DIM_BOLD) {
+            (_, Flags::DIM) | (false, Flags::DIM_BOLD) => true,
+            _ => false,
+        };
+
         match fg {
+            // Colors outside of the palette have no dim variant
+            Color::Spec(rgb) if dim => rgb * color::DIM_FACTOR,
             Color::Spec(rgb) => rgb,
             Color::Named(ansi) => {
-                match (self.config.draw_bold_text_with_bright_colors(), cell.flags & Flags::DIM_BOLD) {
+                match (bright_bold, cell.flags & Flags::DIM_BOLD) {
                     // If no bright foreground is set, treat it like the BOLD flag doesn't exist
                     (_, self::cell::Flags::DIM_BOLD)
                         if ansi == NamedColor::Foreground
-                            && self.config.colors().primary.bright_foreground.is_none() =>
+                            && self.optional_colors.bright_foreground.is_none() =>
                     {
                         self.colors[NamedColor::DimForeground]
                     }
@@ -366,18 +442,13 @@ impl<'a> RenderableCellsIter<'a> {
                 }
             },
             Color::Indexed(idx) => {
-                let idx = match (
-                    self.config.draw_bold_text_with_bright_colors(),
-                    cell.flags & Flags::DIM_BOLD,
-                    idx
-                ) {
-                    (true,  self::cell::Flags::BOLD, 0..=7)  => idx as usize + 8,
-                    (false, self::cell::Flags::DIM,  8..=15) => idx as usize - 8,
-                    (false, self::cell::Flags::DIM,  0..=7)  => idx as usize + 260,
-                    _ => idx as usize,
-                };
-
-                self.colors[idx]
+                match (bright_bold, cell.flags & Flags::DIM_BOLD, idx) {
+                    (true, Flags::BOLD, 0..=7) => self.colors[idx as usize + 8],
+                    (_, _, 8..=15) if dim => self.colors[idx as usize - 8],
+                    (_, _, 0..=7) if dim => self.colors[idx as usize + 260],
+                    _ if dim => self.colors[idx] * color::DIM_FACTOR,
+                    _ => self.colors[idx],
+                }
             }
         }
     }
@@ -409,6 +480,7 @@ pub struct RenderableCell {
     pub bg: Rgb,
     pub bg_alpha: f32,
     pub flags: cell::Flags,
+    pub line_attribute: LineAttribute,
 }
 
 impl<'a> Iterator for RenderableCellsIter<'a> {
@@ -443,7 +515,10 @@ impl<'a> Iterator for RenderableCellsIter<'a> {
 
                 let selected = self.selection.as_ref()
                     .map(|range| range.contains_(index))
-                    .unwrap_or(false);
+                    .unwrap_or(false)
+                    && self.selection_columns.as_ref()
+                        .map(|columns| columns.contains_(cell.column))
+                        .unwrap_or(true);
 
                 // Skip empty cells
                 if cell.is_empty() && !selected {
@@ -453,25 +528,54 @@ impl<'a> Iterator for RenderableCellsIter<'a> {
                 (cell, selected)
             };
 
+            // Only the left half of a double width line fits on screen
+            let line_attribute = self.grid[self.grid.visible_line_to_buffer(cell.line)].attribute();
+            if line_attribute.is_double_width() && cell.column.0 >= max(self.grid.num_cols().0 / 2, 1) {
+                continue;
+            }
+
             // Apply inversion and lookup RGB values
             let mut fg_rgb = self.compute_fg_rgb(cell.fg, &cell);
             let mut bg_rgb = self.compute_bg_rgb(cell.bg);
 
-            let bg_alpha = if selected ^ cell.inverse() {
+            let mut bg_alpha = if selected ^ cell.inverse() {
                 mem::swap(&mut fg_rgb, &mut bg_rgb);
                 self.compute_bg_alpha(cell.fg)
             } else {
                 self.compute_bg_alpha(cell.bg)
             };
 
+            // Configured selection colors replace the inverted ones
+            if selected {
+                let colors = self.optional_colors.selection;
+                fg_rgb = colors.text.unwrap_or(fg_rgb);
+                if let Some(background) = colors.background {
+                    bg_rgb = background;
+                    bg_alpha = 1.0;
+                }
+            }
+
+            // Blinking text is hidden during every other blink interval
+            let mut flags = cell.flags;
+            if flags.intersects(self.hidden_blink) {
+                flags.insert(cell::Flags::HIDDEN);
+            }
+
+            if flags.contains(cell::Flags::PASTED)
+                && self.config.paste_marker() == PasteMarker::Underline
+            {
+                flags.insert(cell::Flags::UNDERLINE);
+            }
+
             return Some(RenderableCell {
                 line: cell.line,
                 column: cell.column,
-                flags: cell.flags,
+                flags,
                 chars: cell.chars(),
                 fg: fg_rgb,
                 bg: bg_rgb,
                 bg_alpha,
+                line_attribute,
             })
         }
     }
@@ -482,22 +586,26 @@ pub mod mode {
     use bitflags::bitflags;
 
     bitflags! {
-        pub struct TermMode: u16 {
-            const SHOW_CURSOR         = 0b00_0000_0000_0001;
-            const APP_CURSOR          = 0b00_0000_0000_0010;
-            const APP_KEYPAD          = 0b00_0000_0000_0100;
-            const MOUSE_REPORT_CLICK  = 0b00_0000_0000_1000;
-            const BRACKETED_PASTE     = 0b00_0000_0001_0000;
-            const SGR_MOUSE           = 0b00_0000_0010_0000;
-            const MOUSE_MOTION        = 0b00_0000_0100_0000;
-            const LINE_WRAP           = 0b00_0000_1000_0000;
-            const LINE_FEED_NEW_LINE  = 0b00_0001_0000_0000;
-            const ORIGIN              = 0b00_0010_0000_0000;
-            const INSERT              = 0b00_0100_0000_0000;
-            const FOCUS_IN_OUT        = 0b00_1000_0000_0000;
-            const ALT_SCREEN          = 0b01_0000_0000_0000;
-            const MOUSE_DRAG          = 0b10_0000_0000_0000;
-            const ANY                 = 0b11_1111_1111_1111;
+        #[derive(Serialize, Deserialize)]
+        pub struct TermMode: u32 {
+            const SHOW_CURSOR         = 0b0000_0000_0000_0000_0001;
+            const APP_CURSOR          = 0b0000_0000_0000_0000_0010;
+            const APP_KEYPAD          = 0b0000_0000_0000_0000_0100;
+            const MOUSE_REPORT_CLICK  = 0b0000_0000_0000_0000_1000;
+            const BRACKETED_PASTE     = 0b0000_0000_0000_0001_0000;
+            const SGR_MOUSE           = 0b0000_0000_0000_0010_0000;
+            const MOUSE_MOTION        = 0b0000_0000_0000_0100_0000;
+            const LINE_WRAP           = 0b0000_0000_0000_1000_0000;
+            const LINE_FEED_NEW_LINE  = 0b0000_0000_0001_0000_0000;
+            const ORIGIN              = 0b0000_0000_0010_0000_0000;
+            const INSERT              = 0b0000_0000_0100_0000_0000;
+            const FOCUS_IN_OUT        = 0b0000_0000_1000_0000_0000;
+            const ALT_SCREEN          = 0b0000_0001_0000_0000_0000;
+            const MOUSE_DRAG          = 0b0000_0010_0000_0000_0000;
+            const URXVT_MOUSE         = 0b0000_0100_0000_0000_0000;
+            const SGR_PIXEL_MOUSE     = 0b0000_1000_0000_0000_0000;
+            const GRAPHEME_CLUSTERING = 0b0001_0000_0000_0000_0000;
+            const ANY                 = 0b0001_1111_1111_1111_1111;
             const NONE                = 0;
         }
     }
@@ -726,6 +834,42 @@ pub struct Term {
     /// Would be nice to avoid the allocation...
     next_title: Option<String>,
 
+    /// Current window title
+    title: String,
+
+    /// Titles saved with `push_title`, most recent last
+    title_stack: Vec<String>,
+
+    /// Working directory reported by the shell with OSC 7
+    working_directory: Option<PathBuf>,
+
+    /// New output is held back by the event loop
+    output_paused: bool,
+
+    /// Bells and finished commands which haven't been delivered yet
+    pub notifications: Vec<Notification>,
+
+    /// Start of the running command, as reported by shell integration
+    command_start: Option<Instant>,
+
+    /// The cursor is on the command line, between the prompt and the start of the command
+    prompt_input: bool,
+
+    /// Number of pasted characters which the shell hasn't echoed yet
+    pasted_echo: usize,
+
+    /// Accent color set with an escape sequence, overrides the configured one
+    accent_color: Option<Rgb>,
+
+    /// Profiles which are applied while a matching process runs in the foreground
+    profiles: Vec<Profile>,
+
+    /// Name of the process in the foreground
+    foreground_process: Option<String>,
+
+    /// Profile matching the foreground process
+    active_profile: Option<Profile>,
+
     /// Got a request to set the mouse cursor; it's buffered here until the next draw
     next_mouse_cursor: Option<MouseCursor>,
 
@@ -755,14 +899,40 @@ pub struct Term {
     pub font_size: Size,
     original_font_size: Size,
 
+    /// Background opacity, changed at runtime relative to the configured opacity
+    background_opacity: Alpha,
+    original_background_opacity: Alpha,
+
+    /// Family of the normal font, changed with OSC 50 and reset by config reloads
+    font_family: String,
+
     /// Size
     size_info: SizeInfo,
 
-    pub dirty: bool,
+    /// Regions which changed since they were last drawn or processed
+    pub damage: DamageTracker,
+
+    /// Line of the cursor when the damage was last flushed
+    damaged_cursor_line: Line,
 
     pub visual_bell: VisualBell,
     pub next_is_urgent: Option<bool>,
 
+    /// Middle button autoscrolling, while the button is held down
+    pub autoscroll: Option<Autoscroll>,
+
+    /// Last time the viewport was scrolled, for fading out the scroll indicator
+    scrolled_at: Option<Instant>,
+
+    /// Overlay for adjusting the effect style, while it's open
+    pub effect_tuning: Option<EffectTuning>,
+
+    /// Performance measurements, while the HUD is shown
+    pub perf_hud: Option<PerfHud>,
+
+    /// Region of a requested screenshot, until the next frame is drawn
+    pub screenshot: Option<screenshot::Region>,
+
     /// Saved cursor from main grid
     cursor_save: Cursor,
 
@@ -777,34 +947,91 @@ pub struct Term {
     /// Is color in `colors` modified or not
     color_modified: [bool; color::COUNT],
 
-    /// Original colors from config
+    /// Original colors from config, or from the selected color scheme
     original_colors: color::List,
 
+    /// Colors of the `colors` section in the config
+    configured_colors: color::List,
+
+    /// Colors which are only used when set, from config or from the selected color scheme
+    optional_colors: color::Optional,
+
+    /// Colors of the `colors` section in the config which are only used when set
+    configured_optional_colors: color::Optional,
+
+    /// Color schemes from the config, by name
+    color_schemes: Vec<(String, color::List, color::Optional)>,
+
+    /// Name of the selected color scheme
+    color_scheme: Option<String>,
+
+    /// Color schemes for the light and dark appearance of the OS
+    appearance_schemes: (Option<String>, Option<String>),
+
+    /// Appearance of the OS, if it's followed
+    appearance: Option<Appearance>,
+
     /// Current style of the cursor
     cursor_style: Option<CursorStyle>,
 
     /// Default style for resetting the cursor
     default_cursor_style: CursorStyle,
 
+    /// Blinking of the cursor requested by the application
+    cursor_blinking: Option<bool>,
+
+    /// When the cursor blinks, depending on the application's request
+    cursor_blinking_config: CursorBlinking,
+
+    /// Interval of the blinking cursor, `None` if blinking is disabled
+    cursor_blink_interval: Option<Duration>,
+
+    /// Start of the first cursor blink interval, restarted by keyboard input
+    cursor_blink_start: Instant,
+
     dynamic_title: bool,
 
+    /// Answer requests for the window title
+    report_title: bool,
+
+    /// Allow applications to change colors
+    dynamic_colors: bool,
+
+    /// Allow applications to take screenshots
+    screenshot_escape: bool,
+
     /// Number of spaces in one tab
     tabspaces: usize,
 
     /// Automatically scroll to bottom when new lines are added
     auto_scroll: bool,
 
+    /// Pause reading from the pty while the renderer is behind
+    auto_throttle: bool,
+
+    /// Width of unicode characters
+    unicode: Unicode,
+
+    /// Interval of slowly blinking text, `None` if blinking is disabled
+    text_blink_interval: Option<Duration>,
+
+    /// Start of the first blink interval
+    blink_start: Instant,
+
     /// Proxy object for clearing displayed errors and warnings
     logger_proxy: Option<LoggerProxy>,
 
     /// Data associated with matrix animation.
     pub undo: MatrixUndo,
+
+    /// Startup banner which is still being typed
+    banner: Option<Banner>,
 }
 
 
 
 /// Terminal size info
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SizeInfo {
     /// Terminal window width
     pub width: f32,
@@ -827,12 +1054,23 @@ pub struct SizeInfo {
     /// DPI factor of the current window
     #[serde(default)]
     pub dpr: f64,
+
+    /// Lines at the bottom of the window which are taken by the message bar
+    #[serde(default)]
+    pub reserved_lines: usize,
 }
 
 impl SizeInfo {
+    /// Lines of the terminal, without the reserved lines
     #[inline]
     pub fn lines(&self) -> Line {
-        Line(((self.height - 2. * self.padding_y) / self.cell_height) as usize)
+        Line(self.window_lines().saturating_sub(self.reserved_lines))
+    }
+
+    /// Lines which fit into the window, including the reserved lines
+    #[inline]
+    pub fn window_lines(&self) -> usize {
+        ((self.height - 2. * self.padding_y) / self.cell_height) as usize
     }
 
     #[inline]
@@ -866,9 +1104,85 @@ impl Term {
 
     /// Clear displayed errors and warnings.
     pub fn clear_log(&mut self) {
-        if let Some(ref mut logger_proxy) = self.logger_proxy {
-            logger_proxy.clear();
+        if let Some(ref logger_proxy) = self.logger_proxy {
+            logger_proxy.messages().clear();
+        }
+        self.damage.damage_all();
+    }
+
+    /// Error or warning shown in the message bar
+    pub fn message(&self) -> Option<Message> {
+        self.logger_proxy.as_ref().and_then(|logger_proxy| logger_proxy.messages().message())
+    }
+
+    /// Close the message shown in the message bar, showing the next one
+    pub fn close_message(&mut self) {
+        if let Some(ref logger_proxy) = self.logger_proxy {
+            logger_proxy.messages().pop();
+        }
+        self.damage.damage_all();
+    }
+
+    /// Annotate a cell of the active area
+    pub fn annotate(&mut self, point: Point, label: Option<String>) {
+        let point = Point::new(self.grid.line_to_offset(point.line), point.col);
+        self.grid.annotate(Annotation { point, label });
+        self.damage.damage_all();
+    }
+
+    pub fn clear_annotations(&mut self) {
+        self.grid.remove_annotations(|_| true);
+        self.damage.damage_all();
+    }
+
+    /// Add or remove a bookmark on the line of the cursor
+    ///
+    /// Bookmarks are annotations without a label, so marks of other tools are left alone.
+    pub fn toggle_bookmark(&mut self) {
+        let line = self.grid.line_to_offset(self.cursor.point.line);
+        let mut removed = false;
+        self.grid.remove_annotations(|annotation| {
+            let bookmark = annotation.point.line == line && annotation.label.is_none();
+            removed |= bookmark;
+            bookmark
+        });
+
+        if !removed {
+            self.grid.annotate(Annotation { point: Point::new(line, Column(0)), label: None });
         }
+        self.damage.damage_all();
+    }
+
+    /// Scroll the closest annotation older or newer than the top line to the top of the screen
+    pub fn jump_to_annotation(&mut self, forward: bool) {
+        let top = self.grid.display_offset() + *self.grid.num_lines() - 1;
+        let lines = self.grid.annotations().iter().map(|annotation| annotation.point.line);
+        let target = if forward {
+            lines.filter(|&line| line < top).max()
+        } else {
+            lines.filter(|&line| line > top).min()
+        };
+
+        if let Some(target) = target {
+            let delta = target as isize - top as isize;
+            self.scroll_display(Scroll::Lines(delta));
+        }
+    }
+
+    /// Visible lines with at least one annotation
+    pub fn annotated_lines(&self) -> Vec<Line> {
+        let mut lines: Vec<Line> = self.grid.annotations()
+            .iter()
+            .filter_map(|annotation| {
+                match self.grid.buffer_line_to_visible(annotation.point.line) {
+                    ViewportPosition::Visible(line) => Some(line),
+                    _ => None,
+                }
+            })
+            .collect();
+        lines.sort();
+        lines.dedup();
+        lines
     }
 
 
@@ -881,9 +1195,92 @@ impl Term {
         self.next_title.take()
     }
 
+    /// Current window title
+    #[inline]
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Accent color set by an application
+    #[inline]
+    pub fn accent_color(&self) -> Option<Rgb> {
+        let profile_accent = self.active_profile.as_ref().and_then(|profile| profile.accent);
+        self.accent_color.or(profile_accent)
+    }
+
+    /// Update the foreground process and switch to its profile
+    pub fn set_foreground_process(&mut self, name: Option<String>) {
+        debug!("Foreground process changed to {:?}", name);
+        self.foreground_process = name;
+        self.apply_profile();
+    }
+
+    /// Apply the colors of the profile matching the foreground process
+    ///
+    /// Colors which were changed by escape sequences are left alone.
+    fn apply_profile(&mut self) {
+        let profile = match self.foreground_process {
+            Some(ref name) => self.profiles.iter().find(|profile| profile.matches(name)).cloned(),
+            None => None,
+        };
+        if profile == self.active_profile {
+            return;
+        }
+
+        let colors = [
+            (NamedColor::Background, profile.as_ref().and_then(|profile| profile.background)),
+            (NamedColor::Foreground, profile.as_ref().and_then(|profile| profile.foreground)),
+        ];
+        for &(index, color) in colors.iter() {
+            if !self.color_modified[index as usize] {
+                self.colors[index] = color.unwrap_or(self.original_colors[index]);
+            }
+        }
+
+        self.active_profile = profile;
+        self.damage.damage_all();
+    }
+
+    #[inline]
+    pub fn output_paused(&self) -> bool {
+        self.output_paused
+    }
+
+    #[inline]
+    pub fn set_output_paused(&mut self, paused: bool) {
+        self.output_paused = paused;
+    }
+
+    /// Text was pasted, its echo on the command line will be marked as pasted
+    ///
+    /// Without shell integration there's no way to tell the echo apart from other output, so
+    /// nothing is marked.
+    pub fn pasted(&mut self, text: &str) {
+        if self.prompt_input {
+            self.pasted_echo += text.chars().filter(|c| !c.is_control()).count();
+        }
+    }
+
+    /// Working directory of the shell, if it has been reported
+    #[inline]
+    pub fn working_directory(&self) -> Option<&Path> {
+        self.working_directory.as_ref().map(PathBuf::as_path)
+    }
+
     pub fn scroll_display(&mut self, scroll: Scroll) {
+        let display_offset = self.grid.display_offset();
         self.grid.scroll_display(scroll);
-        self.dirty = true;
+        if self.grid.display_offset() != display_offset {
+            self.scrolled_at = Some(Instant::now());
+            self.damage.damage_all();
+        }
+    }
+
+    /// Scroll indicator, until it has faded out `fade_delay` after the viewport was last scrolled
+    pub fn scroll_indicator(&self, fade_delay: Duration) -> Option<ScrollIndicator> {
+        let alpha = fade(self.scrolled_at?.elapsed(), fade_delay);
+        let (history, lines) = (self.grid.scroll_limit(), self.grid.num_lines().0);
+        ScrollIndicator::new(self.grid.display_offset(), history, lines, alpha)
     }
 
     #[inline]
@@ -905,19 +1302,41 @@ impl Term {
         let scroll_region = Line(0)..grid.num_lines();
 
         let colors = color::List::from(config.colors());
+        let optional_colors = color::Optional::from(config.colors());
 
         Term {
             next_title: None,
+            title: DEFAULT_TITLE.to_owned(),
+            title_stack: Vec::new(),
+            working_directory: None,
+            output_paused: false,
+            notifications: Vec::new(),
+            command_start: None,
+            prompt_input: false,
+            pasted_echo: 0,
+            accent_color: None,
+            profiles: config.profiles().to_vec(),
+            foreground_process: None,
+            active_profile: None,
             next_mouse_cursor: None,
-            dirty: false,
+            damage: DamageTracker::new(),
+            damaged_cursor_line: Line(0),
             visual_bell: VisualBell::new(config),
             next_is_urgent: None,
+            autoscroll: None,
+            scrolled_at: None,
+            effect_tuning: None,
+            perf_hud: None,
+            screenshot: None,
             input_needs_wrap: false,
             grid,
             alt_grid: alt,
             alt: false,
             font_size: config.font().size(),
             original_font_size: config.font().size(),
+            background_opacity: config.background_opacity(),
+            original_background_opacity: config.background_opacity(),
+            font_family: config.font().normal().family.clone(),
             active_charset: Default::default(),
             cursor: Default::default(),
             cursor_save: Default::default(),
@@ -929,14 +1348,33 @@ impl Term {
             colors,
             color_modified: [false; color::COUNT],
             original_colors: colors,
+            configured_colors: colors,
+            optional_colors,
+            configured_optional_colors: optional_colors,
+            color_schemes: color_schemes(config),
+            color_scheme: None,
+            appearance_schemes: (config.colors().light.clone(), config.colors().dark.clone()),
+            appearance: None,
             semantic_escape_chars: config.selection().semantic_escape_chars.clone(),
             cursor_style: None,
             default_cursor_style: config.cursor_style(),
+            cursor_blinking: None,
+            cursor_blinking_config: config.cursor_blinking(),
+            cursor_blink_interval: config.cursor_blink_interval(),
+            cursor_blink_start: Instant::now(),
             dynamic_title: config.dynamic_title(),
+            report_title: config.report_title(),
+            dynamic_colors: config.colors().dynamic_colors,
+            screenshot_escape: config.screenshot().escape_sequence,
             tabspaces,
             auto_scroll: config.scrolling().auto_scroll,
+            auto_throttle: config.flow_control().auto_throttle,
+            unicode: config.unicode(),
+            text_blink_interval: config.text_blink().interval(),
+            blink_start: Instant::now(),
             logger_proxy: None,
-            undo: MatrixUndo::new(),
+            undo: MatrixUndo::new(config),
+            banner: None,
         }
     }
 
@@ -944,39 +1382,304 @@ impl Term {
         self.logger_proxy = Some(logger_proxy);
     }
 
+    /// Run effects outside of the `effect_schedule`, or follow the schedule again
+    pub fn toggle_forced_effects(&mut self) {
+        self.undo.forced = !self.undo.forced;
+        info!("Effects {}", if self.undo.forced { "forced on" } else { "follow the schedule" });
+    }
+
+    /// Open the effect tuning overlay, or close it and return the config for the adjusted style
+    pub fn toggle_effect_tuning(&mut self) -> Option<String> {
+        self.damage.damage_all();
+        if self.effect_tuning.take().is_some() {
+            Some(config_snippet(&self.undo.style))
+        } else {
+            self.effect_tuning = Some(EffectTuning::default());
+            None
+        }
+    }
+
+    /// Show the performance HUD, or hide it
+    pub fn toggle_perf_hud(&mut self) {
+        self.perf_hud = match self.perf_hud {
+            Some(_) => None,
+            None => Some(PerfHud::default()),
+        };
+        self.damage.damage_all();
+    }
+
+    /// Start typing a banner into the grid, one character per animation tick
+    pub fn start_banner(&mut self, text: &str) {
+        self.banner = Some(Banner::new(text));
+    }
+
+    /// Type the rest of the startup banner at once, before the first output is processed
+    #[inline]
+    pub fn finish_banner(&mut self) {
+        if self.banner.is_some() {
+            type_banner(self, None);
+        }
+    }
+
+    /// Print a note below the final output of a shell which exited while the window is held
+    pub fn show_exit_message(&mut self, exit_status: Option<i32>) {
+        // Nothing reads the pty anymore
+        self.mode.remove(mode::TermMode::FOCUS_IN_OUT);
+
+        let message = match exit_status {
+            Some(code) if code != 0 => format!("[process exited with code {}]", code),
+            _ => String::from("[process exited]"),
+        };
+
+        self.terminal_attribute(Attr::Reset);
+        if self.cursor.point.col != Column(0) {
+            self.carriage_return();
+            self.linefeed();
+        }
+        for c in message.chars() {
+            self.input(c);
+        }
+        self.damage.damage_all();
+    }
+
     pub fn change_font_size(&mut self, delta: f32) {
         // Saturating addition with minimum font size FONT_SIZE_STEP
         let new_size = self.font_size + Size::new(delta);
         self.font_size = max(new_size, Size::new(FONT_SIZE_STEP));
-        self.dirty = true;
+        self.damage.damage_all();
+    }
+
+    /// Family of the normal font
+    #[inline]
+    pub fn font_family(&self) -> &str {
+        &self.font_family
     }
 
     pub fn reset_font_size(&mut self) {
         self.font_size = self.original_font_size;
-        self.dirty = true;
+        self.damage.damage_all();
+    }
+
+    #[inline]
+    pub fn background_opacity(&self) -> Alpha {
+        self.background_opacity
+    }
+
+    pub fn change_background_opacity(&mut self, delta: f32) {
+        self.background_opacity.set(self.background_opacity.get() + delta);
+        self.damage.damage_all();
+    }
+
+    pub fn reset_background_opacity(&mut self) {
+        self.background_opacity = self.original_background_opacity;
+        self.damage.damage_all();
     }
 
     pub fn update_config(&mut self, config: &Config) {
         self.semantic_escape_chars = config.selection().semantic_escape_chars.clone();
-        self.original_colors.fill_named(config.colors());
-        self.original_colors.fill_cube(config.colors());
-        self.original_colors.fill_gray_ramp(config.colors());
-        for i in 0..color::COUNT {
-            if !self.color_modified[i] {
-                self.colors[i] = self.original_colors[i];
-            }
-        }
         self.visual_bell.update_config(config);
         self.default_cursor_style = config.cursor_style();
+        self.cursor_blinking_config = config.cursor_blinking();
+        self.cursor_blink_interval = config.cursor_blink_interval();
         self.dynamic_title = config.dynamic_title();
+        self.report_title = config.report_title();
+        self.dynamic_colors = config.colors().dynamic_colors;
+        self.screenshot_escape = config.screenshot().escape_sequence;
         self.auto_scroll = config.scrolling().auto_scroll;
+        self.auto_throttle = config.flow_control().auto_throttle;
+        self.unicode = config.unicode();
+        self.text_blink_interval = config.text_blink().interval();
+        self.font_family = config.font().normal().family.clone();
+
+        // Keep the size changed at runtime relative to the configured size
+        let delta = self.font_size.as_f32_pts() - self.original_font_size.as_f32_pts();
+        self.original_font_size = config.font().size();
+        self.font_size = max(self.original_font_size + Size::new(delta), Size::new(FONT_SIZE_STEP));
+
+        // The opacity changed at runtime is kept the same way
+        let delta = self.background_opacity.get() - self.original_background_opacity.get();
+        self.original_background_opacity = config.background_opacity();
+        self.background_opacity = Alpha::new(self.original_background_opacity.get() + delta);
+        self.undo.update_config(config);
         self.grid
             .update_history(config.scrolling().history as usize, &self.cursor.template);
+
+        self.profiles = config.profiles().to_vec();
+        self.configured_colors = color::List::from(config.colors());
+        self.configured_optional_colors = color::Optional::from(config.colors());
+        self.color_schemes = color_schemes(config);
+
+        // Keep the selected color scheme, unless it was removed from the config
+        let scheme_colors = self.color_scheme.as_ref().and_then(|name| self.scheme_colors(name));
+        if scheme_colors.is_none() {
+            self.color_scheme = None;
+        }
+        let (colors, optional_colors) = scheme_colors
+            .unwrap_or((self.configured_colors, self.configured_optional_colors));
+        self.original_colors = colors;
+        self.optional_colors = optional_colors;
+        self.refresh_colors();
+
+        // The schemes for the appearance might have changed
+        self.appearance_schemes = (config.colors().light.clone(), config.colors().dark.clone());
+        self.apply_appearance();
+    }
+
+    /// Follow the light or dark appearance of the OS with `colors.light` and `colors.dark`
+    ///
+    /// The scheme is only switched when the appearance changes or the config is reloaded, so
+    /// schemes selected with bindings or escape sequences are kept until then.
+    pub fn set_appearance(&mut self, appearance: Option<Appearance>) {
+        if appearance != self.appearance {
+            self.appearance = appearance;
+            self.apply_appearance();
+        }
+    }
+
+    /// Select the scheme of the current appearance, without a scheme the configured colors are used
+    fn apply_appearance(&mut self) {
+        let (light, dark) = &self.appearance_schemes;
+        let scheme = match self.appearance {
+            _ if light.is_none() && dark.is_none() => return,
+            Some(Appearance::Light) => light.clone(),
+            Some(Appearance::Dark) => dark.clone(),
+            None => return,
+        };
+
+        self.select_color_scheme(scheme.as_ref().map(String::as_str));
+    }
+
+    /// Name of the selected color scheme
+    #[inline]
+    pub fn color_scheme(&self) -> Option<&str> {
+        self.color_scheme.as_ref().map(String::as_str)
+    }
+
+    /// Switch to the color scheme `name`, or back to the configured colors with `None`
+    ///
+    /// Colors which were changed by escape sequences are left alone.
+    pub fn select_color_scheme(&mut self, name: Option<&str>) {
+        let colors = match name {
+            Some(name) => match self.scheme_colors(name) {
+                Some(colors) => colors,
+                None => {
+                    warn!("Unknown color scheme {}", name);
+                    return;
+                },
+            },
+            None => (self.configured_colors, self.configured_optional_colors),
+        };
+
+        debug!("Switching to color scheme {:?}", name);
+        self.color_scheme = name.map(String::from);
+        self.original_colors = colors.0;
+        self.optional_colors = colors.1;
+        self.refresh_colors();
+    }
+
+    /// Switch to the next color scheme, the configured colors follow the last one
+    pub fn cycle_color_scheme(&mut self) {
+        let next = match self.color_scheme {
+            Some(ref name) => {
+                self.color_schemes.iter().position(|scheme| scheme.0 == *name).map_or(0, |i| i + 1)
+            },
+            None => 0,
+        };
+        let name = self.color_schemes.get(next).map(|scheme| scheme.0.clone());
+        self.select_color_scheme(name.as_ref().map(String::as_str));
+    }
+
+    fn scheme_colors(&self, name: &str) -> Option<(color::List, color::Optional)> {
+        self.color_schemes.iter().find(|scheme| scheme.0 == name).map(|scheme| (scheme.1, scheme.2))
+    }
+
+    /// Copy the original colors into all colors which weren't changed by escape sequences
+    fn refresh_colors(&mut self) {
+        for i in 0..color::COUNT {
+            if !self.color_modified[i] {
+                self.colors[i] = self.original_colors[i];
+            }
+        }
+
+        // The profile replaces some of the refreshed colors again
+        self.active_profile = None;
+        self.apply_profile();
+        self.damage.damage_all();
+    }
+
+    #[inline]
+    pub fn auto_throttle(&self) -> bool {
+        self.auto_throttle
     }
 
     #[inline]
     pub fn needs_draw(&self) -> bool {
-        self.dirty
+        self.damage.is_dirty(DamageTracker::RENDERER)
+    }
+
+    /// Pass the lines written to the grid on to the damage tracker, returns false without writes
+    pub fn flush_grid_damage(&mut self) -> bool {
+        let lines = self.grid.take_damage();
+        if lines.is_empty() {
+            return false;
+        }
+
+        if self.grid.display_offset() != 0 {
+            // Lines of the active region are somewhere else on the screen while scrolled back
+            self.damage.damage_all();
+        } else {
+            for lines in lines {
+                self.damage.damage_lines(lines);
+            }
+        }
+        true
+    }
+
+    /// Pass the lines written to the grid and the lines of the cursor on to the damage tracker
+    ///
+    /// Moving, hiding or restyling the cursor doesn't write to the grid, so the lines of the
+    /// cursor before and after the writes are always damaged as well.
+    pub fn flush_damage(&mut self) {
+        self.flush_grid_damage();
+
+        let cursor_line = self.cursor.point.line;
+        self.damage.damage_line(self.damaged_cursor_line);
+        self.damage.damage_line(cursor_line);
+        self.damaged_cursor_line = cursor_line;
+    }
+
+    /// Visible lines and columns covered by the selection
+    ///
+    /// Selections spanning multiple lines cover all columns, unless they're block selections.
+    pub fn selection_cells(&self) -> Option<(Range<Line>, Range<Column>)> {
+        let alt_screen = self.mode.contains(TermMode::ALT_SCREEN);
+        let span = self.grid.selection.as_ref()?.to_span(self, alt_screen)?;
+        let Locations { mut start, mut end, is_block } = span.to_locations();
+
+        if start > end {
+            ::std::mem::swap(&mut start, &mut end);
+        }
+
+        // Buffer lines count up from the bottom, so `end` is the top of the selection
+        let top = match self.grid.buffer_line_to_visible(end.line) {
+            ViewportPosition::Visible(line) => line,
+            ViewportPosition::Above => Line(0),
+            ViewportPosition::Below => return None,
+        };
+        let bottom = match self.grid.buffer_line_to_visible(start.line) {
+            ViewportPosition::Visible(line) => line,
+            ViewportPosition::Below => self.grid.num_lines() - 1,
+            ViewportPosition::Above => return None,
+        };
+
+        let cols = if is_block || start.line == end.line {
+            let last = min(max(start.col, end.col), self.grid.num_cols() - 1);
+            min(start.col, end.col)..last + 1
+        } else {
+            Column(0)..self.grid.num_cols()
+        };
+
+        Some((top..bottom + 1, cols))
     }
 
     pub fn selection_to_string(&self) -> Option<String> {
@@ -1041,7 +1744,9 @@ impl Term {
                             }
                         }
 
-                        if !cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+                        let spacer = cell::Flags::WIDE_CHAR_SPACER
+                            | cell::Flags::LEADING_WIDE_CHAR_SPACER;
+                        if !cell.flags.intersects(spacer) {
                             self.push(cell.c);
                             for c in (&cell.chars()[1..]).iter().filter(|c| **c != ' ') {
                                 self.push(*c);
@@ -1066,12 +1771,23 @@ impl Term {
 
         let mut res = String::new();
 
-        let Locations { mut start, mut end } = span.to_locations();
+        let Locations { mut start, mut end, is_block } = span.to_locations();
 
         if start > end {
             ::std::mem::swap(&mut start, &mut end);
         }
 
+        // Block selection copies the same columns of every line
+        if is_block {
+            let lines: Vec<String> = (start.line..=end.line).rev().map(|line| {
+                let mut text = String::new();
+                text.append(&self.grid, &self.tabs, line, end.col..start.col);
+                text.trim_end_matches('\n').to_owned()
+            }).collect();
+
+            return Some(lines.join("\n"));
+        }
+
         let line_count = end.line - start.line;
         let max_col = Column(usize::max_value() - 1);
 
@@ -1158,30 +1874,78 @@ impl Term {
                 span.to_locations()
             });
 
-        let cursor = if window_focused || !config.unfocused_hollow_cursor() {
-            self.cursor_style.unwrap_or(self.default_cursor_style)
+        let cursor = if !window_focused && config.unfocused_hollow_cursor() {
+            Some(CursorStyle::HollowBlock)
+        } else if window_focused && self.cursor_blink_hidden() {
+            None
         } else {
-            CursorStyle::HollowBlock
+            Some(self.cursor_style.unwrap_or(self.default_cursor_style))
         };
 
         RenderableCellsIter::new(
             &self.grid,
             &self.cursor.point,
             &self.colors,
+            &self.optional_colors,
             self.mode,
             config,
             selection,
             cursor,
+            self.hidden_blink_flags(),
         )
     }
 
-    /// Resize terminal to new dimensions
-    pub fn resize(&mut self, size : &SizeInfo) {
-        debug!("Resizing terminal");
+    /// Check if text with a blink attribute is blinking
+    #[inline]
+    pub fn text_blinks(&self) -> bool {
+        self.text_blink_interval.is_some()
+    }
 
-        // Bounds check; lots of math assumes width and height are > 0
-        if size.width as usize <= 2 * self.size_info.padding_x as usize ||
-            size.height as usize <= 2 * self.size_info.padding_y as usize
+    /// Check if the cursor is blinking
+    #[inline]
+    pub fn cursor_blinks(&self) -> bool {
+        self.cursor_blink_interval.is_some()
+            && self.cursor_blinking_config.blinks(self.cursor_blinking)
+    }
+
+    /// Show the cursor and restart its blink interval
+    #[inline]
+    pub fn reset_cursor_blink(&mut self) {
+        self.cursor_blink_start = Instant::now();
+    }
+
+    /// Check if the blinking cursor is hidden in the current blink phase
+    fn cursor_blink_hidden(&self) -> bool {
+        let interval = match self.cursor_blink_interval {
+            Some(interval) if self.cursor_blinks() => millis(interval),
+            _ => return false,
+        };
+
+        (millis(self.cursor_blink_start.elapsed()) / interval) % 2 == 1
+    }
+
+    /// Blink attributes of the text which is hidden in the current blink phase
+    fn hidden_blink_flags(&self) -> cell::Flags {
+        let mut flags = cell::Flags::empty();
+        let interval = match self.text_blink_interval {
+            Some(interval) => millis(interval),
+            None => return flags,
+        };
+
+        // Rapid blinking is twice as fast as slow blinking
+        let elapsed = millis(self.blink_start.elapsed());
+        flags.set(cell::Flags::BLINK_SLOW, (elapsed / interval) % 2 == 1);
+        flags.set(cell::Flags::BLINK_FAST, (elapsed * 2 / interval) % 2 == 1);
+        flags
+    }
+
+    /// Resize terminal to new dimensions
+    pub fn resize(&mut self, size : &SizeInfo) {
+        debug!("Resizing terminal");
+
+        // Bounds check; lots of math assumes width and height are > 0
+        if size.width as usize <= 2 * self.size_info.padding_x as usize ||
+            size.height as usize <= 2 * self.size_info.padding_y as usize
         {
             return;
         }
@@ -1236,9 +2000,15 @@ impl Term {
 
         debug!("New num_cols is {} and num_lines is {}", num_cols, num_lines);
 
-        // Resize grids to new size
-        self.grid.resize(num_lines, num_cols, &Cell::default());
-        self.alt_grid.resize(num_lines, num_cols, &Cell::default());
+        // Resize grids to new size, only the primary screen is rewrapped
+        let is_alt = self.mode.contains(TermMode::ALT_SCREEN);
+        let alt_cursor_point = if is_alt {
+            &mut self.cursor_save.point
+        } else {
+            &mut self.cursor_save_alt.point
+        };
+        self.grid.resize(!is_alt, num_lines, num_cols, &mut self.cursor.point, &Cell::default());
+        self.alt_grid.resize(is_alt, num_lines, num_cols, alt_cursor_point, &Cell::default());
 
         // Reset scrolling region to new size
         self.scroll_region = Line(0)..self.grid.num_lines();
@@ -1278,6 +2048,7 @@ impl Term {
 
         self.alt = !self.alt;
         ::std::mem::swap(&mut self.grid, &mut self.alt_grid);
+        self.damage.damage_all();
     }
 
     /// Scroll screen down
@@ -1307,6 +2078,95 @@ impl Term {
         self.grid.scroll_up(&(origin..self.scroll_region.end), lines, &self.cursor.template);
     }
 
+    /// Number of columns which fit on a line
+    ///
+    /// Double width lines only have room for half as many characters.
+    #[inline]
+    fn line_cols(&self, line: Line) -> Column {
+        if self.grid[line].attribute().is_double_width() {
+            Column(max(*self.grid.num_cols() / 2, 1))
+        } else {
+            self.grid.num_cols()
+        }
+    }
+
+    /// Make every line in the active region single width again
+    fn reset_line_attributes(&mut self) {
+        for line in IndexRange(Line(0)..self.grid.num_lines()) {
+            self.grid[line].set_attribute(LineAttribute::SingleWidth);
+        }
+    }
+
+    /// Position of the cell containing the previous grapheme cluster
+    fn previous_cell(&self) -> Point {
+        let mut point = self.cursor.point;
+        if !self.input_needs_wrap {
+            point.col = Column(point.col.0.saturating_sub(1));
+        }
+
+        if self.grid[&point].flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+            point.col = Column(point.col.0.saturating_sub(1));
+        }
+
+        point
+    }
+
+    /// Number of cells `c` occupies, based on the configured unicode version
+    fn char_width(&self, c: char) -> Option<usize> {
+        let width = match self.unicode.ambiguous_width {
+            AmbiguousWidth::Narrow => c.width(),
+            AmbiguousWidth::Wide => c.width_cjk(),
+        };
+
+        match width {
+            Some(2) if self.unicode.version == UnicodeVersion::Unicode8 && is_emoji(c) => Some(1),
+            width => width,
+        }
+    }
+
+    /// Check if `c` continues the grapheme cluster of the previous cell
+    fn continues_cluster(&self, c: char) -> bool {
+        match self.char_width(c) {
+            Some(0) => true,
+            // Characters following a zero width joiner are part of the same emoji
+            Some(_) if self.mode.contains(mode::TermMode::GRAPHEME_CLUSTERING) => {
+                self.grid[&self.previous_cell()].last_char() == ZERO_WIDTH_JOINER
+            },
+            _ => false,
+        }
+    }
+
+    /// Add `c` to the grapheme cluster of the previous cell
+    fn join_cluster(&mut self, c: char) {
+        let point = self.previous_cell();
+        self.grid[&point].push_extra(c);
+
+        // An emoji presentation selector turns narrow characters into wide emojis
+        if c != EMOJI_PRESENTATION_SELECTOR
+            || !self.mode.contains(mode::TermMode::GRAPHEME_CLUSTERING)
+            || self.grid[&point].flags.contains(cell::Flags::WIDE_CHAR)
+            || self.input_needs_wrap
+        {
+            return;
+        }
+
+        // The spacer can only be inserted if nothing has been written after the cluster
+        if self.cursor.point.col != point.col + 1 {
+            return;
+        }
+
+        self.grid[&point].flags.insert(cell::Flags::WIDE_CHAR);
+        let spacer = &mut self.grid[&self.cursor.point];
+        *spacer = self.cursor.template;
+        spacer.flags.insert(cell::Flags::WIDE_CHAR_SPACER);
+
+        if (self.cursor.point.col + 1) < self.line_cols(self.cursor.point.line) {
+            self.cursor.point.col += 1;
+        } else {
+            self.input_needs_wrap = true;
+        }
+    }
+
     fn deccolm(&mut self) {
         // Setting 132 column font makes no sense, but run the other side effects
         // Clear scrolling region
@@ -1322,6 +2182,98 @@ impl Term {
     pub fn background_color(&self) -> Rgb {
         self.colors[NamedColor::Background]
     }
+
+    #[inline]
+    pub fn foreground_color(&self) -> Rgb {
+        self.colors[NamedColor::Foreground]
+    }
+
+    /// Visible lines which continue a soft-wrapped line
+    pub fn wrapped_lines(&self) -> Vec<Line> {
+        let last_col = self.grid.num_cols() - 1;
+        (0..self.grid.num_lines().0)
+            .map(Line)
+            .filter(|&line| {
+                let previous = self.grid.visible_line_to_buffer(line) + 1;
+                previous < self.grid.len()
+                    && self.grid[previous][last_col].flags.contains(cell::Flags::WRAPLINE)
+            })
+            .collect()
+    }
+
+    /// Cells of a running refresh sweep, drawn instead of the grid up to the returned column
+    ///
+    /// Columns left of the bar show the screen before the change, the bar shows the new
+    /// content underlined. Lines which are masked from effects are left to the grid.
+    pub fn sweep_cells(&self) -> Option<(Column, Vec<RenderableCell>)> {
+        let sweep = self.undo.sweep.as_ref().filter(|_| self.grid.display_offset() == 0)?;
+        let background = self.colors[NamedColor::Background];
+        let num_lines = self.grid.num_lines().0;
+
+        let mut cells = Vec::new();
+        for (col, column) in sweep.previous.iter().enumerate().take(sweep.column + 1) {
+            for line in (0..num_lines).filter(|&line| !self.undo.is_masked(line)).map(Line) {
+                let mut cell = if col == sweep.column {
+                    let mut cell = self.grid[line][Column(col)];
+                    cell.flags.insert(cell::Flags::UNDERLINE);
+                    cell
+                } else {
+                    match column.get(line.0) {
+                        Some(cell) if !cell.is_empty() => *cell,
+                        _ => continue,
+                    }
+                };
+
+                // Wide characters of the previous screen could overlap the bar
+                if col + 1 == sweep.column && cell.flags.contains(cell::Flags::WIDE_CHAR) {
+                    cell.c = ' ';
+                }
+
+                let (fg, bg) = self.cell_rgb(&cell);
+                cells.push(RenderableCell {
+                    line,
+                    column: Column(col),
+                    chars: cell.chars(),
+                    fg,
+                    bg,
+                    bg_alpha: if bg == background { 0. } else { 1. },
+                    flags: cell.flags,
+                    line_attribute: LineAttribute::default(),
+                });
+            }
+        }
+
+        Some((Column(sweep.column), cells))
+    }
+
+    /// Check if effects leave a visible line alone, since it matched one of the `effect_masks`
+    #[inline]
+    pub fn is_masked(&self, line: Line) -> bool {
+        self.undo.is_masked(line.0)
+    }
+
+    /// Visible cells which are still fading back in after an animation was reverted
+    ///
+    /// Each cell is returned with the color and alpha of the overlay covering it.
+    pub fn fading_cells(&self) -> Vec<(Point, Rgb, f32)> {
+        self.undo.fades.iter()
+            .filter(|fade| self.grid.contains(&fade.point))
+            .filter_map(|fade| {
+                let offset = self.grid.line_to_offset(fade.point.line);
+                match self.grid.buffer_line_to_visible(offset) {
+                    ViewportPosition::Visible(line) => {
+                        let bg = match self.grid[&fade.point].bg {
+                            Color::Spec(rgb) => rgb,
+                            Color::Named(ansi) => self.colors[ansi],
+                            Color::Indexed(idx) => self.colors[idx],
+                        };
+                        Some((Point::new(line, fade.point.col), bg, fade.alpha()))
+                    },
+                    _ => None,
+                }
+            })
+            .collect()
+    }
 }
 
 impl ansi::TermInfo for Term {
@@ -1336,15 +2288,210 @@ impl ansi::TermInfo for Term {
     }
 }
 
+/// Colors of the color schemes in the config, by name
+fn color_schemes(config: &Config) -> Vec<(String, color::List, color::Optional)> {
+    config
+        .color_schemes()
+        .iter()
+        .map(|scheme| {
+            let colors = &scheme.colors;
+            (scheme.name.clone(), color::List::from(colors), color::Optional::from(colors))
+        })
+        .collect()
+}
+
+/// Check if `c` is an emoji which only became wide with Unicode 9
+fn is_emoji(c: char) -> bool {
+    match c {
+        '\u{231a}'..='\u{231b}'
+        | '\u{23e9}'..='\u{23f3}'
+        | '\u{25fd}'..='\u{25fe}'
+        | '\u{2600}'..='\u{27bf}'
+        | '\u{2b1b}'..='\u{2b55}'
+        | '\u{1f300}'..='\u{1f64f}'
+        | '\u{1f680}'..='\u{1f6ff}'
+        | '\u{1f900}'..='\u{1f9ff}' => true,
+        _ => false,
+    }
+}
+
+/// Flag tracking `mode` in the `TermMode`, if it can be changed at all
+fn mode_flag(mode: &ansi::Mode) -> Option<mode::TermMode> {
+    Some(match *mode {
+        ansi::Mode::SwapScreenAndSetRestoreCursor => mode::TermMode::ALT_SCREEN,
+        ansi::Mode::ShowCursor => mode::TermMode::SHOW_CURSOR,
+        ansi::Mode::CursorKeys => mode::TermMode::APP_CURSOR,
+        ansi::Mode::ReportMouseClicks => mode::TermMode::MOUSE_REPORT_CLICK,
+        ansi::Mode::ReportCellMouseMotion => mode::TermMode::MOUSE_DRAG,
+        ansi::Mode::ReportAllMouseMotion => mode::TermMode::MOUSE_MOTION,
+        ansi::Mode::ReportFocusInOut => mode::TermMode::FOCUS_IN_OUT,
+        ansi::Mode::BracketedPaste => mode::TermMode::BRACKETED_PASTE,
+        ansi::Mode::SgrMouse => mode::TermMode::SGR_MOUSE,
+        ansi::Mode::UrxvtMouse => mode::TermMode::URXVT_MOUSE,
+        ansi::Mode::SgrPixelMouse => mode::TermMode::SGR_PIXEL_MOUSE,
+        ansi::Mode::GraphemeClustering => mode::TermMode::GRAPHEME_CLUSTERING,
+        ansi::Mode::LineWrap => mode::TermMode::LINE_WRAP,
+        ansi::Mode::LineFeedNewLine => mode::TermMode::LINE_FEED_NEW_LINE,
+        ansi::Mode::Origin => mode::TermMode::ORIGIN,
+        ansi::Mode::Insert => mode::TermMode::INSERT,
+        ansi::Mode::DECCOLM | ansi::Mode::BlinkingCursor => return None,
+    })
+}
+
+/// Length of `duration` in whole milliseconds
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+/// Encode a `major.minor.patch` version as a single number, like `0.2.7` as `207`
+fn version_number(version: &str) -> usize {
+    version.split(|c: char| !c.is_ascii_digit())
+        .take(3)
+        .fold(0, |number, part| number * 100 + part.parse::<usize>().unwrap_or(0))
+}
+
 impl ansi::Handler for Term {
     /// Set the window title
     #[inline]
     fn set_title(&mut self, title: &str) {
         if self.dynamic_title {
-            self.next_title = Some(title.to_owned());
+            // Control characters would end up in the response to title reports
+            let title: String = title.chars()
+                .filter(|c| !c.is_control())
+                .take(MAX_TITLE_LENGTH)
+                .collect();
+
+            self.title = title.clone();
+            self.next_title = Some(title);
+        }
+    }
+
+    #[inline]
+    fn shell_mark(&mut self, mark: ShellMark) {
+        trace!("Shell mark {:?}", mark);
+        match mark {
+            ShellMark::CommandStart => self.prompt_input = true,
+            ShellMark::CommandExecuted => {
+                self.command_start = Some(Instant::now());
+                self.prompt_input = false;
+                self.pasted_echo = 0;
+            },
+            ShellMark::CommandFinished(status) => {
+                if let Some(start) = self.command_start.take() {
+                    let duration = start.elapsed();
+                    self.notifications.push(Notification::CommandFinished { duration, status });
+                }
+            },
+            ShellMark::PromptStart => {
+                self.prompt_input = false;
+                self.pasted_echo = 0;
+            },
+        }
+    }
+
+    #[inline]
+    fn set_accent_channel(&mut self, index: usize, value: u8) {
+        if !self.dynamic_colors {
+            debug!("Ignoring request to set the accent color");
+            return;
+        }
+
+        trace!("Setting accent color channel {} to {}", index, value);
+        let mut color = self.accent_color.unwrap_or_default();
+        match index {
+            0 => color.r = value,
+            1 => color.g = value,
+            _ => color.b = value,
+        }
+        self.accent_color = Some(color);
+        self.damage.damage_all();
+    }
+
+    #[inline]
+    fn reset_accent_color(&mut self) {
+        trace!("Resetting accent color");
+        self.accent_color = None;
+        self.damage.damage_all();
+    }
+
+    #[inline]
+    fn screenshot(&mut self) {
+        if !self.screenshot_escape {
+            debug!("Ignoring screenshot request");
+            return;
+        }
+
+        self.screenshot = Some(screenshot::Region::Window);
+    }
+
+    #[inline]
+    fn adjust_font_size(&mut self, steps: Option<i32>) {
+        trace!("Adjusting font size by {:?} steps", steps);
+        match steps {
+            Some(steps) => self.change_font_size(steps as f32 * FONT_SIZE_STEP),
+            None => self.reset_font_size(),
+        }
+    }
+
+    #[inline]
+    fn set_font_family(&mut self, family: &str) {
+        trace!("Setting font family to {}", family);
+        self.font_family = family.to_owned();
+    }
+
+    #[inline]
+    fn report_font<W: io::Write>(&mut self, writer: &mut W) {
+        trace!("Reporting font family");
+        let _ = write!(writer, "\x1b]50;{}\x07", self.font_family);
+    }
+
+    #[inline]
+    fn set_color_scheme(&mut self, name: Option<&str>) {
+        if !self.dynamic_colors {
+            debug!("Ignoring request to switch the color scheme");
+            return;
+        }
+
+        self.select_color_scheme(name);
+    }
+
+    #[inline]
+    fn set_working_directory(&mut self, path: PathBuf) {
+        trace!("Setting working directory to {:?}", path);
+        self.working_directory = Some(path);
+    }
+
+    #[inline]
+    fn push_title(&mut self) {
+        trace!("Pushing '{}' onto title stack", self.title);
+
+        if self.title_stack.len() >= TITLE_STACK_MAX_DEPTH {
+            self.title_stack.remove(0);
+        }
+        self.title_stack.push(self.title.clone());
+    }
+
+    #[inline]
+    fn pop_title(&mut self) {
+        trace!("Attempting to pop title from stack");
+
+        if let Some(title) = self.title_stack.pop() {
+            trace!("Title '{}' popped from stack", title);
+            self.set_title(&title);
         }
     }
 
+    #[inline]
+    fn report_title<W: io::Write>(&mut self, writer: &mut W) {
+        if !self.report_title {
+            debug!("Ignoring title report request");
+            return;
+        }
+
+        trace!("Reporting window title");
+        let _ = write!(writer, "\x1b]l{}\x1b\\", self.title);
+    }
+
     /// Set the mouse cursor
     #[inline]
     fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
@@ -1362,6 +2509,20 @@ impl ansi::Handler for Term {
             self.scroll_display(Scroll::Bottom);
         }
 
+        // The shell echoes pasted text before anything typed afterwards
+        let pasted = self.prompt_input && self.pasted_echo > 0;
+        if pasted {
+            self.pasted_echo -= 1;
+        }
+
+        // Characters which are part of the previous grapheme cluster don't advance the cursor,
+        // so they must not cause a wrap either
+        if self.continues_cluster(c) {
+            self.damage.damage_line(self.previous_cell().line);
+            self.join_cluster(c);
+            return;
+        }
+
         if self.input_needs_wrap {
             if !self.mode.contains(mode::TermMode::LINE_WRAP) {
                 return;
@@ -1389,8 +2550,10 @@ impl ansi::Handler for Term {
             self.input_needs_wrap = false;
         }
 
+        self.damage.damage_line(self.cursor.point.line);
+
         // Number of cells the char will occupy
-        if let Some(width) = c.width() {
+        if let Some(width) = self.char_width(c) {
             let num_cols = self.grid.num_cols();
 
             // If in insert mode, first shift cells to the right.
@@ -1399,31 +2562,22 @@ impl ansi::Handler for Term {
             {
                 let line = self.cursor.point.line;
                 let col = self.cursor.point.col;
-                let line = &mut self.grid[line];
+                let cells = self.grid[line].cells_mut(..);
 
-                let src = line[col..].as_ptr();
-                let dst = line[(col + width)..].as_mut_ptr();
+                let src = cells[col.0..].as_ptr();
+                let dst = cells[(col + width).0..].as_mut_ptr();
                 unsafe {
                     // memmove
                     ptr::copy(src, dst, (num_cols - col - width).0);
                 }
             }
 
-            // Handle zero-width characters
-            if width == 0 {
-                let col = self.cursor.point.col.0.saturating_sub(1);
-                let line = self.cursor.point.line;
-                if self.grid[line][Column(col)].flags.contains(cell::Flags::WIDE_CHAR_SPACER)
-                {
-                    col.saturating_sub(1);
-                }
-                self.grid[line][Column(col)].push_extra(c);
-                return;
-            }
-
             let cell = &mut self.grid[&self.cursor.point];
             *cell = self.cursor.template;
             cell.c = self.cursor.charsets[self.active_charset].map(c);
+            if pasted {
+                cell.flags.insert(cell::Flags::PASTED);
+            }
 
             // Handle wide chars
             if width == 2 {
@@ -1438,13 +2592,65 @@ impl ansi::Handler for Term {
             }
         }
 
-        if (self.cursor.point.col + 1) < self.grid.num_cols() {
+        if (self.cursor.point.col + 1) < self.line_cols(self.cursor.point.line) {
             self.cursor.point.col += 1;
         } else {
             self.input_needs_wrap = true;
         }
     }
 
+    /// Write a run of printable ASCII a line at a time
+    ///
+    /// Once the first character went through `input`, the rest can't continue a grapheme
+    /// cluster and each cell is only written once.
+    #[inline]
+    fn input_ascii(&mut self, text: &[u8]) {
+        let (&first, mut rest) = match text.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+        self.input(char::from(first));
+
+        // Insert mode and pasted text need the checks of `input` for every character
+        let pasted = self.prompt_input && self.pasted_echo > 0;
+        if pasted || self.mode.contains(mode::TermMode::INSERT) {
+            for &byte in rest {
+                self.input(char::from(byte));
+            }
+            return;
+        }
+
+        while !rest.is_empty() {
+            let line = self.cursor.point.line;
+            let col = self.cursor.point.col;
+            let line_cols = self.line_cols(line);
+
+            // Wrapping is left to `input`
+            if self.input_needs_wrap || col >= line_cols {
+                self.input(char::from(rest[0]));
+                rest = &rest[1..];
+                continue;
+            }
+
+            let count = min(rest.len(), (line_cols - col).0);
+            let template = self.cursor.template;
+            let charset = self.cursor.charsets[self.active_charset];
+            for (cell, &byte) in self.grid[line].cells_mut(col..).iter_mut().zip(&rest[..count]) {
+                *cell = template;
+                cell.c = charset.map(char::from(byte));
+            }
+            self.damage.damage_line(line);
+            rest = &rest[count..];
+
+            if col + count < line_cols {
+                self.cursor.point.col = col + count;
+            } else {
+                self.cursor.point.col = line_cols - 1;
+                self.input_needs_wrap = true;
+            }
+        }
+    }
+
     #[inline]
     fn dectest(&mut self) {
         trace!("Dectesting");
@@ -1453,6 +2659,22 @@ impl ansi::Handler for Term {
 
         self.grid.region_mut(..)
             .each(|c| c.reset(&template));
+        self.reset_line_attributes();
+    }
+
+    #[inline]
+    fn set_line_attribute(&mut self, attribute: LineAttribute) {
+        trace!("Setting line attribute: {:?}", attribute);
+        let line = self.cursor.point.line;
+        self.grid[line].set_attribute(attribute);
+        self.damage.damage_line(line);
+
+        // The cursor can't be placed in the hidden half of a double width line
+        let max_col = self.line_cols(line) - 1;
+        if self.cursor.point.col > max_col {
+            self.cursor.point.col = max_col;
+            self.input_needs_wrap = false;
+        }
     }
 
     #[inline]
@@ -1491,11 +2713,11 @@ impl ansi::Handler for Term {
         let destination = self.cursor.point.col + count;
         let num_cells = (self.size_info.cols() - destination).0;
 
-        let line = &mut self.grid[self.cursor.point.line];
+        let cells = self.grid[self.cursor.point.line].cells_mut(..);
 
         unsafe {
-            let src = line[source..].as_ptr();
-            let dst = line[destination..].as_mut_ptr();
+            let src = cells[source.0..].as_ptr();
+            let dst = cells[destination.0..].as_mut_ptr();
 
             ptr::copy(src, dst, num_cells);
         }
@@ -1503,7 +2725,7 @@ impl ansi::Handler for Term {
         // Cells were just moved out towards the end of the line; fill in
         // between source and dest with blanks.
         let template = self.cursor.template;
-        for c in &mut line[source..destination] {
+        for c in &mut cells[source.0..destination.0] {
             c.reset(&template);
         }
     }
@@ -1541,6 +2763,51 @@ impl ansi::Handler for Term {
         let _ = writer.write_all(b"\x1b[?6c");
     }
 
+    #[inline]
+    fn secondary_device_attributes<W: io::Write>(&mut self, writer: &mut W) {
+        let version = version_number(env!("CARGO_PKG_VERSION"));
+        let _ = write!(writer, "\x1b[>0;{};1c", version);
+    }
+
+    #[inline]
+    fn report_version<W: io::Write>(&mut self, writer: &mut W) {
+        let _ = write!(writer, "\x1bP>|Alacritty {}\x1b\\", env!("CARGO_PKG_VERSION"));
+    }
+
+    #[inline]
+    fn report_mode<W: io::Write>(&mut self, writer: &mut W, private: bool, mode: i64) {
+        trace!("Reporting mode {}", mode);
+
+        // 0: not recognized, 1: set, 2: reset, 4: permanently reset
+        let state = match ansi::Mode::from_primitive(private, mode) {
+            Some(mode) => match mode_flag(&mode) {
+                Some(flag) if self.mode.contains(flag) => 1,
+                Some(_) => 2,
+                None => 4,
+            },
+            None => 0,
+        };
+
+        let private = if private { "?" } else { "" };
+        let _ = write!(writer, "\x1b[{}{};{}$y", private, mode, state);
+    }
+
+    #[inline]
+    fn report_color<W: io::Write>(&mut self, writer: &mut W, prefix: &str, index: usize) {
+        trace!("Reporting color[{}]", index);
+
+        if index >= color::COUNT {
+            return;
+        }
+
+        let Rgb { r, g, b } = self.colors[index];
+        let _ = write!(
+            writer,
+            "\x1b]{};rgb:{:02x}{:02x}/{:02x}{:02x}/{:02x}{:02x}\x07",
+            prefix, r, r, g, g, b, b
+        );
+    }
+
     #[inline]
     fn device_status<W: io::Write>(&mut self, writer: &mut W, arg: usize) {
         trace!("Reporting device status: {}", arg);
@@ -1634,6 +2901,11 @@ impl ansi::Handler for Term {
         trace!("Bell");
         self.visual_bell.ring();
         self.next_is_urgent = Some(true);
+
+        // Bells which ring before the next draw are only delivered once
+        if !self.notifications.contains(&Notification::Bell) {
+            self.notifications.push(Notification::Bell);
+        }
     }
 
     #[inline]
@@ -1717,7 +2989,7 @@ impl ansi::Handler for Term {
 
         let row = &mut self.grid[self.cursor.point.line];
         let template = self.cursor.template; // Cleared cells have current background color set
-        for c in &mut row[start..end] {
+        for c in row.cells_mut(start..end) {
             c.reset(&template);
         }
     }
@@ -1731,11 +3003,11 @@ impl ansi::Handler for Term {
         let end = min(start + count, self.grid.num_cols() - 1);
         let n = (self.size_info.cols() - end).0;
 
-        let line = &mut self.grid[self.cursor.point.line];
+        let cells = self.grid[self.cursor.point.line].cells_mut(..);
 
         unsafe {
-            let src = line[end..].as_ptr();
-            let dst = line[start..].as_mut_ptr();
+            let src = cells[end.0..].as_ptr();
+            let dst = cells[start.0..].as_mut_ptr();
 
             ptr::copy(src, dst, n);
         }
@@ -1744,7 +3016,7 @@ impl ansi::Handler for Term {
         // 1 cell.
         let template = self.cursor.template;
         let end = self.size_info.cols() - count;
-        for c in &mut line[end..] {
+        for c in &mut cells[end.0..] {
             c.reset(&template);
         }
     }
@@ -1807,19 +3079,19 @@ impl ansi::Handler for Term {
         match mode {
             ansi::LineClearMode::Right => {
                 let row = &mut self.grid[self.cursor.point.line];
-                for cell in &mut row[col..] {
+                for cell in row.cells_mut(col..) {
                     cell.reset(&template);
                 }
             },
             ansi::LineClearMode::Left => {
                 let row = &mut self.grid[self.cursor.point.line];
-                for cell in &mut row[..=col] {
+                for cell in row.cells_mut(..=col) {
                     cell.reset(&template);
                 }
             },
             ansi::LineClearMode::All => {
                 let row = &mut self.grid[self.cursor.point.line];
-                for cell in &mut row[..] {
+                for cell in row.cells_mut(..) {
                     cell.reset(&template);
                 }
             },
@@ -1829,9 +3101,15 @@ impl ansi::Handler for Term {
     /// Set the indexed color value
     #[inline]
     fn set_color(&mut self, index: usize, color: Rgb) {
+        if !self.dynamic_colors {
+            debug!("Ignoring request to set color[{}]", index);
+            return;
+        }
+
         trace!("Setting color[{}] = {:?}", index, color);
         self.colors[index] = color;
         self.color_modified[index] = true;
+        self.damage.damage_all();
     }
 
     /// Reset the indexed color to original value
@@ -1840,6 +3118,7 @@ impl ansi::Handler for Term {
         trace!("Reseting color[{}]", index);
         self.colors[index] = self.original_colors[index];
         self.color_modified[index] = false;
+        self.damage.damage_all();
     }
 
     /// Set the clipboard
@@ -1861,7 +3140,8 @@ impl ansi::Handler for Term {
 
         match mode {
             ansi::ClearMode::Below => {
-                for cell in &mut self.grid[self.cursor.point.line][self.cursor.point.col..] {
+                let col = self.cursor.point.col;
+                for cell in self.grid[self.cursor.point.line].cells_mut(col..) {
                     cell.reset(&template);
                 }
                 if self.cursor.point.line < self.grid.num_lines() - 1 {
@@ -1872,6 +3152,7 @@ impl ansi::Handler for Term {
             ansi::ClearMode::All => {
                 self.clear_log();
                 self.grid.region_mut(..).each(|c| c.reset(&template));
+                self.reset_line_attributes();
             },
             ansi::ClearMode::Above => {
                 // If clearing more than one line
@@ -1882,7 +3163,7 @@ impl ansi::Handler for Term {
                 }
                 // Clear up to the current column in the current line
                 let end = min(self.cursor.point.col + 1, self.grid.num_cols());
-                for cell in &mut self.grid[self.cursor.point.line][..end] {
+                for cell in self.grid[self.cursor.point.line].cells_mut(..end) {
                     cell.reset(&template);
                 }
             },
@@ -1912,6 +3193,12 @@ impl ansi::Handler for Term {
     fn reset_state(&mut self) {
         self.input_needs_wrap = false;
         self.next_title = None;
+        self.title_stack.clear();
+        self.working_directory = None;
+        self.command_start = None;
+        self.prompt_input = false;
+        self.pasted_echo = 0;
+        self.accent_color = None;
         self.next_mouse_cursor = None;
         self.alt = false;
         self.cursor = Default::default();
@@ -1924,8 +3211,10 @@ impl ansi::Handler for Term {
         self.colors = self.original_colors;
         self.color_modified = [false; color::COUNT];
         self.cursor_style = None;
+        self.cursor_blinking = None;
         self.grid.clear_history();
         self.grid.region_mut(..).each(|c| c.reset(&Cell::default()));
+        self.reset_line_attributes();
     }
 
     #[inline]
@@ -1961,6 +3250,15 @@ impl ansi::Handler for Term {
             Attr::CancelItalic => self.cursor.template.flags.remove(cell::Flags::ITALIC),
             Attr::Underscore => self.cursor.template.flags.insert(cell::Flags::UNDERLINE),
             Attr::CancelUnderline => self.cursor.template.flags.remove(cell::Flags::UNDERLINE),
+            Attr::BlinkSlow => {
+                self.cursor.template.flags.remove(cell::Flags::BLINK_FAST);
+                self.cursor.template.flags.insert(cell::Flags::BLINK_SLOW);
+            },
+            Attr::BlinkFast => {
+                self.cursor.template.flags.remove(cell::Flags::BLINK_SLOW);
+                self.cursor.template.flags.insert(cell::Flags::BLINK_FAST);
+            },
+            Attr::CancelBlink => self.cursor.template.flags.remove(cell::Flags::BLINK),
             Attr::Hidden => self.cursor.template.flags.insert(cell::Flags::HIDDEN),
             Attr::CancelHidden => self.cursor.template.flags.remove(cell::Flags::HIDDEN),
             Attr::Strike => self.cursor.template.flags.insert(cell::Flags::STRIKEOUT),
@@ -2000,14 +3298,17 @@ impl ansi::Handler for Term {
             ansi::Mode::ReportFocusInOut => self.mode.insert(mode::TermMode::FOCUS_IN_OUT),
             ansi::Mode::BracketedPaste => self.mode.insert(mode::TermMode::BRACKETED_PASTE),
             ansi::Mode::SgrMouse => self.mode.insert(mode::TermMode::SGR_MOUSE),
+            ansi::Mode::UrxvtMouse => self.mode.insert(mode::TermMode::URXVT_MOUSE),
+            ansi::Mode::SgrPixelMouse => self.mode.insert(mode::TermMode::SGR_PIXEL_MOUSE),
+            ansi::Mode::GraphemeClustering => {
+                self.mode.insert(mode::TermMode::GRAPHEME_CLUSTERING)
+            },
             ansi::Mode::LineWrap => self.mode.insert(mode::TermMode::LINE_WRAP),
             ansi::Mode::LineFeedNewLine => self.mode.insert(mode::TermMode::LINE_FEED_NEW_LINE),
             ansi::Mode::Origin => self.mode.insert(mode::TermMode::ORIGIN),
             ansi::Mode::DECCOLM => self.deccolm(),
             ansi::Mode::Insert => self.mode.insert(mode::TermMode::INSERT), // heh
-            ansi::Mode::BlinkingCursor => {
-                trace!("... unimplemented mode");
-            }
+            ansi::Mode::BlinkingCursor => self.set_cursor_blinking(Some(true)),
         }
     }
 
@@ -2040,14 +3341,17 @@ impl ansi::Handler for Term {
             ansi::Mode::ReportFocusInOut => self.mode.remove(mode::TermMode::FOCUS_IN_OUT),
             ansi::Mode::BracketedPaste => self.mode.remove(mode::TermMode::BRACKETED_PASTE),
             ansi::Mode::SgrMouse => self.mode.remove(mode::TermMode::SGR_MOUSE),
+            ansi::Mode::UrxvtMouse => self.mode.remove(mode::TermMode::URXVT_MOUSE),
+            ansi::Mode::SgrPixelMouse => self.mode.remove(mode::TermMode::SGR_PIXEL_MOUSE),
+            ansi::Mode::GraphemeClustering => {
+                self.mode.remove(mode::TermMode::GRAPHEME_CLUSTERING)
+            },
             ansi::Mode::LineWrap => self.mode.remove(mode::TermMode::LINE_WRAP),
             ansi::Mode::LineFeedNewLine => self.mode.remove(mode::TermMode::LINE_FEED_NEW_LINE),
             ansi::Mode::Origin => self.mode.remove(mode::TermMode::ORIGIN),
             ansi::Mode::DECCOLM => self.deccolm(),
             ansi::Mode::Insert => self.mode.remove(mode::TermMode::INSERT),
-            ansi::Mode::BlinkingCursor => {
-                trace!("... unimplemented mode");
-            }
+            ansi::Mode::BlinkingCursor => self.set_cursor_blinking(Some(false)),
         }
     }
 
@@ -2088,6 +3392,13 @@ impl ansi::Handler for Term {
         trace!("Setting cursor style {:?}", style);
         self.cursor_style = style;
     }
+
+    #[inline]
+    fn set_cursor_blinking(&mut self, blinking: Option<bool>) {
+        trace!("Setting cursor blinking {:?}", blinking);
+        self.cursor_blinking = blinking;
+        self.reset_cursor_blink();
+    }
 }
 
 struct TabStops {
@@ -2133,12 +3444,18 @@ mod tests {
 
     use crate::grid::{Grid, Scroll};
     use crate::index::{Point, Line, Column, Side};
-    use crate::ansi::{self, Handler, CharsetIndex, StandardCharset};
+    use crate::ansi::{self, Attr, Color, Handler, CharsetIndex, StandardCharset, LineAttribute};
+    use crate::ansi::ShellMark;
+    use crate::term::color;
     use crate::selection::Selection;
     use std::mem;
     use crate::input::FONT_SIZE_STEP;
     use font::Size;
-    use crate::config::Config;
+    use crate::config::{AmbiguousWidth, Config, CursorBlinking, UnicodeVersion};
+    use std::time::{Duration, Instant};
+    use crate::Rgb;
+    use crate::appearance::Appearance;
+    use crate::term::damage::{self, DamageTracker};
 
     #[test]
     fn semantic_selection_works() {
@@ -2150,6 +3467,7 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
             dpr: 1.0,
+            reserved_lines: 0,
         };
         let mut term = Term::new(&Default::default(), size);
         let mut grid: Grid<Cell> = Grid::new(Line(3), Column(5), 0, Cell::default());
@@ -2194,6 +3512,7 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
             dpr: 1.0,
+            reserved_lines: 0,
         };
         let mut term = Term::new(&Default::default(), size);
         let mut grid: Grid<Cell> = Grid::new(Line(1), Column(5), 0, Cell::default());
@@ -2220,6 +3539,7 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
             dpr: 1.0,
+            reserved_lines: 0,
         };
         let mut term = Term::new(&Default::default(), size);
         let mut grid: Grid<Cell> = Grid::new(Line(3), Column(3), 0, Cell::default());
@@ -2239,24 +3559,8 @@ mod tests {
         assert_eq!(term.selection_to_string(), Some("aaa\n\naaa\n".into()));
     }
 
-    /// Check that the grid can be serialized back and forth losslessly
-    ///
-    /// This test is in the term module as opposed to the grid since we want to
-    /// test this property with a T=Cell.
-    #[test]
-    fn grid_serde() {
-        let template = Cell::default();
-
-        let grid: Grid<Cell> = Grid::new(Line(24), Column(80), 0, template);
-        let serialized = serde_json::to_string(&grid).expect("ser");
-        let deserialized = serde_json::from_str::<Grid<Cell>>(&serialized)
-                                      .expect("de");
-
-        assert_eq!(deserialized, grid);
-    }
-
     #[test]
-    fn input_line_drawing_character() {
+    fn selection_colors() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2265,17 +3569,30 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
             dpr: 1.0,
+            reserved_lines: 0,
         };
-        let mut term = Term::new(&Default::default(), size);
-        let cursor = Point::new(Line(0), Column(0));
-        term.configure_charset(CharsetIndex::G0,
-                               StandardCharset::SpecialCharacterAndLineDrawing);
+        let yaml = "colors:\n  selection: { text: '0x010203', background: '0x040506' }\n";
+        let config: Config = ::serde_yaml::from_str(yaml).unwrap();
+        let mut term = Term::new(&config, size);
         term.input('a');
+        term.input('b');
 
-        assert_eq!(term.grid()[&cursor].c, '▒');
+        let mut selection = Selection::simple(Point { line: 16, col: Column(0) }, Side::Left);
+        selection.update(Point { line: 16, col: Column(0) }, Side::Right);
+        *term.selection_mut() = Some(selection);
+
+        let cells: Vec<_> = term.renderable_cells(&config, true).collect();
+        let selected = cells.iter().find(|cell| cell.column == Column(0)).unwrap();
+        assert_eq!(selected.fg, Rgb { r: 1, g: 2, b: 3 });
+        assert_eq!(selected.bg, Rgb { r: 4, g: 5, b: 6 });
+        assert_eq!(selected.bg_alpha, 1.0);
+
+        let unselected = cells.iter().find(|cell| cell.column == Column(1)).unwrap();
+        assert_eq!(unselected.fg, term.colors[ansi::NamedColor::Foreground]);
     }
 
-    fn change_font_size_works(font_size: f32) {
+    #[test]
+    fn faint_colors() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2284,6 +3601,113 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
             dpr: 1.0,
+            reserved_lines: 0,
+        };
+        let config: Config =
+            ::serde_yaml::from_str("draw_bold_text_with_bright_colors: true\n").unwrap();
+        let mut term = Term::new(&config, size);
+        let spec = Rgb { r: 100, g: 200, b: 50 };
+
+        term.terminal_attribute(Attr::Dim);
+        term.terminal_attribute(Attr::Foreground(Color::Spec(spec)));
+        term.input('a');
+        term.terminal_attribute(Attr::Foreground(Color::Indexed(1)));
+        term.input('b');
+        term.terminal_attribute(Attr::Foreground(Color::Indexed(196)));
+        term.input('c');
+
+        let fg: Vec<Rgb> = term.renderable_cells(&config, false)
+            .filter(|cell| cell.line == Line(0) && cell.column < Column(3))
+            .map(|cell| cell.fg)
+            .collect();
+        assert_eq!(fg, vec![
+            spec * color::DIM_FACTOR,
+            term.colors[ansi::NamedColor::DimRed],
+            term.colors[196_usize] * color::DIM_FACTOR,
+        ]);
+    }
+
+    #[test]
+    fn block_selection_works() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(3), Column(5), 0, Cell::default());
+        for l in 0..3 {
+            for c in 0..5 {
+                grid[Line(l)][Column(c)].c = (b'a' + (l * 5 + c) as u8) as char;
+            }
+        }
+        grid[Line(1)][Column(4)].flags.insert(cell::Flags::WRAPLINE);
+
+        mem::swap(&mut term.grid, &mut grid);
+
+        let mut selection = Selection::block(Point { line: 2, col: Column(1) }, Side::Left);
+        selection.update(Point { line: 0, col: Column(2) }, Side::Right);
+        *term.selection_mut() = Some(selection);
+        assert_eq!(term.selection_to_string(), Some("bc\ngh\nlm".into()));
+
+        let mut selection = Selection::block(Point { line: 1, col: Column(3) }, Side::Left);
+        selection.update(Point { line: 0, col: Column(4) }, Side::Right);
+        *term.selection_mut() = Some(selection);
+        assert_eq!(term.selection_to_string(), Some("ij\nno".into()));
+    }
+
+    /// Check that the grid can be serialized back and forth losslessly
+    ///
+    /// This test is in the term module as opposed to the grid since we want to
+    /// test this property with a T=Cell.
+    #[test]
+    fn grid_serde() {
+        let template = Cell::default();
+
+        let grid: Grid<Cell> = Grid::new(Line(24), Column(80), 0, template);
+        let serialized = serde_json::to_string(&grid).expect("ser");
+        let deserialized = serde_json::from_str::<Grid<Cell>>(&serialized)
+                                      .expect("de");
+
+        assert_eq!(deserialized, grid);
+    }
+
+    #[test]
+    fn input_line_drawing_character() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let cursor = Point::new(Line(0), Column(0));
+        term.configure_charset(CharsetIndex::G0,
+                               StandardCharset::SpecialCharacterAndLineDrawing);
+        term.input('a');
+
+        assert_eq!(term.grid()[&cursor].c, '▒');
+    }
+
+    fn change_font_size_works(font_size: f32) {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0,
         };
         let config: Config = Default::default();
         let mut term: Term = Term::new(&config, size);
@@ -2313,6 +3737,7 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
             dpr: 1.0,
+            reserved_lines: 0,
         };
         let config: Config = Default::default();
         let mut term: Term = Term::new(&config, size);
@@ -2333,6 +3758,7 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
             dpr: 1.0,
+            reserved_lines: 0,
         };
         let config: Config = Default::default();
         let mut term: Term = Term::new(&config, size);
@@ -2344,6 +3770,86 @@ mod tests {
         assert_eq!(term.font_size, expected_font_size);
     }
 
+    #[test]
+    fn font_size_change_survives_config_reload() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0,
+        };
+        let mut term: Term = Term::new(&Default::default(), size);
+        term.change_font_size(2.0);
+
+        let config: Config = ::serde_yaml::from_str("font:\n  size: 20.0\n").unwrap();
+        term.update_config(&config);
+        assert_eq!(term.font_size, Size::new(22.0));
+
+        term.reset_font_size();
+        assert_eq!(term.font_size, Size::new(20.0));
+    }
+
+    #[test]
+    fn background_opacity_changes() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0,
+        };
+        let mut term: Term = Term::new(&Default::default(), size);
+        term.change_background_opacity(0.5);
+        assert_eq!(term.background_opacity().get(), 1.0);
+
+        let config: Config = ::serde_yaml::from_str("background_opacity: 0.5\n").unwrap();
+        term.update_config(&config);
+        term.change_background_opacity(-0.25);
+        assert_eq!(term.background_opacity().get(), 0.25);
+
+        term.reset_background_opacity();
+        assert_eq!(term.background_opacity().get(), 0.5);
+    }
+
+    #[test]
+    fn flush_damage() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        term.flush_damage();
+        term.damage.reset(DamageTracker::RENDERER);
+
+        // Written lines and the lines the cursor moved between
+        term.goto(Line(5), Column(0));
+        term.linefeed();
+        term.flush_damage();
+        let taken = term.damage.take(DamageTracker::RENDERER);
+        assert_eq!(
+            damage::damaged_lines(&taken, Line(17)),
+            Some(vec![Line(0)..Line(1), Line(6)..Line(7)]),
+        );
+
+        term.input('a');
+        term.flush_damage();
+        let taken = term.damage.take(DamageTracker::RENDERER);
+        assert_eq!(damage::damaged_lines(&taken, Line(17)), Some(vec![Line(6)..Line(7)]));
+    }
+
     #[test]
     fn clear_saved_lines() {
         let size = SizeInfo {
@@ -2353,7 +3859,8 @@ mod tests {
             cell_height: 3.0,
             padding_x: 0.0,
             padding_y: 0.0,
-            dpr: 1.0
+            dpr: 1.0,
+            reserved_lines: 0
         };
         let config: Config = Default::default();
         let mut term: Term = Term::new(&config, size);
@@ -2369,6 +3876,522 @@ mod tests {
         scrolled_grid.scroll_display(Scroll::Top);
         assert_eq!(term.grid, scrolled_grid);
     }
+
+    #[test]
+    fn title_stack() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        term.set_title("first\x1b[31m");
+        assert_eq!(term.get_next_title(), Some(String::from("first[31m")));
+
+        term.push_title();
+        term.set_title("second");
+        term.pop_title();
+        assert_eq!(term.get_next_title(), Some(String::from("first[31m")));
+
+        // Popping an empty stack keeps the current title
+        term.pop_title();
+        assert_eq!(term.get_next_title(), None);
+
+        // Titles are only reported when enabled in the config
+        let mut report = Vec::new();
+        term.report_title(&mut report);
+        assert!(report.is_empty());
+
+        term.report_title = true;
+        term.report_title(&mut report);
+        assert_eq!(report, b"\x1b]lfirst[31m\x1b\\");
+    }
+
+    #[test]
+    fn double_width_lines() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        // Only half of the seven columns fit on a double width line
+        term.set_line_attribute(LineAttribute::DoubleWidth);
+        for c in "abcd".chars() {
+            term.input(c);
+        }
+
+        assert_eq!(term.grid()[Line(0)].attribute(), LineAttribute::DoubleWidth);
+        assert_eq!(term.grid()[Line(0)][Column(2)].c, 'c');
+        assert_eq!(term.grid()[Line(1)][Column(0)].c, 'd');
+        assert_eq!(term.grid()[Line(1)].attribute(), LineAttribute::SingleWidth);
+
+        // The cursor is moved out of the hidden half of the line
+        term.goto(Line(1), Column(6));
+        term.set_line_attribute(LineAttribute::DoubleHeightTop);
+        assert_eq!(term.cursor().point, Point::new(Line(1), Column(2)));
+
+        term.clear_screen(ansi::ClearMode::All);
+        assert_eq!(term.grid()[Line(0)].attribute(), LineAttribute::SingleWidth);
+        assert_eq!(term.grid()[Line(1)].attribute(), LineAttribute::SingleWidth);
+    }
+
+    #[test]
+    fn report_mode() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+        term.set_mode(ansi::Mode::BracketedPaste);
+
+        let mut report = Vec::new();
+        term.report_mode(&mut report, true, 2004);
+        term.report_mode(&mut report, true, 1000);
+        term.report_mode(&mut report, true, 12);
+        term.report_mode(&mut report, false, 9999);
+        assert_eq!(report, &b"\x1b[?2004;1$y\x1b[?1000;2$y\x1b[?12;4$y\x1b[9999;0$y"[..]);
+    }
+
+    #[test]
+    fn report_color() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+        term.set_color(1, Rgb { r: 0xab, g: 0x01, b: 0xff });
+
+        let mut report = Vec::new();
+        term.report_color(&mut report, "4;1", 1);
+        assert_eq!(report, &b"\x1b]4;1;rgb:abab/0101/ffff\x07"[..]);
+    }
+
+    #[test]
+    fn dynamic_colors_can_be_disabled() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+        let original = term.colors[1];
+        let color = Rgb { r: 0xab, g: 0x01, b: 0xff };
+
+        term.set_color(1, color);
+        assert_eq!(term.colors[1], color);
+        term.reset_color(1);
+        assert_eq!(term.colors[1], original);
+
+        term.dynamic_colors = false;
+        term.set_color(1, color);
+        assert_eq!(term.colors[1], original);
+    }
+
+    #[test]
+    fn switch_color_scheme() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = ::serde_yaml::from_str(
+            "color_schemes:\n  \
+               - name: light\n    \
+                 primary: { background: '0xffffff', foreground: '0x000000' }\n  \
+               - name: blue\n    \
+                 primary: { background: '0x000080', foreground: '0xffffff' }\n",
+        ).unwrap();
+        let mut term: Term = Term::new(&config, size);
+        let original = term.background_color();
+        let white = Rgb { r: 0xff, g: 0xff, b: 0xff };
+
+        term.select_color_scheme(Some("light"));
+        assert_eq!(term.color_scheme(), Some("light"));
+        assert_eq!(term.background_color(), white);
+
+        // Colors set by escape sequences are kept
+        let color = Rgb { r: 0xab, g: 0x01, b: 0xff };
+        term.set_color(ansi::NamedColor::Foreground as usize, color);
+        term.cycle_color_scheme();
+        assert_eq!(term.color_scheme(), Some("blue"));
+        assert_eq!(term.background_color(), Rgb { r: 0, g: 0, b: 0x80 });
+        assert_eq!(term.foreground_color(), color);
+
+        term.select_color_scheme(Some("missing"));
+        assert_eq!(term.color_scheme(), Some("blue"));
+
+        term.cycle_color_scheme();
+        assert_eq!(term.color_scheme(), None);
+        assert_eq!(term.background_color(), original);
+    }
+
+    #[test]
+    fn color_scheme_cursor_colors() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = ::serde_yaml::from_str(
+            "color_schemes:\n  \
+               - name: cursor\n    \
+                 cursor: { text: '0x010203', cursor: '0x040506' }\n    \
+                 selection: { text: '0x070809', background: '0x0a0b0c' }\n",
+        ).unwrap();
+        let mut term: Term = Term::new(&config, size);
+        let cursor_cell = |term: &Term| {
+            term.renderable_cells(&config, true)
+                .filter(|cell| cell.line == Line(0) && cell.column == Column(0))
+                .last()
+                .unwrap()
+        };
+
+        let cell = cursor_cell(&term);
+        assert_eq!(cell.bg, term.colors[ansi::NamedColor::Foreground]);
+
+        term.select_color_scheme(Some("cursor"));
+        let cell = cursor_cell(&term);
+        assert_eq!(cell.fg, Rgb { r: 1, g: 2, b: 3 });
+        assert_eq!(cell.bg, Rgb { r: 4, g: 5, b: 6 });
+
+        // Selection colors are taken from the scheme as well
+        term.input('a');
+        let mut selection = Selection::simple(Point { line: 16, col: Column(0) }, Side::Left);
+        selection.update(Point { line: 16, col: Column(0) }, Side::Right);
+        *term.selection_mut() = Some(selection);
+        let cell = term.renderable_cells(&config, true)
+            .find(|cell| cell.line == Line(0) && cell.column == Column(0))
+            .unwrap();
+        assert_eq!(cell.fg, Rgb { r: 7, g: 8, b: 9 });
+        assert_eq!(cell.bg, Rgb { r: 10, g: 11, b: 12 });
+    }
+
+    #[test]
+    fn follow_appearance() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = ::serde_yaml::from_str(
+            "colors:\n  \
+               light: paper\n\
+             color_schemes:\n  \
+               - name: paper\n    \
+                 primary: { background: '0xffffff', foreground: '0x000000' }\n",
+        ).unwrap();
+        let mut term: Term = Term::new(&config, size);
+        let original = term.background_color();
+
+        term.set_appearance(Some(Appearance::Light));
+        assert_eq!(term.color_scheme(), Some("paper"));
+        assert_eq!(term.background_color(), Rgb { r: 0xff, g: 0xff, b: 0xff });
+
+        // Without a dark scheme the configured colors are used
+        term.set_appearance(Some(Appearance::Dark));
+        assert_eq!(term.color_scheme(), None);
+        assert_eq!(term.background_color(), original);
+    }
+
+    #[test]
+    fn grapheme_clusters() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+        term.set_mode(ansi::Mode::GraphemeClustering);
+
+        // Woman technologist, followed by a heart with emoji presentation
+        for c in "\u{1f469}\u{200d}\u{1f4bb}\u{2764}\u{fe0f}".chars() {
+            term.input(c);
+        }
+
+        let cluster = term.grid[Line(0)][Column(0)].chars();
+        assert_eq!(&cluster[..3], &['\u{1f469}', '\u{200d}', '\u{1f4bb}']);
+        assert_eq!(term.grid[Line(0)][Column(2)].last_char(), '\u{fe0f}');
+        assert!(term.grid[Line(0)][Column(2)].flags.contains(cell::Flags::WIDE_CHAR));
+        assert!(term.grid[Line(0)][Column(3)].flags.contains(cell::Flags::WIDE_CHAR_SPACER));
+        assert_eq!(term.cursor.point.col, Column(4));
+
+        // Without grapheme clustering, joined emojis get their own cells
+        term.unset_mode(ansi::Mode::GraphemeClustering);
+        for c in "\u{1f469}\u{200d}\u{1f4bb}".chars() {
+            term.input(c);
+        }
+        assert_eq!(term.grid[Line(0)][Column(6)].c, '\u{1f4bb}');
+    }
+
+    #[test]
+    fn configured_char_width() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        assert_eq!(term.char_width('\u{1f600}'), Some(2));
+        assert_eq!(term.char_width('\u{00a7}'), Some(1));
+
+        term.unicode.version = UnicodeVersion::Unicode8;
+        term.unicode.ambiguous_width = AmbiguousWidth::Wide;
+        assert_eq!(term.char_width('\u{1f600}'), Some(1));
+        assert_eq!(term.char_width('\u{00a7}'), Some(2));
+        assert_eq!(term.char_width('\u{4e00}'), Some(2));
+    }
+
+    #[test]
+    fn blinking_text() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+        term.text_blink_interval = Some(Duration::from_millis(500));
+
+        // Slow blinking text is in its second interval, rapid blinking text in its fourth
+        term.blink_start = Instant::now() - Duration::from_millis(750);
+        assert_eq!(term.hidden_blink_flags(), cell::Flags::BLINK);
+
+        term.text_blink_interval = None;
+        assert_eq!(term.hidden_blink_flags(), cell::Flags::empty());
+    }
+
+    #[test]
+    fn blinking_cursor() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+        let mut parser = ansi::Processor::new();
+
+        // Steady by default, blinking block requested with DECSCUSR
+        assert!(!term.cursor_blinks());
+        for byte in b"\x1b[1 q" {
+            parser.advance(&mut term, *byte, &mut ::std::io::sink());
+        }
+        assert!(term.cursor_blinks());
+
+        term.cursor_blink_start = Instant::now() - Duration::from_millis(1000);
+        assert!(term.cursor_blink_hidden());
+        term.reset_cursor_blink();
+        assert!(!term.cursor_blink_hidden());
+
+        // Configured to never blink
+        term.cursor_blinking_config = CursorBlinking::Never;
+        assert!(!term.cursor_blinks());
+    }
+
+    #[test]
+    fn exit_message() {
+        let size = SizeInfo {
+            width: 60.0,
+            height: 15.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        term.input('$');
+        term.show_exit_message(Some(1));
+
+        let line: String = (0..20).map(|col| term.grid()[Line(1)][Column(col)].c).collect();
+        assert_eq!(term.grid()[Line(0)][Column(0)].c, '$');
+        assert_eq!(line, "[process exited with");
+    }
+
+    #[test]
+    fn mark_pasted_echo() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+        let pasted = |term: &Term, col| {
+            term.grid[Line(0)][Column(col)].flags.contains(cell::Flags::PASTED)
+        };
+
+        // Pastes outside of the command line aren't marked
+        term.pasted("a");
+        term.input('a');
+        assert!(!pasted(&term, 0));
+
+        term.shell_mark(ShellMark::CommandStart);
+        term.input('b');
+        term.pasted("cd\n");
+        for c in "cde".chars() {
+            term.input(c);
+        }
+        assert!(!pasted(&term, 1));
+        assert!(pasted(&term, 2));
+        assert!(pasted(&term, 3));
+        assert!(!pasted(&term, 4));
+
+        // Echo which is still pending is dropped once the command runs
+        term.pasted("f");
+        term.shell_mark(ShellMark::CommandExecuted);
+        term.input('f');
+        assert!(!pasted(&term, 5));
+    }
+
+    #[test]
+    fn wrapped_lines() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        // Only the line continuing the wrapped text is marked
+        for c in "wrapped text".chars() {
+            term.input(c);
+        }
+        term.carriage_return();
+        term.linefeed();
+        term.input('x');
+        assert_eq!(term.wrapped_lines(), vec![Line(1)]);
+    }
+
+    #[test]
+    fn bookmarks() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        term.toggle_bookmark();
+        assert_eq!(term.annotated_lines(), vec![Line(0)]);
+
+        // The bookmark scrolls into the history with its line
+        for _ in 0..20 {
+            term.linefeed();
+        }
+        assert!(term.annotated_lines().is_empty());
+
+        term.jump_to_annotation(false);
+        assert_eq!(term.grid().display_offset(), 4);
+        assert_eq!(term.annotated_lines(), vec![Line(0)]);
+
+        // Toggling twice on the cursor line leaves only the first bookmark
+        term.toggle_bookmark();
+        term.toggle_bookmark();
+        assert_eq!(term.grid().annotations().len(), 1);
+
+        term.clear_annotations();
+        assert!(term.grid().annotations().is_empty());
+    }
+
+    #[test]
+    fn version_number() {
+        assert_eq!(super::version_number("0.2.7"), 207);
+        assert_eq!(super::version_number("1.12.3-dev"), 11203);
+    }
 }
 
 #[cfg(all(test, feature = "bench"))]
@@ -0,0 +1,127 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Debug overlay with performance measurements
+//!
+//! The pty reader and the animation thread record their work here while they hold the terminal
+//! lock, the renderer adds its own timings when the overlay is drawn.
+use std::time::Duration;
+
+use crate::meter::Meter;
+
+/// Timings of the renderer, in microseconds
+#[derive(Debug, Default, Copy, Clone)]
+pub struct FrameTimes {
+    /// Time since the previous frame
+    pub interval: f64,
+    /// Average time between frames
+    pub frame: f64,
+    /// Average time spent drawing the grid
+    pub draw: f64,
+    /// Average time spent waiting for the terminal lock
+    pub lock_wait: f64,
+}
+
+/// Number of lines of the overlay
+pub const LINES: usize = 8;
+
+/// State of the performance HUD, while it's shown
+#[derive(Default)]
+pub struct PerfHud {
+    /// Bytes parsed since the last frame
+    pty_bytes: usize,
+    /// Time spent parsing since the last frame
+    parse_time: Duration,
+    /// Longest time the terminal was locked for parsing since the last frame
+    longest_parse: Duration,
+    /// Duration of the animation steps
+    animation: Meter,
+}
+
+impl PerfHud {
+    /// Record `bytes` read from the pty, which took `duration` to parse
+    pub fn parsed(&mut self, bytes: usize, duration: Duration) {
+        self.pty_bytes += bytes;
+        self.parse_time += duration;
+        self.longest_parse = self.longest_parse.max(duration);
+    }
+
+    /// Record the duration of an animation step
+    pub fn animated(&mut self, duration: Duration) {
+        self.animation.add_sample(duration);
+    }
+
+    /// Lines of the overlay, padded to the same width
+    ///
+    /// The pty counters start over for the next frame.
+    pub fn lines(&mut self, times: FrameTimes) -> Vec<String> {
+        let fps = if times.frame > 0. { 1e6 / times.frame } else { 0. };
+        let throughput = if times.interval > 0. {
+            self.pty_bytes as f64 / 1024. / (times.interval / 1e6)
+        } else {
+            0.
+        };
+        let micros = |duration: Duration| {
+            f64::from(duration.subsec_micros()) + duration.as_secs() as f64 * 1e6
+        };
+        let parse_time = micros(self.parse_time);
+        let longest_parse = micros(self.longest_parse);
+
+        self.pty_bytes = 0;
+        self.parse_time = Duration::default();
+        self.longest_parse = Duration::default();
+
+        let lines = vec![
+            String::from(" PERFORMANCE"),
+            format!(" {:>9.1} fps", fps),
+            format!(" {:>9.0} us draw", times.draw),
+            format!(" {:>9.0} us lock wait", times.lock_wait),
+            format!(" {:>9.1} KiB/s pty", throughput),
+            format!(" {:>9.0} us parse", parse_time),
+            format!(" {:>9.0} us longest parse", longest_parse),
+            format!(" {:>9.0} us animation", self.animation.average()),
+        ];
+
+        let width = lines.iter().map(String::len).max().unwrap_or(0) + 1;
+        lines.into_iter().map(|line| format!("{:1$}", line, width)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{FrameTimes, PerfHud, LINES};
+
+    #[test]
+    fn counters_per_frame() {
+        let mut hud = PerfHud::default();
+        hud.parsed(1024, Duration::from_micros(300));
+        hud.parsed(1024, Duration::from_micros(200));
+
+        let times = FrameTimes { interval: 500_000., frame: 40_000., draw: 800., lock_wait: 12. };
+        let lines = hud.lines(times);
+        assert_eq!(lines.len(), LINES);
+        assert!(lines.iter().all(|line| line.len() == lines[0].len()));
+        assert_eq!(lines[1].trim(), "25.0 fps");
+        assert_eq!(lines[4].trim(), "4.0 KiB/s pty");
+        assert_eq!(lines[5].trim(), "500 us parse");
+        assert_eq!(lines[6].trim(), "300 us longest parse");
+
+        let lines = hud.lines(times);
+        assert_eq!(lines[4].trim(), "0.0 KiB/s pty");
+        assert_eq!(lines[5].trim(), "0 us parse");
+        assert_eq!(lines[6].trim(), "0 us longest parse");
+    }
+}
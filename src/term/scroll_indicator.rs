@@ -0,0 +1,94 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Scrollbar along the right edge of the window
+//!
+//! The indicator appears when the viewport is scrolled into the history, and fades out once it
+//! hasn't been scrolled for a while. It's never shown at the bottom of the history.
+use std::time::Duration;
+
+/// Time it takes for the indicator to fade out
+const FADE_DURATION_MS: f32 = 300.;
+
+/// Position of the viewport in the history
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ScrollIndicator {
+    /// Top of the thumb, as fraction of the window height
+    pub top: f32,
+
+    /// Height of the thumb, as fraction of the window height
+    pub height: f32,
+
+    /// Opacity, decreasing while the indicator fades out
+    pub alpha: f32,
+}
+
+impl ScrollIndicator {
+    /// Indicator for a viewport `display_offset` lines above the bottom of the history
+    pub fn new(
+        display_offset: usize,
+        history: usize,
+        lines: usize,
+        alpha: f32,
+    ) -> Option<ScrollIndicator> {
+        if display_offset == 0 || alpha <= 0. {
+            return None;
+        }
+
+        let total = (history + lines) as f32;
+        Some(ScrollIndicator {
+            top: history.saturating_sub(display_offset) as f32 / total,
+            height: lines as f32 / total,
+            alpha,
+        })
+    }
+}
+
+/// Opacity of the indicator `elapsed` after the viewport was last scrolled
+pub fn fade(elapsed: Duration, delay: Duration) -> f32 {
+    let fading = match elapsed.checked_sub(delay) {
+        Some(fading) => fading,
+        None => return 1.,
+    };
+
+    let fading_ms = fading.as_secs() as f32 * 1000. + fading.subsec_millis() as f32;
+    (1. - fading_ms / FADE_DURATION_MS).max(0.)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{fade, ScrollIndicator};
+
+    #[test]
+    fn thumb_position() {
+        assert_eq!(ScrollIndicator::new(0, 30, 10, 1.), None);
+
+        // At the top of the history
+        let top = ScrollIndicator::new(30, 30, 10, 1.).unwrap();
+        assert_eq!((top.top, top.height), (0., 0.25));
+
+        let middle = ScrollIndicator::new(10, 30, 10, 1.).unwrap();
+        assert_eq!((middle.top, middle.height), (0.5, 0.25));
+    }
+
+    #[test]
+    fn fade_after_delay() {
+        let delay = Duration::from_millis(1000);
+        assert_eq!(fade(Duration::from_millis(500), delay), 1.);
+        assert_eq!(fade(Duration::from_millis(1150), delay), 0.5);
+        assert_eq!(fade(Duration::from_millis(2000), delay), 0.);
+    }
+}
@@ -0,0 +1,775 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Compact binary encoding of serde data types
+//!
+//! Values are written in declaration order without field names. Unsigned integers are LEB128
+//! varints, signed ones are zigzag encoded first, and sequences, maps and strings are prefixed
+//! with their length. The format is not self-describing, it only decodes into the types it was
+//! encoded from.
+use std::fmt::{self, Display};
+use std::io::{self, Read, Write};
+
+use serde::de::value::U32Deserializer;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+
+/// Errors occurring while encoding or decoding
+#[derive(Debug)]
+pub enum Error {
+    /// Error reading or writing the data
+    Io(io::Error),
+
+    /// Data which doesn't match the type
+    Custom(String),
+}
+
+impl ::std::error::Error for Error {
+    fn cause(&self) -> Option<&dyn (::std::error::Error)> {
+        match *self {
+            Error::Io(ref err) => Some(err),
+            Error::Custom(_) => None,
+        }
+    }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(ref err) => err.description(),
+            Error::Custom(ref msg) => msg,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::Custom(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(val: io::Error) -> Error {
+        Error::Io(val)
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Error {
+        Error::Custom(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Error {
+        Error::Custom(msg.to_string())
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// Encode `value` into `writer`
+pub fn to_writer<W: Write, T: Serialize + ?Sized>(writer: W, value: &T) -> Result<()> {
+    value.serialize(&mut Serializer { writer })
+}
+
+/// Decode a value from `reader`
+pub fn from_reader<R: Read, T: DeserializeOwned>(reader: R) -> Result<T> {
+    T::deserialize(&mut Deserializer { reader })
+}
+
+struct Serializer<W> {
+    writer: W,
+}
+
+impl<W: Write> Serializer<W> {
+    fn write_byte(&mut self, byte: u8) -> Result<()> {
+        self.writer.write_all(&[byte])?;
+        Ok(())
+    }
+
+    fn write_varint(&mut self, mut value: u64) -> Result<()> {
+        while value >= 0x80 {
+            self.write_byte(value as u8 | 0x80)?;
+            value >>= 7;
+        }
+        self.write_byte(value as u8)
+    }
+
+    fn write_signed(&mut self, value: i64) -> Result<()> {
+        self.write_varint(((value << 1) ^ (value >> 63)) as u64)
+    }
+
+    /// Write the lowest `bytes` bytes of `value` in little endian order
+    fn write_fixed(&mut self, value: u64, bytes: usize) -> Result<()> {
+        let mut buf = [0u8; 8];
+        for (i, byte) in buf.iter_mut().enumerate() {
+            *byte = (value >> (i * 8)) as u8;
+        }
+        self.writer.write_all(&buf[..bytes])?;
+        Ok(())
+    }
+
+    fn write_len(&mut self, len: Option<usize>) -> Result<()> {
+        match len {
+            Some(len) => self.write_varint(len as u64),
+            None => Err(Error::Custom(String::from("length of sequence is unknown"))),
+        }
+    }
+}
+
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, value: bool) -> Result<()> {
+        self.write_byte(value as u8)
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<()> {
+        self.write_signed(value.into())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<()> {
+        self.write_signed(value.into())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<()> {
+        self.write_signed(value.into())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<()> {
+        self.write_signed(value)
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<()> {
+        self.write_byte(value)
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<()> {
+        self.write_varint(value.into())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<()> {
+        self.write_varint(value.into())
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<()> {
+        self.write_varint(value)
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<()> {
+        self.write_fixed(value.to_bits().into(), 4)
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<()> {
+        self.write_fixed(value.to_bits(), 8)
+    }
+
+    fn serialize_char(self, value: char) -> Result<()> {
+        self.write_varint(u64::from(value as u32))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<()> {
+        self.serialize_bytes(value.as_bytes())
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<()> {
+        self.write_len(Some(value.len()))?;
+        self.writer.write_all(value)?;
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.write_byte(0)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        self.write_byte(1)?;
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        self.write_varint(index.into())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.write_varint(index.into())?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self> {
+        self.write_len(len)?;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.write_varint(index.into())?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self> {
+        self.write_len(len)?;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.write_varint(index.into())?;
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl<'a, W: Write> ser::SerializeSeq for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> ser::SerializeTuple for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> ser::SerializeTupleStruct for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> ser::SerializeTupleVariant for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> ser::SerializeMap for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> ser::SerializeStruct for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> ser::SerializeStructVariant for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct Deserializer<R> {
+    reader: R,
+}
+
+impl<R: Read> Deserializer<R> {
+    fn read_byte(&mut self) -> Result<u8> {
+        let mut buf = [0u8; 1];
+        self.reader.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn read_varint(&mut self) -> Result<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(Error::Custom(String::from("varint is too long")))
+    }
+
+    fn read_signed(&mut self) -> Result<i64> {
+        let value = self.read_varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    /// Read `bytes` bytes of a little endian number
+    fn read_fixed(&mut self, bytes: usize) -> Result<u64> {
+        let mut buf = [0u8; 8];
+        self.reader.read_exact(&mut buf[..bytes])?;
+        Ok(buf.iter().enumerate().fold(0, |value, (i, byte)| value | (u64::from(*byte) << (i * 8))))
+    }
+
+    fn read_len(&mut self) -> Result<usize> {
+        Ok(self.read_varint()? as usize)
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.read_len()?;
+
+        // Grow the buffer while reading, a corrupt length shouldn't allocate all memory
+        let mut bytes = Vec::new();
+        (&mut self.reader).take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+
+        Ok(bytes)
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        String::from_utf8(self.read_bytes()?).map_err(|err| Error::Custom(err.to_string()))
+    }
+}
+
+macro_rules! deserialize_unsigned {
+    ($method:ident, $visit:ident, $ty:ident) => {
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            let value = self.read_varint()?;
+            if value > u64::from(::std::$ty::MAX) {
+                return Err(Error::Custom(format!("{} is too big for {}", value, stringify!($ty))));
+            }
+            visitor.$visit(value as $ty)
+        }
+    };
+}
+
+macro_rules! deserialize_signed {
+    ($method:ident, $visit:ident, $ty:ident) => {
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            let value = self.read_signed()?;
+            if value < i64::from(::std::$ty::MIN) || value > i64::from(::std::$ty::MAX) {
+                let msg = format!("{} is out of range for {}", value, stringify!($ty));
+                return Err(Error::Custom(msg));
+            }
+            visitor.$visit(value as $ty)
+        }
+    };
+}
+
+impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
+    type Error = Error;
+
+    deserialize_signed!(deserialize_i8, visit_i8, i8);
+    deserialize_signed!(deserialize_i16, visit_i16, i16);
+    deserialize_signed!(deserialize_i32, visit_i32, i32);
+    deserialize_unsigned!(deserialize_u16, visit_u16, u16);
+    deserialize_unsigned!(deserialize_u32, visit_u32, u32);
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::Custom(String::from("binary format is not self-describing")))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.read_byte()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            byte => Err(Error::Custom(format!("invalid bool {}", byte))),
+        }
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i64(self.read_signed()?)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u8(self.read_byte()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(self.read_varint()?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f32(f32::from_bits(self.read_fixed(4)? as u32))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f64(f64::from_bits(self.read_fixed(8)?))
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let value = self.read_varint()?;
+        match ::std::char::from_u32(value as u32) {
+            Some(c) if value <= u64::from(::std::u32::MAX) => visitor.visit_char(c),
+            _ => Err(Error::Custom(format!("invalid char {}", value))),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.read_string()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.read_string()?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_byte_buf(self.read_bytes()?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_byte_buf(self.read_bytes()?)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.read_byte()? {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            byte => Err(Error::Custom(format!("invalid option tag {}", byte))),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.read_len()?;
+        visitor.visit_seq(Access { de: self, len })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Access { de: self, len })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_seq(Access { de: self, len })
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.read_len()?;
+        visitor.visit_map(Access { de: self, len })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_seq(Access { de: self, len: fields.len() })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::Custom(String::from("binary format has no identifiers")))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::Custom(String::from("binary format is not self-describing")))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Elements of sequences, tuples, structs and maps
+struct Access<'a, R> {
+    de: &'a mut Deserializer<R>,
+    len: usize,
+}
+
+impl<'de, 'a, R: Read> de::SeqAccess<'de> for Access<'a, R> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+
+        self.len -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+impl<'de, 'a, R: Read> de::MapAccess<'de> for Access<'a, R> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+
+        self.len -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+impl<'de, 'a, R: Read> de::EnumAccess<'de> for &'a mut Deserializer<R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let index = self.read_varint()?;
+        if index > u64::from(::std::u32::MAX) {
+            return Err(Error::Custom(format!("invalid variant {}", index)));
+        }
+
+        let variant: U32Deserializer<Error> = (index as u32).into_deserializer();
+        Ok((seed.deserialize(variant)?, self))
+    }
+}
+
+impl<'de, 'a, R: Read> de::VariantAccess<'de> for &'a mut Deserializer<R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Access { de: self, len })
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_seq(Access { de: self, len: fields.len() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{from_reader, to_writer};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Point(i16, i16),
+        Circle { radius: f32 },
+        Named(String),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Drawing {
+        title: Option<String>,
+        glyph: char,
+        visible: bool,
+        offset: i64,
+        shapes: Vec<Shape>,
+        layers: HashMap<u32, u8>,
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut layers = HashMap::new();
+        layers.insert(300, 7);
+
+        let drawing = Drawing {
+            title: Some(String::from("sketch")),
+            glyph: '▒',
+            visible: true,
+            offset: -129,
+            shapes: vec![
+                Shape::Empty,
+                Shape::Point(-1, 1024),
+                Shape::Circle { radius: 2.5 },
+                Shape::Named(String::from("star")),
+            ],
+            layers,
+        };
+
+        let mut bytes = Vec::new();
+        to_writer(&mut bytes, &drawing).unwrap();
+        assert_eq!(from_reader::<_, Drawing>(&bytes[..]).unwrap(), drawing);
+    }
+
+    #[test]
+    fn varints_are_compact() {
+        let mut bytes = Vec::new();
+        to_writer(&mut bytes, &(127u32, 128u64, -1i32)).unwrap();
+        assert_eq!(bytes, [0x7f, 0x80, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn truncated_input() {
+        let mut bytes = Vec::new();
+        to_writer(&mut bytes, &String::from("truncated")).unwrap();
+        bytes.pop();
+        assert!(from_reader::<_, String>(&bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Snapshots of the terminal state
+//!
+//! A snapshot holds the active grid including its scrollback history, the cursor position and
+//! the terminal modes. Snapshot files start with a magic number and the version of the format,
+//! followed by the snapshot in a compact binary encoding.
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::grid::Grid;
+use crate::index::Point;
+use crate::term::cell::Cell;
+use crate::term::{SizeInfo, Term, TermMode};
+
+pub mod binary;
+
+/// First bytes of every snapshot file
+const MAGIC: &[u8; 8] = b"ALACSNAP";
+
+/// Version of the snapshot format written by `Snapshot::write`
+///
+/// When the layout of the snapshot changes, the version is bumped and `Snapshot::read` keeps
+/// decoding older versions into the current layout.
+pub const VERSION: u16 = 1;
+
+/// State of a terminal at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub size: SizeInfo,
+    pub grid: Grid<Cell>,
+    pub cursor: Point,
+    pub mode: TermMode,
+}
+
+impl Snapshot {
+    /// Write the snapshot with the header of the current version
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION as u8, (VERSION >> 8) as u8])?;
+        binary::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Read a snapshot of the current or an older version
+    pub fn read<R: Read>(mut reader: R) -> Result<Snapshot, Error> {
+        let mut header = [0u8; 10];
+        reader.read_exact(&mut header)?;
+        if header[..8] != MAGIC[..] {
+            return Err(Error::InvalidHeader);
+        }
+
+        match u16::from(header[8]) | (u16::from(header[9]) << 8) {
+            1 => Ok(binary::from_reader(reader)?),
+            version => Err(Error::UnsupportedVersion(version)),
+        }
+    }
+
+    /// Save the snapshot to `path`, replacing any existing file
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load the snapshot at `path`
+    pub fn load(path: &Path) -> Result<Snapshot, Error> {
+        Snapshot::read(BufReader::new(File::open(path)?))
+    }
+}
+
+impl Term {
+    /// Capture the active grid, the cursor and the terminal modes
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            size: self.size_info,
+            grid: self.grid.clone(),
+            cursor: self.cursor.point,
+            mode: self.mode,
+        }
+    }
+
+    /// Restore a snapshot, then resize it to the current size of the terminal
+    pub fn restore_snapshot(&mut self, snapshot: Snapshot) {
+        let size = self.size_info;
+
+        // Both grids need the dimensions of the snapshot before it can be resized
+        self.resize(&snapshot.size);
+        self.grid = snapshot.grid;
+        self.cursor.point = snapshot.cursor;
+        self.mode = snapshot.mode;
+        self.resize(&size);
+
+        self.damage.damage_all();
+    }
+}
+
+/// Errors occurring while saving or loading snapshots
+#[derive(Debug)]
+pub enum Error {
+    /// Error reading or writing the file
+    Io(io::Error),
+
+    /// File which isn't a snapshot
+    InvalidHeader,
+
+    /// Snapshot of a newer version of Alacritty
+    UnsupportedVersion(u16),
+
+    /// Snapshot data which can't be decoded
+    Format(binary::Error),
+}
+
+impl ::std::error::Error for Error {
+    fn cause(&self) -> Option<&dyn (::std::error::Error)> {
+        match *self {
+            Error::Io(ref err) => Some(err),
+            Error::Format(ref err) => Some(err),
+            Error::InvalidHeader | Error::UnsupportedVersion(_) => None,
+        }
+    }
+
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(ref err) => err.description(),
+            Error::InvalidHeader => "Not a terminal snapshot",
+            Error::UnsupportedVersion(_) => "Unsupported snapshot version",
+            Error::Format(ref err) => err.description(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "Unable to access snapshot: {}", err),
+            Error::InvalidHeader => write!(f, "Not a terminal snapshot"),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "Unsupported snapshot version {}, expected at most {}", version, VERSION)
+            },
+            Error::Format(ref err) => write!(f, "Invalid snapshot: {}", err),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(val: io::Error) -> Error {
+        Error::Io(val)
+    }
+}
+
+impl From<binary::Error> for Error {
+    fn from(val: binary::Error) -> Error {
+        match val {
+            binary::Error::Io(err) => Error::Io(err),
+            err => Error::Format(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::{Handler, Mode};
+    use crate::config::Config;
+    use crate::index::{Column, Line};
+    use crate::term::dump::DumpSource;
+    use crate::term::{SizeInfo, Term, TermMode};
+
+    use super::{Error, Snapshot};
+
+    fn size(cols: usize, lines: usize) -> SizeInfo {
+        SizeInfo {
+            width: 10.0 * cols as f32,
+            height: 20.0 * lines as f32,
+            cell_width: 10.0,
+            cell_height: 20.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 1.0,
+            reserved_lines: 0,
+        }
+    }
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let mut term = Term::new(&Config::default(), size(10, 5));
+        for c in "snapshot".chars() {
+            term.input(c);
+        }
+        term.set_mode(Mode::BracketedPaste);
+
+        let snapshot = term.snapshot();
+        let mut bytes = Vec::new();
+        snapshot.write(&mut bytes).unwrap();
+
+        let restored = Snapshot::read(&bytes[..]).unwrap();
+        assert_eq!(restored, snapshot);
+        assert!(restored.mode.contains(TermMode::BRACKETED_PASTE));
+        assert_eq!(restored.grid[Line(0)][Column(0)].c, 's');
+    }
+
+    #[test]
+    fn restore_into_resized_term() {
+        let mut term = Term::new(&Config::default(), size(10, 5));
+        for c in "restore".chars() {
+            term.input(c);
+        }
+        let snapshot = term.snapshot();
+
+        let mut restored = Term::new(&Config::default(), size(20, 8));
+        restored.restore_snapshot(snapshot);
+
+        assert_eq!(restored.grid().num_cols(), Column(20));
+        assert_eq!(restored.grid().num_lines(), Line(8));
+        assert!(restored.dump(DumpSource::History, false).contains("restore"));
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let mut bytes = b"ALACSNAP".to_vec();
+        bytes.extend_from_slice(&[0xff, 0xff]);
+
+        match Snapshot::read(&bytes[..]) {
+            Err(Error::UnsupportedVersion(0xffff)) => (),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Conversion of the grid contents to an SVG image
+//!
+//! Every run of cells with the same style becomes a single `text` element, which is stretched
+//! to the width of its cells. This keeps the text on the grid even when the viewer of the image
+//! falls back to a different font.
+use std::fmt::Write;
+use std::mem;
+
+use crate::ansi::{Color, NamedColor};
+use crate::term::cell::{Cell, Flags};
+use crate::term::dump::{run_text, style_runs, DumpSource};
+use crate::term::Term;
+use crate::Rgb;
+
+/// Part of the cell height below the baseline
+const DESCENT: f32 = 0.2;
+
+/// Font size in logical pixels, matching the size of the rasterized glyphs
+///
+/// Core Text treats points as logical pixels, FreeType and RustType use 96 DPI.
+fn font_size_px(size: font::Size) -> f32 {
+    if cfg!(target_os = "macos") {
+        size.as_f32_pts()
+    } else {
+        size.as_f32_pts() * 96. / 72.
+    }
+}
+
+impl Term {
+    /// SVG image of the screen, the scrollback history or the selection
+    ///
+    /// Sizes are in logical pixels, so the image doesn't depend on the DPI of the screen.
+    pub fn svg(&self, source: DumpSource) -> String {
+        let region = self.dump_region(source);
+        let dpr = self.size_info.dpr as f32;
+        let cell_width = self.size_info.cell_width / dpr;
+        let cell_height = self.size_info.cell_height / dpr;
+        let num_cols = self.grid.num_cols();
+        let bounds = region.bounds(num_cols);
+        let background = self.colors[NamedColor::Background];
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" xml:space=\"preserve\" width=\"{0}\" \
+             height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"{2}\" font-size=\"{3}px\">",
+            (bounds.end - bounds.start).0 as f32 * cell_width,
+            (region.top + 1 - region.bottom) as f32 * cell_height,
+            escape(&format!("'{}', monospace", self.font_family)),
+            font_size_px(self.font_size),
+        );
+        let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="{}"/>"#, hex(background));
+
+        for (index, line) in region.lines().enumerate() {
+            let row = &self.grid[line];
+            let y = index as f32 * cell_height;
+
+            for run in style_runs(row, region.cols(line, num_cols)) {
+                let style = row[run.start];
+                let x = (run.start - bounds.start).0 as f32 * cell_width;
+                let width = (run.end - run.start).0 as f32 * cell_width;
+                let (fg, bg) = self.cell_rgb(&style);
+                if bg != background {
+                    let _ = writeln!(
+                        svg,
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                        x, y, width, cell_height, hex(bg),
+                    );
+                }
+
+                let text = run_text(row, run);
+                if !text.trim().is_empty() && !style.flags.contains(Flags::HIDDEN) {
+                    let _ = write!(
+                        svg,
+                        "<text x=\"{}\" y=\"{}\" textLength=\"{}\" fill=\"{}\"",
+                        x, y + cell_height * (1. - DESCENT), width, hex(fg),
+                    );
+                    write_text_style(&mut svg, style.flags);
+                    let _ = writeln!(svg, ">{}</text>", escape(&text));
+                }
+            }
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Foreground and background color of a cell, after applying inverse and dim attributes
+    pub(super) fn cell_rgb(&self, cell: &Cell) -> (Rgb, Rgb) {
+        let fg = match cell.fg {
+            Color::Named(color) if cell.flags.contains(Flags::DIM) => color.to_dim(),
+            color => color,
+        };
+
+        let mut colors = (self.rgb(fg), self.rgb(cell.bg));
+        if cell.inverse() {
+            mem::swap(&mut colors.0, &mut colors.1);
+        }
+        colors
+    }
+
+    #[inline]
+    fn rgb(&self, color: Color) -> Rgb {
+        match color {
+            Color::Spec(rgb) => rgb,
+            Color::Named(color) => self.colors[color],
+            Color::Indexed(index) => self.colors[index],
+        }
+    }
+}
+
+/// Write the SVG attributes for bold, italic, underlined and struck out text
+fn write_text_style(svg: &mut String, flags: Flags) {
+    if flags.contains(Flags::BOLD) {
+        svg.push_str(" font-weight=\"bold\"");
+    }
+
+    if flags.contains(Flags::ITALIC) {
+        svg.push_str(" font-style=\"italic\"");
+    }
+
+    let decoration = match (flags.contains(Flags::UNDERLINE), flags.contains(Flags::STRIKEOUT)) {
+        (true, true) => Some("underline line-through"),
+        (true, false) => Some("underline"),
+        (false, true) => Some("line-through"),
+        (false, false) => None,
+    };
+    if let Some(decoration) = decoration {
+        let _ = write!(svg, " text-decoration=\"{}\"", decoration);
+    }
+}
+
+#[inline]
+pub(super) fn hex(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+/// Escape text for use in XML content and attributes
+pub(super) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::{Attr, Color, Handler, NamedColor};
+    use crate::config::Config;
+    use crate::term::dump::DumpSource;
+    use crate::term::{SizeInfo, Term};
+
+    use super::font_size_px;
+
+    fn term() -> Term {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 6.0,
+            cell_height: 12.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+            dpr: 2.0,
+            reserved_lines: 0
+        };
+        Term::new(&Config::default(), size)
+    }
+
+    #[test]
+    fn svg_text_runs() {
+        let mut term = term();
+
+        term.input('a');
+        term.input('<');
+        term.terminal_attribute(Attr::Bold);
+        term.terminal_attribute(Attr::Foreground(Color::Named(NamedColor::Red)));
+        term.input('b');
+
+        let svg = term.svg(DumpSource::Screen);
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains("width=\"9\" height=\"24\""));
+        assert!(svg.contains(">a&lt;</text>"));
+        assert!(svg.contains("x=\"6\" y=\"4.8\" textLength=\"3\""));
+        assert!(svg.contains("font-weight=\"bold\">b</text>"));
+        assert!(svg.contains(&format!("font-size=\"{}px\"", font_size_px(term.font_size))));
+    }
+}
@@ -15,10 +15,12 @@
 //! tty related functionality
 use mio;
 use std::{env, io};
+use std::path::PathBuf;
 
 use terminfo::Database;
 
-use crate::config::Config;
+use crate::cli::Options;
+use crate::config::{self, Config};
 
 #[cfg(not(windows))]
 mod unix;
@@ -51,6 +53,43 @@ pub trait EventedReadWrite {
     fn read_token(&self) -> mio::Token;
     fn writer(&mut self) -> &mut Self::Writer;
     fn write_token(&self) -> mio::Token;
+
+    /// Process group of the job in the foreground, like an editor started from the shell
+    fn foreground_process_group(&self) -> Option<u32> {
+        None
+    }
+
+    /// Process ID of the shell running in the pty
+    fn child_pid(&self) -> Option<u32> {
+        None
+    }
+
+    /// Handle for checking the exit of the shell from other threads
+    fn child_exit(&self) -> Option<ChildExit> {
+        None
+    }
+
+    /// Whether the shell running in the pty has exited
+    fn child_exited(&self) -> bool {
+        false
+    }
+}
+
+/// Directory the shell is started in, `--working-directory` takes precedence over the config
+///
+/// A leading `~` is expanded. Directories which don't exist are skipped with a warning, so the
+/// shell still starts in the current directory.
+pub fn working_directory(config: &Config, options: &Options) -> Option<PathBuf> {
+    let dir = options.working_dir.as_ref().map(PathBuf::as_path);
+    let dir = dir.or_else(|| config.working_directory())?;
+
+    let expanded = config::expand_tilde(dir);
+    if expanded.is_dir() {
+        Some(expanded)
+    } else {
+        warn!("Unable to start the shell in {}, it is not a directory", dir.display());
+        None
+    }
 }
 
 // Setup environment variables
@@ -75,3 +114,27 @@ pub fn setup_env(config: &Config) {
         env::set_var(key, value);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::cli::Options;
+    use crate::config::Config;
+
+    use super::working_directory;
+
+    #[test]
+    fn missing_working_directory_is_skipped() {
+        let config = Config::default();
+        let mut options = Options::default();
+        assert_eq!(working_directory(&config, &options), None);
+
+        let dir = env::temp_dir();
+        options.working_dir = Some(dir.clone());
+        assert_eq!(working_directory(&config, &options), Some(dir.clone()));
+
+        options.working_dir = Some(dir.join("alacritty-missing-working-directory"));
+        assert_eq!(working_directory(&config, &options), None);
+    }
+}
@@ -20,9 +20,11 @@ use crate::term::SizeInfo;
 use crate::display::OnResize;
 use crate::config::{Config, Shell};
 use crate::cli::Options;
+use crate::window;
 use mio;
 
-use libc::{self, c_int, pid_t, winsize, SIGCHLD, TIOCSCTTY, WNOHANG};
+use libc::{self, c_int, pid_t, winsize, TIOCSCTTY, WNOHANG};
+use parking_lot::Mutex;
 
 use std::os::unix::io::{FromRawFd, RawFd};
 use std::fs::File;
@@ -33,36 +35,55 @@ use std::ptr;
 use mio::unix::EventedFd;
 use std::io;
 use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
 
 
-/// Process ID of child process
+/// Exit status of a shell, shared by the threads watching for its exit
 ///
-/// Necessary to put this in static storage for `sigchld` to have access
-pub static mut PID: pid_t = 0;
+/// The shell is only reaped when its status is checked, so nothing has to be done in a SIGCHLD
+/// handler. Calling exit() in the handler sometimes caused opengl to deadlock.
+#[derive(Clone)]
+pub struct ChildExit {
+    pid: pid_t,
+
+    /// Status reported by `waitpid`, once the child has exited
+    status: Arc<Mutex<Option<c_int>>>,
+}
 
-/// Exit flag
-///
-/// Calling exit() in the SIGCHLD handler sometimes causes opengl to deadlock,
-/// and the process hangs. Instead, this flag is set, and its status can be
-/// checked via `process_should_exit`.
-static mut SHOULD_EXIT: bool = false;
+impl ChildExit {
+    fn new(pid: pid_t) -> ChildExit {
+        ChildExit { pid, status: Arc::new(Mutex::new(None)) }
+    }
 
-extern "C" fn sigchld(_a: c_int) {
-    let mut status: c_int = 0;
-    unsafe {
-        let p = libc::waitpid(PID, &mut status, WNOHANG);
-        if p < 0 {
-            die!("Waiting for pid {} failed: {}\n", PID, errno());
+    fn status(&self) -> Option<c_int> {
+        let mut status = self.status.lock();
+        if status.is_none() {
+            let mut raw = 0;
+            if unsafe { libc::waitpid(self.pid, &mut raw, WNOHANG) } == self.pid {
+                *status = Some(raw);
+            }
         }
 
-        if PID == p {
-            SHOULD_EXIT = true;
-        }
+        *status
     }
-}
 
-pub fn process_should_exit() -> bool {
-    unsafe { SHOULD_EXIT }
+    /// Whether the shell has exited
+    pub fn exited(&self) -> bool {
+        self.status().is_some()
+    }
+
+    /// Exit code of the shell, once it has exited
+    ///
+    /// Like in most shells, a child killed by a signal reports `128 + signal`.
+    pub fn exit_status(&self) -> Option<i32> {
+        let status = self.status()?;
+
+        if libc::WIFSIGNALED(status) {
+            Some(128 + libc::WTERMSIG(status))
+        } else {
+            Some(libc::WEXITSTATUS(status))
+        }
+    }
 }
 
 /// Get the current value of errno
@@ -188,6 +209,9 @@ pub struct Pty {
     pub fd: File,
     pub raw_fd: RawFd,
     token: mio::Token,
+
+    /// Shell running in the pty, playbacks don't have one
+    child: Option<ChildExit>,
 }
 
 impl Pty {
@@ -259,6 +283,11 @@ pub fn new<T: ToWinsize>(
         builder.env("WINDOWID", format!("{}", window_id));
     }
 
+    // Software rendering of Alacritty shouldn't apply to programs started by the shell
+    if window::software_rendering_env_set() {
+        builder.env_remove(window::SOFTWARE_RENDERING_ENV);
+    }
+
     builder.before_exec(move || {
         // Create a new process group
         unsafe {
@@ -288,19 +317,12 @@ pub fn new<T: ToWinsize>(
     });
 
     // Handle set working directory option
-    if let Some(ref dir) = options.working_dir {
-        builder.current_dir(dir.as_path());
+    if let Some(dir) = super::working_directory(config, options) {
+        builder.current_dir(dir);
     }
 
     match builder.spawn() {
         Ok(child) => {
-            unsafe {
-                // Set PID for SIGCHLD handler
-                PID = child.id() as _;
-
-                // Handle SIGCHLD
-                libc::signal(SIGCHLD, sigchld as _);
-            }
             unsafe {
                 // Maybe this should be done outside of this function so nonblocking
                 // isn't forced upon consumers. Although maybe it should be?
@@ -310,7 +332,8 @@ pub fn new<T: ToWinsize>(
             let pty = Pty {
                 fd: unsafe {File::from_raw_fd(master) },
                 raw_fd: master,
-                token: mio::Token::from(0)
+                token: mio::Token::from(0),
+                child: Some(ChildExit::new(child.id() as pid_t)),
             };
             pty.resize(size);
             pty
@@ -321,6 +344,35 @@ pub fn new<T: ToWinsize>(
     }
 }
 
+/// Create a pty without a child process, for writing output to the terminal from Alacritty
+///
+/// The slave side is in raw mode, so the output is passed through unmodified and input written
+/// to the master side is not echoed.
+pub fn new_playback<T: ToWinsize>(size: &T) -> (Pty, File) {
+    let win = size.to_winsize();
+    let (master, slave) = openpty(win.ws_row as _, win.ws_col as _);
+
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(slave, &mut termios) == 0 {
+            libc::cfmakeraw(&mut termios);
+            libc::tcsetattr(slave, libc::TCSANOW, &termios);
+        }
+
+        set_nonblocking(master);
+    }
+
+    let pty = Pty {
+        fd: unsafe { File::from_raw_fd(master) },
+        raw_fd: master,
+        token: mio::Token::from(0),
+        child: None,
+    };
+    pty.resize(size);
+
+    (pty, unsafe { File::from_raw_fd(slave) })
+}
+
 impl EventedReadWrite for Pty {
     type Reader = File;
     type Writer = File;
@@ -376,6 +428,44 @@ impl EventedReadWrite for Pty {
     fn write_token(&self) -> mio::Token {
         self.token
     }
+
+    #[inline]
+    fn foreground_process_group(&self) -> Option<u32> {
+        match unsafe { libc::tcgetpgrp(self.raw_fd) } {
+            -1 => None,
+            pgid => Some(pgid as u32),
+        }
+    }
+
+    #[inline]
+    fn child_pid(&self) -> Option<u32> {
+        self.child.as_ref().map(|child| child.pid as u32)
+    }
+
+    #[inline]
+    fn child_exit(&self) -> Option<ChildExit> {
+        self.child.clone()
+    }
+
+    #[inline]
+    fn child_exited(&self) -> bool {
+        self.child.as_ref().map_or(false, ChildExit::exited)
+    }
+}
+
+/// Name of the executable of a process
+#[cfg(target_os = "linux")]
+pub fn process_name(pid: u32) -> Option<String> {
+    let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    Some(comm.trim_end().to_owned())
+}
+
+/// Name of the executable of a process
+///
+/// Only Linux exposes process names without extra dependencies.
+#[cfg(not(target_os = "linux"))]
+pub fn process_name(_pid: u32) -> Option<String> {
+    None
 }
 
 /// Types that can produce a `libc::winsize`
@@ -14,6 +14,7 @@
 
 use super::{process_should_exit, Pty, HANDLE};
 
+use std::ffi::OsString;
 use std::i16;
 use std::mem;
 use std::os::windows::io::IntoRawHandle;
@@ -38,6 +39,7 @@ use winapi::um::winbase::{EXTENDED_STARTUPINFO_PRESENT, STARTUPINFOEXW};
 use winapi::um::wincon::COORD;
 
 use crate::cli::Options;
+use crate::tty;
 use crate::config::{Config, Shell};
 use crate::display::OnResize;
 use crate::term::SizeInfo;
@@ -214,18 +216,18 @@ pub fn new<'a>(
     let default_shell = &Shell::new("powershell");
     let shell = config.shell().unwrap_or(default_shell);
     let initial_command = options.command().unwrap_or(shell);
-    let mut cmdline = initial_command.args().to_vec();
-    cmdline.insert(0, initial_command.program().into());
+    let mut cmdline = OsString::from(initial_command.program());
+    for arg in initial_command.args() {
+        cmdline.push(" ");
+        cmdline.push(arg);
+    }
 
     // Warning, here be borrow hell
-    let cwd = options
-        .working_dir
-        .as_ref()
-        .map(|dir| canonicalize(dir).unwrap());
+    let cwd = tty::working_directory(config, options).and_then(|dir| canonicalize(dir).ok());
     let cwd = cwd.as_ref().map(|dir| dir.to_str().unwrap());
 
     // Create the client application, using startup info containing ConPTY info
-    let cmdline = U16CString::from_str(&cmdline.join(" ")).unwrap().into_raw();
+    let cmdline = U16CString::from_os_str(&cmdline).unwrap().into_raw();
     let cwd = cwd.map(|s| U16CString::from_str(&s).unwrap());
     let cwd_ptr = match &cwd {
         Some(b) => b.as_ptr() as LPCWSTR,
@@ -54,6 +54,27 @@ pub fn process_should_exit() -> bool {
     }
 }
 
+/// Exit of the child process, there is only one per process on Windows
+#[derive(Clone)]
+pub struct ChildExit;
+
+impl ChildExit {
+    /// Whether the child process has exited
+    pub fn exited(&self) -> bool {
+        process_should_exit()
+    }
+
+    /// Exit code of the child process, which is not available through winpty and conpty
+    pub fn exit_status(&self) -> Option<i32> {
+        None
+    }
+}
+
+/// Name of the executable of a process, the foreground process isn't known on Windows
+pub fn process_name(_pid: u32) -> Option<String> {
+    None
+}
+
 #[derive(Clone)]
 pub enum PtyHandle<'a> {
     Winpty(winpty::WinptyHandle<'a>),
@@ -331,4 +352,14 @@ impl<'a> EventedReadWrite for Pty<'a> {
     fn write_token(&self) -> mio::Token {
         self.write_token
     }
+
+    #[inline]
+    fn child_exit(&self) -> Option<ChildExit> {
+        Some(ChildExit)
+    }
+
+    #[inline]
+    fn child_exited(&self) -> bool {
+        process_should_exit()
+    }
 }
@@ -30,6 +30,7 @@ use winpty::Config as WinptyConfig;
 use crate::config::{Config, Shell};
 use crate::display::OnResize;
 use crate::cli::Options;
+use crate::tty;
 use crate::term::SizeInfo;
 
 // We store a raw pointer because we need mutable access to call
@@ -98,11 +99,13 @@ pub fn new<'a>(
     let default_shell = &Shell::new("powershell");
     let shell = config.shell().unwrap_or(default_shell);
     let initial_command = options.command().unwrap_or(shell);
-    let mut cmdline = initial_command.args().to_vec();
+    // Winpty only takes UTF-8 command lines
+    let mut cmdline: Vec<_> =
+        initial_command.args().iter().map(|arg| arg.to_string_lossy()).collect();
     cmdline.insert(0, initial_command.program().into());
 
     // Warning, here be borrow hell
-    let cwd = options.working_dir.as_ref().map(|dir| canonicalize(dir).unwrap());
+    let cwd = tty::working_directory(config, options).and_then(|dir| canonicalize(dir).ok());
     let cwd = cwd.as_ref().map(|dir| dir.to_str().unwrap());
 
     // Spawn process
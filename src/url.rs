@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::path::PathBuf;
+
 use url::Url;
 
 // See https://tools.ietf.org/html/rfc3987#page-13
@@ -20,6 +22,60 @@ const URL_DENY_END_CHARS: [char; 8] = ['.', ',', ';', ':', '?', '!', '/', '('];
 const URL_SCHEMES: [&str; 8] = [
     "http", "https", "mailto", "news", "file", "git", "ssh", "ftp",
 ];
+const FILE_DENY_START_CHARS: [char; 3] = ['(', '[', '\''];
+const FILE_DENY_END_CHARS: [char; 7] = ['.', ',', ';', ':', ')', ']', '\''];
+
+/// Location in a file, like the `src/main.rs:10:5` printed by compilers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileLocation {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: Option<usize>,
+}
+
+impl FileLocation {
+    /// Parse `path:line` or `path:line:column`
+    ///
+    /// Paths without a line number are ignored, since most words could be a file name.
+    pub fn parse(text: &str) -> Option<FileLocation> {
+        let text = text
+            .trim_start_matches(|c| FILE_DENY_START_CHARS.contains(&c))
+            .trim_end_matches(|c| FILE_DENY_END_CHARS.contains(&c));
+
+        if text.contains("://") {
+            return None;
+        }
+
+        // Split off up to two trailing numbers, the last one is the column if there are two
+        let mut path = text;
+        let mut numbers = Vec::with_capacity(2);
+        while numbers.len() < 2 {
+            let index = match path.rfind(':') {
+                Some(index) => index,
+                None => break,
+            };
+
+            match path[index + 1..].parse::<usize>() {
+                Ok(number) if number > 0 => numbers.push(number),
+                _ => break,
+            }
+            path = &path[..index];
+        }
+
+        let (line, column) = match numbers.as_slice() {
+            [line] => (*line, None),
+            [column, line] => (*line, Some(*column)),
+            _ => return None,
+        };
+
+        // Timestamps like `12:30:15` are not paths
+        if !path.chars().any(|c| !c.is_ascii_digit()) {
+            return None;
+        }
+
+        Some(FileLocation { path: PathBuf::from(path), line, column })
+    }
+}
 
 // Parser for streaming inside-out detection of URLs.
 pub struct UrlParser {
@@ -109,6 +165,11 @@ impl UrlParser {
         }
     }
 
+    /// Returns the file location if the parser has found any.
+    pub fn file_location(self) -> Option<FileLocation> {
+        FileLocation::parse(&self.state)
+    }
+
     fn advance(&mut self, c: char, pos: usize) -> bool {
         if URL_SEPARATOR_CHARS.contains(&c)
             || (c >= '\u{00}' && c <= '\u{1F}')
@@ -125,7 +186,9 @@ impl UrlParser {
 #[cfg(test)]
 mod tests {
     use std::mem;
+    use std::path::PathBuf;
 
+    use super::FileLocation;
     use crate::grid::Grid;
     use crate::index::{Column, Line, Point};
     use crate::term::{Cell, Search, SizeInfo, Term};
@@ -139,6 +202,7 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
             dpr: 1.0,
+            reserved_lines: 0,
         };
 
         let mut term = Term::new(&Default::default(), size);
@@ -234,4 +298,34 @@ mod tests {
         url_test("ssh://example.org", "ssh://example.org", 5);
         url_test("ftp://example.org", "ftp://example.org", 5);
     }
+
+    fn file_location_test(input: &str, path: &str, line: usize, column: Option<usize>) {
+        let expected = FileLocation { path: PathBuf::from(path), line, column };
+        assert_eq!(FileLocation::parse(input), Some(expected));
+    }
+
+    #[test]
+    fn file_location_parse() {
+        file_location_test("src/main.rs:10", "src/main.rs", 10, None);
+        file_location_test("src/main.rs:10:5", "src/main.rs", 10, Some(5));
+        file_location_test("(src/main.rs:10:5),", "src/main.rs", 10, Some(5));
+        file_location_test("/tmp/a:b.rs:3:", "/tmp/a:b.rs", 3, None);
+        file_location_test("main.rs:1:2:3", "main.rs:1", 2, Some(3));
+    }
+
+    #[test]
+    fn file_location_skip_invalid() {
+        assert_eq!(FileLocation::parse("src/main.rs"), None);
+        assert_eq!(FileLocation::parse("src/main.rs:0"), None);
+        assert_eq!(FileLocation::parse("12:30:15"), None);
+        assert_eq!(FileLocation::parse("https://example.org:80"), None);
+        assert_eq!(FileLocation::parse(":10"), None);
+    }
+
+    #[test]
+    fn file_location_search() {
+        let term = url_create_term("--> src/main.rs:10:5 ");
+        let location = term.file_location_search(Point::new(0, Column(8)));
+        assert_eq!(location.map(|location| location.line), Some(10));
+    }
 }
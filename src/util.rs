@@ -14,8 +14,14 @@
 #[cfg(not(windows))]
 use std::os::unix::process::CommandExt;
 use std::process::Command;
+use std::borrow::Cow;
 use std::ffi::OsStr;
-use std::{cmp, io};
+use std::{cmp, io, iter};
+
+use unicode_width::UnicodeWidthChar;
+
+#[cfg(not(windows))]
+use crate::window;
 
 /// Threading utilities
 pub mod thread {
@@ -40,6 +46,57 @@ pub fn limit<T: Ord>(value: T, min: T, max: T) -> T {
     cmp::min(cmp::max(value, min), max)
 }
 
+/// Number of cells taken by `text`, wide characters take two cells
+pub fn text_width(text: &str) -> usize {
+    text.chars().map(|c| c.width().unwrap_or(0)).sum()
+}
+
+/// Shorten `text` to at most `columns` cells by replacing its middle with an ellipsis
+///
+/// Both ends keep whole characters, a wide character which doesn't fit is dropped entirely.
+pub fn truncate_middle(text: &str, columns: usize) -> Cow<'_, str> {
+    if text_width(text) <= columns {
+        return Cow::Borrowed(text);
+    } else if columns == 0 {
+        return Cow::Borrowed("");
+    }
+
+    // The ellipsis takes one cell, the start of the text gets the odd one out
+    let tail_width = (columns - 1) / 2;
+    let head_width = columns - 1 - tail_width;
+
+    let mut head = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if width + char_width > head_width {
+            break;
+        }
+        width += char_width;
+        head.push(c);
+    }
+
+    let mut tail = Vec::new();
+    width = 0;
+    for c in text.chars().rev() {
+        let char_width = c.width().unwrap_or(0);
+        if width + char_width > tail_width {
+            break;
+        }
+        width += char_width;
+        tail.push(c);
+    }
+
+    // Drop combining characters whose base character was cut off
+    while tail.last().map_or(false, |c| c.width() == Some(0)) {
+        tail.pop();
+    }
+
+    head.push('…');
+    head.extend(tail.into_iter().rev());
+    Cow::Owned(head)
+}
+
 /// Utilities for writing to the
 pub mod fmt {
     use std::fmt;
@@ -77,14 +134,33 @@ pub mod fmt {
     }
 }
 
-#[cfg(not(windows))]
 pub fn start_daemon<I, S>(program: &str, args: I) -> io::Result<()>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
 {
-    Command::new(program)
+    start_daemon_with_env(program, args, iter::empty::<(&str, &str)>())
+}
+
+#[cfg(not(windows))]
+pub fn start_daemon_with_env<I, S, E, K, V>(program: &str, args: I, env: E) -> io::Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+        E: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+{
+    let mut command = Command::new(program);
+
+    // Software rendering of Alacritty shouldn't apply to the programs it starts
+    if window::software_rendering_env_set() {
+        command.env_remove(window::SOFTWARE_RENDERING_ENV);
+    }
+
+    command
         .args(args)
+        .envs(env)
         .before_exec(|| unsafe {
             ::libc::daemon(1, 0);
             Ok(())
@@ -95,17 +171,20 @@ pub fn start_daemon<I, S>(program: &str, args: I) -> io::Result<()>
 }
 
 #[cfg(windows)]
-pub fn start_daemon<I, S>(program: &str, args: I) -> io::Result<()>
+pub fn start_daemon_with_env<I, S, E, K, V>(program: &str, args: I, env: E) -> io::Result<()>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
+        E: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
 {
-    Command::new(program).args(args).spawn().map(|_| ())
+    Command::new(program).args(args).envs(env).spawn().map(|_| ())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::limit;
+    use super::{limit, text_width, truncate_middle};
 
     #[test]
     fn limit_works() {
@@ -113,4 +192,25 @@ mod tests {
         assert_eq!(10, limit(5, 10, 100));
         assert_eq!(100, limit(1000, 10, 100));
     }
+
+    #[test]
+    fn truncate_middle_works() {
+        assert_eq!(truncate_middle("short", 10), "short");
+        assert_eq!(truncate_middle("short", 5), "short");
+        assert_eq!(truncate_middle("/home/user/alacritty.log", 11), "/home…y.log");
+        assert_eq!(truncate_middle("abc", 1), "…");
+        assert_eq!(truncate_middle("abc", 0), "");
+    }
+
+    #[test]
+    fn truncate_middle_wide_chars() {
+        // Wide characters which don't fit entirely are dropped
+        let truncated = truncate_middle("日本語のテキスト", 8);
+        assert_eq!(truncated, "日本…ト");
+        assert!(text_width(&truncated) <= 8);
+
+        // Combining characters stay with their base character
+        assert_eq!(truncate_middle("abcde\u{301}f", 4), "ab…f");
+        assert_eq!(truncate_middle("abe\u{301}ghij", 6), "abe\u{301}…ij");
+    }
 }
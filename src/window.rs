@@ -12,7 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use std::convert::From;
+use std::env;
 use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::gl;
 use glutin::GlContext;
@@ -21,18 +23,28 @@ use glutin::Icon;
 #[cfg(windows)]
 use image::ImageFormat;
 use glutin::{
-    self, ContextBuilder, ControlFlow, Event, EventsLoop,
-    MouseCursor as GlutinMouseCursor, WindowBuilder,
+    self, Api, ContextBuilder, EventsLoop, EventsLoopProxy, GlRequest,
+    MouseCursor as GlutinMouseCursor, WindowBuilder, WindowId,
 };
 use glutin::dpi::{LogicalPosition, LogicalSize, PhysicalSize};
 
-use crate::cli::Options;
-use crate::config::{Decorations, WindowConfig};
+use crate::cli::{Class, Options};
+use crate::config::{Decorations, RendererBackend, StartupMode, WindowConfig};
 use crate::MouseCursor;
 
 #[cfg(windows)]
 static WINDOW_ICON: &'static [u8] = include_bytes!("../assets/windows/alacritty.ico");
 
+/// Environment variable making Mesa use its software rasterizer
+pub const SOFTWARE_RENDERING_ENV: &str = "LIBGL_ALWAYS_SOFTWARE";
+
+/// Mesa selects the software rasterizer through the environment, other platforms with the pixel
+/// format of the context
+const MESA: bool = cfg!(not(any(target_os = "macos", windows)));
+
+/// Whether `SOFTWARE_RENDERING_ENV` was set by Alacritty instead of being inherited
+static SOFTWARE_RENDERING_ENV_SET: AtomicBool = AtomicBool::new(false);
+
 /// Default text for the window's title bar, if not overriden.
 ///
 /// In X11, this the default value for the `WM_NAME` property.
@@ -40,9 +52,8 @@ pub const DEFAULT_TITLE: &str = "Alacritty";
 
 /// Default text for general window class, X11 specific.
 ///
-/// In X11, this is the default value for the `WM_CLASS` property. The
-/// second value of `WM_CLASS` is **never** changed to anything but
-/// the default value.
+/// In X11, this is the default value for both parts of the `WM_CLASS`
+/// property, which are changed with `--class instance,general`.
 ///
 /// ```ignore
 /// $ xprop | grep WM_CLASS
@@ -65,17 +76,30 @@ type Result<T> = ::std::result::Result<T, Error>;
 
 /// A window which can be used for displaying the terminal
 ///
-/// Wraps the underlying windowing library to provide a stable API in Alacritty
+/// Wraps the underlying windowing library to provide a stable API in Alacritty. All windows
+/// share the events loop they were created with, events are told apart by the window's `id`.
 pub struct Window {
-    event_loop: EventsLoop,
+    event_loop_proxy: EventsLoopProxy,
     window: glutin::GlWindow,
     mouse_visible: bool,
+    fullscreen: bool,
+    simple_fullscreen: bool,
+    decorations: bool,
+    always_on_top: bool,
+    sticky: bool,
 
     /// Whether or not the window is the focused window.
     pub is_focused: bool,
+
+    /// Kind of the OpenGL context, for sharing textures with new windows
+    context_kind: ContextKind,
+
+    /// Textures are shared with the context of another window
+    shares_textures: bool,
 }
 
 /// Threadsafe APIs for the window
+#[derive(Clone)]
 pub struct Proxy {
     inner: glutin::EventsLoopProxy,
 }
@@ -129,31 +153,139 @@ impl From<glutin::ContextError> for Error {
     }
 }
 
+/// Kind of OpenGL context of a window, only contexts of the same kind can share textures
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct ContextKind {
+    srgb: bool,
+    gles: bool,
+    software: bool,
+}
+
+impl ContextKind {
+    /// Whether a context of this kind can be used for the renderer backend
+    fn supports(self, renderer: RendererBackend) -> bool {
+        match renderer {
+            RendererBackend::Auto => true,
+            RendererBackend::Gl3 => !self.gles && !self.software,
+            RendererBackend::Gles2 => self.gles,
+            RendererBackend::Software => self.software,
+        }
+    }
+}
+
 fn create_gl_window(
     window: WindowBuilder,
     event_loop: &EventsLoop,
-    srgb: bool,
+    kind: ContextKind,
+    shared: Option<&glutin::Context>,
 ) -> ::std::result::Result<glutin::GlWindow, glutin::CreationError> {
-    let context = ContextBuilder::new()
+    let ContextKind { srgb, gles, software } = kind;
+
+    // With Mesa, `use_software_rendering` has selected the rasterizer for all contexts already
+    let hardware_acceleration = if software && !MESA { Some(false) } else { None };
+
+    let mut context = ContextBuilder::new()
         .with_srgb(srgb)
         .with_vsync(true)
-        .with_hardware_acceleration(None);
+        .with_hardware_acceleration(hardware_acceleration);
+    if gles {
+        context = context.with_gl(GlRequest::Specific(Api::OpenGlEs, (2, 0)));
+    }
+    if let Some(shared) = shared {
+        context = context.with_shared_lists(shared);
+    }
     ::glutin::GlWindow::new(window, context, event_loop)
 }
 
+/// Make OpenGL contexts use the CPU rasterizer of the OpenGL implementation
+///
+/// Mesa only reads this from the environment, so it has to be called before any other threads
+/// are started. The variable stays set for the contexts of later windows, shells don't inherit it.
+pub fn use_software_rendering() {
+    if MESA && env::var_os(SOFTWARE_RENDERING_ENV).is_none() {
+        env::set_var(SOFTWARE_RENDERING_ENV, "1");
+        SOFTWARE_RENDERING_ENV_SET.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Whether `SOFTWARE_RENDERING_ENV` has to be removed from the environment of shells
+pub fn software_rendering_env_set() -> bool {
+    SOFTWARE_RENDERING_ENV_SET.load(Ordering::Relaxed)
+}
+
+/// Exit when the window can't be embedded into the parent passed with `--embed`
+#[cfg(
+    any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd"
+    )
+)]
+unsafe extern "C" fn embed_error_handler(
+    _: *mut x11_dl::xlib::Display,
+    _: *mut x11_dl::xlib::XErrorEvent,
+) -> std::os::raw::c_int {
+    die!("Unable to embed the window into the window passed with --embed");
+}
+
 impl Window {
     /// Create a new window
     ///
-    /// This creates a window and fully initializes a window.
-    pub fn new(options: &Options, window_config: &WindowConfig) -> Result<Window> {
-        let event_loop = EventsLoop::new();
-
+    /// This creates a window and fully initializes a window. The OpenGL context shares its
+    /// textures with the context of the `shared` window when possible.
+    pub fn new(
+        event_loop: &EventsLoop,
+        options: &Options,
+        window_config: &WindowConfig,
+        renderer: RendererBackend,
+        shared: Option<&Window>,
+    ) -> Result<Window> {
         let title = options.title.as_ref().map_or(DEFAULT_TITLE, |t| t);
-        let class = options.class.as_ref().map_or(DEFAULT_TITLE, |c| c);
+        let class = options.class.clone().unwrap_or_default();
         let window_builder = Window::get_platform_window(title, window_config);
-        let window_builder = Window::platform_builder_ext(window_builder, &class);
-        let window = create_gl_window(window_builder.clone(), &event_loop, false)
-            .or_else(|_| create_gl_window(window_builder, &event_loop, true))?;
+        let window_builder = Window::platform_builder_ext(window_builder, class);
+        let create = |srgb, gles, software| {
+            let kind = ContextKind { srgb, gles, software };
+            create_gl_window(window_builder.clone(), event_loop, kind, None)
+                .map(|window| (window, kind))
+        };
+
+        let shared = shared
+            .filter(|shared| shared.context_kind.supports(renderer))
+            .and_then(|shared| {
+                let kind = shared.context_kind;
+                let context = shared.window.context();
+                create_gl_window(window_builder.clone(), event_loop, kind, Some(context))
+                    .map(|window| (window, kind))
+                    .map_err(|err| warn!("Unable to share textures with other windows: {}", err))
+                    .ok()
+            });
+        let shares_textures = shared.is_some();
+
+        let gles = renderer == RendererBackend::Gles2;
+        let software = renderer == RendererBackend::Software;
+        let (window, context_kind) = match shared {
+            Some(shared) => shared,
+            None => create(false, gles, software)
+                .or_else(|_| create(true, gles, software))
+                .or_else(|err| match renderer {
+                    // Without a desktop OpenGL context, OpenGL ES might still be available
+                    RendererBackend::Auto => create(false, true, false),
+                    _ => Err(err),
+                })
+                .or_else(|err| match renderer {
+                    // Mesa falls back to its software rasterizer without a hardware driver
+                    RendererBackend::Auto if !MESA => {
+                        warn!(
+                            "Unable to create an OpenGL context ({}), using software rendering",
+                            err
+                        );
+                        create(false, false, true)
+                    },
+                    _ => Err(err),
+                })?,
+        };
         window.show();
 
         // Text cursor
@@ -167,18 +299,40 @@ impl Window {
         // Set OpenGL symbol loader. This call MUST be after window.make_current on windows.
         gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
 
-        let window = Window {
-            event_loop,
+        let mut window = Window {
+            event_loop_proxy: event_loop.create_proxy(),
             window,
             mouse_visible: true,
+            fullscreen: false,
+            simple_fullscreen: false,
+            decorations: window_config.decorations() != Decorations::None,
+            always_on_top: window_config.always_on_top(),
+            sticky: false,
             is_focused: false,
+            context_kind,
+            shares_textures,
         };
 
         window.run_os_extensions();
+        if let Some(parent) = options.embed {
+            window.embed(parent);
+        }
+        window.set_blur(window_config.blur());
+        window.set_sticky(window_config.sticky());
+
+        if window_config.startup_mode() == StartupMode::Fullscreen {
+            window.set_fullscreen(true);
+        }
 
         Ok(window)
     }
 
+    /// Whether the OpenGL context shares its textures with the window passed to `Window::new`
+    #[inline]
+    pub fn shares_textures(&self) -> bool {
+        self.shares_textures
+    }
+
     /// Get some properties about the device
     ///
     /// Some window properties are provided since subsystems like font
@@ -205,36 +359,32 @@ impl Window {
     #[inline]
     pub fn create_window_proxy(&self) -> Proxy {
         Proxy {
-            inner: self.event_loop.create_proxy(),
+            inner: self.event_loop_proxy.clone(),
         }
     }
 
+    /// Identifier of the window in the events it receives
     #[inline]
-    pub fn swap_buffers(&self) -> Result<()> {
-        self.window.swap_buffers().map_err(From::from)
+    pub fn id(&self) -> WindowId {
+        self.window.id()
     }
 
-    /// Poll for any available events
+    /// Make the OpenGL context of this window the target of all rendering
+    ///
+    /// This must be called before drawing when there is more than one window.
     #[inline]
-    pub fn poll_events<F>(&mut self, func: F)
-    where
-        F: FnMut(Event),
-    {
-        self.event_loop.poll_events(func);
+    pub fn make_current(&self) -> Result<()> {
+        unsafe { self.window.make_current().map_err(From::from) }
     }
 
     #[inline]
-    pub fn resize(&self, size: PhysicalSize) {
-        self.window.resize(size);
+    pub fn swap_buffers(&self) -> Result<()> {
+        self.window.swap_buffers().map_err(From::from)
     }
 
-    /// Block waiting for events
     #[inline]
-    pub fn wait_events<F>(&mut self, func: F)
-    where
-        F: FnMut(Event) -> ControlFlow,
-    {
-        self.event_loop.run_forever(func);
+    pub fn resize(&self, size: PhysicalSize) {
+        self.window.resize(size);
     }
 
     /// Set the window title
@@ -270,9 +420,9 @@ impl Window {
             target_os = "openbsd"
         )
     )]
-    fn platform_builder_ext(window_builder: WindowBuilder, wm_class: &str) -> WindowBuilder {
+    fn platform_builder_ext(window_builder: WindowBuilder, class: Class) -> WindowBuilder {
         use glutin::os::unix::WindowBuilderExt;
-        window_builder.with_class(wm_class.to_owned(), "Alacritty".to_owned())
+        window_builder.with_class(class.instance, class.general)
     }
 
     #[cfg(
@@ -285,7 +435,7 @@ impl Window {
             )
         )
     )]
-    fn platform_builder_ext(window_builder: WindowBuilder, _: &str) -> WindowBuilder {
+    fn platform_builder_ext(window_builder: WindowBuilder, _: Class) -> WindowBuilder {
         window_builder
     }
 
@@ -300,7 +450,8 @@ impl Window {
             .with_title(title)
             .with_visibility(false)
             .with_transparency(true)
-            .with_maximized(window_config.start_maximized())
+            .with_maximized(window_config.startup_mode() == StartupMode::Maximized)
+            .with_always_on_top(window_config.always_on_top())
             .with_decorations(decorations)
     }
 
@@ -318,7 +469,8 @@ impl Window {
             .with_visibility(cfg!(windows))
             .with_decorations(decorations)
             .with_transparency(true)
-            .with_maximized(window_config.start_maximized())
+            .with_maximized(window_config.startup_mode() == StartupMode::Maximized)
+            .with_always_on_top(window_config.always_on_top())
             .with_window_icon(Some(icon))
     }
 
@@ -330,7 +482,8 @@ impl Window {
             .with_title(title)
             .with_visibility(false)
             .with_transparency(true)
-            .with_maximized(window_config.start_maximized());
+            .with_maximized(window_config.startup_mode() == StartupMode::Maximized)
+            .with_always_on_top(window_config.always_on_top());
 
         match window_config.decorations() {
             Decorations::Full => window,
@@ -393,6 +546,302 @@ impl Window {
     pub fn hide(&self) {
         self.window.hide();
     }
+
+    /// Make the window cover the monitor it's on, or restore its previous size
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        let monitor = if fullscreen { Some(self.window.get_current_monitor()) } else { None };
+        self.window.set_fullscreen(monitor);
+        self.fullscreen = fullscreen;
+    }
+
+    pub fn toggle_fullscreen(&mut self) {
+        let fullscreen = !self.fullscreen;
+        self.set_fullscreen(fullscreen);
+    }
+
+    /// Fullscreen without the animation and separate space of native macOS fullscreen
+    #[cfg(target_os = "macos")]
+    pub fn toggle_simple_fullscreen(&mut self) {
+        use glutin::os::macos::WindowExt;
+
+        let simple_fullscreen = !self.simple_fullscreen;
+        if self.window.set_simple_fullscreen(simple_fullscreen) {
+            self.simple_fullscreen = simple_fullscreen;
+        }
+    }
+
+    /// Simple fullscreen is only available on macOS, other platforms use normal fullscreen
+    #[cfg(not(target_os = "macos"))]
+    pub fn toggle_simple_fullscreen(&mut self) {
+        self.toggle_fullscreen();
+    }
+
+    pub fn toggle_decorations(&mut self) {
+        self.decorations = !self.decorations;
+        self.window.set_decorations(self.decorations);
+    }
+
+    /// Keep the window above all other windows
+    pub fn set_always_on_top(&mut self, always_on_top: bool) {
+        self.window.set_always_on_top(always_on_top);
+        self.always_on_top = always_on_top;
+    }
+
+    pub fn toggle_always_on_top(&mut self) {
+        let always_on_top = !self.always_on_top;
+        self.set_always_on_top(always_on_top);
+    }
+
+    pub fn toggle_sticky(&mut self) {
+        let sticky = !self.sticky;
+        self.set_sticky(sticky);
+    }
+
+    /// Show the window on all workspaces
+    ///
+    /// The request is sent to the window manager with `_NET_WM_STATE_STICKY`, which isn't
+    /// supported on Wayland.
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    pub fn set_sticky(&mut self, sticky: bool) {
+        use glutin::os::unix::WindowExt;
+        use std::mem;
+        use std::os::raw::c_long;
+        use x11_dl::xlib;
+
+        let xlib_display = self.window.get_xlib_display();
+        let xlib_window = self.window.get_xlib_window();
+
+        let (xlib_window, xlib_display) = match (xlib_window, xlib_display) {
+            (Some(xlib_window), Some(xlib_display)) => (xlib_window, xlib_display),
+            _ => {
+                if sticky {
+                    debug!("Sticky windows are not supported on Wayland");
+                }
+                return;
+            },
+        };
+
+        let xlib = xlib::Xlib::open().expect("get xlib");
+        unsafe {
+            let display = xlib_display as *mut xlib::Display;
+            let atom = |name: &[u8]| (xlib.XInternAtom)(display, name.as_ptr() as *const _, 0);
+
+            // Mapped windows have to ask the window manager to change their state
+            let mut event: xlib::XClientMessageEvent = mem::zeroed();
+            event.type_ = xlib::ClientMessage;
+            event.window = xlib_window as _;
+            event.message_type = atom(b"_NET_WM_STATE\0");
+            event.format = 32;
+            event.data.set_long(0, sticky as c_long);
+            event.data.set_long(1, atom(b"_NET_WM_STATE_STICKY\0") as c_long);
+
+            let root = (xlib.XDefaultRootWindow)(display);
+            let mask = xlib::SubstructureRedirectMask | xlib::SubstructureNotifyMask;
+            let event = &mut event as *mut xlib::XClientMessageEvent as *mut xlib::XEvent;
+            (xlib.XSendEvent)(display, root, 0, mask, event);
+            (xlib.XFlush)(display);
+        }
+
+        self.sticky = sticky;
+    }
+
+    /// Show the window on all spaces
+    #[cfg(target_os = "macos")]
+    pub fn set_sticky(&mut self, sticky: bool) {
+        use glutin::os::macos::WindowExt;
+        use objc::runtime::Object;
+
+        // NSWindowCollectionBehaviorCanJoinAllSpaces
+        const CAN_JOIN_ALL_SPACES: usize = 1;
+
+        unsafe {
+            let nswindow = self.window.get_nswindow() as *mut Object;
+            let behavior: usize = msg_send![nswindow, collectionBehavior];
+            let behavior = if sticky {
+                behavior | CAN_JOIN_ALL_SPACES
+            } else {
+                behavior & !CAN_JOIN_ALL_SPACES
+            };
+            let _: () = msg_send![nswindow, setCollectionBehavior: behavior];
+        }
+
+        self.sticky = sticky;
+    }
+
+    #[cfg(windows)]
+    pub fn set_sticky(&mut self, _sticky: bool) {}
+
+    /// Embed the window into the X11 window `parent`, following the XEmbed protocol
+    ///
+    /// The parent resizes the window, which is handled like any other resize.
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    fn embed(&self, parent: u64) {
+        use glutin::os::unix::WindowExt;
+        use std::os::raw::c_long;
+        use x11_dl::xlib::{self, PropModeReplace};
+
+        let xlib_display = self.window.get_xlib_display();
+        let xlib_window = self.window.get_xlib_window();
+
+        let (xlib_window, xlib_display) = match (xlib_window, xlib_display) {
+            (Some(xlib_window), Some(xlib_display)) => (xlib_window, xlib_display),
+            _ => {
+                warn!("Embedding is not supported on Wayland");
+                return;
+            },
+        };
+
+        let xlib = xlib::Xlib::open().expect("get xlib");
+        unsafe {
+            let display = xlib_display as *mut xlib::Display;
+            let atom = (xlib.XInternAtom)(display, b"_XEMBED_INFO\0".as_ptr() as *const _, 0);
+
+            // Protocol version 0, with the XEMBED_MAPPED flag set
+            let info: [c_long; 2] = [0, 1];
+            (xlib.XChangeProperty)(
+                display,
+                xlib_window as _,
+                atom,
+                atom,
+                32,
+                PropModeReplace,
+                info.as_ptr() as *const u8,
+                2,
+            );
+
+            // Exit if the parent doesn't exist, instead of staying a top-level window
+            let handler = (xlib.XSetErrorHandler)(Some(embed_error_handler));
+            (xlib.XReparentWindow)(display, xlib_window as _, parent as _, 0, 0);
+            (xlib.XSync)(display, 0);
+            (xlib.XSetErrorHandler)(handler);
+        }
+    }
+
+    #[cfg(
+        not(
+            any(
+                target_os = "linux",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "openbsd"
+            )
+        )
+    )]
+    fn embed(&self, _parent: u64) {
+        warn!("Embedding is only supported on X11");
+    }
+
+    /// Ask the compositor to blur the content behind the window
+    ///
+    /// On X11 this is only supported by KWin. Compositors which don't support it ignore the
+    /// request, so the background is just translucent.
+    #[cfg(
+        any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd"
+        )
+    )]
+    pub fn set_blur(&self, blur: bool) {
+        use glutin::os::unix::WindowExt;
+        use std::ffi::CStr;
+        use std::ptr;
+        use x11_dl::xlib::{self, PropModeReplace, XA_CARDINAL};
+
+        let xlib_display = self.window.get_xlib_display();
+        let xlib_window = self.window.get_xlib_window();
+
+        let (xlib_window, xlib_display) = match (xlib_window, xlib_display) {
+            (Some(xlib_window), Some(xlib_display)) => (xlib_window, xlib_display),
+            _ => {
+                if blur {
+                    debug!("Background blur is not supported on Wayland");
+                }
+                return;
+            },
+        };
+
+        let xlib = xlib::Xlib::open().expect("get xlib");
+        unsafe {
+            let name = CStr::from_ptr(b"_KDE_NET_WM_BLUR_BEHIND_REGION\0".as_ptr() as *const _);
+            let atom = (xlib.XInternAtom)(xlib_display as *mut _, name.as_ptr(), 0);
+
+            // An empty region blurs behind the entire window
+            if blur {
+                (xlib.XChangeProperty)(
+                    xlib_display as _,
+                    xlib_window as _,
+                    atom,
+                    XA_CARDINAL,
+                    32,
+                    PropModeReplace,
+                    ptr::null(),
+                    0,
+                );
+            } else {
+                (xlib.XDeleteProperty)(xlib_display as _, xlib_window as _, atom);
+            }
+        }
+    }
+
+    /// Ask the window server to blur the content behind the window
+    ///
+    /// This uses the private `CGSSetWindowBackgroundBlurRadius` of the window server, which is
+    /// also used by other terminals. The public `NSVisualEffectView` can't be used, since it is a
+    /// view of its own and would cover the OpenGL view instead of the desktop behind the window.
+    ///
+    /// Private functions can disappear with any macOS update, so both are looked up at runtime
+    /// and the background just stays translucent without them.
+    #[cfg(target_os = "macos")]
+    pub fn set_blur(&self, blur: bool) {
+        use glutin::os::macos::WindowExt;
+        use objc::runtime::Object;
+        use std::mem;
+        use std::os::raw::{c_int, c_long};
+
+        type MainConnectionId = unsafe extern "C" fn() -> *mut Object;
+        type SetBlurRadius = unsafe extern "C" fn(*mut Object, c_long, c_int) -> c_int;
+
+        let radius = if blur { 20 } else { 0 };
+        unsafe {
+            let symbol = |name: &[u8]| libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr() as *const _);
+            let connection = symbol(b"CGSMainConnectionID\0");
+            let set_radius = symbol(b"CGSSetWindowBackgroundBlurRadius\0");
+            if connection.is_null() || set_radius.is_null() {
+                if blur {
+                    warn!("Background blur is not supported by this version of macOS");
+                }
+                return;
+            }
+
+            let connection: MainConnectionId = mem::transmute(connection);
+            let set_radius: SetBlurRadius = mem::transmute(set_radius);
+
+            let nswindow = self.window.get_nswindow() as *mut Object;
+            let window_number: c_long = msg_send![nswindow, windowNumber];
+            if set_radius(connection(), window_number, radius) != 0 {
+                warn!("Unable to set the background blur of the window");
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    pub fn set_blur(&self, _blur: bool) {}
 }
 
 pub trait OsExtensions {
@@ -466,7 +915,7 @@ impl OsExtensions for Window {
 }
 
 impl Proxy {
-    /// Wakes up the event loop of the window
+    /// Wakes up the event loop shared by all windows
     ///
     /// This is useful for triggering a draw when the renderer would otherwise
     /// be waiting on user input.
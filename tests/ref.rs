@@ -10,8 +10,10 @@ use alacritty::Grid;
 use alacritty::Term;
 use alacritty::ansi;
 use alacritty::index::Column;
+use alacritty::ref_test;
 use alacritty::term::Cell;
 use alacritty::term::SizeInfo;
+use alacritty::term::snapshot::Snapshot;
 use alacritty::util::fmt::{Red, Green};
 use alacritty::config::Config;
 
@@ -51,16 +53,9 @@ ref_tests! {
     history
     grid_reset
     zerowidth
-}
-
-fn read_u8<P>(path: P) -> Vec<u8>
-    where P: AsRef<Path>
-{
-    let mut res = Vec::new();
-    File::open(path.as_ref()).unwrap()
-        .read_to_end(&mut res).unwrap();
-
-    res
+    sgr_faint
+    sgr_conceal
+    sgr_strikethrough
 }
 
 fn read_string<P>(path: P) -> Result<String, ::std::io::Error>
@@ -78,7 +73,8 @@ struct RefConfig {
 }
 
 fn ref_test(dir: &Path) {
-    let recording = read_u8(dir.join("alacritty.recording"));
+    // Chunks of the recording, split according to its timing if it has been recorded
+    let recording = ref_test::load(dir).unwrap();
     let serialized_size = read_string(dir.join("size.json")).unwrap();
     let serialized_grid = read_string(dir.join("grid.json")).unwrap();
     let serialized_cfg = read_string(dir.join("config.json")).unwrap_or_default();
@@ -93,10 +89,16 @@ fn ref_test(dir: &Path) {
     let mut terminal = Term::new(&config, size);
     let mut parser = ansi::Processor::new();
 
-    for byte in recording {
-        parser.advance(&mut terminal, byte, &mut io::sink());
+    for (_, chunk) in recording.events {
+        parser.advance_bytes(&mut terminal, &chunk, &mut io::sink());
     }
 
+    // Snapshots of the terminal have to decode into the exact same state
+    let snapshot = terminal.snapshot();
+    let mut encoded = Vec::new();
+    snapshot.write(&mut encoded).unwrap();
+    assert_eq!(Snapshot::read(&encoded[..]).unwrap(), snapshot);
+
     // Truncate invisible lines from the grid
     let mut term_grid = terminal.grid().clone();
     term_grid.initialize_all(&Cell::default());